@@ -0,0 +1,101 @@
+//! Statement generation of the built-in handlers, from the account delivered by the validator
+//! to the SQL sent to the database. Run with:
+//!
+//! ```text
+//! cargo bench --features test-harness --bench handlers
+//! ```
+use criterion::black_box;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::Criterion;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::postgres_client::DbAccountInfo;
+use solana_geyser_plugin_postgres::test_harness::AccountFixture;
+use solana_geyser_plugin_postgres::test_harness::SelectedHandlers;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+static TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+static METADATA_PROGRAM_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+static UNKNOWN_PROGRAM_ID: Pubkey = pubkey!("11111111111111111111111111111111");
+
+fn config() -> GeyserPluginPostgresConfig {
+    serde_json::from_value(serde_json::json!({
+        "connection_str": "",
+        "accounts_selector": {
+            "owners": {
+                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA": [{ "handler_id": "token_account" }],
+                "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s": [{ "handler_id": "token_metadata_creators" }],
+                "11111111111111111111111111111111": [{ "handler_id": "unknown_account" }]
+            }
+        }
+    }))
+    .unwrap()
+}
+
+/// An initialized spl token account
+fn token_account() -> AccountFixture {
+    let mut data = vec![0; 165];
+    data[..32].copy_from_slice(Pubkey::new_unique().as_ref());
+    data[32..64].copy_from_slice(Pubkey::new_unique().as_ref());
+    data[64..72].copy_from_slice(&1_u64.to_le_bytes());
+    data[108] = 1;
+    AccountFixture::new(Pubkey::new_unique(), TOKEN_PROGRAM_ID, data)
+}
+
+/// A metadata account with `creators` creators
+fn metadata(creators: u32) -> AccountFixture {
+    let mut data = vec![0; 679];
+    data[0] = 4;
+    data[33..65].copy_from_slice(Pubkey::new_unique().as_ref());
+    let mut offset = 322;
+    data[offset..offset + 4].copy_from_slice(&creators.to_le_bytes());
+    offset += 4;
+    for _ in 0..creators {
+        data[offset..offset + 32].copy_from_slice(Pubkey::new_unique().as_ref());
+        data[offset + 32] = 1;
+        data[offset + 33] = (100 / creators) as u8;
+        offset += 34;
+    }
+    AccountFixture::new(Pubkey::new_unique(), METADATA_PROGRAM_ID, data)
+}
+
+fn bench_db_account_info(c: &mut Criterion) {
+    let fixture = token_account();
+    c.bench_function("db_account_info/token_account", |b| {
+        b.iter(|| {
+            let account = ReplicaAccountInfoV2 {
+                pubkey: fixture.pubkey.as_ref(),
+                lamports: fixture.lamports,
+                owner: fixture.owner.as_ref(),
+                executable: false,
+                rent_epoch: 0,
+                data: &fixture.data,
+                write_version: fixture.write_version,
+                txn_signature: None,
+            };
+            // the workers hand the data buffer back once the account is written
+            black_box(DbAccountInfo::new(black_box(&account), fixture.slot)).recycle()
+        })
+    });
+}
+
+fn bench_account_update(c: &mut Criterion) {
+    let handlers = SelectedHandlers::new(&config());
+    let mut group = c.benchmark_group("account_update");
+    for (name, fixture) in [
+        ("token_account", token_account()),
+        ("metadata_creators/1", metadata(1)),
+        ("metadata_creators/5", metadata(5)),
+        ("unknown_account", AccountFixture::new(Pubkey::new_unique(), UNKNOWN_PROGRAM_ID, vec![7; 256])),
+        ("unselected", AccountFixture::new(Pubkey::new_unique(), Pubkey::new_unique(), vec![7; 256])),
+    ] {
+        let account = fixture.to_db_account();
+        group.bench_function(name, |b| b.iter(|| handlers.account_update(black_box(&account), false)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_db_account_info, bench_account_update);
+criterion_main!(benches);
@@ -0,0 +1,5 @@
+fn main() {
+    // the service streaming the updates to the subscribers of the "grpc" server
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/geyser.proto").unwrap();
+}
@@ -0,0 +1,185 @@
+//! Derive macros generating `AccountHandler` implementations for Borsh account structs.
+//!
+//! ```ignore
+//! #[derive(BorshDeserialize, GeyserAccountHandler)]
+//! #[geyser(program_id = "mgr99QFMYByTqGPWmNqunV7vBLmWWXdSrHUfV8Jf3JM", account = "TokenManager", table = "token_manager")]
+//! pub struct TokenManager { ... }
+//! ```
+//!
+//! generates a `TokenManagerHandler` writing every field of matching accounts to
+//! `token_manager`, keyed by the account pubkey. The table is named with the `prefix` of the handler,
+//! `TokenManagerHandler { prefix: TablePrefix::new(&config) }` for the `table_prefix` of the config. The upsert resolves conflicts with the
+//! `ConflictStrategy` named by `conflict`, `slot_write_version` by default.
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::format_ident;
+use quote::quote;
+use sha2::Digest;
+use sha2::Sha256;
+use syn::parse_macro_input;
+use syn::Data;
+use syn::DeriveInput;
+use syn::Error;
+use syn::Fields;
+use syn::Lit;
+use syn::Meta;
+use syn::NestedMeta;
+
+struct HandlerAttributes {
+    program_id: Option<String>,
+    account: Option<String>,
+    table: Option<String>,
+    handler: Option<String>,
+    conflict: Option<String>,
+}
+
+/// The `ConflictStrategy` variant of each `conflict` attribute value
+const CONFLICT_STRATEGIES: [(&str, &str); 4] = [
+    ("latest_slot", "LatestSlot"),
+    ("slot_write_version", "SlotWriteVersion"),
+    ("always_overwrite", "AlwaysOverwrite"),
+    ("merge_non_null", "MergeNonNull"),
+];
+
+fn parse_attributes(input: &DeriveInput) -> Result<HandlerAttributes, Error> {
+    let mut attributes = HandlerAttributes {
+        program_id: None,
+        account: None,
+        table: None,
+        handler: None,
+        conflict: None,
+    };
+    for attr in input.attrs.iter().filter(|attr| attr.path.is_ident("geyser")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new_spanned(meta, "expected #[geyser(key = \"value\", ...)]")),
+        };
+        for nested in list.nested {
+            let name_value = match nested {
+                NestedMeta::Meta(Meta::NameValue(name_value)) => name_value,
+                nested => return Err(Error::new_spanned(nested, "expected key = \"value\"")),
+            };
+            let value = match &name_value.lit {
+                Lit::Str(value) => value.value(),
+                lit => return Err(Error::new_spanned(lit, "expected a string literal")),
+            };
+            let key = name_value.path.get_ident().map(|ident| ident.to_string()).unwrap_or_default();
+            match key.as_str() {
+                "program_id" => attributes.program_id = Some(value),
+                "account" => attributes.account = Some(value),
+                "table" => attributes.table = Some(value),
+                "handler" => attributes.handler = Some(value),
+                "conflict" => attributes.conflict = Some(value),
+                _ => {
+                    return Err(Error::new_spanned(
+                        name_value.path,
+                        "unknown geyser attribute, expected program_id, account, table, handler or conflict",
+                    ))
+                }
+            }
+        }
+    }
+    Ok(attributes)
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+/// Generate an `AccountHandler` named `<Struct>Handler` for an Anchor account struct
+#[proc_macro_derive(GeyserAccountHandler, attributes(geyser))]
+pub fn derive_geyser_account_handler(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let attributes = parse_attributes(&input)?;
+    let ident = &input.ident;
+    let program_id = attributes.program_id.ok_or_else(|| Error::new(Span::call_site(), "missing #[geyser(program_id = \"...\")]"))?;
+    let program_id = match bs58::decode(&program_id).into_vec() {
+        Ok(bytes) if bytes.len() == 32 => bytes,
+        _ => return Err(Error::new(Span::call_site(), format!("program_id \"{}\" is not a valid pubkey", program_id))),
+    };
+    let account = attributes.account.unwrap_or_else(|| ident.to_string());
+    let discriminator = Sha256::digest(format!("account:{}", account).as_bytes())[..8].to_vec();
+    let table = attributes.table.unwrap_or_else(|| snake_case(&ident.to_string()));
+    let handler = format_ident!("{}", attributes.handler.unwrap_or_else(|| format!("{}Handler", ident)));
+    let conflict = attributes.conflict.unwrap_or_else(|| "slot_write_version".to_string());
+    let conflict = match CONFLICT_STRATEGIES.iter().find(|(name, _)| *name == conflict) {
+        Some((_, variant)) => format_ident!("{}", variant),
+        None => {
+            let names = CONFLICT_STRATEGIES.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+            return Err(Error::new(Span::call_site(), format!("conflict \"{}\" must be one of {:?}", conflict, names)));
+        }
+    };
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect::<Vec<_>>(),
+            _ => return Err(Error::new_spanned(ident, "GeyserAccountHandler requires named fields")),
+        },
+        _ => return Err(Error::new_spanned(ident, "GeyserAccountHandler can only be derived for structs")),
+    };
+    let field_idents = fields.iter().map(|f| f.ident.clone().unwrap()).collect::<Vec<_>>();
+    let field_types = fields.iter().map(|f| f.ty.clone()).collect::<Vec<_>>();
+    let columns = field_idents.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+    let column_list = columns.join(", ");
+    let insert = format!("INSERT INTO {{}}{0} AS acc (id, {1}, slot, write_version) VALUES ({{}}, {{}}, {{}}, {{}}) {{}};", table, column_list);
+    let create_table = format!(
+        "CREATE TABLE IF NOT EXISTS {{}}{} (id VARCHAR(44) NOT NULL, {{}} slot BIGINT NOT NULL, write_version BIGINT NOT NULL DEFAULT 0, PRIMARY KEY(id));",
+        table
+    );
+    let add_write_version = format!("ALTER TABLE {{}}{} ADD COLUMN IF NOT EXISTS write_version BIGINT NOT NULL DEFAULT 0;", table);
+    let krate = quote!(::solana_geyser_plugin_postgres::postgres_client);
+
+    Ok(quote! {
+        #[derive(Clone, Default)]
+        pub struct #handler {
+            pub prefix: #krate::table_prefix::TablePrefix,
+        }
+
+        impl #krate::AccountHandler for #handler {
+            fn init(&self, config: &::solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig) -> String {
+                if !self.enabled(config) {
+                    return "".to_string();
+                }
+                let columns = [#(#krate::sql_value::column_definition::<#field_types>(#columns)),*];
+                format!(#create_table, self.prefix, columns.iter().map(|c| format!("{},", c)).collect::<Vec<String>>().join(" "))
+            }
+
+            fn schema_migrations(&self, _config: &::solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig) -> Vec<String> {
+                vec![format!(#add_write_version, self.prefix)]
+            }
+
+            fn account_match(&self, account: &#krate::DbAccountInfo) -> bool {
+                const PROGRAM_ID: [u8; 32] = [#(#program_id),*];
+                const DISCRIMINATOR: [u8; 8] = [#(#discriminator),*];
+                account.owner[..] == PROGRAM_ID[..] && account.data.get(..8) == Some(&DISCRIMINATOR[..])
+            }
+
+            fn account_update(&self, account: &#krate::DbAccountInfo) -> String {
+                if !self.account_match(account) {
+                    return "".to_string();
+                }
+                let decoded: #ident = match ::borsh::BorshDeserialize::deserialize(&mut &account.data[8..]) {
+                    Ok(decoded) => decoded,
+                    Err(_) => return "".to_string(),
+                };
+                let values = [#(#krate::sql_value::SqlValue::sql_literal(&decoded.#field_idents)),*];
+                let on_conflict = #krate::ConflictStrategy::#conflict.on_conflict("id", &[#(#columns,)* "write_version"]);
+                format!(#insert, self.prefix, #krate::sql_value::pubkey_literal(&account.pubkey), values.join(", "), account.slot, account.write_version, on_conflict)
+            }
+        }
+    })
+}
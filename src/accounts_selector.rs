@@ -1,8 +1,11 @@
+use crate::postgres_client::AccountHandlerId;
 use log::*;
 use serde::Deserialize;
 use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::str::FromStr;
 
 /// * The `accounts_selector` section allows the user to controls accounts selections.
 /// "accounts_selector" : {
@@ -23,6 +26,47 @@ pub struct AccountsSelectorConfig {
     pub owners: Option<HashMap<String, Vec<AccountHandlerConfig>>>,
 }
 
+impl AccountsSelectorConfig {
+    /// Check that every selector key is a valid pubkey and every handler id is either built in or external
+    pub fn validate(&self, external_handler_ids: &[String]) -> Result<(), String> {
+        for (section, entries) in [("accounts", &self.accounts), ("owners", &self.owners)] {
+            for (key, handlers) in entries.iter().flatten() {
+                if Pubkey::from_str(key).is_err() {
+                    return Err(format!("accounts_selector.{} key \"{}\" is not a valid pubkey", section, key));
+                }
+                for handler in handlers {
+                    match AccountHandlerId::from_str(&handler.handler_id) {
+                        Ok(handler_id) if !handler_id.is_compiled() => {
+                            return Err(format!(
+                                "accounts_selector.{}.{} handler_id \"{}\" requires the plugin to be built with the \"{}\" feature",
+                                section,
+                                key,
+                                handler.handler_id,
+                                handler_id.feature().unwrap_or_default()
+                            ));
+                        }
+                        Ok(_) => {}
+                        Err(_) if !external_handler_ids.contains(&handler.handler_id) => {
+                            return Err(format!("accounts_selector.{}.{} has unknown handler_id \"{}\"", section, key, handler.handler_id));
+                        }
+                        Err(_) => {}
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The ids of the handlers selected for the accounts or owners
+    pub fn handler_ids(&self) -> impl Iterator<Item = &str> {
+        [&self.accounts, &self.owners]
+            .into_iter()
+            .flatten()
+            .flat_map(|entries| entries.values().flatten())
+            .map(|handler| handler.handler_id.as_str())
+    }
+}
+
 #[derive(Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct AccountHandlerConfig {
     pub handler_id: String,
@@ -50,7 +94,18 @@ impl AccountsSelector {
     }
 
     pub fn is_account_selected(&self, account: &[u8], owner: &[u8]) -> bool {
-        self.accounts.contains(account) || self.owners.contains(owner)
+        self.select_account(account, owner).is_some()
+    }
+
+    /// The selector section and entry that selected the account, if any
+    pub fn select_account<'a>(&self, account: &'a [u8], owner: &'a [u8]) -> Option<(&'static str, &'a [u8])> {
+        if self.accounts.contains(account) {
+            Some(("accounts", account))
+        } else if self.owners.contains(owner) {
+            Some(("owners", owner))
+        } else {
+            None
+        }
     }
 
     pub fn is_enabled(&self) -> bool {
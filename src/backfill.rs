@@ -0,0 +1,263 @@
+//! Backfills the tables with the blocks of a past slot range.
+//!
+//! The `geyser-backfill` runner loads the plugin the way a validator would, then fetches every
+//! block of the configured slot range with `getBlock` and notifies the plugin of its transactions,
+//! its metadata and its rooted slot, in slot order. The transaction selector and the handlers are
+//! the same as on a validator, so an operator joining mid-history fills the same tables.
+//!
+//! The blocks are fetched from an RPC node. Nodes running with `--enable-rpc-bigtable-ledger-storage`
+//! serve the blocks older than their ledger from Bigtable, which is how the full history is reached;
+//! Bigtable isn't read directly. RPC nodes don't serve past account states, the selected accounts can
+//! only be loaded as they are now, as startup accounts before the blocks.
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+#[cfg(feature = "rpc-ingest")]
+pub use runner::Backfill;
+#[cfg(feature = "rpc-ingest")]
+pub use runner::BackfillCounts;
+
+/// * The `backfill` section configures the `geyser-backfill` runner, requires the "rpc-ingest" feature.
+/// "backfill" : { "rpc_url" : "https://api.mainnet-beta.solana.com", "start_slot" : 150000000, "end_slot" : 150100000 }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackfillConfig {
+    /// The JSON RPC endpoint the blocks are fetched from
+    pub rpc_url: String,
+    /// The first slot to backfill
+    pub start_slot: u64,
+    /// The last slot to backfill, the last finalized slot when not set
+    pub end_slot: Option<u64>,
+    /// The blocks fetched at the same time
+    pub concurrency: usize,
+    /// Load the current state of the selected accounts as startup accounts before the blocks
+    pub load_accounts: bool,
+}
+
+impl Default for BackfillConfig {
+    fn default() -> Self {
+        Self {
+            rpc_url: "http://127.0.0.1:8899".to_string(),
+            start_slot: 0,
+            end_slot: None,
+            concurrency: 4,
+            load_accounts: false,
+        }
+    }
+}
+
+impl BackfillConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if cfg!(not(feature = "rpc-ingest")) {
+            return Err("\"backfill\" requires the plugin to be built with the \"rpc-ingest\" feature".to_string());
+        }
+        if !self.rpc_url.starts_with("http://") && !self.rpc_url.starts_with("https://") {
+            return Err(format!("backfill.rpc_url \"{}\" must be an http or https url", self.rpc_url));
+        }
+        if self.end_slot.map_or(false, |end_slot| end_slot < self.start_slot) {
+            return Err("backfill.end_slot must not be lower than backfill.start_slot".to_string());
+        }
+        if self.concurrency == 0 {
+            return Err("backfill.concurrency must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rpc-ingest")]
+mod runner {
+    use super::BackfillConfig;
+    use crate::config::GeyserPluginPostgresConfig;
+    use crate::geyser_plugin_postgres::GeyserPluginPostgres;
+    use crate::rpc_ingest::load_accounts;
+    use crate::rpc_ingest::notify_transaction;
+    use crate::rpc_ingest::selected_pubkeys;
+    use crate::rpc_ingest::IngestedTransaction;
+    use log::*;
+    use solana_account_decoder::UiAccountEncoding;
+    use solana_client::rpc_client::RpcClient;
+    use solana_client::rpc_config::RpcAccountInfoConfig;
+    use solana_client::rpc_config::RpcBlockConfig;
+    use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
+    use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaBlockInfo;
+    use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaBlockInfoVersions;
+    use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+    use solana_sdk::commitment_config::CommitmentConfig;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_transaction_status::TransactionDetails;
+    use solana_transaction_status::UiConfirmedBlock;
+    use solana_transaction_status::UiTransactionEncoding;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::AtomicU64;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::thread;
+
+    /// The slots listed per getBlocks request, the RPC nodes accept up to 500000
+    const BLOCKS_CHUNK: u64 = 10_000;
+    /// How many blocks go by between two progress logs
+    const PROGRESS_INTERVAL: u64 = 1000;
+
+    /// What the runner notified the plugin of
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct BackfillCounts {
+        pub startup_accounts: u64,
+        pub blocks: u64,
+        pub transactions: u64,
+        /// The last slot whose block was notified, the backfill resumes after it
+        pub last_slot: Option<u64>,
+    }
+
+    /// Fetches the blocks of a slot range and notifies the plugin
+    pub struct Backfill {
+        config: BackfillConfig,
+        rpc_client: RpcClient,
+        owners: Vec<Pubkey>,
+        accounts: Vec<Pubkey>,
+        exit: Arc<AtomicBool>,
+    }
+
+    impl Backfill {
+        pub fn new(config: &GeyserPluginPostgresConfig) -> Result<Self, String> {
+            let backfill = config.backfill.clone().ok_or_else(|| "the config has no \"backfill\" section".to_string())?;
+            backfill.validate()?;
+            let (owners, accounts) = selected_pubkeys(config)?;
+            Ok(Self {
+                rpc_client: RpcClient::new_with_commitment(backfill.rpc_url.clone(), CommitmentConfig::finalized()),
+                config: backfill,
+                owners,
+                accounts,
+                exit: Arc::new(AtomicBool::new(false)),
+            })
+        }
+
+        /// Set it to stop the runner, e.g. from a signal handler
+        pub fn exit(&self) -> Arc<AtomicBool> {
+            self.exit.clone()
+        }
+
+        fn fetch_block(&self, slot: u64) -> Result<UiConfirmedBlock, String> {
+            let config = RpcBlockConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                transaction_details: Some(TransactionDetails::Full),
+                rewards: Some(true),
+                commitment: Some(CommitmentConfig::finalized()),
+                max_supported_transaction_version: Some(0),
+            };
+            self.rpc_client.get_block_with_config(slot, config).map_err(|e| format!("getBlock {} failed: {}", slot, e))
+        }
+
+        fn notify_block(&self, plugin: &mut GeyserPluginPostgres, slot: u64, block: UiConfirmedBlock, counts: &mut BackfillCounts) -> Result<(), String> {
+            for (index, transaction) in block.transactions.into_iter().flatten().enumerate() {
+                match IngestedTransaction::from_encoded(transaction, slot, index) {
+                    Ok(transaction) => {
+                        if notify_transaction(plugin, transaction)? {
+                            counts.transactions += 1;
+                        }
+                    }
+                    Err(err) => warn!("[backfill] skipping transaction {} of slot {}: {}", index, slot, err),
+                }
+            }
+            let rewards = block.rewards.unwrap_or_default();
+            plugin
+                .notify_block_metadata(ReplicaBlockInfoVersions::V0_0_1(&ReplicaBlockInfo {
+                    slot,
+                    blockhash: &block.blockhash,
+                    rewards: &rewards,
+                    block_time: block.block_time,
+                    block_height: block.block_height,
+                }))
+                .map_err(|e| e.to_string())?;
+            plugin.update_slot_status(slot, Some(block.parent_slot), SlotStatus::Rooted).map_err(|e| e.to_string())?;
+            counts.blocks += 1;
+            counts.last_slot = Some(slot);
+            if counts.blocks % PROGRESS_INTERVAL == 0 {
+                info!("[backfill] notified {} blocks, up to slot {}", counts.blocks, slot);
+            }
+            Ok(())
+        }
+
+        /// Notify the blocks of the slots in order, fetching `concurrency` of them at a time
+        fn backfill(&self, plugin: &mut GeyserPluginPostgres, slots: &[u64], counts: &mut BackfillCounts) -> Result<(), String> {
+            for chunk in slots.chunks(self.config.concurrency) {
+                if self.exit.load(Ordering::Relaxed) {
+                    break;
+                }
+                let blocks = thread::scope(|scope| {
+                    let fetches: Vec<_> = chunk.iter().map(|slot| scope.spawn(move || self.fetch_block(*slot))).collect();
+                    fetches.into_iter().map(|fetch| fetch.join().unwrap()).collect::<Result<Vec<_>, String>>()
+                })?;
+                for (slot, block) in chunk.iter().zip(blocks) {
+                    self.notify_block(plugin, *slot, block, counts)?;
+                }
+            }
+            Ok(())
+        }
+
+        fn ingest(&self, plugin: &mut GeyserPluginPostgres, counts: &mut BackfillCounts) -> Result<(), String> {
+            if self.config.load_accounts {
+                let account_config = RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    commitment: Some(CommitmentConfig::finalized()),
+                    ..RpcAccountInfoConfig::default()
+                };
+                counts.startup_accounts = load_accounts(plugin, &self.rpc_client, account_config, &self.owners, &self.accounts, &AtomicU64::new(0))?;
+            }
+            plugin.notify_end_of_startup().map_err(|e| e.to_string())?;
+            let end_slot = match self.config.end_slot {
+                Some(end_slot) => end_slot,
+                None => self.rpc_client.get_slot().map_err(|e| format!("getSlot failed: {}", e))?,
+            };
+            info!("[backfill] backfilling slots {} to {}", self.config.start_slot, end_slot);
+            let mut start_slot = self.config.start_slot;
+            while start_slot <= end_slot && !self.exit.load(Ordering::Relaxed) {
+                let chunk_end = end_slot.min(start_slot.saturating_add(BLOCKS_CHUNK - 1));
+                // the skipped slots have no block and aren't listed
+                let slots = self
+                    .rpc_client
+                    .get_blocks(start_slot, Some(chunk_end))
+                    .map_err(|e| format!("getBlocks {}..={} failed: {}", start_slot, chunk_end, e))?;
+                self.backfill(plugin, &slots, counts)?;
+                start_slot = chunk_end + 1;
+            }
+            Ok(())
+        }
+
+        /// Notify the loaded plugin of the blocks of the slot range, or until `exit` is set, then wait for the
+        /// workers to receive the queued requests
+        pub fn run(&self, plugin: &mut GeyserPluginPostgres) -> Result<BackfillCounts, String> {
+            let mut counts = BackfillCounts::default();
+            let result = self.ingest(plugin, &mut counts);
+            plugin.wait_for_empty_queue();
+            result.map(|_| counts).map_err(|err| match counts.last_slot {
+                Some(last_slot) => format!("{} (backfilled up to slot {})", err, last_slot),
+                None => err,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backfill_config() {
+        let config = BackfillConfig {
+            start_slot: 100,
+            end_slot: Some(200),
+            ..BackfillConfig::default()
+        };
+        assert_eq!(config.validate().is_ok(), cfg!(feature = "rpc-ingest"));
+        if cfg!(feature = "rpc-ingest") {
+            assert!(BackfillConfig { end_slot: Some(99), ..config.clone() }.validate().is_err());
+            assert!(BackfillConfig { concurrency: 0, ..config.clone() }.validate().is_err());
+            assert!(BackfillConfig {
+                rpc_url: "ws://127.0.0.1:8900".to_string(),
+                ..config
+            }
+            .validate()
+            .is_err());
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Backfill the tables with the blocks of a past slot range.
+//!
+//! Loads the plugin with the given config, whose `backfill` section names the RPC node and the
+//! slot range, optionally notifies it of the current state of the selected accounts as startup
+//! accounts, then notifies it of the transactions and the metadata of every block of the range
+//! until done or interrupted. The queued requests are written before exiting. The last
+//! backfilled slot is printed, to resume from the next one.
+//!
+//! Usage: geyser-backfill <config-file>
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
+use solana_geyser_plugin_postgres::backfill::Backfill;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::geyser_plugin_postgres::GeyserPluginPostgres;
+use std::env;
+use std::process::exit;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+fn main() {
+    let config_file = match env::args().nth(1) {
+        Some(config_file) => config_file,
+        None => {
+            eprintln!("Usage: geyser-backfill <config-file>");
+            exit(2);
+        }
+    };
+    let config = GeyserPluginPostgresConfig::read_from(&config_file).unwrap_or_else(|err| {
+        eprintln!("[config] {}", err);
+        exit(1);
+    });
+    let backfill = Backfill::new(&config).unwrap_or_else(|err| {
+        eprintln!("[config] {}", err);
+        exit(1);
+    });
+
+    let mut plugin = GeyserPluginPostgres::new();
+    if let Err(err) = plugin.on_load(&config_file) {
+        eprintln!("[load] failed: {}", err);
+        exit(1);
+    }
+    let exit_flag = backfill.exit();
+    if let Err(err) = ctrlc::set_handler(move || exit_flag.store(true, Ordering::Relaxed)) {
+        eprintln!("[backfill] failed to handle interrupts: {}", err);
+        exit(1);
+    }
+
+    let start = Instant::now();
+    let result = backfill.run(&mut plugin);
+    plugin.on_unload();
+    match result {
+        Ok(counts) => println!(
+            "[backfill] startup_accounts={} blocks={} transactions={} last_slot={} elapsed={:.2}s",
+            counts.startup_accounts,
+            counts.blocks,
+            counts.transactions,
+            counts.last_slot.map_or_else(|| "none".to_string(), |slot| slot.to_string()),
+            start.elapsed().as_secs_f64()
+        ),
+        Err(err) => {
+            eprintln!("[backfill] failed: {}", err);
+            exit(1);
+        }
+    }
+}
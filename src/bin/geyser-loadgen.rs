@@ -0,0 +1,199 @@
+//! Pump synthetic account updates through `ParallelClient` at a fixed rate.
+//!
+//! Builds the worker pool from the given config, as the plugin does on load, and sends it
+//! updates of a pool of generated accounts, cycling through them with increasing slots.
+//! The enqueued rate and queue depth are printed every second, and the time taken to drain
+//! the queue at the end, so throughput can be compared between releases.
+//!
+//! Usage: geyser-loadgen <config-file> [--rate <per-second>] [--duration-secs <secs>] [--accounts <count>] [--kind token_account|metadata|unknown] [--startup]
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::metrics::registry;
+use solana_geyser_plugin_postgres::metrics::QUEUE_DEPTH;
+use solana_geyser_plugin_postgres::metrics::SHED_TOTAL;
+use solana_geyser_plugin_postgres::postgres_client::CustomHandlers;
+use solana_geyser_plugin_postgres::postgres_client::PostgresClientBuilder;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use std::env;
+use std::process::exit;
+use std::str::FromStr;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::Instant;
+
+const USAGE: &str = "Usage: geyser-loadgen <config-file> [--rate <per-second>] [--duration-secs <secs>] [--accounts <count>] [--kind token_account|metadata|unknown] [--startup]";
+
+static TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+static METADATA_PROGRAM_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+struct Args {
+    config_file: String,
+    /// Updates per second, 0 sends as fast as the queue accepts them
+    rate: u64,
+    duration: Duration,
+    accounts: usize,
+    kind: String,
+    startup: bool,
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: Option<&String>) -> T {
+    match value.map(|v| v.parse::<T>()) {
+        Some(Ok(value)) => value,
+        _ => {
+            eprintln!("{} expects a number\n{}", flag, USAGE);
+            exit(2);
+        }
+    }
+}
+
+fn parse_args() -> Args {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut parsed = Args {
+        config_file: "".to_string(),
+        rate: 1000,
+        duration: Duration::from_secs(60),
+        accounts: 10_000,
+        kind: "token_account".to_string(),
+        startup: false,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rate" => parsed.rate = parse_value(arg, args.next()),
+            "--duration-secs" => parsed.duration = Duration::from_secs(parse_value(arg, args.next())),
+            "--accounts" => parsed.accounts = parse_value::<usize>(arg, args.next()).max(1),
+            "--kind" => parsed.kind = args.next().cloned().unwrap_or_default(),
+            "--startup" => parsed.startup = true,
+            config_file if !config_file.starts_with("--") && parsed.config_file.is_empty() => parsed.config_file = config_file.to_string(),
+            _ => {
+                eprintln!("{}", USAGE);
+                exit(2);
+            }
+        }
+    }
+    if parsed.config_file.is_empty() || !["token_account", "metadata", "unknown"].contains(&parsed.kind.as_str()) {
+        eprintln!("{}", USAGE);
+        exit(2);
+    }
+    parsed
+}
+
+/// The owner and data of a generated account of the given kind
+fn synthetic_account(kind: &str) -> (Pubkey, Vec<u8>) {
+    match kind {
+        "token_account" => {
+            let mut data = vec![0; 165];
+            data[..32].copy_from_slice(Pubkey::new_unique().as_ref());
+            data[32..64].copy_from_slice(Pubkey::new_unique().as_ref());
+            data[64..72].copy_from_slice(&1_u64.to_le_bytes());
+            data[108] = 1;
+            (TOKEN_PROGRAM_ID, data)
+        }
+        "metadata" => {
+            let mut data = vec![0; 679];
+            data[0] = 4;
+            data[33..65].copy_from_slice(Pubkey::new_unique().as_ref());
+            data[322..326].copy_from_slice(&1_u32.to_le_bytes());
+            data[326..358].copy_from_slice(Pubkey::new_unique().as_ref());
+            data[358] = 1;
+            data[359] = 100;
+            (METADATA_PROGRAM_ID, data)
+        }
+        _ => (Pubkey::new_unique(), (0..200).map(|_| rand::random::<u8>()).collect()),
+    }
+}
+
+fn main() {
+    let args = parse_args();
+    let config = match GeyserPluginPostgresConfig::read_from(&args.config_file) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("[config] failed to read {}: {}", args.config_file, err);
+            exit(1);
+        }
+    };
+    let (mut client, _) = match PostgresClientBuilder::build_pararallel_postgres_client(&config, &CustomHandlers::default()) {
+        Ok(client) => client,
+        Err(err) => {
+            eprintln!("[load] failed: {}", err);
+            exit(1);
+        }
+    };
+
+    let accounts = (0..args.accounts)
+        .map(|_| (Pubkey::new_unique(), synthetic_account(&args.kind)))
+        .collect::<Vec<(Pubkey, (Pubkey, Vec<u8>))>>();
+    println!(
+        "[loadgen] kind={} accounts={} rate={}/s duration={}s startup={}",
+        args.kind,
+        args.accounts,
+        args.rate,
+        args.duration.as_secs(),
+        args.startup
+    );
+
+    let start = Instant::now();
+    let (mut sent, mut last_report, mut sent_at_last_report) = (0_u64, start, 0_u64);
+    while start.elapsed() < args.duration {
+        if args.rate > 0 {
+            // keep to the schedule rather than sleeping a fixed interval, so slow enqueues don't lower the rate
+            let scheduled = start + Duration::from_secs_f64(sent as f64 / args.rate as f64);
+            if let Some(wait) = scheduled.checked_duration_since(Instant::now()) {
+                sleep(wait);
+            }
+        }
+        let (pubkey, (owner, data)) = &accounts[sent as usize % accounts.len()];
+        // every pass over the pool is a new slot, so each update supersedes the previous one
+        let slot = 1 + sent / accounts.len() as u64;
+        let account = ReplicaAccountInfoV2 {
+            pubkey: pubkey.as_ref(),
+            lamports: 1,
+            owner: owner.as_ref(),
+            executable: false,
+            rent_epoch: 0,
+            data,
+            write_version: sent,
+            txn_signature: None,
+        };
+        if let Err(err) = client.update_account(&account, slot, args.startup) {
+            eprintln!("[loadgen] update failed: {}", err);
+            exit(1);
+        }
+        sent += 1;
+        if last_report.elapsed() >= Duration::from_secs(1) {
+            println!(
+                "[loadgen] elapsed={}s enqueued={}/s queue_depth={}",
+                start.elapsed().as_secs(),
+                sent - sent_at_last_report,
+                registry().gauge(QUEUE_DEPTH, &[]).unwrap_or_default()
+            );
+            (last_report, sent_at_last_report) = (Instant::now(), sent);
+        }
+    }
+    let enqueue_elapsed = start.elapsed();
+
+    let drain_start = Instant::now();
+    if args.startup {
+        if let Err(err) = client.notify_end_of_startup() {
+            eprintln!("[loadgen] end of startup failed: {}", err);
+            exit(1);
+        }
+    }
+    // the workers finish the requests they received and flush before exiting
+    client.wait_for_empty_queue();
+    if let Err(err) = client.join() {
+        eprintln!("[loadgen] worker failed: {:?}", err);
+    }
+    let drain_elapsed = drain_start.elapsed();
+
+    let total_elapsed = start.elapsed();
+    println!(
+        "[loadgen] sent={} shed={} enqueue_rate={:.0}/s drain={:.2}s throughput={:.0}/s",
+        sent,
+        registry().counter(SHED_TOTAL, &[("request", "update_account")]),
+        sent as f64 / enqueue_elapsed.as_secs_f64().max(f64::EPSILON),
+        drain_elapsed.as_secs_f64(),
+        sent as f64 / total_elapsed.as_secs_f64().max(f64::EPSILON)
+    );
+}
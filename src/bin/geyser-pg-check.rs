@@ -0,0 +1,74 @@
+//! Validate a plugin config outside of the validator.
+//!
+//! Loads the config, validates selectors and handler ids, connects to PostgreSQL
+//! with the configured TLS settings and prints the DDL the plugin would apply.
+//!
+//! Usage: geyser-pg-check <config-file> [--no-connect]
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::postgres_client::CustomHandlers;
+use solana_geyser_plugin_postgres::postgres_client::PostgresClientBuilder;
+use solana_geyser_plugin_postgres::postgres_client::SimplePostgresClient;
+use solana_geyser_plugin_postgres::sinks::DbBackend;
+use std::env;
+use std::process::exit;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let config_file = match args.iter().find(|a| !a.starts_with("--")) {
+        Some(config_file) => config_file,
+        None => {
+            eprintln!("Usage: geyser-pg-check <config-file> [--no-connect]");
+            exit(2);
+        }
+    };
+    let connect = !args.iter().any(|a| a == "--no-connect");
+
+    let config = match GeyserPluginPostgresConfig::read_from(config_file) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("[config] failed to read {}: {}", config_file, err);
+            exit(1);
+        }
+    };
+    if let Err(err) = config.validate() {
+        eprintln!("[config] invalid: {}", err);
+        exit(1);
+    }
+    println!("[config] ok profile={:?} threads={} batch_size={}", config.profile, config.threads, config.batch_size);
+
+    if !config.writes_postgres() {
+        match config.db_backend {
+            DbBackend::Clickhouse => println!("[clickhouse] db_backend is clickhouse, the events are only written to {}", config.clickhouse.url),
+            DbBackend::Postgres => println!("[kafka] replace_postgres is set, the events are only published to Kafka"),
+        }
+        return;
+    }
+    if connect {
+        let mut client = match SimplePostgresClient::connect_to_db(&config) {
+            Ok(client) => client,
+            Err(err) => {
+                eprintln!("[connect] failed: {}", err);
+                exit(1);
+            }
+        };
+        match client.query_one("SELECT version()", &[]) {
+            Ok(row) => {
+                let version: String = row.get(0);
+                println!("[connect] ok {}", version);
+            }
+            Err(err) => {
+                eprintln!("[connect] query failed: {}", err);
+                exit(1);
+            }
+        }
+    }
+
+    println!("[ddl]");
+    match PostgresClientBuilder::init_query(&config, &CustomHandlers::default()) {
+        Ok(init_query) => println!("{}", init_query),
+        Err(err) => {
+            eprintln!("[ddl] failed: {}", err);
+            exit(1);
+        }
+    }
+}
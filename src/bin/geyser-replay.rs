@@ -0,0 +1,166 @@
+//! Replay recorded fixtures through the plugin without a validator.
+//!
+//! Loads the plugin with the given config and feeds it the records of every fixture file,
+//! ordered by slot, as the validator would: startup accounts first, then the end of startup
+//! notification, then live accounts and transactions. Useful for load testing and for
+//! re-indexing after handler changes.
+//!
+//! Usage: geyser-replay <config-file> <fixture-file-or-directory>...
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoV2;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoVersions;
+use solana_geyser_plugin_postgres::fixtures::read_fixtures;
+use solana_geyser_plugin_postgres::fixtures::FixtureFormat;
+use solana_geyser_plugin_postgres::fixtures::RecordedAccount;
+use solana_geyser_plugin_postgres::fixtures::RecordedTransaction;
+use solana_geyser_plugin_postgres::fixtures::ACCOUNTS_FILE;
+use solana_geyser_plugin_postgres::fixtures::TRANSACTIONS_FILE;
+use solana_geyser_plugin_postgres::geyser_plugin_postgres::GeyserPluginPostgres;
+use std::env;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::exit;
+use std::time::Instant;
+
+enum Record {
+    Account(RecordedAccount),
+    Transaction(RecordedTransaction),
+}
+
+impl Record {
+    fn slot(&self) -> u64 {
+        match self {
+            Record::Account(account) => account.slot,
+            Record::Transaction(transaction) => transaction.slot,
+        }
+    }
+
+    fn is_startup(&self) -> bool {
+        matches!(self, Record::Account(account) if account.is_startup)
+    }
+}
+
+/// The fixture files given directly or found in the given directories
+fn fixture_files(paths: &[&String]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        let path = Path::new(path);
+        if path.is_dir() {
+            let mut entries = match path.read_dir() {
+                Ok(entries) => entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| FixtureFormat::from_path(p).is_some())
+                    .collect::<Vec<PathBuf>>(),
+                Err(err) => {
+                    eprintln!("[fixtures] failed to read {}: {}", path.display(), err);
+                    exit(1);
+                }
+            };
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
+    files
+}
+
+fn read_records(file: &Path) -> Result<Vec<Record>, String> {
+    let name = file.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    if name.starts_with(TRANSACTIONS_FILE) {
+        Ok(read_fixtures::<RecordedTransaction>(file)?.into_iter().map(Record::Transaction).collect())
+    } else if name.starts_with(ACCOUNTS_FILE) {
+        Ok(read_fixtures::<RecordedAccount>(file)?.into_iter().map(Record::Account).collect())
+    } else {
+        Err(format!("{} is neither an {} nor a {} fixture", file.display(), ACCOUNTS_FILE, TRANSACTIONS_FILE))
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (config_file, paths) = match args.iter().collect::<Vec<&String>>().split_first() {
+        Some((config_file, paths)) if !paths.is_empty() => (config_file.to_string(), paths.to_vec()),
+        _ => {
+            eprintln!("Usage: geyser-replay <config-file> <fixture-file-or-directory>...");
+            exit(2);
+        }
+    };
+
+    let mut records = Vec::new();
+    for file in fixture_files(&paths) {
+        match read_records(&file) {
+            Ok(file_records) => {
+                println!("[fixtures] {} records={}", file.display(), file_records.len());
+                records.extend(file_records);
+            }
+            Err(err) => {
+                eprintln!("[fixtures] {}", err);
+                exit(1);
+            }
+        }
+    }
+    // startup accounts come first, then everything else by slot with accounts before the transactions of their slot
+    records.sort_by_key(|record| (!record.is_startup(), record.slot(), matches!(record, Record::Transaction(_))));
+
+    let mut plugin = GeyserPluginPostgres::new();
+    if let Err(err) = plugin.on_load(&config_file) {
+        eprintln!("[load] failed: {}", err);
+        exit(1);
+    }
+
+    let start = Instant::now();
+    let (mut accounts, mut transactions, mut startup_done) = (0, 0, false);
+    for record in records {
+        if !startup_done && !record.is_startup() {
+            plugin.notify_end_of_startup().unwrap_or_else(|err| panic!("[replay] end of startup failed: {}", err));
+            startup_done = true;
+        }
+        let result = match record {
+            Record::Account(account) => {
+                accounts += 1;
+                plugin.update_account(ReplicaAccountInfoVersions::V0_0_2(&account.as_replica()), account.slot, account.is_startup)
+            }
+            Record::Transaction(transaction) => {
+                transactions += 1;
+                let (signature, is_vote, index, slot) = (transaction.signature, transaction.is_vote, transaction.index, transaction.slot);
+                match transaction.into_replica_parts() {
+                    Ok((sanitized, meta)) => plugin.notify_transaction(
+                        ReplicaTransactionInfoVersions::V0_0_2(&ReplicaTransactionInfoV2 {
+                            signature: &signature,
+                            is_vote,
+                            transaction: &sanitized,
+                            transaction_status_meta: &meta,
+                            index,
+                        }),
+                        slot,
+                    ),
+                    Err(err) => {
+                        eprintln!("[replay] skipping transaction {}: {}", signature, err);
+                        continue;
+                    }
+                }
+            }
+        };
+        if let Err(err) = result {
+            eprintln!("[replay] failed: {}", err);
+            exit(1);
+        }
+    }
+    if !startup_done {
+        plugin.notify_end_of_startup().unwrap_or_else(|err| panic!("[replay] end of startup failed: {}", err));
+    }
+    // the workers finish the requests they received and flush before exiting
+    plugin.wait_for_empty_queue();
+    plugin.on_unload();
+
+    let elapsed = start.elapsed();
+    println!(
+        "[replay] accounts={} transactions={} elapsed={:.2}s rate={:.0}/s",
+        accounts,
+        transactions,
+        elapsed.as_secs_f64(),
+        (accounts + transactions) as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+    );
+}
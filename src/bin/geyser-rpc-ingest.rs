@@ -0,0 +1,64 @@
+//! Run the plugin against an RPC node instead of inside a validator.
+//!
+//! Loads the plugin with the given config, whose `rpc_ingest` section names the RPC node,
+//! notifies it of the selected accounts as startup accounts, then streams the account, slot
+//! and transaction notifications of the node's websocket subscriptions until interrupted.
+//! The queued requests are written before exiting.
+//!
+//! Usage: geyser-rpc-ingest <config-file>
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::geyser_plugin_postgres::GeyserPluginPostgres;
+use solana_geyser_plugin_postgres::rpc_ingest::RpcIngest;
+use std::env;
+use std::process::exit;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+fn main() {
+    let config_file = match env::args().nth(1) {
+        Some(config_file) => config_file,
+        None => {
+            eprintln!("Usage: geyser-rpc-ingest <config-file>");
+            exit(2);
+        }
+    };
+    let config = GeyserPluginPostgresConfig::read_from(&config_file).unwrap_or_else(|err| {
+        eprintln!("[config] {}", err);
+        exit(1);
+    });
+    let rpc_ingest = RpcIngest::new(&config).unwrap_or_else(|err| {
+        eprintln!("[config] {}", err);
+        exit(1);
+    });
+
+    let mut plugin = GeyserPluginPostgres::new();
+    if let Err(err) = plugin.on_load(&config_file) {
+        eprintln!("[load] failed: {}", err);
+        exit(1);
+    }
+    let exit_flag = rpc_ingest.exit();
+    if let Err(err) = ctrlc::set_handler(move || exit_flag.store(true, Ordering::Relaxed)) {
+        eprintln!("[rpc_ingest] failed to handle interrupts: {}", err);
+        exit(1);
+    }
+
+    let start = Instant::now();
+    let result = rpc_ingest.run(&mut plugin);
+    plugin.on_unload();
+    match result {
+        Ok(counts) => println!(
+            "[rpc_ingest] startup_accounts={} accounts={} slots={} transactions={} blocks={} elapsed={:.2}s",
+            counts.startup_accounts,
+            counts.accounts,
+            counts.slots,
+            counts.transactions,
+            counts.blocks,
+            start.elapsed().as_secs_f64()
+        ),
+        Err(err) => {
+            eprintln!("[rpc_ingest] failed: {}", err);
+            exit(1);
+        }
+    }
+}
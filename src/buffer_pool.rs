@@ -0,0 +1,58 @@
+//! Account data buffers recycled across account updates.
+//!
+//! Every update copies the account data out of the validator's memory before it is queued.
+//! Once a worker has written an account its buffer goes back to the pool, and the next
+//! update copies into it instead of allocating, so the steady state of a busy validator
+//! allocates no data buffers at all. The pool is a bounded channel, shared by the callback
+//! threads taking buffers and the workers returning them without a lock.
+use crossbeam_channel::bounded;
+use crossbeam_channel::Receiver;
+use crossbeam_channel::Sender;
+use lazy_static::lazy_static;
+
+/// Buffers kept in the pool, the ones returned to a full pool are freed
+const POOL_SIZE: usize = 4096;
+/// Larger buffers are freed rather than kept, so a few big program accounts don't pin memory
+const MAX_POOLED_CAPACITY: usize = 4096;
+
+lazy_static! {
+    static ref POOL: (Sender<Vec<u8>>, Receiver<Vec<u8>>) = bounded(POOL_SIZE);
+}
+
+/// An empty buffer able to hold `len` bytes, from the pool when one is available
+pub fn take(len: usize) -> Vec<u8> {
+    match POOL.1.try_recv() {
+        Ok(mut buffer) => {
+            buffer.reserve(len);
+            buffer
+        }
+        Err(_) => Vec::with_capacity(len),
+    }
+}
+
+/// Return a buffer to the pool once its content was written
+pub fn recycle(mut buffer: Vec<u8>) {
+    if buffer.capacity() == 0 || buffer.capacity() > MAX_POOLED_CAPACITY {
+        return;
+    }
+    buffer.clear();
+    let _ = POOL.0.try_send(buffer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_pool() {
+        let mut buffer = take(165);
+        assert!(buffer.is_empty() && buffer.capacity() >= 165);
+        buffer.extend_from_slice(&[7; 165]);
+        recycle(buffer);
+        // the tests share the pool, any buffer taken is empty and large enough
+        let buffer = take(679);
+        assert!(buffer.is_empty() && buffer.capacity() >= 679);
+        recycle(Vec::with_capacity(MAX_POOLED_CAPACITY + 1));
+        recycle(buffer);
+    }
+}
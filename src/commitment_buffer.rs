@@ -0,0 +1,222 @@
+use crate::memory_stats::add_queued_bytes;
+use crate::memory_stats::sub_queued_bytes;
+use crate::memory_stats::Queue;
+use crate::metrics::registry;
+use crate::metrics::COMMITMENT_ABANDONED_TOTAL;
+use crate::metrics::COMMITMENT_BUFFERED_ACCOUNTS;
+use crate::postgres_client::DbAccountInfo;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::mem::size_of;
+
+/// The status a slot must reach before the live account updates written at it are written to the database
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommitmentLevel {
+    /// Write the updates as they are received
+    #[default]
+    Processed,
+    /// Write the updates of a slot once it is confirmed or rooted
+    Confirmed,
+    /// Write the updates of a slot once it is rooted
+    Finalized,
+}
+
+impl CommitmentLevel {
+    /// Whether a slot with `status` reached the level
+    pub fn is_reached(&self, status: SlotStatus) -> bool {
+        matches!(
+            (self, status),
+            (Self::Processed, _) | (Self::Confirmed, SlotStatus::Confirmed | SlotStatus::Rooted) | (Self::Finalized, SlotStatus::Rooted)
+        )
+    }
+}
+
+/// The live account updates of the slots that haven't reached the commitment level yet. A slot reaching it
+/// releases its updates and those of its buffered ancestors. Once a slot is rooted, the updates still buffered
+/// below it, or on a fork of a slot below it, were written on abandoned forks and are dropped.
+#[derive(Default)]
+pub struct CommitmentBuffer {
+    level: CommitmentLevel,
+    /// The buffered updates of every slot, in the order they were received, with whether they only close the account
+    slots: BTreeMap<u64, Vec<(DbAccountInfo, bool)>>,
+    /// The parents of the slots above the last rooted slot, from their status updates
+    parents: HashMap<u64, u64>,
+    /// The slots above the last rooted slot that reached the level, their updates are no longer buffered
+    committed: HashSet<u64>,
+    last_rooted: Option<u64>,
+    buffered: usize,
+}
+
+fn buffered_bytes((account, _): &(DbAccountInfo, bool)) -> usize {
+    size_of::<DbAccountInfo>() + account.heap_bytes()
+}
+
+impl CommitmentBuffer {
+    pub fn new(level: CommitmentLevel) -> Self {
+        Self { level, ..Self::default() }
+    }
+
+    /// Buffer a live update, or hand it back when its slot already reached the level
+    pub fn push(&mut self, account: DbAccountInfo, close_only: bool) -> Option<(DbAccountInfo, bool)> {
+        let slot = account.slot as u64;
+        let update = (account, close_only);
+        // nothing is replayed below the root any longer
+        if self.committed.contains(&slot) || matches!(self.last_rooted, Some(rooted) if slot <= rooted) {
+            return Some(update);
+        }
+        add_queued_bytes(Queue::CommitmentBuffer, buffered_bytes(&update));
+        self.slots.entry(slot).or_default().push(update);
+        self.buffered += 1;
+        registry().set_gauge(COMMITMENT_BUFFERED_ACCOUNTS, &[], self.buffered as i64);
+        None
+    }
+
+    /// The updates released by the status of `slot`, ancestors first
+    pub fn update_slot_status(&mut self, slot: u64, parent: Option<u64>, status: SlotStatus) -> Vec<(DbAccountInfo, bool)> {
+        if let Some(parent) = parent {
+            self.parents.insert(slot, parent);
+        }
+        if !self.level.is_reached(status) {
+            return Vec::new();
+        }
+        let mut released = Vec::new();
+        let mut current = Some(slot);
+        while let Some(ancestor) = current {
+            // the ancestors of a committed slot were committed with it
+            if !self.committed.insert(ancestor) && ancestor != slot {
+                break;
+            }
+            if let Some(accounts) = self.slots.remove(&ancestor) {
+                released.push(accounts);
+            }
+            current = self.parents.get(&ancestor).copied();
+        }
+        let released: Vec<(DbAccountInfo, bool)> = released.into_iter().rev().flatten().collect();
+        self.remove(&released);
+        if status == SlotStatus::Rooted {
+            let mut kept = self.slots.split_off(&slot);
+            let forks: Vec<u64> = kept.keys().copied().filter(|s| self.is_abandoned(*s, slot)).collect();
+            let mut abandoned: Vec<(DbAccountInfo, bool)> = forks.iter().filter_map(|s| kept.remove(s)).flatten().collect();
+            abandoned.extend(std::mem::replace(&mut self.slots, kept).into_values().flatten());
+            self.remove(&abandoned);
+            if !abandoned.is_empty() {
+                registry().inc_counter(COMMITMENT_ABANDONED_TOTAL, &[], abandoned.len() as u64);
+            }
+            abandoned.into_iter().for_each(|(account, _)| account.recycle());
+            self.parents.retain(|s, _| *s > slot);
+            self.committed.retain(|s| *s > slot);
+            self.last_rooted = Some(slot);
+        }
+        released
+    }
+
+    /// Whether `slot`, above the rooted slot, descends from a slot below it rather than from the rooted slot
+    fn is_abandoned(&self, slot: u64, rooted: u64) -> bool {
+        let mut current = slot;
+        while current > rooted {
+            current = match self.parents.get(&current) {
+                Some(parent) => *parent,
+                None => return false,
+            };
+        }
+        current < rooted
+    }
+
+    /// Drop the buffered updates, returning how many there were
+    pub fn clear(&mut self) -> usize {
+        let dropped: Vec<(DbAccountInfo, bool)> = std::mem::take(&mut self.slots).into_values().flatten().collect();
+        self.remove(&dropped);
+        let count = dropped.len();
+        dropped.into_iter().for_each(|(account, _)| account.recycle());
+        count
+    }
+
+    fn remove(&mut self, accounts: &[(DbAccountInfo, bool)]) {
+        sub_queued_bytes(Queue::CommitmentBuffer, accounts.iter().map(buffered_bytes).sum());
+        self.buffered -= accounts.len();
+        registry().set_gauge(COMMITMENT_BUFFERED_ACCOUNTS, &[], self.buffered as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::AccountKey;
+
+    fn account(slot: i64, write_version: i64) -> DbAccountInfo {
+        DbAccountInfo {
+            pubkey: AccountKey::from_slice(&[1; 32]),
+            lamports: 1,
+            owner: AccountKey::from_slice(&[2; 32]),
+            executable: false,
+            rent_epoch: 0,
+            data: vec![],
+            slot,
+            write_version,
+            txn_signature: None,
+        }
+    }
+
+    fn write_versions(accounts: &[(DbAccountInfo, bool)]) -> Vec<i64> {
+        accounts.iter().map(|(account, _)| account.write_version).collect()
+    }
+
+    #[test]
+    fn test_commitment_levels() {
+        assert!(CommitmentLevel::Processed.is_reached(SlotStatus::Processed));
+        assert!(!CommitmentLevel::Confirmed.is_reached(SlotStatus::Processed));
+        assert!(CommitmentLevel::Confirmed.is_reached(SlotStatus::Rooted));
+        assert!(!CommitmentLevel::Finalized.is_reached(SlotStatus::Confirmed));
+        assert!(CommitmentLevel::Finalized.is_reached(SlotStatus::Rooted));
+    }
+
+    #[test]
+    fn test_commitment_buffer() {
+        let mut buffer = CommitmentBuffer::new(CommitmentLevel::Confirmed);
+        // 10 <- 11 <- 13 and 10 <- 12, the fork of 12 is abandoned
+        for (slot, parent) in [(11, 10), (12, 10), (13, 11)] {
+            assert!(buffer.update_slot_status(slot, Some(parent), SlotStatus::Processed).is_empty());
+        }
+        for (slot, write_version, close_only) in [(11, 1, false), (12, 2, false), (13, 3, false), (11, 4, true)] {
+            assert!(buffer.push(account(slot, write_version), close_only).is_none());
+        }
+        // confirming 13 releases its ancestor 11 first, in the order the updates were received
+        let released = buffer.update_slot_status(13, Some(11), SlotStatus::Confirmed);
+        assert_eq!(write_versions(&released), vec![1, 4, 3]);
+        assert_eq!(released.iter().map(|(_, close_only)| *close_only).collect::<Vec<_>>(), vec![false, true, false]);
+        assert_eq!(write_versions(&buffer.push(account(13, 5), false).into_iter().collect::<Vec<_>>()), vec![5]);
+        assert!(buffer.update_slot_status(11, Some(10), SlotStatus::Rooted).is_empty());
+        // the update of the abandoned fork was dropped with the root
+        assert_eq!(buffer.buffered, 0);
+        assert!(buffer.push(account(14, 6), false).is_none());
+        assert_eq!(buffer.clear(), 1);
+    }
+
+    #[test]
+    fn test_unknown_parent() {
+        // 22 is confirmed before the status of 21 links them, 21 is released once the root does
+        let mut buffer = CommitmentBuffer::new(CommitmentLevel::Confirmed);
+        for (slot, write_version) in [(21, 1), (22, 2)] {
+            assert!(buffer.push(account(slot, write_version), false).is_none());
+        }
+        assert_eq!(write_versions(&buffer.update_slot_status(22, None, SlotStatus::Confirmed)), vec![2]);
+        assert!(!buffer.is_abandoned(21, 20));
+        assert_eq!(write_versions(&buffer.update_slot_status(22, Some(21), SlotStatus::Rooted)), vec![1]);
+        assert_eq!(buffer.buffered, 0);
+
+        // without the link, the root drops the updates below it rather than keeping them
+        let mut buffer = CommitmentBuffer::new(CommitmentLevel::Confirmed);
+        for (slot, write_version) in [(21, 1), (22, 2)] {
+            assert!(buffer.push(account(slot, write_version), false).is_none());
+        }
+        assert_eq!(write_versions(&buffer.update_slot_status(22, None, SlotStatus::Confirmed)), vec![2]);
+        assert!(buffer.update_slot_status(22, None, SlotStatus::Rooted).is_empty());
+        assert_eq!(buffer.buffered, 0);
+        assert!(buffer.slots.is_empty());
+    }
+}
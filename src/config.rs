@@ -1,15 +1,69 @@
 use crate::accounts_selector::AccountsSelectorConfig;
+use crate::backfill::BackfillConfig;
+use crate::commitment_buffer::CommitmentLevel;
+use crate::config_profiles;
+use crate::fixtures::FixtureRecorderConfig;
+use crate::grpc::GrpcConfig;
+use crate::memory_stats::MemoryStatsConfig;
+use crate::metrics::MetricsConfig;
+use crate::metrics::ReportingConfig;
+use crate::parallel_client::QueueSaturationConfig;
+use crate::parallel_client::WorkerWatchdogConfig;
+use crate::postgres_client::databases::validate_databases;
+use crate::postgres_client::index_manager::validate_index_config;
+use crate::postgres_client::index_manager::TableIndexConfig;
+use crate::postgres_client::table_prefix;
+use crate::postgres_client::validate_promoted_columns;
+use crate::postgres_client::AccountAuditConfig;
+use crate::postgres_client::AccountAuditMode;
+use crate::postgres_client::AccountDataCompression;
+use crate::postgres_client::AccountHandlerId;
+use crate::postgres_client::AccountHandlersConfig;
+use crate::postgres_client::CloseDetectionConfig;
+use crate::postgres_client::ConflictStrategy;
+use crate::postgres_client::DatabaseConfig;
+use crate::postgres_client::EpochsConfig;
+use crate::postgres_client::ExternalHandlerConfig;
+use crate::postgres_client::FaultInjectionConfig;
+use crate::postgres_client::ForkCleanupConfig;
+use crate::postgres_client::HeartbeatConfig;
+use crate::postgres_client::IdlConfig;
+use crate::postgres_client::LayoutConfig;
+use crate::postgres_client::LogicalReplicationConfig;
+use crate::postgres_client::PluginStatsConfig;
+use crate::postgres_client::PoolConfig;
+use crate::postgres_client::PromotedColumnConfig;
+use crate::postgres_client::ReconnectConfig;
+use crate::postgres_client::RetentionConfig;
+use crate::postgres_client::ScriptHandlerConfig;
+use crate::postgres_client::SerializationRetryConfig;
+use crate::postgres_client::TokenHolderViewsConfig;
+use crate::postgres_client::UnloadConfig;
+use crate::postgres_client::WasmHandlerConfig;
+use crate::rpc_ingest::RpcIngestConfig;
+use crate::sinks::ClickHouseSinkConfig;
+use crate::sinks::DbBackend;
+use crate::sinks::KafkaSinkConfig;
+use crate::sinks::WebhookSinkConfig;
+use crate::spool::SpoolConfig;
+use crate::traces::TracingConfig;
 use crate::transaction_selector::TransactionSelectorConfig;
+use log::*;
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
 use serde_json;
+use serde_json::Value;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
 use solana_geyser_plugin_interface::geyser_plugin_interface::Result;
+use std::collections::HashMap;
 use std::fs::File;
+use std::io::Read;
 use std::path::Path;
 
 /// Config for the PostgreSQL plugin
 ///
+/// The config is read from JSON, or from YAML/TOML when the file has a `.yaml`/`.yml`/`.toml` extension.
+///
 /// # Format of the config file:
 /// * The `accounts_selector` section allows the user to controls accounts selections.
 /// "accounts_selector" : {
@@ -26,18 +80,186 @@ use std::path::Path;
 /// "accounts_selector" : {
 ///     "accounts" : \["*"\],
 /// }
+/// * "selector_reload_interval_secs" optional, checks the config file for changes that often and swaps in its
+/// "accounts_selector" and "transaction_selector" without restarting the validator. The default is to not reload them.
 /// * "connection_str", the custom PostgreSQL connection string.
 /// Please refer to https://docs.rs/postgres/0.19.2/postgres/config/struct.Config.html for the connection configuration.
 /// When `connection_str` is set, the values in "host", "user" and "port" are ignored. If `connection_str` is not given,
 /// `host` and `user` must be given.
-/// "store_account_historical_data", optional, set it to 'true', to store historical account data to account_audit
-/// table.
 /// * "threads" optional, specifies the number of worker threads for the plugin. A thread
 /// maintains a PostgreSQL connection to the server. The default is '10'.
+/// * "worker_thread_name_prefix" optional, the name prefix of the worker threads. The default is 'worker'.
+/// * "worker_cpu_affinity" optional, the list of CPU core ids worker threads are pinned to, assigned round robin.
+/// By default worker threads are not pinned.
 /// * "batch_size" optional, specifies the batch size of bulk insert when the AccountsDb is created
 /// from restoring a snapshot. The default is '10'.
+/// * "flush_interval_ms" optional, specifies how long live account updates may sit in a pending batch
+/// before being flushed. The default is '0', writing every live update immediately.
+/// * "live_batch_size" optional, specifies how many live account updates are flushed together in a
+/// single transaction. The default is the "batch_size".
+/// * "profile", optional, names a bundle of handlers/selectors/batch settings the config extends. One of
+/// 'light', 'nft-only' or 'full-archive'. Values in the config file take precedence over the profile.
 /// * "panic_on_db_errors", optional, contols if to panic when there are errors replicating data to the
 /// PostgreSQL database. The default is 'false'.
+/// * "indexes", optional, drops or adds secondary indexes per table:
+/// "indexes" : {
+///     "account" : { "drop" : \["account_slot"\] },
+///     "spl_token_account" : { "create" : \[{ "columns" : \["mint", "owner"\] }\] }
+/// }
+/// * "logical_replication", optional, gives the tables created without a primary key one, their unique index or an `id`
+/// column, and sets the replica identity of every table, 'default' or 'full', so they can be published to logical replication:
+/// "logical_replication" : { "replica_identity" : "default" }
+/// * "account_handlers", optional, the account handlers created, and whose tables are initialized, either only the
+/// "enabled" ones or all but the "disabled" ones. The default is to create every handler:
+/// "account_handlers" : { "disabled" : \["balance_history", "listing"\] }
+/// * "conflict_strategies", optional, how the upserts of a handler resolve a row already written for the key, one of
+/// 'latest_slot', 'slot_write_version', 'always_overwrite' or 'merge_non_null'. The default is 'slot_write_version'
+/// for the 'unknown_account' and 'token_account' handlers, whose tables record the write version, 'merge_non_null' for 'listing'
+/// and 'latest_slot' otherwise:
+/// "conflict_strategies" : { "token_manager" : "always_overwrite", "layout" : "merge_non_null" }
+/// * "idl", optional, the programs whose accounts the 'idl' handler decodes with their Anchor IDL, requires the `idl` feature:
+/// "idl" : {
+///     "rpc_url" : "https://api.mainnet-beta.solana.com",
+///     "programs" : { "<program id>" : { "table" : "my_program_account" } }
+/// }
+/// * "store_decoded_accounts", optional, set it to 'true' to also write accounts decoded by the 'idl' and 'layout'
+/// handlers to the JSONB `decoded_account` table. The default is 'false'.
+/// * "account_data_compression", optional, 'zstd' or 'lz4' to compress the `data` of the `account` table, whose
+/// `data_codec` column records the codec of each row. The default is 'none'.
+/// * "account_audit", optional, how the 'account_audit' handler stores the versions of an account, 'full' copies or
+/// the 'diff' of the changed byte ranges with a full copy every "checkpoint_interval" versions:
+/// "account_audit" : { "mode" : "diff", "checkpoint_interval" : 100, "chunk_bytes" : 32 }
+/// * "promoted_columns", optional, promotes JSON paths of decoded accounts to indexed columns per table:
+/// "promoted_columns" : {
+///     "decoded_account" : \[{ "name" : "mint", "path" : "mint", "type" : "VARCHAR(44)" }\]
+/// }
+/// * "discriminator_registry", optional, set it to 'true' to count the (owner, discriminator) combinations of
+/// the selected accounts in the `discriminator_registry` table. The default is 'false'.
+/// * "layouts", optional, account layouts the 'layout' handler decodes into a table per layout:
+/// "layouts" : \[{
+///     "table" : "stake_entry", "owner" : "<program id>", "anchor_account" : "StakeEntry",
+///     "fields" : \[{ "name" : "pool", "type" : "pubkey", "offset" : 9 }, { "name" : "amount", "type" : "u64" }\]
+/// }\]
+/// * "external_handlers", optional, loads additional account and transaction handlers from dynamic libraries:
+/// "external_handlers" : \[{ "libpath" : "/solana/libmy_handlers.so", "account_handlers" : \["my_pool"\] }\]
+/// * "wasm_handlers", optional, account handlers compiled to WebAssembly, requires the "wasm" feature:
+/// "wasm_handlers" : \[{ "handler_id" : "my_pool", "path" : "/solana/my_pool.wasm", "table" : "my_pool" }\]
+/// * "script_handlers", optional, account handlers written in Rhai, requires the "scripting" feature:
+/// "script_handlers" : \[{ "handler_id" : "my_pool", "path" : "/solana/my_pool.rhai", "table" : "my_pool" }\]
+/// * "fixture_recorder", optional, dumps the selected accounts and transactions to files for replay in tests:
+/// "fixture_recorder" : { "path" : "/solana/fixtures", "format" : "json" }
+/// * "metrics", optional, serves the plugin metrics in the Prometheus text format on `/metrics`:
+/// "metrics" : { "bind_address" : "127.0.0.1:9187" }
+/// * "reporting", optional, the cadence of the periodic reports, latency sampling and whether solana-metrics is used:
+/// "reporting" : { "interval_ms" : 30000, "solana_metrics" : false, "histogram_sample_rate" : 10 }
+/// * "heartbeat", optional, writes the last processed slot to the `plugin_heartbeat` table every `interval_secs`:
+/// "heartbeat" : { "instance" : "validator-1", "interval_secs" : 10 }
+/// * "epochs", optional, writes the first and last slot of every epoch a slot is rooted in to the `epoch` table, and
+/// with `leader_schedule`, fetches the leader schedule of every new epoch, which requires the "leader-schedule" feature:
+/// "epochs" : { "slots_per_epoch" : 432000, "leader_schedule" : { "rpc_url" : "http://127.0.0.1:8899" } }
+/// * "token_holder_views", optional, maintains the `token_holders_per_mint` and `token_balance_per_owner` materialized
+/// views of `spl_token_account`, refreshed every `interval_secs` and, with `rooted_slots`, every `rooted_slots` rooted slots:
+/// "token_holder_views" : { "interval_secs" : 300, "rooted_slots" : 0 }
+/// * "retention", optional, deletes the rows of `tables` older than their `max_age_secs` every `interval_secs`, by the
+/// UTC timestamp in their `column`, `updated_on` by default:
+/// "retention" : { "interval_secs" : 3600, "tables" : { "transaction" : { "max_age_secs" : 604800 }, "slot" : { "max_age_secs" : 86400 } } }
+/// * "plugin_stats", optional, writes the queue length and the counts of every interval to the `plugin_stats` table:
+/// "plugin_stats" : { "instance" : "validator-1", "interval_secs" : 60 }
+/// * "memory_stats", optional, reports the bytes held by the request queues and, with the "jemalloc" feature,
+/// the jemalloc statistics every `interval_secs`:
+/// "memory_stats" : { "interval_secs" : 30 }
+/// * "startup_summary", optional, set it to 'true' to also write the summary logged at the end of startup
+/// to the `startup_summary` table. The default is 'false'.
+/// * "startup_progress", optional, set it to 'true' to count the committed startup batches in the `startup_progress`
+/// table, and record the startup that never completed, if any, when the plugin is loaded again. The snapshot is then
+/// written again whole. The default is 'false'.
+/// * "unload", optional, how long the queued requests are drained on unload, and whether the summary of the
+/// processed and dropped requests is also written to the `unload_summary` table. The default is 30 seconds:
+/// "unload" : { "drain_timeout_secs" : 30, "write_summary" : true }
+/// * "write_batches", optional, set it to 'true' to record the id, worker and accounts of every flush
+/// in the `write_batch` table. The default is 'false'.
+/// * "pgbouncer", optional, set it to 'true' when connecting through PgBouncer in transaction pooling mode. The statements
+/// with typed parameters are then prepared in the transaction they run in, and nothing is left on the server connection
+/// across transactions. The default is 'false'.
+/// * "async_mode", optional, set it to 'true' to also open a tokio-postgres connection per worker and write the live
+/// flushes on it, pipelining the rows of the handlers with prepared statements rather than waiting for every row to be
+/// written before sending the next one. Can't be set with "pgbouncer". The default is 'false'.
+/// * "schema", optional, the schema the tables of the plugin are created and written in, created when missing. The
+/// connections set their `search_path` to it. The default is the schema of the role's search path, usually 'public'.
+/// * "table_prefix", optional, prepended to the names of the tables, views, indexes and functions of the plugin, e.g.
+/// "devnet_", so the plugins of several clusters can share a schema. The handlers name their tables with the prefix of
+/// the config their client was built from, external and custom handlers with `table_prefix::table`. The default is no
+/// prefix.
+/// * "startup_bulk_load", optional, set it to 'true' to load the startup accounts of the 'unknown_account' and
+/// 'token_account' handlers through a temporary table, applying only the latest entry of each account of a batch.
+/// The default is 'false'.
+/// * "startup_copy", optional, set it to 'true' to load the startup accounts as with "startup_bulk_load", copying each
+/// batch to the temporary tables with `COPY ... FROM STDIN BINARY`. A larger "batch_size" then makes fewer, larger copies.
+/// The default is 'false'.
+/// * "dead_letters", optional, set it to 'true' to record the statements of the handlers that failed a flush on their own,
+/// and the accounts or transactions they were writing, in the `dead_letter` table. The other handlers of the flush are
+/// still written. The default is 'false'.
+/// * "skip_block_rewards_array", optional, set it to 'true' to leave the `rewards` column of the `block` table empty,
+/// the rewards are still written to the `block_reward` table. The default is 'false'.
+/// * "slot_finality_notify", optional, set it to 'true' to `NOTIFY slot_finality` with the slot and its status
+/// when a slot is confirmed or rooted. The default is 'false'.
+/// * "commitment_level", optional, one of 'processed', 'confirmed' or 'finalized'. The live account updates are held in
+/// memory until their slot is confirmed, or rooted with 'finalized', and those of the abandoned forks are never written.
+/// The default is 'processed', writing every update as it is received.
+/// * "fork_cleanup", optional, gives the slots of the forks abandoned by a rooted slot the 'abandoned' status, and deletes
+/// the rows written at them from `purge_tables`. The default purges no table, only marking the slots:
+/// "fork_cleanup" : { "purge_tables" : \["account", "spl_token_account", "token_manager"\], "lookback_slots" : 256 }
+/// * "close_detection", optional, deletes the rows of the accounts closed by a live update, their lamports dropped to
+/// zero, from `tables`, or sets their `closed_at_slot` column with the "mark" mode until the account is written again:
+/// "close_detection" : { "mode" : "delete", "tables" : { "account" : "pubkey", "spl_token_account" : "pubkey", "token_manager" : "id" } }
+/// * "kafka", optional, also publishes the account, slot, transaction and block events to Kafka topics, or only publishes
+/// them with "replace_postgres", requires the `kafka` feature:
+/// "kafka" : { "brokers" : "localhost:9092", "format" : "json", "replace_postgres" : false }
+/// * "db_backend", optional, the database the events are written to, 'postgres' or 'clickhouse'. With 'clickhouse' the
+/// account, slot, transaction and block events are inserted into MergeTree tables of the `clickhouse` server instead of
+/// the tables of the handlers, and no PostgreSQL connection is made. The default is 'postgres'.
+/// * "clickhouse", optional, the ClickHouse server of the 'clickhouse' `db_backend` and how its inserts are batched,
+/// requires the `clickhouse` feature:
+/// "clickhouse" : { "url" : "http://localhost:8123", "database" : "solana", "batch_size" : 10000, "flush_interval_ms" : 1000 }
+/// * "webhooks", optional, POSTs the account updates and slot statuses as JSON to HTTP endpoints, signed with the
+/// webhook's `secret`, requires the `webhook` feature:
+/// "webhooks" : \[{ "url" : "https://indexer.example.com/geyser", "secret" : "...", "owners" : \[...\], "slot_statuses" : \["rooted"\] }\]
+/// * "grpc", optional, streams the selected account updates, transactions and slot statuses to the subscribers of a gRPC
+/// server, each with its own filter, requires the `grpc` feature:
+/// "grpc" : { "bind_address" : "0.0.0.0:10000", "channel_capacity" : 100000, "max_subscribers" : 64 }
+/// * "tracing", optional, logs the duration of one in `sample_rate` callbacks and of the worker requests they queue:
+/// "tracing" : { "sample_rate" : 1000 }
+/// * "worker_watchdog", optional, restarts workers that exited or stopped making progress:
+/// "worker_watchdog" : { "stall_timeout_secs" : 60, "check_interval_ms" : 1000, "max_retired_workers" : 2 }
+/// * "queue_saturation", optional, warns when the request channel fills up and can shed live updates:
+/// "queue_saturation" : { "warn_percent" : 75, "shed_percent" : 95, "shed" : true }
+/// * "serialization_retry", optional, retries the batches failing on a deadlock or a serialization failure
+/// with a jittered exponential backoff. The default is 5 retries from 10ms up to 1000ms:
+/// "serialization_retry" : { "max_retries" : 5, "base_delay_ms" : 10, "max_delay_ms" : 1000 }
+/// * "reconnect", optional, reconnects a worker whose database connection was lost with a jittered exponential backoff,
+/// and writes the batch the lost connection failed again. Once `max_retries` attempts failed, the validator is aborted
+/// with `panic_on_db_errors`, otherwise the request is dropped and the next one reconnects again.
+/// The default is 10 attempts from 100ms up to 30000ms:
+/// "reconnect" : { "max_retries" : 10, "base_delay_ms" : 100, "max_delay_ms" : 30000 }
+/// * "pool", optional, shares a pool of connections between the worker threads instead of a connection per thread.
+/// A worker checks a connection out for each request and hands it back once written. The default is no pool:
+/// "pool" : { "min_connections" : 1, "max_connections" : 10, "idle_timeout_secs" : 600, "connection_timeout_secs" : 30, "statement_cache" : true }
+/// * "spool", optional, appends the live events to a file while a worker is reconnecting, and replays them once the
+/// workers are connected again. The workers then keep reconnecting past `max_retries` rather than giving up:
+/// "spool" : { "path" : "/solana/spool", "max_bytes" : 10737418240, "replay_batch" : 1000 }
+/// * "databases", optional, writes the account and transaction handlers listed in a database's `handlers` to it rather
+/// than to the database of `connection_str`, each database with `threads` workers and optionally a `pool`, a `schema` and
+/// a `table_prefix` of its own:
+/// "databases" : \[{ "name" : "transactions", "connection_str" : "host=... dbname=transactions", "threads" : 4, "handlers" : \["transaction", "token_transfer"\] }\]
+/// * "fault_injection", optional, for tests only, injects connection drops, serialization failures, constraint
+/// violations and slow statements in the batches of the workers, requires the "fault-injection" feature:
+/// "fault_injection" : { "seed" : 7, "serialization_failure_one_in" : 3 }
+/// * "rpc_ingest", optional, the RPC node the `geyser-rpc-ingest` runner subscribes to instead of running in a
+/// validator, and where it streams the transactions from, requires the "rpc-ingest" feature:
+/// "rpc_ingest" : { "rpc_url" : "https://api.devnet.solana.com", "commitment" : "confirmed", "transactions" : "logs" }
+/// * "backfill", optional, the RPC node and the slot range the `geyser-backfill` runner fetches the past blocks of,
+/// requires the "rpc-ingest" feature:
+/// "backfill" : { "rpc_url" : "https://api.mainnet-beta.solana.com", "start_slot" : 150000000, "end_slot" : 150100000 }
 /// * "transaction_selector", optional, controls if and what transaction to store. If this field is missing
 /// None of the transction is stored.
 /// "transaction_selector" : {
@@ -66,6 +288,9 @@ use std::path::Path;
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct GeyserPluginPostgresConfig {
+    /// The named profile this config extends, if any
+    pub profile: Option<String>,
+
     /// The connection string of PostgreSQL database, if this is set
     /// `host`, `user` and `port` will be ignored.
     pub connection_str: String,
@@ -77,14 +302,207 @@ pub struct GeyserPluginPostgresConfig {
     /// `host`, `user` and `port` will be ignored.
     pub transaction_selector: Option<TransactionSelectorConfig>,
 
+    /// Seconds between two checks of the config file for new selectors, not reloaded when not set
+    pub selector_reload_interval_secs: Option<u64>,
+
     /// Controls the number of threads establishing connections to
     /// the PostgreSQL server. The default is 10.
     pub threads: usize,
 
+    /// The name prefix of the worker threads, suffixed with the worker index.
+    /// The default is "worker".
+    pub worker_thread_name_prefix: String,
+
+    /// CPU core ids to pin the worker threads to, assigned round robin so
+    /// indexing work can be isolated from the validator's own threads.
+    /// The default is to not pin worker threads.
+    pub worker_cpu_affinity: Option<Vec<usize>>,
+
     /// Controls the batch size when bulk loading accounts.
     /// The default is 10.
     pub batch_size: usize,
 
+    /// Controls how long, in milliseconds, live account updates may be held
+    /// in a pending batch before being forced out. Batches are also flushed
+    /// when they reach `live_batch_size`. The default is 0, writing every live
+    /// update immediately.
+    pub flush_interval_ms: u64,
+
+    /// The pending live account updates that force a flush, `batch_size` when not set. An update counts once
+    /// whatever the statements and rows its handlers wrote.
+    pub live_batch_size: Option<usize>,
+
+    /// Secondary indexes to drop or create per table
+    pub indexes: HashMap<String, TableIndexConfig>,
+
+    /// Give every table a primary key and a replica identity for logical replication
+    pub logical_replication: Option<LogicalReplicationConfig>,
+
+    /// The account handlers created for this deployment
+    pub account_handlers: Option<AccountHandlersConfig>,
+
+    /// How the upserts of each handler resolve conflicting rows
+    pub conflict_strategies: HashMap<String, ConflictStrategy>,
+
+    /// Programs whose accounts are decoded using their Anchor IDL
+    pub idl: Option<IdlConfig>,
+
+    /// Account layouts decoded without a dedicated handler
+    pub layouts: Vec<LayoutConfig>,
+
+    /// Controls whether accounts decoded by the IDL and layout handlers are also
+    /// written to the generic `decoded_account` table. The default is false.
+    pub store_decoded_accounts: bool,
+
+    /// The codec the unknown account handler compresses the account data with. The default is none.
+    pub account_data_compression: AccountDataCompression,
+
+    /// How the account audit handler stores the versions of an account. The default is a full copy per version.
+    pub account_audit: AccountAuditConfig,
+
+    /// Controls whether the (owner, discriminator) combinations of selected accounts
+    /// are counted in the `discriminator_registry` table. The default is false.
+    pub discriminator_registry: bool,
+
+    /// JSON paths of decoded accounts promoted to indexed columns, per table
+    pub promoted_columns: HashMap<String, Vec<PromotedColumnConfig>>,
+
+    /// Dynamic libraries registering additional handlers
+    pub external_handlers: Vec<ExternalHandlerConfig>,
+
+    /// Sandboxed account handlers compiled to WebAssembly
+    pub wasm_handlers: Vec<WasmHandlerConfig>,
+
+    /// Account handlers written in Rhai, reloaded when the script changes
+    pub script_handlers: Vec<ScriptHandlerConfig>,
+
+    /// Record the selected accounts and transactions to fixture files
+    pub fixture_recorder: Option<FixtureRecorderConfig>,
+
+    /// Serve the plugin metrics for Prometheus
+    pub metrics: Option<MetricsConfig>,
+
+    /// Reporting cadence, latency sampling and solana-metrics datapoints
+    pub reporting: ReportingConfig,
+
+    /// Periodically write a liveness row to the `plugin_heartbeat` table
+    pub heartbeat: Option<HeartbeatConfig>,
+
+    /// Record the epoch boundaries and optionally the leader schedules
+    pub epochs: Option<EpochsConfig>,
+
+    /// Maintain materialized views of the token holders
+    pub token_holder_views: Option<TokenHolderViewsConfig>,
+
+    /// Delete the rows older than the retention of their table
+    pub retention: Option<RetentionConfig>,
+
+    /// Periodically write the plugin counters to the `plugin_stats` table
+    pub plugin_stats: Option<PluginStatsConfig>,
+
+    /// Periodically report the allocator statistics and the bytes held by the queues
+    pub memory_stats: Option<MemoryStatsConfig>,
+
+    /// Write the end of startup summary to the `startup_summary` table
+    pub startup_summary: bool,
+
+    /// Count the committed startup batches in the `startup_progress` table
+    pub startup_progress: bool,
+
+    /// Drain the queued requests on unload and account for the dropped ones
+    pub unload: UnloadConfig,
+
+    /// Record the provenance of every flush in the `write_batch` table
+    pub write_batches: bool,
+
+    /// Record the statements of the handlers failing a flush in the `dead_letter` table
+    pub dead_letters: bool,
+
+    /// Resolve the duplicate startup accounts of a batch in the database rather than with conflicting upserts
+    pub startup_bulk_load: bool,
+
+    /// Copy the startup accounts of the bulk load to its staging tables in binary
+    pub startup_copy: bool,
+
+    /// Keep no prepared statement or temporary table on the server connection across transactions
+    pub pgbouncer: bool,
+
+    /// Pipeline the bound rows of the handlers on an async connection per worker
+    pub async_mode: bool,
+
+    /// The schema of the tables, the search path of the connections
+    pub schema: Option<String>,
+
+    /// Prepended to the names of the tables, views, indexes and functions
+    pub table_prefix: String,
+
+    /// Only write the block rewards to the `block_reward` table, not to the array column of `block`
+    pub skip_block_rewards_array: bool,
+
+    /// Notify the `slot_finality` channel when a slot is confirmed or rooted
+    pub slot_finality_notify: bool,
+
+    /// The status a slot must reach before its live account updates are written
+    pub commitment_level: CommitmentLevel,
+
+    /// Mark the slots of abandoned forks and delete the rows written at them
+    pub fork_cleanup: Option<ForkCleanupConfig>,
+
+    /// Delete or mark the rows of closed accounts
+    pub close_detection: Option<CloseDetectionConfig>,
+
+    /// Publish the events to Kafka topics, next to or instead of PostgreSQL
+    pub kafka: Option<KafkaSinkConfig>,
+
+    /// The database the events are written to. The default is PostgreSQL
+    pub db_backend: DbBackend,
+
+    /// The ClickHouse server of the `clickhouse` backend
+    pub clickhouse: ClickHouseSinkConfig,
+
+    /// The HTTP endpoints notified of the account updates and slot statuses
+    pub webhooks: Vec<WebhookSinkConfig>,
+
+    /// Stream the updates to gRPC subscribers
+    pub grpc: Option<GrpcConfig>,
+
+    /// Trace a sample of the callbacks through the workers
+    pub tracing: Option<TracingConfig>,
+
+    /// Restart the workers that exited or stalled
+    pub worker_watchdog: Option<WorkerWatchdogConfig>,
+
+    /// Warn about, and optionally shed load on, a filling request channel
+    pub queue_saturation: Option<QueueSaturationConfig>,
+
+    /// Retry the batches that deadlocked with another worker or failed to serialize
+    pub serialization_retry: SerializationRetryConfig,
+
+    /// Reconnect the workers whose database connection was lost
+    pub reconnect: ReconnectConfig,
+
+    /// Share a pool of connections between the workers
+    pub pool: Option<PoolConfig>,
+
+    /// Spool the live events to disk while the database is unreachable
+    pub spool: Option<SpoolConfig>,
+
+    /// The databases written by the handlers assigned to them
+    pub databases: Vec<DatabaseConfig>,
+
+    /// The database of `databases` the workers write to, set on the configs of its workers
+    #[serde(skip)]
+    pub database: Option<String>,
+
+    /// Inject faults in the batches of the workers, for tests
+    pub fault_injection: Option<FaultInjectionConfig>,
+
+    /// Subscribe to an RPC node with the `geyser-rpc-ingest` runner
+    pub rpc_ingest: Option<RpcIngestConfig>,
+
+    /// Backfill the blocks of a past slot range with the `geyser-backfill` runner
+    pub backfill: Option<BackfillConfig>,
+
     /// Controls whether to panic the validator in case of errors
     /// writing to PostgreSQL server. The default is false
     pub panic_on_db_errors: bool,
@@ -114,11 +532,73 @@ pub struct GeyserPluginPostgresConfig {
 impl Default for GeyserPluginPostgresConfig {
     fn default() -> Self {
         Self {
+            profile: None,
             connection_str: "".to_string(),
             accounts_selector: None,
             transaction_selector: None,
+            selector_reload_interval_secs: None,
             threads: 10,
+            worker_thread_name_prefix: "worker".to_string(),
+            worker_cpu_affinity: None,
             batch_size: 10,
+            flush_interval_ms: 0,
+            live_batch_size: None,
+            indexes: HashMap::default(),
+            logical_replication: None,
+            account_handlers: None,
+            conflict_strategies: HashMap::default(),
+            idl: None,
+            layouts: Vec::default(),
+            store_decoded_accounts: false,
+            account_data_compression: AccountDataCompression::None,
+            account_audit: AccountAuditConfig::default(),
+            discriminator_registry: false,
+            promoted_columns: HashMap::default(),
+            external_handlers: Vec::default(),
+            wasm_handlers: Vec::default(),
+            script_handlers: Vec::default(),
+            fixture_recorder: None,
+            metrics: None,
+            reporting: ReportingConfig::default(),
+            heartbeat: None,
+            epochs: None,
+            token_holder_views: None,
+            retention: None,
+            plugin_stats: None,
+            memory_stats: None,
+            startup_summary: false,
+            startup_progress: false,
+            unload: UnloadConfig::default(),
+            write_batches: false,
+            dead_letters: false,
+            startup_bulk_load: false,
+            startup_copy: false,
+            pgbouncer: false,
+            async_mode: false,
+            schema: None,
+            table_prefix: "".to_string(),
+            skip_block_rewards_array: false,
+            slot_finality_notify: false,
+            commitment_level: CommitmentLevel::default(),
+            fork_cleanup: None,
+            close_detection: None,
+            kafka: None,
+            db_backend: DbBackend::default(),
+            clickhouse: ClickHouseSinkConfig::default(),
+            webhooks: Vec::new(),
+            grpc: None,
+            tracing: None,
+            worker_watchdog: None,
+            queue_saturation: None,
+            serialization_retry: SerializationRetryConfig::default(),
+            reconnect: ReconnectConfig::default(),
+            pool: None,
+            spool: None,
+            databases: Vec::new(),
+            database: None,
+            fault_injection: None,
+            rpc_ingest: None,
+            backfill: None,
             panic_on_db_errors: false,
             use_ssl: None,
             server_ca: None,
@@ -130,11 +610,263 @@ impl Default for GeyserPluginPostgresConfig {
     }
 }
 
+/// Keys of earlier releases that are still accepted but no longer do anything, with what replaced them
+const DEPRECATED_KEYS: &[(&str, &str)] = &[
+    ("index_token_owner", "the spl_token_account owner index, which \"indexes\" drops or adds"),
+    ("index_token_mint", "the spl_token_account mint index, which \"indexes\" drops or adds"),
+];
+
+/// The deprecated keys set in a raw config, with what replaced them
+pub fn deprecated_keys(value: &Value) -> Vec<(&'static str, &'static str)> {
+    DEPRECATED_KEYS.iter().copied().filter(|(key, _)| value.get(key).is_some()).collect()
+}
+
 impl GeyserPluginPostgresConfig {
-    /// Read plugin from JSON file.
+    /// Read plugin from a JSON, YAML or TOML file, detected by the file extension.
     pub fn read_from<P: AsRef<Path>>(config_path: P) -> Result<Self> {
-        let file = File::open(config_path)?;
-        let this: Self = serde_json::from_reader(file).map_err(|e| GeyserPluginError::ConfigFileReadError { msg: e.to_string() })?;
+        let extension = config_path.as_ref().extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+        let mut file = File::open(config_path)?;
+        let value: Value = match extension.as_deref() {
+            Some("yaml") | Some("yml") => serde_yaml::from_reader(file).map_err(|e| GeyserPluginError::ConfigFileReadError { msg: e.to_string() })?,
+            Some("toml") => {
+                let mut contents = String::new();
+                file.read_to_string(&mut contents)?;
+                toml::from_str(&contents).map_err(|e| GeyserPluginError::ConfigFileReadError { msg: e.to_string() })?
+            }
+            _ => serde_json::from_reader(file).map_err(|e| GeyserPluginError::ConfigFileReadError { msg: e.to_string() })?,
+        };
+        Self::from_value(value)
+    }
+
+    /// Build the config from a raw value, applying its profile if one is named
+    pub fn from_value(value: Value) -> Result<Self> {
+        let value = match value.get("profile").and_then(Value::as_str).map(str::to_string) {
+            Some(profile_name) => {
+                let mut profile = config_profiles::profile(&profile_name).ok_or_else(|| GeyserPluginError::ConfigFileReadError {
+                    msg: format!("Unknown profile \"{}\", expected one of {:?}", profile_name, config_profiles::PROFILE_NAMES),
+                })?;
+                config_profiles::merge(&mut profile, value);
+                profile
+            }
+            None => value,
+        };
+        for (key, replacement) in deprecated_keys(&value) {
+            warn!("[config] \"{}\" is deprecated and ignored, replaced by {}", key, replacement);
+        }
+        let this: Self = serde_json::from_value(value).map_err(|e| GeyserPluginError::ConfigFileReadError { msg: e.to_string() })?;
         Ok(this)
     }
+
+    /// The conflict strategy of the upserts of a handler
+    pub fn conflict_strategy(&self, handler_id: &str) -> ConflictStrategy {
+        self.conflict_strategies
+            .get(handler_id)
+            .copied()
+            .unwrap_or_else(|| AccountHandlerId::resolve(handler_id).default_conflict_strategy())
+    }
+
+    /// Whether the `account_handlers` config creates a handler
+    pub fn account_handler_enabled(&self, handler_id: &str) -> bool {
+        self.account_handlers.as_ref().map_or(true, |account_handlers| account_handlers.is_enabled(handler_id))
+    }
+
+    /// Whether the events are written to PostgreSQL, rather than only published to the sinks
+    pub fn writes_postgres(&self) -> bool {
+        self.db_backend == DbBackend::Postgres && !self.kafka.as_ref().map_or(false, |kafka| kafka.replace_postgres)
+    }
+
+    /// Check the config for values that would otherwise fail at runtime
+    pub fn validate(&self) -> Result<()> {
+        self.validate_with_custom_handlers(&[], &[])
+    }
+
+    /// Like `validate`, additionally accepting the ids of handlers registered in code
+    pub fn validate_with_custom_handlers(&self, account_handler_ids: &[String], transaction_handler_ids: &[String]) -> Result<()> {
+        let invalid = |msg: String| Err(GeyserPluginError::ConfigFileReadError { msg });
+        if self.connection_str.is_empty() && self.writes_postgres() {
+            return invalid("\"connection_str\" must be specified".to_string());
+        }
+        if self.threads == 0 {
+            return invalid("\"threads\" must be greater than 0".to_string());
+        }
+        if let Some(core_ids) = &self.worker_cpu_affinity {
+            if core_ids.is_empty() {
+                return invalid("\"worker_cpu_affinity\" must list at least one core id".to_string());
+            }
+        }
+        if self.batch_size == 0 {
+            return invalid("\"batch_size\" must be greater than 0".to_string());
+        }
+        if self.selector_reload_interval_secs == Some(0) {
+            return invalid("\"selector_reload_interval_secs\" must be greater than 0".to_string());
+        }
+        if self.live_batch_size == Some(0) {
+            return invalid("\"live_batch_size\" must be greater than 0".to_string());
+        }
+        table_prefix::validate(&self.schema, &self.table_prefix).or_else(invalid)?;
+        if self.schema.is_some() && self.pgbouncer {
+            // a server connection in transaction pooling mode doesn't keep the search path of the session
+            return invalid("\"schema\" can't be set with \"pgbouncer\", set the search_path of the database role instead".to_string());
+        }
+        if self.async_mode && self.pgbouncer {
+            // the statements are prepared once for the session of the pipelined connection
+            return invalid("\"async_mode\" can't be set with \"pgbouncer\"".to_string());
+        }
+        if self.use_ssl == Some(true) {
+            for (name, value) in [("server_ca", &self.server_ca), ("client_cert", &self.client_cert), ("client_key", &self.client_key)] {
+                match value {
+                    Some(path) if !Path::new(path).exists() => return invalid(format!("\"{}\" file {} does not exist", name, path)),
+                    Some(_) => {}
+                    None => return invalid(format!("\"{}\" must be specified when \"use_ssl\" is set", name)),
+                }
+            }
+        }
+        validate_index_config(&self.indexes).or_else(invalid)?;
+        for (handler_id, strategy) in &self.conflict_strategies {
+            let id = AccountHandlerId::resolve(handler_id);
+            let decodes_with_module = self.wasm_handlers.iter().any(|h| &h.handler_id == handler_id) || self.script_handlers.iter().any(|h| &h.handler_id == handler_id);
+            if !id.supports_conflict_strategy() && !decodes_with_module {
+                return invalid(format!("conflict_strategies: handler \"{}\" doesn't upsert with a conflict strategy", handler_id));
+            }
+            if *strategy == ConflictStrategy::SlotWriteVersion && !id.records_write_version() {
+                return invalid(format!(
+                    "conflict_strategies: \"slot_write_version\" requires a handler whose tables record the write version, \"{}\" doesn't",
+                    handler_id
+                ));
+            }
+        }
+        if let Some(idl) = &self.idl {
+            idl.validate().or_else(invalid)?;
+        }
+        validate_promoted_columns(&self.promoted_columns).or_else(invalid)?;
+        self.account_audit.validate().or_else(invalid)?;
+        for layout in &self.layouts {
+            layout.validate().or_else(invalid)?;
+        }
+        for external in &self.external_handlers {
+            if !Path::new(&external.libpath).exists() {
+                return invalid(format!("external_handlers libpath {} does not exist", external.libpath));
+            }
+        }
+        for wasm_handler in &self.wasm_handlers {
+            wasm_handler.validate().or_else(invalid)?;
+        }
+        if let Some(fixture_recorder) = &self.fixture_recorder {
+            if fixture_recorder.path.is_empty() {
+                return invalid("\"fixture_recorder\" must set \"path\"".to_string());
+            }
+        }
+        if let Some(spool) = &self.spool {
+            if spool.path.is_empty() {
+                return invalid("\"spool\" must set \"path\"".to_string());
+            }
+            if self.reconnect.max_retries == 0 {
+                return invalid("\"spool\" requires the workers to reconnect, \"reconnect.max_retries\" can't be 0".to_string());
+            }
+        }
+        if let Some(epochs) = &self.epochs {
+            epochs.validate().or_else(invalid)?;
+        }
+        if let Some(token_holder_views) = &self.token_holder_views {
+            token_holder_views.validate().or_else(invalid)?;
+        }
+        if let Some(retention) = &self.retention {
+            retention.validate().or_else(invalid)?;
+        }
+        if let Some(fork_cleanup) = &self.fork_cleanup {
+            fork_cleanup.validate().or_else(invalid)?;
+        }
+        if self.account_audit.mode == AccountAuditMode::Diff {
+            let retained = self.retention.iter().flat_map(|retention| retention.tables.keys());
+            let purged = self.fork_cleanup.iter().flat_map(|fork_cleanup| fork_cleanup.purge_tables.iter());
+            if retained.chain(purged).any(|table| table == "account_audit") {
+                return invalid(
+                    "\"account_audit\" can't be deleted from by \"retention\" or \"fork_cleanup\" in the 'diff' mode, the deltas of the remaining rows start from the deleted ones".to_string(),
+                );
+            }
+        }
+        if let Some(close_detection) = &self.close_detection {
+            close_detection.validate().or_else(invalid)?;
+        }
+        if let Some(kafka) = &self.kafka {
+            kafka.validate().or_else(invalid)?;
+        }
+        if let Some(pool) = &self.pool {
+            pool.validate().or_else(invalid)?;
+        }
+        if self.db_backend == DbBackend::Clickhouse {
+            self.clickhouse.validate().or_else(invalid)?;
+        }
+        for webhook in &self.webhooks {
+            webhook.validate().or_else(invalid)?;
+        }
+        if let Some(grpc) = &self.grpc {
+            grpc.validate().or_else(invalid)?;
+        }
+        if !self.writes_postgres() {
+            let database_sections = [
+                ("heartbeat", self.heartbeat.is_some()),
+                ("epochs", self.epochs.is_some()),
+                ("token_holder_views", self.token_holder_views.is_some()),
+                ("retention", self.retention.is_some()),
+                ("plugin_stats", self.plugin_stats.is_some()),
+                ("fork_cleanup", self.fork_cleanup.is_some()),
+                ("close_detection", self.close_detection.is_some()),
+                ("pool", self.pool.is_some()),
+                ("databases", !self.databases.is_empty()),
+                ("async_mode", self.async_mode),
+                ("startup_summary", self.startup_summary),
+                ("startup_progress", self.startup_progress),
+                ("unload.write_summary", self.unload.write_summary),
+                ("skip_upsert_existing_accounts_at_startup", self.skip_upsert_existing_accounts_at_startup),
+            ];
+            if let Some((section, _)) = database_sections.iter().find(|(_, set)| *set) {
+                let replaced_by = match self.db_backend {
+                    DbBackend::Clickhouse => "the \"clickhouse\" db_backend",
+                    DbBackend::Postgres => "kafka \"replace_postgres\"",
+                };
+                return invalid(format!("\"{}\" writes to PostgreSQL, which {} doesn't connect to", section, replaced_by));
+            }
+        }
+        if let Some(fault_injection) = &self.fault_injection {
+            fault_injection.validate().or_else(invalid)?;
+        }
+        if let Some(rpc_ingest) = &self.rpc_ingest {
+            rpc_ingest.validate(self.accounts_selector.as_ref()).or_else(invalid)?;
+        }
+        if let Some(backfill) = &self.backfill {
+            backfill.validate().or_else(invalid)?;
+        }
+        for script_handler in &self.script_handlers {
+            script_handler.validate().or_else(invalid)?;
+        }
+        let external_account_ids = self
+            .external_handlers
+            .iter()
+            .flat_map(|e| e.account_handlers.clone())
+            .chain(self.wasm_handlers.iter().map(|w| w.handler_id.clone()))
+            .chain(self.script_handlers.iter().map(|s| s.handler_id.clone()))
+            .chain(account_handler_ids.iter().cloned())
+            .collect::<Vec<String>>();
+        if let Some(account_handlers) = &self.account_handlers {
+            account_handlers.validate(&external_account_ids).or_else(invalid)?;
+        }
+        if let Some(accounts_selector) = &self.accounts_selector {
+            accounts_selector.validate(&external_account_ids).or_else(invalid)?;
+            if let Some(handler_id) = accounts_selector.handler_ids().find(|handler_id| !self.account_handler_enabled(handler_id)) {
+                return invalid(format!("accounts_selector selects handler_id \"{}\" which account_handlers doesn't create", handler_id));
+            }
+        }
+        let external_transaction_ids = self
+            .external_handlers
+            .iter()
+            .flat_map(|e| e.transaction_handlers.clone())
+            .chain(transaction_handler_ids.iter().cloned())
+            .collect::<Vec<String>>();
+        if let Some(transaction_selector) = &self.transaction_selector {
+            transaction_selector.validate(&external_transaction_ids).or_else(invalid)?;
+        }
+        validate_databases(&self.databases, &external_account_ids, &external_transaction_ids).or_else(invalid)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,75 @@
+use serde_json::json;
+use serde_json::Value;
+
+pub const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const METADATA_PROGRAM_ID: &str = "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s";
+pub const TOKEN_MANAGER_PROGRAM_ID: &str = "mgr99QFMYByTqGPWmNqunV7vBLmWWXdSrHUfV8Jf3JM";
+pub const TRANSFER_AUTHORITY_PROGRAM_ID: &str = "trttGqe8YQZbgDT5dWvVqmfXC4LNvpyfCxsVyNMbB58";
+
+/// Names of the profiles a config can extend with `"profile": "<name>"`
+pub const PROFILE_NAMES: [&str; 3] = ["light", "nft-only", "full-archive"];
+
+/// Bundled settings for a named profile. Values set in the config file take
+/// precedence over the profile, objects are merged key by key.
+pub fn profile(name: &str) -> Option<Value> {
+    match name {
+        // token ownership only, small footprint
+        "light" => Some(json!({
+            "threads": 4,
+            "batch_size": 10,
+            "accounts_selector": {
+                "owners": {
+                    TOKEN_PROGRAM_ID: [{ "handler_id": "token_account" }]
+                }
+            }
+        })),
+        // token ownership plus metadata creators and the token manager lifecycle
+        "nft-only" => Some(json!({
+            "threads": 10,
+            "batch_size": 100,
+            "accounts_selector": {
+                "owners": {
+                    TOKEN_PROGRAM_ID: [{ "handler_id": "token_account" }],
+                    METADATA_PROGRAM_ID: [{ "handler_id": "token_metadata_creators", "skip_on_startup": true }],
+                    TOKEN_MANAGER_PROGRAM_ID: [{ "handler_id": "token_manager" }, { "handler_id": "token_manager_receipt" }],
+                    TRANSFER_AUTHORITY_PROGRAM_ID: [{ "handler_id": "transfer_authority" }]
+                }
+            }
+        })),
+        // everything in nft-only plus every transaction
+        "full-archive" => Some(json!({
+            "threads": 32,
+            "batch_size": 500,
+            "flush_interval_ms": 100,
+            "accounts_selector": {
+                "owners": {
+                    TOKEN_PROGRAM_ID: [{ "handler_id": "token_account" }],
+                    METADATA_PROGRAM_ID: [{ "handler_id": "token_metadata_creators" }],
+                    TOKEN_MANAGER_PROGRAM_ID: [{ "handler_id": "token_manager" }, { "handler_id": "token_manager_receipt" }],
+                    TRANSFER_AUTHORITY_PROGRAM_ID: [{ "handler_id": "transfer_authority" }]
+                }
+            },
+            "transaction_selector": {
+                "mentions": ["*"]
+            }
+        })),
+        _ => None,
+    }
+}
+
+/// Recursively merge `overlay` on top of `base`, objects are merged and any other value replaces the base
+pub fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
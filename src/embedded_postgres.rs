@@ -0,0 +1,102 @@
+//! An ephemeral PostgreSQL server, so the integration tests run without a database
+//! provisioned at localhost:5432.
+//!
+//! The server binaries are downloaded on first use and cached. Every `EmbeddedPostgres`
+//! runs its own server on a free port with a temporary data directory, both gone once it
+//! is dropped:
+//!
+//! ```ignore
+//! let database = EmbeddedPostgres::start().unwrap();
+//! let config_file = database.config_file(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json")).unwrap();
+//! geyser_plugin.on_load(config_file.path().to_str().unwrap()).unwrap();
+//! ```
+use pg_embed::pg_enums::PgAuthMethod;
+use pg_embed::pg_errors::PgEmbedError;
+use pg_embed::pg_fetch::PgFetchSettings;
+use pg_embed::pg_fetch::PG_V13;
+use pg_embed::postgres::PgEmbed;
+use pg_embed::postgres::PgSettings;
+use postgres::Client;
+use postgres::NoTls;
+use serde_json::Value;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use std::fs::File;
+use std::net::TcpListener;
+use std::path::Path;
+use std::time::Duration;
+use tempfile::NamedTempFile;
+use tempfile::TempDir;
+use tokio::runtime::Runtime;
+
+const USER: &str = "solana";
+const PASSWORD: &str = "solana";
+
+fn custom_error<E: std::error::Error + Send + Sync + 'static>(err: E) -> GeyserPluginError {
+    GeyserPluginError::Custom(Box::new(err))
+}
+
+pub struct EmbeddedPostgres {
+    /// Stops the server when dropped
+    _server: PgEmbed,
+    _runtime: Runtime,
+    _data_dir: TempDir,
+    port: u16,
+}
+
+impl EmbeddedPostgres {
+    /// Start a server with a `solana` superuser and database, returning once it accepts connections
+    pub fn start() -> Result<Self, GeyserPluginError> {
+        let runtime = Runtime::new().map_err(custom_error)?;
+        let data_dir = tempfile::tempdir().map_err(custom_error)?;
+        // let the system pick a free port, the server binds it right after
+        let port = TcpListener::bind("127.0.0.1:0").and_then(|listener| listener.local_addr()).map_err(custom_error)?.port();
+        let settings = PgSettings {
+            database_dir: data_dir.path().join("db"),
+            port,
+            user: USER.to_string(),
+            password: PASSWORD.to_string(),
+            auth_method: PgAuthMethod::Plain,
+            persistent: false,
+            timeout: Some(Duration::from_secs(30)),
+            migration_dir: None,
+        };
+        let fetch_settings = PgFetchSettings {
+            version: PG_V13,
+            ..PgFetchSettings::default()
+        };
+        let server = runtime
+            .block_on(async {
+                let mut server = PgEmbed::new(settings, fetch_settings).await?;
+                server.setup().await?;
+                server.start_db().await?;
+                Ok::<PgEmbed, PgEmbedError>(server)
+            })
+            .map_err(custom_error)?;
+        let database = Self {
+            _server: server,
+            _runtime: runtime,
+            _data_dir: data_dir,
+            port,
+        };
+        let mut client = Client::connect(&database.connection_str_to("postgres"), NoTls).map_err(custom_error)?;
+        client.batch_execute(&format!("CREATE DATABASE {};", USER)).map_err(custom_error)?;
+        Ok(database)
+    }
+
+    fn connection_str_to(&self, dbname: &str) -> String {
+        format!("host=localhost user={} password={} port={} dbname={}", USER, PASSWORD, self.port, dbname)
+    }
+
+    pub fn connection_str(&self) -> String {
+        self.connection_str_to(USER)
+    }
+
+    /// A copy of the JSON config file at `path` connecting to this server
+    pub fn config_file<P: AsRef<Path>>(&self, path: P) -> Result<NamedTempFile, GeyserPluginError> {
+        let mut config: Value = serde_json::from_reader(File::open(path)?).map_err(custom_error)?;
+        config["connection_str"] = Value::String(self.connection_str());
+        let config_file = tempfile::Builder::new().suffix(".json").tempfile()?;
+        serde_json::to_writer(config_file.as_file(), &config).map_err(custom_error)?;
+        Ok(config_file)
+    }
+}
@@ -0,0 +1,266 @@
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoV2;
+use solana_sdk::hash::Hash;
+use solana_sdk::message::v0::LoadedAddresses;
+use solana_sdk::message::SimpleAddressLoader;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::MessageHash;
+use solana_sdk::transaction::SanitizedTransaction;
+use solana_sdk::transaction::VersionedTransaction;
+use solana_storage_proto::StoredTransactionStatusMeta;
+use solana_transaction_status::TransactionStatusMeta;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+
+pub const ACCOUNTS_FILE: &str = "accounts";
+pub const TRANSACTIONS_FILE: &str = "transactions";
+
+/// * The `fixture_recorder` section dumps the accounts and transactions matching the selectors to files.
+/// "fixture_recorder" : { "path" : "/solana/fixtures", "format" : "json", "max_records" : 10000 }
+/// Records are appended to `accounts.ndjson` and `transactions.ndjson`, or `.bin` for bincode.
+#[derive(Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FixtureRecorderConfig {
+    /// The directory the fixture files are written to
+    pub path: String,
+    pub format: FixtureFormat,
+    /// Stop recording after this many records of each kind
+    pub max_records: Option<u64>,
+}
+
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixtureFormat {
+    /// One JSON record per line
+    #[default]
+    Json,
+    /// Consecutive bincode encoded records
+    Bincode,
+}
+
+impl FixtureFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FixtureFormat::Json => "ndjson",
+            FixtureFormat::Bincode => "bin",
+        }
+    }
+
+    /// The format of a fixture file, from its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "ndjson" | "json" => Some(FixtureFormat::Json),
+            "bin" => Some(FixtureFormat::Bincode),
+            _ => None,
+        }
+    }
+}
+
+/// An account update as delivered by the validator
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedAccount {
+    pub pubkey: Vec<u8>,
+    pub lamports: u64,
+    pub owner: Vec<u8>,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    pub data: Vec<u8>,
+    pub write_version: u64,
+    pub txn_signature: Option<Signature>,
+    pub slot: u64,
+    pub is_startup: bool,
+}
+
+impl RecordedAccount {
+    pub fn new(account: &ReplicaAccountInfoV2, slot: u64, is_startup: bool) -> Self {
+        Self {
+            pubkey: account.pubkey.to_vec(),
+            lamports: account.lamports,
+            owner: account.owner.to_vec(),
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data: account.data.to_vec(),
+            write_version: account.write_version,
+            txn_signature: account.txn_signature.cloned(),
+            slot,
+            is_startup,
+        }
+    }
+
+    pub fn as_replica(&self) -> ReplicaAccountInfoV2 {
+        ReplicaAccountInfoV2 {
+            pubkey: &self.pubkey,
+            lamports: self.lamports,
+            owner: &self.owner,
+            executable: self.executable,
+            rent_epoch: self.rent_epoch,
+            data: &self.data,
+            write_version: self.write_version,
+            txn_signature: self.txn_signature.as_ref(),
+        }
+    }
+}
+
+/// A transaction as delivered by the validator, with the addresses it loaded from lookup tables
+#[derive(Serialize, Deserialize)]
+pub struct RecordedTransaction {
+    pub signature: Signature,
+    pub is_vote: bool,
+    pub index: usize,
+    pub message_hash: Hash,
+    pub transaction: VersionedTransaction,
+    pub meta: StoredTransactionStatusMeta,
+    pub loaded_addresses: LoadedAddresses,
+    pub slot: u64,
+}
+
+impl RecordedTransaction {
+    pub fn new(transaction_info: &ReplicaTransactionInfoV2, slot: u64) -> Self {
+        Self {
+            signature: *transaction_info.signature,
+            is_vote: transaction_info.is_vote,
+            index: transaction_info.index,
+            message_hash: *transaction_info.transaction.message_hash(),
+            transaction: transaction_info.transaction.to_versioned_transaction(),
+            meta: transaction_info.transaction_status_meta.clone().into(),
+            loaded_addresses: transaction_info.transaction_status_meta.loaded_addresses.clone(),
+            slot,
+        }
+    }
+
+    /// Rebuild the sanitized transaction and status meta the validator delivered
+    pub fn into_replica_parts(self) -> Result<(SanitizedTransaction, TransactionStatusMeta), String> {
+        let mut meta = TransactionStatusMeta::try_from(self.meta).map_err(|e| e.to_string())?;
+        meta.loaded_addresses = self.loaded_addresses;
+        into_replica_parts(self.transaction, self.message_hash, Some(self.is_vote), meta)
+    }
+}
+
+/// The sanitized transaction and status meta of a transaction, its lookup tables resolved to the addresses of the meta.
+/// The vote flag is computed from the message when not given
+pub fn into_replica_parts(
+    transaction: VersionedTransaction,
+    message_hash: impl Into<MessageHash>,
+    is_vote: Option<bool>,
+    meta: TransactionStatusMeta,
+) -> Result<(SanitizedTransaction, TransactionStatusMeta), String> {
+    let loaded_addresses = SimpleAddressLoader::Enabled(meta.loaded_addresses.clone());
+    let transaction = SanitizedTransaction::try_create(transaction, message_hash, is_vote, loaded_addresses, true).map_err(|e| e.to_string())?;
+    Ok((transaction, meta))
+}
+
+/// Whether the transaction loads one of `keys`, from its message or its lookup tables
+pub fn mentions_any(transaction: &VersionedTransaction, loaded_addresses: &LoadedAddresses, keys: &[Pubkey]) -> bool {
+    transaction
+        .message
+        .static_account_keys()
+        .iter()
+        .chain(&loaded_addresses.writable)
+        .chain(&loaded_addresses.readonly)
+        .any(|key| keys.contains(key))
+}
+
+struct FixtureWriter {
+    writer: BufWriter<File>,
+    records: u64,
+}
+
+impl FixtureWriter {
+    fn open(dir: &Path, name: &str, format: FixtureFormat) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(dir.join(format!("{}.{}", name, format.extension())))?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+            records: 0,
+        })
+    }
+}
+
+/// Appends the selected accounts and transactions to fixture files
+pub struct FixtureRecorder {
+    format: FixtureFormat,
+    max_records: Option<u64>,
+    accounts: FixtureWriter,
+    transactions: FixtureWriter,
+}
+
+fn recorder_err(msg: String) -> GeyserPluginError {
+    GeyserPluginError::Custom(msg.into())
+}
+
+impl FixtureRecorder {
+    pub fn new(config: &FixtureRecorderConfig) -> Result<Self, GeyserPluginError> {
+        let dir = Path::new(&config.path);
+        let open_err = |e: std::io::Error| GeyserPluginError::ConfigFileReadError {
+            msg: format!("Failed to open fixture_recorder path {}: {}", config.path, e),
+        };
+        std::fs::create_dir_all(dir).map_err(open_err)?;
+        Ok(Self {
+            format: config.format,
+            max_records: config.max_records,
+            accounts: FixtureWriter::open(dir, ACCOUNTS_FILE, config.format).map_err(open_err)?,
+            transactions: FixtureWriter::open(dir, TRANSACTIONS_FILE, config.format).map_err(open_err)?,
+        })
+    }
+
+    fn write<T: serde::Serialize>(format: FixtureFormat, max_records: Option<u64>, writer: &mut FixtureWriter, record: &T) -> Result<(), GeyserPluginError> {
+        if max_records.map_or(false, |max| writer.records >= max) {
+            return Ok(());
+        }
+        match format {
+            FixtureFormat::Json => {
+                serde_json::to_writer(&mut writer.writer, record).map_err(|e| recorder_err(e.to_string()))?;
+                writer.writer.write_all(b"\n").map_err(|e| recorder_err(e.to_string()))?;
+            }
+            FixtureFormat::Bincode => bincode::serialize_into(&mut writer.writer, record).map_err(|e| recorder_err(e.to_string()))?,
+        }
+        writer.records += 1;
+        Ok(())
+    }
+
+    pub fn record_account(&mut self, account: &ReplicaAccountInfoV2, slot: u64, is_startup: bool) -> Result<(), GeyserPluginError> {
+        Self::write(self.format, self.max_records, &mut self.accounts, &RecordedAccount::new(account, slot, is_startup))
+    }
+
+    pub fn record_transaction(&mut self, transaction_info: &ReplicaTransactionInfoV2, slot: u64) -> Result<(), GeyserPluginError> {
+        Self::write(self.format, self.max_records, &mut self.transactions, &RecordedTransaction::new(transaction_info, slot))
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.accounts.writer.flush()?;
+        self.transactions.writer.flush()
+    }
+}
+
+/// Read every record of a fixture file, the format is taken from the extension
+pub fn read_fixtures<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>, String> {
+    let format = FixtureFormat::from_path(path).ok_or_else(|| format!("Unknown fixture format {}", path.display()))?;
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut reader = BufReader::new(file);
+    let mut records = Vec::new();
+    match format {
+        FixtureFormat::Json => {
+            for line in reader.lines() {
+                let line = line.map_err(|e| e.to_string())?;
+                if !line.trim().is_empty() {
+                    records.push(serde_json::from_str(&line).map_err(|e| format!("Invalid record in {}: {}", path.display(), e))?);
+                }
+            }
+        }
+        FixtureFormat::Bincode => {
+            while !reader.fill_buf().map_err(|e| e.to_string())?.is_empty() {
+                records.push(bincode::deserialize_from(&mut reader).map_err(|e| format!("Invalid record in {}: {}", path.display(), e))?);
+            }
+        }
+    }
+    Ok(records)
+}
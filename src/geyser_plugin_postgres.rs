@@ -1,20 +1,62 @@
 use crate::accounts_selector::AccountsSelector;
 use crate::config::GeyserPluginPostgresConfig;
+use crate::fixtures::FixtureRecorder;
+#[cfg(feature = "grpc")]
+use crate::grpc::GrpcAccount;
+#[cfg(feature = "grpc")]
+use crate::grpc::GrpcServer;
+#[cfg(feature = "grpc")]
+use crate::grpc::GrpcTransaction;
+#[cfg(feature = "grpc")]
+use crate::grpc::GrpcUpdate;
+use crate::memory_stats::MemoryStats;
+use crate::metrics::configure_reporting;
+use crate::metrics::registry;
+use crate::metrics::report_interval_ms;
+use crate::metrics::solana_metrics_enabled;
+use crate::metrics::MetricsServer;
+use crate::metrics::UPDATE_ACCOUNT_US;
 use crate::parallel_client::ParallelClient;
+use crate::postgres_client::close_detection::is_reassigned_close;
+use crate::postgres_client::epoch_handler::EpochTracker;
+use crate::postgres_client::fault_injection;
+use crate::postgres_client::heartbeat_handler::record_tip_slot;
+use crate::postgres_client::heartbeat_handler::Heartbeat;
+use crate::postgres_client::plugin_stats_handler::PluginStats;
+use crate::postgres_client::retention::Retention;
+use crate::postgres_client::token_holder_views::TokenHolderViews;
+use crate::postgres_client::AccountHandler;
+use crate::postgres_client::CustomHandlers;
+use crate::postgres_client::CustomTransactionHandler;
 use crate::postgres_client::PostgresClientBuilder;
+use crate::selector_reload;
+use crate::selector_reload::SelectorReload;
+use crate::selector_stats::SelectorStats;
+use crate::selector_stats::ACCOUNTS_SELECTOR;
+use crate::selector_stats::TRANSACTION_SELECTOR;
+use crate::traces;
+use crate::traces::callback_span;
 use crate::transaction_selector::TransactionSelector;
 use bs58;
 use log::*;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfo;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaBlockInfoVersions;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfo;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoV2;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoVersions;
 use solana_geyser_plugin_interface::geyser_plugin_interface::Result;
 use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
 use solana_measure::measure::Measure;
 use solana_metrics::*;
+use solana_sdk::timing::AtomicInterval;
+use std::sync::Arc;
 use thiserror::Error;
+use tracing::field;
+use tracing::Span;
 
 #[derive(Default)]
 pub struct GeyserPluginPostgres {
@@ -22,7 +64,23 @@ pub struct GeyserPluginPostgres {
     client: Option<ParallelClient>,
     accounts_selector: Option<AccountsSelector>,
     transaction_selector: Option<TransactionSelector>,
+    selector_reload: Option<SelectorReload>,
     batch_starting_slot: Option<u64>,
+    selector_stats: SelectorStats,
+    last_selector_stats_report: AtomicInterval,
+    custom_handlers: CustomHandlers,
+    fixture_recorder: Option<FixtureRecorder>,
+    metrics_server: Option<MetricsServer>,
+    #[cfg(feature = "grpc")]
+    grpc_server: Option<GrpcServer>,
+    heartbeat: Option<Heartbeat>,
+    epochs: Option<EpochTracker>,
+    token_holder_views: Option<TokenHolderViews>,
+    retention: Option<Retention>,
+    plugin_stats: Option<PluginStats>,
+    memory_stats: Option<MemoryStats>,
+    /// Forward the closed accounts the selectors skip, with `close_detection`
+    detect_closes: bool,
 }
 
 impl std::fmt::Debug for GeyserPluginPostgres {
@@ -35,6 +93,59 @@ impl GeyserPluginPostgres {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Block until the workers have received every queued request, so unloading doesn't drop any
+    pub fn wait_for_empty_queue(&self) {
+        if let Some(client) = &self.client {
+            client.wait_for_empty_queue();
+        }
+    }
+
+    /// Swap in the selectors of the config file when it changed, the workers pick them up from their next request
+    fn reload_selectors(&mut self) {
+        let (selector_reload, config) = match (&mut self.selector_reload, &self.config) {
+            (Some(selector_reload), Some(config)) => (selector_reload, config),
+            _ => return,
+        };
+        let reloaded = selector_reload.poll(config, &self.custom_handlers.account_handler_ids(), &self.custom_handlers.transaction_handler_ids());
+        if let Some(config) = reloaded {
+            self.accounts_selector = config.accounts_selector.as_ref().map(AccountsSelector::new);
+            self.transaction_selector = config.transaction_selector.as_ref().map(TransactionSelector::new);
+            selector_reload::publish(config.accounts_selector.clone(), config.transaction_selector.clone());
+            self.config = Some(config);
+        }
+    }
+}
+
+/// Builds the plugin with additional handlers, for crates that link this one as a library
+/// and export their own `_create_plugin`. Registered ids can be used in the selectors like
+/// the built in ones.
+#[derive(Default)]
+pub struct GeyserPluginPostgresBuilder {
+    custom_handlers: CustomHandlers,
+}
+
+impl GeyserPluginPostgresBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_account_handler(mut self, handler_id: &str, handler: Box<dyn AccountHandler + Send + Sync>) -> Self {
+        self.custom_handlers.account_handlers.insert(handler_id.to_string(), Arc::from(handler));
+        self
+    }
+
+    pub fn with_transaction_handler(mut self, handler_id: &str, handler: Box<dyn CustomTransactionHandler + Send + Sync>) -> Self {
+        self.custom_handlers.transaction_handlers.insert(handler_id.to_string(), Arc::from(handler));
+        self
+    }
+
+    pub fn build(self) -> GeyserPluginPostgres {
+        GeyserPluginPostgres {
+            custom_handlers: self.custom_handlers,
+            ..GeyserPluginPostgres::default()
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -43,6 +154,33 @@ pub enum GeyserPluginPostgresError {
     ConnectionError { msg: String },
     #[error("Error preparing data store schema. Error message: ({msg})")]
     DataSchemaError { msg: String },
+    #[error("Error publishing to an event sink. Error message: ({msg})")]
+    SinkError { msg: String },
+}
+
+/// An account of a validator delivering `V0_0_1`, which doesn't report the transaction that wrote it
+fn account_info_v2<'a>(account: &ReplicaAccountInfo<'a>) -> ReplicaAccountInfoV2<'a> {
+    ReplicaAccountInfoV2 {
+        pubkey: account.pubkey,
+        lamports: account.lamports,
+        owner: account.owner,
+        executable: account.executable,
+        rent_epoch: account.rent_epoch,
+        data: account.data,
+        write_version: account.write_version,
+        txn_signature: None,
+    }
+}
+
+/// A transaction of a validator delivering `V0_0_1`, which doesn't report its index in the block, stored as 0
+fn transaction_info_v2<'a>(transaction_info: &ReplicaTransactionInfo<'a>) -> ReplicaTransactionInfoV2<'a> {
+    ReplicaTransactionInfoV2 {
+        signature: transaction_info.signature,
+        is_vote: transaction_info.is_vote,
+        transaction: transaction_info.transaction,
+        transaction_status_meta: transaction_info.transaction_status_meta,
+        index: 0,
+    }
 }
 
 fn client_err() -> Result<()> {
@@ -51,6 +189,29 @@ fn client_err() -> Result<()> {
     })))
 }
 
+/// Periodically report the selector hits and misses as datapoints and to the `selector_stats` table
+fn report_selector_stats(client: &mut ParallelClient, selector_stats: &mut SelectorStats, last_report: &AtomicInterval) -> Result<()> {
+    if !last_report.should_update(report_interval_ms()) {
+        return Ok(());
+    }
+    let stats = selector_stats.drain();
+    if stats.is_empty() {
+        return Ok(());
+    }
+    if solana_metrics_enabled() {
+        for stat in &stats {
+            datapoint_info!(
+                "geyser-plugin-postgres-selector-stats",
+                ("selector", stat.selector, String),
+                ("entry", stat.entry, String),
+                ("selected", stat.selected, i64),
+                ("skipped", stat.skipped, i64),
+            );
+        }
+    }
+    client.update_selector_stats(stats)
+}
+
 impl GeyserPlugin for GeyserPluginPostgres {
     fn name(&self) -> &'static str {
         "GeyserPluginPostgres"
@@ -60,11 +221,34 @@ impl GeyserPlugin for GeyserPluginPostgres {
         solana_logger::setup_with_default("info");
         info!("[on_load] name=[{:?}] config_file=[{:?}]", self.name(), config_file);
         let config = GeyserPluginPostgresConfig::read_from(config_file)?;
-        let (client, batch_starting_slot) = PostgresClientBuilder::build_pararallel_postgres_client(&config)?;
+        config.validate_with_custom_handlers(&self.custom_handlers.account_handler_ids(), &self.custom_handlers.transaction_handler_ids())?;
+        configure_reporting(&config.reporting);
+        traces::init(&config.tracing);
+        fault_injection::init(&config.fault_injection);
+        let (client, batch_starting_slot) = PostgresClientBuilder::build_pararallel_postgres_client(&config, &self.custom_handlers)?;
         self.client = Some(client);
         self.batch_starting_slot = batch_starting_slot;
         self.accounts_selector = config.accounts_selector.as_ref().map(AccountsSelector::new);
         self.transaction_selector = config.transaction_selector.as_ref().map(TransactionSelector::new);
+        self.selector_reload = SelectorReload::new(config_file, &config);
+        if let Some(fixture_recorder) = &config.fixture_recorder {
+            info!("[on_load] recording fixtures path=[{}]", fixture_recorder.path);
+            self.fixture_recorder = Some(FixtureRecorder::new(fixture_recorder)?);
+        }
+        if let Some(metrics) = &config.metrics {
+            self.metrics_server = Some(MetricsServer::start(metrics)?);
+        }
+        #[cfg(feature = "grpc")]
+        if let Some(grpc) = &config.grpc {
+            self.grpc_server = Some(GrpcServer::start(grpc)?);
+        }
+        self.heartbeat = Heartbeat::start(&config)?;
+        self.epochs = EpochTracker::start(&config)?;
+        self.token_holder_views = TokenHolderViews::start(&config)?;
+        self.retention = Retention::start(&config)?;
+        self.plugin_stats = PluginStats::start(&config)?;
+        self.memory_stats = MemoryStats::start(&config.memory_stats);
+        self.detect_closes = config.close_detection.is_some();
         self.config = Some(config);
         Ok(())
     }
@@ -77,6 +261,36 @@ impl GeyserPlugin for GeyserPluginPostgres {
                 client.join().unwrap();
             }
         }
+        if let Some(fixture_recorder) = &mut self.fixture_recorder {
+            if let Err(err) = fixture_recorder.flush() {
+                error!("[on_unload] failed to flush fixtures error=[{}]", err);
+            }
+        }
+        if let Some(metrics_server) = &mut self.metrics_server {
+            metrics_server.stop();
+        }
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_server) = &mut self.grpc_server {
+            grpc_server.stop();
+        }
+        if let Some(heartbeat) = &mut self.heartbeat {
+            heartbeat.stop();
+        }
+        if let Some(epochs) = &mut self.epochs {
+            epochs.stop();
+        }
+        if let Some(token_holder_views) = &mut self.token_holder_views {
+            token_holder_views.stop();
+        }
+        if let Some(retention) = &mut self.retention {
+            retention.stop();
+        }
+        if let Some(plugin_stats) = &mut self.plugin_stats {
+            plugin_stats.stop();
+        }
+        if let Some(memory_stats) = &mut self.memory_stats {
+            memory_stats.stop();
+        }
     }
 
     fn update_account(&mut self, account: ReplicaAccountInfoVersions, slot: u64, is_startup: bool) -> Result<()> {
@@ -89,53 +303,100 @@ impl GeyserPlugin for GeyserPluginPostgres {
             Some(client) => client,
             None => return client_err(),
         };
+        report_selector_stats(client, &mut self.selector_stats, &self.last_selector_stats_report)?;
 
+        let span = callback_span!("update_account", slot, is_startup, pubkey = field::Empty).entered();
         let mut measure_all = Measure::start("geyser-plugin-postgres-update-account-main");
-        match account {
-            ReplicaAccountInfoVersions::V0_0_2(account) => {
-                let mut measure_select = Measure::start("geyser-plugin-postgres-update-account-select");
-                if let Some(accounts_selector) = &self.accounts_selector {
-                    if !accounts_selector.is_account_selected(account.pubkey, account.owner) {
-                        return Ok(());
-                    }
-                } else {
+        let converted;
+        let account = match account {
+            ReplicaAccountInfoVersions::V0_0_1(account) => {
+                converted = account_info_v2(account);
+                &converted
+            }
+            ReplicaAccountInfoVersions::V0_0_2(account) => account,
+        };
+        if !span.is_disabled() {
+            span.record("pubkey", &field::display(bs58::encode(account.pubkey).into_string()));
+        }
+        let mut measure_select = Measure::start("geyser-plugin-postgres-update-account-select");
+        let close_only = match &self.accounts_selector {
+            Some(accounts_selector) => match accounts_selector.select_account(account.pubkey, account.owner) {
+                Some((selector, entry)) => {
+                    self.selector_stats.record_selected(selector, entry);
+                    false
+                }
+                // the system program reassigns the accounts it closes, away from the program their rows were selected for
+                None if !is_startup && self.detect_closes && is_reassigned_close(account.lamports, account.owner) => true,
+                None => {
+                    self.selector_stats.record_skipped(ACCOUNTS_SELECTOR);
                     return Ok(());
                 }
-                measure_select.stop();
-                inc_new_counter_debug!("geyser-plugin-postgres-update-account-select-us", measure_select.as_us() as usize, 100000, 100000);
+            },
+            None => return Ok(()),
+        };
+        measure_select.stop();
+        registry().observe_us(UPDATE_ACCOUNT_US, &[("stage", "select")], measure_select.as_us());
 
-                debug!(
-                    "[update_account][ingest] pubkey=[{:?}] owner=[{:?}] slot=[{:?}]",
-                    bs58::encode(account.pubkey).into_string(),
-                    bs58::encode(account.owner).into_string(),
-                    slot,
-                );
+        debug!(
+            "[update_account][ingest] pubkey=[{:?}] owner=[{:?}] slot=[{:?}]",
+            bs58::encode(account.pubkey).into_string(),
+            bs58::encode(account.owner).into_string(),
+            slot,
+        );
 
-                let mut measure_update = Measure::start("geyser-plugin-postgres-update-account-client");
-                let result = client.update_account(account, slot, is_startup);
-                measure_update.stop();
+        if let Some(fixture_recorder) = &mut self.fixture_recorder {
+            fixture_recorder.record_account(account, slot, is_startup)?;
+        }
+        // the gRPC subscribers only get the accounts the selectors picked
+        if close_only {
+            let result = client.close_account(account, slot);
+            measure_all.stop();
+            registry().observe_us(UPDATE_ACCOUNT_US, &[("stage", "main")], measure_all.as_us());
+            return result.map_err(|err| GeyserPluginError::AccountsUpdateError {
+                msg: format!("Failed to persist the close of account to the PostgreSQL database. Error: {:?}", err),
+            });
+        }
 
-                inc_new_counter_debug!("geyser-plugin-postgres-update-account-client-us", measure_update.as_us() as usize, 100000, 100000);
-                if let Err(err) = result {
-                    return Err(GeyserPluginError::AccountsUpdateError {
-                        msg: format!("Failed to persist the update of account to the PostgreSQL database. Error: {:?}", err),
-                    });
-                }
-            }
-            _ => {
-                return Err(GeyserPluginError::AccountsUpdateError {
-                    msg: "Failed to update account. Unsupported format.".to_string(),
-                });
-            }
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_server) = self.grpc_server.as_ref().filter(|grpc_server| grpc_server.has_subscribers()) {
+            grpc_server.publish(GrpcUpdate::Account(GrpcAccount::new(account, slot, is_startup)));
+        }
+
+        let mut measure_update = Measure::start("geyser-plugin-postgres-update-account-client");
+        let result = client.update_account(account, slot, is_startup);
+        measure_update.stop();
+
+        registry().observe_us(UPDATE_ACCOUNT_US, &[("stage", "client")], measure_update.as_us());
+        if let Err(err) = result {
+            return Err(GeyserPluginError::AccountsUpdateError {
+                msg: format!("Failed to persist the update of account to the PostgreSQL database. Error: {:?}", err),
+            });
         }
 
         measure_all.stop();
-        inc_new_counter_debug!("geyser-plugin-postgres-update-account-main-us", measure_all.as_us() as usize, 100000, 100000);
+        registry().observe_us(UPDATE_ACCOUNT_US, &[("stage", "main")], measure_all.as_us());
         Ok(())
     }
 
     fn update_slot_status(&mut self, slot: u64, parent: Option<u64>, status: SlotStatus) -> Result<()> {
         debug!("[update_slot_status] slot=[{:?}] status=[{:?}]", slot, status);
+        let _span = callback_span!("update_slot_status", slot, status = ?status).entered();
+        record_tip_slot(slot);
+        self.reload_selectors();
+        if let (Some(epochs), SlotStatus::Rooted) = (&mut self.epochs, status) {
+            epochs.record_rooted_slot(slot);
+        }
+        if let (Some(token_holder_views), SlotStatus::Rooted) = (&mut self.token_holder_views, status) {
+            token_holder_views.record_rooted_slot(slot);
+        }
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_server) = self.grpc_server.as_ref().filter(|grpc_server| grpc_server.has_subscribers()) {
+            grpc_server.publish(GrpcUpdate::Slot {
+                slot,
+                parent,
+                status: status.as_str(),
+            });
+        }
         let client = match &mut self.client {
             Some(client) => client,
             None => return client_err(),
@@ -150,6 +411,10 @@ impl GeyserPlugin for GeyserPluginPostgres {
 
     fn notify_end_of_startup(&mut self) -> Result<()> {
         info!("[notify_end_of_startup]");
+        let _span = match traces::tracing_enabled() {
+            true => tracing::debug_span!("notify_end_of_startup").entered(),
+            false => Span::none().entered(),
+        };
         let client = match &mut self.client {
             Some(client) => client,
             None => return client_err(),
@@ -170,30 +435,47 @@ impl GeyserPlugin for GeyserPluginPostgres {
             Some(client) => client,
             None => return client_err(),
         };
+        report_selector_stats(client, &mut self.selector_stats, &self.last_selector_stats_report)?;
 
-        match transaction_info {
-            ReplicaTransactionInfoVersions::V0_0_2(transaction_info) => {
-                if let Some(transaction_selector) = &self.transaction_selector {
-                    if !transaction_selector.is_transaction_selected(transaction_info.is_vote, Box::new(transaction_info.transaction.message().account_keys().iter())) {
-                        return Ok(());
-                    }
-                } else {
+        let span = callback_span!("notify_transaction", slot, signature = field::Empty).entered();
+        let converted;
+        let transaction_info = match transaction_info {
+            ReplicaTransactionInfoVersions::V0_0_1(transaction_info) => {
+                converted = transaction_info_v2(transaction_info);
+                &converted
+            }
+            ReplicaTransactionInfoVersions::V0_0_2(transaction_info) => transaction_info,
+        };
+        if !span.is_disabled() {
+            span.record("signature", &field::display(transaction_info.signature));
+        }
+        if let Some(transaction_selector) = &self.transaction_selector {
+            match transaction_selector.select_transaction(transaction_info.is_vote, Box::new(transaction_info.transaction.message().account_keys().iter())) {
+                Some(entry) => self.selector_stats.record_selected("mentions", entry),
+                None => {
+                    self.selector_stats.record_skipped(TRANSACTION_SELECTOR);
                     return Ok(());
                 }
+            }
+        } else {
+            return Ok(());
+        }
 
-                let result = client.log_transaction_info(transaction_info, slot);
+        if let Some(fixture_recorder) = &mut self.fixture_recorder {
+            fixture_recorder.record_transaction(transaction_info, slot)?;
+        }
 
-                if let Err(err) = result {
-                    return Err(GeyserPluginError::SlotStatusUpdateError {
-                        msg: format!("Failed to persist the transaction info to the PostgreSQL database. Error: {:?}", err),
-                    });
-                }
-            }
-            _ => {
-                return Err(GeyserPluginError::SlotStatusUpdateError {
-                    msg: "Failed to persist the transaction info to the PostgreSQL database. Unsupported format.".to_string(),
-                });
-            }
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_server) = self.grpc_server.as_ref().filter(|grpc_server| grpc_server.has_subscribers()) {
+            grpc_server.publish(GrpcUpdate::Transaction(GrpcTransaction::new(transaction_info, slot)));
+        }
+
+        let result = client.log_transaction_info(transaction_info, slot);
+
+        if let Err(err) = result {
+            return Err(GeyserPluginError::SlotStatusUpdateError {
+                msg: format!("Failed to persist the transaction info to the PostgreSQL database. Error: {:?}", err),
+            });
         }
 
         Ok(())
@@ -207,6 +489,7 @@ impl GeyserPlugin for GeyserPluginPostgres {
         };
         match block_info {
             ReplicaBlockInfoVersions::V0_0_1(block_info) => {
+                let _span = callback_span!("notify_block_metadata", slot = block_info.slot).entered();
                 let result = client.update_block_metadata(block_info);
 
                 if let Err(err) = result {
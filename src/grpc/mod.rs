@@ -0,0 +1,309 @@
+//! Streams the account, transaction and slot updates to gRPC subscribers.
+//!
+//! The plugin callbacks publish the updates the selectors picked to a broadcast channel, next to
+//! the requests of the PostgreSQL workers, and each subscription forwards those matching its
+//! filter. The service is defined in `proto/geyser.proto`.
+#[cfg(feature = "grpc")]
+mod server;
+
+#[cfg(feature = "grpc")]
+pub use server::proto;
+#[cfg(feature = "grpc")]
+pub use server::GrpcServer;
+
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoV2;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+/// * The `grpc` section streams the updates to the subscribers of a gRPC server, requires the `grpc` feature.
+/// "grpc" : { "bind_address" : "0.0.0.0:10000", "channel_capacity" : 100000, "max_subscribers" : 64 }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GrpcConfig {
+    /// The address the server listens on
+    pub bind_address: String,
+    /// Updates a subscriber can lag behind before its stream is closed
+    pub channel_capacity: usize,
+    /// Subscriptions served at once, further ones are refused with RESOURCE_EXHAUSTED
+    pub max_subscribers: usize,
+    /// Threads of the server's runtime
+    pub worker_threads: usize,
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: "0.0.0.0:10000".to_string(),
+            channel_capacity: 100_000,
+            max_subscribers: 64,
+            worker_threads: 2,
+        }
+    }
+}
+
+impl GrpcConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if cfg!(not(feature = "grpc")) {
+            return Err("\"grpc\" requires the plugin to be built with the \"grpc\" feature".to_string());
+        }
+        if SocketAddr::from_str(&self.bind_address).is_err() {
+            return Err(format!("grpc \"bind_address\" {} must be an ip:port address", self.bind_address));
+        }
+        if self.channel_capacity == 0 {
+            return Err("grpc \"channel_capacity\" must be greater than 0".to_string());
+        }
+        if self.max_subscribers == 0 {
+            return Err("grpc \"max_subscribers\" must be greater than 0".to_string());
+        }
+        if self.worker_threads == 0 {
+            return Err("grpc \"worker_threads\" must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GrpcAccount {
+    pub pubkey: Vec<u8>,
+    pub owner: Vec<u8>,
+    pub lamports: u64,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    pub data: Vec<u8>,
+    pub write_version: u64,
+    pub slot: u64,
+    pub txn_signature: Option<Vec<u8>>,
+    pub is_startup: bool,
+}
+
+impl GrpcAccount {
+    pub fn new(account: &ReplicaAccountInfoV2, slot: u64, is_startup: bool) -> Self {
+        Self {
+            pubkey: account.pubkey.to_vec(),
+            owner: account.owner.to_vec(),
+            lamports: account.lamports,
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data: account.data.to_vec(),
+            write_version: account.write_version,
+            slot,
+            txn_signature: account.txn_signature.map(|signature| signature.as_ref().to_vec()),
+            is_startup,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GrpcTransaction {
+    pub signature: Vec<u8>,
+    pub slot: u64,
+    pub index: u64,
+    pub is_vote: bool,
+    pub error: Option<String>,
+    pub fee: u64,
+    pub compute_units_consumed: Option<u64>,
+    /// The keys of the message followed by the loaded addresses
+    pub account_keys: Vec<Vec<u8>>,
+    pub log_messages: Vec<String>,
+    /// The bincode serialized `VersionedTransaction`
+    pub transaction: Vec<u8>,
+}
+
+impl GrpcTransaction {
+    pub fn new(transaction_info: &ReplicaTransactionInfoV2, slot: u64) -> Self {
+        let meta = transaction_info.transaction_status_meta;
+        Self {
+            signature: transaction_info.signature.as_ref().to_vec(),
+            slot,
+            index: transaction_info.index as u64,
+            is_vote: transaction_info.is_vote,
+            error: meta.status.as_ref().err().map(|err| err.to_string()),
+            fee: meta.fee,
+            compute_units_consumed: meta.compute_units_consumed,
+            account_keys: transaction_info.transaction.message().account_keys().iter().map(|key| key.to_bytes().to_vec()).collect(),
+            log_messages: meta.log_messages.clone().unwrap_or_default(),
+            transaction: bincode::serialize(&transaction_info.transaction.to_versioned_transaction()).unwrap_or_default(),
+        }
+    }
+}
+
+/// An update published to the subscribers
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GrpcUpdate {
+    Account(GrpcAccount),
+    Slot { slot: u64, parent: Option<u64>, status: &'static str },
+    Transaction(GrpcTransaction),
+}
+
+fn parse_pubkeys(keys: &[String]) -> Result<HashSet<Vec<u8>>, String> {
+    keys.iter()
+        .map(|key| Pubkey::from_str(key).map(|key| key.to_bytes().to_vec()).map_err(|_| format!("\"{}\" is not a valid pubkey", key)))
+        .collect()
+}
+
+/// The account updates of a subscription
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccountFilter {
+    pubkeys: HashSet<Vec<u8>>,
+    owners: HashSet<Vec<u8>>,
+    include_startup: bool,
+}
+
+impl AccountFilter {
+    pub fn new(pubkeys: &[String], owners: &[String], include_startup: bool) -> Result<Self, String> {
+        Ok(Self {
+            pubkeys: parse_pubkeys(pubkeys)?,
+            owners: parse_pubkeys(owners)?,
+            include_startup,
+        })
+    }
+
+    fn matches(&self, account: &GrpcAccount) -> bool {
+        if account.is_startup && !self.include_startup {
+            return false;
+        }
+        (self.pubkeys.is_empty() && self.owners.is_empty()) || self.pubkeys.contains(&account.pubkey) || self.owners.contains(&account.owner)
+    }
+}
+
+/// The transactions of a subscription
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TransactionFilter {
+    mentions: HashSet<Vec<u8>>,
+    include_votes: bool,
+    include_failed: bool,
+}
+
+impl TransactionFilter {
+    pub fn new(mentions: &[String], include_votes: bool, include_failed: bool) -> Result<Self, String> {
+        Ok(Self {
+            mentions: parse_pubkeys(mentions)?,
+            include_votes,
+            include_failed,
+        })
+    }
+
+    fn matches(&self, transaction: &GrpcTransaction) -> bool {
+        if (transaction.is_vote && !self.include_votes) || (transaction.error.is_some() && !self.include_failed) {
+            return false;
+        }
+        self.mentions.is_empty() || transaction.account_keys.iter().any(|key| self.mentions.contains(key))
+    }
+}
+
+/// The updates a subscriber asked for, nothing is streamed by default
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubscriptionFilter {
+    pub accounts: Option<AccountFilter>,
+    pub transactions: Option<TransactionFilter>,
+    pub slots: bool,
+}
+
+impl SubscriptionFilter {
+    pub fn matches(&self, update: &GrpcUpdate) -> bool {
+        match update {
+            GrpcUpdate::Account(account) => self.accounts.as_ref().map_or(false, |filter| filter.matches(account)),
+            GrpcUpdate::Slot { .. } => self.slots,
+            GrpcUpdate::Transaction(transaction) => self.transactions.as_ref().map_or(false, |filter| filter.matches(transaction)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(pubkey: &Pubkey, owner: &Pubkey, is_startup: bool) -> GrpcUpdate {
+        GrpcUpdate::Account(GrpcAccount {
+            pubkey: pubkey.to_bytes().to_vec(),
+            owner: owner.to_bytes().to_vec(),
+            lamports: 1,
+            executable: false,
+            rent_epoch: 0,
+            data: vec![1, 2, 3],
+            write_version: 1,
+            slot: 5,
+            txn_signature: None,
+            is_startup,
+        })
+    }
+
+    fn transaction(account_keys: &[Pubkey], is_vote: bool, error: Option<String>) -> GrpcUpdate {
+        GrpcUpdate::Transaction(GrpcTransaction {
+            signature: vec![1; 64],
+            slot: 5,
+            index: 0,
+            is_vote,
+            error,
+            fee: 5000,
+            compute_units_consumed: None,
+            account_keys: account_keys.iter().map(|key| key.to_bytes().to_vec()).collect(),
+            log_messages: Vec::new(),
+            transaction: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn test_subscription_filter() {
+        let (pubkey, owner, other) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let slot = GrpcUpdate::Slot {
+            slot: 5,
+            parent: Some(4),
+            status: "rooted",
+        };
+        let nothing = SubscriptionFilter::default();
+        assert!(!nothing.matches(&account(&pubkey, &owner, false)));
+        assert!(!nothing.matches(&slot));
+        assert!(!nothing.matches(&transaction(&[pubkey], false, None)));
+
+        let filter = SubscriptionFilter {
+            accounts: Some(AccountFilter::new(&[], &[owner.to_string()], false).unwrap()),
+            transactions: Some(TransactionFilter::new(&[pubkey.to_string()], false, false).unwrap()),
+            slots: true,
+        };
+        assert!(filter.matches(&account(&pubkey, &owner, false)));
+        assert!(!filter.matches(&account(&pubkey, &owner, true)));
+        assert!(!filter.matches(&account(&pubkey, &other, false)));
+        assert!(filter.matches(&slot));
+        assert!(filter.matches(&transaction(&[other, pubkey], false, None)));
+        assert!(!filter.matches(&transaction(&[other], false, None)));
+        assert!(!filter.matches(&transaction(&[pubkey], true, None)));
+        assert!(!filter.matches(&transaction(&[pubkey], false, Some("InsufficientFundsForFee".to_string()))));
+
+        let everything = SubscriptionFilter {
+            accounts: Some(AccountFilter::new(&[], &[], true).unwrap()),
+            transactions: Some(TransactionFilter::new(&[], true, true).unwrap()),
+            slots: false,
+        };
+        assert!(everything.matches(&account(&other, &other, true)));
+        assert!(everything.matches(&transaction(&[other], true, Some("AccountInUse".to_string()))));
+        assert!(!everything.matches(&slot));
+
+        assert!(AccountFilter::new(&["not a pubkey".to_string()], &[], false).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = GrpcConfig::default();
+        assert_eq!(config.validate().is_ok(), cfg!(feature = "grpc"));
+        if cfg!(feature = "grpc") {
+            assert!(GrpcConfig {
+                bind_address: "localhost".to_string(),
+                ..GrpcConfig::default()
+            }
+            .validate()
+            .is_err());
+            assert!(GrpcConfig {
+                channel_capacity: 0,
+                ..GrpcConfig::default()
+            }
+            .validate()
+            .is_err());
+        }
+    }
+}
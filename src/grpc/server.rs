@@ -0,0 +1,294 @@
+use super::AccountFilter;
+use super::GrpcConfig;
+use super::GrpcUpdate;
+use super::SubscriptionFilter;
+use super::TransactionFilter;
+use crate::metrics::registry;
+use crate::metrics::GRPC_LAGGED_TOTAL;
+use crate::metrics::GRPC_SUBSCRIBERS;
+use log::*;
+use proto::geyser_server::Geyser;
+use proto::geyser_server::GeyserServer;
+use proto::subscribe_update::Update;
+use proto::AccountUpdate;
+use proto::SlotUpdate;
+use proto::SubscribeRequest;
+use proto::SubscribeUpdate;
+use proto::TransactionUpdate;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use std::pin::Pin;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::runtime::Builder;
+use tokio::runtime::Runtime;
+use tokio::sync::broadcast;
+use tokio::sync::oneshot;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::TcpListenerStream;
+use tokio_stream::Stream;
+use tokio_stream::StreamExt;
+use tonic::transport::Server;
+use tonic::Request;
+use tonic::Response;
+use tonic::Status;
+
+pub mod proto {
+    tonic::include_proto!("geyser");
+}
+
+/// How long the open subscriptions are given to close when the server stops
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn subscription_filter(request: SubscribeRequest) -> Result<SubscriptionFilter, String> {
+    Ok(SubscriptionFilter {
+        accounts: request
+            .accounts
+            .map(|accounts| AccountFilter::new(&accounts.pubkeys, &accounts.owners, accounts.include_startup))
+            .transpose()?,
+        transactions: request
+            .transactions
+            .map(|transactions| TransactionFilter::new(&transactions.mentions, transactions.include_votes, transactions.include_failed))
+            .transpose()?,
+        slots: request.slots,
+    })
+}
+
+fn subscribe_update(update: &GrpcUpdate) -> SubscribeUpdate {
+    let update = match update {
+        GrpcUpdate::Account(account) => Update::Account(AccountUpdate {
+            pubkey: account.pubkey.clone(),
+            owner: account.owner.clone(),
+            lamports: account.lamports,
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data: account.data.clone(),
+            write_version: account.write_version,
+            slot: account.slot,
+            txn_signature: account.txn_signature.clone(),
+            is_startup: account.is_startup,
+        }),
+        GrpcUpdate::Slot { slot, parent, status } => Update::Slot(SlotUpdate {
+            slot: *slot,
+            parent: *parent,
+            status: status.to_string(),
+        }),
+        GrpcUpdate::Transaction(transaction) => Update::Transaction(TransactionUpdate {
+            signature: transaction.signature.clone(),
+            slot: transaction.slot,
+            index: transaction.index,
+            is_vote: transaction.is_vote,
+            success: transaction.error.is_none(),
+            error: transaction.error.clone(),
+            fee: transaction.fee,
+            compute_units_consumed: transaction.compute_units_consumed,
+            account_keys: transaction.account_keys.clone(),
+            log_messages: transaction.log_messages.clone(),
+            transaction: transaction.transaction.clone(),
+        }),
+    };
+    SubscribeUpdate { update: Some(update) }
+}
+
+/// Counts a subscription until its stream is dropped
+struct Subscriber(Arc<AtomicUsize>);
+
+impl Subscriber {
+    fn new(subscribers: &Arc<AtomicUsize>) -> (Self, usize) {
+        let count = subscribers.fetch_add(1, Ordering::SeqCst) + 1;
+        registry().set_gauge(GRPC_SUBSCRIBERS, &[], count as i64);
+        (Self(subscribers.clone()), count)
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        let count = self.0.fetch_sub(1, Ordering::SeqCst) - 1;
+        registry().set_gauge(GRPC_SUBSCRIBERS, &[], count as i64);
+    }
+}
+
+struct GeyserService {
+    sender: broadcast::Sender<Arc<GrpcUpdate>>,
+    subscribers: Arc<AtomicUsize>,
+    max_subscribers: usize,
+}
+
+#[tonic::async_trait]
+impl Geyser for GeyserService {
+    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<SubscribeUpdate, Status>> + Send>>;
+
+    async fn subscribe(&self, request: Request<SubscribeRequest>) -> Result<Response<Self::SubscribeStream>, Status> {
+        let filter = subscription_filter(request.into_inner()).map_err(Status::invalid_argument)?;
+        let (subscriber, count) = Subscriber::new(&self.subscribers);
+        if count > self.max_subscribers {
+            return Err(Status::resource_exhausted(format!("the server serves {} subscribers at most", self.max_subscribers)));
+        }
+        info!("[grpc] subscribed subscribers=[{}] filter=[{:?}]", count, filter);
+        // tonic ends the stream with the status of its first error
+        let stream = BroadcastStream::new(self.sender.subscribe()).filter_map(move |update| {
+            let _subscriber = &subscriber;
+            match update {
+                Ok(update) => filter.matches(&update).then(|| Ok(subscribe_update(&update))),
+                Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                    registry().inc_counter(GRPC_LAGGED_TOTAL, &[], 1);
+                    warn!("[grpc] closing a subscription lagging skipped=[{}]", skipped);
+                    Some(Err(Status::data_loss(format!("the subscription lagged {} updates behind", skipped))))
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+/// Serves the `Geyser` service from a runtime of its own, streaming the published updates to its subscribers
+pub struct GrpcServer {
+    sender: broadcast::Sender<Arc<GrpcUpdate>>,
+    shutdown: Option<oneshot::Sender<()>>,
+    runtime: Option<Runtime>,
+}
+
+impl GrpcServer {
+    pub fn start(config: &GrpcConfig) -> Result<Self, GeyserPluginError> {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(config.worker_threads)
+            .thread_name("grpc")
+            .enable_all()
+            .build()
+            .map_err(|e| GeyserPluginError::ConfigFileReadError {
+                msg: format!("Failed to start the grpc runtime: {}", e),
+            })?;
+        let listener = runtime.block_on(TcpListener::bind(&config.bind_address)).map_err(|e| GeyserPluginError::ConfigFileReadError {
+            msg: format!("Failed to bind grpc bind_address {}: {}", config.bind_address, e),
+        })?;
+        let (sender, _) = broadcast::channel(config.channel_capacity);
+        let service = GeyserService {
+            sender: sender.clone(),
+            subscribers: Arc::default(),
+            max_subscribers: config.max_subscribers,
+        };
+        let (shutdown, shutdown_receiver) = oneshot::channel::<()>();
+        runtime.spawn(async move {
+            let result = Server::builder()
+                .add_service(GeyserServer::new(service))
+                .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+                    let _ = shutdown_receiver.await;
+                })
+                .await;
+            if let Err(err) = result {
+                error!("[grpc] server failed error=[{}]", err);
+            }
+        });
+        info!("[grpc] serving on {}", config.bind_address);
+        Ok(Self {
+            sender,
+            shutdown: Some(shutdown),
+            runtime: Some(runtime),
+        })
+    }
+
+    /// Whether a subscription is open, the callbacks don't build the updates otherwise
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    /// Publish an update to the subscribers without waiting for them, those lagging behind are closed
+    pub fn publish(&self, update: GrpcUpdate) {
+        let _ = self.sender.send(Arc::new(update));
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_timeout(SHUTDOWN_TIMEOUT);
+        }
+    }
+}
+
+impl Drop for GrpcServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::GrpcAccount;
+    use proto::geyser_client::GeyserClient;
+    use proto::AccountFilter as AccountFilterRequest;
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn test_subscribe() {
+        let config = GrpcConfig {
+            bind_address: "127.0.0.1:10017".to_string(),
+            ..GrpcConfig::default()
+        };
+        let mut server = GrpcServer::start(&config).unwrap();
+        let owner = Pubkey::new_unique();
+        let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+        let mut stream = runtime.block_on(async {
+            let mut client = GeyserClient::connect("http://127.0.0.1:10017").await.unwrap();
+            let invalid = SubscribeRequest {
+                accounts: Some(AccountFilterRequest {
+                    owners: vec!["not a pubkey".to_string()],
+                    ..AccountFilterRequest::default()
+                }),
+                ..SubscribeRequest::default()
+            };
+            assert_eq!(client.subscribe(invalid).await.unwrap_err().code(), tonic::Code::InvalidArgument);
+            let request = SubscribeRequest {
+                accounts: Some(AccountFilterRequest {
+                    owners: vec![owner.to_string()],
+                    ..AccountFilterRequest::default()
+                }),
+                slots: true,
+                ..SubscribeRequest::default()
+            };
+            client.subscribe(request).await.unwrap().into_inner()
+        });
+        assert!(server.has_subscribers());
+
+        let account = |owner: &Pubkey| {
+            GrpcUpdate::Account(GrpcAccount {
+                pubkey: vec![1; 32],
+                owner: owner.to_bytes().to_vec(),
+                lamports: 10,
+                executable: false,
+                rent_epoch: 0,
+                data: vec![1, 2, 3],
+                write_version: 7,
+                slot: 5,
+                txn_signature: None,
+                is_startup: false,
+            })
+        };
+        server.publish(account(&Pubkey::new_unique()));
+        server.publish(account(&owner));
+        server.publish(GrpcUpdate::Slot {
+            slot: 5,
+            parent: Some(4),
+            status: "rooted",
+        });
+        runtime.block_on(async {
+            match stream.message().await.unwrap().unwrap().update {
+                Some(Update::Account(update)) => {
+                    assert_eq!(update.owner, owner.to_bytes().to_vec());
+                    assert_eq!(update.write_version, 7);
+                }
+                update => panic!("unexpected update {:?}", update),
+            }
+            match stream.message().await.unwrap().unwrap().update {
+                Some(Update::Slot(update)) => assert_eq!((update.slot, update.parent, update.status.as_str()), (5, Some(4), "rooted")),
+                update => panic!("unexpected update {:?}", update),
+            }
+        });
+        server.stop();
+    }
+}
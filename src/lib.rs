@@ -1,19 +1,41 @@
+// lets the derive macros refer to this crate by name from within it
+extern crate self as solana_geyser_plugin_postgres;
+
 use geyser_plugin_postgres::GeyserPluginPostgres;
+pub use geyser_plugin_postgres::GeyserPluginPostgresBuilder;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
 
 pub mod accounts_selector;
+pub mod backfill;
+pub mod buffer_pool;
+pub mod commitment_buffer;
 pub mod config;
+pub mod config_profiles;
+#[cfg(feature = "embedded-postgres")]
+pub mod embedded_postgres;
+pub mod fixtures;
 pub mod geyser_plugin_postgres;
+pub mod grpc;
+pub mod memory_stats;
+pub mod metrics;
 pub mod parallel_client;
 pub mod parallel_client_worker;
 pub mod postgres_client;
+pub mod rpc_ingest;
+pub mod selector_reload;
+pub mod selector_stats;
+pub mod sinks;
+pub mod spool;
+#[cfg(feature = "test-harness")]
+pub mod test_harness;
+pub mod traces;
 pub mod transaction_selector;
 
 #[no_mangle]
 #[allow(improper_ctypes_definitions)]
 /// # Safety
 ///
-/// This function returns a pointer to the Kafka Plugin box implementing trait GeyserPlugin.
+/// This function returns a pointer to the plugin box implementing trait GeyserPlugin.
 ///
 /// The Solana validator and this plugin must be compiled with the same Rust compiler version and Solana core version.
 /// Loading this plugin with mismatching versions is undefined behavior and will likely cause memory corruption.
@@ -0,0 +1,245 @@
+//! Memory used by the plugin, reported periodically.
+//!
+//! The plugin shares the validator's address space, so its memory growth does not show up
+//! on its own in the process statistics. With the `jemalloc` feature the statistics of the
+//! jemalloc linked into the plugin are reported as well. The plugin keeps the global
+//! allocator of the process: boxed plugins and errors are freed on the other side of the
+//! validator/plugin boundary, so an allocator of its own would free memory it never
+//! allocated. The bytes held by the request channel and by the workers' pending batches
+//! are tracked either way.
+use crate::metrics::registry;
+use crate::metrics::solana_metrics_enabled;
+use crate::metrics::MEMORY_ALLOCATOR_BYTES;
+use crate::metrics::MEMORY_QUEUE_BYTES;
+use crate::metrics::MEMORY_RESIDENT_BYTES;
+use log::*;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use solana_metrics::*;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicI64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::sleep;
+use std::thread::Builder;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How often the reporter thread checks whether it is stopping
+const EXIT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// * The `memory_stats` section reports the allocator statistics and the bytes held by the queues.
+/// "memory_stats" : { "interval_secs" : 30 }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryStatsConfig {
+    /// Seconds between two reports
+    pub interval_secs: u64,
+}
+
+impl Default for MemoryStatsConfig {
+    fn default() -> Self {
+        Self { interval_secs: 30 }
+    }
+}
+
+/// Where requests wait before they are written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Queue {
+    /// The channel between the plugin callbacks and the workers
+    Requests,
+    /// The live updates the workers are batching
+    LiveUpdates,
+    /// The startup accounts the workers are batching
+    StartupAccounts,
+    /// The live account updates waiting for their slot to reach the `commitment_level`
+    CommitmentBuffer,
+}
+
+impl Queue {
+    pub const ALL: [Queue; 4] = [Queue::Requests, Queue::LiveUpdates, Queue::StartupAccounts, Queue::CommitmentBuffer];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Queue::Requests => "requests",
+            Queue::LiveUpdates => "live_updates",
+            Queue::StartupAccounts => "startup_accounts",
+            Queue::CommitmentBuffer => "commitment_buffer",
+        }
+    }
+}
+
+static QUEUED_BYTES: [AtomicI64; 4] = [AtomicI64::new(0), AtomicI64::new(0), AtomicI64::new(0), AtomicI64::new(0)];
+
+/// Account for `bytes` entering `queue`
+pub fn add_queued_bytes(queue: Queue, bytes: usize) {
+    QUEUED_BYTES[queue as usize].fetch_add(bytes as i64, Ordering::Relaxed);
+}
+
+/// Account for `bytes` leaving `queue`
+pub fn sub_queued_bytes(queue: Queue, bytes: usize) {
+    QUEUED_BYTES[queue as usize].fetch_sub(bytes as i64, Ordering::Relaxed);
+}
+
+/// The approximate bytes held by `queue`
+pub fn queued_bytes(queue: Queue) -> i64 {
+    QUEUED_BYTES[queue as usize].load(Ordering::Relaxed)
+}
+
+/// The jemalloc statistics, in bytes
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AllocatorStats {
+    /// Allocated by the plugin
+    pub allocated: u64,
+    /// In the pages holding the allocations
+    pub active: u64,
+    /// Held by the allocator, including its metadata
+    pub resident: u64,
+    /// Mapped by the allocator
+    pub mapped: u64,
+    /// Unmapped but kept reserved for reuse
+    pub retained: u64,
+}
+
+impl AllocatorStats {
+    /// Only available with the `jemalloc` feature
+    #[cfg(feature = "jemalloc")]
+    pub fn read() -> Option<Self> {
+        use tikv_jemalloc_ctl::epoch;
+        use tikv_jemalloc_ctl::stats;
+        // the statistics are cached until the epoch advances
+        if let Err(err) = epoch::advance() {
+            warn!("[memory_stats] failed to refresh the allocator stats {}", err);
+            return None;
+        }
+        Some(Self {
+            allocated: stats::allocated::read().ok()? as u64,
+            active: stats::active::read().ok()? as u64,
+            resident: stats::resident::read().ok()? as u64,
+            mapped: stats::mapped::read().ok()? as u64,
+            retained: stats::retained::read().ok()? as u64,
+        })
+    }
+
+    #[cfg(not(feature = "jemalloc"))]
+    pub fn read() -> Option<Self> {
+        None
+    }
+
+    fn values(&self) -> [(&'static str, u64); 5] {
+        [
+            ("allocated", self.allocated),
+            ("active", self.active),
+            ("resident", self.resident),
+            ("mapped", self.mapped),
+            ("retained", self.retained),
+        ]
+    }
+}
+
+/// The resident set size of the whole validator process, from `VmRSS` in /proc/self/status
+fn process_resident_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    parse_vm_rss(&status)
+}
+
+fn parse_vm_rss(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    Some(kb * 1024)
+}
+
+/// Set the memory gauges and send the `geyser-plugin-postgres-memory` datapoint
+pub fn report() {
+    let allocator = AllocatorStats::read();
+    if let Some(allocator) = &allocator {
+        for (stat, value) in allocator.values() {
+            registry().set_gauge(MEMORY_ALLOCATOR_BYTES, &[("stat", stat)], value as i64);
+        }
+    }
+    for queue in Queue::ALL {
+        registry().set_gauge(MEMORY_QUEUE_BYTES, &[("queue", queue.as_str())], queued_bytes(queue));
+    }
+    let process_resident = process_resident_bytes();
+    if let Some(process_resident) = process_resident {
+        registry().set_gauge(MEMORY_RESIDENT_BYTES, &[], process_resident as i64);
+    }
+    if solana_metrics_enabled() {
+        let allocator = allocator.unwrap_or_default();
+        datapoint_info!(
+            "geyser-plugin-postgres-memory",
+            ("allocated-bytes", allocator.allocated as i64, i64),
+            ("active-bytes", allocator.active as i64, i64),
+            ("resident-bytes", allocator.resident as i64, i64),
+            ("mapped-bytes", allocator.mapped as i64, i64),
+            ("retained-bytes", allocator.retained as i64, i64),
+            ("requests-queue-bytes", queued_bytes(Queue::Requests), i64),
+            ("live-updates-queue-bytes", queued_bytes(Queue::LiveUpdates), i64),
+            ("startup-accounts-queue-bytes", queued_bytes(Queue::StartupAccounts), i64),
+            ("commitment-buffer-bytes", queued_bytes(Queue::CommitmentBuffer), i64),
+            ("process-resident-bytes", process_resident.unwrap_or_default() as i64, i64),
+        );
+    }
+}
+
+/// Reports the memory statistics every interval from a dedicated thread
+pub struct MemoryStats {
+    exit: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MemoryStats {
+    pub fn start(config: &Option<MemoryStatsConfig>) -> Option<Self> {
+        let interval = Duration::from_secs(config.as_ref()?.interval_secs.max(1));
+        if cfg!(not(feature = "jemalloc")) {
+            info!("[memory_stats] built without the jemalloc feature, only reporting the queues");
+        }
+        let exit = Arc::new(AtomicBool::new(false));
+        let exit_reporter = exit.clone();
+        let thread = Builder::new()
+            .name("memory_stats".to_string())
+            .spawn(move || {
+                let mut last_report: Option<Instant> = None;
+                while !exit_reporter.load(Ordering::Relaxed) {
+                    if last_report.map_or(true, |last_report| last_report.elapsed() >= interval) {
+                        last_report = Some(Instant::now());
+                        report();
+                    }
+                    sleep(EXIT_CHECK_INTERVAL);
+                }
+            })
+            .unwrap();
+        Some(Self { exit, thread: Some(thread) })
+    }
+
+    pub fn stop(&mut self) {
+        self.exit.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for MemoryStats {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_stats() {
+        assert_eq!(parse_vm_rss("Name:\tsolana-validator\nVmRSS:\t  204800 kB\nVmData:\t1024 kB\n"), Some(204800 * 1024));
+        assert_eq!(parse_vm_rss("Name:\tsolana-validator\n"), None);
+
+        add_queued_bytes(Queue::LiveUpdates, 300);
+        sub_queued_bytes(Queue::LiveUpdates, 100);
+        report();
+        assert_eq!(registry().gauge(MEMORY_QUEUE_BYTES, &[("queue", "live_updates")]), Some(200));
+        assert_eq!(registry().gauge(MEMORY_ALLOCATOR_BYTES, &[("stat", "allocated")]).is_some(), cfg!(feature = "jemalloc"));
+    }
+}
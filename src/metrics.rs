@@ -0,0 +1,399 @@
+//! Plugin metrics kept in process and served in the Prometheus text format.
+//!
+//! Counters, gauges and latency histograms are recorded in one registry shared by the
+//! plugin, the parallel client and the workers, so they are available without a
+//! solana-metrics host. When the `metrics` section is configured they are served on
+//! `http://<bind_address>/metrics`.
+use lazy_static::lazy_static;
+use log::*;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::ErrorKind;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::thread::Builder;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Upper bounds of the latency histogram buckets, in microseconds
+pub const LATENCY_BUCKETS_US: [u64; 12] = [10, 50, 100, 500, 1_000, 5_000, 10_000, 50_000, 100_000, 500_000, 1_000_000, 5_000_000];
+
+pub const UPDATE_ACCOUNT_US: &str = "geyser_plugin_postgres_update_account_us";
+pub const ENQUEUE_US: &str = "geyser_plugin_postgres_enqueue_us";
+pub const QUEUE_DEPTH: &str = "geyser_plugin_postgres_queue_depth";
+pub const QUEUE_UTILIZATION_PERCENT: &str = "geyser_plugin_postgres_queue_utilization_percent";
+pub const QUEUE_SATURATION_TOTAL: &str = "geyser_plugin_postgres_queue_saturation_total";
+pub const SHED_TOTAL: &str = "geyser_plugin_postgres_shed_total";
+pub const DEGRADED_MODE: &str = "geyser_plugin_postgres_degraded_mode";
+pub const DEGRADED_MODE_CHANGES_TOTAL: &str = "geyser_plugin_postgres_degraded_mode_changes_total";
+pub const WORKER_RECV_US: &str = "geyser_plugin_postgres_worker_recv_us";
+pub const FLUSH_US: &str = "geyser_plugin_postgres_flush_us";
+pub const REQUESTS_TOTAL: &str = "geyser_plugin_postgres_requests_total";
+pub const BYTES_WRITTEN_TOTAL: &str = "geyser_plugin_postgres_bytes_written_total";
+pub const DB_CONNECTS_TOTAL: &str = "geyser_plugin_postgres_db_connects_total";
+pub const DB_RECONNECTS_TOTAL: &str = "geyser_plugin_postgres_db_reconnects_total";
+pub const ERRORS_TOTAL: &str = "geyser_plugin_postgres_errors_total";
+pub const DB_ERRORS_TOTAL: &str = "geyser_plugin_postgres_db_errors_total";
+pub const DB_RETRIES_TOTAL: &str = "geyser_plugin_postgres_db_retries_total";
+pub const FAULTS_INJECTED_TOTAL: &str = "geyser_plugin_postgres_faults_injected_total";
+pub const HANDLER_ERRORS_TOTAL: &str = "geyser_plugin_postgres_handler_errors_total";
+pub const HANDLER_ROWS_TOTAL: &str = "geyser_plugin_postgres_handler_rows_total";
+pub const HANDLER_DECODE_ERRORS_TOTAL: &str = "geyser_plugin_postgres_handler_decode_errors_total";
+pub const HANDLER_QUEUE_DEPTH: &str = "geyser_plugin_postgres_handler_queue_depth";
+pub const HANDLER_FLUSH_US: &str = "geyser_plugin_postgres_handler_flush_us";
+pub const SINK_ERRORS_TOTAL: &str = "geyser_plugin_postgres_sink_errors_total";
+pub const SINK_DROPPED_TOTAL: &str = "geyser_plugin_postgres_sink_dropped_total";
+pub const STARTUP_ACCOUNTS_TOTAL: &str = "geyser_plugin_postgres_startup_accounts_total";
+pub const STARTUP_RESUMES_TOTAL: &str = "geyser_plugin_postgres_startup_resumes_total";
+pub const WORKER_RESTARTS_TOTAL: &str = "geyser_plugin_postgres_worker_restarts_total";
+pub const UNLOAD_REQUESTS_TOTAL: &str = "geyser_plugin_postgres_unload_requests_total";
+pub const TIP_SLOT_GAUGE: &str = "geyser_plugin_postgres_tip_slot";
+pub const LAST_PROCESSED_SLOT_GAUGE: &str = "geyser_plugin_postgres_last_processed_slot";
+pub const INDEXING_LAG_SLOTS: &str = "geyser_plugin_postgres_indexing_lag_slots";
+pub const SLOT_WATERMARK: &str = "geyser_plugin_postgres_slot_watermark";
+pub const SLOT_GAPS_TOTAL: &str = "geyser_plugin_postgres_slot_gaps_total";
+pub const MEMORY_ALLOCATOR_BYTES: &str = "geyser_plugin_postgres_memory_allocator_bytes";
+pub const MEMORY_QUEUE_BYTES: &str = "geyser_plugin_postgres_memory_queue_bytes";
+pub const MEMORY_RESIDENT_BYTES: &str = "geyser_plugin_postgres_memory_resident_bytes";
+pub const COMMITMENT_BUFFERED_ACCOUNTS: &str = "geyser_plugin_postgres_commitment_buffered_accounts";
+pub const COMMITMENT_ABANDONED_TOTAL: &str = "geyser_plugin_postgres_commitment_abandoned_total";
+pub const SPOOL_BYTES: &str = "geyser_plugin_postgres_spool_bytes";
+pub const SPOOL_RECORDS_TOTAL: &str = "geyser_plugin_postgres_spool_records_total";
+pub const RETENTION_ROWS_DELETED_TOTAL: &str = "geyser_plugin_postgres_retention_rows_deleted_total";
+pub const SELECTOR_RELOADS_TOTAL: &str = "geyser_plugin_postgres_selector_reloads_total";
+pub const DB_POOL_CONNECTIONS: &str = "geyser_plugin_postgres_db_pool_connections";
+pub const GRPC_SUBSCRIBERS: &str = "geyser_plugin_postgres_grpc_subscribers";
+pub const GRPC_LAGGED_TOTAL: &str = "geyser_plugin_postgres_grpc_lagged_total";
+
+/// Milliseconds between two periodic reports, see `ReportingConfig`
+static REPORT_INTERVAL_MS: AtomicU64 = AtomicU64::new(30000);
+/// Whether datapoints are sent to solana-metrics
+static SOLANA_METRICS: AtomicBool = AtomicBool::new(true);
+
+/// How often the listener checks whether the server is stopping
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// * The `metrics` section serves the plugin metrics in the Prometheus text format.
+/// "metrics" : { "bind_address" : "127.0.0.1:9187" }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// The address the `/metrics` endpoint listens on
+    pub bind_address: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: "127.0.0.1:9187".to_string(),
+        }
+    }
+}
+
+/// * The `reporting` section controls the periodic reports and the solana-metrics datapoints.
+/// "reporting" : { "interval_ms" : 30000, "solana_metrics" : false, "histogram_sample_rate" : 10 }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReportingConfig {
+    /// Milliseconds between the queue length and selector statistics reports
+    pub interval_ms: u64,
+    /// Send datapoints to solana-metrics, disable it when only scraping `/metrics`
+    pub solana_metrics: bool,
+    /// Record one in this many latency observations in the histograms
+    pub histogram_sample_rate: u64,
+}
+
+impl Default for ReportingConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 30000,
+            solana_metrics: true,
+            histogram_sample_rate: 1,
+        }
+    }
+}
+
+/// Apply the reporting settings to the whole plugin
+pub fn configure_reporting(config: &ReportingConfig) {
+    REPORT_INTERVAL_MS.store(config.interval_ms, Ordering::Relaxed);
+    SOLANA_METRICS.store(config.solana_metrics, Ordering::Relaxed);
+    registry().set_histogram_sample_rate(config.histogram_sample_rate);
+}
+
+pub fn report_interval_ms() -> u64 {
+    REPORT_INTERVAL_MS.load(Ordering::Relaxed)
+}
+
+pub fn solana_metrics_enabled() -> bool {
+    SOLANA_METRICS.load(Ordering::Relaxed)
+}
+
+type MetricKey = (&'static str, Vec<(&'static str, String)>);
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS_US.len()],
+    sum: u64,
+    count: u64,
+}
+
+/// Counters, gauges and latency histograms keyed by name and labels
+#[derive(Debug, Default)]
+pub struct MetricsRegistry {
+    counters: Mutex<BTreeMap<MetricKey, u64>>,
+    gauges: Mutex<BTreeMap<MetricKey, i64>>,
+    histograms: Mutex<BTreeMap<MetricKey, Histogram>>,
+    /// Record one in this many latency observations, 0 and 1 record all of them
+    histogram_sample_rate: AtomicU64,
+    observations: AtomicU64,
+}
+
+lazy_static! {
+    static ref REGISTRY: MetricsRegistry = MetricsRegistry::default();
+}
+
+/// The registry shared by the whole plugin
+pub fn registry() -> &'static MetricsRegistry {
+    &REGISTRY
+}
+
+fn key(name: &'static str, labels: &[(&'static str, &str)]) -> MetricKey {
+    (name, labels.iter().map(|(label, value)| (*label, value.to_string())).collect())
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// `name{label="value",...}` with any extra label appended
+fn series(name: &str, suffix: &str, labels: &[(&'static str, String)], extra: Option<(&str, &str)>) -> String {
+    let labels = labels
+        .iter()
+        .map(|(label, value)| (*label, value.as_str()))
+        .chain(extra)
+        .map(|(label, value)| format!("{}=\"{}\"", label, escape_label(value)))
+        .collect::<Vec<String>>();
+    if labels.is_empty() {
+        format!("{}{}", name, suffix)
+    } else {
+        format!("{}{}{{{}}}", name, suffix, labels.join(","))
+    }
+}
+
+impl MetricsRegistry {
+    pub fn inc_counter(&self, name: &'static str, labels: &[(&'static str, &str)], value: u64) {
+        *self.counters.lock().unwrap().entry(key(name, labels)).or_default() += value;
+    }
+
+    pub fn set_gauge(&self, name: &'static str, labels: &[(&'static str, &str)], value: i64) {
+        self.gauges.lock().unwrap().insert(key(name, labels), value);
+    }
+
+    pub fn set_histogram_sample_rate(&self, sample_rate: u64) {
+        self.histogram_sample_rate.store(sample_rate, Ordering::Relaxed);
+    }
+
+    /// Record a latency in microseconds, subject to the histogram sample rate
+    pub fn observe_us(&self, name: &'static str, labels: &[(&'static str, &str)], value_us: u64) {
+        let sample_rate = self.histogram_sample_rate.load(Ordering::Relaxed).max(1);
+        if self.observations.fetch_add(1, Ordering::Relaxed) % sample_rate != 0 {
+            return;
+        }
+        let mut histograms = self.histograms.lock().unwrap();
+        let histogram = histograms.entry(key(name, labels)).or_default();
+        if let Some(bucket) = LATENCY_BUCKETS_US.iter().position(|bound| value_us <= *bound) {
+            histogram.buckets[bucket] += 1;
+        }
+        histogram.sum += value_us;
+        histogram.count += 1;
+    }
+
+    pub fn counter(&self, name: &'static str, labels: &[(&'static str, &str)]) -> u64 {
+        self.counters.lock().unwrap().get(&key(name, labels)).copied().unwrap_or_default()
+    }
+
+    /// The values of a counter summed by one of its labels
+    pub fn counters_by(&self, name: &'static str, label: &str) -> BTreeMap<String, u64> {
+        self.counters_by_where(name, label, &[])
+    }
+
+    /// The values of a counter having the `filter` labels, summed by one of its labels
+    pub fn counters_by_where(&self, name: &'static str, label: &str, filter: &[(&str, &str)]) -> BTreeMap<String, u64> {
+        let mut values = BTreeMap::new();
+        for ((_, labels), value) in self
+            .counters
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((counter, labels), _)| *counter == name && filter.iter().all(|filter| labels.iter().any(|(l, v)| (*l, v.as_str()) == *filter)))
+        {
+            let key = labels.iter().find(|(l, _)| *l == label).map(|(_, v)| v.clone()).unwrap_or_default();
+            *values.entry(key).or_default() += value;
+        }
+        values
+    }
+
+    pub fn gauge(&self, name: &'static str, labels: &[(&'static str, &str)]) -> Option<i64> {
+        self.gauges.lock().unwrap().get(&key(name, labels)).copied()
+    }
+
+    /// Every metric in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut last_name = "";
+        for ((name, labels), value) in self.counters.lock().unwrap().iter() {
+            if *name != last_name {
+                let _ = writeln!(out, "# TYPE {} counter", name);
+                last_name = *name;
+            }
+            let _ = writeln!(out, "{} {}", series(name, "", labels, None), value);
+        }
+        for ((name, labels), value) in self.gauges.lock().unwrap().iter() {
+            if *name != last_name {
+                let _ = writeln!(out, "# TYPE {} gauge", name);
+                last_name = *name;
+            }
+            let _ = writeln!(out, "{} {}", series(name, "", labels, None), value);
+        }
+        for ((name, labels), histogram) in self.histograms.lock().unwrap().iter() {
+            if *name != last_name {
+                let _ = writeln!(out, "# TYPE {} histogram", name);
+                last_name = *name;
+            }
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS_US.iter().zip(histogram.buckets.iter()) {
+                cumulative += count;
+                let _ = writeln!(out, "{} {}", series(name, "_bucket", labels, Some(("le", &bound.to_string()))), cumulative);
+            }
+            let _ = writeln!(out, "{} {}", series(name, "_bucket", labels, Some(("le", "+Inf"))), histogram.count);
+            let _ = writeln!(out, "{} {}", series(name, "_sum", labels, None), histogram.sum);
+            let _ = writeln!(out, "{} {}", series(name, "_count", labels, None), histogram.count);
+        }
+        out
+    }
+}
+
+/// Serves the registry on `/metrics` from a background thread
+pub struct MetricsServer {
+    exit: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+fn respond(mut stream: TcpStream) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let (status, body) = match request_line.split_whitespace().nth(1) {
+        Some("/metrics") => ("200 OK", registry().render()),
+        _ => ("404 Not Found", "Not Found\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+impl MetricsServer {
+    pub fn start(config: &MetricsConfig) -> Result<Self, GeyserPluginError> {
+        let listener = TcpListener::bind(&config.bind_address)
+            .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+            .map_err(|e| GeyserPluginError::ConfigFileReadError {
+                msg: format!("Failed to bind metrics bind_address {}: {}", config.bind_address, e),
+            })?;
+        let exit = Arc::new(AtomicBool::new(false));
+        let exit_server = exit.clone();
+        let thread = Builder::new()
+            .name("metrics".to_string())
+            .spawn(move || {
+                while !exit_server.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Err(err) = respond(stream) {
+                                debug!("[metrics] failed to respond error=[{}]", err);
+                            }
+                        }
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => sleep(ACCEPT_INTERVAL),
+                        Err(err) => error!("[metrics] failed to accept error=[{}]", err),
+                    }
+                }
+            })
+            .unwrap();
+        info!("[metrics] serving on http://{}/metrics", config.bind_address);
+        Ok(Self { exit, thread: Some(thread) })
+    }
+
+    pub fn stop(&mut self) {
+        self.exit.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let registry = MetricsRegistry::default();
+        registry.inc_counter("errors_total", &[("request", "update_account")], 2);
+        registry.set_gauge("queue_depth", &[], 7);
+        registry.observe_us("flush_us", &[("kind", "live")], 40);
+        registry.observe_us("flush_us", &[("kind", "live")], 10_000_000);
+        let rendered = registry.render();
+        assert!(rendered.contains("# TYPE errors_total counter\nerrors_total{request=\"update_account\"} 2\n"));
+        assert!(rendered.contains("# TYPE queue_depth gauge\nqueue_depth 7\n"));
+        assert!(rendered.contains("flush_us_bucket{kind=\"live\",le=\"10\"} 0\n"));
+        assert!(rendered.contains("flush_us_bucket{kind=\"live\",le=\"50\"} 1\n"));
+        assert!(rendered.contains("flush_us_bucket{kind=\"live\",le=\"5000000\"} 1\n"));
+        assert!(rendered.contains("flush_us_bucket{kind=\"live\",le=\"+Inf\"} 2\n"));
+        assert!(rendered.contains("flush_us_sum{kind=\"live\"} 10000040\n"));
+        assert!(rendered.contains("flush_us_count{kind=\"live\"} 2\n"));
+        registry.inc_counter("errors_total", &[("request", "update_account")], 1);
+        registry.inc_counter("errors_total", &[("request", "log_transaction")], 1);
+        assert_eq!(
+            registry.counters_by("errors_total", "request"),
+            BTreeMap::from([("log_transaction".to_string(), 1), ("update_account".to_string(), 3)])
+        );
+        registry.inc_counter("unload_total", &[("request", "update_account"), ("outcome", "processed")], 5);
+        registry.inc_counter("unload_total", &[("request", "update_account"), ("outcome", "dropped")], 2);
+        registry.inc_counter("unload_total", &[("request", "update_slot"), ("outcome", "dropped")], 1);
+        assert_eq!(
+            registry.counters_by_where("unload_total", "request", &[("outcome", "dropped")]),
+            BTreeMap::from([("update_account".to_string(), 2), ("update_slot".to_string(), 1)])
+        );
+    }
+
+    #[test]
+    fn test_histogram_sample_rate() {
+        let registry = MetricsRegistry::default();
+        registry.set_histogram_sample_rate(3);
+        for _ in 0..7 {
+            registry.observe_us("flush_us", &[], 40);
+        }
+        assert!(registry.render().contains("flush_us_count 3\n"));
+    }
+}
@@ -1,17 +1,58 @@
 use crate::abort;
+use crate::commitment_buffer::CommitmentBuffer;
+use crate::commitment_buffer::CommitmentLevel;
 use crate::config::GeyserPluginPostgresConfig;
+use crate::fixtures::RecordedAccount;
+use crate::fixtures::RecordedTransaction;
+use crate::memory_stats::add_queued_bytes;
+use crate::memory_stats::sub_queued_bytes;
+use crate::memory_stats::Queue;
+use crate::metrics::registry;
+use crate::metrics::report_interval_ms;
+use crate::metrics::solana_metrics_enabled;
+use crate::metrics::DEGRADED_MODE;
+use crate::metrics::DEGRADED_MODE_CHANGES_TOTAL;
+use crate::metrics::ENQUEUE_US;
+use crate::metrics::QUEUE_DEPTH;
+use crate::metrics::QUEUE_SATURATION_TOTAL;
+use crate::metrics::QUEUE_UTILIZATION_PERCENT;
+use crate::metrics::SHED_TOTAL;
+use crate::metrics::WORKER_RESTARTS_TOTAL;
 use crate::parallel_client_worker::LogTransactionRequest;
 use crate::parallel_client_worker::ParallelClientWorker;
 use crate::parallel_client_worker::UpdateAccountRequest;
 use crate::parallel_client_worker::UpdateBlockMetadataRequest;
+use crate::parallel_client_worker::UpdateSelectorStatsRequest;
 use crate::parallel_client_worker::UpdateSlotRequest;
 use crate::parallel_client_worker::WorkRequest;
+use crate::parallel_client_worker::WorkerStatus;
 use crate::postgres_client::build_db_transaction;
+use crate::postgres_client::connection_pool::ConnectionPool;
+use crate::postgres_client::startup_progress::StartupProgressHandler;
+use crate::postgres_client::startup_summary::StartupTracker;
+use crate::postgres_client::unload_summary::record_unload_request;
+use crate::postgres_client::unload_summary::UnloadSummary;
+use crate::postgres_client::CustomHandlers;
 use crate::postgres_client::DbAccountInfo;
 use crate::postgres_client::DbBlockInfo;
+use crate::postgres_client::DbSelectorStat;
+use crate::postgres_client::IdlRegistry;
+use crate::postgres_client::SlotGap;
+use crate::postgres_client::SlotWatermarks;
+#[cfg(feature = "webhook")]
+use crate::sinks::webhook_sink::Webhook;
+use crate::spool::RecordedBlock;
+use crate::spool::RecordedSlotStatus;
+use crate::spool::Spool;
+use crate::spool::SpoolRecord;
+use core_affinity::CoreId;
 use crossbeam_channel::bounded;
+use crossbeam_channel::Receiver;
+use crossbeam_channel::SendError;
 use crossbeam_channel::Sender;
 use log::*;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaBlockInfo;
@@ -19,6 +60,7 @@ use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionI
 use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
 use solana_measure::measure::Measure;
 use solana_metrics::*;
+use solana_sdk::timing::timestamp;
 use solana_sdk::timing::AtomicInterval;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicU64;
@@ -30,126 +72,614 @@ use std::thread::Builder;
 use std::thread::JoinHandle;
 use std::thread::{self};
 use std::time::Duration;
+use std::time::Instant;
+use tracing::Span;
 
 const MAX_ASYNC_REQUESTS: usize = 40960;
 
+/// * The `worker_watchdog` section restarts workers that exited, or stalled while requests are queued.
+/// "worker_watchdog" : { "stall_timeout_secs" : 60, "check_interval_ms" : 1000, "max_retired_workers" : 2 }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkerWatchdogConfig {
+    /// Seconds a worker may go without completing a loop iteration while the queue is not empty
+    pub stall_timeout_secs: u64,
+    /// Milliseconds between two checks of the workers
+    pub check_interval_ms: u64,
+    /// Stalled workers retired but not exited yet, each holding a connection, past which no more are replaced
+    pub max_retired_workers: usize,
+}
+
+impl Default for WorkerWatchdogConfig {
+    fn default() -> Self {
+        Self {
+            stall_timeout_secs: 60,
+            check_interval_ms: 1000,
+            max_retired_workers: 2,
+        }
+    }
+}
+
+/// * The `queue_saturation` section warns, and optionally sheds load, when the request channel fills up.
+/// "queue_saturation" : { "warn_percent" : 75, "shed_percent" : 95, "shed" : true }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueueSaturationConfig {
+    /// Channel utilization, in percent of its capacity, above which a warning is logged
+    pub warn_percent: u64,
+    /// Channel utilization, in percent of its capacity, above which load is shed
+    pub shed_percent: u64,
+    /// Drop live account updates and transactions above `shed_percent` instead of
+    /// letting the validator block once the channel is full
+    pub shed: bool,
+    /// Skip some handlers while the channel stays above `warn_percent`
+    pub degraded_mode: Option<DegradedModeConfig>,
+}
+
+impl Default for QueueSaturationConfig {
+    fn default() -> Self {
+        Self {
+            warn_percent: 75,
+            shed_percent: 95,
+            shed: false,
+            degraded_mode: None,
+        }
+    }
+}
+
+/// "degraded_mode" : { "after_secs" : 30, "recover_after_secs" : 60, "skip_handlers" : \["unknown_account"\] }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DegradedModeConfig {
+    /// Seconds the channel has to stay above `warn_percent` before degrading
+    pub after_secs: u64,
+    /// Seconds the channel has to stay below `warn_percent` before restoring full fidelity
+    pub recover_after_secs: u64,
+    /// The handlers left out of live account updates while degraded
+    pub skip_handlers: Vec<String>,
+}
+
+impl Default for DegradedModeConfig {
+    fn default() -> Self {
+        Self {
+            after_secs: 30,
+            recover_after_secs: 60,
+            skip_handlers: vec!["unknown_account".to_string()],
+        }
+    }
+}
+
+/// Whether the workers currently skip the `skip_handlers` of the degraded mode
+static DEGRADED: AtomicBool = AtomicBool::new(false);
+
+pub fn is_degraded() -> bool {
+    DEGRADED.load(Ordering::Relaxed)
+}
+
+/// Tracks how long the channel has been saturated, or relieved, to switch the degraded mode
+#[derive(Debug, Default)]
+pub struct DegradedMode {
+    degraded: bool,
+    saturated_since: Option<Instant>,
+    relieved_since: Option<Instant>,
+}
+
+impl DegradedMode {
+    /// The new state when it changes
+    pub fn update(&mut self, config: &DegradedModeConfig, saturated: bool, now: Instant) -> Option<bool> {
+        if saturated {
+            self.relieved_since = None;
+            self.saturated_since.get_or_insert(now);
+        } else {
+            self.saturated_since = None;
+            self.relieved_since.get_or_insert(now);
+        }
+        let held_for = |since: Option<Instant>, secs: u64| since.map_or(false, |since| now.duration_since(since) >= Duration::from_secs(secs));
+        let degraded = match self.degraded {
+            false => held_for(self.saturated_since, config.after_secs),
+            true => !held_for(self.relieved_since, config.recover_after_secs),
+        };
+        if degraded == self.degraded {
+            return None;
+        }
+        self.degraded = degraded;
+        Some(degraded)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SaturationLevel {
+    Normal,
+    Warn,
+    Shed,
+}
+
+impl SaturationLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SaturationLevel::Normal => "normal",
+            SaturationLevel::Warn => "warn",
+            SaturationLevel::Shed => "shed",
+        }
+    }
+}
+
+/// How full the channel is, `Shed` is only reached when shedding is enabled
+pub fn saturation_level(config: &QueueSaturationConfig, len: usize, capacity: usize) -> SaturationLevel {
+    let percent = (len * 100 / capacity.max(1)) as u64;
+    if config.shed && percent >= config.shed_percent {
+        SaturationLevel::Shed
+    } else if percent >= config.warn_percent {
+        SaturationLevel::Warn
+    } else {
+        SaturationLevel::Normal
+    }
+}
+
+/// A worker thread and the status it reports
+struct Worker {
+    handle: JoinHandle<Result<(), GeyserPluginError>>,
+    status: Arc<WorkerStatus>,
+}
+
 #[warn(clippy::large_enum_variant)]
 pub struct ParallelClient {
-    workers: Vec<JoinHandle<Result<(), GeyserPluginError>>>,
+    workers: Vec<Worker>,
+    /// The replaced workers, joined once they flushed their pending updates and exited
+    retired_workers: Vec<Worker>,
     exit_worker: Arc<AtomicBool>,
     is_startup_done: Arc<AtomicBool>,
     startup_done_count: Arc<AtomicUsize>,
     initialized_worker_count: Arc<AtomicUsize>,
     sender: Sender<WorkRequest>,
-    last_report: AtomicInterval,
+    receiver: Receiver<WorkRequest>,
     transaction_write_version: AtomicU64,
+    config: GeyserPluginPostgresConfig,
+    idl_registry: Arc<IdlRegistry>,
+    custom_handlers: CustomHandlers,
+    /// The connections shared by the workers, when the `pool` is configured
+    pool: Option<Arc<ConnectionPool>>,
+    last_watchdog_check: AtomicInterval,
+    last_report: AtomicInterval,
+    saturation_level: SaturationLevel,
+    degraded_mode: DegradedMode,
+    startup_tracker: Option<StartupTracker>,
+    /// The live account updates held back until their slot reaches the `commitment_level`
+    commitment_buffer: Option<CommitmentBuffer>,
+    /// The live events received while a worker was reconnecting, and not replayed yet
+    spool: Option<Spool>,
+    /// Set while the spooled events are replayed, they are queued as they are received
+    replaying: bool,
+    /// The clients of the `databases`, forwarded the events once they are buffered and spooled
+    databases: Vec<ParallelClient>,
+    /// The highest slot of each status, raised in the order the validator notifies the slots
+    slot_watermarks: SlotWatermarks,
+    /// Fed the account updates and slot statuses in the order they are queued, each sends them from a thread of its own
+    #[cfg(feature = "webhook")]
+    webhooks: Vec<Webhook>,
 }
 
 impl ParallelClient {
-    pub fn new(config: &GeyserPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
+    pub fn new(config: &GeyserPluginPostgresConfig, idl_registry: Arc<IdlRegistry>, custom_handlers: &CustomHandlers, pool: Option<Arc<ConnectionPool>>) -> Result<Self, GeyserPluginError> {
         info!("[ParallelClient] config=[{:?}]", config);
         let (sender, receiver) = bounded(MAX_ASYNC_REQUESTS);
-        let exit_worker = Arc::new(AtomicBool::new(false));
-        let mut workers = Vec::default();
-        let is_startup_done = Arc::new(AtomicBool::new(false));
-        let startup_done_count = Arc::new(AtomicUsize::new(0));
-        let worker_count = config.threads;
-        let initialized_worker_count = Arc::new(AtomicUsize::new(0));
-        for i in 0..worker_count {
-            let cloned_receiver = receiver.clone();
-            let exit_clone = exit_worker.clone();
-            let is_startup_done_clone = is_startup_done.clone();
-            let startup_done_count_clone = startup_done_count.clone();
-            let initialized_worker_count_clone = initialized_worker_count.clone();
-            let config = config.clone();
-            let worker = Builder::new()
-                .name(format!("worker-{}", i))
-                .spawn(move || -> Result<(), GeyserPluginError> {
-                    let panic_on_db_errors = config.panic_on_db_errors;
-                    match ParallelClientWorker::new(config) {
-                        Ok(mut worker) => {
-                            initialized_worker_count_clone.fetch_add(1, Ordering::Relaxed);
-                            worker.do_work(cloned_receiver, exit_clone, is_startup_done_clone, startup_done_count_clone, panic_on_db_errors)?;
-                            Ok(())
-                        }
-                        Err(err) => {
-                            error!("Error when making connection to database: ({})", err);
-                            if panic_on_db_errors {
-                                abort();
-                            }
-                            Err(err)
+        let mut client = Self {
+            workers: Vec::default(),
+            retired_workers: Vec::default(),
+            exit_worker: Arc::new(AtomicBool::new(false)),
+            is_startup_done: Arc::new(AtomicBool::new(false)),
+            startup_done_count: Arc::new(AtomicUsize::new(0)),
+            initialized_worker_count: Arc::new(AtomicUsize::new(0)),
+            sender,
+            receiver,
+            transaction_write_version: AtomicU64::default(),
+            config: config.clone(),
+            idl_registry,
+            custom_handlers: custom_handlers.clone(),
+            pool,
+            last_watchdog_check: AtomicInterval::default(),
+            last_report: AtomicInterval::default(),
+            saturation_level: SaturationLevel::Normal,
+            degraded_mode: DegradedMode::default(),
+            startup_tracker: None,
+            commitment_buffer: (config.commitment_level != CommitmentLevel::Processed).then(|| CommitmentBuffer::new(config.commitment_level)),
+            spool: config.spool.as_ref().map(Spool::open).transpose()?,
+            replaying: false,
+            databases: Vec::new(),
+            slot_watermarks: SlotWatermarks::default(),
+            #[cfg(feature = "webhook")]
+            webhooks: config.webhooks.iter().map(Webhook::start).collect::<Result<Vec<Webhook>, GeyserPluginError>>()?,
+        };
+        for i in 0..config.threads {
+            let worker = client.spawn_worker(i);
+            client.workers.push(worker);
+        }
+        Ok(client)
+    }
+
+    fn spawn_worker(&self, i: usize) -> Worker {
+        let cloned_receiver = self.receiver.clone();
+        let exit_clone = self.exit_worker.clone();
+        let is_startup_done_clone = self.is_startup_done.clone();
+        let startup_done_count_clone = self.startup_done_count.clone();
+        let initialized_worker_count_clone = self.initialized_worker_count.clone();
+        let config = self.config.clone();
+        let idl_registry = self.idl_registry.clone();
+        let custom_handlers = self.custom_handlers.clone();
+        let pool = self.pool.clone();
+        let status = Arc::new(WorkerStatus::new());
+        let status_clone = status.clone();
+        let core_id = config.worker_cpu_affinity.as_ref().map(|core_ids| core_ids[i % core_ids.len()]);
+        let handle = Builder::new()
+            .name(format!("{}-{}", config.worker_thread_name_prefix, i))
+            .spawn(move || -> Result<(), GeyserPluginError> {
+                if let Some(core_id) = core_id {
+                    if !core_affinity::set_for_current(CoreId { id: core_id }) {
+                        warn!("[ParallelClient] failed to pin worker {} to core {}", i, core_id);
+                    }
+                }
+                let panic_on_db_errors = config.panic_on_db_errors;
+                match ParallelClientWorker::new(config, idl_registry, &custom_handlers, pool, exit_clone) {
+                    Ok(mut worker) => {
+                        status_clone.initialized.store(true, Ordering::Relaxed);
+                        initialized_worker_count_clone.fetch_add(1, Ordering::Relaxed);
+                        worker.do_work(cloned_receiver, is_startup_done_clone, startup_done_count_clone, status_clone, panic_on_db_errors)?;
+                        Ok(())
+                    }
+                    Err(err) => {
+                        error!("Error when making connection to database: ({})", err);
+                        if panic_on_db_errors {
+                            abort();
                         }
+                        Err(err)
                     }
-                })
-                .unwrap();
+                }
+            })
+            .unwrap();
+        Worker { handle, status }
+    }
 
-            workers.push(worker);
+    /// Replace the workers that exited, or stopped going through their loop while requests are queued
+    fn check_workers(&mut self) {
+        for database in &mut self.databases {
+            database.check_workers();
+        }
+        let watchdog = match &self.config.worker_watchdog {
+            Some(watchdog) => watchdog.clone(),
+            None => return,
+        };
+        if !self.last_watchdog_check.should_update(watchdog.check_interval_ms) {
+            return;
         }
+        self.join_retired_workers(false);
+        for i in 0..self.workers.len() {
+            let status = &self.workers[i].status;
+            let stalled_ms = timestamp().saturating_sub(status.last_progress_ms.load(Ordering::Relaxed));
+            let reason = if self.workers[i].handle.is_finished() {
+                "exited"
+            } else if !self.sender.is_empty() && stalled_ms >= watchdog.stall_timeout_secs * 1000 {
+                "stalled"
+            } else {
+                continue;
+            };
+            if reason == "stalled" && self.retired_workers.len() >= watchdog.max_retired_workers {
+                warn!("[ParallelClient] not restarting stalled worker {}, {} retired workers have yet to exit", i, self.retired_workers.len());
+                continue;
+            }
+            warn!("[ParallelClient] restarting worker {} reason=[{}] stalled_ms=[{}]", i, reason, stalled_ms);
+            if solana_metrics_enabled() {
+                datapoint_error!(
+                    "geyser-plugin-postgres-worker-restart",
+                    ("worker", i as i64, i64),
+                    ("reason", reason, String),
+                    ("stalled-ms", stalled_ms as i64, i64),
+                );
+            }
+            registry().inc_counter(WORKER_RESTARTS_TOTAL, &[("reason", reason)], 1);
 
-        Ok(Self {
-            last_report: AtomicInterval::default(),
-            workers,
-            exit_worker,
-            is_startup_done,
-            startup_done_count,
-            initialized_worker_count,
-            sender,
-            transaction_write_version: AtomicU64::default(),
+            let replacement = self.spawn_worker(i);
+            let retired = std::mem::replace(&mut self.workers[i], replacement);
+            retired.status.retired.store(true, Ordering::Relaxed);
+            if retired.status.initialized.load(Ordering::Relaxed) {
+                self.initialized_worker_count.fetch_sub(1, Ordering::Relaxed);
+            }
+            if retired.status.startup_done.load(Ordering::Relaxed) {
+                self.startup_done_count.fetch_sub(1, Ordering::Relaxed);
+            }
+            // a stalled worker flushes its pending updates and exits on its own once it gets unstuck
+            self.retired_workers.push(retired);
+        }
+        self.join_retired_workers(false);
+    }
+
+    /// Join the retired workers that exited, or all of them when `wait`
+    fn join_retired_workers(&mut self, wait: bool) {
+        let (exited, running): (Vec<Worker>, Vec<Worker>) = std::mem::take(&mut self.retired_workers).into_iter().partition(|worker| wait || worker.handle.is_finished());
+        self.retired_workers = running;
+        for worker in exited {
+            match worker.handle.join() {
+                Ok(Err(err)) => error!("[ParallelClient] retired worker exited error=[{}]", err),
+                Err(err) => error!("[ParallelClient] retired worker panicked {:?}", err),
+                Ok(Ok(())) => {}
+            }
+        }
+    }
+
+    /// Queue a request for the workers, accounting for the bytes it holds until a worker receives it
+    fn send(&self, work: WorkRequest) -> Result<(), SendError<WorkRequest>> {
+        let size_bytes = work.size_bytes();
+        add_queued_bytes(Queue::Requests, size_bytes);
+        self.sender.send(work).map_err(|err| {
+            sub_queued_bytes(Queue::Requests, size_bytes);
+            err
         })
     }
 
+    /// Report the channel utilization and whether the next sheddable request should be dropped
+    fn check_saturation(&mut self) -> bool {
+        let (len, capacity) = (self.sender.len(), MAX_ASYNC_REQUESTS);
+        registry().set_gauge(QUEUE_DEPTH, &[], len as i64);
+        if solana_metrics_enabled() && self.last_report.should_update(report_interval_ms()) {
+            datapoint_debug!("postgres-plugin-stats", ("message-queue-length", len as i64, i64),);
+        }
+        registry().set_gauge(QUEUE_UTILIZATION_PERCENT, &[], (len * 100 / capacity) as i64);
+        let config = match &self.config.queue_saturation {
+            Some(config) => config,
+            None => return false,
+        };
+        let level = saturation_level(config, len, capacity);
+        if level != self.saturation_level {
+            if level > self.saturation_level {
+                warn!("[ParallelClient] queue saturation level=[{}] depth=[{}/{}]", level.as_str(), len, capacity);
+                registry().inc_counter(QUEUE_SATURATION_TOTAL, &[("level", level.as_str())], 1);
+            } else {
+                info!("[ParallelClient] queue saturation level=[{}] depth=[{}/{}]", level.as_str(), len, capacity);
+            }
+            self.saturation_level = level;
+        }
+        if let Some(degraded_mode) = &config.degraded_mode {
+            if let Some(degraded) = self.degraded_mode.update(degraded_mode, level >= SaturationLevel::Warn, Instant::now()) {
+                let state = if degraded { "degraded" } else { "full" };
+                warn!("[ParallelClient] fidelity=[{}] skip_handlers=[{:?}] depth=[{}/{}]", state, degraded_mode.skip_handlers, len, capacity);
+                DEGRADED.store(degraded, Ordering::Relaxed);
+                registry().set_gauge(DEGRADED_MODE, &[], degraded as i64);
+                registry().inc_counter(DEGRADED_MODE_CHANGES_TOTAL, &[("state", state)], 1);
+                if solana_metrics_enabled() {
+                    datapoint_warn!("geyser-plugin-postgres-degraded-mode", ("degraded", degraded, bool), ("queue-length", len as i64, i64),);
+                }
+            }
+        }
+        level == SaturationLevel::Shed
+    }
+
+    /// Whether a worker is reconnecting to the database
+    fn is_disconnected(&self) -> bool {
+        self.workers.iter().any(|worker| worker.status.disconnected.load(Ordering::Relaxed))
+    }
+
+    /// Spool a live event while a worker is reconnecting, or while the events spooled before it are
+    /// replayed so they are queued in order. Returns whether the event was spooled
+    fn spool(&mut self, record: impl FnOnce() -> SpoolRecord) -> Result<bool, GeyserPluginError> {
+        if self.replaying || !self.is_startup_done.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+        let disconnected = self.is_disconnected();
+        match &mut self.spool {
+            Some(spool) if disconnected || !spool.is_empty() => {
+                spool.append(&record())?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Queue the next batch of spooled events, once every worker is connected and while the channel isn't filling up
+    fn replay_spool(&mut self) -> Result<(), GeyserPluginError> {
+        if self.replaying || !self.is_startup_done.load(Ordering::Relaxed) || self.saturation_level != SaturationLevel::Normal || self.is_disconnected() {
+            return Ok(());
+        }
+        let records = match &mut self.spool {
+            Some(spool) if !spool.is_empty() => spool.next_batch()?,
+            _ => return Ok(()),
+        };
+        self.replaying = true;
+        let result = records.into_iter().try_for_each(|record| match record {
+            SpoolRecord::Account(account) => self.update_account(&account.as_replica(), account.slot, account.is_startup),
+            SpoolRecord::ClosedAccount(account) => self.close_account(&account.as_replica(), account.slot),
+            SpoolRecord::Slot { slot, parent, status } => self.update_slot_status(slot, parent, status.into()),
+            SpoolRecord::Transaction(transaction) => {
+                let (signature, is_vote, index, slot) = (transaction.signature, transaction.is_vote, transaction.index, transaction.slot);
+                match transaction.into_replica_parts() {
+                    Ok((sanitized, meta)) => self.log_transaction_info(
+                        &ReplicaTransactionInfoV2 {
+                            signature: &signature,
+                            is_vote,
+                            transaction: &sanitized,
+                            transaction_status_meta: &meta,
+                            index,
+                        },
+                        slot,
+                    ),
+                    Err(err) => {
+                        warn!("[ParallelClient] skipping spooled transaction {} error=[{}]", signature, err);
+                        Ok(())
+                    }
+                }
+            }
+            SpoolRecord::Block(block) => self.update_block_metadata(&block.as_replica()),
+        });
+        self.replaying = false;
+        result
+    }
+
+    /// Drain the queued requests, stop the workers and account for the requests left behind
     pub fn join(&mut self) -> thread::Result<()> {
+        let started = Instant::now();
+        for database in &mut self.databases {
+            database.join()?;
+        }
+        info!("[ParallelClient] draining {} queued requests", self.receiver.len());
         self.exit_worker.store(true, Ordering::Relaxed);
-        while !self.workers.is_empty() {
-            let worker = self.workers.pop();
-            if worker.is_none() {
-                break;
-            }
-            let worker = worker.unwrap();
-            let result = worker.join().unwrap();
+        while let Some(worker) = self.workers.pop() {
+            let result = worker.handle.join().unwrap();
             if result.is_err() {
                 error!("The worker thread has failed: {:?}", result);
             }
         }
+        self.join_retired_workers(true);
+        for work in self.receiver.try_iter() {
+            sub_queued_bytes(Queue::Requests, work.size_bytes());
+            record_unload_request(work.name(), "dropped", 1);
+        }
+        #[cfg(feature = "webhook")]
+        for webhook in &mut self.webhooks {
+            webhook.stop();
+        }
+        if let Some(commitment_buffer) = &mut self.commitment_buffer {
+            record_unload_request("update_account", "dropped", commitment_buffer.clear() as u64);
+        }
+        // the spooled events are replayed by the next run
+        if let Some(spool) = &mut self.spool {
+            match spool.flush() {
+                Ok(()) if !spool.is_empty() => info!("[ParallelClient] {} spooled bytes left to replay", spool.bytes()),
+                Ok(()) => {}
+                Err(err) => error!("[ParallelClient] failed to flush the spool {}", err),
+            }
+        }
 
+        // the summary of the main database accounts for the requests of every database
+        if self.config.database.is_some() {
+            return Ok(());
+        }
+        let summary = UnloadSummary::new(started.elapsed().as_millis() as u64);
+        summary.log();
+        if self.config.unload.write_summary {
+            if let Err(err) = summary.write(&self.config) {
+                error!("[unload_summary] failed to write the summary {}", err);
+            }
+        }
         Ok(())
     }
 
     pub fn update_account(&mut self, account: &ReplicaAccountInfoV2, slot: u64, is_startup: bool) -> Result<(), GeyserPluginError> {
-        if self.last_report.should_update(30000) {
-            datapoint_debug!("postgres-plugin-stats", ("message-queue-length", self.sender.len() as i64, i64),);
+        self.queue_account(account, slot, is_startup, false)
+    }
+
+    /// Queue the close of a live account the selectors didn't pick, only written by the databases detecting closes
+    pub fn close_account(&mut self, account: &ReplicaAccountInfoV2, slot: u64) -> Result<(), GeyserPluginError> {
+        self.queue_account(account, slot, false, true)
+    }
+
+    fn queue_account(&mut self, account: &ReplicaAccountInfoV2, slot: u64, is_startup: bool, close_only: bool) -> Result<(), GeyserPluginError> {
+        self.check_workers();
+        self.replay_spool()?;
+        if self.spool(|| match close_only {
+            true => SpoolRecord::ClosedAccount(RecordedAccount::new(account, slot, is_startup)),
+            false => SpoolRecord::Account(RecordedAccount::new(account, slot, is_startup)),
+        })? {
+            return Ok(());
+        }
+        if self.check_saturation() && !is_startup {
+            registry().inc_counter(SHED_TOTAL, &[("request", "update_account")], 1);
+            return Ok(());
         }
         let mut measure = Measure::start("geyser-plugin-posgres-create-work-item");
-        let wrk_item = WorkRequest::UpdateAccount(Box::new(UpdateAccountRequest {
-            account: DbAccountInfo::new(account, slot),
-            is_startup,
-        }));
+        let account = DbAccountInfo::new(account, slot);
+        // the startup accounts are rooted
+        let (account, close_only) = match &mut self.commitment_buffer {
+            Some(commitment_buffer) if !is_startup => match commitment_buffer.push(account, close_only) {
+                Some(update) => update,
+                None => return Ok(()),
+            },
+            _ => (account, close_only),
+        };
         measure.stop();
-        inc_new_counter_debug!("geyser-plugin-posgres-create-work-item-us", measure.as_us() as usize, 100000, 100000);
+        registry().observe_us(ENQUEUE_US, &[("stage", "create_work_item")], measure.as_us());
+        self.send_account(account, is_startup, close_only)
+    }
 
+    /// Send an update to the databases, the webhooks and the workers. A close only reaches the workers of the
+    /// databases detecting closes, the webhooks and the sinks only get the accounts the selectors picked
+    fn send_account(&self, account: DbAccountInfo, is_startup: bool, close_only: bool) -> Result<(), GeyserPluginError> {
+        for database in &self.databases {
+            database.send_account(account.clone(), is_startup, close_only)?;
+        }
+        #[cfg(feature = "webhook")]
+        for webhook in self.webhooks.iter().filter(|_| !close_only) {
+            webhook.update_account(&account, is_startup);
+        }
+        if close_only && self.config.close_detection.is_none() {
+            account.recycle();
+            return Ok(());
+        }
         let mut measure = Measure::start("geyser-plugin-posgres-send-msg");
-        if let Err(err) = self.sender.send(wrk_item) {
+        let pubkey = account.pubkey.clone();
+        let wrk_item = WorkRequest::UpdateAccount(Box::new(UpdateAccountRequest {
+            account,
+            is_startup,
+            close_only,
+            span: Span::current(),
+        }));
+        if let Err(err) = self.send(wrk_item) {
             return Err(GeyserPluginError::AccountsUpdateError {
-                msg: format!("Failed to update the account {:?}, error: {:?}", bs58::encode(&account.pubkey).into_string(), err),
+                msg: format!("Failed to update the account {:?}, error: {:?}", bs58::encode(&pubkey).into_string(), err),
             });
         }
         measure.stop();
-        inc_new_counter_debug!("geyser-plugin-posgres-send-msg-us", measure.as_us() as usize, 100000, 100000);
+        registry().observe_us(ENQUEUE_US, &[("stage", "send")], measure.as_us());
         Ok(())
     }
 
     pub fn update_slot_status(&mut self, slot: u64, parent: Option<u64>, status: SlotStatus) -> Result<(), GeyserPluginError> {
-        if let Err(err) = self.sender.send(WorkRequest::UpdateSlot(Box::new(UpdateSlotRequest { slot, parent, slot_status: status }))) {
+        self.check_workers();
+        self.replay_spool()?;
+        if self.spool(|| SpoolRecord::Slot {
+            slot,
+            parent,
+            status: RecordedSlotStatus::from(&status),
+        })? {
+            return Ok(());
+        }
+        // the updates a slot releases are queued ahead of its status
+        if let Some(commitment_buffer) = &mut self.commitment_buffer {
+            for (account, close_only) in commitment_buffer.update_slot_status(slot, parent, status) {
+                self.send_account(account, false, close_only)?;
+            }
+        }
+        let gap = self.slot_watermarks.update(slot, parent, status);
+        self.send_slot_status(slot, parent, status, gap)
+    }
+
+    fn send_slot_status(&mut self, slot: u64, parent: Option<u64>, status: SlotStatus, gap: Option<SlotGap>) -> Result<(), GeyserPluginError> {
+        if let Err(err) = self.send(WorkRequest::UpdateSlot(Box::new(UpdateSlotRequest {
+            slot,
+            parent,
+            slot_status: status,
+            gap,
+            span: Span::current(),
+        }))) {
             return Err(GeyserPluginError::SlotStatusUpdateError {
                 msg: format!("Failed to update the slot {:?}, error: {:?}", slot, err),
             });
         }
+        for database in &mut self.databases {
+            database.send_slot_status(slot, parent, status, gap)?;
+        }
+        #[cfg(feature = "webhook")]
+        for webhook in &self.webhooks {
+            webhook.update_slot_status(slot, parent, status);
+        }
         Ok(())
     }
 
     pub fn update_block_metadata(&mut self, block_info: &ReplicaBlockInfo) -> Result<(), GeyserPluginError> {
-        if let Err(err) = self.sender.send(WorkRequest::UpdateBlockMetadata(Box::new(UpdateBlockMetadataRequest {
+        self.replay_spool()?;
+        if self.spool(|| SpoolRecord::Block(RecordedBlock::new(block_info)))? {
+            return Ok(());
+        }
+        if let Err(err) = self.send(WorkRequest::UpdateBlockMetadata(Box::new(UpdateBlockMetadataRequest {
             block_info: DbBlockInfo::from(block_info),
+            span: Span::current(),
         }))) {
             return Err(GeyserPluginError::SlotStatusUpdateError {
                 msg: format!("Failed to update the block metadata at slot {:?}, error: {:?}", block_info.slot, err),
@@ -158,12 +688,44 @@ impl ParallelClient {
         Ok(())
     }
 
-    pub fn notify_end_of_startup(&mut self) -> Result<(), GeyserPluginError> {
-        info!("[notify_end_of_startup]");
-        // Ensure all items in the queue has been received by the workers
+    pub fn update_selector_stats(&mut self, stats: Vec<DbSelectorStat>) -> Result<(), GeyserPluginError> {
+        if let Err(err) = self.send(WorkRequest::UpdateSelectorStats(Box::new(UpdateSelectorStatsRequest { stats }))) {
+            return Err(GeyserPluginError::SlotStatusUpdateError {
+                msg: format!("Failed to update the selector stats, error: {:?}", err),
+            });
+        }
+        Ok(())
+    }
+
+    /// Report a summary of the snapshot load at the end of startup
+    pub fn set_startup_tracker(&mut self, startup_tracker: StartupTracker) {
+        self.startup_tracker = Some(startup_tracker);
+    }
+
+    /// Report the slots missed since the watermarks of the previous runs
+    pub fn set_slot_watermarks(&mut self, slot_watermarks: SlotWatermarks) {
+        self.slot_watermarks = slot_watermarks;
+    }
+
+    /// Forward the events of the handlers assigned to the `databases` to their clients
+    pub fn set_databases(&mut self, databases: Vec<ParallelClient>) {
+        self.databases = databases;
+    }
+
+    /// Block until the workers have received every queued request
+    pub fn wait_for_empty_queue(&self) {
         while !self.sender.is_empty() {
             sleep(Duration::from_millis(100));
         }
+        for database in &self.databases {
+            database.wait_for_empty_queue();
+        }
+    }
+
+    pub fn notify_end_of_startup(&mut self) -> Result<(), GeyserPluginError> {
+        info!("[notify_end_of_startup]");
+        // Ensure all items in the queue has been received by the workers
+        self.wait_for_empty_queue();
         self.is_startup_done.store(true, Ordering::Relaxed);
 
         // Wait for all worker threads to be done with flushing
@@ -175,16 +737,49 @@ impl ParallelClient {
             );
             sleep(Duration::from_millis(100));
         }
+        // the startup accounts pending in a retired worker are flushed before it exits
+        self.join_retired_workers(true);
+        for database in &mut self.databases {
+            database.notify_end_of_startup()?;
+        }
+        // the startup progress is recorded in the main database
+        if self.config.database.is_none() {
+            if let Err(err) = StartupProgressHandler::complete(&self.config) {
+                error!("[notify_end_of_startup] failed to complete the startup progress {}", err);
+            }
+        }
+        if let Some(startup_tracker) = self.startup_tracker.take() {
+            if let Err(err) = startup_tracker.finish(&self.config) {
+                error!("[notify_end_of_startup] failed to report the startup summary {}", err);
+            }
+        }
         Ok(())
     }
 
     pub fn log_transaction_info(&mut self, transaction_info: &ReplicaTransactionInfoV2, slot: u64) -> Result<(), GeyserPluginError> {
+        self.replay_spool()?;
+        if self.spool(|| SpoolRecord::Transaction(Box::new(RecordedTransaction::new(transaction_info, slot))))? {
+            return Ok(());
+        }
+        if self.check_saturation() {
+            registry().inc_counter(SHED_TOTAL, &[("request", "log_transaction")], 1);
+            return Ok(());
+        }
         self.transaction_write_version.fetch_add(1, Ordering::Relaxed);
+        let write_version = self.transaction_write_version.load(Ordering::Relaxed);
+        for database in &self.databases {
+            database.send_transaction(transaction_info, slot, write_version)?;
+        }
+        self.send_transaction(transaction_info, slot, write_version)
+    }
+
+    fn send_transaction(&self, transaction_info: &ReplicaTransactionInfoV2, slot: u64, write_version: u64) -> Result<(), GeyserPluginError> {
         let wrk_item = WorkRequest::LogTransaction(Box::new(LogTransactionRequest {
-            transaction_info: build_db_transaction(slot, transaction_info, self.transaction_write_version.load(Ordering::Relaxed)),
+            transaction_info: build_db_transaction(slot, transaction_info, write_version),
+            span: Span::current(),
         }));
 
-        if let Err(err) = self.sender.send(wrk_item) {
+        if let Err(err) = self.send(wrk_item) {
             return Err(GeyserPluginError::SlotStatusUpdateError {
                 msg: format!("Failed to update the transaction, error: {:?}", err),
             });
@@ -192,3 +787,37 @@ impl ParallelClient {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saturation_level() {
+        let mut config = QueueSaturationConfig::default();
+        assert_eq!(saturation_level(&config, 0, 100), SaturationLevel::Normal);
+        assert_eq!(saturation_level(&config, 74, 100), SaturationLevel::Normal);
+        assert_eq!(saturation_level(&config, 75, 100), SaturationLevel::Warn);
+        assert_eq!(saturation_level(&config, 100, 100), SaturationLevel::Warn);
+        config.shed = true;
+        assert_eq!(saturation_level(&config, 94, 100), SaturationLevel::Warn);
+        assert_eq!(saturation_level(&config, 95, 100), SaturationLevel::Shed);
+    }
+
+    #[test]
+    fn test_degraded_mode() {
+        let config = DegradedModeConfig::default();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut degraded_mode = DegradedMode::default();
+        assert_eq!(degraded_mode.update(&config, true, at(0)), None);
+        assert_eq!(degraded_mode.update(&config, true, at(29)), None);
+        assert_eq!(degraded_mode.update(&config, true, at(30)), Some(true));
+        assert_eq!(degraded_mode.update(&config, false, at(31)), None);
+        // saturated again before recovering
+        assert_eq!(degraded_mode.update(&config, true, at(80)), None);
+        assert_eq!(degraded_mode.update(&config, false, at(81)), None);
+        assert_eq!(degraded_mode.update(&config, false, at(141)), Some(false));
+        assert_eq!(degraded_mode.update(&config, false, at(200)), None);
+    }
+}
@@ -1,40 +1,101 @@
 use crate::abort;
 use crate::config::GeyserPluginPostgresConfig;
+use crate::memory_stats::sub_queued_bytes;
+use crate::memory_stats::Queue;
+use crate::metrics::registry;
+use crate::metrics::DB_RECONNECTS_TOTAL;
+use crate::metrics::ERRORS_TOTAL;
+use crate::metrics::REQUESTS_TOTAL;
+use crate::metrics::SINK_ERRORS_TOTAL;
+use crate::metrics::WORKER_RECV_US;
+use crate::postgres_client::connection_pool::ConnectionPool;
+use crate::postgres_client::unload_summary::record_unload_request;
+use crate::postgres_client::CustomHandlers;
 use crate::postgres_client::DbAccountInfo;
 use crate::postgres_client::DbBlockInfo;
+use crate::postgres_client::DbSelectorStat;
 use crate::postgres_client::DbTransaction;
+use crate::postgres_client::IdlRegistry;
 use crate::postgres_client::PostgresClient;
 use crate::postgres_client::SimplePostgresClient;
+use crate::postgres_client::SlotGap;
+use crate::sinks::build_sinks;
+use crate::sinks::EventSink;
+use crate::traces::child_span;
 use crossbeam_channel::Receiver;
 use crossbeam_channel::RecvTimeoutError;
 use log::*;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
 use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
 use solana_measure::measure::Measure;
-use solana_metrics::*;
+use solana_sdk::timing::timestamp;
+use std::mem::size_of;
 use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::thread::sleep;
 use std::time::Duration;
+use std::time::Instant;
+use tracing::Span;
+
+const DEFAULT_RECV_TIMEOUT_MS: u64 = 500;
 
 pub struct UpdateAccountRequest {
     pub account: DbAccountInfo,
     pub is_startup: bool,
+    /// Only write the close of the account, the selectors didn't pick it
+    pub close_only: bool,
+    /// The span of the callback that queued the request
+    pub span: Span,
 }
 
 pub struct UpdateSlotRequest {
     pub slot: u64,
     pub parent: Option<u64>,
     pub slot_status: SlotStatus,
+    /// The slots the parent revealed were missed at the status
+    pub gap: Option<SlotGap>,
+    pub span: Span,
 }
 
 pub struct LogTransactionRequest {
     pub transaction_info: DbTransaction,
+    pub span: Span,
 }
 
 pub struct UpdateBlockMetadataRequest {
     pub block_info: DbBlockInfo,
+    pub span: Span,
+}
+
+pub struct UpdateSelectorStatsRequest {
+    pub stats: Vec<DbSelectorStat>,
+}
+
+/// Liveness of a worker thread, watched by the parallel client
+#[derive(Debug, Default)]
+pub struct WorkerStatus {
+    /// Set when the worker was replaced, it exits once it gets back to its loop
+    pub retired: AtomicBool,
+    /// Set once the worker connected to the database
+    pub initialized: AtomicBool,
+    /// Set once the worker flushed its startup accounts
+    pub startup_done: AtomicBool,
+    /// When the worker last went through its loop, in milliseconds since the unix epoch
+    pub last_progress_ms: AtomicU64,
+    /// Set while the worker is reconnecting to the database, the live events are spooled meanwhile
+    pub disconnected: AtomicBool,
+}
+
+impl WorkerStatus {
+    pub fn new() -> Self {
+        Self {
+            last_progress_ms: AtomicU64::new(timestamp()),
+            ..Self::default()
+        }
+    }
 }
 
 #[warn(clippy::large_enum_variant)]
@@ -43,19 +104,78 @@ pub enum WorkRequest {
     UpdateSlot(Box<UpdateSlotRequest>),
     LogTransaction(Box<LogTransactionRequest>),
     UpdateBlockMetadata(Box<UpdateBlockMetadataRequest>),
+    UpdateSelectorStats(Box<UpdateSelectorStatsRequest>),
+}
+
+impl WorkRequest {
+    /// The request label of the metrics
+    pub fn name(&self) -> &'static str {
+        match self {
+            WorkRequest::UpdateAccount(_) => "update_account",
+            WorkRequest::UpdateSlot(_) => "update_slot",
+            WorkRequest::LogTransaction(_) => "log_transaction",
+            WorkRequest::UpdateBlockMetadata(_) => "update_block_metadata",
+            WorkRequest::UpdateSelectorStats(_) => "update_selector_stats",
+        }
+    }
+
+    /// The approximate bytes held by the request while it is queued
+    pub fn size_bytes(&self) -> usize {
+        size_of::<WorkRequest>()
+            + match self {
+                WorkRequest::UpdateAccount(request) => size_of::<UpdateAccountRequest>() + request.account.heap_bytes(),
+                WorkRequest::UpdateSlot(_) => size_of::<UpdateSlotRequest>(),
+                WorkRequest::LogTransaction(request) => size_of::<LogTransactionRequest>() + request.transaction_info.heap_bytes(),
+                WorkRequest::UpdateBlockMetadata(_) => size_of::<UpdateBlockMetadataRequest>(),
+                WorkRequest::UpdateSelectorStats(request) => size_of::<UpdateSelectorStatsRequest>() + request.stats.len() * size_of::<DbSelectorStat>(),
+            }
+    }
 }
 
 pub struct ParallelClientWorker {
-    client: SimplePostgresClient,
+    /// The database client, none when the events are only published to the sinks
+    client: Option<SimplePostgresClient>,
+    /// The outputs the events are published to next to the database
+    sinks: Vec<Box<dyn EventSink>>,
     /// Indicating if accounts notification during startup is done.
     is_startup_done: bool,
+    /// How long to wait for work before checking for due live updates.
+    recv_timeout: Duration,
+    /// How long to keep running the queued requests once the plugin is unloading.
+    drain_timeout: Duration,
+    /// The config the lost connections are made again from
+    config: GeyserPluginPostgresConfig,
+    /// Set once the plugin is unloading
+    exit_worker: Arc<AtomicBool>,
 }
 
 impl ParallelClientWorker {
-    pub fn new(config: GeyserPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
-        let result = SimplePostgresClient::new(&config);
+    pub fn new(
+        config: GeyserPluginPostgresConfig,
+        idl_registry: Arc<IdlRegistry>,
+        custom_handlers: &CustomHandlers,
+        pool: Option<Arc<ConnectionPool>>,
+        exit_worker: Arc<AtomicBool>,
+    ) -> Result<Self, GeyserPluginError> {
+        let result = match config.writes_postgres() {
+            true => SimplePostgresClient::new_with_pool(&config, idl_registry, custom_handlers, pool).map(Some),
+            false => Ok(None),
+        }
+        .and_then(|client| Ok((client, build_sinks(&config)?)));
+        let recv_timeout = match config.flush_interval_ms {
+            0 => Duration::from_millis(DEFAULT_RECV_TIMEOUT_MS),
+            flush_interval_ms => Duration::from_millis(flush_interval_ms.min(DEFAULT_RECV_TIMEOUT_MS)),
+        };
         match result {
-            Ok(client) => Ok(ParallelClientWorker { client, is_startup_done: false }),
+            Ok((client, sinks)) => Ok(ParallelClientWorker {
+                client,
+                sinks,
+                is_startup_done: false,
+                recv_timeout,
+                drain_timeout: Duration::from_secs(config.unload.drain_timeout_secs),
+                config,
+                exit_worker,
+            }),
             Err(err) => {
                 error!("[ParallelClientWorker] error=[{}]", err);
                 Err(err)
@@ -66,62 +186,84 @@ impl ParallelClientWorker {
     pub fn do_work(
         &mut self,
         receiver: Receiver<WorkRequest>,
-        exit_worker: Arc<AtomicBool>,
         is_startup_done: Arc<AtomicBool>,
         startup_done_count: Arc<AtomicUsize>,
+        status: Arc<WorkerStatus>,
         panic_on_db_errors: bool,
     ) -> Result<(), GeyserPluginError> {
-        while !exit_worker.load(Ordering::Relaxed) {
+        let exit_worker = self.exit_worker.clone();
+        // once unloading, the queued requests are run until the channel is empty or the drain times out
+        let mut drain_deadline: Option<Instant> = None;
+        while !status.retired.load(Ordering::Relaxed) {
+            let exiting = exit_worker.load(Ordering::Relaxed);
+            if exiting && Instant::now() >= *drain_deadline.get_or_insert_with(|| Instant::now() + self.drain_timeout) {
+                warn!("[ParallelClientWorker] drain timed out with {} requests queued", receiver.len());
+                break;
+            }
+            status.last_progress_ms.store(timestamp(), Ordering::Relaxed);
             let mut measure = Measure::start("geyser-plugin-postgres-worker-recv");
-            let work = receiver.recv_timeout(Duration::from_millis(500));
+            let work = receiver.recv_timeout(self.recv_timeout);
             measure.stop();
-            inc_new_counter_debug!("geyser-plugin-postgres-worker-recv-us", measure.as_us() as usize, 100000, 100000);
+            registry().observe_us(WORKER_RECV_US, &[], measure.as_us());
             match work {
-                Ok(work) => match work {
-                    WorkRequest::UpdateAccount(request) => {
-                        if let Err(err) = self.client.update_account(request.account, request.is_startup) {
-                            error!("Failed to update account: ({})", err);
-                            if panic_on_db_errors {
-                                abort();
-                            }
-                        }
+                Ok(work) => {
+                    sub_queued_bytes(Queue::Requests, work.size_bytes());
+                    if exiting {
+                        record_unload_request(work.name(), "processed", 1);
                     }
-                    WorkRequest::UpdateSlot(request) => {
-                        if let Err(err) = self.client.update_slot_status(request.slot, request.parent, request.slot_status) {
-                            error!("Failed to update slot: ({})", err);
-                            if panic_on_db_errors {
-                                abort();
+                    match work {
+                        WorkRequest::UpdateAccount(request) => {
+                            registry().inc_counter(REQUESTS_TOTAL, &[("request", "update_account")], 1);
+                            let _span = child_span!(&request.span, "worker_update_account").entered();
+                            if !request.close_only {
+                                self.publish("update_account", panic_on_db_errors, |sink| sink.update_account(&request.account, request.is_startup));
                             }
+                            let mut account = Some(request.account);
+                            // the account is in the batches kept from the lost connection once handed over
+                            self.write("update_account", panic_on_db_errors, &status, |client| match account.take() {
+                                Some(account) if request.close_only => client.close_account(account),
+                                Some(account) => client.update_account(account, request.is_startup),
+                                None => client.flush_retained(),
+                            });
                         }
-                    }
-                    WorkRequest::LogTransaction(transaction_log_info) => {
-                        if let Err(err) = self.client.log_transaction(transaction_log_info.transaction_info) {
-                            error!("Failed to update transaction: ({})", err);
-                            if panic_on_db_errors {
-                                abort();
-                            }
+                        WorkRequest::UpdateSlot(request) => {
+                            registry().inc_counter(REQUESTS_TOTAL, &[("request", "update_slot")], 1);
+                            let _span = child_span!(&request.span, "worker_update_slot").entered();
+                            self.publish("update_slot", panic_on_db_errors, |sink| sink.update_slot_status(request.slot, request.parent, request.slot_status));
+                            self.write("update_slot", panic_on_db_errors, &status, |client| {
+                                client.update_slot_status(request.slot, request.parent, request.slot_status, request.gap)
+                            });
                         }
-                    }
-                    WorkRequest::UpdateBlockMetadata(block_info) => {
-                        if let Err(err) = self.client.update_block_metadata(block_info.block_info) {
-                            error!("Failed to update block metadata: ({})", err);
-                            if panic_on_db_errors {
-                                abort();
-                            }
+                        WorkRequest::LogTransaction(transaction_log_info) => {
+                            registry().inc_counter(REQUESTS_TOTAL, &[("request", "log_transaction")], 1);
+                            let _span = child_span!(&transaction_log_info.span, "worker_log_transaction").entered();
+                            self.publish("log_transaction", panic_on_db_errors, |sink| sink.log_transaction(&transaction_log_info.transaction_info));
+                            self.write("log_transaction", panic_on_db_errors, &status, |client| client.log_transaction(&transaction_log_info.transaction_info));
+                        }
+                        WorkRequest::UpdateBlockMetadata(block_info) => {
+                            registry().inc_counter(REQUESTS_TOTAL, &[("request", "update_block_metadata")], 1);
+                            let _span = child_span!(&block_info.span, "worker_update_block_metadata").entered();
+                            self.publish("update_block_metadata", panic_on_db_errors, |sink| sink.update_block_metadata(&block_info.block_info));
+                            self.write("update_block_metadata", panic_on_db_errors, &status, |client| client.update_block_metadata(&block_info.block_info));
+                        }
+                        WorkRequest::UpdateSelectorStats(request) => {
+                            registry().inc_counter(REQUESTS_TOTAL, &[("request", "update_selector_stats")], 1);
+                            self.write("update_selector_stats", panic_on_db_errors, &status, |client| client.update_selector_stats(&request.stats));
                         }
                     }
-                },
+                }
                 Err(err) => match err {
+                    RecvTimeoutError::Timeout if exiting => break,
                     RecvTimeoutError::Timeout => {
+                        self.publish("flush", panic_on_db_errors, |sink| sink.flush());
+                        self.write("flush_live_updates", panic_on_db_errors, &status, |client| client.flush_live_updates(false));
                         if !self.is_startup_done && is_startup_done.load(Ordering::Relaxed) {
-                            if let Err(err) = self.client.notify_end_of_startup() {
-                                error!("Error in notifying end of startup: ({})", err);
-                                if panic_on_db_errors {
-                                    abort();
-                                }
-                            }
+                            self.write("notify_end_of_startup", panic_on_db_errors, &status, |client| client.notify_end_of_startup());
                             self.is_startup_done = true;
-                            startup_done_count.fetch_add(1, Ordering::Relaxed);
+                            if !status.retired.load(Ordering::Relaxed) {
+                                status.startup_done.store(true, Ordering::Relaxed);
+                                startup_done_count.fetch_add(1, Ordering::Relaxed);
+                            }
                         }
 
                         continue;
@@ -136,6 +278,122 @@ impl ParallelClientWorker {
                 },
             }
         }
+
+        self.publish("flush", panic_on_db_errors, |sink| sink.flush());
+        let client = match &mut self.client {
+            Some(client) => client,
+            None => return Ok(()),
+        };
+        let exiting = exit_worker.load(Ordering::Relaxed);
+        let pending = client.pending_live_requests();
+        if let Err(err) = client.flush_live_updates(true) {
+            error!("Failed to flush live updates on exit: ({})", err);
+            registry().inc_counter(ERRORS_TOTAL, &[("request", "flush_live_updates")], 1);
+            if exiting {
+                record_unload_request("update_account", "unflushed", pending as u64);
+            }
+            if panic_on_db_errors {
+                abort();
+            }
+        }
+        let pending = client.pending_startup_accounts();
+        if let Err(err) = client.flush_startup_accounts("flush_startup_accounts") {
+            error!("Failed to flush startup accounts on exit: ({})", err);
+            registry().inc_counter(ERRORS_TOTAL, &[("request", "flush_startup_accounts")], 1);
+            if exiting {
+                record_unload_request("update_account", "unflushed", pending as u64);
+            }
+            if panic_on_db_errors {
+                abort();
+            }
+        }
+        client.release_connection();
         Ok(())
     }
+
+    /// Run a write of `request` on the database client. A write failing on a lost connection is run
+    /// again once the worker reconnected, other failures are logged and counted.
+    fn write(&mut self, request: &'static str, panic_on_db_errors: bool, status: &WorkerStatus, mut write: impl FnMut(&mut SimplePostgresClient) -> Result<(), GeyserPluginError>) {
+        let client = match &mut self.client {
+            Some(client) => client,
+            None => return,
+        };
+        while let Err(err) = write(client) {
+            error!("Failed to run {}: ({})", request, err);
+            registry().inc_counter(ERRORS_TOTAL, &[("request", request)], 1);
+            if self.config.reconnect.max_retries == 0 || !client.is_connection_lost() {
+                if panic_on_db_errors {
+                    abort();
+                }
+                break;
+            }
+            if !Self::reconnect(client, &self.config, panic_on_db_errors, status, &self.exit_worker) {
+                error!("[ParallelClientWorker] dropping {}, the connection is still lost", request);
+                break;
+            }
+        }
+        // a pooled connection serves the other workers in between requests
+        client.release_connection();
+    }
+
+    /// Reconnect with an exponential backoff. When the live events are spooled the worker keeps reconnecting until
+    /// the plugin is unloaded, otherwise it gives up once `max_retries` attempts failed and the validator is aborted
+    /// with `panic_on_db_errors`. Returns whether the worker reconnected
+    fn reconnect(client: &mut SimplePostgresClient, config: &GeyserPluginPostgresConfig, panic_on_db_errors: bool, status: &WorkerStatus, exit_worker: &AtomicBool) -> bool {
+        let reconnect = &config.reconnect;
+        status.disconnected.store(true, Ordering::Relaxed);
+        let mut attempt = 0;
+        while attempt < reconnect.max_retries || (config.spool.is_some() && !exit_worker.load(Ordering::Relaxed)) {
+            let delay = reconnect.backoff(attempt, rand::random::<f64>());
+            warn!(
+                "[ParallelClientWorker] connection lost, reconnecting in {:?} attempt=[{}/{}]",
+                delay,
+                attempt + 1,
+                reconnect.max_retries
+            );
+            sleep(delay);
+            // a reconnecting worker is not taken for a stalled one by the watchdog
+            status.last_progress_ms.store(timestamp(), Ordering::Relaxed);
+            match client.reconnect(config) {
+                Ok(()) => {
+                    info!("[ParallelClientWorker] reconnected attempt=[{}/{}]", attempt + 1, reconnect.max_retries);
+                    registry().inc_counter(DB_RECONNECTS_TOTAL, &[("outcome", "success")], 1);
+                    status.disconnected.store(false, Ordering::Relaxed);
+                    return true;
+                }
+                Err(err) => {
+                    error!("[ParallelClientWorker] failed to reconnect: ({})", err);
+                    registry().inc_counter(DB_RECONNECTS_TOTAL, &[("outcome", "failure")], 1);
+                }
+            }
+            attempt = attempt.saturating_add(1);
+        }
+        if config.spool.is_some() {
+            warn!("[ParallelClientWorker] unloading while disconnected, giving up reconnecting after {} attempts", attempt);
+            return false;
+        }
+        registry().inc_counter(DB_RECONNECTS_TOTAL, &[("outcome", "exhausted")], 1);
+        if panic_on_db_errors {
+            error!("[ParallelClientWorker] aborting, failed to reconnect after {} attempts", reconnect.max_retries);
+            abort();
+        }
+        error!(
+            "[ParallelClientWorker] failed to reconnect after {} attempts, reconnecting again on the next request",
+            reconnect.max_retries
+        );
+        false
+    }
+
+    /// Publish an event to every sink, a failing sink doesn't keep the event from the others
+    fn publish(&mut self, request: &'static str, panic_on_db_errors: bool, mut publish: impl FnMut(&mut dyn EventSink) -> Result<(), GeyserPluginError>) {
+        for sink in self.sinks.iter_mut() {
+            if let Err(err) = publish(sink.as_mut()) {
+                error!("Failed to publish {} to the {} sink: ({})", request, sink.name(), err);
+                registry().inc_counter(SINK_ERRORS_TOTAL, &[("sink", sink.name()), ("request", request)], 1);
+                if panic_on_db_errors {
+                    abort();
+                }
+            }
+        }
+    }
 }
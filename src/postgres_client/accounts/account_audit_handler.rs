@@ -0,0 +1,367 @@
+use super::account_handler::AccountHandler;
+use super::DbAccountInfo;
+use crate::postgres_client::table_prefix::TablePrefix;
+use chrono::Utc;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+/// How the `account_audit` handler stores the data of each version of an account
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountAuditMode {
+    /// A full copy of the data per version
+    #[default]
+    Full,
+    /// The byte ranges changed since the previous version, with a full copy every `checkpoint_interval` versions
+    Diff,
+}
+
+/// * The `account_audit` section controls how the `account_audit` handler stores the versions of an account:
+/// "account_audit" : { "mode" : "diff", "checkpoint_interval" : 100, "chunk_bytes" : 32 }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccountAuditConfig {
+    pub mode: AccountAuditMode,
+    /// Versions stored as a delta before the next one is stored in full, bounding the versions a read applies
+    pub checkpoint_interval: u32,
+    /// The granularity in bytes the data is compared with, smaller chunks store smaller deltas with more ranges
+    pub chunk_bytes: u32,
+}
+
+impl Default for AccountAuditConfig {
+    fn default() -> Self {
+        Self {
+            mode: AccountAuditMode::Full,
+            checkpoint_interval: 100,
+            chunk_bytes: 32,
+        }
+    }
+}
+
+impl AccountAuditConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.checkpoint_interval == 0 {
+            return Err("account_audit \"checkpoint_interval\" must be greater than 0".to_string());
+        }
+        if self.chunk_bytes == 0 {
+            return Err("account_audit \"chunk_bytes\" must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Apply a `delta` of the `account_audit` table to the data of the version it was taken against
+pub fn apply_account_data_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, String> {
+    let read_u32 = |at: usize| {
+        delta
+            .get(at..at + 4)
+            .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()) as usize)
+            .ok_or_else(|| format!("account data delta truncated at byte {}", at))
+    };
+    let mut data = base.to_vec();
+    data.resize(read_u32(0)?, 0);
+    let mut at = 4;
+    while at < delta.len() {
+        let (start, length) = (read_u32(at)?, read_u32(at + 4)?);
+        let bytes = delta.get(at + 8..at + 8 + length).ok_or_else(|| format!("account data delta truncated at byte {}", at + 8))?;
+        data.get_mut(start..start + length)
+            .ok_or_else(|| format!("account data delta range {}..{} is out of the data", start, start + length))?
+            .copy_from_slice(bytes);
+        at += 8 + length;
+    }
+    Ok(data)
+}
+
+#[derive(Clone, Default)]
+pub struct AccountAuditHandler {
+    pub config: AccountAuditConfig,
+    pub prefix: TablePrefix,
+}
+
+impl AccountHandler for AccountAuditHandler {
+    fn init(&self, config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        if !self.enabled(config) {
+            return "".to_string();
+        };
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}account_audit (
+                id BIGSERIAL PRIMARY KEY,
+                pubkey BYTEA NOT NULL,
+                owner BYTEA,
+                lamports BIGINT NOT NULL,
+                executable BOOL NOT NULL,
+                rent_epoch BIGINT NOT NULL,
+                data BYTEA,
+                delta BYTEA,
+                base_id BIGINT,
+                slot BIGINT NOT NULL,
+                write_version BIGINT NOT NULL,
+                txn_signature BYTEA,
+                updated_on TIMESTAMP NOT NULL
+            );
+            CREATE UNIQUE INDEX IF NOT EXISTS {prefix}account_audit_version ON {prefix}account_audit (pubkey, slot, write_version);
+            CREATE TABLE IF NOT EXISTS {prefix}account_audit_state (
+                pubkey BYTEA PRIMARY KEY,
+                audit_id BIGINT NOT NULL,
+                data BYTEA NOT NULL,
+                slot BIGINT NOT NULL,
+                write_version BIGINT NOT NULL,
+                deltas INT NOT NULL
+            );
+            CREATE OR REPLACE FUNCTION {prefix}account_data_delta(previous_data BYTEA, next_data BYTEA, chunk_bytes INT) RETURNS BYTEA AS $$
+            DECLARE
+                delta BYTEA := int4send(length(next_data));
+                range_start INT := -1;
+                chunk_start INT := 0;
+            BEGIN
+                WHILE chunk_start < length(next_data) LOOP
+                    IF chunk_start >= length(previous_data)
+                        OR substr(previous_data, chunk_start + 1, chunk_bytes) IS DISTINCT FROM substr(next_data, chunk_start + 1, chunk_bytes) THEN
+                        IF range_start < 0 THEN
+                            range_start := chunk_start;
+                        END IF;
+                    ELSIF range_start >= 0 THEN
+                        delta := delta || int4send(range_start) || int4send(chunk_start - range_start) || substr(next_data, range_start + 1, chunk_start - range_start);
+                        range_start := -1;
+                    END IF;
+                    chunk_start := chunk_start + chunk_bytes;
+                END LOOP;
+                IF range_start >= 0 THEN
+                    delta := delta || int4send(range_start) || int4send(length(next_data) - range_start) || substr(next_data, range_start + 1, length(next_data) - range_start);
+                END IF;
+                RETURN delta;
+            END;
+            $$ LANGUAGE plpgsql IMMUTABLE;
+            CREATE OR REPLACE FUNCTION {prefix}account_data_patch(base_data BYTEA, delta BYTEA) RETURNS BYTEA AS $$
+            DECLARE
+                data_length INT := ('x' || encode(substr(delta, 1, 4), 'hex'))::BIT(32)::INT;
+                patched BYTEA;
+                read_at INT := 4;
+                range_start INT;
+                range_length INT;
+            BEGIN
+                IF length(base_data) >= data_length THEN
+                    patched := substr(base_data, 1, data_length);
+                ELSE
+                    patched := base_data || decode(repeat('00', data_length - length(base_data)), 'hex');
+                END IF;
+                WHILE read_at < length(delta) LOOP
+                    range_start := ('x' || encode(substr(delta, read_at + 1, 4), 'hex'))::BIT(32)::INT;
+                    range_length := ('x' || encode(substr(delta, read_at + 5, 4), 'hex'))::BIT(32)::INT;
+                    patched := substr(patched, 1, range_start) || substr(delta, read_at + 9, range_length) || substr(patched, range_start + range_length + 1);
+                    read_at := read_at + 8 + range_length;
+                END LOOP;
+                RETURN patched;
+            END;
+            $$ LANGUAGE plpgsql IMMUTABLE;
+            CREATE OR REPLACE FUNCTION {prefix}account_audit_data(audit_id BIGINT) RETURNS BYTEA AS $$
+            DECLARE
+                current_id BIGINT := audit_id;
+                next_id BIGINT;
+                row_data BYTEA;
+                row_delta BYTEA;
+                deltas BYTEA[] := ARRAY[]::BYTEA[];
+            BEGIN
+                LOOP
+                    SELECT a.base_id, a.data, a.delta INTO next_id, row_data, row_delta FROM {prefix}account_audit a WHERE a.id = current_id;
+                    IF NOT FOUND THEN
+                        RETURN NULL;
+                    END IF;
+                    EXIT WHEN row_data IS NOT NULL;
+                    deltas := array_prepend(row_delta, deltas);
+                    current_id := next_id;
+                END LOOP;
+                FOREACH row_delta IN ARRAY deltas LOOP
+                    row_data := {prefix}account_data_patch(row_data, row_delta);
+                END LOOP;
+                RETURN row_data;
+            END;
+            $$ LANGUAGE plpgsql STABLE;
+            CREATE OR REPLACE FUNCTION {prefix}account_audit_append(audit_pubkey BYTEA, audit_owner BYTEA, audit_lamports BIGINT, audit_executable BOOL,
+                audit_rent_epoch BIGINT, audit_data BYTEA, audit_slot BIGINT, audit_write_version BIGINT, audit_txn_signature BYTEA,
+                audit_updated_on TIMESTAMP, checkpoint_interval INT, chunk_bytes INT) RETURNS VOID AS $$
+            DECLARE
+                previous RECORD;
+                has_previous BOOLEAN;
+                delta BYTEA;
+                delta_count INT := 0;
+                new_id BIGINT;
+            BEGIN
+                SELECT s.audit_id, s.data, s.slot, s.write_version, s.deltas INTO previous FROM {prefix}account_audit_state s WHERE s.pubkey = audit_pubkey FOR UPDATE;
+                has_previous := FOUND;
+                -- a version older than the latest one has no delta to be stored as
+                IF has_previous AND (previous.slot, previous.write_version) >= (audit_slot, audit_write_version) THEN
+                    INSERT INTO {prefix}account_audit (pubkey, owner, lamports, executable, rent_epoch, data, slot, write_version, txn_signature, updated_on)
+                    VALUES (audit_pubkey, audit_owner, audit_lamports, audit_executable, audit_rent_epoch, audit_data, audit_slot, audit_write_version, audit_txn_signature, audit_updated_on)
+                    ON CONFLICT (pubkey, slot, write_version) DO NOTHING;
+                    RETURN;
+                END IF;
+                IF has_previous AND previous.deltas < checkpoint_interval THEN
+                    delta := {prefix}account_data_delta(previous.data, audit_data, chunk_bytes);
+                    delta_count := previous.deltas + 1;
+                END IF;
+                IF delta IS NULL OR length(delta) >= length(audit_data) THEN
+                    delta_count := 0;
+                    INSERT INTO {prefix}account_audit (pubkey, owner, lamports, executable, rent_epoch, data, slot, write_version, txn_signature, updated_on)
+                    VALUES (audit_pubkey, audit_owner, audit_lamports, audit_executable, audit_rent_epoch, audit_data, audit_slot, audit_write_version, audit_txn_signature, audit_updated_on)
+                    ON CONFLICT (pubkey, slot, write_version) DO NOTHING
+                    RETURNING id INTO new_id;
+                ELSE
+                    INSERT INTO {prefix}account_audit (pubkey, owner, lamports, executable, rent_epoch, delta, base_id, slot, write_version, txn_signature, updated_on)
+                    VALUES (audit_pubkey, audit_owner, audit_lamports, audit_executable, audit_rent_epoch, delta, previous.audit_id, audit_slot, audit_write_version, audit_txn_signature, audit_updated_on)
+                    ON CONFLICT (pubkey, slot, write_version) DO NOTHING
+                    RETURNING id INTO new_id;
+                END IF;
+                IF new_id IS NULL THEN
+                    RETURN;
+                END IF;
+                INSERT INTO {prefix}account_audit_state AS s (pubkey, audit_id, data, slot, write_version, deltas)
+                VALUES (audit_pubkey, new_id, audit_data, audit_slot, audit_write_version, delta_count)
+                ON CONFLICT (pubkey) DO UPDATE SET audit_id=excluded.audit_id, data=excluded.data, slot=excluded.slot, write_version=excluded.write_version, deltas=excluded.deltas;
+            END;
+            $$ LANGUAGE plpgsql;
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn account_match(&self, _account: &DbAccountInfo) -> bool {
+        true
+    }
+
+    /// Appends a version of the account, the `diff` mode stores its delta against the previous version
+    fn account_update(&self, account: &DbAccountInfo) -> String {
+        if !self.account_match(account) {
+            return "".to_string();
+        };
+        let txn_signature = account.txn_signature.as_deref().map_or("NULL".to_string(), |tx| format!("'\\x{}'", hex::encode(tx)));
+        match self.config.mode {
+            AccountAuditMode::Full => format!(
+                "
+                    INSERT INTO {prefix}account_audit (pubkey, owner, lamports, executable, rent_epoch, data, slot, write_version, txn_signature, updated_on) \
+                    VALUES ('\\x{0}', '\\x{1}', {2}, {3}, {4}, '\\x{5}', {6}, {7}, {8}, '{9}') \
+                    ON CONFLICT (pubkey, slot, write_version) DO NOTHING;
+                ",
+                hex::encode(&account.pubkey),
+                hex::encode(&account.owner),
+                account.lamports,
+                account.executable,
+                account.rent_epoch,
+                hex::encode(&account.data),
+                account.slot,
+                account.write_version,
+                txn_signature,
+                &Utc::now().naive_utc(),
+                prefix = self.prefix,
+            ),
+            AccountAuditMode::Diff => format!(
+                "SELECT {prefix}account_audit_append('\\x{0}', '\\x{1}', {2}, {3}, {4}, '\\x{5}', {6}, {7}, {8}, '{9}', {10}, {11});",
+                hex::encode(&account.pubkey),
+                hex::encode(&account.owner),
+                account.lamports,
+                account.executable,
+                account.rent_epoch,
+                hex::encode(&account.data),
+                account.slot,
+                account.write_version,
+                txn_signature,
+                &Utc::now().naive_utc(),
+                self.config.checkpoint_interval,
+                self.config.chunk_bytes,
+                prefix = self.prefix,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::AccountKey;
+    use solana_sdk::pubkey::Pubkey;
+
+    /// The delta the `account_data_delta` SQL function computes for `next` against `previous`: the length of `next`, then
+    /// the offset, the length and the bytes of each range of changed chunks, the integers as big endian u32
+    fn account_data_delta(previous: &[u8], next: &[u8], chunk_bytes: usize) -> Vec<u8> {
+        let mut delta = (next.len() as u32).to_be_bytes().to_vec();
+        let push_range = |delta: &mut Vec<u8>, start: usize, end: usize| {
+            delta.extend_from_slice(&(start as u32).to_be_bytes());
+            delta.extend_from_slice(&((end - start) as u32).to_be_bytes());
+            delta.extend_from_slice(&next[start..end]);
+        };
+        let mut range_start = None;
+        for chunk_start in (0..next.len()).step_by(chunk_bytes) {
+            let changed = chunk_start >= previous.len() || previous[chunk_start..(chunk_start + chunk_bytes).min(previous.len())] != next[chunk_start..(chunk_start + chunk_bytes).min(next.len())];
+            match (changed, range_start) {
+                (true, None) => range_start = Some(chunk_start),
+                (false, Some(start)) => {
+                    push_range(&mut delta, start, chunk_start);
+                    range_start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(start) = range_start {
+            push_range(&mut delta, start, next.len());
+        }
+        delta
+    }
+
+    #[test]
+    fn test_account_data_delta() {
+        let previous: Vec<u8> = (0..200).map(|i| i as u8).collect();
+        let mut next = previous.clone();
+        next[5] = 0xff;
+        next[100..110].fill(0xee);
+        let delta = account_data_delta(&previous, &next, 32);
+        // the length, then the first and fourth chunks
+        assert_eq!(delta.len(), 4 + (8 + 32) * 2);
+        assert_eq!(apply_account_data_delta(&previous, &delta).unwrap(), next);
+
+        // the data grows into a chunk of its own, then shrinks
+        next.extend_from_slice(&[1, 2, 3]);
+        let delta = account_data_delta(&previous, &next, 32);
+        assert_eq!(apply_account_data_delta(&previous, &delta).unwrap(), next);
+        let delta = account_data_delta(&next, &previous[..50], 32);
+        assert_eq!(apply_account_data_delta(&next, &delta).unwrap(), &previous[..50]);
+        assert_eq!(account_data_delta(&previous, &previous, 32), 200u32.to_be_bytes());
+
+        assert!(apply_account_data_delta(&previous, &delta[..2]).is_err());
+        assert!(apply_account_data_delta(&previous, &delta[..delta.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_account_audit_update() {
+        let account = DbAccountInfo {
+            pubkey: AccountKey::from_slice(Pubkey::new_unique().as_ref()),
+            lamports: 10,
+            owner: AccountKey::from_slice(Pubkey::default().as_ref()),
+            executable: false,
+            rent_epoch: 0,
+            data: vec![1, 2, 3],
+            slot: 7,
+            write_version: 3,
+            txn_signature: None,
+        };
+        let full = AccountAuditHandler::default().account_update(&account);
+        assert!(full.contains("INSERT INTO account_audit"), "{}", full);
+        assert!(full.contains("'\\x010203', 7, 3, NULL"), "{}", full);
+        let diff = AccountAuditHandler {
+            config: AccountAuditConfig {
+                mode: AccountAuditMode::Diff,
+                ..AccountAuditConfig::default()
+            },
+            ..AccountAuditHandler::default()
+        }
+        .account_update(&account);
+        assert!(diff.starts_with(&format!("SELECT account_audit_append('\\x{}'", hex::encode(&account.pubkey))), "{}", diff);
+        assert!(diff.ends_with(", 100, 32);"), "{}", diff);
+        assert!(AccountAuditConfig {
+            chunk_bytes: 0,
+            ..AccountAuditConfig::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
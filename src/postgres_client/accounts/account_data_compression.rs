@@ -0,0 +1,68 @@
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use std::borrow::Cow;
+
+pub const ZSTD: &str = "zstd";
+pub const LZ4: &str = "lz4";
+
+/// The codec the `data` column of the `account` table is compressed with, recorded per row in `data_codec`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountDataCompression {
+    /// Store the data as it is
+    #[default]
+    None,
+    /// Zstandard at its default level, the better ratio
+    Zstd,
+    /// LZ4 with the size prepended, the faster codec
+    Lz4,
+}
+
+impl AccountDataCompression {
+    /// The data to store and its codec. The data is stored as it is, with a NULL codec, when compressing it
+    /// fails or doesn't make it smaller.
+    pub fn compress<'a>(&self, data: &'a [u8]) -> (Cow<'a, [u8]>, Option<&'static str>) {
+        let compressed = match self {
+            Self::None => return (Cow::Borrowed(data), None),
+            _ if data.is_empty() => return (Cow::Borrowed(data), None),
+            Self::Zstd => zstd::bulk::compress(data, zstd::DEFAULT_COMPRESSION_LEVEL).map(|compressed| (compressed, ZSTD)),
+            Self::Lz4 => lz4::block::compress(data, None, true).map(|compressed| (compressed, LZ4)),
+        };
+        match compressed {
+            Ok((compressed, codec)) if compressed.len() < data.len() => (Cow::Owned(compressed), Some(codec)),
+            _ => (Cow::Borrowed(data), None),
+        }
+    }
+}
+
+/// Decompress the `data` of an `account` row read back along with its `data_codec`
+pub fn decompress_account_data(codec: Option<&str>, data: &[u8]) -> Result<Vec<u8>, String> {
+    match codec {
+        None => Ok(data.to_vec()),
+        Some(ZSTD) => zstd::stream::decode_all(data).map_err(|e| format!("invalid zstd account data: {}", e)),
+        Some(LZ4) => lz4::block::decompress(data, None).map_err(|e| format!("invalid lz4 account data: {}", e)),
+        Some(codec) => Err(format!("unknown account data codec \"{}\"", codec)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_data_compression() {
+        let data = [7u8; 1024];
+        for compression in [AccountDataCompression::Zstd, AccountDataCompression::Lz4] {
+            let (compressed, codec) = compression.compress(&data);
+            assert!(codec.is_some());
+            assert!(compressed.len() < data.len());
+            assert_eq!(decompress_account_data(codec, &compressed).unwrap(), data);
+            // data that doesn't shrink is stored as it is
+            assert_eq!(compression.compress(&[1, 2, 3]), (Cow::Borrowed(&[1u8, 2, 3][..]), None));
+        }
+        assert_eq!(AccountDataCompression::None.compress(&data).1, None);
+        assert_eq!(decompress_account_data(None, &data).unwrap(), data);
+        assert!(decompress_account_data(Some("gzip"), &data).is_err());
+        assert!(decompress_account_data(Some(ZSTD), &data).is_err());
+    }
+}
@@ -1,46 +1,322 @@
 use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use crate::accounts_selector::AccountHandlerConfig;
 use crate::accounts_selector::AccountsSelectorConfig;
+use crate::buffer_pool;
 use crate::config::GeyserPluginPostgresConfig;
+use crate::postgres_client::bound_rows::BoundRow;
+use crate::postgres_client::bound_rows::BoundValues;
+use crate::postgres_client::bound_rows::RowBatch;
+use crate::postgres_client::bulk_load::BulkLoad;
+use crate::postgres_client::bulk_load::StagingTable;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::ConflictStrategy;
+use log::*;
+use serde::Deserialize;
+use serde::Serialize;
+use smallvec::SmallVec;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
 
+use super::account_audit_handler::AccountAuditHandler;
+use super::associated_token_account_handler::AssociatedTokenAccountHandler;
+use super::balance_change_handler::BalanceChangeAccountHandler;
+use super::balance_history_handler::BalanceHistoryAccountHandler;
+#[cfg(feature = "idl")]
+use super::diff_account_handler::DiffAccountHandler;
+#[cfg(feature = "idl")]
+use super::idl_account_handler::IdlAccountHandler;
+use super::idl_registry::IdlRegistry;
+use super::layout_account_handler::LayoutAccountHandler;
+#[cfg(feature = "cardinal")]
+use super::listing_handler::ListingAccountHandler;
+#[cfg(feature = "metaplex")]
+use super::metadata_account_handler::MetadataAccountHandler;
+#[cfg(feature = "metaplex")]
 use super::metadata_creators_account_handler::MetadataCreatorsAccountHandler;
+use super::mint_account_handler::MintAccountHandler;
 use super::token_account_handler::TokenAccountHandler;
+#[cfg(feature = "cardinal")]
 use super::token_manager_handler::TokenManagerAccountHandler;
+#[cfg(feature = "cardinal")]
+use super::token_manager_receipt_handler::TokenManagerReceiptAccountHandler;
+use super::token_mint_handler::TokenMintAccountHandler;
+#[cfg(feature = "cardinal")]
+use super::transfer_authority_handler::TransferAuthorityAccountHandler;
 use super::unknown_account_handler::UnknownAccountHandler;
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum AccountHandlerId {
     TokenMetadataCreators,
+    TokenMetadata,
     TokenAccount,
+    AssociatedTokenAccount,
+    TokenMint,
+    MintAccount,
     TokenManager,
+    TokenManagerReceipt,
+    TransferAuthority,
+    Listing,
     UnknownAccount,
+    BalanceHistory,
+    BalanceChange,
+    AccountAudit,
+    Idl,
+    Layout,
+    Diff,
+    /// A handler registered by an external library
+    External(String),
 }
 #[derive(Debug)]
 pub struct UnknownAccountHandlerId;
 
+impl AccountHandlerId {
+    /// Built in handler ids, any other id refers to an external handler
+    pub fn resolve(handler_id: &str) -> Self {
+        Self::from_str(handler_id).unwrap_or_else(|_| Self::External(handler_id.to_string()))
+    }
+
+    /// The id used in the selectors
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::TokenMetadataCreators => "token_metadata_creators",
+            Self::TokenMetadata => "token_metadata",
+            Self::TokenAccount => "token_account",
+            Self::AssociatedTokenAccount => "associated_token_account",
+            Self::TokenMint => "token_mint",
+            Self::MintAccount => "mint_account",
+            Self::TokenManager => "token_manager",
+            Self::TokenManagerReceipt => "token_manager_receipt",
+            Self::TransferAuthority => "transfer_authority",
+            Self::Listing => "listing",
+            Self::UnknownAccount => "unknown_account",
+            Self::BalanceHistory => "balance_history",
+            Self::BalanceChange => "balance_change",
+            Self::AccountAudit => "account_audit",
+            Self::Idl => "idl",
+            Self::Layout => "layout",
+            Self::Diff => "diff",
+            Self::External(handler_id) => handler_id,
+        }
+    }
+
+    /// The Cargo feature the handler is compiled with, if it is not always built in
+    pub fn feature(&self) -> Option<&'static str> {
+        match self {
+            Self::TokenManager | Self::TokenManagerReceipt | Self::TransferAuthority | Self::Listing => Some("cardinal"),
+            Self::TokenMetadataCreators | Self::TokenMetadata => Some("metaplex"),
+            Self::Idl | Self::Diff => Some("idl"),
+            _ => None,
+        }
+    }
+
+    /// Whether the handler upserts with `ConflictStrategy::on_conflict`, so it can be given a `conflict_strategies` entry.
+    /// The history handlers only append, and `diff` keeps the previous state of each account to compare with
+    pub fn supports_conflict_strategy(&self) -> bool {
+        !matches!(self, Self::TokenMint | Self::BalanceHistory | Self::BalanceChange | Self::AccountAudit | Self::Diff | Self::External(_))
+    }
+
+    /// Whether the handler's tables record the write version the `slot_write_version` strategy compares
+    pub fn records_write_version(&self) -> bool {
+        matches!(self, Self::UnknownAccount | Self::TokenAccount | Self::MintAccount)
+    }
+
+    /// The strategy used without a `conflict_strategies` entry. A listing keeps the mint it was given
+    /// once its token manager was indexed
+    pub fn default_conflict_strategy(&self) -> ConflictStrategy {
+        match self {
+            Self::Listing => ConflictStrategy::MergeNonNull,
+            _ if self.records_write_version() => ConflictStrategy::SlotWriteVersion,
+            _ => ConflictStrategy::LatestSlot,
+        }
+    }
+
+    /// Whether the plugin was built with the handler
+    pub fn is_compiled(&self) -> bool {
+        match self {
+            Self::TokenManager | Self::TokenManagerReceipt | Self::TransferAuthority | Self::Listing => cfg!(feature = "cardinal"),
+            Self::TokenMetadataCreators | Self::TokenMetadata => cfg!(feature = "metaplex"),
+            Self::Idl | Self::Diff => cfg!(feature = "idl"),
+            _ => true,
+        }
+    }
+}
+
 impl FromStr for AccountHandlerId {
     type Err = UnknownAccountHandlerId;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
         match input {
             "token_metadata_creators" => Ok(Self::TokenMetadataCreators),
+            "token_metadata" => Ok(Self::TokenMetadata),
             "token_account" => Ok(Self::TokenAccount),
+            "associated_token_account" => Ok(Self::AssociatedTokenAccount),
+            "token_mint" => Ok(Self::TokenMint),
+            "mint_account" => Ok(Self::MintAccount),
             "token_manager" => Ok(Self::TokenManager),
+            "token_manager_receipt" => Ok(Self::TokenManagerReceipt),
+            "transfer_authority" => Ok(Self::TransferAuthority),
+            "listing" => Ok(Self::Listing),
             "unknown_account" => Ok(Self::UnknownAccount),
+            "balance_history" => Ok(Self::BalanceHistory),
+            "balance_change" => Ok(Self::BalanceChange),
+            "account_audit" => Ok(Self::AccountAudit),
+            "idl" => Ok(Self::Idl),
+            "layout" => Ok(Self::Layout),
+            "diff" => Ok(Self::Diff),
             _ => Err(UnknownAccountHandlerId),
         }
     }
 }
 
-pub fn all_account_handlers() -> HashMap<AccountHandlerId, Box<dyn AccountHandler>> {
+/// * The `account_handlers` section controls which account handlers are created, and so which tables they initialize.
+/// "account_handlers" : { "enabled" : \["token_account", "unknown_account"\] }
+/// or:
+/// "account_handlers" : { "disabled" : \["balance_history", "listing"\] }
+/// Handlers left out are neither created nor have their tables initialized, so no selector may refer to them.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccountHandlersConfig {
+    /// The only handlers created, every handler when empty
+    pub enabled: Vec<String>,
+    /// The handlers not created
+    pub disabled: Vec<String>,
+}
+
+impl AccountHandlersConfig {
+    pub fn is_enabled(&self, handler_id: &str) -> bool {
+        (self.enabled.is_empty() || self.enabled.iter().any(|id| id == handler_id)) && !self.disabled.iter().any(|id| id == handler_id)
+    }
+
+    /// Check that every handler id is either built in or external
+    pub fn validate(&self, external_handler_ids: &[String]) -> Result<(), String> {
+        if !self.enabled.is_empty() && !self.disabled.is_empty() {
+            return Err("account_handlers can either list the \"enabled\" or the \"disabled\" handlers, not both".to_string());
+        }
+        for (section, handler_ids) in [("enabled", &self.enabled), ("disabled", &self.disabled)] {
+            for handler_id in handler_ids {
+                match AccountHandlerId::from_str(handler_id) {
+                    Ok(id) if !id.is_compiled() => {
+                        return Err(format!(
+                            "account_handlers.{} handler_id \"{}\" requires the plugin to be built with the \"{}\" feature",
+                            section,
+                            handler_id,
+                            id.feature().unwrap_or_default()
+                        ));
+                    }
+                    Ok(_) => {}
+                    Err(_) if !external_handler_ids.contains(handler_id) => {
+                        return Err(format!("account_handlers.{} has unknown handler_id \"{}\"", section, handler_id));
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The built-in handlers, those of the handler families left out of the build or of the `account_handlers` config are not registered
+#[cfg_attr(not(feature = "idl"), allow(unused_variables))]
+pub fn all_account_handlers(config: &GeyserPluginPostgresConfig, idl_registry: Arc<IdlRegistry>) -> HashMap<AccountHandlerId, Box<dyn AccountHandler>> {
+    let conflict = |handler_id: AccountHandlerId| config.conflict_strategy(handler_id.as_str());
+    let prefix = || TablePrefix::new(config);
     let mut account_handlers: HashMap<AccountHandlerId, Box<dyn AccountHandler>> = HashMap::default();
-    account_handlers.insert(AccountHandlerId::TokenAccount, Box::new(TokenAccountHandler {}));
-    account_handlers.insert(AccountHandlerId::TokenMetadataCreators, Box::new(MetadataCreatorsAccountHandler {}));
-    account_handlers.insert(AccountHandlerId::TokenManager, Box::new(TokenManagerAccountHandler {}));
-    account_handlers.insert(AccountHandlerId::UnknownAccount, Box::new(UnknownAccountHandler {}));
+    account_handlers.insert(
+        AccountHandlerId::TokenAccount,
+        Box::new(TokenAccountHandler {
+            conflict: conflict(AccountHandlerId::TokenAccount),
+            prefix: prefix(),
+        }),
+    );
+    account_handlers.insert(
+        AccountHandlerId::AssociatedTokenAccount,
+        Box::new(AssociatedTokenAccountHandler {
+            conflict: conflict(AccountHandlerId::AssociatedTokenAccount),
+            prefix: prefix(),
+        }),
+    );
+    account_handlers.insert(AccountHandlerId::TokenMint, Box::new(TokenMintAccountHandler { prefix: prefix() }));
+    account_handlers.insert(
+        AccountHandlerId::MintAccount,
+        Box::new(MintAccountHandler {
+            conflict: conflict(AccountHandlerId::MintAccount),
+            prefix: prefix(),
+        }),
+    );
+    #[cfg(feature = "metaplex")]
+    {
+        account_handlers.insert(
+            AccountHandlerId::TokenMetadataCreators,
+            Box::new(MetadataCreatorsAccountHandler {
+                conflict: conflict(AccountHandlerId::TokenMetadataCreators),
+                prefix: prefix(),
+            }),
+        );
+        account_handlers.insert(
+            AccountHandlerId::TokenMetadata,
+            Box::new(MetadataAccountHandler {
+                conflict: conflict(AccountHandlerId::TokenMetadata),
+                prefix: prefix(),
+            }),
+        );
+    }
+    #[cfg(feature = "cardinal")]
+    {
+        account_handlers.insert(
+            AccountHandlerId::TokenManager,
+            Box::new(TokenManagerAccountHandler::new(conflict(AccountHandlerId::TokenManager), prefix())),
+        );
+        account_handlers.insert(
+            AccountHandlerId::TokenManagerReceipt,
+            Box::new(TokenManagerReceiptAccountHandler::new(conflict(AccountHandlerId::TokenManagerReceipt), prefix())),
+        );
+        account_handlers.insert(
+            AccountHandlerId::TransferAuthority,
+            Box::new(TransferAuthorityAccountHandler::new(conflict(AccountHandlerId::TransferAuthority), prefix())),
+        );
+        account_handlers.insert(AccountHandlerId::Listing, Box::new(ListingAccountHandler::new(conflict(AccountHandlerId::Listing), prefix())));
+    }
+    account_handlers.insert(
+        AccountHandlerId::UnknownAccount,
+        Box::new(UnknownAccountHandler {
+            conflict: conflict(AccountHandlerId::UnknownAccount),
+            compression: config.account_data_compression,
+            prefix: prefix(),
+        }),
+    );
+    account_handlers.insert(AccountHandlerId::BalanceHistory, Box::new(BalanceHistoryAccountHandler { prefix: prefix() }));
+    account_handlers.insert(AccountHandlerId::BalanceChange, Box::new(BalanceChangeAccountHandler { prefix: prefix() }));
+    account_handlers.insert(
+        AccountHandlerId::AccountAudit,
+        Box::new(AccountAuditHandler {
+            config: config.account_audit.clone(),
+            prefix: prefix(),
+        }),
+    );
+    account_handlers.insert(AccountHandlerId::Layout, Box::new(LayoutAccountHandler::new(config)));
+    #[cfg(feature = "idl")]
+    {
+        account_handlers.insert(
+            AccountHandlerId::Idl,
+            Box::new(IdlAccountHandler {
+                registry: idl_registry.clone(),
+                store_decoded_accounts: config.store_decoded_accounts,
+                conflict: conflict(AccountHandlerId::Idl),
+                prefix: prefix(),
+            }),
+        );
+        account_handlers.insert(
+            AccountHandlerId::Diff,
+            Box::new(DiffAccountHandler {
+                registry: idl_registry,
+                layouts: LayoutAccountHandler::new(config),
+                prefix: prefix(),
+            }),
+        );
+    }
+    account_handlers.retain(|handler_id, _| config.account_handler_enabled(handler_id.as_str()));
     account_handlers
 }
 
@@ -66,6 +342,69 @@ pub fn select_account_handlers(account_selector: &Option<AccountsSelectorConfig>
     selected_handlers.into_iter().filter(|h| !is_startup || !h.skip_on_startup.unwrap_or(false)).collect()
 }
 
+/// Feed an account through every handler selected for it, but `skip_handlers`, and join the resulting statements
+pub fn account_update_query(
+    account_selector: &Option<AccountsSelectorConfig>,
+    account_handlers: &HashMap<AccountHandlerId, Box<dyn AccountHandler>>,
+    account: &DbAccountInfo,
+    is_startup: bool,
+    skip_handlers: &[String],
+) -> String {
+    account_update_queries(account_selector, account_handlers, account, is_startup, skip_handlers, None, None)
+        .into_iter()
+        .map(|(_, query)| query)
+        .collect()
+}
+
+/// The statements of every handler selected for an account, but `skip_handlers`, by handler id. The rows of
+/// the handlers with a staging table are staged in `bulk_load` instead, when given, and the bound rows of the
+/// handlers with prepared statements are added to `rows`
+pub fn account_update_queries(
+    account_selector: &Option<AccountsSelectorConfig>,
+    account_handlers: &HashMap<AccountHandlerId, Box<dyn AccountHandler>>,
+    account: &DbAccountInfo,
+    is_startup: bool,
+    skip_handlers: &[String],
+    mut bulk_load: Option<&mut BulkLoad>,
+    mut rows: Option<&mut RowBatch>,
+) -> Vec<(String, String)> {
+    let mut queries = Vec::new();
+    for h in select_account_handlers(account_selector, account, is_startup)
+        .into_iter()
+        .filter(|h| !skip_handlers.contains(&h.handler_id))
+    {
+        // the ids are validated when the config is loaded or reloaded, one without a handler writes nothing
+        let handler = match account_handlers.get(&AccountHandlerId::resolve(&h.handler_id)) {
+            Some(handler) => handler,
+            None => {
+                error!(
+                    "[account_update_queries] skipping unknown handler_id=[{}] account=[{}]",
+                    h.handler_id,
+                    bs58::encode(&account.pubkey).into_string()
+                );
+                continue;
+            }
+        };
+        // bound rows are written next to the staged rows of a handler with both
+        if let Some(rows) = rows.as_deref_mut() {
+            let bound_rows = handler.account_rows(account);
+            if !bound_rows.is_empty() {
+                rows.push(&h.handler_id, bound_rows);
+            }
+        }
+        if let Some(bulk_load) = bulk_load.as_deref_mut() {
+            if bulk_load.stage(&h.handler_id, handler.as_ref(), account) {
+                continue;
+            }
+        }
+        let query = handler.account_update(account);
+        if !query.is_empty() {
+            queries.push((h.handler_id, query));
+        }
+    }
+    queries
+}
+
 pub trait AccountHandler {
     fn enabled(&self, _config: &GeyserPluginPostgresConfig) -> bool {
         true
@@ -73,16 +412,53 @@ pub trait AccountHandler {
 
     fn init(&self, config: &GeyserPluginPostgresConfig) -> String;
 
+    /// SQL run once per schema version bump after `init`, the handler's schema version is the number of migrations.
+    /// Migrations are only appended to, and should be idempotent since `init` already creates the latest schema on a new database.
+    fn schema_migrations(&self, _config: &GeyserPluginPostgresConfig) -> Vec<String> {
+        Vec::new()
+    }
+
     fn account_match(&self, account: &DbAccountInfo) -> bool;
 
     fn account_update(&self, account: &DbAccountInfo) -> String;
+
+    /// The table the startup accounts are bulk loaded to with `startup_bulk_load`, for the handlers
+    /// writing a single row per account with its slot and write version
+    fn staging_table(&self) -> Option<StagingTable> {
+        None
+    }
+
+    /// The values of the `staging_table` row of an account, as a parenthesized list
+    fn staging_row(&self, _account: &DbAccountInfo) -> Option<String> {
+        None
+    }
+
+    /// The values of the `staging_table` row of an account, in column order, copied in binary with `startup_copy`.
+    /// The `staging_row` of a handler without values is staged instead
+    fn staging_values(&self, _account: &DbAccountInfo) -> Option<BoundValues> {
+        None
+    }
+
+    /// The statements the handler writes its `account_rows` with, prepared once per connection. Writing rows
+    /// through them rather than `account_update` keeps the values of an account out of the SQL
+    fn prepared_statements(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The rows of an account, bound to the handler's `prepared_statements`
+    fn account_rows(&self, _account: &DbAccountInfo) -> Vec<BoundRow> {
+        Vec::new()
+    }
 }
 
+/// A pubkey or owner, stored inline rather than in a heap allocation of its own
+pub type AccountKey = SmallVec<[u8; 32]>;
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct DbAccountInfo {
-    pub pubkey: Vec<u8>,
+    pub pubkey: AccountKey,
     pub lamports: i64,
-    pub owner: Vec<u8>,
+    pub owner: AccountKey,
     pub executable: bool,
     pub rent_epoch: i64,
     pub data: Vec<u8>,
@@ -93,17 +469,29 @@ pub struct DbAccountInfo {
 
 impl DbAccountInfo {
     pub fn new(account: &ReplicaAccountInfoV2, slot: u64) -> DbAccountInfo {
-        let data = account.data.to_vec();
+        let mut data = buffer_pool::take(account.data.len());
+        data.extend_from_slice(account.data);
         Self {
-            pubkey: account.pubkey.to_vec(),
+            pubkey: AccountKey::from_slice(account.pubkey),
             lamports: account.lamports as i64,
-            owner: account.owner.to_vec(),
+            owner: AccountKey::from_slice(account.owner),
             executable: account.executable,
             rent_epoch: account.rent_epoch as i64,
             data,
             slot: slot as i64,
             write_version: account.write_version as i64,
-            txn_signature: None,
+            txn_signature: account.txn_signature.map(|signature| signature.as_ref().to_vec()),
         }
     }
+
+    /// The bytes held on the heap by the account
+    pub fn heap_bytes(&self) -> usize {
+        let key_bytes = |key: &AccountKey| if key.spilled() { key.capacity() } else { 0 };
+        key_bytes(&self.pubkey) + key_bytes(&self.owner) + self.data.len() + self.txn_signature.as_ref().map_or(0, |s| s.len())
+    }
+
+    /// Hand the data buffer back for the next account once the account is written
+    pub fn recycle(self) {
+        buffer_pool::recycle(self.data);
+    }
 }
@@ -0,0 +1,149 @@
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::pubkey::PUBKEY_BYTES;
+
+use super::account_handler::AccountHandler;
+use super::token_account_handler::is_token_account;
+use super::token_account_handler::SPL_TOKEN_ACCOUNT_MINT_OFFSET;
+use super::token_account_handler::SPL_TOKEN_ACCOUNT_OWNER_OFFSET;
+use super::token_account_handler::TOKENZ_PROGRAM_ID;
+use super::token_account_handler::TOKEN_PROGRAM_ID;
+use super::DbAccountInfo;
+use crate::postgres_client::sql_value::pubkey_literal;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::ConflictStrategy;
+
+pub static ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/// The associated token account of a wallet for a mint, derived from the wallet, the token program and the mint
+pub fn associated_token_address(wallet: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[wallet.as_ref(), token_program.as_ref(), mint.as_ref()], &ASSOCIATED_TOKEN_PROGRAM_ID).0
+}
+
+/// Maps every wallet and mint to its associated token account. A token account is only recorded if it is
+/// the associated token account of its owner for its mint, and its mapping is deleted once it is closed or
+/// no longer is, as a legacy token account can be given to another owner.
+#[derive(Clone, Default)]
+pub struct AssociatedTokenAccountHandler {
+    pub conflict: ConflictStrategy,
+    pub prefix: TablePrefix,
+}
+
+impl AssociatedTokenAccountHandler {
+    fn is_closed(account: &DbAccountInfo) -> bool {
+        account.lamports == 0 && (account.owner.as_slice() == TOKEN_PROGRAM_ID.as_ref() || account.owner.as_slice() == TOKENZ_PROGRAM_ID.as_ref())
+    }
+
+    fn delete(&self, account: &DbAccountInfo) -> String {
+        format!("DELETE FROM {}wallet_ata WHERE ata = {} AND slot <= {};", self.prefix, pubkey_literal(&account.pubkey), account.slot)
+    }
+}
+
+impl AccountHandler for AssociatedTokenAccountHandler {
+    fn init(&self, config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        if !self.enabled(config) {
+            return "".to_string();
+        };
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}wallet_ata (
+                wallet VARCHAR(44) NOT NULL,
+                mint VARCHAR(44) NOT NULL,
+                ata VARCHAR(44) NOT NULL,
+                token_program VARCHAR(44) NOT NULL,
+                slot BIGINT NOT NULL,
+                PRIMARY KEY (wallet, mint)
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}wallet_ata_ata ON {prefix}wallet_ata (ata);
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn account_match(&self, account: &DbAccountInfo) -> bool {
+        Self::is_closed(account) || is_token_account(account)
+    }
+
+    fn account_update(&self, account: &DbAccountInfo) -> String {
+        if !self.account_match(account) {
+            return "".to_string();
+        };
+        if Self::is_closed(account) {
+            return self.delete(account);
+        }
+        let mint: &Pubkey = bytemuck::from_bytes(&account.data[SPL_TOKEN_ACCOUNT_MINT_OFFSET..SPL_TOKEN_ACCOUNT_MINT_OFFSET + PUBKEY_BYTES]);
+        let wallet: &Pubkey = bytemuck::from_bytes(&account.data[SPL_TOKEN_ACCOUNT_OWNER_OFFSET..SPL_TOKEN_ACCOUNT_OWNER_OFFSET + PUBKEY_BYTES]);
+        let token_program: &Pubkey = bytemuck::from_bytes(account.owner.as_slice());
+        if associated_token_address(wallet, mint, token_program).as_ref() != account.pubkey.as_slice() {
+            return self.delete(account);
+        }
+        format!(
+            "
+                INSERT INTO {prefix}wallet_ata AS acc (wallet, mint, ata, token_program, slot) \
+                VALUES ('{0}', '{1}', {2}, '{3}', {4}) \
+                {5};
+            ",
+            wallet,
+            mint,
+            pubkey_literal(&account.pubkey),
+            token_program,
+            account.slot,
+            self.conflict.on_conflict("wallet, mint", &["ata", "token_program"]),
+            prefix = self.prefix,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::accounts::token_account_handler::SPL_TOKEN_ACCOUNT_LENGTH;
+    use crate::postgres_client::AccountKey;
+
+    fn token_account(pubkey: &Pubkey, wallet: &Pubkey, mint: &Pubkey) -> DbAccountInfo {
+        let mut data = vec![0; SPL_TOKEN_ACCOUNT_LENGTH];
+        data[SPL_TOKEN_ACCOUNT_MINT_OFFSET..SPL_TOKEN_ACCOUNT_MINT_OFFSET + PUBKEY_BYTES].copy_from_slice(mint.as_ref());
+        data[SPL_TOKEN_ACCOUNT_OWNER_OFFSET..SPL_TOKEN_ACCOUNT_OWNER_OFFSET + PUBKEY_BYTES].copy_from_slice(wallet.as_ref());
+        DbAccountInfo {
+            pubkey: AccountKey::from_slice(pubkey.as_ref()),
+            lamports: 2_039_280,
+            owner: AccountKey::from_slice(TOKEN_PROGRAM_ID.as_ref()),
+            executable: false,
+            rent_epoch: 0,
+            data,
+            slot: 5,
+            write_version: 1,
+            txn_signature: None,
+        }
+    }
+
+    #[test]
+    fn test_associated_token_address() {
+        let wallet = pubkey!("9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM");
+        let mint = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+        assert_eq!(associated_token_address(&wallet, &mint, &TOKEN_PROGRAM_ID), pubkey!("FGETo8T8wMcN2wCjav8VK6eh3dLk63evNDPxzLSJra8B"));
+        assert_ne!(
+            associated_token_address(&wallet, &mint, &TOKENZ_PROGRAM_ID),
+            associated_token_address(&wallet, &mint, &TOKEN_PROGRAM_ID)
+        );
+    }
+
+    #[test]
+    fn test_associated_token_account_update() {
+        let handler = AssociatedTokenAccountHandler::default();
+        let wallet = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let ata = associated_token_address(&wallet, &mint, &TOKEN_PROGRAM_ID);
+        let account = token_account(&ata, &wallet, &mint);
+        let query = handler.account_update(&account);
+        assert!(query.contains("INSERT INTO wallet_ata"), "{}", query);
+        assert!(query.contains(&format!("VALUES ('{}', '{}', '{}', '{}', 5)", wallet, mint, ata, TOKEN_PROGRAM_ID)));
+        assert!(query.contains("ON CONFLICT (wallet, mint)"));
+
+        let other = token_account(&Pubkey::new_unique(), &wallet, &mint);
+        assert!(handler.account_update(&other).starts_with("DELETE FROM wallet_ata"));
+
+        let closed = DbAccountInfo { lamports: 0, data: vec![], ..account };
+        assert_eq!(handler.account_update(&closed), format!("DELETE FROM wallet_ata WHERE ata = '{}' AND slot <= 5;", ata));
+    }
+}
@@ -0,0 +1,90 @@
+use chrono::Utc;
+use solana_sdk::pubkey::Pubkey;
+
+use super::account_handler::AccountHandler;
+use super::DbAccountInfo;
+use crate::postgres_client::table_prefix::TablePrefix;
+
+#[derive(Clone, Default)]
+pub struct BalanceChangeAccountHandler {
+    pub prefix: TablePrefix,
+}
+
+impl AccountHandler for BalanceChangeAccountHandler {
+    fn init(&self, config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        if !self.enabled(config) {
+            return "".to_string();
+        };
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}balance_change (
+                pubkey VARCHAR(44) NOT NULL,
+                slot BIGINT NOT NULL,
+                write_version BIGINT NOT NULL,
+                old_lamports BIGINT,
+                new_lamports BIGINT NOT NULL,
+                delta BIGINT,
+                updated_on TIMESTAMP NOT NULL,
+                PRIMARY KEY (pubkey, slot, write_version)
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}balance_change_slot ON {prefix}balance_change (slot);
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn account_match(&self, _account: &DbAccountInfo) -> bool {
+        true
+    }
+
+    /// Appends a row for every update, the old lamports being the new lamports of the previous row by slot and
+    /// write version. The first update of an account has no old lamports nor delta, a replayed update is skipped
+    fn account_update(&self, account: &DbAccountInfo) -> String {
+        if !self.account_match(account) {
+            return "".to_string();
+        };
+        let pubkey_bytes: [u8; 32] = account.pubkey[..].try_into().unwrap();
+        let pubkey = Pubkey::from(pubkey_bytes).to_string();
+        format!(
+            "
+                INSERT INTO {prefix}balance_change (pubkey, slot, write_version, old_lamports, new_lamports, delta, updated_on) \
+                SELECT '{0}', {2}, {3}, prev.lamports, {1}, {1} - prev.lamports, '{4}' \
+                FROM (SELECT (SELECT new_lamports FROM {prefix}balance_change WHERE pubkey = '{0}' AND (slot, write_version) < ({2}, {3}) \
+                ORDER BY slot DESC, write_version DESC LIMIT 1) AS lamports) prev \
+                ON CONFLICT (pubkey, slot, write_version) DO NOTHING;
+            ",
+            pubkey,
+            account.lamports,
+            account.slot,
+            account.write_version,
+            &Utc::now().naive_utc(),
+            prefix = self.prefix,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::AccountKey;
+
+    #[test]
+    fn test_balance_change_update() {
+        let pubkey = Pubkey::new_unique();
+        let account = DbAccountInfo {
+            pubkey: AccountKey::from_slice(pubkey.as_ref()),
+            lamports: 2_039_280,
+            owner: AccountKey::from_slice(Pubkey::default().as_ref()),
+            executable: false,
+            rent_epoch: 0,
+            data: vec![],
+            slot: 7,
+            write_version: 3,
+            txn_signature: None,
+        };
+        let query = BalanceChangeAccountHandler::default().account_update(&account);
+        assert!(query.contains(&format!("SELECT '{}', 7, 3, prev.lamports, 2039280, 2039280 - prev.lamports,", pubkey)), "{}", query);
+        assert!(query.contains(&format!("WHERE pubkey = '{}' AND (slot, write_version) < (7, 3)", pubkey)));
+        assert!(query.contains("ON CONFLICT (pubkey, slot, write_version) DO NOTHING"));
+    }
+}
@@ -0,0 +1,86 @@
+use chrono::Utc;
+use solana_sdk::pubkey::Pubkey;
+
+use super::account_handler::AccountHandler;
+use super::DbAccountInfo;
+use crate::postgres_client::table_prefix::TablePrefix;
+
+#[derive(Clone, Default)]
+pub struct BalanceHistoryAccountHandler {
+    pub prefix: TablePrefix,
+}
+
+impl AccountHandler for BalanceHistoryAccountHandler {
+    fn init(&self, config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        if !self.enabled(config) {
+            return "".to_string();
+        };
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}balance_history (
+                pubkey VARCHAR(44) NOT NULL,
+                lamports BIGINT NOT NULL,
+                slot BIGINT NOT NULL,
+                write_version BIGINT NOT NULL,
+                updated_on TIMESTAMP NOT NULL,
+                PRIMARY KEY (pubkey, slot)
+            );
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn account_match(&self, _account: &DbAccountInfo) -> bool {
+        true
+    }
+
+    /// Appends a row when the balance differs from the one of the latest row written before the update in
+    /// (slot, write_version) order, the last write of a slot wins
+    fn account_update(&self, account: &DbAccountInfo) -> String {
+        if !self.account_match(account) {
+            return "".to_string();
+        };
+        let pubkey_bytes: [u8; 32] = account.pubkey[..].try_into().unwrap();
+        let pubkey = Pubkey::from(pubkey_bytes).to_string();
+        format!(
+            "
+                INSERT INTO {prefix}balance_history AS bh (pubkey, lamports, slot, write_version, updated_on) \
+                SELECT '{0}', {1}, {2}, {3}, '{4}' \
+                WHERE {1} IS DISTINCT FROM (SELECT lamports FROM {prefix}balance_history WHERE pubkey = '{0}' AND (slot, write_version) < ({2}, {3}) ORDER BY slot DESC, write_version DESC LIMIT 1) \
+                ON CONFLICT (pubkey, slot) DO UPDATE SET lamports=excluded.lamports, write_version=excluded.write_version, updated_on=excluded.updated_on \
+                WHERE bh.write_version < excluded.write_version;
+            ",
+            pubkey,
+            account.lamports,
+            account.slot,
+            account.write_version,
+            &Utc::now().naive_utc(),
+            prefix = self.prefix,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::AccountKey;
+
+    #[test]
+    fn test_balance_history_update() {
+        let pubkey = Pubkey::new_unique();
+        let account = DbAccountInfo {
+            pubkey: AccountKey::from_slice(pubkey.as_ref()),
+            lamports: 2_039_280,
+            owner: AccountKey::from_slice(Pubkey::default().as_ref()),
+            executable: false,
+            rent_epoch: 0,
+            data: vec![],
+            slot: 7,
+            write_version: 3,
+            txn_signature: None,
+        };
+        let query = BalanceHistoryAccountHandler::default().account_update(&account);
+        assert!(query.contains(&format!("SELECT '{}', 2039280, 7, 3,", pubkey)), "{}", query);
+        assert!(query.contains(&format!("WHERE pubkey = '{}' AND (slot, write_version) < (7, 3)", pubkey)), "{}", query);
+    }
+}
@@ -0,0 +1,124 @@
+use super::DbAccountInfo;
+use crate::config::GeyserPluginPostgresConfig;
+use crate::postgres_client::is_identifier;
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::ConflictStrategy;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// * The `promoted_columns` section promotes JSON paths of decoded accounts to indexed columns, per table.
+/// "promoted_columns" : {
+///     "decoded_account" : \[{ "name" : "mint", "path" : "mint", "type" : "VARCHAR(44)" }\],
+///     "token_manager_idl" : \[{ "name" : "state", "path" : "state", "type" : "SMALLINT", "index" : false }\]
+/// }
+/// Columns are generated from the `data` column, so they stay in sync with every upsert.
+#[derive(Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PromotedColumnConfig {
+    /// The column name
+    pub name: String,
+    /// Dot separated path into the decoded JSON, e.g. `kind.Unmanaged.expiration`
+    pub path: String,
+    /// The column type the JSON value is cast to
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// Whether to index the column. The default is true
+    #[serde(default = "default_index")]
+    pub index: bool,
+}
+
+fn default_index() -> bool {
+    true
+}
+
+/// Check table, column, path and type names so they can be safely interpolated into DDL
+pub fn validate_promoted_columns(promoted_columns: &HashMap<String, Vec<PromotedColumnConfig>>) -> Result<(), String> {
+    for (table, columns) in promoted_columns {
+        if !is_identifier(table) {
+            return Err(format!("promoted_columns table \"{}\" is not a valid identifier", table));
+        }
+        for column in columns {
+            if !is_identifier(&column.name) {
+                return Err(format!("promoted_columns.{} column \"{}\" is not a valid identifier", table, column.name));
+            }
+            if !column.path.split('.').all(is_identifier) {
+                return Err(format!("promoted_columns.{}.{} path \"{}\" is not a dot separated list of keys", table, column.name, column.path));
+            }
+            if column.ty.is_empty() || !column.ty.chars().all(|c| c.is_ascii_alphanumeric() || " (),".contains(c)) {
+                return Err(format!("promoted_columns.{}.{} type \"{}\" is not a valid column type", table, column.name, column.ty));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Add the promoted columns as stored generated columns of the tables' JSONB `data`
+pub fn promoted_columns_init(config: &GeyserPluginPostgresConfig) -> String {
+    let prefix = TablePrefix::new(config);
+    let mut query = String::new();
+    for (table, columns) in &config.promoted_columns {
+        for column in columns {
+            query.push_str(&format!(
+                "\nALTER TABLE {0} ADD COLUMN IF NOT EXISTS {1} {2} GENERATED ALWAYS AS ((data #>> '{{{3}}}')::{2}) STORED;",
+                prefix.table(table),
+                column.name,
+                column.ty,
+                column.path.replace('.', ",")
+            ));
+            if column.index {
+                query.push_str(&format!("\nCREATE INDEX IF NOT EXISTS {0}_{1} ON {0} ({1});", prefix.table(table), column.name));
+            }
+        }
+    }
+    query
+}
+
+/// The `decoded_account` table shared by the IDL and layout decoders, holding
+/// every decoded account as JSONB regardless of its program
+pub struct DecodedAccountTable {}
+
+impl DecodedAccountTable {
+    pub fn enabled(config: &GeyserPluginPostgresConfig) -> bool {
+        config.store_decoded_accounts
+    }
+
+    pub fn init(config: &GeyserPluginPostgresConfig) -> String {
+        if !Self::enabled(config) {
+            return "".to_string();
+        }
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}decoded_account (
+                pubkey VARCHAR(44) NOT NULL,
+                owner VARCHAR(44) NOT NULL,
+                account_type VARCHAR(64) NOT NULL,
+                slot BIGINT NOT NULL,
+                data JSONB NOT NULL,
+                PRIMARY KEY(pubkey)
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}decoded_account_owner_account_type ON {prefix}decoded_account (owner, account_type);
+            CREATE INDEX IF NOT EXISTS {prefix}decoded_account_data ON {prefix}decoded_account USING GIN (data);
+        ",
+            prefix = TablePrefix::new(config)
+        );
+    }
+
+    pub fn update(prefix: &TablePrefix, account: &DbAccountInfo, account_type: &str, data: &Value) -> String {
+        format!(
+            "
+            INSERT INTO {6}decoded_account AS acc (pubkey, owner, account_type, slot, data) \
+            VALUES ('{0}', '{1}', {2}, {3}, {4}) \
+            {5};
+            ",
+            bs58::encode(&account.pubkey).into_string(),
+            bs58::encode(&account.owner).into_string(),
+            account_type.to_string().sql_literal(),
+            &account.slot,
+            data.to_string().sql_literal(),
+            ConflictStrategy::LatestSlot.on_conflict("pubkey", &["owner", "account_type", "data"]),
+            prefix,
+        )
+    }
+}
@@ -0,0 +1,134 @@
+use super::account_handler::AccountHandler;
+use super::idl_registry::IdlRegistry;
+use super::layout_account_handler::LayoutAccountHandler;
+use super::DbAccountInfo;
+use crate::config::GeyserPluginPostgresConfig;
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use serde_json::Map;
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+use std::sync::Arc;
+
+/// Records which decoded fields changed between consecutive updates of an account.
+/// Accounts are decoded with their program's `idl` or a matching `layout`, the last decoded
+/// state is kept in `account_diff_state` and every changed field is written to `account_field_change`.
+pub struct DiffAccountHandler {
+    pub registry: Arc<IdlRegistry>,
+    pub layouts: LayoutAccountHandler,
+    pub prefix: TablePrefix,
+}
+
+/// Flatten nested objects into dot separated paths, arrays are compared as a whole
+pub fn flatten_fields(prefix: &str, value: &Value, fields: &mut Map<String, Value>) {
+    match value {
+        Value::Object(object) if !object.is_empty() => {
+            for (key, value) in object {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten_fields(&path, value, fields);
+            }
+        }
+        _ => {
+            fields.insert(prefix.to_string(), value.clone());
+        }
+    }
+}
+
+impl DiffAccountHandler {
+    fn decode(&self, account: &DbAccountInfo) -> Option<(String, Value)> {
+        if let Some(program) = self.registry.get(&account.owner) {
+            if account.pubkey[..] == program.idl_address[..] {
+                return None;
+            }
+            if let Some((account_type, data)) = program.decoder.decode_account(&account.data) {
+                return Some((account_type.to_string(), data));
+            }
+        }
+        self.layouts.decode(account).map(|(table, data)| (table.to_string(), data))
+    }
+}
+
+impl AccountHandler for DiffAccountHandler {
+    fn enabled(&self, config: &GeyserPluginPostgresConfig) -> bool {
+        config.idl.is_some() || !config.layouts.is_empty()
+    }
+
+    fn init(&self, config: &GeyserPluginPostgresConfig) -> String {
+        if !self.enabled(config) {
+            return "".to_string();
+        };
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}account_diff_state (
+                pubkey VARCHAR(44) NOT NULL,
+                account_type VARCHAR(64) NOT NULL,
+                data JSONB NOT NULL,
+                slot BIGINT NOT NULL,
+                PRIMARY KEY(pubkey)
+            );
+            CREATE TABLE IF NOT EXISTS {prefix}account_field_change (
+                pubkey VARCHAR(44) NOT NULL,
+                account_type VARCHAR(64) NOT NULL,
+                field TEXT NOT NULL,
+                old_value JSONB,
+                new_value JSONB,
+                slot BIGINT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}account_field_change_pubkey_slot ON {prefix}account_field_change (pubkey, slot);
+            CREATE INDEX IF NOT EXISTS {prefix}account_field_change_field ON {prefix}account_field_change (account_type, field);
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn account_match(&self, account: &DbAccountInfo) -> bool {
+        self.registry.get(&account.owner).is_some() || self.layouts.account_match(account)
+    }
+
+    fn account_update(&self, account: &DbAccountInfo) -> String {
+        let (account_type, data) = match self.decode(account) {
+            Some(decoded) => decoded,
+            None => return "".to_string(),
+        };
+        let mut fields = Map::new();
+        flatten_fields("", &data, &mut fields);
+        let account_key: &Pubkey = bytemuck::from_bytes(&account.pubkey);
+        let account_type = account_type.to_string().sql_literal();
+        let fields = Value::Object(fields).to_string().sql_literal();
+        // the first update of an account only records its state, later ones diff against it
+        format!(
+            "
+            WITH prev AS (SELECT data FROM {4}account_diff_state WHERE pubkey = '{0}' AND slot < {3}), \
+            old AS (SELECT o.key, o.value FROM prev, jsonb_each(prev.data) o), \
+            new AS (SELECT n.key, n.value FROM jsonb_each({2}::jsonb) n) \
+            INSERT INTO {4}account_field_change (pubkey, account_type, field, old_value, new_value, slot) \
+            SELECT '{0}', {1}, COALESCE(new.key, old.key), old.value, new.value, {3} \
+            FROM old FULL JOIN new ON old.key = new.key \
+            WHERE EXISTS (SELECT 1 FROM prev) AND old.value IS DISTINCT FROM new.value;
+            INSERT INTO {4}account_diff_state AS state (pubkey, account_type, data, slot) \
+            VALUES ('{0}', {1}, {2}, {3}) \
+            ON CONFLICT (pubkey) \
+            DO UPDATE SET account_type=excluded.account_type, data=excluded.data, slot=excluded.slot \
+            WHERE state.slot < excluded.slot;
+            ",
+            &account_key.to_string(),
+            account_type,
+            fields,
+            &account.slot,
+            self.prefix,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_flatten_fields() {
+        let mut fields = Map::new();
+        flatten_fields("", &json!({ "state": 1, "kind": { "Managed": { "expiration": 5 } }, "invalidators": ["a"], "empty": {} }), &mut fields);
+        assert_eq!(Value::Object(fields), json!({ "state": 1, "kind.Managed.expiration": 5, "invalidators": ["a"], "empty": {} }));
+    }
+}
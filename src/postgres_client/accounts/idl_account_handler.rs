@@ -0,0 +1,102 @@
+use super::account_handler::AccountHandler;
+use super::decoded_account::DecodedAccountTable;
+use super::idl_registry::IdlRegistry;
+use super::DbAccountInfo;
+use crate::metrics::registry;
+use crate::metrics::HANDLER_DECODE_ERRORS_TOTAL;
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::ConflictStrategy;
+use log::debug;
+use log::error;
+use log::info;
+use solana_sdk::pubkey::Pubkey;
+use std::sync::Arc;
+
+/// Decodes accounts of the programs configured in `idl` into a JSONB table per program
+pub struct IdlAccountHandler {
+    pub registry: Arc<IdlRegistry>,
+    /// Also write decoded accounts to the shared `decoded_account` table
+    pub store_decoded_accounts: bool,
+    pub conflict: ConflictStrategy,
+    pub prefix: TablePrefix,
+}
+
+impl AccountHandler for IdlAccountHandler {
+    fn enabled(&self, config: &crate::config::GeyserPluginPostgresConfig) -> bool {
+        config.idl.is_some()
+    }
+
+    fn init(&self, config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        if !self.enabled(config) {
+            return "".to_string();
+        };
+        config
+            .idl
+            .iter()
+            .flat_map(|idl| idl.programs.iter())
+            .map(|(program_id, program)| {
+                format!(
+                    "
+                    CREATE TABLE IF NOT EXISTS {0} (
+                        id VARCHAR(44) NOT NULL,
+                        account_type VARCHAR(64) NOT NULL,
+                        data JSONB NOT NULL,
+                        slot BIGINT NOT NULL,
+                        PRIMARY KEY(id)
+                    );
+                    CREATE INDEX IF NOT EXISTS {0}_account_type ON {0} (account_type);
+                ",
+                    self.prefix.table(&program.table(program_id))
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("")
+    }
+
+    fn account_match(&self, account: &DbAccountInfo) -> bool {
+        self.registry.get(&account.owner).is_some()
+    }
+
+    fn account_update(&self, account: &DbAccountInfo) -> String {
+        let program = match self.registry.get(&account.owner) {
+            Some(program) => program,
+            None => return "".to_string(),
+        };
+        // the IDL account itself isn't stored, an update reloads the program's decoder
+        if account.pubkey[..] == program.idl_address[..] {
+            match self.registry.reload(&account.owner, &account.data, account.slot) {
+                Ok(true) => info!("[account_update] Reloaded idl program=[{:?}] slot=[{}]", bs58::encode(&account.owner).into_string(), account.slot),
+                Ok(false) => {}
+                Err(e) => error!("[account_update] Failed to reload idl program=[{:?}] error=[{}]", bs58::encode(&account.owner).into_string(), e),
+            }
+            return "".to_string();
+        }
+        let (account_type, data) = match program.decoder.decode_account(&account.data) {
+            Some(decoded) => decoded,
+            None => {
+                debug!("[account_update] Failed to decode idl account pubkey=[{:?}]", bs58::encode(&account.pubkey).into_string());
+                registry().inc_counter(HANDLER_DECODE_ERRORS_TOTAL, &[("handler", "idl")], 1);
+                return "".to_string();
+            }
+        };
+        let account_key: &Pubkey = bytemuck::from_bytes(&account.pubkey);
+        let mut query = format!(
+            "
+            INSERT INTO {0} AS acc (id, account_type, data, slot) \
+            VALUES ('{1}', {2}, {3}, {4}) \
+            {5};
+            ",
+            self.prefix.table(&program.table),
+            &account_key.to_string(),
+            account_type.to_string().sql_literal(),
+            data.to_string().sql_literal(),
+            &account.slot,
+            self.conflict.on_conflict("id", &["account_type", "data"]),
+        );
+        if self.store_decoded_accounts {
+            query.push_str(&DecodedAccountTable::update(&self.prefix, account, account_type, &data));
+        }
+        query
+    }
+}
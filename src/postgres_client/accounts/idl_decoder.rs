@@ -0,0 +1,328 @@
+use serde_derive::Deserialize;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+use solana_program::hash::hash;
+
+/// The subset of an Anchor IDL needed to decode program accounts
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Idl {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub accounts: Vec<IdlTypeDef>,
+    #[serde(default)]
+    pub types: Vec<IdlTypeDef>,
+    #[serde(default)]
+    pub events: Vec<IdlEvent>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct IdlEvent {
+    pub name: String,
+    /// Explicit discriminator, present in IDLs built with Anchor 0.30 and later
+    pub discriminator: Option<Vec<u8>>,
+    /// Missing on newer IDL events, whose type is declared in `types`
+    pub fields: Option<Vec<IdlField>>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct IdlTypeDef {
+    pub name: String,
+    /// Explicit discriminator, present in IDLs built with Anchor 0.30 and later
+    pub discriminator: Option<Vec<u8>>,
+    /// Missing on newer IDL accounts, whose type is declared in `types`
+    #[serde(rename = "type")]
+    pub ty: Option<IdlTypeDefTy>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum IdlTypeDefTy {
+    Struct {
+        #[serde(default)]
+        fields: Vec<IdlField>,
+    },
+    Enum {
+        variants: Vec<IdlEnumVariant>,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct IdlField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Value,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct IdlEnumVariant {
+    pub name: String,
+    /// Either named fields or a list of tuple field types
+    pub fields: Option<Vec<Value>>,
+}
+
+/// Decodes Borsh serialized accounts into JSON using an IDL
+#[derive(Clone, Debug)]
+pub struct IdlDecoder {
+    pub idl: Idl,
+    discriminators: Vec<[u8; 8]>,
+    event_discriminators: Vec<[u8; 8]>,
+}
+
+fn discriminator(namespace: &str, name: &str, explicit: Option<&[u8]>) -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    match explicit {
+        Some(explicit) if explicit.len() == 8 => discriminator.copy_from_slice(explicit),
+        _ => discriminator.copy_from_slice(&hash(format!("{}:{}", namespace, name).as_bytes()).to_bytes()[..8]),
+    }
+    discriminator
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if data.len() < len {
+        return None;
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Some(head)
+}
+
+macro_rules! read_le {
+    ($data:expr, $t:ty) => {
+        <$t>::from_le_bytes(take($data, std::mem::size_of::<$t>())?.try_into().ok()?)
+    };
+}
+
+fn read_len(data: &mut &[u8]) -> Option<usize> {
+    let len = read_le!(data, u32) as usize;
+    // every element takes at least one byte, reject lengths the remaining data can't hold
+    if len > data.len() {
+        return None;
+    }
+    Some(len)
+}
+
+impl IdlDecoder {
+    pub fn new(idl: Idl) -> Self {
+        let discriminators = idl.accounts.iter().map(|account| discriminator("account", &account.name, account.discriminator.as_deref())).collect();
+        let event_discriminators = idl.events.iter().map(|event| discriminator("event", &event.name, event.discriminator.as_deref())).collect();
+        Self {
+            idl,
+            discriminators,
+            event_discriminators,
+        }
+    }
+
+    /// Decode an event emitted with `emit!`, returning its IDL event name and decoded fields
+    pub fn decode_event(&self, data: &[u8]) -> Option<(&str, Value)> {
+        let discriminator = data.get(..8)?;
+        let index = self.event_discriminators.iter().position(|d| d == discriminator)?;
+        let event = &self.idl.events[index];
+        let mut data = &data[8..];
+        let value = match &event.fields {
+            Some(fields) => self.decode_fields(fields, &mut data)?,
+            None => self.decode_type_def(self.type_def(&event.name)?, &mut data)?,
+        };
+        Some((&event.name, value))
+    }
+
+    /// The IDL account name of a discriminator
+    pub fn account_name(&self, discriminator: &[u8]) -> Option<&str> {
+        let index = self.discriminators.iter().position(|d| d == discriminator)?;
+        Some(&self.idl.accounts[index].name)
+    }
+
+    /// Decode an account, returning its IDL account name and decoded fields
+    pub fn decode_account(&self, data: &[u8]) -> Option<(&str, Value)> {
+        let discriminator = data.get(..8)?;
+        let index = self.discriminators.iter().position(|d| d == discriminator)?;
+        let account = &self.idl.accounts[index];
+        let ty = match &account.ty {
+            Some(ty) => ty,
+            None => self.type_def(&account.name)?,
+        };
+        let mut data = &data[8..];
+        Some((&account.name, self.decode_type_def(ty, &mut data)?))
+    }
+
+    fn type_def(&self, name: &str) -> Option<&IdlTypeDefTy> {
+        self.idl.types.iter().chain(self.idl.accounts.iter()).find(|t| t.name == name).and_then(|t| t.ty.as_ref())
+    }
+
+    fn decode_type_def(&self, ty: &IdlTypeDefTy, data: &mut &[u8]) -> Option<Value> {
+        match ty {
+            IdlTypeDefTy::Struct { fields } => self.decode_fields(fields, data),
+            IdlTypeDefTy::Enum { variants } => {
+                let variant = variants.get(read_le!(data, u8) as usize)?;
+                let fields = match &variant.fields {
+                    None => return Some(Value::String(variant.name.clone())),
+                    Some(fields) => fields,
+                };
+                let value = if fields.iter().all(|f| f.get("name").is_some()) {
+                    let fields = fields.iter().map(|f| serde_json::from_value(f.clone()).ok()).collect::<Option<Vec<IdlField>>>()?;
+                    self.decode_fields(&fields, data)?
+                } else {
+                    Value::Array(fields.iter().map(|ty| self.decode_type(ty, data)).collect::<Option<Vec<Value>>>()?)
+                };
+                let mut object = Map::new();
+                object.insert(variant.name.clone(), value);
+                Some(Value::Object(object))
+            }
+        }
+    }
+
+    fn decode_fields(&self, fields: &[IdlField], data: &mut &[u8]) -> Option<Value> {
+        let mut object = Map::new();
+        for field in fields {
+            object.insert(field.name.clone(), self.decode_type(&field.ty, data)?);
+        }
+        Some(Value::Object(object))
+    }
+
+    fn decode_type(&self, ty: &Value, data: &mut &[u8]) -> Option<Value> {
+        match ty {
+            Value::String(primitive) => Some(match primitive.as_str() {
+                "bool" => Value::Bool(read_le!(data, u8) != 0),
+                "u8" => json!(read_le!(data, u8)),
+                "i8" => json!(read_le!(data, i8)),
+                "u16" => json!(read_le!(data, u16)),
+                "i16" => json!(read_le!(data, i16)),
+                "u32" => json!(read_le!(data, u32)),
+                "i32" => json!(read_le!(data, i32)),
+                "u64" => json!(read_le!(data, u64)),
+                "i64" => json!(read_le!(data, i64)),
+                "f32" => json!(read_le!(data, f32)),
+                "f64" => json!(read_le!(data, f64)),
+                // beyond the range of JSON numbers in most consumers
+                "u128" => Value::String(read_le!(data, u128).to_string()),
+                "i128" => Value::String(read_le!(data, i128).to_string()),
+                "publicKey" | "pubkey" => Value::String(bs58::encode(take(data, 32)?).into_string()),
+                // strings padded with NUL bytes are common on chain, and JSONB can't hold them
+                "string" => {
+                    let len = read_len(data)?;
+                    Value::String(String::from_utf8_lossy(take(data, len)?).replace('\0', ""))
+                }
+                "bytes" => {
+                    let len = read_len(data)?;
+                    Value::String(hex::encode(take(data, len)?))
+                }
+                _ => return None,
+            }),
+            Value::Object(ty) => {
+                if let Some(inner) = ty.get("vec") {
+                    let len = read_len(data)?;
+                    return (0..len).map(|_| self.decode_type(inner, data)).collect::<Option<Vec<Value>>>().map(Value::Array);
+                }
+                if let Some(inner) = ty.get("option") {
+                    return match read_le!(data, u8) {
+                        0 => Some(Value::Null),
+                        _ => self.decode_type(inner, data),
+                    };
+                }
+                if let Some(inner) = ty.get("coption") {
+                    return match read_le!(data, u32) {
+                        0 => Some(Value::Null),
+                        _ => self.decode_type(inner, data),
+                    };
+                }
+                if let Some(Value::Array(array)) = ty.get("array") {
+                    let inner = array.first()?;
+                    let len = array.get(1)?.as_u64()?;
+                    return (0..len).map(|_| self.decode_type(inner, data)).collect::<Option<Vec<Value>>>().map(Value::Array);
+                }
+                if let Some(defined) = ty.get("defined") {
+                    let name = defined.as_str().or_else(|| defined.get("name").and_then(Value::as_str))?;
+                    return self.decode_type_def(self.type_def(name)?, data);
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use borsh::BorshSerialize;
+    use solana_sdk::pubkey::Pubkey;
+
+    #[allow(dead_code)]
+    #[derive(BorshSerialize)]
+    enum Kind {
+        Managed,
+        Unmanaged { expiration: i64 },
+    }
+
+    #[derive(BorshSerialize)]
+    struct Stake {
+        owner: Pubkey,
+        amount: u64,
+        name: String,
+        receipt: Option<Pubkey>,
+        kind: Kind,
+    }
+
+    fn stake_idl() -> Idl {
+        serde_json::from_value(json!({
+            "name": "stake_pool",
+            "accounts": [{
+                "name": "Stake",
+                "type": {
+                    "kind": "struct",
+                    "fields": [
+                        { "name": "owner", "type": "publicKey" },
+                        { "name": "amount", "type": "u64" },
+                        { "name": "name", "type": "string" },
+                        { "name": "receipt", "type": { "option": "publicKey" } },
+                        { "name": "kind", "type": { "defined": "Kind" } }
+                    ]
+                }
+            }],
+            "types": [{
+                "name": "Kind",
+                "type": {
+                    "kind": "enum",
+                    "variants": [
+                        { "name": "Managed" },
+                        { "name": "Unmanaged", "fields": [{ "name": "expiration", "type": "i64" }] }
+                    ]
+                }
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_decode_account() {
+        let owner = Pubkey::new_unique();
+        let mut data = hash(b"account:Stake").to_bytes()[..8].to_vec();
+        Stake {
+            owner,
+            amount: 42,
+            name: "pool\0\0\0\0".to_string(),
+            receipt: None,
+            kind: Kind::Unmanaged { expiration: 100 },
+        }
+        .serialize(&mut data)
+        .unwrap();
+
+        let decoder = IdlDecoder::new(stake_idl());
+        let (account_type, value) = decoder.decode_account(&data).expect("Failed to decode");
+        assert_eq!(account_type, "Stake");
+        assert_eq!(
+            value,
+            json!({
+                "owner": owner.to_string(),
+                "amount": 42,
+                "name": "pool",
+                "receipt": null,
+                "kind": { "Unmanaged": { "expiration": 100 } }
+            })
+        );
+
+        assert!(decoder.decode_account(&data[..20]).is_none(), "Truncated data should not decode");
+        assert!(decoder.decode_account(&[0u8; 64]).is_none(), "Unknown discriminator should not decode");
+    }
+}
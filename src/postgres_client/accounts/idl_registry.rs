@@ -0,0 +1,218 @@
+#[cfg(feature = "idl")]
+use super::idl_decoder::Idl;
+use super::idl_decoder::IdlDecoder;
+use crate::config::GeyserPluginPostgresConfig;
+#[cfg(feature = "idl")]
+use flate2::read::ZlibDecoder;
+#[cfg(feature = "idl")]
+use log::*;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+#[cfg(feature = "idl")]
+use solana_client::rpc_client::RpcClient;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+#[cfg(feature = "idl")]
+use std::fs::File;
+#[cfg(feature = "idl")]
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::RwLock;
+
+/// * The `idl` section configures the programs whose accounts the 'idl' handler decodes using their Anchor IDL, requires the `idl` feature.
+/// "idl" : {
+///     "rpc_url" : "https://api.mainnet-beta.solana.com",
+///     "programs" : {
+///         "mgr99QFMYByTqGPWmNqunV7vBLmWWXdSrHUfV8Jf3JM" : { "table" : "token_manager_idl" },
+///         "<program id>" : { "path" : "/solana/idl/my_program.json" }
+///     }
+/// }
+/// IDLs are read from `path` when set, otherwise fetched from `rpc_url`, once when the plugin is loaded.
+/// Fetched IDLs are reloaded whenever the program's IDL account changes, local IDLs stay pinned.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdlConfig {
+    /// The RPC endpoint the IDL accounts are fetched from
+    pub rpc_url: String,
+    /// Programs to decode, keyed by program id
+    pub programs: HashMap<String, IdlProgramConfig>,
+}
+
+impl Default for IdlConfig {
+    fn default() -> Self {
+        Self {
+            rpc_url: "https://api.mainnet-beta.solana.com".to_string(),
+            programs: HashMap::default(),
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdlProgramConfig {
+    /// The table decoded accounts are written to, defaults to `idl_<program id>` in lowercase
+    pub table: Option<String>,
+    /// A local IDL JSON file used instead of fetching the IDL account
+    pub path: Option<String>,
+}
+
+impl IdlProgramConfig {
+    pub fn table(&self, program_id: &str) -> String {
+        self.table.clone().unwrap_or_else(|| format!("idl_{}", program_id.to_lowercase()))
+    }
+}
+
+impl IdlConfig {
+    /// Check that every program id is a valid pubkey and every table name can be interpolated into DDL
+    pub fn validate(&self) -> Result<(), String> {
+        if cfg!(not(feature = "idl")) {
+            return Err("\"idl\" requires the plugin to be built with the \"idl\" feature".to_string());
+        }
+        for (program_id, program) in &self.programs {
+            if Pubkey::from_str(program_id).is_err() {
+                return Err(format!("idl.programs key \"{}\" is not a valid pubkey", program_id));
+            }
+            let table = program.table(program_id);
+            if table.is_empty() || !table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("idl.programs.{}.table \"{}\" is not a valid identifier", program_id, table));
+            }
+            if let Some(path) = &program.path {
+                if !Path::new(path).exists() {
+                    return Err(format!("idl.programs.{}.path file {} does not exist", program_id, path));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A program's decoder and the table its accounts are written to
+pub struct IdlProgram {
+    pub table: String,
+    pub decoder: IdlDecoder,
+    /// The program's IDL account, watched for upgrades
+    pub idl_address: Vec<u8>,
+    /// Set for IDLs read from a local file, which are never reloaded
+    pub pinned: bool,
+    /// The slot of the IDL account the decoder was built from, 0 when loaded on startup
+    pub slot: i64,
+}
+
+/// IDL decoders shared by every worker, keyed by program id
+#[derive(Default)]
+pub struct IdlRegistry {
+    programs: RwLock<HashMap<Vec<u8>, Arc<IdlProgram>>>,
+}
+
+impl IdlRegistry {
+    pub fn get(&self, program_id: &[u8]) -> Option<Arc<IdlProgram>> {
+        self.programs.read().unwrap().get(program_id).cloned()
+    }
+
+    pub fn insert(&self, program_id: Vec<u8>, program: IdlProgram) {
+        self.programs.write().unwrap().insert(program_id, Arc::new(program));
+    }
+
+    /// Without the `idl` feature there are no programs to decode
+    #[cfg(not(feature = "idl"))]
+    pub fn load(_config: &GeyserPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
+        Ok(Self::default())
+    }
+}
+
+#[cfg(feature = "idl")]
+impl IdlRegistry {
+    /// Load the IDLs of the configured programs. Local IDL files must parse, programs
+    /// whose IDL can't be fetched are logged and left undecoded.
+    pub fn load(config: &GeyserPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
+        let registry = Self::default();
+        let idl_config = match &config.idl {
+            Some(idl_config) => idl_config,
+            None => return Ok(registry),
+        };
+        let mut rpc_client = None;
+        for (program_id, program_config) in &idl_config.programs {
+            let program_key = match Pubkey::from_str(program_id) {
+                Ok(program_key) => program_key,
+                Err(e) => {
+                    error!("[idl] invalid program id=[{}] error=[{:?}]", program_id, e);
+                    continue;
+                }
+            };
+            let idl = match &program_config.path {
+                Some(path) => Ok(read_idl_file(path).map_err(|msg| GeyserPluginError::ConfigFileReadError {
+                    msg: format!("Failed to read the IDL of program {} from {}: {}", program_id, path, msg),
+                })?),
+                None => fetch_idl(rpc_client.get_or_insert_with(|| RpcClient::new(idl_config.rpc_url.clone())), &program_key),
+            };
+            // programs without an IDL yet stay registered so an IDL account update can load one
+            let idl = idl.unwrap_or_else(|e| {
+                error!("[idl] failed to fetch idl program=[{}] error=[{}]", program_id, e);
+                Idl::default()
+            });
+            info!("[idl] loaded program=[{}] name=[{}] accounts=[{}]", program_id, idl.name, idl.accounts.len());
+            registry.insert(
+                program_key.as_ref().to_vec(),
+                IdlProgram {
+                    table: program_config.table(program_id),
+                    decoder: IdlDecoder::new(idl),
+                    idl_address: idl_address(&program_key).as_ref().to_vec(),
+                    pinned: program_config.path.is_some(),
+                    slot: 0,
+                },
+            );
+        }
+        Ok(registry)
+    }
+
+    /// Swap in the decoder built from an updated IDL account. Returns false when the
+    /// program is pinned or the update is older than the loaded IDL.
+    pub fn reload(&self, program_id: &[u8], idl_account_data: &[u8], slot: i64) -> Result<bool, String> {
+        let mut programs = self.programs.write().unwrap();
+        let current = match programs.get(program_id) {
+            Some(current) if !current.pinned && current.slot <= slot => current,
+            _ => return Ok(false),
+        };
+        let program = IdlProgram {
+            table: current.table.clone(),
+            decoder: IdlDecoder::new(parse_idl_account(idl_account_data)?),
+            idl_address: current.idl_address.clone(),
+            pinned: false,
+            slot,
+        };
+        programs.insert(program_id.to_vec(), Arc::new(program));
+        Ok(true)
+    }
+}
+
+/// The address of a program's Anchor IDL account
+pub fn idl_address(program_id: &Pubkey) -> Pubkey {
+    let (base, _) = Pubkey::find_program_address(&[], program_id);
+    Pubkey::create_with_seed(&base, "anchor:idl", program_id).expect("Invalid idl seed")
+}
+
+/// Parse an Anchor IDL account: discriminator, authority and the zlib compressed IDL JSON
+#[cfg(feature = "idl")]
+pub fn parse_idl_account(data: &[u8]) -> Result<Idl, String> {
+    let len = data.get(40..44).ok_or("idl account is too short")?;
+    let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+    let compressed = data.get(44..44 + len).ok_or("idl account data is truncated")?;
+    let mut json = Vec::new();
+    ZlibDecoder::new(compressed).read_to_end(&mut json).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&json).map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "idl")]
+fn read_idl_file(path: &str) -> Result<Idl, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    serde_json::from_reader(file).map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "idl")]
+fn fetch_idl(rpc_client: &RpcClient, program_id: &Pubkey) -> Result<Idl, String> {
+    let data = rpc_client.get_account_data(&idl_address(program_id)).map_err(|e| e.to_string())?;
+    parse_idl_account(&data)
+}
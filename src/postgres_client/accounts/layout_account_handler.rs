@@ -0,0 +1,414 @@
+use super::account_handler::AccountHandler;
+use super::account_handler::AccountHandlerId;
+use super::decoded_account::DecodedAccountTable;
+use super::DbAccountInfo;
+use crate::config::GeyserPluginPostgresConfig;
+use crate::metrics::registry;
+use crate::metrics::HANDLER_DECODE_ERRORS_TOTAL;
+use crate::postgres_client::is_identifier;
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::ConflictStrategy;
+use log::debug;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use serde_json::Map;
+use serde_json::Number;
+use serde_json::Value;
+use solana_program::hash::hash;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+/// * The `layouts` section describes account layouts decoded by the 'layout' handler into a table per layout.
+/// "layouts" : \[{
+///     "table" : "stake_entry",
+///     "owner" : "stkBL96RZkjY5ine4TvPihGqW8UHJfch2cokjAPzV8i",
+///     "anchor_account" : "StakeEntry",
+///     "fields" : \[
+///         { "name" : "pool", "type" : "pubkey", "offset" : 9 },
+///         { "name" : "amount", "type" : "u64" }
+///     \]
+/// }\]
+/// Fields without an `offset` follow the previous field, so Borsh layouts only need the field order.
+#[derive(Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayoutConfig {
+    /// The table decoded accounts are written to
+    pub table: String,
+    /// The program owning the accounts
+    pub owner: String,
+    /// Hex encoded prefix the account data must start with
+    pub discriminator: Option<String>,
+    /// Anchor account name, used to derive the discriminator when `discriminator` is not set
+    pub anchor_account: Option<String>,
+    pub fields: Vec<LayoutFieldConfig>,
+}
+
+#[derive(Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LayoutFieldConfig {
+    pub name: String,
+    /// One of bool, u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64, pubkey, string or bytes
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// Byte offset in the account data, defaults to the end of the previous field
+    pub offset: Option<usize>,
+    /// Fixed length of a `bytes` field, otherwise the bytes are prefixed by a u32 length
+    pub len: Option<usize>,
+    /// The value is preceded by a one byte Borsh option tag and is NULL when unset
+    #[serde(default)]
+    pub optional: bool,
+}
+
+fn sql_type(ty: &str) -> Option<&'static str> {
+    Some(match ty {
+        "bool" => "BOOL",
+        "u8" | "i8" | "i16" => "SMALLINT",
+        "u16" | "i32" => "INTEGER",
+        "u32" | "i64" => "BIGINT",
+        "u64" => "NUMERIC(20,0)",
+        "u128" | "i128" => "NUMERIC(39,0)",
+        "f32" => "REAL",
+        "f64" => "DOUBLE PRECISION",
+        "pubkey" => "VARCHAR(44)",
+        "string" => "TEXT",
+        "bytes" => "BYTEA",
+        _ => return None,
+    })
+}
+
+impl LayoutConfig {
+    /// Check the layout can be turned into a table and decoder
+    pub fn validate(&self) -> Result<(), String> {
+        if !is_identifier(&self.table) {
+            return Err(format!("layouts table \"{}\" is not a valid identifier", self.table));
+        }
+        if Pubkey::from_str(&self.owner).is_err() {
+            return Err(format!("layouts.{}.owner \"{}\" is not a valid pubkey", self.table, self.owner));
+        }
+        if let Some(discriminator) = &self.discriminator {
+            if hex::decode(discriminator).is_err() {
+                return Err(format!("layouts.{}.discriminator \"{}\" is not valid hex", self.table, discriminator));
+            }
+        }
+        if self.fields.is_empty() {
+            return Err(format!("layouts.{} must list at least one field", self.table));
+        }
+        for field in &self.fields {
+            if !is_identifier(&field.name) || field.name == "id" || field.name == "slot" {
+                return Err(format!("layouts.{} field \"{}\" is not a valid column name", self.table, field.name));
+            }
+            if sql_type(&field.ty).is_none() {
+                return Err(format!("layouts.{}.{} has unknown type \"{}\"", self.table, field.name, field.ty));
+            }
+        }
+        Ok(())
+    }
+
+    fn discriminator_bytes(&self) -> Vec<u8> {
+        match (&self.discriminator, &self.anchor_account) {
+            (Some(discriminator), _) => hex::decode(discriminator).unwrap_or_default(),
+            (None, Some(name)) => hash(format!("account:{}", name).as_bytes()).to_bytes()[..8].to_vec(),
+            (None, None) => Vec::new(),
+        }
+    }
+}
+
+struct Layout {
+    config: LayoutConfig,
+    owner: Vec<u8>,
+    discriminator: Vec<u8>,
+}
+
+fn take<'a>(data: &'a [u8], offset: &mut usize, len: usize) -> Option<&'a [u8]> {
+    let bytes = data.get(*offset..offset.checked_add(len)?)?;
+    *offset += len;
+    Some(bytes)
+}
+
+macro_rules! read_le {
+    ($data:expr, $offset:expr, $t:ty) => {
+        <$t>::from_le_bytes(take($data, $offset, std::mem::size_of::<$t>())?.try_into().ok()?)
+    };
+}
+
+/// A decoded layout field
+enum LayoutValue {
+    Null,
+    Bool(bool),
+    Number(Number),
+    /// 128 bit integers, beyond the range of JSON numbers in most consumers
+    BigInteger(String),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl LayoutValue {
+    fn sql_literal(&self) -> String {
+        match self {
+            Self::Null => "NULL".to_string(),
+            Self::Bool(value) => value.to_string(),
+            Self::Number(value) => value.to_string(),
+            Self::BigInteger(value) => value.clone(),
+            Self::Text(value) => value.sql_literal(),
+            Self::Bytes(value) => format!("'\\x{}'", hex::encode(value)),
+        }
+    }
+
+    fn json(&self) -> Value {
+        match self {
+            Self::Null => Value::Null,
+            Self::Bool(value) => Value::Bool(*value),
+            Self::Number(value) => Value::Number(value.clone()),
+            Self::BigInteger(value) | Self::Text(value) => Value::String(value.clone()),
+            Self::Bytes(value) => Value::String(hex::encode(value)),
+        }
+    }
+}
+
+fn float(value: f64) -> LayoutValue {
+    Number::from_f64(value).map_or(LayoutValue::Null, LayoutValue::Number)
+}
+
+/// Read a field at `offset`, advancing `offset` past it
+fn read_field(field: &LayoutFieldConfig, data: &[u8], offset: &mut usize) -> Option<LayoutValue> {
+    if field.optional && read_le!(data, offset, u8) == 0 {
+        return Some(LayoutValue::Null);
+    }
+    Some(match field.ty.as_str() {
+        "bool" => LayoutValue::Bool(read_le!(data, offset, u8) != 0),
+        "u8" => LayoutValue::Number(read_le!(data, offset, u8).into()),
+        "i8" => LayoutValue::Number(read_le!(data, offset, i8).into()),
+        "u16" => LayoutValue::Number(read_le!(data, offset, u16).into()),
+        "i16" => LayoutValue::Number(read_le!(data, offset, i16).into()),
+        "u32" => LayoutValue::Number(read_le!(data, offset, u32).into()),
+        "i32" => LayoutValue::Number(read_le!(data, offset, i32).into()),
+        "u64" => LayoutValue::Number(read_le!(data, offset, u64).into()),
+        "i64" => LayoutValue::Number(read_le!(data, offset, i64).into()),
+        "u128" => LayoutValue::BigInteger(read_le!(data, offset, u128).to_string()),
+        "i128" => LayoutValue::BigInteger(read_le!(data, offset, i128).to_string()),
+        "f32" => float(read_le!(data, offset, f32) as f64),
+        "f64" => float(read_le!(data, offset, f64)),
+        "pubkey" => LayoutValue::Text(bs58::encode(take(data, offset, 32)?).into_string()),
+        "string" => {
+            let len = read_le!(data, offset, u32) as usize;
+            LayoutValue::Text(String::from_utf8_lossy(take(data, offset, len)?).to_string())
+        }
+        "bytes" => {
+            let len = match field.len {
+                Some(len) => len,
+                None => read_le!(data, offset, u32) as usize,
+            };
+            LayoutValue::Bytes(take(data, offset, len)?.to_vec())
+        }
+        _ => return None,
+    })
+}
+
+/// Decodes accounts described by the configured `layouts` into a table per layout
+pub struct LayoutAccountHandler {
+    layouts: Vec<Layout>,
+    store_decoded_accounts: bool,
+    conflict: ConflictStrategy,
+    prefix: TablePrefix,
+}
+
+impl LayoutAccountHandler {
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Self {
+        let layouts = config
+            .layouts
+            .iter()
+            .filter_map(|layout| {
+                Some(Layout {
+                    owner: Pubkey::from_str(&layout.owner).ok()?.as_ref().to_vec(),
+                    discriminator: layout.discriminator_bytes(),
+                    config: layout.clone(),
+                })
+            })
+            .collect();
+        Self {
+            layouts,
+            store_decoded_accounts: config.store_decoded_accounts,
+            conflict: config.conflict_strategy(AccountHandlerId::Layout.as_str()),
+            prefix: TablePrefix::new(config),
+        }
+    }
+
+    fn layout(&self, account: &DbAccountInfo) -> Option<&Layout> {
+        self.layouts
+            .iter()
+            .find(|layout| layout.owner == account.owner.as_slice() && account.data.starts_with(&layout.discriminator))
+    }
+
+    fn read_values(layout: &Layout, account: &DbAccountInfo) -> Option<Vec<LayoutValue>> {
+        let mut offset = layout.discriminator.len();
+        let mut values = Vec::with_capacity(layout.config.fields.len());
+        for field in &layout.config.fields {
+            if let Some(field_offset) = field.offset {
+                offset = field_offset;
+            }
+            match read_field(field, &account.data, &mut offset) {
+                Some(value) => values.push(value),
+                None => {
+                    debug!(
+                        "[account_update] Failed to decode layout=[{}] field=[{}] pubkey=[{:?}]",
+                        layout.config.table,
+                        field.name,
+                        bs58::encode(&account.pubkey).into_string()
+                    );
+                    return None;
+                }
+            }
+        }
+        Some(values)
+    }
+
+    fn json(layout: &Layout, values: &[LayoutValue]) -> Value {
+        Value::Object(
+            layout
+                .config
+                .fields
+                .iter()
+                .zip(values.iter())
+                .map(|(field, value)| (field.name.clone(), value.json()))
+                .collect::<Map<String, Value>>(),
+        )
+    }
+
+    /// Decode the account with the first matching layout, returning the layout's table and the fields as JSON
+    pub fn decode(&self, account: &DbAccountInfo) -> Option<(&str, Value)> {
+        let layout = self.layout(account)?;
+        let values = Self::read_values(layout, account)?;
+        Some((&layout.config.table, Self::json(layout, &values)))
+    }
+}
+
+impl AccountHandler for LayoutAccountHandler {
+    fn enabled(&self, config: &GeyserPluginPostgresConfig) -> bool {
+        !config.layouts.is_empty()
+    }
+
+    fn init(&self, config: &GeyserPluginPostgresConfig) -> String {
+        if !self.enabled(config) {
+            return "".to_string();
+        };
+        config
+            .layouts
+            .iter()
+            .map(|layout| {
+                let columns = layout
+                    .fields
+                    .iter()
+                    .map(|field| format!("{} {},", field.name, sql_type(&field.ty).unwrap_or("BYTEA")))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                format!(
+                    "
+                    CREATE TABLE IF NOT EXISTS {0} (
+                        id VARCHAR(44) NOT NULL,
+                        {1}
+                        slot BIGINT NOT NULL,
+                        PRIMARY KEY(id)
+                    );
+                ",
+                    self.prefix.table(&layout.table),
+                    columns
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("")
+    }
+
+    fn account_match(&self, account: &DbAccountInfo) -> bool {
+        self.layout(account).is_some()
+    }
+
+    fn account_update(&self, account: &DbAccountInfo) -> String {
+        let layout = match self.layout(account) {
+            Some(layout) => layout,
+            None => return "".to_string(),
+        };
+        let values = match Self::read_values(layout, account) {
+            Some(values) => values,
+            None => {
+                registry().inc_counter(HANDLER_DECODE_ERRORS_TOTAL, &[("handler", "layout")], 1);
+                return "".to_string();
+            }
+        };
+        let columns = layout.config.fields.iter().map(|field| field.name.as_str()).collect::<Vec<&str>>();
+        let account_key: &Pubkey = bytemuck::from_bytes(&account.pubkey);
+        let mut query = format!(
+            "
+            INSERT INTO {0} AS acc (id, {1}, slot) \
+            VALUES ('{2}', {3}, {4}) \
+            {5};
+            ",
+            self.prefix.table(&layout.config.table),
+            columns.join(", "),
+            &account_key.to_string(),
+            values.iter().map(LayoutValue::sql_literal).collect::<Vec<String>>().join(", "),
+            &account.slot,
+            self.conflict.on_conflict("id", &columns),
+        );
+        if self.store_decoded_accounts {
+            query.push_str(&DecodedAccountTable::update(&self.prefix, account, &layout.config.table, &Self::json(layout, &values)));
+        }
+        query
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::postgres_client::AccountKey;
+
+    #[test]
+    fn test_layout_account_update() {
+        let owner = Pubkey::new_unique();
+        let pool = Pubkey::new_unique();
+        let config = GeyserPluginPostgresConfig {
+            layouts: vec![serde_json::from_value(serde_json::json!({
+                "table": "stake_entry",
+                "owner": owner.to_string(),
+                "discriminator": "0102",
+                "fields": [
+                    { "name": "pool", "type": "pubkey", "offset": 3 },
+                    { "name": "amount", "type": "u64" },
+                    { "name": "last_staker", "type": "pubkey", "optional": true }
+                ]
+            }))
+            .unwrap()],
+            store_decoded_accounts: true,
+            ..GeyserPluginPostgresConfig::default()
+        };
+        config.layouts[0].validate().unwrap();
+
+        let mut data = vec![1, 2, 0xff];
+        data.extend_from_slice(pool.as_ref());
+        data.extend_from_slice(&7u64.to_le_bytes());
+        data.push(0);
+        let account = DbAccountInfo {
+            pubkey: AccountKey::from_slice(Pubkey::new_unique().as_ref()),
+            lamports: 0,
+            owner: AccountKey::from_slice(owner.as_ref()),
+            executable: false,
+            rent_epoch: 0,
+            data,
+            slot: 5,
+            write_version: 0,
+            txn_signature: None,
+        };
+        let handler = LayoutAccountHandler::new(&config);
+        let query = handler.account_update(&account);
+        assert!(query.contains(&format!("'{}', 7, NULL, 5", pool)), "Unexpected query {}", query);
+        assert!(query.contains("INSERT INTO decoded_account"), "Missing decoded account {}", query);
+        assert!(
+            query.contains(&format!("'{{\"amount\":7,\"last_staker\":null,\"pool\":\"{}\"}}'", pool)),
+            "Unexpected decoded data {}",
+            query
+        );
+
+        let mut other = account.clone();
+        other.data[0] = 0;
+        assert!(!handler.account_match(&other), "Discriminator mismatch should not match");
+    }
+}
@@ -0,0 +1,229 @@
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use log::error;
+use solana_sdk::pubkey::Pubkey;
+
+use super::account_handler::AccountHandler;
+use super::token_manager_handler::account_discriminator;
+use super::transfer_authority_handler::TRANSFER_AUTHORITY_PROGRAM_ID;
+use super::DbAccountInfo;
+use crate::metrics::registry;
+use crate::metrics::HANDLER_DECODE_ERRORS_TOTAL;
+use crate::postgres_client::sql_value::pubkey_literal;
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::ConflictStrategy;
+
+/// Written by Anchor over the discriminator of a closed account
+const CLOSED_ACCOUNT_DISCRIMINATOR: [u8; 8] = [255; 8];
+
+/// A token manager listed for `payment_amount` of `payment_mint` on `marketplace`
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Eq, Hash)]
+pub struct Listing {
+    pub bump: u8,
+    pub lister: Pubkey,
+    pub token_manager: Pubkey,
+    pub marketplace: Pubkey,
+    pub payment_amount: u64,
+    pub payment_mint: Pubkey,
+}
+
+/// A marketplace listings are made on, restricted to `payment_mints` when set
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Eq, Hash)]
+pub struct Marketplace {
+    pub bump: u8,
+    pub name: String,
+    pub payment_manager: Pubkey,
+    pub authority: Pubkey,
+    pub payment_mints: Option<Vec<Pubkey>>,
+}
+
+/// Listings and marketplaces of the transfer authority program. Only the active listings are kept,
+/// a listing is deleted once its account is closed
+pub struct ListingAccountHandler {
+    listing_discriminator: [u8; 8],
+    marketplace_discriminator: [u8; 8],
+    conflict: ConflictStrategy,
+    prefix: TablePrefix,
+}
+
+impl Default for ListingAccountHandler {
+    fn default() -> Self {
+        Self::new(ConflictStrategy::MergeNonNull, TablePrefix::default())
+    }
+}
+
+impl ListingAccountHandler {
+    pub fn new(conflict: ConflictStrategy, prefix: TablePrefix) -> Self {
+        Self {
+            listing_discriminator: account_discriminator("Listing"),
+            marketplace_discriminator: account_discriminator("Marketplace"),
+            conflict,
+            prefix,
+        }
+    }
+
+    fn is_closed(account: &DbAccountInfo) -> bool {
+        account.lamports == 0 || account.data.get(0..8) == Some(&CLOSED_ACCOUNT_DISCRIMINATOR[..])
+    }
+
+    /// The mint is the one of the listed token manager, kept once known by the default `merge_non_null` strategy
+    /// if the token manager isn't indexed yet
+    fn listing_update(&self, account: &DbAccountInfo, listing: &Listing) -> String {
+        format!(
+            "
+            INSERT INTO {8}listing AS acc (id, lister, token_manager, mint, marketplace, payment_amount, payment_mint, slot) \
+            VALUES ({0}, '{1}', '{2}', (SELECT mint FROM {8}token_manager WHERE id = '{2}'), '{3}', {4}, '{5}', {6}) \
+            {7};
+            ",
+            pubkey_literal(&account.pubkey),
+            listing.lister,
+            listing.token_manager,
+            listing.marketplace,
+            listing.payment_amount.sql_literal(),
+            listing.payment_mint,
+            &account.slot,
+            self.conflict.on_conflict("id", &["lister", "token_manager", "mint", "marketplace", "payment_amount", "payment_mint"]),
+            self.prefix,
+        )
+    }
+
+    fn marketplace_update(&self, account: &DbAccountInfo, marketplace: &Marketplace) -> String {
+        format!(
+            "
+            INSERT INTO {7}marketplace AS acc (id, name, payment_manager, authority, payment_mints, slot) \
+            VALUES ({0}, {1}, '{2}', '{3}', {4}, {5}) \
+            {6};
+            ",
+            pubkey_literal(&account.pubkey),
+            marketplace.name.sql_literal(),
+            marketplace.payment_manager,
+            marketplace.authority,
+            marketplace.payment_mints.sql_literal(),
+            &account.slot,
+            self.conflict.on_conflict("id", &["name", "payment_manager", "authority", "payment_mints"]),
+            self.prefix,
+        )
+    }
+}
+
+impl AccountHandler for ListingAccountHandler {
+    fn init(&self, config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        if !self.enabled(config) {
+            return "".to_string();
+        };
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}listing (
+                id VARCHAR(44) NOT NULL,
+                lister VARCHAR(44) NOT NULL,
+                token_manager VARCHAR(44) NOT NULL,
+                mint VARCHAR(44),
+                marketplace VARCHAR(44) NOT NULL,
+                payment_amount NUMERIC(20,0) NOT NULL,
+                payment_mint VARCHAR(44) NOT NULL,
+                slot BIGINT NOT NULL,
+                PRIMARY KEY(id)
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}listing_mint ON {prefix}listing (mint);
+            CREATE INDEX IF NOT EXISTS {prefix}listing_lister ON {prefix}listing (lister);
+            CREATE INDEX IF NOT EXISTS {prefix}listing_marketplace ON {prefix}listing (marketplace);
+            CREATE TABLE IF NOT EXISTS {prefix}marketplace (
+                id VARCHAR(44) NOT NULL,
+                name TEXT NOT NULL,
+                payment_manager VARCHAR(44) NOT NULL,
+                authority VARCHAR(44) NOT NULL,
+                payment_mints VARCHAR(44)[],
+                slot BIGINT NOT NULL,
+                PRIMARY KEY(id)
+            );
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn account_match(&self, account: &DbAccountInfo) -> bool {
+        account.owner.as_slice() == TRANSFER_AUTHORITY_PROGRAM_ID.as_ref()
+            && (Self::is_closed(account) || matches!(account.data.get(0..8), Some(d) if d == self.listing_discriminator || d == self.marketplace_discriminator))
+    }
+
+    fn account_update(&self, account: &DbAccountInfo) -> String {
+        if !self.account_match(account) {
+            return "".to_string();
+        };
+        if Self::is_closed(account) {
+            return format!("DELETE FROM {}listing WHERE id = {} AND slot <= {};", self.prefix, pubkey_literal(&account.pubkey), account.slot);
+        }
+        let data = &mut account.data[8..].as_ref();
+        if account.data[0..8] == self.listing_discriminator {
+            match Listing::deserialize(data) {
+                Ok(listing) => self.listing_update(account, &listing),
+                Err(e) => {
+                    error!("[account_update] Failed to deserialize listing pubkey=[{:?}] error=[{:?}]", account.pubkey, e);
+                    registry().inc_counter(HANDLER_DECODE_ERRORS_TOTAL, &[("handler", "listing")], 1);
+                    "".to_string()
+                }
+            }
+        } else {
+            match Marketplace::deserialize(data) {
+                Ok(marketplace) => self.marketplace_update(account, &marketplace),
+                Err(e) => {
+                    error!("[account_update] Failed to deserialize marketplace pubkey=[{:?}] error=[{:?}]", account.pubkey, e);
+                    registry().inc_counter(HANDLER_DECODE_ERRORS_TOTAL, &[("handler", "listing")], 1);
+                    "".to_string()
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::AccountKey;
+
+    #[test]
+    fn test_listing_account_update() {
+        let handler = ListingAccountHandler::default();
+        let listing = Listing {
+            bump: 255,
+            lister: Pubkey::new_unique(),
+            token_manager: Pubkey::new_unique(),
+            marketplace: Pubkey::new_unique(),
+            payment_amount: 1_500_000_000,
+            payment_mint: Pubkey::default(),
+        };
+        let mut data = handler.listing_discriminator.to_vec();
+        listing.serialize(&mut data).unwrap();
+        let account = DbAccountInfo {
+            pubkey: AccountKey::from_slice(Pubkey::new_unique().as_ref()),
+            lamports: 1,
+            owner: AccountKey::from_slice(TRANSFER_AUTHORITY_PROGRAM_ID.as_ref()),
+            executable: false,
+            rent_epoch: 0,
+            data,
+            slot: 5,
+            write_version: 1,
+            txn_signature: None,
+        };
+        let query = handler.account_update(&account);
+        assert!(query.contains("INSERT INTO listing"), "{}", query);
+        assert!(query.contains(&format!(
+            "(SELECT mint FROM token_manager WHERE id = '{}'), '{}', 1500000000",
+            listing.token_manager, listing.marketplace
+        )));
+
+        let closed = DbAccountInfo {
+            lamports: 0,
+            data: vec![],
+            ..account.clone()
+        };
+        assert!(handler.account_update(&closed).starts_with("DELETE FROM listing"));
+
+        let mut transfer_authority = account;
+        transfer_authority.data[0..8].copy_from_slice(&account_discriminator("TransferAuthority"));
+        assert!(!handler.account_match(&transfer_authority));
+    }
+}
@@ -0,0 +1,287 @@
+use borsh::BorshDeserialize;
+use log::error;
+use solana_sdk::pubkey::Pubkey;
+
+use super::account_handler::AccountHandler;
+use super::metadata_creators_account_handler::Creator;
+use super::metadata_creators_account_handler::METADATA_PROGRAM_ID;
+use super::DbAccountInfo;
+use crate::metrics::registry;
+use crate::metrics::HANDLER_DECODE_ERRORS_TOTAL;
+use crate::postgres_client::bound_rows::BoundRow;
+use crate::postgres_client::bound_rows::Numeric;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::ConflictStrategy;
+
+const TOKEN_METADATA_DISCRIMINATOR: u8 = 4;
+
+const COLUMNS: [&str; 17] = [
+    "mint",
+    "pubkey",
+    "update_authority",
+    "name",
+    "symbol",
+    "uri",
+    "seller_fee_basis_points",
+    "primary_sale_happened",
+    "is_mutable",
+    "edition_nonce",
+    "token_standard",
+    "collection",
+    "collection_verified",
+    "use_method",
+    "uses_remaining",
+    "uses_total",
+    "slot",
+];
+
+#[derive(BorshDeserialize)]
+struct Collection {
+    verified: bool,
+    key: Pubkey,
+}
+
+#[derive(BorshDeserialize)]
+struct Uses {
+    use_method: u8,
+    remaining: u64,
+    total: u64,
+}
+
+fn token_standard(value: u8) -> &'static str {
+    match value {
+        0 => "non_fungible",
+        1 => "fungible_asset",
+        2 => "fungible",
+        3 => "non_fungible_edition",
+        4 => "programmable_non_fungible",
+        5 => "programmable_non_fungible_edition",
+        _ => "unknown",
+    }
+}
+
+fn use_method(value: u8) -> &'static str {
+    match value {
+        0 => "burn",
+        1 => "multiple",
+        2 => "single",
+        _ => "unknown",
+    }
+}
+
+/// A string of the account, padded with nulls to its maximum length
+fn padded_string(buf: &mut &[u8]) -> std::io::Result<String> {
+    let bytes = Vec::<u8>::deserialize(buf)?;
+    Ok(String::from_utf8_lossy(&bytes).replace('\0', ""))
+}
+
+/// An optional field appended to the account in a later version of the program, absent from the older accounts
+fn appended<T: BorshDeserialize>(buf: &mut &[u8]) -> std::io::Result<Option<T>> {
+    match buf.is_empty() {
+        true => Ok(None),
+        false => Option::<T>::deserialize(buf),
+    }
+}
+
+/// The decoded `mpl_token_metadata::state::Metadata` account, but its creators
+#[derive(Debug, PartialEq)]
+struct DbMetadata {
+    update_authority: Pubkey,
+    mint: Pubkey,
+    name: String,
+    symbol: String,
+    uri: String,
+    seller_fee_basis_points: u16,
+    primary_sale_happened: bool,
+    is_mutable: bool,
+    edition_nonce: Option<u8>,
+    token_standard: Option<&'static str>,
+    collection: Option<(Pubkey, bool)>,
+    uses: Option<(&'static str, u64, u64)>,
+}
+
+impl DbMetadata {
+    fn parse(data: &[u8]) -> std::io::Result<Self> {
+        let buf = &mut data.get(1..).unwrap_or_default();
+        let update_authority = Pubkey::deserialize(buf)?;
+        let mint = Pubkey::deserialize(buf)?;
+        let name = padded_string(buf)?;
+        let symbol = padded_string(buf)?;
+        let uri = padded_string(buf)?;
+        let seller_fee_basis_points = u16::deserialize(buf)?;
+        // written by the `token_metadata_creators` handler
+        Option::<Vec<Creator>>::deserialize(buf)?;
+        let primary_sale_happened = bool::deserialize(buf)?;
+        let is_mutable = bool::deserialize(buf)?;
+        let edition_nonce = appended::<u8>(buf)?;
+        let token_standard = appended::<u8>(buf)?.map(token_standard);
+        let collection = appended::<Collection>(buf)?.map(|collection| (collection.key, collection.verified));
+        let uses = appended::<Uses>(buf)?.map(|uses| (use_method(uses.use_method), uses.remaining, uses.total));
+        Ok(Self {
+            update_authority,
+            mint,
+            name,
+            symbol,
+            uri,
+            seller_fee_basis_points,
+            primary_sale_happened,
+            is_mutable,
+            edition_nonce,
+            token_standard,
+            collection,
+            uses,
+        })
+    }
+}
+
+/// Decodes the whole Metaplex metadata account of a mint into a `token_metadata` row
+#[derive(Clone, Default)]
+pub struct MetadataAccountHandler {
+    pub conflict: ConflictStrategy,
+    pub prefix: TablePrefix,
+}
+
+impl AccountHandler for MetadataAccountHandler {
+    fn init(&self, config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        if !self.enabled(config) {
+            return "".to_string();
+        };
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}token_metadata (
+                mint VARCHAR(44) PRIMARY KEY,
+                pubkey VARCHAR(44) NOT NULL,
+                update_authority VARCHAR(44) NOT NULL,
+                name TEXT NOT NULL,
+                symbol TEXT NOT NULL,
+                uri TEXT NOT NULL,
+                seller_fee_basis_points INTEGER NOT NULL,
+                primary_sale_happened BOOL NOT NULL,
+                is_mutable BOOL NOT NULL,
+                edition_nonce SMALLINT,
+                token_standard VARCHAR(40),
+                collection VARCHAR(44),
+                collection_verified BOOL,
+                use_method VARCHAR(8),
+                uses_remaining NUMERIC(20, 0),
+                uses_total NUMERIC(20, 0),
+                slot BIGINT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}token_metadata_collection ON {prefix}token_metadata (collection);
+            CREATE INDEX IF NOT EXISTS {prefix}token_metadata_update_authority ON {prefix}token_metadata (update_authority);
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn account_match(&self, account: &DbAccountInfo) -> bool {
+        account.owner.as_slice() == METADATA_PROGRAM_ID.as_ref() && TOKEN_METADATA_DISCRIMINATOR == *account.data.get(0).unwrap_or(&0)
+    }
+
+    /// The metadata is written with typed parameters, see `account_rows`
+    fn account_update(&self, _account: &DbAccountInfo) -> String {
+        "".to_string()
+    }
+
+    fn prepared_statements(&self) -> Vec<String> {
+        vec![format!(
+            "INSERT INTO {3}token_metadata AS acc ({0}) \
+            VALUES ({1}) \
+            {2};",
+            COLUMNS.join(", "),
+            (1..=COLUMNS.len()).map(|i| format!("${}", i)).collect::<Vec<String>>().join(", "),
+            self.conflict.on_conflict("mint", &COLUMNS[1..COLUMNS.len() - 1]),
+            self.prefix,
+        )]
+    }
+
+    fn account_rows(&self, account: &DbAccountInfo) -> Vec<BoundRow> {
+        if !self.account_match(account) {
+            return Vec::new();
+        };
+        let metadata = match DbMetadata::parse(&account.data) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                error!("[account_rows] Failed to deserialize metadata pubkey=[{:?}] error=[{:?}]", account.pubkey, e);
+                registry().inc_counter(HANDLER_DECODE_ERRORS_TOTAL, &[("handler", "token_metadata")], 1);
+                return Vec::new();
+            }
+        };
+        vec![BoundRow::new(
+            0,
+            vec![
+                Box::new(metadata.mint.to_string()),
+                Box::new(bs58::encode(&account.pubkey).into_string()),
+                Box::new(metadata.update_authority.to_string()),
+                Box::new(metadata.name),
+                Box::new(metadata.symbol),
+                Box::new(metadata.uri),
+                Box::new(metadata.seller_fee_basis_points as i32),
+                Box::new(metadata.primary_sale_happened),
+                Box::new(metadata.is_mutable),
+                Box::new(metadata.edition_nonce.map(|nonce| nonce as i16)),
+                Box::new(metadata.token_standard),
+                Box::new(metadata.collection.map(|(key, _)| key.to_string())),
+                Box::new(metadata.collection.map(|(_, verified)| verified)),
+                Box::new(metadata.uses.map(|(method, _, _)| method)),
+                Box::new(metadata.uses.map(|(_, remaining, _)| Numeric(remaining))),
+                Box::new(metadata.uses.map(|(_, _, total)| Numeric(total))),
+                Box::new(account.slot),
+            ],
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::BorshSerialize;
+
+    fn padded(value: &str, length: usize) -> Vec<u8> {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize(length, 0);
+        bytes.try_to_vec().unwrap()
+    }
+
+    #[test]
+    fn test_metadata_parse() {
+        let (update_authority, mint, creator, collection) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut data = vec![TOKEN_METADATA_DISCRIMINATOR];
+        data.extend_from_slice(update_authority.as_ref());
+        data.extend_from_slice(mint.as_ref());
+        data.extend(padded("Hero #1", 32));
+        data.extend(padded("HERO", 10));
+        data.extend(padded("https://example.com/1.json", 200));
+        data.extend(500u16.to_le_bytes());
+        let creators = Some(vec![Creator {
+            address: creator,
+            verified: true,
+            share: 100,
+        }]);
+        data.extend(creators.try_to_vec().unwrap());
+        // an account of a version without the appended fields
+        let mut legacy = data.clone();
+        legacy.extend([1, 0]);
+        let metadata = DbMetadata::parse(&legacy).unwrap();
+        assert_eq!((metadata.name.as_str(), metadata.symbol.as_str(), metadata.seller_fee_basis_points), ("Hero #1", "HERO", 500));
+        assert!(metadata.primary_sale_happened && !metadata.is_mutable);
+        assert_eq!((metadata.edition_nonce, metadata.collection, metadata.uses), (None, None, None));
+
+        data.extend([0, 1, 1, 254, 1, 4, 1, 1]);
+        data.extend_from_slice(collection.as_ref());
+        data.extend([1, 1]);
+        data.extend(3u64.to_le_bytes());
+        data.extend(5u64.to_le_bytes());
+        data.resize(679, 0);
+        let metadata = DbMetadata::parse(&data).unwrap();
+        assert_eq!(metadata.mint, mint);
+        assert_eq!(metadata.update_authority, update_authority);
+        assert_eq!(metadata.uri, "https://example.com/1.json");
+        assert_eq!(metadata.edition_nonce, Some(254));
+        assert_eq!(metadata.token_standard, Some("programmable_non_fungible"));
+        assert_eq!(metadata.collection, Some((collection, true)));
+        assert_eq!(metadata.uses, Some(("multiple", 3, 5)));
+
+        assert!(DbMetadata::parse(&data[..100]).is_err());
+    }
+}
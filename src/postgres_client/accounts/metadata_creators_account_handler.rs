@@ -7,6 +7,11 @@ use solana_sdk::pubkey::PUBKEY_BYTES;
 
 use super::account_handler::AccountHandler;
 use super::DbAccountInfo;
+use crate::metrics::registry;
+use crate::metrics::HANDLER_DECODE_ERRORS_TOTAL;
+use crate::postgres_client::bound_rows::BoundRow;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::ConflictStrategy;
 
 pub static METADATA_PROGRAM_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
 const TOKEN_METADATA_MINT_OFFSET: usize = 33;
@@ -23,16 +28,20 @@ pub struct Creator {
     pub share: u8,
 }
 
-#[derive(Clone, Copy)]
-pub struct MetadataCreatorsAccountHandler {}
+#[derive(Clone, Default)]
+pub struct MetadataCreatorsAccountHandler {
+    pub conflict: ConflictStrategy,
+    pub prefix: TablePrefix,
+}
 
 impl AccountHandler for MetadataCreatorsAccountHandler {
     fn init(&self, config: &crate::config::GeyserPluginPostgresConfig) -> String {
         if !self.enabled(config) {
             return "".to_string();
         };
-        return "
-            CREATE TABLE IF NOT EXISTS token_metadata_creators (
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}token_metadata_creators (
                 mint VARCHAR(44) NOT NULL,
                 creator VARCHAR(44) NOT NULL,
                 verified BOOL NOT NULL,
@@ -41,53 +50,64 @@ impl AccountHandler for MetadataCreatorsAccountHandler {
                 slot BIGINT NOT NULL,
                 PRIMARY KEY(creator, mint)
             );
-        "
-        .to_string();
+        ",
+            prefix = self.prefix
+        );
     }
 
     fn account_match(&self, account: &DbAccountInfo) -> bool {
-        account.owner == METADATA_PROGRAM_ID.as_ref() && TOKEN_METADATA_DISCRIMINATOR == *account.data.get(0).unwrap_or(&0)
+        account.owner.as_slice() == METADATA_PROGRAM_ID.as_ref() && TOKEN_METADATA_DISCRIMINATOR == *account.data.get(0).unwrap_or(&0)
+    }
+
+    /// Creators are written with typed parameters, see `account_rows`
+    fn account_update(&self, _account: &DbAccountInfo) -> String {
+        "".to_string()
     }
 
-    fn account_update(&self, account: &DbAccountInfo) -> String {
+    fn prepared_statements(&self) -> Vec<String> {
+        vec![format!(
+            "INSERT INTO {1}token_metadata_creators AS acc (mint, creator, verified, share, position, slot) \
+            VALUES ($1, $2, $3, $4, $5, $6) \
+            {0};",
+            self.conflict.on_conflict("mint, creator", &["verified"]),
+            self.prefix,
+        )]
+    }
+
+    fn account_rows(&self, account: &DbAccountInfo) -> Vec<BoundRow> {
         if !self.account_match(account) {
-            return "".to_string();
+            return Vec::new();
         };
 
         let buf = &mut &account.data[TOKEN_METADATA_CREATORS_OFFSET..];
         if buf[0] == 0 {
-            return "".to_string();
+            return Vec::new();
         }
         let creators: Vec<Creator> = match BorshDeserialize::deserialize(buf) {
             Ok(c) => c,
             Err(e) => {
-                error!("[account_update] Failed to deserialize creators pubkey=[{:?}] error=[{:?}]", account.pubkey, e);
-                return "".to_string();
+                error!("[account_rows] Failed to deserialize creators pubkey=[{:?}] error=[{:?}]", account.pubkey, e);
+                registry().inc_counter(HANDLER_DECODE_ERRORS_TOTAL, &[("handler", "token_metadata_creators")], 1);
+                return Vec::new();
             }
         };
         let mint: &Pubkey = bytemuck::from_bytes(&account.data[TOKEN_METADATA_MINT_OFFSET..TOKEN_METADATA_MINT_OFFSET + PUBKEY_BYTES]);
-        let slot = account.slot;
-        return creators
+        creators
             .iter()
             .enumerate()
             .map(|(index, c)| {
-                format!(
-                    "
-                    INSERT INTO token_metadata_creators AS acc (mint, creator, verified, share, position, slot) \
-                    VALUES ('{0}', '{1}', {2}, {3}, {4}, {5}) \
-                    ON CONFLICT (mint, creator) \
-                    DO UPDATE SET slot=excluded.slot, verified=excluded.verified \
-                    WHERE acc.slot < excluded.slot;
-                ",
-                    &bs58::encode(mint).into_string(),
-                    &bs58::encode(c.address).into_string(),
-                    &c.verified,
-                    &c.share,
-                    &index,
-                    &slot,
+                BoundRow::new(
+                    0,
+                    vec![
+                        Box::new(mint.to_string()),
+                        Box::new(c.address.to_string()),
+                        Box::new(c.verified),
+                        Box::new(c.share as i16),
+                        Box::new(index as i16),
+                        Box::new(account.slot),
+                    ],
                 )
             })
-            .collect::<Vec<String>>()
-            .join("");
+            .collect()
     }
 }
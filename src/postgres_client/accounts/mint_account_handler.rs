@@ -0,0 +1,234 @@
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::pubkey::PUBKEY_BYTES;
+
+use super::account_handler::AccountHandler;
+use super::token_mint_handler::is_mint;
+use super::token_mint_handler::SPL_MINT_DECIMALS_OFFSET;
+use super::token_mint_handler::SPL_MINT_SUPPLY_OFFSET;
+use super::DbAccountInfo;
+use crate::postgres_client::bound_rows::BoundValues;
+use crate::postgres_client::bound_rows::Numeric;
+use crate::postgres_client::bulk_load::StagingTable;
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::ConflictStrategy;
+
+/*
+    /// The SPL mint definition, the authorities are a 4 byte tag followed by the pubkey
+    spl_token::state::Mint {
+        mint_authority: COption<Pubkey>,
+        supply: u64,
+        decimals: u8,
+        is_initialized: bool,
+        freeze_authority: COption<Pubkey>,
+    }
+*/
+const SPL_MINT_MINT_AUTHORITY_OFFSET: usize = 0;
+const SPL_MINT_IS_INITIALIZED_OFFSET: usize = 45;
+const SPL_MINT_FREEZE_AUTHORITY_OFFSET: usize = 46;
+
+const COLUMNS: [&str; 8] = ["pubkey", "token_program", "mint_authority", "supply", "decimals", "freeze_authority", "slot", "write_version"];
+/// The columns of the unique index a row is upserted on
+const KEY: &str = "pubkey";
+
+/// The pubkey of a `COption<Pubkey>` at `offset`
+fn optional_pubkey(data: &[u8], offset: usize) -> Option<String> {
+    match u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) {
+        1 => Some(bs58::encode(&data[offset + 4..offset + 4 + PUBKEY_BYTES]).into_string()),
+        _ => None,
+    }
+}
+
+/// The decoded state of a mint
+#[derive(Debug, PartialEq)]
+struct DbMint {
+    pubkey: String,
+    token_program: String,
+    mint_authority: Option<String>,
+    supply: u64,
+    decimals: u8,
+    freeze_authority: Option<String>,
+}
+
+impl DbMint {
+    /// The state of an initialized mint of either token program
+    fn parse(account: &DbAccountInfo) -> Option<Self> {
+        if !is_mint(account) || account.data[SPL_MINT_IS_INITIALIZED_OFFSET] == 0 {
+            return None;
+        }
+        let token_program: &Pubkey = bytemuck::from_bytes(account.owner.as_slice());
+        Some(Self {
+            pubkey: bs58::encode(&account.pubkey).into_string(),
+            token_program: token_program.to_string(),
+            mint_authority: optional_pubkey(&account.data, SPL_MINT_MINT_AUTHORITY_OFFSET),
+            supply: u64::from_le_bytes(account.data[SPL_MINT_SUPPLY_OFFSET..SPL_MINT_SUPPLY_OFFSET + 8].try_into().unwrap()),
+            decimals: account.data[SPL_MINT_DECIMALS_OFFSET],
+            freeze_authority: optional_pubkey(&account.data, SPL_MINT_FREEZE_AUTHORITY_OFFSET),
+        })
+    }
+}
+
+/// Maintains a `spl_token_mint` row per mint of either token program, with its supply, decimals and authorities
+#[derive(Clone, Default)]
+pub struct MintAccountHandler {
+    pub conflict: ConflictStrategy,
+    pub prefix: TablePrefix,
+}
+
+impl AccountHandler for MintAccountHandler {
+    fn init(&self, config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        if !self.enabled(config) {
+            return "".to_string();
+        };
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}spl_token_mint (
+                pubkey VARCHAR(44) PRIMARY KEY,
+                token_program VARCHAR(44) NOT NULL,
+                mint_authority VARCHAR(44),
+                supply NUMERIC(20, 0) NOT NULL,
+                decimals SMALLINT NOT NULL,
+                freeze_authority VARCHAR(44),
+                slot BIGINT NOT NULL,
+                write_version BIGINT NOT NULL DEFAULT 0
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}spl_token_mint_mint_authority ON {prefix}spl_token_mint (mint_authority);
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn account_match(&self, account: &DbAccountInfo) -> bool {
+        is_mint(account)
+    }
+
+    fn account_update(&self, account: &DbAccountInfo) -> String {
+        match self.staging_row(account) {
+            Some(row) => format!(
+                "
+                    INSERT INTO {3}spl_token_mint AS acc ({0}) \
+                    VALUES {1} \
+                    {2};
+                ",
+                COLUMNS.join(", "),
+                row,
+                self.on_conflict(),
+                self.prefix,
+            ),
+            None => "".to_string(),
+        }
+    }
+
+    fn staging_table(&self) -> Option<StagingTable> {
+        Some(StagingTable {
+            table: self.prefix.table("spl_token_mint"),
+            columns: &COLUMNS,
+            key: KEY,
+            on_conflict: self.on_conflict(),
+        })
+    }
+
+    fn staging_row(&self, account: &DbAccountInfo) -> Option<String> {
+        let mint = DbMint::parse(account)?;
+        Some(format!(
+            "('{0}', '{1}', {2}, {3}, {4}, {5}, {6}, {7})",
+            mint.pubkey,
+            mint.token_program,
+            mint.mint_authority.sql_literal(),
+            mint.supply,
+            mint.decimals,
+            mint.freeze_authority.sql_literal(),
+            account.slot,
+            account.write_version,
+        ))
+    }
+
+    fn staging_values(&self, account: &DbAccountInfo) -> Option<BoundValues> {
+        let mint = DbMint::parse(account)?;
+        Some(vec![
+            Box::new(mint.pubkey),
+            Box::new(mint.token_program),
+            Box::new(mint.mint_authority),
+            Box::new(Numeric(mint.supply)),
+            Box::new(mint.decimals as i16),
+            Box::new(mint.freeze_authority),
+            Box::new(account.slot),
+            Box::new(account.write_version),
+        ])
+    }
+}
+
+impl MintAccountHandler {
+    fn on_conflict(&self) -> String {
+        self.conflict.on_conflict(KEY, &["mint_authority", "supply", "decimals", "freeze_authority", "write_version"])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::accounts::token_account_handler::SPL_TOKEN_ACCOUNT_LENGTH;
+    use crate::postgres_client::accounts::token_account_handler::TOKENZ_PROGRAM_ID;
+    use crate::postgres_client::accounts::token_account_handler::TOKEN_PROGRAM_ID;
+    use crate::postgres_client::accounts::token_mint_handler::SPL_MINT_LENGTH;
+    use crate::postgres_client::AccountKey;
+
+    fn mint_account(owner: &Pubkey, data: Vec<u8>) -> DbAccountInfo {
+        DbAccountInfo {
+            pubkey: AccountKey::from_slice(&[1; 32]),
+            lamports: 1_461_600,
+            owner: AccountKey::from_slice(owner.as_ref()),
+            executable: false,
+            rent_epoch: 0,
+            data,
+            slot: 5,
+            write_version: 7,
+            txn_signature: None,
+        }
+    }
+
+    #[test]
+    fn test_mint_account_update() {
+        let authority = Pubkey::new_unique();
+        let mut data = vec![0; SPL_MINT_LENGTH];
+        data[..4].copy_from_slice(&1u32.to_le_bytes());
+        data[4..36].copy_from_slice(authority.as_ref());
+        data[SPL_MINT_SUPPLY_OFFSET..SPL_MINT_SUPPLY_OFFSET + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        data[SPL_MINT_DECIMALS_OFFSET] = 6;
+        data[SPL_MINT_IS_INITIALIZED_OFFSET] = 1;
+        let account = mint_account(&TOKEN_PROGRAM_ID, data.clone());
+        assert_eq!(
+            DbMint::parse(&account),
+            Some(DbMint {
+                pubkey: "4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi".to_string(),
+                token_program: TOKEN_PROGRAM_ID.to_string(),
+                mint_authority: Some(authority.to_string()),
+                supply: u64::MAX,
+                decimals: 6,
+                freeze_authority: None,
+            })
+        );
+        let handler = MintAccountHandler::default();
+        let query = handler.account_update(&account);
+        assert!(
+            query.contains(&format!(
+                "VALUES ('4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi', '{}', '{}', 18446744073709551615, 6, NULL, 5, 7)",
+                TOKEN_PROGRAM_ID, authority
+            )),
+            "{}",
+            query
+        );
+        assert!(query.contains("ON CONFLICT (pubkey) DO UPDATE SET mint_authority=excluded.mint_authority"));
+
+        // a Token-2022 mint with extensions is padded to the length of a token account, followed by its account type
+        let mut extended = data;
+        extended.resize(SPL_TOKEN_ACCOUNT_LENGTH + 1, 0);
+        extended[SPL_TOKEN_ACCOUNT_LENGTH] = 1;
+        assert!(DbMint::parse(&mint_account(&TOKENZ_PROGRAM_ID, extended.clone())).is_some());
+        extended[SPL_TOKEN_ACCOUNT_LENGTH] = 2;
+        assert!(handler.account_update(&mint_account(&TOKENZ_PROGRAM_ID, extended)).is_empty());
+
+        let uninitialized = mint_account(&TOKEN_PROGRAM_ID, vec![0; SPL_MINT_LENGTH]);
+        assert!(handler.staging_values(&uninitialized).is_none());
+    }
+}
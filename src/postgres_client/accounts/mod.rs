@@ -1,7 +1,35 @@
+pub mod account_audit_handler;
+pub mod account_data_compression;
 pub mod account_handler;
+pub mod associated_token_account_handler;
+pub mod balance_change_handler;
+pub mod balance_history_handler;
+pub mod decoded_account;
+#[cfg(feature = "idl")]
+pub mod diff_account_handler;
+#[cfg(feature = "idl")]
+pub mod idl_account_handler;
+pub mod idl_decoder;
+pub mod idl_registry;
+pub mod layout_account_handler;
+#[cfg(feature = "cardinal")]
+pub mod listing_handler;
+#[cfg(feature = "metaplex")]
+pub mod metadata_account_handler;
+#[cfg(feature = "metaplex")]
 pub mod metadata_creators_account_handler;
+pub mod mint_account_handler;
+pub mod script_account_handler;
 pub mod token_account_handler;
+pub mod token_extensions;
+#[cfg(feature = "cardinal")]
 pub mod token_manager_handler;
+#[cfg(feature = "cardinal")]
+pub mod token_manager_receipt_handler;
+pub mod token_mint_handler;
+#[cfg(feature = "cardinal")]
+pub mod transfer_authority_handler;
 pub mod unknown_account_handler;
+pub mod wasm_account_handler;
 
 pub use self::account_handler::DbAccountInfo;
@@ -0,0 +1,301 @@
+#[cfg(feature = "scripting")]
+pub use self::runtime::ScriptAccountHandler;
+use crate::postgres_client::is_identifier;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+/// * The `script_handlers` section registers account handlers written in Rhai, requires the `scripting` feature.
+/// "script_handlers" : \[{
+///     "handler_id" : "my_pool",
+///     "path" : "/solana/my_pool.rhai",
+///     "table" : "my_pool"
+/// }\]
+/// The script defines `fn decode(account)` returning a map stored as JSON, or `()` to skip the account.
+/// Scripts are reloaded when the file changes.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptHandlerConfig {
+    /// The id used in the `accounts_selector`
+    pub handler_id: String,
+    /// Path to the script
+    pub path: String,
+    /// The table decoded accounts are written to
+    pub table: String,
+    /// Maximum number of operations a single `decode` call may run
+    pub max_operations: u64,
+}
+
+impl Default for ScriptHandlerConfig {
+    fn default() -> Self {
+        Self {
+            handler_id: String::default(),
+            path: String::default(),
+            table: String::default(),
+            max_operations: 100_000,
+        }
+    }
+}
+
+impl ScriptHandlerConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if cfg!(not(feature = "scripting")) {
+            return Err(format!("script_handlers \"{}\" requires the plugin to be built with the \"scripting\" feature", self.handler_id));
+        }
+        if self.handler_id.is_empty() {
+            return Err("script_handlers entries must set \"handler_id\"".to_string());
+        }
+        if !is_identifier(&self.table) {
+            return Err(format!("script_handlers.{} table \"{}\" is not a valid identifier", self.handler_id, self.table));
+        }
+        if !std::path::Path::new(&self.path).exists() {
+            return Err(format!("script_handlers.{} path {} does not exist", self.handler_id, self.path));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "scripting")]
+mod runtime {
+    use super::super::account_handler::AccountHandler;
+    use super::super::decoded_account::DecodedAccountTable;
+    use super::super::DbAccountInfo;
+    use super::ScriptHandlerConfig;
+    use crate::config::GeyserPluginPostgresConfig;
+    use crate::metrics::registry;
+    use crate::metrics::HANDLER_DECODE_ERRORS_TOTAL;
+    use crate::postgres_client::sql_value::SqlValue;
+    use crate::postgres_client::table_prefix::TablePrefix;
+    use crate::postgres_client::ConflictStrategy;
+    use log::error;
+    use log::info;
+    use rhai::Blob;
+    use rhai::Dynamic;
+    use rhai::Engine;
+    use rhai::EvalAltResult;
+    use rhai::Map;
+    use rhai::Scope;
+    use rhai::AST;
+    use serde_json::Value;
+    use solana_sdk::pubkey::Pubkey;
+    use std::sync::RwLock;
+    use std::time::Duration;
+    use std::time::Instant;
+    use std::time::SystemTime;
+
+    /// How often the script file is checked for changes
+    const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+    struct LoadedScript {
+        ast: AST,
+        modified: Option<SystemTime>,
+        checked: Instant,
+    }
+
+    /// Decodes accounts with a Rhai script into a JSONB table
+    pub struct ScriptAccountHandler {
+        config: ScriptHandlerConfig,
+        store_decoded_accounts: bool,
+        conflict: ConflictStrategy,
+        prefix: TablePrefix,
+        engine: Engine,
+        script: RwLock<LoadedScript>,
+    }
+
+    fn read_bytes<const N: usize>(data: &Blob, offset: i64) -> Result<[u8; N], Box<EvalAltResult>> {
+        usize::try_from(offset)
+            .ok()
+            .and_then(|offset| data.get(offset..offset.checked_add(N)?))
+            .map(|bytes| bytes.try_into().unwrap())
+            .ok_or_else(|| format!("read of {} bytes at offset {} is out of bounds", N, offset).into())
+    }
+
+    /// The engine with the decoding helpers available to scripts
+    pub fn script_engine(max_operations: u64) -> Engine {
+        let mut engine = Engine::new();
+        engine.set_max_operations(max_operations);
+        engine.register_fn("read_u8", |data: &mut Blob, offset: i64| read_bytes::<1>(data, offset).map(|b| b[0] as i64));
+        engine.register_fn("read_bool", |data: &mut Blob, offset: i64| read_bytes::<1>(data, offset).map(|b| b[0] != 0));
+        engine.register_fn("read_u16", |data: &mut Blob, offset: i64| read_bytes::<2>(data, offset).map(|b| u16::from_le_bytes(b) as i64));
+        engine.register_fn("read_u32", |data: &mut Blob, offset: i64| read_bytes::<4>(data, offset).map(|b| u32::from_le_bytes(b) as i64));
+        engine.register_fn("read_i64", |data: &mut Blob, offset: i64| read_bytes::<8>(data, offset).map(i64::from_le_bytes));
+        // u64 values that don't fit an integer are returned as strings
+        engine.register_fn("read_u64", |data: &mut Blob, offset: i64| {
+            read_bytes::<8>(data, offset).map(|b| {
+                let value = u64::from_le_bytes(b);
+                i64::try_from(value).map(Dynamic::from).unwrap_or_else(|_| Dynamic::from(value.to_string()))
+            })
+        });
+        engine.register_fn("read_pubkey", |data: &mut Blob, offset: i64| {
+            read_bytes::<32>(data, offset).map(|b| Pubkey::new_from_array(b).to_string())
+        });
+        engine.register_fn("base58", |data: &mut Blob| bs58::encode(data).into_string());
+        engine
+    }
+
+    impl ScriptAccountHandler {
+        pub fn new(config: &ScriptHandlerConfig, store_decoded_accounts: bool, conflict: ConflictStrategy, prefix: TablePrefix) -> Result<Self, String> {
+            let engine = script_engine(config.max_operations);
+            let script = RwLock::new(LoadedScript {
+                ast: engine.compile_file(config.path.clone().into()).map_err(|e| e.to_string())?,
+                modified: std::fs::metadata(&config.path).and_then(|m| m.modified()).ok(),
+                checked: Instant::now(),
+            });
+            Ok(Self {
+                config: config.clone(),
+                store_decoded_accounts,
+                conflict,
+                prefix,
+                engine,
+                script,
+            })
+        }
+
+        /// Recompile the script if the file changed since it was loaded, a script that fails to compile keeps the previous version
+        fn reload_if_modified(&self) {
+            if self.script.read().unwrap().checked.elapsed() < RELOAD_CHECK_INTERVAL {
+                return;
+            }
+            let mut script = self.script.write().unwrap();
+            script.checked = Instant::now();
+            let modified = std::fs::metadata(&self.config.path).and_then(|m| m.modified()).ok();
+            if modified == script.modified {
+                return;
+            }
+            script.modified = modified;
+            match self.engine.compile_file(self.config.path.clone().into()) {
+                Ok(ast) => {
+                    script.ast = ast;
+                    info!("[account_update] Reloaded script handler=[{}] path=[{}]", self.config.handler_id, self.config.path);
+                }
+                Err(e) => error!("[account_update] Failed to reload script handler=[{}] error=[{}]", self.config.handler_id, e),
+            }
+        }
+
+        /// Run the script's `decode` on the account, returning the map it produced if any
+        pub fn decode(&self, account: &DbAccountInfo) -> Result<Option<Value>, String> {
+            self.reload_if_modified();
+            let mut input = Map::new();
+            input.insert("pubkey".into(), Dynamic::from(bs58::encode(&account.pubkey).into_string()));
+            input.insert("owner".into(), Dynamic::from(bs58::encode(&account.owner).into_string()));
+            input.insert("lamports".into(), Dynamic::from(account.lamports));
+            input.insert("slot".into(), Dynamic::from(account.slot));
+            input.insert("data".into(), Dynamic::from_blob(account.data.clone()));
+            let script = self.script.read().unwrap();
+            let output = self.engine.call_fn::<Dynamic>(&mut Scope::new(), &script.ast, "decode", (input,)).map_err(|e| e.to_string())?;
+            if output.is_unit() {
+                return Ok(None);
+            }
+            if !output.is_map() {
+                return Err(format!("decode returned {} instead of a map", output.type_name()));
+            }
+            rhai::serde::from_dynamic::<Value>(&output).map(Some).map_err(|e| e.to_string())
+        }
+    }
+
+    impl AccountHandler for ScriptAccountHandler {
+        fn init(&self, _config: &GeyserPluginPostgresConfig) -> String {
+            format!(
+                "
+                CREATE TABLE IF NOT EXISTS {0} (
+                    id VARCHAR(44) NOT NULL,
+                    data JSONB NOT NULL,
+                    slot BIGINT NOT NULL,
+                    PRIMARY KEY(id)
+                );
+            ",
+                self.prefix.table(&self.config.table)
+            )
+        }
+
+        fn account_match(&self, _account: &DbAccountInfo) -> bool {
+            true
+        }
+
+        fn account_update(&self, account: &DbAccountInfo) -> String {
+            let data = match self.decode(account) {
+                Ok(Some(data)) => data,
+                Ok(None) => return "".to_string(),
+                Err(e) => {
+                    error!(
+                        "[account_update] Script handler failed handler=[{}] pubkey=[{:?}] error=[{}]",
+                        self.config.handler_id,
+                        bs58::encode(&account.pubkey).into_string(),
+                        e
+                    );
+                    registry().inc_counter(HANDLER_DECODE_ERRORS_TOTAL, &[("handler", &self.config.handler_id)], 1);
+                    return "".to_string();
+                }
+            };
+            let account_key: &Pubkey = bytemuck::from_bytes(&account.pubkey);
+            let mut query = format!(
+                "
+                INSERT INTO {0} AS acc (id, data, slot) \
+                VALUES ('{1}', {2}, {3}) \
+                {4};
+                ",
+                self.prefix.table(&self.config.table),
+                &account_key.to_string(),
+                data.to_string().sql_literal(),
+                &account.slot,
+                self.conflict.on_conflict("id", &["data"]),
+            );
+            if self.store_decoded_accounts {
+                query.push_str(&DecodedAccountTable::update(&self.prefix, account, &self.config.handler_id, &data));
+            }
+            query
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::postgres_client::AccountKey;
+        use std::io::Write;
+
+        #[test]
+        fn test_script_account_update() {
+            let mut file = tempfile::Builder::new().suffix(".rhai").tempfile().unwrap();
+            write!(
+                file,
+                r#"
+                fn decode(account) {{
+                    if account.data.len() < 41 {{ return (); }}
+                    #{{ "mint": account.data.read_pubkey(0), "amount": account.data.read_u64(32), "frozen": account.data.read_bool(40) }}
+                }}
+                "#
+            )
+            .unwrap();
+            let config = ScriptHandlerConfig {
+                handler_id: "script".to_string(),
+                path: file.path().to_str().unwrap().to_string(),
+                table: "script".to_string(),
+                ..ScriptHandlerConfig::default()
+            };
+            let handler = ScriptAccountHandler::new(&config, false, ConflictStrategy::default(), TablePrefix::default()).unwrap();
+
+            let mint = Pubkey::new_unique();
+            let mut data = mint.as_ref().to_vec();
+            data.extend_from_slice(&u64::MAX.to_le_bytes());
+            data.push(1);
+            let mut account = DbAccountInfo {
+                pubkey: AccountKey::from_slice(Pubkey::new_unique().as_ref()),
+                lamports: 1,
+                owner: AccountKey::from_slice(Pubkey::new_unique().as_ref()),
+                executable: false,
+                rent_epoch: 0,
+                data,
+                slot: 5,
+                write_version: 1,
+                txn_signature: None,
+            };
+            assert_eq!(
+                handler.decode(&account).unwrap(),
+                Some(serde_json::json!({ "mint": mint.to_string(), "amount": u64::MAX.to_string(), "frozen": true }))
+            );
+            assert!(handler.account_update(&account).contains(&mint.to_string()));
+
+            account.data.truncate(8);
+            assert_eq!(handler.decode(&account).unwrap(), None);
+        }
+    }
+}
@@ -3,12 +3,20 @@ use solana_sdk::pubkey::Pubkey;
 use solana_sdk::pubkey::PUBKEY_BYTES;
 
 use super::account_handler::AccountHandler;
+use super::token_extensions;
+use super::token_extensions::DbTokenExtensions;
 use super::DbAccountInfo;
+use crate::postgres_client::bound_rows::BoundRow;
+use crate::postgres_client::bound_rows::BoundValues;
+use crate::postgres_client::bound_rows::Numeric;
+use crate::postgres_client::bulk_load::StagingTable;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::ConflictStrategy;
 
 pub static TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
 pub static TOKENZ_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
 /*
-    /// The SPL token definition -- we care about only the mint and owner fields for now at offset 0 and 32 respectively
+    /// The SPL token definition -- we care about the mint, owner and amount fields at offset 0, 32 and 64 respectively
     spl_token::state::Account {
         mint: Pubkey,
         owner: Pubkey,
@@ -20,59 +28,172 @@ pub static TOKENZ_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHn
         close_authority: COption<Pubkey>,
     }
 */
-const SPL_TOKEN_ACCOUNT_MINT_OFFSET: usize = 0;
-const SPL_TOKEN_ACCOUNT_OWNER_OFFSET: usize = 32;
-const SPL_TOKEN_ACCOUNT_LENGTH: usize = 165;
+pub(crate) const SPL_TOKEN_ACCOUNT_MINT_OFFSET: usize = 0;
+pub(crate) const SPL_TOKEN_ACCOUNT_OWNER_OFFSET: usize = 32;
+const SPL_TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+/// Token-2022 accounts of either type with extensions are padded to this length, followed by the account type
+pub(crate) const SPL_TOKEN_ACCOUNT_LENGTH: usize = 165;
 const SPL_TOKEN_ACCOUNT_DISCRIMINATOR: u8 = 2;
+const SPL_TOKEN_MINT_DISCRIMINATOR: u8 = 1;
 
-#[derive(Clone, Copy)]
-pub struct TokenAccountHandler {}
+const COLUMNS: [&str; 6] = ["pubkey", "owner", "mint", "amount", "slot", "write_version"];
+/// The columns of the unique index a row is upserted on
+const KEY: &str = "pubkey, owner, mint";
+
+/// Whether the account is a token account of either token program
+pub(crate) fn is_token_account(account: &DbAccountInfo) -> bool {
+    account.owner.as_slice() == TOKEN_PROGRAM_ID.as_ref() && account.data.len() == SPL_TOKEN_ACCOUNT_LENGTH
+        || account.owner.as_slice() == TOKENZ_PROGRAM_ID.as_ref() && SPL_TOKEN_ACCOUNT_DISCRIMINATOR == *account.data.get(SPL_TOKEN_ACCOUNT_LENGTH).unwrap_or(&0)
+}
+
+/// Whether the account is a Token-2022 mint with extensions
+fn is_extended_mint(account: &DbAccountInfo) -> bool {
+    account.owner.as_slice() == TOKENZ_PROGRAM_ID.as_ref() && SPL_TOKEN_MINT_DISCRIMINATOR == *account.data.get(SPL_TOKEN_ACCOUNT_LENGTH).unwrap_or(&0)
+}
+
+/// Maintains the `spl_token_account` rows of the token accounts, and the `spl_token_extension` rows of the
+/// Token-2022 mints and token accounts with extensions
+#[derive(Clone, Default)]
+pub struct TokenAccountHandler {
+    pub conflict: ConflictStrategy,
+    pub prefix: TablePrefix,
+}
 
 impl AccountHandler for TokenAccountHandler {
     fn init(&self, config: &crate::config::GeyserPluginPostgresConfig) -> String {
         if !self.enabled(config) {
             return "".to_string();
         };
-        return "
-            CREATE TABLE IF NOT EXISTS spl_token_account (
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}spl_token_account (
                 pubkey VARCHAR(44) NOT NULL,
                 owner VARCHAR(44) NOT NULL,
                 mint VARCHAR(44) NOT NULL,
-                slot BIGINT NOT NULL
+                amount NUMERIC(20, 0) NOT NULL DEFAULT 0,
+                slot BIGINT NOT NULL,
+                write_version BIGINT NOT NULL DEFAULT 0
             );
-            CREATE INDEX IF NOT EXISTS spl_token_account_owner ON spl_token_account (owner);
-            CREATE INDEX IF NOT EXISTS spl_token_account_mint ON spl_token_account (mint);
-            CREATE UNIQUE INDEX IF NOT EXISTS spl_token_account_owner_pair ON spl_token_account (pubkey, owner, mint);
-        "
-        .to_string();
+            CREATE INDEX IF NOT EXISTS {prefix}spl_token_account_owner ON {prefix}spl_token_account (owner);
+            CREATE INDEX IF NOT EXISTS {prefix}spl_token_account_mint ON {prefix}spl_token_account (mint);
+            CREATE UNIQUE INDEX IF NOT EXISTS {prefix}spl_token_account_owner_pair ON {prefix}spl_token_account (pubkey, owner, mint);
+            CREATE TABLE IF NOT EXISTS {prefix}spl_token_extension (
+                pubkey VARCHAR(44) PRIMARY KEY,
+                account_type VARCHAR(8) NOT NULL,
+                extensions VARCHAR(40)[] NOT NULL,
+                transfer_fee_config_authority VARCHAR(44),
+                withdraw_withheld_authority VARCHAR(44),
+                withheld_amount NUMERIC(20, 0),
+                older_transfer_fee_epoch BIGINT,
+                older_maximum_fee NUMERIC(20, 0),
+                older_transfer_fee_basis_points INTEGER,
+                newer_transfer_fee_epoch BIGINT,
+                newer_maximum_fee NUMERIC(20, 0),
+                newer_transfer_fee_basis_points INTEGER,
+                permanent_delegate VARCHAR(44),
+                interest_rate_authority VARCHAR(44),
+                interest_initialization_timestamp BIGINT,
+                interest_pre_update_average_rate SMALLINT,
+                interest_last_update_timestamp BIGINT,
+                interest_current_rate SMALLINT,
+                metadata_pointer_authority VARCHAR(44),
+                metadata_address VARCHAR(44),
+                slot BIGINT NOT NULL,
+                write_version BIGINT NOT NULL DEFAULT 0
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}spl_token_extension_permanent_delegate ON {prefix}spl_token_extension (permanent_delegate);
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn schema_migrations(&self, _config: &crate::config::GeyserPluginPostgresConfig) -> Vec<String> {
+        vec![
+            format!("ALTER TABLE {}spl_token_account ADD COLUMN IF NOT EXISTS amount NUMERIC(20, 0) NOT NULL DEFAULT 0;", self.prefix),
+            format!("ALTER TABLE {}spl_token_account ADD COLUMN IF NOT EXISTS write_version BIGINT NOT NULL DEFAULT 0;", self.prefix),
+        ]
     }
 
     fn account_match(&self, account: &DbAccountInfo) -> bool {
-        account.owner == TOKEN_PROGRAM_ID.as_ref() && account.data.len() == SPL_TOKEN_ACCOUNT_LENGTH
-            || account.owner == TOKENZ_PROGRAM_ID.as_ref() && SPL_TOKEN_ACCOUNT_DISCRIMINATOR == *account.data.get(SPL_TOKEN_ACCOUNT_LENGTH).unwrap_or(&0)
+        is_token_account(account) || is_extended_mint(account)
     }
 
     fn account_update(&self, account: &DbAccountInfo) -> String {
-        if !self.account_match(account) {
-            return "".to_string();
+        match self.staging_row(account) {
+            Some(row) => format!(
+                "
+                    INSERT INTO {prefix}spl_token_account AS acc ({0}) \
+                    VALUES {1} \
+                    {2};
+                ",
+                COLUMNS.join(", "),
+                row,
+                self.on_conflict(),
+                prefix = self.prefix,
+            ),
+            None => "".to_string(),
+        }
+    }
+
+    fn staging_table(&self) -> Option<StagingTable> {
+        Some(StagingTable {
+            table: self.prefix.table("spl_token_account"),
+            columns: &COLUMNS,
+            key: KEY,
+            on_conflict: self.on_conflict(),
+        })
+    }
+
+    fn staging_row(&self, account: &DbAccountInfo) -> Option<String> {
+        let (pubkey, owner, mint, amount) = Self::token_account(account)?;
+        Some(format!("('{0}', '{1}', '{2}', {3}, {4}, {5})", pubkey, owner, mint, amount, &account.slot, account.write_version))
+    }
+
+    fn staging_values(&self, account: &DbAccountInfo) -> Option<BoundValues> {
+        let (pubkey, owner, mint, amount) = Self::token_account(account)?;
+        Some(vec![
+            Box::new(pubkey),
+            Box::new(owner),
+            Box::new(mint),
+            Box::new(Numeric(amount)),
+            Box::new(account.slot),
+            Box::new(account.write_version),
+        ])
+    }
+
+    fn prepared_statements(&self) -> Vec<String> {
+        vec![format!(
+            "INSERT INTO {prefix}spl_token_extension AS acc ({0}) \
+            VALUES ({1}) \
+            {2};",
+            token_extensions::COLUMNS.join(", "),
+            token_extensions::placeholders(),
+            self.conflict.on_conflict("pubkey", &token_extensions::UPDATED_COLUMNS),
+            prefix = self.prefix,
+        )]
+    }
+
+    fn account_rows(&self, account: &DbAccountInfo) -> Vec<BoundRow> {
+        match DbTokenExtensions::parse(account) {
+            Some(extensions) => vec![extensions.bind(0)],
+            None => Vec::new(),
+        }
+    }
+}
+
+impl TokenAccountHandler {
+    /// The pubkey, owner and mint in base58 and the amount of a token account
+    fn token_account(account: &DbAccountInfo) -> Option<(String, String, String, u64)> {
+        if !is_token_account(account) {
+            return None;
         };
         let mint: &Pubkey = bytemuck::from_bytes(&account.data[SPL_TOKEN_ACCOUNT_MINT_OFFSET..SPL_TOKEN_ACCOUNT_MINT_OFFSET + PUBKEY_BYTES]);
         let owner: &Pubkey = bytemuck::from_bytes(&account.data[SPL_TOKEN_ACCOUNT_OWNER_OFFSET..SPL_TOKEN_ACCOUNT_OWNER_OFFSET + PUBKEY_BYTES]);
-        let pubkey_bytes: [u8; 32] = account.pubkey[..].try_into().unwrap();
-        let pubkey = Pubkey::from(pubkey_bytes);
-        let slot = account.slot;
-        format!(
-            "
-                INSERT INTO spl_token_account AS spl_token_entry (pubkey, owner, mint, slot) \
-                VALUES ('{0}', '{1}', '{2}', {3}) \
-                ON CONFLICT (pubkey, owner, mint) \
-                DO UPDATE SET slot=excluded.slot \
-                WHERE spl_token_entry.slot < excluded.slot;
-            ",
-            &bs58::encode(pubkey).into_string(),
-            &bs58::encode(owner).into_string(),
-            &bs58::encode(mint).into_string(),
-            &slot,
-        )
+        let amount = u64::from_le_bytes(account.data[SPL_TOKEN_ACCOUNT_AMOUNT_OFFSET..SPL_TOKEN_ACCOUNT_AMOUNT_OFFSET + 8].try_into().unwrap());
+        Some((bs58::encode(&account.pubkey).into_string(), bs58::encode(owner).into_string(), bs58::encode(mint).into_string(), amount))
+    }
+
+    fn on_conflict(&self) -> String {
+        self.conflict.on_conflict(KEY, &["amount", "write_version"])
     }
 }
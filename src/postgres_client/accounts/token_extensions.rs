@@ -0,0 +1,405 @@
+use solana_sdk::pubkey::PUBKEY_BYTES;
+
+use super::token_account_handler::SPL_TOKEN_ACCOUNT_LENGTH;
+use super::token_account_handler::TOKENZ_PROGRAM_ID;
+use super::DbAccountInfo;
+use crate::postgres_client::bound_rows::BoundRow;
+
+/*
+    /// Token-2022 mints and token accounts with extensions are padded to the length of a token account,
+    /// followed by the account type and the extensions, each as a type-length-value entry
+    [base state][padding to 165][account type: u8][extension type: u16][length: u16][value]...
+*/
+const ACCOUNT_TYPE_OFFSET: usize = SPL_TOKEN_ACCOUNT_LENGTH;
+const TLV_OFFSET: usize = SPL_TOKEN_ACCOUNT_LENGTH + 1;
+const TLV_HEADER_LENGTH: usize = 4;
+const MINT_ACCOUNT_TYPE: u8 = 1;
+const TOKEN_ACCOUNT_TYPE: u8 = 2;
+
+const TRANSFER_FEE_CONFIG: u16 = 1;
+const TRANSFER_FEE_AMOUNT: u16 = 2;
+const INTEREST_BEARING_CONFIG: u16 = 10;
+const PERMANENT_DELEGATE: u16 = 12;
+const METADATA_POINTER: u16 = 18;
+
+/// The names of the extension types, by type
+const EXTENSION_NAMES: [&str; 24] = [
+    "uninitialized",
+    "transfer_fee_config",
+    "transfer_fee_amount",
+    "mint_close_authority",
+    "confidential_transfer_mint",
+    "confidential_transfer_account",
+    "default_account_state",
+    "immutable_owner",
+    "memo_transfer",
+    "non_transferable",
+    "interest_bearing_config",
+    "cpi_guard",
+    "permanent_delegate",
+    "non_transferable_account",
+    "transfer_hook",
+    "transfer_hook_account",
+    "confidential_transfer_fee_config",
+    "confidential_transfer_fee_amount",
+    "metadata_pointer",
+    "token_metadata",
+    "group_pointer",
+    "token_group",
+    "group_member_pointer",
+    "token_group_member",
+];
+
+/// The columns of the `spl_token_extension` rows, in the order they are bound
+pub(crate) const COLUMNS: [&str; 22] = [
+    "pubkey",
+    "account_type",
+    "extensions",
+    "transfer_fee_config_authority",
+    "withdraw_withheld_authority",
+    "withheld_amount",
+    "older_transfer_fee_epoch",
+    "older_maximum_fee",
+    "older_transfer_fee_basis_points",
+    "newer_transfer_fee_epoch",
+    "newer_maximum_fee",
+    "newer_transfer_fee_basis_points",
+    "permanent_delegate",
+    "interest_rate_authority",
+    "interest_initialization_timestamp",
+    "interest_pre_update_average_rate",
+    "interest_last_update_timestamp",
+    "interest_current_rate",
+    "metadata_pointer_authority",
+    "metadata_address",
+    "slot",
+    "write_version",
+];
+
+/// The columns of a row updated on conflict, the slot is always updated
+pub(crate) const UPDATED_COLUMNS: [&str; 20] = [
+    "account_type",
+    "extensions",
+    "transfer_fee_config_authority",
+    "withdraw_withheld_authority",
+    "withheld_amount",
+    "older_transfer_fee_epoch",
+    "older_maximum_fee",
+    "older_transfer_fee_basis_points",
+    "newer_transfer_fee_epoch",
+    "newer_maximum_fee",
+    "newer_transfer_fee_basis_points",
+    "permanent_delegate",
+    "interest_rate_authority",
+    "interest_initialization_timestamp",
+    "interest_pre_update_average_rate",
+    "interest_last_update_timestamp",
+    "interest_current_rate",
+    "metadata_pointer_authority",
+    "metadata_address",
+    "write_version",
+];
+
+/// The columns bound as text and cast, as u64 amounts have no binary mapping to NUMERIC
+const NUMERIC_COLUMNS: [&str; 3] = ["withheld_amount", "older_maximum_fee", "newer_maximum_fee"];
+
+/// An `OptionalNonZeroPubkey`, all zeroes when unset
+fn optional_pubkey(data: &[u8]) -> Option<String> {
+    match data.iter().all(|b| *b == 0) {
+        true => None,
+        false => Some(bs58::encode(data).into_string()),
+    }
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn i64_at(data: &[u8], offset: usize) -> i64 {
+    i64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn i16_at(data: &[u8], offset: usize) -> i16 {
+    i16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+/// The fee of a `TransferFeeConfig` from its epoch on
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DbTransferFee {
+    pub epoch: i64,
+    pub maximum_fee: u64,
+    pub basis_points: i32,
+}
+
+impl DbTransferFee {
+    const LENGTH: usize = 18;
+
+    fn parse(data: &[u8]) -> Self {
+        Self {
+            epoch: u64_at(data, 0) as i64,
+            maximum_fee: u64_at(data, 8),
+            basis_points: u16_at(data, 16) as i32,
+        }
+    }
+}
+
+/// The state of the common extensions of a Token-2022 mint or token account
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DbTokenExtensions {
+    pub pubkey: String,
+    pub account_type: &'static str,
+    /// Every extension of the account, including the ones without columns
+    pub extensions: Vec<String>,
+    pub transfer_fee_config_authority: Option<String>,
+    pub withdraw_withheld_authority: Option<String>,
+    /// Withheld on the mint by its `TransferFeeConfig`, or on the token account by its `TransferFeeAmount`
+    pub withheld_amount: Option<u64>,
+    pub older_transfer_fee: Option<DbTransferFee>,
+    pub newer_transfer_fee: Option<DbTransferFee>,
+    pub permanent_delegate: Option<String>,
+    pub interest_rate_authority: Option<String>,
+    pub interest_initialization_timestamp: Option<i64>,
+    pub interest_pre_update_average_rate: Option<i16>,
+    pub interest_last_update_timestamp: Option<i64>,
+    pub interest_current_rate: Option<i16>,
+    pub metadata_pointer_authority: Option<String>,
+    pub metadata_address: Option<String>,
+    pub slot: i64,
+    pub write_version: i64,
+}
+
+impl DbTokenExtensions {
+    /// The extensions of a Token-2022 mint or token account, none if it has none
+    pub fn parse(account: &DbAccountInfo) -> Option<Self> {
+        if account.owner.as_slice() != TOKENZ_PROGRAM_ID.as_ref() || account.data.len() <= TLV_OFFSET {
+            return None;
+        }
+        let account_type = match account.data[ACCOUNT_TYPE_OFFSET] {
+            MINT_ACCOUNT_TYPE => "mint",
+            TOKEN_ACCOUNT_TYPE => "account",
+            _ => return None,
+        };
+        let mut row = Self {
+            pubkey: bs58::encode(&account.pubkey).into_string(),
+            account_type,
+            slot: account.slot,
+            write_version: account.write_version,
+            ..Self::default()
+        };
+        let data = &account.data[TLV_OFFSET..];
+        let mut offset = 0;
+        while offset + TLV_HEADER_LENGTH <= data.len() {
+            let extension_type = u16_at(data, offset);
+            let length = u16_at(data, offset + 2) as usize;
+            let value = match data.get(offset + TLV_HEADER_LENGTH..offset + TLV_HEADER_LENGTH + length) {
+                Some(value) => value,
+                None => break,
+            };
+            // the space left after the last extension is zeroed
+            if extension_type == 0 {
+                break;
+            }
+            row.extensions.push(match EXTENSION_NAMES.get(extension_type as usize) {
+                Some(name) => name.to_string(),
+                None => format!("unknown_{}", extension_type),
+            });
+            row.parse_extension(extension_type, value);
+            offset += TLV_HEADER_LENGTH + length;
+        }
+        match row.extensions.is_empty() {
+            true => None,
+            false => Some(row),
+        }
+    }
+
+    /// Extensions shorter than their layout are left out of the columns
+    fn parse_extension(&mut self, extension_type: u16, value: &[u8]) {
+        match extension_type {
+            TRANSFER_FEE_CONFIG if value.len() >= 2 * PUBKEY_BYTES + 8 + 2 * DbTransferFee::LENGTH => {
+                self.transfer_fee_config_authority = optional_pubkey(&value[..PUBKEY_BYTES]);
+                self.withdraw_withheld_authority = optional_pubkey(&value[PUBKEY_BYTES..2 * PUBKEY_BYTES]);
+                self.withheld_amount = Some(u64_at(value, 2 * PUBKEY_BYTES));
+                let fees = &value[2 * PUBKEY_BYTES + 8..];
+                self.older_transfer_fee = Some(DbTransferFee::parse(&fees[..DbTransferFee::LENGTH]));
+                self.newer_transfer_fee = Some(DbTransferFee::parse(&fees[DbTransferFee::LENGTH..]));
+            }
+            TRANSFER_FEE_AMOUNT if value.len() >= 8 => {
+                self.withheld_amount = Some(u64_at(value, 0));
+            }
+            INTEREST_BEARING_CONFIG if value.len() >= PUBKEY_BYTES + 20 => {
+                self.interest_rate_authority = optional_pubkey(&value[..PUBKEY_BYTES]);
+                self.interest_initialization_timestamp = Some(i64_at(value, PUBKEY_BYTES));
+                self.interest_pre_update_average_rate = Some(i16_at(value, PUBKEY_BYTES + 8));
+                self.interest_last_update_timestamp = Some(i64_at(value, PUBKEY_BYTES + 10));
+                self.interest_current_rate = Some(i16_at(value, PUBKEY_BYTES + 18));
+            }
+            PERMANENT_DELEGATE if value.len() >= PUBKEY_BYTES => {
+                self.permanent_delegate = optional_pubkey(&value[..PUBKEY_BYTES]);
+            }
+            METADATA_POINTER if value.len() >= 2 * PUBKEY_BYTES => {
+                self.metadata_pointer_authority = optional_pubkey(&value[..PUBKEY_BYTES]);
+                self.metadata_address = optional_pubkey(&value[PUBKEY_BYTES..2 * PUBKEY_BYTES]);
+            }
+            _ => {}
+        }
+    }
+
+    /// The row bound to the upsert of `statement`, in column order
+    pub fn bind(self, statement: usize) -> BoundRow {
+        let (older, newer) = (self.older_transfer_fee.as_ref(), self.newer_transfer_fee.as_ref());
+        BoundRow::new(
+            statement,
+            vec![
+                Box::new(self.pubkey),
+                Box::new(self.account_type.to_string()),
+                Box::new(self.extensions),
+                Box::new(self.transfer_fee_config_authority),
+                Box::new(self.withdraw_withheld_authority),
+                Box::new(self.withheld_amount.map(|amount| amount.to_string())),
+                Box::new(older.map(|fee| fee.epoch)),
+                Box::new(older.map(|fee| fee.maximum_fee.to_string())),
+                Box::new(older.map(|fee| fee.basis_points)),
+                Box::new(newer.map(|fee| fee.epoch)),
+                Box::new(newer.map(|fee| fee.maximum_fee.to_string())),
+                Box::new(newer.map(|fee| fee.basis_points)),
+                Box::new(self.permanent_delegate),
+                Box::new(self.interest_rate_authority),
+                Box::new(self.interest_initialization_timestamp),
+                Box::new(self.interest_pre_update_average_rate),
+                Box::new(self.interest_last_update_timestamp),
+                Box::new(self.interest_current_rate),
+                Box::new(self.metadata_pointer_authority),
+                Box::new(self.metadata_address),
+                Box::new(self.slot),
+                Box::new(self.write_version),
+            ],
+        )
+    }
+}
+
+/// The placeholders of the upsert, in column order
+pub(crate) fn placeholders() -> String {
+    COLUMNS
+        .iter()
+        .enumerate()
+        .map(|(i, column)| match NUMERIC_COLUMNS.contains(column) {
+            true => format!("${}::TEXT::NUMERIC", i + 1),
+            false => format!("${}", i + 1),
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::AccountKey;
+    use solana_sdk::pubkey::Pubkey;
+
+    fn tlv_entry(extension_type: u16, value: &[u8]) -> Vec<u8> {
+        let mut entry = extension_type.to_le_bytes().to_vec();
+        entry.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        entry.extend_from_slice(value);
+        entry
+    }
+
+    fn token_2022_account(account_type: u8, extensions: &[Vec<u8>]) -> DbAccountInfo {
+        let mut data = vec![0; SPL_TOKEN_ACCOUNT_LENGTH];
+        data.push(account_type);
+        for extension in extensions {
+            data.extend_from_slice(extension);
+        }
+        // reallocated accounts can have zeroed space left after their extensions
+        data.extend_from_slice(&[0; 8]);
+        DbAccountInfo {
+            pubkey: AccountKey::from_slice(Pubkey::new_unique().as_ref()),
+            lamports: 1,
+            owner: AccountKey::from_slice(TOKENZ_PROGRAM_ID.as_ref()),
+            executable: false,
+            rent_epoch: 0,
+            data,
+            slot: 5,
+            write_version: 2,
+            txn_signature: None,
+        }
+    }
+
+    #[test]
+    fn test_mint_extensions() {
+        let (authority, delegate, metadata) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut transfer_fee_config = authority.to_bytes().to_vec();
+        transfer_fee_config.extend_from_slice(&[0; 32]);
+        transfer_fee_config.extend_from_slice(&7u64.to_le_bytes());
+        for (epoch, maximum_fee, basis_points) in [(1u64, 1_000u64, 50u16), (300, u64::MAX, 100)] {
+            transfer_fee_config.extend_from_slice(&epoch.to_le_bytes());
+            transfer_fee_config.extend_from_slice(&maximum_fee.to_le_bytes());
+            transfer_fee_config.extend_from_slice(&basis_points.to_le_bytes());
+        }
+        let mut interest_bearing_config = [0; 32].to_vec();
+        interest_bearing_config.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+        interest_bearing_config.extend_from_slice(&(-5i16).to_le_bytes());
+        interest_bearing_config.extend_from_slice(&1_700_000_100i64.to_le_bytes());
+        interest_bearing_config.extend_from_slice(&25i16.to_le_bytes());
+        let mut metadata_pointer = [0; 32].to_vec();
+        metadata_pointer.extend_from_slice(metadata.as_ref());
+        let account = token_2022_account(
+            MINT_ACCOUNT_TYPE,
+            &[
+                tlv_entry(TRANSFER_FEE_CONFIG, &transfer_fee_config),
+                tlv_entry(INTEREST_BEARING_CONFIG, &interest_bearing_config),
+                tlv_entry(PERMANENT_DELEGATE, delegate.as_ref()),
+                tlv_entry(METADATA_POINTER, &metadata_pointer),
+                tlv_entry(9, &[]),
+            ],
+        );
+
+        let row = DbTokenExtensions::parse(&account).unwrap();
+        assert_eq!(row.account_type, "mint");
+        assert_eq!(
+            row.extensions,
+            ["transfer_fee_config", "interest_bearing_config", "permanent_delegate", "metadata_pointer", "non_transferable"]
+        );
+        assert_eq!(row.transfer_fee_config_authority, Some(authority.to_string()));
+        assert_eq!(row.withdraw_withheld_authority, None);
+        assert_eq!(row.withheld_amount, Some(7));
+        assert_eq!(
+            row.newer_transfer_fee,
+            Some(DbTransferFee {
+                epoch: 300,
+                maximum_fee: u64::MAX,
+                basis_points: 100
+            })
+        );
+        assert_eq!(row.older_transfer_fee.as_ref().map(|fee| fee.basis_points), Some(50));
+        assert_eq!(row.interest_rate_authority, None);
+        assert_eq!(row.interest_pre_update_average_rate, Some(-5));
+        assert_eq!(row.interest_last_update_timestamp, Some(1_700_000_100));
+        assert_eq!(row.interest_current_rate, Some(25));
+        assert_eq!(row.permanent_delegate, Some(delegate.to_string()));
+        assert_eq!(row.metadata_pointer_authority, None);
+        assert_eq!(row.metadata_address, Some(metadata.to_string()));
+        assert_eq!(row.bind(0).params.len(), COLUMNS.len());
+    }
+
+    #[test]
+    fn test_account_extensions() {
+        let account = token_2022_account(TOKEN_ACCOUNT_TYPE, &[tlv_entry(TRANSFER_FEE_AMOUNT, &42u64.to_le_bytes()), tlv_entry(7, &[])]);
+        let row = DbTokenExtensions::parse(&account).unwrap();
+        assert_eq!(row.account_type, "account");
+        assert_eq!(row.extensions, ["transfer_fee_amount", "immutable_owner"]);
+        assert_eq!(row.withheld_amount, Some(42));
+        assert_eq!(row.newer_transfer_fee, None);
+
+        // without extensions, or truncated
+        assert_eq!(DbTokenExtensions::parse(&token_2022_account(TOKEN_ACCOUNT_TYPE, &[])), None);
+        let mut truncated = token_2022_account(TOKEN_ACCOUNT_TYPE, &[tlv_entry(TRANSFER_FEE_AMOUNT, &42u64.to_le_bytes())]);
+        truncated.data.truncate(TLV_OFFSET + 6);
+        assert_eq!(DbTokenExtensions::parse(&truncated), None);
+        assert!(UPDATED_COLUMNS.iter().all(|column| COLUMNS.contains(column)));
+        assert!(placeholders().starts_with("$1, $2, $3, $4, $5, $6::TEXT::NUMERIC, $7, $8::TEXT::NUMERIC"));
+    }
+}
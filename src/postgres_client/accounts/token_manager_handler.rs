@@ -7,6 +7,11 @@ use solana_sdk::pubkey::Pubkey;
 
 use super::account_handler::AccountHandler;
 use super::DbAccountInfo;
+use crate::metrics::registry;
+use crate::metrics::HANDLER_DECODE_ERRORS_TOTAL;
+use crate::postgres_client::bound_rows::BoundRow;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::ConflictStrategy;
 
 pub static TOKEN_MANAGER_PROGRAM_ID: Pubkey = pubkey!("mgr99QFMYByTqGPWmNqunV7vBLmWWXdSrHUfV8Jf3JM");
 
@@ -32,15 +37,134 @@ pub struct TokenManager {
     pub invalidators: Vec<Pubkey>,
 }
 
-pub struct TokenManagerAccountHandler {}
+/// A `token_manager` row as bound to the handler's upsert
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DbTokenManager {
+    pub id: String,
+    pub version: i16,
+    pub bump: i16,
+    pub count: i64,
+    pub num_invalidators: i16,
+    pub issuer: String,
+    pub mint: String,
+    pub amount: i64,
+    pub kind: i16,
+    pub state: i16,
+    pub state_changed_at: i64,
+    pub invalidation_type: i16,
+    pub recipient_token_account: String,
+    pub receipt_mint: Option<String>,
+    pub claim_approver: Option<String>,
+    pub transfer_authority: Option<String>,
+    pub invalidators: Vec<String>,
+    pub slot: i64,
+}
+
+impl DbTokenManager {
+    pub fn new(id: &Pubkey, token_manager: &TokenManager, slot: i64) -> Self {
+        Self {
+            id: id.to_string(),
+            version: token_manager.version as i16,
+            bump: token_manager.bump as i16,
+            count: token_manager.count as i64,
+            num_invalidators: token_manager.num_invalidators as i16,
+            issuer: token_manager.issuer.to_string(),
+            mint: token_manager.mint.to_string(),
+            amount: token_manager.amount as i64,
+            kind: token_manager.kind as i16,
+            state: token_manager.state as i16,
+            state_changed_at: token_manager.state_changed_at,
+            invalidation_type: token_manager.invalidation_type as i16,
+            recipient_token_account: token_manager.recipient_token_account.to_string(),
+            receipt_mint: token_manager.receipt_mint.map(|k| k.to_string()),
+            claim_approver: token_manager.claim_approver.map(|k| k.to_string()),
+            transfer_authority: token_manager.transfer_authority.map(|k| k.to_string()),
+            invalidators: token_manager.invalidators.iter().map(|k| k.to_string()).collect(),
+            slot,
+        }
+    }
+
+    /// The row bound to the upsert, in column order
+    pub fn bind(self) -> BoundRow {
+        BoundRow::new(
+            0,
+            vec![
+                Box::new(self.id),
+                Box::new(self.version),
+                Box::new(self.bump),
+                Box::new(self.count),
+                Box::new(self.num_invalidators),
+                Box::new(self.issuer),
+                Box::new(self.mint),
+                Box::new(self.amount),
+                Box::new(self.kind),
+                Box::new(self.state),
+                Box::new(self.state_changed_at),
+                Box::new(self.invalidation_type),
+                Box::new(self.recipient_token_account),
+                Box::new(self.receipt_mint),
+                Box::new(self.claim_approver),
+                Box::new(self.transfer_authority),
+                Box::new(self.invalidators),
+                Box::new(self.slot),
+            ],
+        )
+    }
+}
+
+/// The Anchor discriminator of an account type
+pub fn account_discriminator(name: &str) -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(format!("account:{}", name).as_bytes()).to_bytes()[..8]);
+    discriminator
+}
+
+/// Token managers, written with a prepared upsert binding the row of each account
+pub struct TokenManagerAccountHandler {
+    discriminator: [u8; 8],
+    conflict: ConflictStrategy,
+    prefix: TablePrefix,
+}
+
+impl Default for TokenManagerAccountHandler {
+    fn default() -> Self {
+        Self::new(ConflictStrategy::default(), TablePrefix::default())
+    }
+}
+
+impl TokenManagerAccountHandler {
+    pub fn new(conflict: ConflictStrategy, prefix: TablePrefix) -> Self {
+        Self {
+            discriminator: account_discriminator("TokenManager"),
+            conflict,
+            prefix,
+        }
+    }
+
+    /// Decode a token manager account into its row
+    pub fn row(&self, account: &DbAccountInfo) -> Option<DbTokenManager> {
+        if !self.account_match(account) {
+            return None;
+        };
+        match BorshDeserialize::deserialize(&mut account.data[8..].as_ref()) {
+            Ok(token_manager) => Some(DbTokenManager::new(bytemuck::from_bytes(&account.pubkey), &token_manager, account.slot)),
+            Err(e) => {
+                error!("[account_update] Failed to deserialize token manager pubkey=[{:?}] error=[{:?}]", account.pubkey, e);
+                registry().inc_counter(HANDLER_DECODE_ERRORS_TOTAL, &[("handler", "token_manager")], 1);
+                None
+            }
+        }
+    }
+}
 
 impl AccountHandler for TokenManagerAccountHandler {
     fn init(&self, config: &crate::config::GeyserPluginPostgresConfig) -> String {
         if !self.enabled(config) {
             return "".to_string();
         };
-        return "
-            CREATE TABLE IF NOT EXISTS token_manager (
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}token_manager (
                 id VARCHAR(44) NOT NULL,
                 version SMALLINT NOT NULL,
                 bump SMALLINT NOT NULL,
@@ -61,60 +185,88 @@ impl AccountHandler for TokenManagerAccountHandler {
                 slot BIGINT NOT NULL,
                 PRIMARY KEY(id)
             );
-        "
-        .to_string();
+        ",
+            prefix = self.prefix
+        );
     }
 
     fn account_match(&self, account: &DbAccountInfo) -> bool {
-        let discriminator_preimage = format!("account:{}", "TokenManager");
-        let mut discriminator = [0u8; 8];
-        discriminator.copy_from_slice(&hash(discriminator_preimage.as_bytes()).to_bytes()[..8]);
-        account.owner == TOKEN_MANAGER_PROGRAM_ID.as_ref() && discriminator == *account.data.get(0..8).unwrap_or(&[0, 0, 0, 0, 0, 0, 0, 0])
+        account.owner.as_slice() == TOKEN_MANAGER_PROGRAM_ID.as_ref() && account.data.get(0..8) == Some(&self.discriminator[..])
     }
 
-    fn account_update(&self, account: &DbAccountInfo) -> String {
-        if !self.account_match(account) {
-            return "".to_string();
-        };
+    /// Token managers are written with typed parameters, see `account_rows`
+    fn account_update(&self, _account: &DbAccountInfo) -> String {
+        "".to_string()
+    }
 
-        let token_manager: TokenManager = match BorshDeserialize::deserialize(&mut account.data[8..].as_ref()) {
-            Ok(c) => c,
-            Err(e) => {
-                error!("[account_update] Failed to deserialize token manager pubkey=[{:?}] error=[{:?}]", account.pubkey, e);
-                return "".to_string();
-            }
+    fn prepared_statements(&self) -> Vec<String> {
+        vec![format!(
+            "INSERT INTO {1}token_manager AS acc (id, version, bump, count, num_invalidators, issuer, mint, amount, kind, state, state_changed_at, invalidation_type, recipient_token_account, receipt_mint, claim_approver, transfer_authority, invalidators, slot) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18) \
+            {0};",
+            self.conflict.on_conflict("id", &["num_invalidators", "issuer", "kind", "state", "state_changed_at", "invalidation_type", "invalidators"]),
+            self.prefix
+        )]
+    }
+
+    fn account_rows(&self, account: &DbAccountInfo) -> Vec<BoundRow> {
+        self.row(account).map(DbTokenManager::bind).into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::AccountKey;
+
+    #[test]
+    fn test_token_manager_row() {
+        let token_manager = TokenManager {
+            version: 0,
+            bump: 1,
+            count: 2,
+            num_invalidators: 1,
+            issuer: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            amount: 1,
+            kind: 2,
+            state: 1,
+            state_changed_at: 100,
+            invalidation_type: 3,
+            recipient_token_account: Pubkey::new_unique(),
+            receipt_mint: None,
+            claim_approver: Some(Pubkey::new_unique()),
+            transfer_authority: None,
+            invalidators: vec![Pubkey::new_unique()],
         };
-        let token_manager_key: &Pubkey = bytemuck::from_bytes(&account.pubkey);
-        let slot = account.slot;
-        format!(
-            "
-            INSERT INTO token_manager AS acc (id, version, bump, count, num_invalidators, issuer, mint, amount, kind, state, state_changed_at, invalidation_type, recipient_token_account, receipt_mint, claim_approver, transfer_authority, invalidators, slot) \
-            VALUES ('{0}', {1}, {2}, {3}, {4}, '{5}', '{6}', {7}, {8}, {9}, {10}, {11}, '{12}', {13}, {14}, {15}, '{16}', {17}) \
-            ON CONFLICT (id) \
-            DO UPDATE SET num_invalidators=excluded.num_invalidators, issuer=excluded.issuer, kind=excluded.kind, state=excluded.state, state_changed_at=excluded.state_changed_at, invalidation_type=excluded.invalidation_type, invalidators=excluded.invalidators \
-            WHERE acc.slot < excluded.slot;
-            ",
-            &token_manager_key.to_string(),
-            &token_manager.version,
-            &token_manager.bump,
-            &token_manager.count,
-            &token_manager.num_invalidators,
-            &token_manager.issuer.to_string(),
-            &token_manager.mint.to_string(),
-            &token_manager.amount,
-            &token_manager.kind,
-            &token_manager.state,
-            &token_manager.state_changed_at,
-            &token_manager.invalidation_type,
-            &token_manager.recipient_token_account.to_string(),
-            token_manager.receipt_mint.map_or("NULL".to_string(), |rm| format!("'{}'", rm.to_string())),
-            token_manager.claim_approver.map_or("NULL".to_string(), |rm| format!("'{}'", rm.to_string())),
-            token_manager.transfer_authority.map_or("NULL".to_string(), |rm| format!("'{}'", rm.to_string())),
-            format!("{{{}}}", token_manager.invalidators.iter().map(|inv| {
-                inv.to_string()
-            }).collect::<Vec<String>>()
-            .join(",")),
-            &slot
-        )
+        let handler = TokenManagerAccountHandler::default();
+        let mut data = handler.discriminator.to_vec();
+        token_manager.serialize(&mut data).unwrap();
+        let id = Pubkey::new_unique();
+        let account = DbAccountInfo {
+            pubkey: AccountKey::from_slice(id.as_ref()),
+            lamports: 1,
+            owner: AccountKey::from_slice(TOKEN_MANAGER_PROGRAM_ID.as_ref()),
+            executable: false,
+            rent_epoch: 0,
+            data,
+            slot: 5,
+            write_version: 1,
+            txn_signature: None,
+        };
+        let row = handler.row(&account).unwrap();
+        assert_eq!(row, DbTokenManager::new(&id, &token_manager, 5));
+        assert_eq!(row.receipt_mint, None);
+        assert_eq!(row.claim_approver, token_manager.claim_approver.map(|k| k.to_string()));
+        assert_eq!(row.invalidators, vec![token_manager.invalidators[0].to_string()]);
+        assert_eq!(handler.account_update(&account), "");
+        let rows = handler.account_rows(&account);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].params().len(), 18);
+        assert!(handler.prepared_statements()[0].contains("$18"));
+
+        let mut other = account.clone();
+        other.owner = AccountKey::from_slice(Pubkey::new_unique().as_ref());
+        assert_eq!(handler.row(&other), None);
     }
 }
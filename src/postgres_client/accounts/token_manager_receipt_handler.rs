@@ -0,0 +1,171 @@
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use log::error;
+use solana_sdk::pubkey::Pubkey;
+
+use super::account_handler::AccountHandler;
+use super::token_manager_handler::account_discriminator;
+use super::token_manager_handler::TOKEN_MANAGER_PROGRAM_ID;
+use super::DbAccountInfo;
+use crate::metrics::registry;
+use crate::metrics::HANDLER_DECODE_ERRORS_TOTAL;
+use crate::postgres_client::sql_value::pubkey_literal;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::ConflictStrategy;
+
+/// Written when a token manager is claimed, or approved for a claim, by `target`
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Eq, Hash)]
+pub struct ClaimReceipt {
+    pub mint_count: u64,
+    pub token_manager: Pubkey,
+    pub target: Pubkey,
+}
+
+/// Written when the holder of a token manager is approved to transfer it to `target`
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Eq, Hash)]
+pub struct TransferReceipt {
+    pub mint_count: u64,
+    pub token_manager: Pubkey,
+    pub target: Pubkey,
+}
+
+/// Claim and transfer receipts of the token manager program, joined to `token_manager` on `token_manager`
+pub struct TokenManagerReceiptAccountHandler {
+    claim_receipt_discriminator: [u8; 8],
+    transfer_receipt_discriminator: [u8; 8],
+    conflict: ConflictStrategy,
+    prefix: TablePrefix,
+}
+
+impl Default for TokenManagerReceiptAccountHandler {
+    fn default() -> Self {
+        Self::new(ConflictStrategy::default(), TablePrefix::default())
+    }
+}
+
+impl TokenManagerReceiptAccountHandler {
+    pub fn new(conflict: ConflictStrategy, prefix: TablePrefix) -> Self {
+        Self {
+            claim_receipt_discriminator: account_discriminator("ClaimReceipt"),
+            transfer_receipt_discriminator: account_discriminator("TransferReceipt"),
+            conflict,
+            prefix,
+        }
+    }
+
+    fn receipt_update(&self, table: &str, account: &DbAccountInfo, mint_count: u64, token_manager: &Pubkey, target: &Pubkey) -> String {
+        format!(
+            "
+            INSERT INTO {0} AS acc (id, mint_count, token_manager, target, slot) \
+            VALUES ({1}, {2}, '{3}', '{4}', {5}) \
+            {6};
+            ",
+            self.prefix.table(table),
+            pubkey_literal(&account.pubkey),
+            mint_count as i64,
+            token_manager,
+            target,
+            &account.slot,
+            self.conflict.on_conflict("id", &["mint_count", "token_manager", "target"]),
+        )
+    }
+}
+
+impl AccountHandler for TokenManagerReceiptAccountHandler {
+    fn init(&self, config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        if !self.enabled(config) {
+            return "".to_string();
+        };
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}claim_receipt (
+                id VARCHAR(44) NOT NULL,
+                mint_count BIGINT NOT NULL,
+                token_manager VARCHAR(44) NOT NULL,
+                target VARCHAR(44) NOT NULL,
+                slot BIGINT NOT NULL,
+                PRIMARY KEY(id)
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}claim_receipt_token_manager ON {prefix}claim_receipt (token_manager);
+            CREATE TABLE IF NOT EXISTS {prefix}transfer_receipt (
+                id VARCHAR(44) NOT NULL,
+                mint_count BIGINT NOT NULL,
+                token_manager VARCHAR(44) NOT NULL,
+                target VARCHAR(44) NOT NULL,
+                slot BIGINT NOT NULL,
+                PRIMARY KEY(id)
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}transfer_receipt_token_manager ON {prefix}transfer_receipt (token_manager);
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn account_match(&self, account: &DbAccountInfo) -> bool {
+        account.owner.as_slice() == TOKEN_MANAGER_PROGRAM_ID.as_ref() && matches!(account.data.get(0..8), Some(d) if d == self.claim_receipt_discriminator || d == self.transfer_receipt_discriminator)
+    }
+
+    fn account_update(&self, account: &DbAccountInfo) -> String {
+        if !self.account_match(account) {
+            return "".to_string();
+        };
+        let data = &mut account.data[8..].as_ref();
+        if account.data[0..8] == self.claim_receipt_discriminator {
+            match ClaimReceipt::deserialize(data) {
+                Ok(receipt) => self.receipt_update("claim_receipt", account, receipt.mint_count, &receipt.token_manager, &receipt.target),
+                Err(e) => {
+                    error!("[account_update] Failed to deserialize claim receipt pubkey=[{:?}] error=[{:?}]", account.pubkey, e);
+                    registry().inc_counter(HANDLER_DECODE_ERRORS_TOTAL, &[("handler", "token_manager_receipt")], 1);
+                    "".to_string()
+                }
+            }
+        } else {
+            match TransferReceipt::deserialize(data) {
+                Ok(receipt) => self.receipt_update("transfer_receipt", account, receipt.mint_count, &receipt.token_manager, &receipt.target),
+                Err(e) => {
+                    error!("[account_update] Failed to deserialize transfer receipt pubkey=[{:?}] error=[{:?}]", account.pubkey, e);
+                    registry().inc_counter(HANDLER_DECODE_ERRORS_TOTAL, &[("handler", "token_manager_receipt")], 1);
+                    "".to_string()
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::AccountKey;
+
+    #[test]
+    fn test_receipt_account_update() {
+        let handler = TokenManagerReceiptAccountHandler::default();
+        let receipt = TransferReceipt {
+            mint_count: 3,
+            token_manager: Pubkey::new_unique(),
+            target: Pubkey::new_unique(),
+        };
+        let mut data = handler.transfer_receipt_discriminator.to_vec();
+        receipt.serialize(&mut data).unwrap();
+        let account = DbAccountInfo {
+            pubkey: AccountKey::from_slice(Pubkey::new_unique().as_ref()),
+            lamports: 1,
+            owner: AccountKey::from_slice(TOKEN_MANAGER_PROGRAM_ID.as_ref()),
+            executable: false,
+            rent_epoch: 0,
+            data,
+            slot: 5,
+            write_version: 1,
+            txn_signature: None,
+        };
+        let query = handler.account_update(&account);
+        assert!(query.contains("INSERT INTO transfer_receipt"));
+        assert!(query.contains(&format!("3, '{}', '{}', 5", receipt.token_manager, receipt.target)));
+
+        let mut token_manager = account.clone();
+        token_manager.data[0..8].copy_from_slice(&account_discriminator("TokenManager"));
+        assert!(!handler.account_match(&token_manager));
+    }
+}
@@ -0,0 +1,121 @@
+use chrono::Utc;
+use solana_sdk::pubkey::Pubkey;
+
+use super::account_handler::AccountHandler;
+use super::token_account_handler::SPL_TOKEN_ACCOUNT_LENGTH;
+use super::token_account_handler::TOKENZ_PROGRAM_ID;
+use super::token_account_handler::TOKEN_PROGRAM_ID;
+use super::DbAccountInfo;
+use crate::postgres_client::table_prefix::TablePrefix;
+
+/*
+    /// The SPL mint definition -- we care about the supply and decimals at offset 36 and 44
+    spl_token::state::Mint {
+        mint_authority: COption<Pubkey>,
+        supply: u64,
+        decimals: u8,
+        is_initialized: bool,
+        freeze_authority: COption<Pubkey>,
+    }
+*/
+pub(crate) const SPL_MINT_SUPPLY_OFFSET: usize = 36;
+pub(crate) const SPL_MINT_DECIMALS_OFFSET: usize = 44;
+pub(crate) const SPL_MINT_LENGTH: usize = 82;
+const SPL_MINT_DISCRIMINATOR: u8 = 1;
+
+/// Whether the account is a mint of either token program, Token-2022 mints with extensions being padded to the length of a token account
+pub(crate) fn is_mint(account: &DbAccountInfo) -> bool {
+    account.owner.as_slice() == TOKEN_PROGRAM_ID.as_ref() && account.data.len() == SPL_MINT_LENGTH
+        || account.owner.as_slice() == TOKENZ_PROGRAM_ID.as_ref() && (account.data.len() == SPL_MINT_LENGTH || SPL_MINT_DISCRIMINATOR == *account.data.get(SPL_TOKEN_ACCOUNT_LENGTH).unwrap_or(&0))
+}
+
+#[derive(Clone, Default)]
+pub struct TokenMintAccountHandler {
+    pub prefix: TablePrefix,
+}
+
+impl AccountHandler for TokenMintAccountHandler {
+    fn init(&self, config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        if !self.enabled(config) {
+            return "".to_string();
+        };
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}mint_supply_history (
+                mint VARCHAR(44) NOT NULL,
+                supply NUMERIC(20, 0) NOT NULL,
+                decimals SMALLINT NOT NULL,
+                slot BIGINT NOT NULL,
+                updated_on TIMESTAMP NOT NULL,
+                PRIMARY KEY (mint, slot)
+            );
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn account_match(&self, account: &DbAccountInfo) -> bool {
+        is_mint(account)
+    }
+
+    /// Appends a row when the supply differs from the one of the latest row up to the slot of the update
+    fn account_update(&self, account: &DbAccountInfo) -> String {
+        if !self.account_match(account) {
+            return "".to_string();
+        };
+        let supply = u64::from_le_bytes(account.data[SPL_MINT_SUPPLY_OFFSET..SPL_MINT_SUPPLY_OFFSET + 8].try_into().unwrap());
+        let decimals = account.data[SPL_MINT_DECIMALS_OFFSET];
+        let pubkey_bytes: [u8; 32] = account.pubkey[..].try_into().unwrap();
+        let mint = Pubkey::from(pubkey_bytes).to_string();
+        format!(
+            "
+                INSERT INTO {5}mint_supply_history (mint, supply, decimals, slot, updated_on) \
+                SELECT '{0}', {1}, {2}, {3}, '{4}' \
+                WHERE {1} IS DISTINCT FROM (SELECT supply FROM {5}mint_supply_history WHERE mint = '{0}' AND slot <= {3} ORDER BY slot DESC LIMIT 1) \
+                ON CONFLICT (mint, slot) DO UPDATE SET supply=excluded.supply, decimals=excluded.decimals, updated_on=excluded.updated_on;
+            ",
+            mint,
+            supply,
+            decimals,
+            account.slot,
+            &Utc::now().naive_utc(),
+            self.prefix,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::AccountKey;
+
+    #[test]
+    fn test_mint_account_update() {
+        let mint = Pubkey::new_unique();
+        let mut data = vec![0; SPL_MINT_LENGTH];
+        data[SPL_MINT_SUPPLY_OFFSET..SPL_MINT_SUPPLY_OFFSET + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        data[SPL_MINT_DECIMALS_OFFSET] = 6;
+        let account = DbAccountInfo {
+            pubkey: AccountKey::from_slice(mint.as_ref()),
+            lamports: 1,
+            owner: AccountKey::from_slice(TOKEN_PROGRAM_ID.as_ref()),
+            executable: false,
+            rent_epoch: 0,
+            data,
+            slot: 5,
+            write_version: 1,
+            txn_signature: None,
+        };
+        let handler = TokenMintAccountHandler::default();
+        let query = handler.account_update(&account);
+        assert!(query.contains(&format!("SELECT '{}', 18446744073709551615, 6, 5,", mint)), "{}", query);
+        assert!(query.contains(&format!("WHERE mint = '{}' AND slot <= 5", mint)));
+
+        // token accounts share the owner
+        let token_account = DbAccountInfo {
+            data: vec![0; SPL_TOKEN_ACCOUNT_LENGTH],
+            ..account
+        };
+        assert!(!handler.account_match(&token_account));
+    }
+}
@@ -0,0 +1,108 @@
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use log::error;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+use super::account_handler::AccountHandler;
+use super::token_manager_handler::account_discriminator;
+use super::DbAccountInfo;
+use crate::metrics::registry;
+use crate::metrics::HANDLER_DECODE_ERRORS_TOTAL;
+use crate::postgres_client::sql_value::pubkey_literal;
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::ConflictStrategy;
+
+pub static TRANSFER_AUTHORITY_PROGRAM_ID: Pubkey = pubkey!("trttGqe8YQZbgDT5dWvVqmfXC4LNvpyfCxsVyNMbB58");
+
+/// Approves transfers of the token managers whose `transfer_authority` it is
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, PartialEq, Debug, Clone, Eq, Hash)]
+pub struct TransferAuthority {
+    pub bump: u8,
+    pub name: String,
+    pub authority: Pubkey,
+    pub payment_manager: Pubkey,
+    pub allowed_marketplaces: Option<Vec<Pubkey>>,
+}
+
+/// Transfer authorities, joined to `token_manager` on `token_manager.transfer_authority`
+pub struct TransferAuthorityAccountHandler {
+    discriminator: [u8; 8],
+    conflict: ConflictStrategy,
+    prefix: TablePrefix,
+}
+
+impl Default for TransferAuthorityAccountHandler {
+    fn default() -> Self {
+        Self::new(ConflictStrategy::default(), TablePrefix::default())
+    }
+}
+
+impl TransferAuthorityAccountHandler {
+    pub fn new(conflict: ConflictStrategy, prefix: TablePrefix) -> Self {
+        Self {
+            discriminator: account_discriminator("TransferAuthority"),
+            conflict,
+            prefix,
+        }
+    }
+}
+
+impl AccountHandler for TransferAuthorityAccountHandler {
+    fn init(&self, config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        if !self.enabled(config) {
+            return "".to_string();
+        };
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}transfer_authority (
+                id VARCHAR(44) NOT NULL,
+                bump SMALLINT NOT NULL,
+                name TEXT NOT NULL,
+                authority VARCHAR(44) NOT NULL,
+                payment_manager VARCHAR(44) NOT NULL,
+                allowed_marketplaces VARCHAR(44)[],
+                slot BIGINT NOT NULL,
+                PRIMARY KEY(id)
+            );
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn account_match(&self, account: &DbAccountInfo) -> bool {
+        account.owner.as_slice() == TRANSFER_AUTHORITY_PROGRAM_ID.as_ref() && account.data.get(0..8) == Some(&self.discriminator[..])
+    }
+
+    fn account_update(&self, account: &DbAccountInfo) -> String {
+        if !self.account_match(account) {
+            return "".to_string();
+        };
+        let transfer_authority = match TransferAuthority::deserialize(&mut account.data[8..].as_ref()) {
+            Ok(transfer_authority) => transfer_authority,
+            Err(e) => {
+                error!("[account_update] Failed to deserialize transfer authority pubkey=[{:?}] error=[{:?}]", account.pubkey, e);
+                registry().inc_counter(HANDLER_DECODE_ERRORS_TOTAL, &[("handler", "transfer_authority")], 1);
+                return "".to_string();
+            }
+        };
+        format!(
+            "
+            INSERT INTO {8}transfer_authority AS acc (id, bump, name, authority, payment_manager, allowed_marketplaces, slot) \
+            VALUES ({0}, {1}, {2}, '{3}', '{4}', {5}, {6}) \
+            {7};
+            ",
+            pubkey_literal(&account.pubkey),
+            transfer_authority.bump,
+            transfer_authority.name.sql_literal(),
+            transfer_authority.authority,
+            transfer_authority.payment_manager,
+            transfer_authority.allowed_marketplaces.sql_literal(),
+            &account.slot,
+            self.conflict.on_conflict("id", &["name", "authority", "payment_manager", "allowed_marketplaces"]),
+            self.prefix,
+        )
+    }
+}
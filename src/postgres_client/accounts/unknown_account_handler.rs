@@ -1,17 +1,43 @@
+use super::account_data_compression::AccountDataCompression;
 use super::account_handler::AccountHandler;
 use super::DbAccountInfo;
+use crate::postgres_client::bound_rows::BoundValues;
+use crate::postgres_client::bulk_load::StagingTable;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::ConflictStrategy;
 use chrono::Utc;
 
-#[derive(Clone, Copy)]
-pub struct UnknownAccountHandler {}
+/// The columns of a row, the pubkey and slot first
+const COLUMNS: [&str; 11] = [
+    "pubkey",
+    "slot",
+    "owner",
+    "lamports",
+    "executable",
+    "rent_epoch",
+    "data",
+    "write_version",
+    "updated_on",
+    "txn_signature",
+    "data_codec",
+];
+
+#[derive(Clone, Default)]
+pub struct UnknownAccountHandler {
+    pub conflict: ConflictStrategy,
+    /// The codec `data` is compressed with
+    pub compression: AccountDataCompression,
+    pub prefix: TablePrefix,
+}
 
 impl AccountHandler for UnknownAccountHandler {
     fn init(&self, config: &crate::config::GeyserPluginPostgresConfig) -> String {
         if !self.enabled(config) {
             return "".to_string();
         };
-        return "
-            CREATE TABLE IF NOT EXISTS account (
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}account (
                 pubkey BYTEA PRIMARY KEY,
                 owner BYTEA,
                 lamports BIGINT NOT NULL,
@@ -21,12 +47,18 @@ impl AccountHandler for UnknownAccountHandler {
                 data BYTEA,
                 write_version BIGINT NOT NULL,
                 updated_on TIMESTAMP NOT NULL,
-                txn_signature BYTEA
+                txn_signature BYTEA,
+                data_codec VARCHAR(8)
             );
-            CREATE INDEX IF NOT EXISTS account_owner ON account (owner);
-            CREATE INDEX IF NOT EXISTS account_slot ON account (slot);
-        "
-        .to_string();
+            CREATE INDEX IF NOT EXISTS {prefix}account_owner ON {prefix}account (owner);
+            CREATE INDEX IF NOT EXISTS {prefix}account_slot ON {prefix}account (slot);
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn schema_migrations(&self, _config: &crate::config::GeyserPluginPostgresConfig) -> Vec<String> {
+        vec![format!("ALTER TABLE {}account ADD COLUMN IF NOT EXISTS data_codec VARCHAR(8);", self.prefix)]
     }
 
     fn account_match(&self, _account: &DbAccountInfo) -> bool {
@@ -39,25 +71,69 @@ impl AccountHandler for UnknownAccountHandler {
         };
         format!(
             "
-                INSERT INTO account AS acct (pubkey, slot, owner, lamports, executable, rent_epoch, data, write_version, updated_on, txn_signature) \
-                VALUES ('\\x{0}', {1}, '\\x{2}', {3}, {4}, {5}, '\\x{6}', {7}, '{8}', {9}) \
-                ON CONFLICT (pubkey) DO UPDATE SET
-                    slot=excluded.slot, owner=excluded.owner, lamports=excluded.lamports, \
-                    executable=excluded.executable, rent_epoch=excluded.rent_epoch, \
-                    data=excluded.data, write_version=excluded.write_version, updated_on=excluded.updated_on, \
-                    txn_signature=excluded.txn_signature \
-                WHERE acct.slot < excluded.slot OR (acct.slot = excluded.slot AND acct.write_version < excluded.write_version);
+                INSERT INTO {3}account AS acc ({0}) \
+                VALUES {1} \
+                {2};
             ",
+            COLUMNS.join(", "),
+            self.row(account),
+            self.on_conflict(),
+            self.prefix,
+        )
+    }
+
+    fn staging_table(&self) -> Option<StagingTable> {
+        Some(StagingTable {
+            table: self.prefix.table("account"),
+            columns: &COLUMNS,
+            key: "pubkey",
+            on_conflict: self.on_conflict(),
+        })
+    }
+
+    fn staging_row(&self, account: &DbAccountInfo) -> Option<String> {
+        Some(self.row(account))
+    }
+
+    fn staging_values(&self, account: &DbAccountInfo) -> Option<BoundValues> {
+        let (data, codec) = self.compression.compress(&account.data);
+        Some(vec![
+            Box::new(account.pubkey.to_vec()),
+            Box::new(account.slot),
+            Box::new(account.owner.to_vec()),
+            Box::new(account.lamports),
+            Box::new(account.executable),
+            Box::new(account.rent_epoch),
+            Box::new(data.into_owned()),
+            Box::new(account.write_version),
+            Box::new(Utc::now().naive_utc()),
+            Box::new(account.txn_signature.clone()),
+            Box::new(codec),
+        ])
+    }
+}
+
+impl UnknownAccountHandler {
+    fn row(&self, account: &DbAccountInfo) -> String {
+        let (data, codec) = self.compression.compress(&account.data);
+        format!(
+            "('\\x{0}', {1}, '\\x{2}', {3}, {4}, {5}, '\\x{6}', {7}, '{8}', {9}, {10})",
             hex::encode(&account.pubkey),
             &account.slot,
             hex::encode(&account.owner),
             &account.lamports,
             &account.executable,
             &account.rent_epoch,
-            hex::encode(&account.data),
+            hex::encode(data),
             &account.write_version,
             &Utc::now().naive_utc(),
             account.txn_signature.as_deref().map_or("NULL".to_string(), |tx| format!("'\\x{}'", hex::encode(tx))),
+            codec.map_or("NULL".to_string(), |codec| format!("'{}'", codec)),
         )
     }
+
+    /// The codec always follows the data it was written with, a NULL codec marks uncompressed data
+    fn on_conflict(&self) -> String {
+        self.conflict.on_conflict_overwriting("pubkey", &COLUMNS[2..10], &COLUMNS[10..])
+    }
 }
@@ -0,0 +1,283 @@
+#[cfg(feature = "wasm")]
+pub use self::runtime::WasmAccountHandler;
+use crate::postgres_client::is_identifier;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+/// * The `wasm_handlers` section registers account handlers compiled to WebAssembly, requires the `wasm` feature.
+/// "wasm_handlers" : \[{
+///     "handler_id" : "my_pool",
+///     "path" : "/solana/my_pool.wasm",
+///     "table" : "my_pool"
+/// }\]
+/// The module exports `memory`, `alloc(len: i32) -> i32` and `decode(ptr: i32, len: i32) -> i64`.
+/// `decode` receives the account pubkey, owner and data concatenated and returns the pointer and length
+/// of a JSON object packed as `ptr << 32 | len`, a length of 0 skips the account.
+/// Modules can't import any host functions and run with bounded fuel and memory.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WasmHandlerConfig {
+    /// The id used in the `accounts_selector`
+    pub handler_id: String,
+    /// Path to the compiled module
+    pub path: String,
+    /// The table decoded accounts are written to
+    pub table: String,
+    /// Fuel available to each `decode` call
+    pub fuel: u64,
+    /// Maximum size of the module's linear memory in bytes
+    pub max_memory_bytes: usize,
+}
+
+impl Default for WasmHandlerConfig {
+    fn default() -> Self {
+        Self {
+            handler_id: String::default(),
+            path: String::default(),
+            table: String::default(),
+            fuel: 10_000_000,
+            max_memory_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+impl WasmHandlerConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if cfg!(not(feature = "wasm")) {
+            return Err(format!("wasm_handlers \"{}\" requires the plugin to be built with the \"wasm\" feature", self.handler_id));
+        }
+        if self.handler_id.is_empty() {
+            return Err("wasm_handlers entries must set \"handler_id\"".to_string());
+        }
+        if !is_identifier(&self.table) {
+            return Err(format!("wasm_handlers.{} table \"{}\" is not a valid identifier", self.handler_id, self.table));
+        }
+        if !std::path::Path::new(&self.path).exists() {
+            return Err(format!("wasm_handlers.{} path {} does not exist", self.handler_id, self.path));
+        }
+        if self.fuel == 0 {
+            return Err(format!("wasm_handlers.{} fuel must be greater than 0", self.handler_id));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "wasm")]
+mod runtime {
+    use super::super::account_handler::AccountHandler;
+    use super::super::decoded_account::DecodedAccountTable;
+    use super::super::DbAccountInfo;
+    use super::WasmHandlerConfig;
+    use crate::config::GeyserPluginPostgresConfig;
+    use crate::metrics::registry;
+    use crate::metrics::HANDLER_DECODE_ERRORS_TOTAL;
+    use crate::postgres_client::sql_value::SqlValue;
+    use crate::postgres_client::table_prefix::TablePrefix;
+    use crate::postgres_client::ConflictStrategy;
+    use log::error;
+    use serde_json::Value;
+    use solana_sdk::pubkey::Pubkey;
+    use std::sync::Mutex;
+    use wasmtime::Engine;
+    use wasmtime::Instance;
+    use wasmtime::Memory;
+    use wasmtime::Module;
+    use wasmtime::Store;
+    use wasmtime::StoreLimits;
+    use wasmtime::StoreLimitsBuilder;
+    use wasmtime::TypedFunc;
+
+    struct WasmInstance {
+        store: Store<StoreLimits>,
+        memory: Memory,
+        alloc: TypedFunc<i32, i32>,
+        decode: TypedFunc<(i32, i32), i64>,
+    }
+
+    /// Decodes accounts with a sandboxed WebAssembly module into a JSONB table
+    pub struct WasmAccountHandler {
+        config: WasmHandlerConfig,
+        store_decoded_accounts: bool,
+        conflict: ConflictStrategy,
+        prefix: TablePrefix,
+        engine: Engine,
+        module: Module,
+        /// Recreated after a trap so a failing call can't leave corrupted state behind
+        instance: Mutex<Option<WasmInstance>>,
+    }
+
+    impl WasmAccountHandler {
+        pub fn new(config: &WasmHandlerConfig, store_decoded_accounts: bool, conflict: ConflictStrategy, prefix: TablePrefix) -> Result<Self, String> {
+            let module_bytes = std::fs::read(&config.path).map_err(|e| format!("Failed to read {}: {}", config.path, e))?;
+            Self::from_bytes(config, store_decoded_accounts, conflict, prefix, &module_bytes)
+        }
+
+        pub fn from_bytes(config: &WasmHandlerConfig, store_decoded_accounts: bool, conflict: ConflictStrategy, prefix: TablePrefix, module_bytes: &[u8]) -> Result<Self, String> {
+            let mut engine_config = wasmtime::Config::new();
+            engine_config.consume_fuel(true);
+            let engine = Engine::new(&engine_config).map_err(|e| e.to_string())?;
+            let module = Module::new(&engine, module_bytes).map_err(|e| format!("Invalid module {}: {}", config.path, e))?;
+            let handler = Self {
+                config: config.clone(),
+                store_decoded_accounts,
+                conflict,
+                prefix,
+                engine,
+                module,
+                instance: Mutex::new(None),
+            };
+            // fail on startup rather than on the first account if the module doesn't follow the ABI
+            let instance = handler.instantiate()?;
+            *handler.instance.lock().unwrap() = Some(instance);
+            Ok(handler)
+        }
+
+        fn instantiate(&self) -> Result<WasmInstance, String> {
+            let mut store = Store::new(&self.engine, StoreLimitsBuilder::new().memory_size(self.config.max_memory_bytes).build());
+            store.limiter(|limits| limits);
+            let instance = Instance::new(&mut store, &self.module, &[]).map_err(|e| e.to_string())?;
+            let memory = instance.get_memory(&mut store, "memory").ok_or_else(|| "module does not export \"memory\"".to_string())?;
+            let alloc = instance.get_typed_func::<i32, i32, _>(&mut store, "alloc").map_err(|e| e.to_string())?;
+            let decode = instance.get_typed_func::<(i32, i32), i64, _>(&mut store, "decode").map_err(|e| e.to_string())?;
+            Ok(WasmInstance { store, memory, alloc, decode })
+        }
+
+        /// Run `decode` on the account, returning the JSON object it produced if any
+        pub fn decode(&self, account: &DbAccountInfo) -> Result<Option<Value>, String> {
+            let mut guard = self.instance.lock().unwrap();
+            if guard.is_none() {
+                *guard = Some(self.instantiate()?);
+            }
+            let instance = guard.as_mut().unwrap();
+            let result = self.call(instance, account);
+            if result.is_err() {
+                *guard = None;
+            }
+            result
+        }
+
+        fn call(&self, instance: &mut WasmInstance, account: &DbAccountInfo) -> Result<Option<Value>, String> {
+            let remaining = instance.store.consume_fuel(0).map_err(|e| e.to_string())?;
+            instance.store.add_fuel(self.config.fuel.saturating_sub(remaining)).map_err(|e| e.to_string())?;
+
+            let input = [account.pubkey.as_slice(), account.owner.as_slice(), account.data.as_slice()].concat();
+            let ptr = instance.alloc.call(&mut instance.store, input.len() as i32).map_err(|e| e.to_string())?;
+            instance.memory.write(&mut instance.store, ptr as u32 as usize, &input).map_err(|e| e.to_string())?;
+            let packed = instance.decode.call(&mut instance.store, (ptr, input.len() as i32)).map_err(|e| e.to_string())? as u64;
+            let (output_ptr, output_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+            if output_len == 0 {
+                return Ok(None);
+            }
+            let mut output = vec![0; output_len];
+            instance.memory.read(&instance.store, output_ptr, &mut output).map_err(|e| e.to_string())?;
+            match serde_json::from_slice(&output).map_err(|e| e.to_string())? {
+                Value::Object(object) => Ok(Some(Value::Object(object))),
+                _ => Err("decode did not return a JSON object".to_string()),
+            }
+        }
+    }
+
+    impl AccountHandler for WasmAccountHandler {
+        fn init(&self, _config: &GeyserPluginPostgresConfig) -> String {
+            format!(
+                "
+                CREATE TABLE IF NOT EXISTS {0} (
+                    id VARCHAR(44) NOT NULL,
+                    data JSONB NOT NULL,
+                    slot BIGINT NOT NULL,
+                    PRIMARY KEY(id)
+                );
+            ",
+                self.prefix.table(&self.config.table)
+            )
+        }
+
+        fn account_match(&self, _account: &DbAccountInfo) -> bool {
+            true
+        }
+
+        fn account_update(&self, account: &DbAccountInfo) -> String {
+            let data = match self.decode(account) {
+                Ok(Some(data)) => data,
+                Ok(None) => return "".to_string(),
+                Err(e) => {
+                    error!(
+                        "[account_update] Wasm handler failed handler=[{}] pubkey=[{:?}] error=[{}]",
+                        self.config.handler_id,
+                        bs58::encode(&account.pubkey).into_string(),
+                        e
+                    );
+                    registry().inc_counter(HANDLER_DECODE_ERRORS_TOTAL, &[("handler", &self.config.handler_id)], 1);
+                    return "".to_string();
+                }
+            };
+            let account_key: &Pubkey = bytemuck::from_bytes(&account.pubkey);
+            let mut query = format!(
+                "
+                INSERT INTO {0} AS acc (id, data, slot) \
+                VALUES ('{1}', {2}, {3}) \
+                {4};
+                ",
+                self.prefix.table(&self.config.table),
+                &account_key.to_string(),
+                data.to_string().sql_literal(),
+                &account.slot,
+                self.conflict.on_conflict("id", &["data"]),
+            );
+            if self.store_decoded_accounts {
+                query.push_str(&DecodedAccountTable::update(&self.prefix, account, &self.config.handler_id, &data));
+            }
+            query
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::postgres_client::AccountKey;
+
+        // echoes `{"len":N}` with N the input length, traps on inputs longer than 255 bytes
+        const MODULE: &str = r#"
+            (module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "{\"len\":000}")
+                (func (export "alloc") (param i32) (result i32) (i32.const 64))
+                (func (export "decode") (param $ptr i32) (param $len i32) (result i64)
+                    (if (i32.gt_u (local.get $len) (i32.const 255)) (then unreachable))
+                    (i32.store8 (i32.const 7) (i32.add (i32.const 48) (i32.div_u (local.get $len) (i32.const 100))))
+                    (i32.store8 (i32.const 8) (i32.add (i32.const 48) (i32.rem_u (i32.div_u (local.get $len) (i32.const 10)) (i32.const 10))))
+                    (i32.store8 (i32.const 9) (i32.add (i32.const 48) (i32.rem_u (local.get $len) (i32.const 10))))
+                    (i64.const 11)))
+        "#;
+
+        fn account(data_len: usize) -> DbAccountInfo {
+            DbAccountInfo {
+                pubkey: AccountKey::from_slice(Pubkey::new_unique().as_ref()),
+                lamports: 1,
+                owner: AccountKey::from_slice(Pubkey::new_unique().as_ref()),
+                executable: false,
+                rent_epoch: 0,
+                data: vec![0; data_len],
+                slot: 5,
+                write_version: 1,
+                txn_signature: None,
+            }
+        }
+
+        #[test]
+        fn test_wasm_account_update() {
+            let config = WasmHandlerConfig {
+                handler_id: "echo".to_string(),
+                table: "echo".to_string(),
+                ..WasmHandlerConfig::default()
+            };
+            let handler = WasmAccountHandler::from_bytes(&config, false, ConflictStrategy::default(), TablePrefix::default(), MODULE.as_bytes()).unwrap();
+            assert_eq!(handler.decode(&account(36)).unwrap(), Some(serde_json::json!({ "len": 100 })));
+            assert!(handler.account_update(&account(36)).contains("'{\"len\":100}'"));
+
+            // a trap is reported and the next call runs on a fresh instance
+            assert!(handler.decode(&account(200)).is_err());
+            assert_eq!(handler.decode(&account(90)).unwrap(), Some(serde_json::json!({ "len": 154 })));
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! The `async_mode` connection of a worker.
+//!
+//! The blocking client waits for the answer of every statement before sending the next one, so the
+//! rows bound to the prepared statements of the handlers cost a round trip each. In `async_mode` each
+//! worker also opens a tokio-postgres connection, driven by a runtime of its own, and writes the live
+//! flushes on it, sending the rows of a flush without waiting, up to `PIPELINE_DEPTH` of them in flight
+//! on the connection.
+use crate::config::GeyserPluginPostgresConfig;
+use crate::geyser_plugin_postgres::GeyserPluginPostgresError;
+use crate::metrics::registry;
+use crate::metrics::BYTES_WRITTEN_TOTAL;
+use crate::metrics::DB_CONNECTS_TOTAL;
+use crate::metrics::HANDLER_ERRORS_TOTAL;
+use crate::postgres_client::bound_rows::BoundRow;
+use crate::postgres_client::bound_rows::RowBatch;
+use crate::postgres_client::db_errors::record_db_error;
+use crate::postgres_client::db_errors::retry_transaction;
+use crate::postgres_client::db_errors::SerializationRetryConfig;
+use crate::postgres_client::fault_injection;
+use crate::postgres_client::handler_batch::isolate;
+use crate::postgres_client::handler_batch::HandlerBatch;
+use crate::postgres_client::handler_batch::HandlerFailure;
+use crate::postgres_client::AccountHandler;
+use crate::postgres_client::AccountHandlerId;
+use crate::postgres_client::SimplePostgresClient;
+use futures::future::try_join_all;
+use log::*;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use std::collections::HashMap;
+use tokio::runtime::Builder;
+use tokio::runtime::Runtime;
+use tokio_postgres::Client;
+use tokio_postgres::NoTls;
+use tokio_postgres::Statement;
+use tokio_postgres::Transaction;
+
+/// The rows sent on the connection before waiting for the answers of the first ones
+const PIPELINE_DEPTH: usize = 256;
+
+/// A pipelined connection and the statements of the handlers prepared on it
+pub struct AsyncPipeline {
+    runtime: Runtime,
+    client: Client,
+    statements: HashMap<String, Vec<(String, Statement)>>,
+}
+
+impl AsyncPipeline {
+    pub fn connect(config: &GeyserPluginPostgresConfig, account_handlers: &HashMap<AccountHandlerId, Box<dyn AccountHandler>>) -> Result<Self, GeyserPluginError> {
+        let connection_err = |err: String| {
+            GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::ConnectionError {
+                msg: format!("[AsyncPipeline::connect] connection_str={} error={}", config.connection_str, err),
+            }))
+        };
+        // the connection is only read from and written to while the worker blocks on a write
+        let runtime = Builder::new_current_thread().enable_all().build().map_err(|err| connection_err(err.to_string()))?;
+        let tls_connector = SimplePostgresClient::tls_connector(config)?;
+        let (client, statements) = runtime
+            .block_on(async {
+                let client = match tls_connector {
+                    Some(connector) => {
+                        let (client, connection) = tokio_postgres::connect(&config.connection_str, connector).await?;
+                        tokio::spawn(drive(connection));
+                        client
+                    }
+                    None => {
+                        let (client, connection) = tokio_postgres::connect(&config.connection_str, NoTls).await?;
+                        tokio::spawn(drive(connection));
+                        client
+                    }
+                };
+                if let Some(schema) = &config.schema {
+                    client.batch_execute(&format!("SET search_path TO {};", schema)).await?;
+                }
+                let mut statements = HashMap::default();
+                for (handler_id, handler) in account_handlers.iter().filter(|(_, h)| h.enabled(config)) {
+                    let mut prepared = Vec::new();
+                    for query in handler.prepared_statements() {
+                        let statement = client.prepare(&query).await?;
+                        prepared.push((query, statement));
+                    }
+                    if !prepared.is_empty() {
+                        statements.insert(handler_id.as_str().to_string(), prepared);
+                    }
+                }
+                Ok::<_, tokio_postgres::Error>((client, statements))
+            })
+            .map_err(|err| connection_err(err.to_string()))?;
+        registry().inc_counter(DB_CONNECTS_TOTAL, &[], 1);
+        Ok(Self { runtime, client, statements })
+    }
+
+    /// Whether the connection was lost, the worker then connects again
+    pub fn is_closed(&self) -> bool {
+        self.client.is_closed()
+    }
+
+    /// Write the statement batches, the rows and the closes of a flush in a single transaction, as
+    /// `execute_transaction` does on the blocking connection. The statement batches run in savepoints with `isolate`,
+    /// then the rows of all the handlers are pipelined in a savepoint, and when it fails, the rows of each handler in a
+    /// savepoint of their own. The closes run last, after the rows written at earlier slots of the flush
+    pub fn execute_flush(
+        &mut self,
+        operation: &'static str,
+        updates: &HandlerBatch,
+        batch: &RowBatch,
+        closes: &HandlerBatch,
+        retry: &SerializationRetryConfig,
+    ) -> Result<Vec<HandlerFailure>, tokio_postgres::Error> {
+        retry_transaction(operation, retry, || self.flush_transaction(operation, updates, batch, closes))
+    }
+
+    fn flush_transaction(&mut self, operation: &'static str, updates: &HandlerBatch, batch: &RowBatch, closes: &HandlerBatch) -> Result<Vec<HandlerFailure>, tokio_postgres::Error> {
+        let Self { runtime, client, statements } = self;
+        let mut transaction = runtime.block_on(client.transaction())?;
+        let mut failures = isolate_in(runtime, &mut transaction, operation, updates)?;
+        let groups = batch.groups();
+        let pipelined = groups.len() > 1
+            && match runtime.block_on(execute_in(&mut transaction, statements, groups)) {
+                Ok(()) => true,
+                Err(err) if record_db_error(operation, &err).is_transient() => return Err(err),
+                Err(err) => {
+                    warn!("[{}] isolating the rows of {} handlers after error=[{}]", operation, groups.len(), err);
+                    false
+                }
+            };
+        for group in groups.iter().filter(|_| !pipelined) {
+            let (handler_id, rows) = group;
+            let handler_statements = match statements.get(handler_id) {
+                Some(handler_statements) => handler_statements,
+                None => {
+                    error!("[{}] handler=[{}] wrote {} rows without prepared statements", operation, handler_id, rows.len());
+                    continue;
+                }
+            };
+            if let Err(error) = runtime.block_on(execute_in(&mut transaction, statements, std::slice::from_ref(group))) {
+                if record_db_error(operation, &error).is_transient() {
+                    return Err(error);
+                }
+                error!("[{}] handler=[{}] error=[{}]", operation, handler_id, error);
+                registry().inc_counter(HANDLER_ERRORS_TOTAL, &[("handler", handler_id)], 1);
+                failures.push(HandlerFailure {
+                    handler_id: handler_id.clone(),
+                    statements: rows.iter().map(|row| format!("{} -- {:?}\n", handler_statements[row.statement].0, row.params)).collect(),
+                    error,
+                });
+            }
+        }
+        failures.extend(isolate_in(runtime, &mut transaction, operation, closes)?);
+        runtime.block_on(transaction.commit())?;
+        Ok(failures)
+    }
+}
+
+/// Run the statement batches of `batch` in savepoints of `transaction` with `isolate`
+fn isolate_in(runtime: &Runtime, transaction: &mut Transaction<'_>, operation: &'static str, batch: &HandlerBatch) -> Result<Vec<HandlerFailure>, tokio_postgres::Error> {
+    isolate(operation, batch, |query| {
+        let query = fault_injection::inject(operation, query);
+        registry().inc_counter(BYTES_WRITTEN_TOTAL, &[], query.len() as u64);
+        let result = runtime.block_on(async {
+            let savepoint = transaction.transaction().await?;
+            savepoint.batch_execute(&query).await?;
+            savepoint.commit().await
+        });
+        if let Err(err) = &result {
+            record_db_error(operation, err);
+        }
+        result
+    })
+}
+
+/// Pipeline the rows of `groups` in a savepoint of `transaction`
+async fn execute_in(transaction: &mut Transaction<'_>, statements: &HashMap<String, Vec<(String, Statement)>>, groups: &[(String, Vec<BoundRow>)]) -> Result<(), tokio_postgres::Error> {
+    let rows: Vec<_> = groups
+        .iter()
+        .filter_map(|(handler_id, rows)| statements.get(handler_id).map(|handler_statements| (handler_statements, rows)))
+        .flat_map(|(handler_statements, rows)| rows.iter().map(move |row| (&handler_statements[row.statement].1, row)))
+        .collect();
+    let savepoint = transaction.transaction().await?;
+    for chunk in rows.chunks(PIPELINE_DEPTH) {
+        let savepoint = &savepoint;
+        try_join_all(chunk.iter().map(|(statement, row)| async move { savepoint.execute(*statement, &row.params()).await })).await?;
+    }
+    savepoint.commit().await
+}
+
+/// Run the connection until it closes, the client reports it closed from then on
+async fn drive<T>(connection: T)
+where
+    T: std::future::Future<Output = Result<(), tokio_postgres::Error>>,
+{
+    if let Err(err) = connection.await {
+        error!("[AsyncPipeline] connection error=[{}]", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connect_error() {
+        let config = GeyserPluginPostgresConfig {
+            connection_str: "host=127.0.0.1 port=1 user=solana connect_timeout=1".to_string(),
+            ..GeyserPluginPostgresConfig::default()
+        };
+        let result = AsyncPipeline::connect(&config, &HashMap::default());
+        assert!(matches!(result, Err(GeyserPluginError::Custom(_))));
+    }
+}
@@ -1,9 +1,12 @@
 use crate::config::GeyserPluginPostgresConfig;
 use crate::geyser_plugin_postgres::GeyserPluginPostgresError;
+use crate::postgres_client::db_errors::record_db_error;
+use crate::postgres_client::prepared_statement::PreparedStatement;
+use crate::postgres_client::table_prefix::TablePrefix;
 use chrono::Utc;
 use log::*;
 use postgres::Client;
-use postgres::Statement;
+use postgres::Transaction;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaBlockInfo;
 
@@ -31,27 +34,48 @@ impl<'a> From<&ReplicaBlockInfo<'a>> for DbBlockInfo {
 }
 
 pub struct BlockHandler {
-    pub upsert_statement: Statement,
+    pub upsert_statement: PreparedStatement,
+    /// Writes the rewards of the block to the `block_reward` table, one row per reward
+    pub rewards_statement: PreparedStatement,
+    /// Leave the `rewards` array column of the `block` table empty
+    skip_rewards_array: bool,
 }
 
 impl BlockHandler {
-    pub fn new(client: &mut Client, _config: &GeyserPluginPostgresConfig) -> Result<BlockHandler, GeyserPluginError> {
-        let stmt = "INSERT INTO block (slot, blockhash, rewards, block_time, block_height, updated_on) \
-        VALUES ($1, $2, $3, $4, $5, $6) \
-        ON CONFLICT (slot) DO UPDATE SET blockhash=excluded.blockhash, rewards=excluded.rewards, \
-        block_time=excluded.block_time, block_height=excluded.block_height, updated_on=excluded.updated_on;";
-        match client.prepare(stmt) {
-            Ok(statement) => Ok(BlockHandler { upsert_statement: statement }),
-            Err(err) => Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
+    pub fn new(transaction: &mut Transaction, config: &GeyserPluginPostgresConfig) -> Result<BlockHandler, GeyserPluginError> {
+        let prefix = TablePrefix::new(config);
+        let stmt = format!(
+            "INSERT INTO {}block (slot, blockhash, rewards, block_time, block_height, updated_on) \
+            VALUES ($1, $2, $3, $4, $5, $6) \
+            ON CONFLICT (slot) DO UPDATE SET blockhash=excluded.blockhash, rewards=excluded.rewards, \
+            block_time=excluded.block_time, block_height=excluded.block_height, updated_on=excluded.updated_on;",
+            prefix
+        );
+        let rewards_stmt = format!(
+            "INSERT INTO {}block_reward (slot, reward_index, pubkey, lamports, post_balance, reward_type, commission, updated_on) \
+            SELECT $1, (r.ordinality - 1)::INT, r.pubkey, r.lamports, r.post_balance, r.reward_type, r.commission, $3 \
+            FROM UNNEST($2::\"Reward\"[]) WITH ORDINALITY AS r(pubkey, lamports, post_balance, reward_type, commission, ordinality) \
+            ON CONFLICT (slot, reward_index) DO UPDATE SET pubkey=excluded.pubkey, lamports=excluded.lamports, post_balance=excluded.post_balance, \
+            reward_type=excluded.reward_type, commission=excluded.commission, updated_on=excluded.updated_on;",
+            prefix
+        );
+        let prepare_err = |err: postgres::Error| {
+            GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
                 msg: format!("[block_handler::new] error={}", err),
-            }))),
-        }
+            }))
+        };
+        Ok(BlockHandler {
+            upsert_statement: PreparedStatement::prepare(transaction, &stmt, config.pgbouncer).map_err(prepare_err)?,
+            rewards_statement: PreparedStatement::prepare(transaction, &rewards_stmt, config.pgbouncer).map_err(prepare_err)?,
+            skip_rewards_array: config.skip_block_rewards_array,
+        })
     }
 
-    pub fn init(_config: &crate::config::GeyserPluginPostgresConfig) -> String {
-        return "
+    pub fn init(config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        return format!(
+            "
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'RewardType') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'RewardType' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"RewardType\" AS ENUM (
                         'Fee',
                         'Rent',
@@ -62,7 +86,7 @@ impl BlockHandler {
             END $$;
             
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'Reward') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'Reward' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"Reward\" AS (
                         pubkey VARCHAR(44),
                         lamports BIGINT,
@@ -73,7 +97,7 @@ impl BlockHandler {
                 END IF;
             END $$;     
             
-            CREATE TABLE IF NOT EXISTS block (
+            CREATE TABLE IF NOT EXISTS {prefix}block (
                 slot BIGINT PRIMARY KEY,
                 blockhash VARCHAR(44),
                 rewards \"Reward\"[],
@@ -81,23 +105,40 @@ impl BlockHandler {
                 block_height BIGINT,
                 updated_on TIMESTAMP NOT NULL
             );
-        "
-        .to_string();
-    }
 
-    pub fn update(&self, client: &mut Client, block_info: DbBlockInfo) -> Result<(), GeyserPluginError> {
-        let result = client.query(
-            &self.upsert_statement,
-            &[
-                &block_info.slot,
-                &block_info.blockhash,
-                &block_info.rewards,
-                &block_info.block_time,
-                &block_info.block_height,
-                &Utc::now().naive_utc(),
-            ],
+            CREATE TABLE IF NOT EXISTS {prefix}block_reward (
+                slot BIGINT NOT NULL,
+                reward_index INT NOT NULL,
+                pubkey VARCHAR(44) NOT NULL,
+                lamports BIGINT NOT NULL,
+                post_balance BIGINT NOT NULL,
+                reward_type \"RewardType\",
+                commission SMALLINT,
+                updated_on TIMESTAMP NOT NULL,
+                PRIMARY KEY (slot, reward_index)
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}block_reward_pubkey ON {prefix}block_reward (pubkey, slot);
+        ",
+            prefix = TablePrefix::new(config)
         );
+    }
+
+    pub fn update(&self, client: &mut Client, block_info: &DbBlockInfo) -> Result<(), GeyserPluginError> {
+        let updated_on = Utc::now().naive_utc();
+        let rewards_array = (!self.skip_rewards_array).then_some(&block_info.rewards);
+        // the block and its rewards are committed together
+        let result = client.transaction().and_then(|mut transaction| {
+            self.upsert_statement.execute(
+                &mut transaction,
+                &[&block_info.slot, &block_info.blockhash, &rewards_array, &block_info.block_time, &block_info.block_height, &updated_on],
+            )?;
+            if !block_info.rewards.is_empty() {
+                self.rewards_statement.execute(&mut transaction, &[&block_info.slot, &block_info.rewards, &updated_on])?;
+            }
+            transaction.commit()
+        });
         if let Err(err) = result {
+            record_db_error("update_block_metadata", &err);
             let msg = format!("Failed to persist the update of block metadata to the PostgreSQL database. Error: {:?}", err);
             error!("{}", msg);
             return Err(GeyserPluginError::AccountsUpdateError { msg });
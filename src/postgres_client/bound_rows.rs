@@ -0,0 +1,223 @@
+use crate::config::GeyserPluginPostgresConfig;
+use crate::metrics::registry;
+use crate::metrics::HANDLER_ERRORS_TOTAL;
+use crate::postgres_client::db_errors::record_db_error;
+use crate::postgres_client::handler_batch::HandlerFailure;
+use crate::postgres_client::prepared_statement::PreparedStatement;
+use crate::postgres_client::AccountHandler;
+use crate::postgres_client::AccountHandlerId;
+use bytes::BufMut;
+use bytes::BytesMut;
+use log::*;
+use postgres::types::IsNull;
+use postgres::types::ToSql;
+use postgres::types::Type;
+use postgres::GenericClient;
+use postgres::Transaction;
+use postgres_types::accepts;
+use postgres_types::to_sql_checked;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// The values of a row bound as typed parameters, in column order
+pub type BoundValues = Vec<Box<dyn ToSql + Sync + Send>>;
+
+/// A u64 bound to a `NUMERIC` column, which has no binary mapping from the integer types
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Numeric(pub u64);
+
+impl ToSql for Numeric {
+    /// The binary `NUMERIC` format, the count of base 10000 digits, the weight of the first one, the sign and the
+    /// display scale, followed by the digits
+    fn to_sql(&self, _ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        let mut digits = Vec::new();
+        let mut value = self.0;
+        while value > 0 {
+            digits.push((value % 10_000) as i16);
+            value /= 10_000;
+        }
+        digits.reverse();
+        out.put_i16(digits.len() as i16);
+        out.put_i16((digits.len() as i16 - 1).max(0));
+        out.put_u16(0);
+        out.put_u16(0);
+        for digit in digits {
+            out.put_i16(digit);
+        }
+        Ok(IsNull::No)
+    }
+
+    accepts!(NUMERIC);
+    to_sql_checked!();
+}
+
+/// A row an account handler writes with one of its `prepared_statements`, its values bound as typed
+/// parameters rather than formatted into the SQL
+#[derive(Debug)]
+pub struct BoundRow {
+    /// The index of the statement in the handler's `prepared_statements`
+    pub statement: usize,
+    pub params: BoundValues,
+}
+
+impl BoundRow {
+    pub fn new(statement: usize, params: BoundValues) -> Self {
+        Self { statement, params }
+    }
+
+    pub fn params(&self) -> Vec<&(dyn ToSql + Sync)> {
+        self.params.iter().map(|param| param.as_ref() as &(dyn ToSql + Sync)).collect()
+    }
+}
+
+/// The `prepared_statements` of the account handlers, by handler id
+#[derive(Default)]
+pub struct HandlerStatements {
+    statements: HashMap<String, Vec<(String, PreparedStatement)>>,
+}
+
+impl HandlerStatements {
+    /// Prepare the statements of the enabled handlers in the transaction the client prepares all of its statements in
+    pub fn prepare(transaction: &mut Transaction, account_handlers: &HashMap<AccountHandlerId, Box<dyn AccountHandler>>, config: &GeyserPluginPostgresConfig) -> Result<Self, postgres::Error> {
+        let mut statements = HashMap::default();
+        for (handler_id, handler) in account_handlers.iter().filter(|(_, h)| h.enabled(config)) {
+            let queries = handler.prepared_statements();
+            if queries.is_empty() {
+                continue;
+            }
+            let mut prepared = Vec::with_capacity(queries.len());
+            for query in queries {
+                let statement = PreparedStatement::prepare(transaction, &query, config.pgbouncer)?;
+                prepared.push((query, statement));
+            }
+            statements.insert(handler_id.as_str().to_string(), prepared);
+        }
+        Ok(Self { statements })
+    }
+}
+
+/// The bound rows of a flush grouped by the handler that wrote them, in the order the handlers first appeared
+#[derive(Debug, Default)]
+pub struct RowBatch {
+    groups: Vec<(String, Vec<BoundRow>)>,
+    rows: usize,
+}
+
+impl RowBatch {
+    pub fn push(&mut self, handler_id: &str, rows: Vec<BoundRow>) {
+        self.rows += rows.len();
+        match self.groups.iter_mut().find(|(id, _)| id == handler_id) {
+            Some((_, group)) => group.extend(rows),
+            None => self.groups.push((handler_id.to_string(), rows)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows == 0
+    }
+
+    /// The rows of each handler
+    pub fn groups(&self) -> &[(String, Vec<BoundRow>)] {
+        &self.groups
+    }
+
+    /// The rows bound by each handler
+    pub fn handler_counts(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.groups.iter().map(|(handler_id, rows)| (handler_id.as_str(), rows.len()))
+    }
+
+    pub fn clear(&mut self) {
+        self.groups.clear();
+        self.rows = 0;
+    }
+}
+
+/// Write the rows of all the handlers in a single savepoint of the transaction of a flush, and when it fails, the rows
+/// of each handler in a savepoint of their own, so only the handlers at fault lose their rows. As with
+/// `execute_isolated_in`, the failures
+/// are counted in the handler errors and returned to be recorded, and transient errors fail the whole flush.
+pub fn execute_rows(client: &mut impl GenericClient, operation: &'static str, statements: &HandlerStatements, batch: &RowBatch) -> Result<Vec<HandlerFailure>, postgres::Error> {
+    if batch.groups.len() > 1 {
+        let result = client.transaction().and_then(|mut transaction| {
+            for (handler_id, rows) in &batch.groups {
+                if let Some(handler_statements) = statements.statements.get(handler_id) {
+                    for row in rows {
+                        handler_statements[row.statement].1.execute(&mut transaction, &row.params())?;
+                    }
+                }
+            }
+            transaction.commit()
+        });
+        match result {
+            Ok(()) => return Ok(Vec::new()),
+            Err(err) if record_db_error(operation, &err).is_transient() => return Err(err),
+            Err(err) => warn!("[{}] isolating the rows of {} handlers after error=[{}]", operation, batch.groups.len(), err),
+        }
+    }
+    let mut failures = Vec::new();
+    for (handler_id, rows) in &batch.groups {
+        let handler_statements = match statements.statements.get(handler_id) {
+            Some(handler_statements) => handler_statements,
+            None => {
+                error!("[{}] handler=[{}] wrote {} rows without prepared statements", operation, handler_id, rows.len());
+                continue;
+            }
+        };
+        let result = client.transaction().and_then(|mut transaction| {
+            for row in rows {
+                handler_statements[row.statement].1.execute(&mut transaction, &row.params())?;
+            }
+            transaction.commit()
+        });
+        if let Err(error) = result {
+            if record_db_error(operation, &error).is_transient() {
+                return Err(error);
+            }
+            error!("[{}] handler=[{}] error=[{}]", operation, handler_id, error);
+            registry().inc_counter(HANDLER_ERRORS_TOTAL, &[("handler", handler_id)], 1);
+            failures.push(HandlerFailure {
+                handler_id: handler_id.clone(),
+                statements: rows.iter().map(|row| format!("{} -- {:?}\n", handler_statements[row.statement].0, row.params)).collect(),
+                error,
+            });
+        }
+    }
+    Ok(failures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_batch() {
+        let mut batch = RowBatch::default();
+        batch.push("token_manager", vec![BoundRow::new(0, vec![Box::new("a".to_string()), Box::new(1i64)])]);
+        batch.push("token_metadata_creators", vec![BoundRow::new(0, vec![Box::new(true)]), BoundRow::new(0, vec![Box::new(false)])]);
+        batch.push("token_manager", vec![BoundRow::new(0, vec![Box::new("b".to_string()), Box::new(2i64)])]);
+        assert_eq!(batch.len(), 4);
+        assert_eq!(batch.groups.len(), 2);
+        assert_eq!(batch.handler_counts().collect::<Vec<_>>(), vec![("token_manager", 2), ("token_metadata_creators", 2)]);
+        assert_eq!(batch.groups[0].1.len(), 2);
+        assert_eq!(format!("{:?}", batch.groups[0].1[1].params()), "[\"b\", 2]");
+        batch.clear();
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn test_numeric() {
+        let encode = |value: u64| {
+            let mut out = BytesMut::new();
+            Numeric(value).to_sql(&Type::NUMERIC, &mut out).unwrap();
+            out.to_vec()
+        };
+        assert_eq!(encode(0), [0, 0, 0, 0, 0, 0, 0, 0]);
+        // 26186805005000 is 26 1868 0500 5000 in base 10000, the first digit 10000^3
+        assert_eq!(encode(26186805005000), [0, 4, 0, 3, 0, 0, 0, 0, 0, 26, 0x07, 0x4c, 0x01, 0xf4, 0x13, 0x88]);
+        assert_eq!(encode(u64::MAX)[..4], [0, 5, 0, 4]);
+    }
+}
@@ -0,0 +1,223 @@
+use super::accounts::account_handler::AccountHandler;
+use super::bound_rows::BoundValues;
+use super::db_errors::record_db_error;
+use super::handler_batch::HandlerFailure;
+use super::DbAccountInfo;
+use crate::metrics::registry;
+use crate::metrics::HANDLER_ERRORS_TOTAL;
+use log::*;
+use postgres::binary_copy::BinaryCopyInWriter;
+use postgres::types::ToSql;
+use postgres::types::Type;
+use postgres::GenericClient;
+use std::collections::HashMap;
+
+/// A table a handler writes one row per account to, with `slot` and `write_version` columns
+pub struct StagingTable {
+    /// The prefixed name of the table
+    pub table: String,
+    /// The columns of the rows returned by `AccountHandler::staging_row`
+    pub columns: &'static [&'static str],
+    /// The columns of the row's unique key
+    pub key: &'static str,
+    /// The `ON CONFLICT` clause applying a row to the table
+    pub on_conflict: String,
+}
+
+impl StagingTable {
+    /// Stage the rows in a temporary table emptied on commit, or dropped with `pgbouncer` as the next
+    /// transaction may run on another server connection, and apply the latest row of each key, by slot
+    /// then write version, in a single statement
+    pub fn apply(&self, rows: &[String], pgbouncer: bool) -> String {
+        format!(
+            "{0}INSERT INTO {1}_staging ({2}) VALUES {3};{4}",
+            self.create(pgbouncer),
+            self.table,
+            self.columns.join(", "),
+            rows.join(", "),
+            self.merge(),
+        )
+    }
+
+    fn create(&self, pgbouncer: bool) -> String {
+        format!(
+            "
+                CREATE TEMP TABLE IF NOT EXISTS {0}_staging (LIKE {0} INCLUDING DEFAULTS) ON COMMIT {1};
+            ",
+            self.table,
+            if pgbouncer { "DROP" } else { "DELETE ROWS" },
+        )
+    }
+
+    fn merge(&self) -> String {
+        format!(
+            "
+                INSERT INTO {0} AS acc ({1}) \
+                SELECT DISTINCT ON ({2}) {1} FROM {0}_staging ORDER BY {2}, slot DESC, write_version DESC \
+                {3};
+            ",
+            self.table,
+            self.columns.join(", "),
+            self.key,
+            self.on_conflict,
+        )
+    }
+
+    /// The types of the staged columns, read from the staging table
+    fn column_types(&self, client: &mut impl GenericClient) -> Result<Vec<Type>, postgres::Error> {
+        let statement = client.prepare(&format!("SELECT {} FROM {}_staging", self.columns.join(", "), self.table))?;
+        Ok(statement.columns().iter().map(|column| column.type_().clone()).collect())
+    }
+}
+
+/// The staged rows of a handler, as SQL values or, with `copy`, as bound values
+struct Staged {
+    handler_id: String,
+    table: StagingTable,
+    rows: Vec<String>,
+    values: Vec<BoundValues>,
+}
+
+/// The startup accounts of the handlers with a staging table, loaded in bulk so the entries a
+/// snapshot holds for the same account are resolved by the server instead of by conflicting upserts
+pub struct BulkLoad {
+    tables: Vec<Staged>,
+    pgbouncer: bool,
+    /// Copy the `staging_values` of the handlers in binary rather than inserting their `staging_row`
+    copy: bool,
+}
+
+impl BulkLoad {
+    pub fn new(pgbouncer: bool, copy: bool) -> Self {
+        Self { tables: Vec::new(), pgbouncer, copy }
+    }
+
+    /// Stage the row of an account for a handler, returns false if the handler has no staging table
+    pub fn stage(&mut self, handler_id: &str, handler: &dyn AccountHandler, account: &DbAccountInfo) -> bool {
+        let index = match self.tables.iter().position(|staged| staged.handler_id == handler_id) {
+            Some(index) => index,
+            None => match handler.staging_table() {
+                Some(table) => {
+                    self.tables.push(Staged {
+                        handler_id: handler_id.to_string(),
+                        table,
+                        rows: Vec::new(),
+                        values: Vec::new(),
+                    });
+                    self.tables.len() - 1
+                }
+                None => return false,
+            },
+        };
+        if let Some(values) = self.copy.then(|| handler.staging_values(account)).flatten() {
+            self.tables[index].values.push(values);
+        } else if let Some(row) = handler.staging_row(account) {
+            self.tables[index].rows.push(row);
+        }
+        true
+    }
+
+    /// The rows staged by each handler
+    pub fn handler_counts(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.tables.iter().map(|staged| (staged.handler_id.as_str(), staged.rows.len() + staged.values.len()))
+    }
+
+    /// The statements applying the staged rows, by handler id
+    pub fn queries(&self) -> Vec<(String, String)> {
+        self.tables
+            .iter()
+            .filter(|staged| !staged.rows.is_empty())
+            .map(|staged| (staged.handler_id.clone(), staged.table.apply(&staged.rows, self.pgbouncer)))
+            .collect()
+    }
+
+    /// Copy the staged values of each handler to its staging table and apply them, in a transaction per handler.
+    /// As with `execute_rows`, only the handlers at fault lose their rows and transient errors fail the whole flush.
+    /// The column types of the staging tables are kept in `column_types` across flushes
+    pub fn copy(&self, client: &mut impl GenericClient, operation: &'static str, column_types: &mut HashMap<String, Vec<Type>>) -> Result<Vec<HandlerFailure>, postgres::Error> {
+        let mut failures = Vec::new();
+        for staged in self.tables.iter().filter(|staged| !staged.values.is_empty()) {
+            let table = &staged.table;
+            let result = client.transaction().and_then(|mut transaction| {
+                transaction.batch_execute(&table.create(self.pgbouncer))?;
+                if !column_types.contains_key(&table.table) {
+                    column_types.insert(table.table.clone(), table.column_types(&mut transaction)?);
+                }
+                let writer = transaction.copy_in(&format!("COPY {}_staging ({}) FROM STDIN BINARY", table.table, table.columns.join(", ")))?;
+                let mut writer = BinaryCopyInWriter::new(writer, &column_types[&table.table]);
+                for values in &staged.values {
+                    writer.write(&values.iter().map(|value| value.as_ref() as &(dyn ToSql + Sync)).collect::<Vec<_>>())?;
+                }
+                writer.finish()?;
+                transaction.batch_execute(&table.merge())?;
+                transaction.commit()
+            });
+            if let Err(error) = result {
+                if record_db_error(operation, &error).is_transient() {
+                    return Err(error);
+                }
+                error!("[{}] handler=[{}] table=[{}] error=[{}]", operation, staged.handler_id, table.table, error);
+                registry().inc_counter(HANDLER_ERRORS_TOTAL, &[("handler", &staged.handler_id)], 1);
+                failures.push(HandlerFailure {
+                    handler_id: staged.handler_id.clone(),
+                    statements: format!("COPY {}_staging -- {} rows\n", table.table, staged.values.len()),
+                    error,
+                });
+            }
+        }
+        Ok(failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::accounts::unknown_account_handler::UnknownAccountHandler;
+    use crate::postgres_client::AccountKey;
+    use crate::postgres_client::ConflictStrategy;
+
+    #[test]
+    fn test_staging_table_apply() {
+        let table = StagingTable {
+            table: "spl_token_account".to_string(),
+            columns: &["pubkey", "owner", "mint", "amount", "slot", "write_version"],
+            key: "pubkey, owner, mint",
+            on_conflict: ConflictStrategy::SlotWriteVersion.on_conflict("pubkey, owner, mint", &["amount", "write_version"]),
+        };
+        let rows = ["('a', 'o', 'm', 1, 5, 1)".to_string(), "('a', 'o', 'm', 2, 5, 2)".to_string()];
+        let query = table.apply(&rows, false);
+        assert!(query.contains("CREATE TEMP TABLE IF NOT EXISTS spl_token_account_staging (LIKE spl_token_account INCLUDING DEFAULTS) ON COMMIT DELETE ROWS;"));
+        assert!(query.contains("VALUES ('a', 'o', 'm', 1, 5, 1), ('a', 'o', 'm', 2, 5, 2);"));
+        assert!(query.contains(
+            "SELECT DISTINCT ON (pubkey, owner, mint) pubkey, owner, mint, amount, slot, write_version FROM spl_token_account_staging \
+            ORDER BY pubkey, owner, mint, slot DESC, write_version DESC ON CONFLICT (pubkey, owner, mint)"
+        ));
+        assert!(table.apply(&rows, true).contains("ON COMMIT DROP;"));
+    }
+
+    #[test]
+    fn test_bulk_load_copy() {
+        let account = DbAccountInfo {
+            pubkey: AccountKey::from_slice(&[1; 32]),
+            lamports: 1,
+            owner: AccountKey::from_slice(&[2; 32]),
+            executable: false,
+            rent_epoch: 0,
+            data: vec![1, 2, 3],
+            slot: 5,
+            write_version: 1,
+            txn_signature: None,
+        };
+        let handler = UnknownAccountHandler::default();
+        let mut copy = BulkLoad::new(false, true);
+        assert!(copy.stage("unknown_account", &handler, &account));
+        assert_eq!(copy.tables[0].values.len(), 1);
+        assert_eq!(copy.tables[0].values[0].len(), copy.tables[0].table.columns.len());
+        // the copied rows are applied by `copy`, not by the statements of the flush
+        assert!(copy.queries().is_empty());
+
+        let mut insert = BulkLoad::new(false, false);
+        assert!(insert.stage("unknown_account", &handler, &account));
+        assert_eq!(insert.queries().len(), 1);
+    }
+}
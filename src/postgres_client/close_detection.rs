@@ -0,0 +1,215 @@
+use crate::config::GeyserPluginPostgresConfig;
+use crate::postgres_client::is_identifier;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::DbAccountInfo;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use solana_sdk::system_program;
+use std::collections::BTreeMap;
+
+/// What becomes of the rows of a closed account
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CloseMode {
+    /// Delete the rows
+    #[default]
+    Delete,
+    /// Set the `closed_at_slot` column of the rows
+    Mark,
+}
+
+/// * The `close_detection` section handles the accounts closed by a live update, their lamports dropped to zero
+/// whatever their owner, as a closed token account keeps the token program as its owner. An account the system
+/// program reassigned as it closed it no longer matches the selector it was written for, only its close is written.
+/// "close_detection" : { "mode" : "delete", "tables" : { "account" : "pubkey", "spl_token_account" : "pubkey", "token_manager" : "id" } }
+/// The rows of a closed account written at or before the slot it was closed are deleted from `tables`, found by
+/// the account's address in the key column of each table, or with "mark" get the slot in a `closed_at_slot` column.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CloseDetectionConfig {
+    pub mode: CloseMode,
+    /// The tables with a `slot` column, and the column of each holding the account's address, as bytes or base58
+    pub tables: BTreeMap<String, String>,
+}
+
+impl Default for CloseDetectionConfig {
+    fn default() -> Self {
+        Self {
+            mode: CloseMode::default(),
+            tables: BTreeMap::from([
+                ("account".to_string(), "pubkey".to_string()),
+                ("spl_token_account".to_string(), "pubkey".to_string()),
+                ("token_manager".to_string(), "id".to_string()),
+            ]),
+        }
+    }
+}
+
+impl CloseDetectionConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.tables.is_empty() {
+            return Err("close_detection \"tables\" must name at least one table".to_string());
+        }
+        if let Some((table, column)) = self.tables.iter().find(|(table, column)| !is_identifier(table) || !is_identifier(column) || *table == "slot") {
+            return Err(format!(
+                "close_detection.tables \"{}\" : \"{}\" is not a table and key column the rows of closed accounts can be found in",
+                table, column
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Whether an account was closed, its lamports drained. The system program only reassigns the accounts it
+/// closes, a token account closed by the token program keeps its owner
+pub fn is_closed(lamports: u64) -> bool {
+    lamports == 0
+}
+
+/// Whether an account was closed and reassigned to the system program, the selectors skip it once it moved away from
+/// the program its rows were written for
+pub fn is_reassigned_close(lamports: u64, owner: &[u8]) -> bool {
+    is_closed(lamports) && owner == system_program::id().as_ref()
+}
+
+pub struct CloseDetectionHandler {}
+
+impl CloseDetectionHandler {
+    /// Runs after the handlers created their tables. The key types of the tables are looked up once, here, and
+    /// `close_account` is generated with a statement per existing table. With "mark" the marked tables get their
+    /// `closed_at_slot` column, cleared by a trigger when the account is written again at a later slot
+    pub fn init(config: &GeyserPluginPostgresConfig) -> String {
+        let close_detection = match &config.close_detection {
+            Some(close_detection) => close_detection,
+            None => return "".to_string(),
+        };
+        let prefix = TablePrefix::new(config);
+        let mark = close_detection.mode == CloseMode::Mark;
+        let mut query = String::new();
+        if mark {
+            query.push_str(&format!(
+                "
+                CREATE OR REPLACE FUNCTION {}reopen_closed_account() RETURNS TRIGGER AS $$
+                BEGIN
+                    IF NEW.closed_at_slot IS NOT DISTINCT FROM OLD.closed_at_slot AND NEW.slot > OLD.closed_at_slot THEN
+                        NEW.closed_at_slot := NULL;
+                    END IF;
+                    RETURN NEW;
+                END;
+                $$ LANGUAGE plpgsql;
+            ",
+                prefix
+            ));
+            for table in close_detection.tables.keys() {
+                query.push_str(&format!(
+                    "DO $$ BEGIN IF to_regclass('{0}') IS NOT NULL THEN ALTER TABLE {0} ADD COLUMN IF NOT EXISTS closed_at_slot BIGINT; END IF; END $$;\n",
+                    prefix.table(table)
+                ));
+            }
+        }
+        let literal = |name: &str| format!("'{}'", prefix.table(name));
+        query.push_str(&format!(
+            "
+            DO $$
+            DECLARE
+                tables TEXT[] := ARRAY[{0}]::TEXT[];
+                key_columns TEXT[] := ARRAY[{1}]::TEXT[];
+                key_type TEXT;
+                body TEXT := '';
+            BEGIN
+                FOR i IN 1 .. array_length(tables, 1) LOOP
+                    SELECT c.data_type INTO key_type FROM information_schema.columns c
+                    WHERE c.table_schema = current_schema() AND c.table_name = tables[i] AND c.column_name = key_columns[i];
+                    IF key_type IS NULL THEN
+                        CONTINUE;
+                    END IF;
+                    IF {2} THEN
+                        body := body || format('UPDATE %I SET closed_at_slot = closed_slot WHERE %I = %s AND slot <= closed_slot;', tables[i], key_columns[i],
+                            CASE WHEN key_type = 'bytea' THEN 'closed_pubkey' ELSE 'closed_address' END);
+                        EXECUTE format('DROP TRIGGER IF EXISTS reopen_closed_account ON %I', tables[i]);
+                        EXECUTE format('CREATE TRIGGER reopen_closed_account BEFORE UPDATE ON %I FOR EACH ROW EXECUTE PROCEDURE %I()', tables[i], {3});
+                    ELSE
+                        body := body || format('DELETE FROM %I WHERE %I = %s AND slot <= closed_slot;', tables[i], key_columns[i],
+                            CASE WHEN key_type = 'bytea' THEN 'closed_pubkey' ELSE 'closed_address' END);
+                    END IF;
+                END LOOP;
+                EXECUTE format('CREATE OR REPLACE FUNCTION %I(closed_pubkey BYTEA, closed_address TEXT, closed_slot BIGINT) RETURNS VOID AS %L LANGUAGE plpgsql',
+                    {4}, 'BEGIN ' || body || ' RETURN; END;');
+            END $$;
+        ",
+            close_detection.tables.keys().map(|table| literal(table)).collect::<Vec<String>>().join(", "),
+            close_detection.tables.values().map(|column| format!("'{}'", column)).collect::<Vec<String>>().join(", "),
+            mark,
+            literal("reopen_closed_account"),
+            literal("close_account"),
+        ));
+        query
+    }
+
+    /// Deletes or marks the rows of the closed account, in the flush of the update closing it
+    pub fn closed(prefix: &TablePrefix, account: &DbAccountInfo) -> String {
+        format!(
+            "SELECT {}close_account('\\x{}', '{}', {});",
+            prefix,
+            hex::encode(&account.pubkey),
+            bs58::encode(&account.pubkey).into_string(),
+            account.slot,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::AccountKey;
+
+    #[test]
+    fn test_close_detection() {
+        let close_detection = CloseDetectionConfig::default();
+        assert!(close_detection.validate().is_ok());
+        let account = DbAccountInfo {
+            pubkey: AccountKey::from_slice(&[1; 32]),
+            lamports: 0,
+            owner: AccountKey::from_slice(&[6; 32]),
+            executable: false,
+            rent_epoch: 0,
+            data: vec![],
+            slot: 42,
+            write_version: 1,
+            txn_signature: None,
+        };
+        // a closed token account keeps the token program as its owner
+        assert!(is_closed(account.lamports as u64));
+        assert!(!is_closed(1));
+        assert!(!is_reassigned_close(account.lamports as u64, &account.owner));
+        assert!(is_reassigned_close(0, system_program::id().as_ref()));
+        assert!(!is_reassigned_close(1, system_program::id().as_ref()));
+        assert_eq!(
+            CloseDetectionHandler::closed(&TablePrefix::default(), &account),
+            format!("SELECT close_account('\\x{}', '4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi', 42);", "01".repeat(32))
+        );
+        let config = GeyserPluginPostgresConfig {
+            close_detection: Some(CloseDetectionConfig {
+                mode: CloseMode::Mark,
+                ..close_detection.clone()
+            }),
+            table_prefix: "devnet_".to_string(),
+            ..GeyserPluginPostgresConfig::default()
+        };
+        let init = CloseDetectionHandler::init(&config);
+        assert!(init.contains("ARRAY['devnet_account', 'devnet_spl_token_account', 'devnet_token_manager']::TEXT[]"));
+        assert!(init.contains("CREATE TRIGGER reopen_closed_account BEFORE UPDATE"));
+        assert!(init.contains("CREATE OR REPLACE FUNCTION devnet_reopen_closed_account()"));
+        assert!(init.contains("ALTER TABLE devnet_spl_token_account ADD COLUMN IF NOT EXISTS closed_at_slot"));
+        assert!(CloseDetectionHandler::closed(&TablePrefix::new(&config), &account).starts_with("SELECT devnet_close_account("));
+
+        for tables in [BTreeMap::new(), BTreeMap::from([("account".to_string(), "pubkey; DROP TABLE block".to_string())])] {
+            assert!(CloseDetectionConfig {
+                tables,
+                ..CloseDetectionConfig::default()
+            }
+            .validate()
+            .is_err());
+        }
+    }
+}
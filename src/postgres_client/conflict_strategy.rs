@@ -0,0 +1,82 @@
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+/// How a handler's upsert resolves a row already written for the same key
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    /// Overwrite the row with an update of a later slot
+    #[default]
+    LatestSlot,
+    /// Overwrite the row with an update of a later slot, or a later write of the same slot,
+    /// only for the handlers whose tables record the `write_version`
+    SlotWriteVersion,
+    /// Overwrite the row with every update, in the order they are written
+    AlwaysOverwrite,
+    /// Fill in the columns an update of the same or a later slot has a value for, keeping the others
+    MergeNonNull,
+}
+
+impl ConflictStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LatestSlot => "latest_slot",
+            Self::SlotWriteVersion => "slot_write_version",
+            Self::AlwaysOverwrite => "always_overwrite",
+            Self::MergeNonNull => "merge_non_null",
+        }
+    }
+
+    /// The `ON CONFLICT` clause of an upsert aliased `acc` into a table with a `slot` column, updating
+    /// `columns` and the slot. The `write_version` is updated as one of the `columns` when it is recorded
+    pub fn on_conflict(&self, key: &str, columns: &[&str]) -> String {
+        self.on_conflict_overwriting(key, columns, &[])
+    }
+
+    /// Like `on_conflict`, also setting the `overwritten` columns to the values of the update whatever
+    /// the strategy, for the columns whose NULL is a value that must not be merged away
+    pub fn on_conflict_overwriting(&self, key: &str, columns: &[&str], overwritten: &[&str]) -> String {
+        let mut updates = columns
+            .iter()
+            .map(|column| match self {
+                Self::MergeNonNull => format!("{0}=COALESCE(excluded.{0}, acc.{0})", column),
+                _ => format!("{0}=excluded.{0}", column),
+            })
+            .chain(overwritten.iter().map(|column| format!("{0}=excluded.{0}", column)))
+            .collect::<Vec<String>>();
+        updates.push("slot=excluded.slot".to_string());
+        let guard = match self {
+            Self::LatestSlot => " WHERE acc.slot < excluded.slot",
+            Self::SlotWriteVersion => " WHERE acc.slot < excluded.slot OR (acc.slot = excluded.slot AND acc.write_version < excluded.write_version)",
+            Self::AlwaysOverwrite => "",
+            Self::MergeNonNull => " WHERE acc.slot <= excluded.slot",
+        };
+        format!("ON CONFLICT ({}) DO UPDATE SET {}{}", key, updates.join(", "), guard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_on_conflict() {
+        assert_eq!(
+            ConflictStrategy::LatestSlot.on_conflict("id", &["name", "authority"]),
+            "ON CONFLICT (id) DO UPDATE SET name=excluded.name, authority=excluded.authority, slot=excluded.slot WHERE acc.slot < excluded.slot"
+        );
+        assert!(ConflictStrategy::SlotWriteVersion
+            .on_conflict("pubkey", &["lamports", "write_version"])
+            .ends_with("WHERE acc.slot < excluded.slot OR (acc.slot = excluded.slot AND acc.write_version < excluded.write_version)"));
+        assert!(ConflictStrategy::AlwaysOverwrite.on_conflict("id", &["name"]).ends_with("slot=excluded.slot"));
+        assert_eq!(
+            ConflictStrategy::MergeNonNull.on_conflict("mint, creator", &["verified"]),
+            "ON CONFLICT (mint, creator) DO UPDATE SET verified=COALESCE(excluded.verified, acc.verified), slot=excluded.slot WHERE acc.slot <= excluded.slot"
+        );
+        assert_eq!(
+            ConflictStrategy::MergeNonNull.on_conflict_overwriting("pubkey", &["data"], &["data_codec"]),
+            "ON CONFLICT (pubkey) DO UPDATE SET data=COALESCE(excluded.data, acc.data), data_codec=excluded.data_codec, slot=excluded.slot WHERE acc.slot <= excluded.slot"
+        );
+        assert_eq!(serde_json::from_str::<ConflictStrategy>("\"merge_non_null\"").unwrap(), ConflictStrategy::MergeNonNull);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::config::GeyserPluginPostgresConfig;
+use crate::geyser_plugin_postgres::GeyserPluginPostgresError;
+use crate::metrics::registry;
+use crate::metrics::DB_POOL_CONNECTIONS;
+use crate::postgres_client::block_handler::BlockHandler;
+use crate::postgres_client::bound_rows::HandlerStatements;
+use crate::postgres_client::transaction_handler::TransactionHandler;
+use crate::postgres_client::SimplePostgresClient;
+use log::*;
+use postgres::Client;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// * The `pool` section shares a pool of connections between the workers, which check a connection out for each
+/// request and hand it back once it is written, so the connections can be fewer than the threads.
+/// "pool" : { "min_connections" : 2, "max_connections" : 4, "idle_timeout_secs" : 600, "connection_timeout_secs" : 30 }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PoolConfig {
+    /// The connections opened on load and kept open while idle
+    pub min_connections: usize,
+    /// The connections open at the same time, a worker waits for one to be handed back beyond it
+    pub max_connections: usize,
+    /// Idle connections above `min_connections` are closed after this many seconds
+    pub idle_timeout_secs: u64,
+    /// How long a worker waits for a connection before the request fails
+    pub connection_timeout_secs: u64,
+    /// Keep the statements of the handlers prepared on each pooled connection, otherwise they are prepared
+    /// every time a connection is checked out
+    pub statement_cache: bool,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            min_connections: 1,
+            max_connections: 10,
+            idle_timeout_secs: 600,
+            connection_timeout_secs: 30,
+            statement_cache: true,
+        }
+    }
+}
+
+impl PoolConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_connections == 0 || self.min_connections > self.max_connections {
+            return Err("pool \"max_connections\" must be greater than 0 and not lower than \"min_connections\"".to_string());
+        }
+        if self.connection_timeout_secs == 0 {
+            return Err("pool \"connection_timeout_secs\" must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The statements of the handlers, prepared on the connection they belong to
+pub struct ConnectionStatements {
+    pub block_handler: BlockHandler,
+    pub transaction_handler: TransactionHandler,
+    pub handler_statements: HandlerStatements,
+}
+
+/// A connection to the database and the statements prepared on it, if any yet
+pub struct PooledConnection {
+    pub client: Client,
+    pub statements: Option<ConnectionStatements>,
+    idle_since: Instant,
+}
+
+impl PooledConnection {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            statements: None,
+            idle_since: Instant::now(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct PoolState {
+    idle: Vec<PooledConnection>,
+    /// The connections open, idle or checked out
+    open: usize,
+}
+
+/// The connections shared by the workers, opened on demand up to `max_connections`
+pub struct ConnectionPool {
+    plugin_config: GeyserPluginPostgresConfig,
+    config: PoolConfig,
+    state: Mutex<PoolState>,
+    available: Condvar,
+}
+
+impl ConnectionPool {
+    /// Opens `min_connections` right away so a wrong configuration fails the plugin load
+    pub fn new(plugin_config: &GeyserPluginPostgresConfig, config: &PoolConfig) -> Result<Arc<Self>, GeyserPluginError> {
+        let pool = Arc::new(Self {
+            plugin_config: plugin_config.clone(),
+            config: config.clone(),
+            state: Mutex::new(PoolState::default()),
+            available: Condvar::new(),
+        });
+        for _ in 0..config.min_connections {
+            let connection = PooledConnection::new(SimplePostgresClient::connect_to_db(plugin_config)?);
+            let mut state = pool.state.lock().unwrap();
+            state.open += 1;
+            state.idle.push(connection);
+        }
+        pool.report(&pool.state.lock().unwrap());
+        Ok(pool)
+    }
+
+    /// The config the connections are made from
+    pub fn plugin_config(&self) -> &GeyserPluginPostgresConfig {
+        &self.plugin_config
+    }
+
+    /// Check a connection out, the most recently used idle one first, waiting up to `connection_timeout_secs`
+    /// for one to be handed back once `max_connections` are open
+    pub fn get(&self) -> Result<PooledConnection, GeyserPluginError> {
+        let deadline = Instant::now() + Duration::from_secs(self.config.connection_timeout_secs);
+        let mut state = self.state.lock().unwrap();
+        loop {
+            self.close_idle(&mut state);
+            if let Some(connection) = state.idle.pop() {
+                self.report(&state);
+                return Ok(connection);
+            }
+            if state.open < self.config.max_connections {
+                state.open += 1;
+                drop(state);
+                let connection = SimplePostgresClient::connect_to_db(&self.plugin_config);
+                let mut state = self.state.lock().unwrap();
+                if connection.is_err() {
+                    state.open -= 1;
+                    self.available.notify_one();
+                }
+                self.report(&state);
+                return connection.map(PooledConnection::new);
+            }
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                return Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::ConnectionError {
+                    msg: format!("[connection_pool] no connection was handed back within {}s", self.config.connection_timeout_secs),
+                })));
+            }
+            state = self.available.wait_timeout(state, timeout).unwrap().0;
+        }
+    }
+
+    /// Hand a connection back, a closed connection is dropped and its place freed
+    pub fn put(&self, mut connection: PooledConnection) {
+        let mut state = self.state.lock().unwrap();
+        if connection.client.is_closed() {
+            state.open -= 1;
+        } else {
+            if !self.config.statement_cache {
+                connection.statements = None;
+            }
+            connection.idle_since = Instant::now();
+            state.idle.push(connection);
+        }
+        self.report(&state);
+        self.available.notify_one();
+    }
+
+    /// Drop a connection that was lost, freeing its place
+    pub fn discard(&self, connection: PooledConnection) {
+        drop(connection);
+        let mut state = self.state.lock().unwrap();
+        state.open -= 1;
+        self.report(&state);
+        self.available.notify_one();
+    }
+
+    /// Close the connections idle for longer than `idle_timeout_secs`, down to `min_connections`
+    fn close_idle(&self, state: &mut PoolState) {
+        let idle_timeout = Duration::from_secs(self.config.idle_timeout_secs);
+        // the least recently used connections are first
+        while state.open > self.config.min_connections && state.idle.first().map_or(false, |connection| connection.idle_since.elapsed() >= idle_timeout) {
+            state.idle.remove(0);
+            state.open -= 1;
+            debug!("[connection_pool] closed an idle connection open=[{}]", state.open);
+        }
+    }
+
+    fn report(&self, state: &PoolState) {
+        registry().set_gauge(DB_POOL_CONNECTIONS, &[("state", "open")], state.open as i64);
+        registry().set_gauge(DB_POOL_CONNECTIONS, &[("state", "idle")], state.idle.len() as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_config() {
+        assert!(PoolConfig::default().validate().is_ok());
+        let config = PoolConfig {
+            min_connections: 4,
+            max_connections: 2,
+            ..PoolConfig::default()
+        };
+        assert!(config.validate().is_err());
+        assert!(PoolConfig {
+            max_connections: 0,
+            ..PoolConfig::default()
+        }
+        .validate()
+        .is_err());
+        assert!(PoolConfig {
+            connection_timeout_secs: 0,
+            ..PoolConfig::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,323 @@
+use crate::accounts_selector::AccountsSelectorConfig;
+use crate::commitment_buffer::CommitmentLevel;
+use crate::config::GeyserPluginPostgresConfig;
+use crate::postgres_client::table_prefix;
+use crate::postgres_client::AccountHandlerId;
+use crate::postgres_client::PoolConfig;
+use crate::postgres_client::TransactionHandlerId;
+use crate::postgres_client::UnloadConfig;
+use crate::transaction_selector::TransactionMentionsConfig;
+use crate::transaction_selector::TransactionSelectorConfig;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// * The `databases` section writes the handlers assigned to a database to it rather than to the main one, each database
+/// with workers of its own:
+/// "databases" : \[{ "name" : "transactions", "connection_str" : "host=... dbname=transactions", "threads" : 4, "handlers" : \["transaction", "token_transfer"\] }\]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    /// The name the database is logged and its worker threads are named with
+    pub name: String,
+    pub connection_str: String,
+    /// Workers writing to the database
+    pub threads: usize,
+    /// The account and transaction handlers written to the database
+    pub handlers: Vec<String>,
+    /// Share a pool of connections between the database's workers
+    pub pool: Option<PoolConfig>,
+    /// The schema of the database's tables, the `schema` of the main database when not set
+    pub schema: Option<String>,
+    /// The prefix of the database's tables, the `table_prefix` of the main database when not set
+    pub table_prefix: Option<String>,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            name: "".to_string(),
+            connection_str: "".to_string(),
+            threads: 4,
+            handlers: Vec::new(),
+            pool: None,
+            schema: None,
+            table_prefix: None,
+        }
+    }
+}
+
+impl DatabaseConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() || !self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            return Err(format!("databases \"name\" \"{}\" must be made of letters, digits, '_' and '-'", self.name));
+        }
+        if self.connection_str.is_empty() {
+            return Err(format!("database {} \"connection_str\" must be specified", self.name));
+        }
+        if self.threads == 0 {
+            return Err(format!("database {} \"threads\" must be greater than 0", self.name));
+        }
+        if self.handlers.is_empty() {
+            return Err(format!("database {} \"handlers\" must list at least one handler", self.name));
+        }
+        if let Some(pool) = &self.pool {
+            pool.validate().map_err(|err| format!("database {} {}", self.name, err))?;
+        }
+        table_prefix::validate(&self.schema, self.table_prefix.as_deref().unwrap_or_default()).map_err(|err| format!("database {} {}", self.name, err))?;
+        Ok(())
+    }
+
+    /// The config of the database's workers. The events are buffered, spooled and shed by the client of the main
+    /// database before they are forwarded, and the sinks and the table maintenance only run with the main database
+    pub fn worker_config(&self, config: &GeyserPluginPostgresConfig) -> GeyserPluginPostgresConfig {
+        GeyserPluginPostgresConfig {
+            connection_str: self.connection_str.clone(),
+            threads: self.threads,
+            pool: self.pool.clone(),
+            schema: self.schema.clone().or_else(|| config.schema.clone()),
+            table_prefix: self.table_prefix.clone().unwrap_or_else(|| config.table_prefix.clone()),
+            database: Some(self.name.clone()),
+            worker_thread_name_prefix: format!("{}-{}", config.worker_thread_name_prefix, self.name),
+            commitment_level: CommitmentLevel::Processed,
+            spool: None,
+            queue_saturation: None,
+            kafka: None,
+            webhooks: Vec::new(),
+            fork_cleanup: None,
+            close_detection: None,
+            heartbeat: None,
+            epochs: None,
+            token_holder_views: None,
+            retention: None,
+            plugin_stats: None,
+            discriminator_registry: false,
+            startup_progress: false,
+            startup_summary: false,
+            unload: UnloadConfig {
+                write_summary: false,
+                ..config.unload.clone()
+            },
+            ..config.clone()
+        }
+    }
+}
+
+/// Check that the databases have distinct names and that each handler is known and assigned to one database at most
+pub fn validate_databases(databases: &[DatabaseConfig], account_handler_ids: &[String], transaction_handler_ids: &[String]) -> Result<(), String> {
+    let mut names = HashSet::new();
+    let mut handlers = HashSet::new();
+    for database in databases {
+        database.validate()?;
+        if !names.insert(database.name.as_str()) {
+            return Err(format!("databases \"name\" \"{}\" is used twice", database.name));
+        }
+        for handler_id in &database.handlers {
+            let is_known = AccountHandlerId::from_str(handler_id).is_ok()
+                || TransactionHandlerId::from_str(handler_id).is_ok()
+                || account_handler_ids.contains(handler_id)
+                || transaction_handler_ids.contains(handler_id);
+            if !is_known {
+                return Err(format!("database {} has unknown handler_id \"{}\"", database.name, handler_id));
+            }
+            if !handlers.insert(handler_id.as_str()) {
+                return Err(format!("handler_id \"{}\" is assigned to more than one database", handler_id));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Routes the handlers of the selectors to the database they are written to, those no database is assigned
+/// are written to the main database
+#[derive(Clone, Debug, Default)]
+pub struct DatabaseRouter {
+    handler_databases: HashMap<String, String>,
+    /// The database the workers write to, the main one when not set
+    database: Option<String>,
+}
+
+impl DatabaseRouter {
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Self {
+        Self {
+            handler_databases: config
+                .databases
+                .iter()
+                .flat_map(|database| database.handlers.iter().map(move |handler_id| (handler_id.clone(), database.name.clone())))
+                .collect(),
+            database: config.database.clone(),
+        }
+    }
+
+    fn writes_handler(&self, handler_id: &str) -> bool {
+        self.handler_databases.get(handler_id) == self.database.as_ref()
+    }
+
+    /// The accounts selector keeping the handlers the workers write. The accounts are still selected for the
+    /// other handlers, so the selector statistics of each database count the same accounts
+    pub fn route_accounts_selector(&self, selector: Option<AccountsSelectorConfig>) -> Option<AccountsSelectorConfig> {
+        if self.handler_databases.is_empty() {
+            return selector;
+        }
+        selector.map(|mut selector| {
+            for entries in [&mut selector.accounts, &mut selector.owners].into_iter().flatten() {
+                for handlers in entries.values_mut() {
+                    handlers.retain(|handler| self.writes_handler(&handler.handler_id));
+                }
+            }
+            selector
+        })
+    }
+
+    /// The transaction selector keeping the handlers the workers write
+    pub fn route_transaction_selector(&self, selector: Option<TransactionSelectorConfig>) -> Option<TransactionSelectorConfig> {
+        if self.handler_databases.is_empty() {
+            return selector;
+        }
+        match selector {
+            // a plain list of mentions stores the raw transaction
+            Some(TransactionSelectorConfig {
+                mentions: TransactionMentionsConfig::Addresses(_),
+            }) if !self.writes_handler(TransactionHandlerId::Transaction.as_str()) => None,
+            Some(TransactionSelectorConfig {
+                mentions: TransactionMentionsConfig::Handlers(mut mentions),
+            }) => {
+                for handlers in mentions.values_mut() {
+                    handlers.retain(|handler| self.writes_handler(&handler.handler_id));
+                }
+                Some(TransactionSelectorConfig {
+                    mentions: TransactionMentionsConfig::Handlers(mentions),
+                })
+            }
+            selector => selector,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts_selector::AccountHandlerConfig;
+    use crate::postgres_client::accounts::account_handler::all_account_handlers;
+    use crate::postgres_client::CustomHandlers;
+    use crate::postgres_client::PostgresClientBuilder;
+    use crate::transaction_selector::TransactionHandlerConfig;
+    use std::sync::Arc;
+
+    fn handlers(handler_ids: &[&str]) -> Vec<AccountHandlerConfig> {
+        handler_ids
+            .iter()
+            .map(|handler_id| AccountHandlerConfig {
+                handler_id: handler_id.to_string(),
+                skip_on_startup: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_route_selectors() {
+        let database = DatabaseConfig {
+            name: "tokens".to_string(),
+            connection_str: "host=tokens".to_string(),
+            handlers: vec!["token_account".to_string(), "token_transfer".to_string()],
+            ..DatabaseConfig::default()
+        };
+        let config = GeyserPluginPostgresConfig {
+            databases: vec![database.clone()],
+            ..GeyserPluginPostgresConfig::default()
+        };
+        let token_program = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string();
+        let accounts_selector = AccountsSelectorConfig {
+            accounts: None,
+            owners: Some(HashMap::from([(token_program.clone(), handlers(&["token_account", "unknown_account"]))])),
+        };
+        let transaction_selector = TransactionSelectorConfig {
+            mentions: TransactionMentionsConfig::Handlers(HashMap::from([(
+                token_program.clone(),
+                vec![
+                    TransactionHandlerConfig {
+                        handler_id: "transaction".to_string(),
+                    },
+                    TransactionHandlerConfig {
+                        handler_id: "token_transfer".to_string(),
+                    },
+                ],
+            )])),
+        };
+
+        let main = DatabaseRouter::new(&config).route_accounts_selector(Some(accounts_selector.clone())).unwrap();
+        assert_eq!(main.owners.unwrap()[&token_program], handlers(&["unknown_account"]));
+        let worker_config = database.worker_config(&config);
+        assert_eq!(worker_config.connection_str, "host=tokens");
+        assert_eq!(worker_config.worker_thread_name_prefix, "worker-tokens");
+        let router = DatabaseRouter::new(&worker_config);
+        let routed = router.route_accounts_selector(Some(accounts_selector)).unwrap();
+        assert_eq!(routed.owners.unwrap()[&token_program], handlers(&["token_account"]));
+
+        let routed = router.route_transaction_selector(Some(transaction_selector)).unwrap();
+        match routed.mentions {
+            TransactionMentionsConfig::Handlers(mentions) => assert_eq!(mentions[&token_program][0].handler_id, "token_transfer"),
+            mentions => panic!("unexpected mentions {:?}", mentions),
+        }
+        let addresses = TransactionSelectorConfig {
+            mentions: TransactionMentionsConfig::Addresses(vec!["*".to_string()]),
+        };
+        assert!(DatabaseRouter::new(&config).route_transaction_selector(Some(addresses.clone())).is_some());
+        assert!(router.route_transaction_selector(Some(addresses)).is_none());
+    }
+
+    #[test]
+    fn test_database_prefixes() {
+        let database = |name: &str, table_prefix: &str, handler_id: &str| DatabaseConfig {
+            name: name.to_string(),
+            connection_str: "host=localhost".to_string(),
+            handlers: vec![handler_id.to_string()],
+            table_prefix: Some(table_prefix.to_string()),
+            ..DatabaseConfig::default()
+        };
+        let config = GeyserPluginPostgresConfig {
+            table_prefix: "main_".to_string(),
+            databases: vec![database("tokens", "tokens_", "token_account"), database("transactions", "transactions_", "transaction")],
+            ..GeyserPluginPostgresConfig::default()
+        };
+        assert!(config.databases.iter().all(|database| database.validate().is_ok()));
+
+        // the clients of both databases are built in the same process, each names the tables of its own prefix
+        let (tokens, transactions) = (config.databases[0].worker_config(&config), config.databases[1].worker_config(&config));
+        let tokens_init = PostgresClientBuilder::init_query(&tokens, &CustomHandlers::default()).unwrap();
+        let transactions_init = PostgresClientBuilder::init_query(&transactions, &CustomHandlers::default()).unwrap();
+        assert!(tokens_init.contains("CREATE TABLE IF NOT EXISTS tokens_slot ("));
+        assert!(!tokens_init.contains("transactions_slot") && !tokens_init.contains("main_slot"));
+        assert!(transactions_init.contains("CREATE TABLE IF NOT EXISTS transactions_transaction ("));
+        assert!(!transactions_init.contains("tokens_slot") && !transactions_init.contains("main_slot"));
+        let handlers = all_account_handlers(&tokens, Arc::default());
+        assert_eq!(handlers[&AccountHandlerId::TokenAccount].staging_table().unwrap().table, "tokens_spl_token_account");
+        let handlers = all_account_handlers(&transactions, Arc::default());
+        assert_eq!(handlers[&AccountHandlerId::TokenAccount].staging_table().unwrap().table, "transactions_spl_token_account");
+
+        // a database without a prefix of its own keeps the prefix of the main database
+        let inherited = DatabaseConfig {
+            table_prefix: None,
+            ..config.databases[0].clone()
+        };
+        assert_eq!(inherited.worker_config(&config).table_prefix, "main_");
+        assert!(database("tokens", "Tokens", "token_account").validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_databases() {
+        let database = |name: &str, handlers: &[&str]| DatabaseConfig {
+            name: name.to_string(),
+            connection_str: "host=localhost".to_string(),
+            handlers: handlers.iter().map(|handler| handler.to_string()).collect(),
+            ..DatabaseConfig::default()
+        };
+        assert!(validate_databases(&[database("accounts", &["token_account"]), database("transactions", &["transaction"])], &[], &[]).is_ok());
+        assert!(validate_databases(&[database("accounts", &["token_account"]), database("accounts", &["transaction"])], &[], &[]).is_err());
+        assert!(validate_databases(&[database("accounts", &["token_account"]), database("other", &["token_account"])], &[], &[]).is_err());
+        assert!(validate_databases(&[database("accounts", &["my_pool"])], &[], &[]).is_err());
+        assert!(validate_databases(&[database("accounts", &["my_pool"])], &["my_pool".to_string()], &[]).is_ok());
+    }
+}
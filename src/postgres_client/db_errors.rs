@@ -0,0 +1,274 @@
+use crate::metrics::registry;
+use crate::metrics::BYTES_WRITTEN_TOTAL;
+use crate::metrics::DB_ERRORS_TOTAL;
+use crate::metrics::DB_RETRIES_TOTAL;
+use crate::postgres_client::fault_injection;
+use log::*;
+use postgres::Client;
+use postgres::Transaction;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use std::thread::sleep;
+use std::time::Duration;
+
+/// What a failed statement ran into, from its SQLSTATE
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DbErrorClass {
+    /// Class 08, or the connection was closed
+    Connection,
+    /// 40001 and 40P01, the statement can be retried
+    SerializationFailure,
+    /// 53100
+    DiskFull,
+    /// Class 53 other than a full disk, e.g. out of memory or too many connections
+    InsufficientResources,
+    /// 57014 and other operator interventions such as a server shutdown
+    OperatorIntervention,
+    /// Class 23, e.g. unique or not null violations
+    ConstraintViolation,
+    /// Class 22, e.g. out of range or malformed values
+    DataException,
+    /// Class 42, e.g. a missing table or column
+    Schema,
+    Other,
+}
+
+impl DbErrorClass {
+    pub fn from_code(code: &str) -> Self {
+        match code {
+            "40001" | "40P01" => Self::SerializationFailure,
+            "53100" => Self::DiskFull,
+            _ => match code.get(..2) {
+                Some("08") => Self::Connection,
+                Some("53") => Self::InsufficientResources,
+                Some("57") => Self::OperatorIntervention,
+                Some("23") => Self::ConstraintViolation,
+                Some("22") => Self::DataException,
+                Some("42") => Self::Schema,
+                _ => Self::Other,
+            },
+        }
+    }
+
+    pub fn from_error(err: &postgres::Error) -> Self {
+        match err.code() {
+            Some(code) => Self::from_code(code.code()),
+            // errors without a SQLSTATE come from the client side of the connection
+            None => Self::Connection,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Connection => "connection",
+            Self::SerializationFailure => "serialization_failure",
+            Self::DiskFull => "disk_full",
+            Self::InsufficientResources => "insufficient_resources",
+            Self::OperatorIntervention => "operator_intervention",
+            Self::ConstraintViolation => "constraint_violation",
+            Self::DataException => "data_exception",
+            Self::Schema => "schema",
+            Self::Other => "other",
+        }
+    }
+
+    /// Whether retrying the same statement later can succeed, as opposed to a problem with the statement or schema
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::Connection | Self::SerializationFailure | Self::DiskFull | Self::InsufficientResources | Self::OperatorIntervention
+        )
+    }
+}
+
+/// Count a failed statement by operation and error class
+pub fn record_db_error(operation: &'static str, err: &postgres::Error) -> DbErrorClass {
+    let class = DbErrorClass::from_error(err);
+    let kind = if class.is_transient() { "transient" } else { "structural" };
+    registry().inc_counter(DB_ERRORS_TOTAL, &[("operation", operation), ("class", class.as_str()), ("kind", kind)], 1);
+    class
+}
+
+/// * The `serialization_retry` section retries the batches that failed on a deadlock or a serialization failure.
+/// "serialization_retry" : { "max_retries" : 5, "base_delay_ms" : 10, "max_delay_ms" : 1000 }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SerializationRetryConfig {
+    /// Retries of a batch before it fails, 0 to never retry
+    pub max_retries: u32,
+    /// Upper bound of the delay before the first retry, doubled on every retry
+    pub base_delay_ms: u64,
+    /// Upper bound of the delay before any retry
+    pub max_delay_ms: u64,
+}
+
+impl Default for SerializationRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay_ms: 10,
+            max_delay_ms: 1000,
+        }
+    }
+}
+
+impl SerializationRetryConfig {
+    /// The delay before retry `attempt`, between half and all of the exponential bound depending
+    /// on `jitter` in [0, 1), so the workers that deadlocked each other do not retry in lockstep
+    pub fn backoff(&self, attempt: u32, jitter: f64) -> Duration {
+        backoff(self.base_delay_ms, self.max_delay_ms, attempt, jitter)
+    }
+}
+
+/// * The `reconnect` section reconnects the workers whose database connection was lost.
+/// "reconnect" : { "max_retries" : 10, "base_delay_ms" : 100, "max_delay_ms" : 30000 }
+/// A worker retries connecting with an exponential backoff, and aborts the validator once `max_retries` attempts failed.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectConfig {
+    /// Attempts to reconnect before aborting, 0 to never reconnect
+    pub max_retries: u32,
+    /// Upper bound of the delay before the first attempt, doubled on every attempt
+    pub base_delay_ms: u64,
+    /// Upper bound of the delay before any attempt
+    pub max_delay_ms: u64,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 10,
+            base_delay_ms: 100,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+impl ReconnectConfig {
+    /// The delay before attempt `attempt`, jittered so the workers that lost their connections together don't reconnect in lockstep
+    pub fn backoff(&self, attempt: u32, jitter: f64) -> Duration {
+        backoff(self.base_delay_ms, self.max_delay_ms, attempt, jitter)
+    }
+}
+
+/// Between half and all of the exponential bound of `attempt`, depending on `jitter` in [0, 1)
+fn backoff(base_delay_ms: u64, max_delay_ms: u64, attempt: u32, jitter: f64) -> Duration {
+    let bound = base_delay_ms.saturating_mul(1u64.checked_shl(attempt).unwrap_or(u64::MAX)).min(max_delay_ms);
+    Duration::from_millis(bound / 2 + ((bound - bound / 2) as f64 * jitter) as u64)
+}
+
+/// Run the statements of `query` with `batch_execute`. The statements of a batch run in a single
+/// implicit transaction, so a batch that failed on a deadlock or a serialization failure left
+/// nothing behind and is run again after a backoff. Other errors, and the last failure once the
+/// retries are exhausted, are counted in the db errors.
+pub fn execute_batch(client: &mut Client, operation: &'static str, query: &str, retry: &SerializationRetryConfig) -> Result<(), postgres::Error> {
+    let mut attempt = 0;
+    loop {
+        let query = fault_injection::inject(operation, query);
+        registry().inc_counter(BYTES_WRITTEN_TOTAL, &[], query.len() as u64);
+        let err = match client.batch_execute(&query) {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+        if attempt >= retry.max_retries || DbErrorClass::from_error(&err) != DbErrorClass::SerializationFailure {
+            record_db_error(operation, &err);
+            return Err(err);
+        }
+        let delay = retry.backoff(attempt, rand::random::<f64>());
+        debug!("[{}] retrying in {}ms after {}", operation, delay.as_millis(), err);
+        registry().inc_counter(DB_RETRIES_TOTAL, &[("operation", operation)], 1);
+        sleep(delay);
+        attempt += 1;
+    }
+}
+
+/// Run the statements of `query` in a savepoint of `transaction`, a failure rolls the savepoint back and
+/// leaves the transaction usable. Serialization failures are counted and retried with the whole
+/// transaction, see `execute_transaction`.
+pub fn execute_savepoint(transaction: &mut Transaction, operation: &'static str, query: &str) -> Result<(), postgres::Error> {
+    let query = fault_injection::inject(operation, query);
+    registry().inc_counter(BYTES_WRITTEN_TOTAL, &[], query.len() as u64);
+    let result = transaction.transaction().and_then(|mut savepoint| {
+        savepoint.batch_execute(&query)?;
+        savepoint.commit()
+    });
+    if let Err(err) = &result {
+        record_db_error(operation, err);
+    }
+    result
+}
+
+/// Run `run` in a transaction and commit it, the transaction is run again after a backoff when it failed on a
+/// deadlock or a serialization failure, as `execute_batch` does for a statement batch
+pub fn execute_transaction<T>(
+    client: &mut Client,
+    operation: &'static str,
+    retry: &SerializationRetryConfig,
+    mut run: impl FnMut(&mut Transaction) -> Result<T, postgres::Error>,
+) -> Result<T, postgres::Error> {
+    retry_transaction(operation, retry, || {
+        client.transaction().and_then(|mut transaction| {
+            let value = run(&mut transaction)?;
+            transaction.commit()?;
+            Ok(value)
+        })
+    })
+}
+
+/// Run `run`, which commits a transaction of its own, again after a backoff while it fails on a deadlock or a
+/// serialization failure
+pub fn retry_transaction<T>(operation: &'static str, retry: &SerializationRetryConfig, mut run: impl FnMut() -> Result<T, postgres::Error>) -> Result<T, postgres::Error> {
+    let mut attempt = 0;
+    loop {
+        let err = match run() {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+        if attempt >= retry.max_retries || DbErrorClass::from_error(&err) != DbErrorClass::SerializationFailure {
+            return Err(err);
+        }
+        let delay = retry.backoff(attempt, rand::random::<f64>());
+        debug!("[{}] retrying the transaction in {}ms after {}", operation, delay.as_millis(), err);
+        registry().inc_counter(DB_RETRIES_TOTAL, &[("operation", operation)], 1);
+        sleep(delay);
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_sqlstate() {
+        assert_eq!(DbErrorClass::from_code("23505"), DbErrorClass::ConstraintViolation);
+        assert_eq!(DbErrorClass::from_code("40001"), DbErrorClass::SerializationFailure);
+        assert_eq!(DbErrorClass::from_code("40P01"), DbErrorClass::SerializationFailure);
+        assert_eq!(DbErrorClass::from_code("53100"), DbErrorClass::DiskFull);
+        assert_eq!(DbErrorClass::from_code("53200"), DbErrorClass::InsufficientResources);
+        assert_eq!(DbErrorClass::from_code("08006"), DbErrorClass::Connection);
+        assert_eq!(DbErrorClass::from_code("42P01"), DbErrorClass::Schema);
+        assert_eq!(DbErrorClass::from_code("XX000"), DbErrorClass::Other);
+        assert!(DbErrorClass::DiskFull.is_transient());
+        assert!(!DbErrorClass::ConstraintViolation.is_transient());
+    }
+
+    #[test]
+    fn test_serialization_retry_backoff() {
+        let retry = SerializationRetryConfig::default();
+        assert_eq!(retry.backoff(0, 0.0), Duration::from_millis(5));
+        assert_eq!(retry.backoff(0, 0.99), Duration::from_millis(9));
+        assert_eq!(retry.backoff(3, 0.0), Duration::from_millis(40));
+        assert_eq!(retry.backoff(3, 0.5), Duration::from_millis(60));
+        assert_eq!(retry.backoff(10, 0.0), Duration::from_millis(500));
+        assert_eq!(retry.backoff(64, 0.99), Duration::from_millis(995));
+    }
+
+    #[test]
+    fn test_reconnect_backoff() {
+        let reconnect = ReconnectConfig::default();
+        assert_eq!(reconnect.backoff(0, 0.0), Duration::from_millis(50));
+        assert_eq!(reconnect.backoff(4, 0.5), Duration::from_millis(1200));
+        assert_eq!(reconnect.backoff(20, 0.0), Duration::from_millis(15_000));
+    }
+}
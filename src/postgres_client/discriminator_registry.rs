@@ -0,0 +1,100 @@
+use super::accounts::idl_registry::IdlRegistry;
+use super::DbAccountInfo;
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How often a worker writes the discriminators it observed
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+struct Observation {
+    count: i64,
+    first_seen_slot: i64,
+    last_seen_slot: i64,
+}
+
+/// Counts the (owner, discriminator) combinations of the accounts a worker
+/// handles and periodically adds them to the `discriminator_registry` table
+pub struct DiscriminatorRegistry {
+    observations: HashMap<(Vec<u8>, [u8; 8]), Observation>,
+    last_flush: Instant,
+}
+
+impl Default for DiscriminatorRegistry {
+    fn default() -> Self {
+        Self {
+            observations: HashMap::default(),
+            last_flush: Instant::now(),
+        }
+    }
+}
+
+impl DiscriminatorRegistry {
+    pub fn init(config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        if !config.discriminator_registry {
+            return "".to_string();
+        }
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {}discriminator_registry (
+                owner VARCHAR(44) NOT NULL,
+                discriminator BYTEA NOT NULL,
+                account_name VARCHAR(64),
+                count BIGINT NOT NULL,
+                first_seen_slot BIGINT NOT NULL,
+                last_seen_slot BIGINT NOT NULL,
+                PRIMARY KEY (owner, discriminator)
+            );
+        ",
+            TablePrefix::new(config)
+        );
+    }
+
+    pub fn record(&mut self, account: &DbAccountInfo) {
+        let discriminator: [u8; 8] = match account.data.get(..8).and_then(|d| d.try_into().ok()) {
+            Some(discriminator) => discriminator,
+            None => return,
+        };
+        let observation = self.observations.entry((account.owner.to_vec(), discriminator)).or_insert(Observation {
+            count: 0,
+            first_seen_slot: account.slot,
+            last_seen_slot: account.slot,
+        });
+        observation.count += 1;
+        observation.first_seen_slot = observation.first_seen_slot.min(account.slot);
+        observation.last_seen_slot = observation.last_seen_slot.max(account.slot);
+    }
+
+    /// The upserts for the observations since the last flush, once due or when forced
+    pub fn flush_query(&mut self, prefix: &TablePrefix, force: bool, idl_registry: &IdlRegistry) -> String {
+        if self.observations.is_empty() || (!force && self.last_flush.elapsed() < FLUSH_INTERVAL) {
+            return "".to_string();
+        }
+        self.last_flush = Instant::now();
+        self.observations
+            .drain()
+            .map(|((owner, discriminator), observation)| {
+                let account_name = idl_registry.get(&owner).and_then(|program| program.decoder.account_name(&discriminator).map(str::to_string));
+                format!(
+                    "
+                    INSERT INTO {6}discriminator_registry AS r (owner, discriminator, account_name, count, first_seen_slot, last_seen_slot) \
+                    VALUES ('{0}', '\\x{1}', {2}, {3}, {4}, {5}) \
+                    ON CONFLICT (owner, discriminator) DO UPDATE SET account_name=COALESCE(excluded.account_name, r.account_name), \
+                    count=r.count + excluded.count, first_seen_slot=LEAST(r.first_seen_slot, excluded.first_seen_slot), \
+                    last_seen_slot=GREATEST(r.last_seen_slot, excluded.last_seen_slot);
+                ",
+                    bs58::encode(&owner).into_string(),
+                    hex::encode(discriminator),
+                    account_name.sql_literal(),
+                    observation.count,
+                    observation.first_seen_slot,
+                    observation.last_seen_slot,
+                    prefix,
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("")
+    }
+}
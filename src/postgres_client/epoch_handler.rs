@@ -0,0 +1,270 @@
+use crate::config::GeyserPluginPostgresConfig;
+use crate::postgres_client::periodic_writer::execute;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::SimplePostgresClient;
+use chrono::NaiveDateTime;
+use chrono::Utc;
+use crossbeam_channel::unbounded;
+use crossbeam_channel::Sender;
+use log::*;
+use postgres::Client;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use solana_sdk::epoch_schedule::EpochSchedule;
+use solana_sdk::epoch_schedule::MINIMUM_SLOTS_PER_EPOCH;
+use std::thread::Builder;
+use std::thread::JoinHandle;
+
+/// * The `epochs` section writes the boundaries of every epoch a slot is rooted in to the `epoch` table.
+/// "epochs" : { "slots_per_epoch" : 432000, "warmup" : false, "leader_schedule" : { "rpc_url" : "http://127.0.0.1:8899" } }
+/// The epoch schedule must match the cluster's, the default is the one of mainnet-beta.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EpochsConfig {
+    pub slots_per_epoch: u64,
+    /// Whether the first epochs are shorter, as on clusters created with warmup
+    pub warmup: bool,
+    /// Fetch the leader schedule of every new epoch into the `leader_schedule` table, requires the `leader-schedule` feature
+    pub leader_schedule: Option<LeaderScheduleConfig>,
+}
+
+impl Default for EpochsConfig {
+    fn default() -> Self {
+        Self {
+            slots_per_epoch: 432000,
+            warmup: false,
+            leader_schedule: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeaderScheduleConfig {
+    /// The RPC endpoint the schedule is fetched from
+    pub rpc_url: String,
+}
+
+impl Default for LeaderScheduleConfig {
+    fn default() -> Self {
+        Self {
+            rpc_url: "http://127.0.0.1:8899".to_string(),
+        }
+    }
+}
+
+impl EpochsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.slots_per_epoch < MINIMUM_SLOTS_PER_EPOCH {
+            return Err(format!("epochs.slots_per_epoch must be at least {}", MINIMUM_SLOTS_PER_EPOCH));
+        }
+        if let Some(leader_schedule) = &self.leader_schedule {
+            if cfg!(not(feature = "leader-schedule")) {
+                return Err("\"epochs.leader_schedule\" requires the plugin to be built with the \"leader-schedule\" feature".to_string());
+            }
+            if leader_schedule.rpc_url.is_empty() {
+                return Err("epochs.leader_schedule.rpc_url must be specified".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn epoch_schedule(&self) -> EpochSchedule {
+        EpochSchedule::custom(self.slots_per_epoch, self.slots_per_epoch, self.warmup)
+    }
+}
+
+pub struct EpochHandler {}
+
+impl EpochHandler {
+    pub fn init(config: &GeyserPluginPostgresConfig) -> String {
+        let epochs = match &config.epochs {
+            Some(epochs) => epochs,
+            None => return "".to_string(),
+        };
+        let prefix = TablePrefix::new(config);
+        let mut query = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {}epoch (
+                epoch BIGINT PRIMARY KEY,
+                first_slot BIGINT NOT NULL,
+                last_slot BIGINT NOT NULL,
+                first_rooted_slot BIGINT NOT NULL,
+                first_rooted_on TIMESTAMP NOT NULL
+            );
+        ",
+            prefix
+        );
+        if epochs.leader_schedule.is_some() {
+            query.push_str(&format!(
+                "
+                CREATE TABLE IF NOT EXISTS {0}leader_schedule (
+                    slot BIGINT PRIMARY KEY,
+                    epoch BIGINT NOT NULL,
+                    leader VARCHAR(44) NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS {0}leader_schedule_leader ON {0}leader_schedule (leader, slot);
+            ",
+                prefix
+            ));
+        }
+        query
+    }
+
+    /// The row of `epoch`, recording the first slot rooted in it. An epoch already recorded keeps its row
+    pub fn update(prefix: &TablePrefix, schedule: &EpochSchedule, epoch: u64, rooted_slot: u64, rooted_on: &NaiveDateTime) -> String {
+        format!(
+            "
+                INSERT INTO {5}epoch (epoch, first_slot, last_slot, first_rooted_slot, first_rooted_on) \
+                VALUES ({0}, {1}, {2}, {3}, '{4}') \
+                ON CONFLICT (epoch) DO NOTHING;
+            ",
+            epoch,
+            schedule.get_first_slot_in_epoch(epoch),
+            schedule.get_last_slot_in_epoch(epoch),
+            rooted_slot,
+            rooted_on,
+            prefix
+        )
+    }
+}
+
+/// Fetch the leader schedule of `epoch` and replace its rows of `leader_schedule`
+#[cfg(feature = "leader-schedule")]
+fn write_leader_schedule(client: &mut Client, prefix: &TablePrefix, config: &LeaderScheduleConfig, schedule: &EpochSchedule, epoch: u64) -> Result<usize, String> {
+    use solana_client::rpc_client::RpcClient;
+    use solana_sdk::commitment_config::CommitmentConfig;
+
+    let first_slot = schedule.get_first_slot_in_epoch(epoch);
+    let rpc_client = RpcClient::new(config.rpc_url.clone());
+    let leaders = rpc_client
+        .get_leader_schedule_with_commitment(Some(first_slot), CommitmentConfig::finalized())
+        .map_err(|e| format!("getLeaderSchedule failed: {}", e))?
+        .ok_or_else(|| format!("the RPC node has no leader schedule for epoch {}", epoch))?;
+    let (mut slots, mut pubkeys) = (Vec::new(), Vec::new());
+    for (leader, slot_indexes) in leaders {
+        for slot_index in slot_indexes {
+            slots.push((first_slot + slot_index as u64) as i64);
+            pubkeys.push(leader.clone());
+        }
+    }
+    client
+        .execute(
+            &format!(
+                "INSERT INTO {}leader_schedule (slot, epoch, leader) SELECT UNNEST($1::BIGINT[]), $2, UNNEST($3::VARCHAR[]) \
+                ON CONFLICT (slot) DO UPDATE SET epoch=excluded.epoch, leader=excluded.leader;",
+                prefix
+            ),
+            &[&slots, &(epoch as i64), &pubkeys],
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(slots.len())
+}
+
+#[cfg(not(feature = "leader-schedule"))]
+fn write_leader_schedule(_client: &mut Client, _prefix: &TablePrefix, _config: &LeaderScheduleConfig, _schedule: &EpochSchedule, _epoch: u64) -> Result<usize, String> {
+    Err("the plugin was built without the \"leader-schedule\" feature".to_string())
+}
+
+/// Records the epochs of the rooted slots from a dedicated thread on its own connection, so
+/// fetching a leader schedule at the start of an epoch doesn't hold up the workers
+pub struct EpochTracker {
+    schedule: EpochSchedule,
+    last_epoch: Option<u64>,
+    sender: Option<Sender<(u64, u64, NaiveDateTime)>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EpochTracker {
+    /// Opens the connection before the thread starts, the first epoch is written once a slot is rooted, too late to
+    /// fail the load on an unreachable database
+    pub fn start(plugin_config: &GeyserPluginPostgresConfig) -> Result<Option<Self>, GeyserPluginError> {
+        let config = match &plugin_config.epochs {
+            Some(config) => config.clone(),
+            None => return Ok(None),
+        };
+        let schedule = config.epoch_schedule();
+        let mut client = Some(SimplePostgresClient::connect_to_db(plugin_config)?);
+        let prefix = TablePrefix::new(plugin_config);
+        let plugin_config = plugin_config.clone();
+        let (sender, receiver) = unbounded::<(u64, u64, NaiveDateTime)>();
+        let thread = Builder::new()
+            .name("epochs".to_string())
+            .spawn(move || {
+                // ends once the tracker drops the sender
+                for (epoch, rooted_slot, rooted_on) in receiver {
+                    info!("[epochs] epoch={} first_rooted_slot={}", epoch, rooted_slot);
+                    if let Err(err) = execute(&mut client, &plugin_config, "update_epoch", &EpochHandler::update(&prefix, &schedule, epoch, rooted_slot, &rooted_on)) {
+                        error!("[epochs] {}", err);
+                        continue;
+                    }
+                    if let (Some(leader_schedule), Some(client)) = (&config.leader_schedule, &mut client) {
+                        match write_leader_schedule(client, &prefix, leader_schedule, &schedule, epoch) {
+                            Ok(slots) => info!("[epochs] epoch={} leader_schedule_slots={}", epoch, slots),
+                            Err(err) => error!("[epochs] failed to write the leader schedule of epoch {}: {}", epoch, err),
+                        }
+                    }
+                }
+            })
+            .unwrap();
+        Ok(Some(Self {
+            schedule,
+            last_epoch: None,
+            sender: Some(sender),
+            thread: Some(thread),
+        }))
+    }
+
+    /// Record the epoch of a rooted slot the first time a slot of it is rooted
+    pub fn record_rooted_slot(&mut self, slot: u64) {
+        let epoch = self.schedule.get_epoch(slot);
+        if self.last_epoch.map_or(false, |last_epoch| last_epoch >= epoch) {
+            return;
+        }
+        self.last_epoch = Some(epoch);
+        if let Some(sender) = &self.sender {
+            let _ = sender.send((epoch, slot, Utc::now().naive_utc()));
+        }
+    }
+
+    /// Write the epochs already recorded and stop the thread
+    pub fn stop(&mut self) {
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for EpochTracker {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_epoch_update() {
+        let schedule = EpochsConfig::default().epoch_schedule();
+        assert_eq!(schedule.get_epoch(180_000_000), 416);
+        let rooted_on = NaiveDateTime::from_timestamp_opt(1_680_000_000, 0).unwrap();
+        let query = EpochHandler::update(&TablePrefix::default(), &schedule, 416, 180_000_000, &rooted_on);
+        assert!(query.contains("VALUES (416, 179712000, 180143999, 180000000, '2023-03-28 10:40:00')"), "{}", query);
+
+        assert!(EpochsConfig {
+            slots_per_epoch: 8,
+            ..EpochsConfig::default()
+        }
+        .validate()
+        .is_err());
+        let leader_schedule = EpochsConfig {
+            leader_schedule: Some(LeaderScheduleConfig::default()),
+            ..EpochsConfig::default()
+        };
+        assert_eq!(leader_schedule.validate().is_ok(), cfg!(feature = "leader-schedule"));
+    }
+}
@@ -0,0 +1,209 @@
+use super::accounts::account_handler::AccountHandler;
+#[cfg(feature = "scripting")]
+use super::accounts::script_account_handler::ScriptAccountHandler;
+#[cfg(feature = "wasm")]
+use super::accounts::wasm_account_handler::WasmAccountHandler;
+use super::bound_rows::BoundRow;
+use super::bound_rows::BoundValues;
+use super::bulk_load::StagingTable;
+#[cfg(any(feature = "wasm", feature = "scripting"))]
+use super::table_prefix::TablePrefix;
+use super::transactions::transaction_router::CustomTransactionHandler;
+use super::DbAccountInfo;
+use super::DbTransaction;
+use crate::config::GeyserPluginPostgresConfig;
+use libloading::Library;
+use libloading::Symbol;
+use log::*;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The symbol external handler libraries export to register their handlers
+pub const REGISTER_HANDLERS_SYMBOL: &[u8] = b"_register_handlers";
+
+/// Signature of the registration function exported as `_register_handlers`
+///
+/// # Safety
+///
+/// Handlers are passed as Rust trait objects, the library must be compiled with the same
+/// Rust compiler version and version of this crate as the plugin.
+#[allow(improper_ctypes_definitions)]
+pub type RegisterHandlers = unsafe extern "C" fn(registrar: &mut HandlerRegistrar);
+
+/// * The `external_handlers` section loads additional handlers from dynamic libraries.
+/// "external_handlers" : \[{
+///     "libpath" : "/solana/libmy_handlers.so",
+///     "account_handlers" : \["my_pool"\],
+///     "transaction_handlers" : \["my_events"\]
+/// }\]
+/// The declared handler ids can be used in the selectors like the built in ones.
+#[derive(Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExternalHandlerConfig {
+    pub libpath: String,
+    /// Ids of the account handlers the library registers
+    pub account_handlers: Vec<String>,
+    /// Ids of the transaction handlers the library registers
+    pub transaction_handlers: Vec<String>,
+}
+
+/// Collects the handlers registered by a library
+#[derive(Default)]
+pub struct HandlerRegistrar {
+    account_handlers: Vec<(String, Box<dyn AccountHandler>)>,
+    transaction_handlers: Vec<(String, Box<dyn CustomTransactionHandler>)>,
+}
+
+impl HandlerRegistrar {
+    pub fn register_account_handler(&mut self, handler_id: &str, handler: Box<dyn AccountHandler>) {
+        self.account_handlers.push((handler_id.to_string(), handler));
+    }
+
+    pub fn register_transaction_handler(&mut self, handler_id: &str, handler: Box<dyn CustomTransactionHandler>) {
+        self.transaction_handlers.push((handler_id.to_string(), handler));
+    }
+}
+
+/// Handlers registered in code by crates embedding the plugin, shared by every worker
+#[derive(Clone, Default)]
+pub struct CustomHandlers {
+    pub account_handlers: HashMap<String, Arc<dyn AccountHandler + Send + Sync>>,
+    pub transaction_handlers: HashMap<String, Arc<dyn CustomTransactionHandler + Send + Sync>>,
+}
+
+impl CustomHandlers {
+    pub fn account_handler_ids(&self) -> Vec<String> {
+        self.account_handlers.keys().cloned().collect()
+    }
+
+    pub fn transaction_handler_ids(&self) -> Vec<String> {
+        self.transaction_handlers.keys().cloned().collect()
+    }
+}
+
+impl<T: AccountHandler + ?Sized> AccountHandler for Arc<T> {
+    fn enabled(&self, config: &GeyserPluginPostgresConfig) -> bool {
+        self.as_ref().enabled(config)
+    }
+
+    fn init(&self, config: &GeyserPluginPostgresConfig) -> String {
+        self.as_ref().init(config)
+    }
+
+    fn schema_migrations(&self, config: &GeyserPluginPostgresConfig) -> Vec<String> {
+        self.as_ref().schema_migrations(config)
+    }
+
+    fn account_match(&self, account: &DbAccountInfo) -> bool {
+        self.as_ref().account_match(account)
+    }
+
+    fn account_update(&self, account: &DbAccountInfo) -> String {
+        self.as_ref().account_update(account)
+    }
+
+    fn staging_table(&self) -> Option<StagingTable> {
+        self.as_ref().staging_table()
+    }
+
+    fn staging_row(&self, account: &DbAccountInfo) -> Option<String> {
+        self.as_ref().staging_row(account)
+    }
+
+    fn staging_values(&self, account: &DbAccountInfo) -> Option<BoundValues> {
+        self.as_ref().staging_values(account)
+    }
+
+    fn prepared_statements(&self) -> Vec<String> {
+        self.as_ref().prepared_statements()
+    }
+
+    fn account_rows(&self, account: &DbAccountInfo) -> Vec<BoundRow> {
+        self.as_ref().account_rows(account)
+    }
+}
+
+impl<T: CustomTransactionHandler + ?Sized> CustomTransactionHandler for Arc<T> {
+    fn init(&self, config: &GeyserPluginPostgresConfig) -> String {
+        self.as_ref().init(config)
+    }
+
+    fn transaction_update(&self, transaction: &DbTransaction) -> String {
+        self.as_ref().transaction_update(transaction)
+    }
+}
+
+/// Handlers registered in code, loaded from the configured libraries, WebAssembly modules and scripts. The libraries must outlive the handlers.
+#[derive(Default)]
+pub struct ExternalHandlers {
+    pub account_handlers: HashMap<String, Box<dyn AccountHandler>>,
+    pub transaction_handlers: HashMap<String, Box<dyn CustomTransactionHandler>>,
+    pub libraries: Vec<Arc<Library>>,
+}
+
+impl ExternalHandlers {
+    pub fn load(config: &GeyserPluginPostgresConfig, custom_handlers: &CustomHandlers) -> Result<Self, GeyserPluginError> {
+        let mut external_handlers = Self::default();
+        for (handler_id, handler) in &custom_handlers.account_handlers {
+            external_handlers.account_handlers.insert(handler_id.clone(), Box::new(handler.clone()));
+        }
+        for (handler_id, handler) in &custom_handlers.transaction_handlers {
+            external_handlers.transaction_handlers.insert(handler_id.clone(), Box::new(handler.clone()));
+        }
+        for external_config in &config.external_handlers {
+            let load_err = |msg: String| GeyserPluginError::ConfigFileReadError {
+                msg: format!("Failed to load external handlers from {}: {}", external_config.libpath, msg),
+            };
+            let mut registrar = HandlerRegistrar::default();
+            let library = unsafe {
+                let library = Library::new(&external_config.libpath).map_err(|e| load_err(e.to_string()))?;
+                let register: Symbol<RegisterHandlers> = library.get(REGISTER_HANDLERS_SYMBOL).map_err(|e| load_err(e.to_string()))?;
+                register(&mut registrar);
+                library
+            };
+            // keep the library loaded before any of its handlers can be dropped
+            external_handlers.libraries.push(Arc::new(library));
+            for (handler_id, handler) in registrar.account_handlers {
+                external_handlers.account_handlers.insert(handler_id, handler);
+            }
+            for (handler_id, handler) in registrar.transaction_handlers {
+                external_handlers.transaction_handlers.insert(handler_id, handler);
+            }
+            if let Some(missing) = external_config.account_handlers.iter().find(|id| !external_handlers.account_handlers.contains_key(*id)) {
+                return Err(load_err(format!("account handler \"{}\" was not registered", missing)));
+            }
+            if let Some(missing) = external_config.transaction_handlers.iter().find(|id| !external_handlers.transaction_handlers.contains_key(*id)) {
+                return Err(load_err(format!("transaction handler \"{}\" was not registered", missing)));
+            }
+            info!("[external_handlers] loaded libpath=[{}]", external_config.libpath);
+        }
+        #[cfg(feature = "wasm")]
+        for wasm_config in &config.wasm_handlers {
+            let handler = WasmAccountHandler::new(wasm_config, config.store_decoded_accounts, config.conflict_strategy(&wasm_config.handler_id), TablePrefix::new(config)).map_err(|msg| {
+                GeyserPluginError::ConfigFileReadError {
+                    msg: format!("Failed to load wasm handler {}: {}", wasm_config.handler_id, msg),
+                }
+            })?;
+            info!("[external_handlers] loaded wasm handler=[{}] path=[{}]", wasm_config.handler_id, wasm_config.path);
+            external_handlers.account_handlers.insert(wasm_config.handler_id.clone(), Box::new(handler));
+        }
+        #[cfg(feature = "scripting")]
+        for script_config in &config.script_handlers {
+            let handler = ScriptAccountHandler::new(
+                script_config,
+                config.store_decoded_accounts,
+                config.conflict_strategy(&script_config.handler_id),
+                TablePrefix::new(config),
+            )
+            .map_err(|msg| GeyserPluginError::ConfigFileReadError {
+                msg: format!("Failed to load script handler {}: {}", script_config.handler_id, msg),
+            })?;
+            info!("[external_handlers] loaded script handler=[{}] path=[{}]", script_config.handler_id, script_config.path);
+            external_handlers.account_handlers.insert(script_config.handler_id.clone(), Box::new(handler));
+        }
+        Ok(external_handlers)
+    }
+}
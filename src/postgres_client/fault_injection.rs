@@ -0,0 +1,183 @@
+//! Faults injected into the batches of the workers, to exercise the serialization retries,
+//! `panic_on_db_errors` and the worker watchdog against a real database in tests.
+//!
+//! Requires the `fault-injection` feature. A fault is a statement run ahead of the batch, in
+//! the same implicit transaction, so the batch fails the way it would in production and
+//! nothing it wrote is kept. Every worker draws the faults of its batches from its own
+//! generator seeded with `seed`, so a single threaded run injects the same faults on every
+//! run of a test.
+use crate::metrics::registry;
+use crate::metrics::FAULTS_INJECTED_TOTAL;
+use lazy_static::lazy_static;
+use log::*;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::RwLock;
+
+lazy_static! {
+    /// The injected faults and the generation of the config, bumped on every load
+    static ref FAULT_INJECTION: RwLock<Option<(u64, FaultInjectionConfig)>> = RwLock::new(None);
+}
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// The generator of the worker, reseeded when the plugin is loaded again
+    static RNG: RefCell<Option<(u64, StdRng)>> = RefCell::new(None);
+}
+
+/// * The `fault_injection` section injects faults in the batches of the workers, requires the `fault-injection` feature.
+/// "fault_injection" : { "seed" : 7, "operations" : ["flush_live_updates"], "serialization_failure_one_in" : 3 }
+/// A fault is drawn for one in this many batches, 0 never injects it.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FaultInjectionConfig {
+    /// Seed of the generators of the workers
+    pub seed: u64,
+    /// The operations whose batches get faults, e.g. `flush_live_updates`, empty for all of them
+    pub operations: Vec<String>,
+    /// Terminate the worker's connection, as a server restart would
+    pub connection_drop_one_in: u64,
+    /// Fail the batch with a deadlock-like serialization failure, which is retried
+    pub serialization_failure_one_in: u64,
+    /// Fail the batch with a unique violation, which is not retried
+    pub constraint_violation_one_in: u64,
+    /// Sleep in the database for `slow_statement_ms` before the batch
+    pub slow_statement_one_in: u64,
+    pub slow_statement_ms: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    ConnectionDrop,
+    SerializationFailure,
+    ConstraintViolation,
+    SlowStatement,
+}
+
+impl Fault {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ConnectionDrop => "connection_drop",
+            Self::SerializationFailure => "serialization_failure",
+            Self::ConstraintViolation => "constraint_violation",
+            Self::SlowStatement => "slow_statement",
+        }
+    }
+
+    /// The statement causing the fault
+    pub fn statement(&self, config: &FaultInjectionConfig) -> String {
+        match self {
+            Self::ConnectionDrop => "SELECT pg_terminate_backend(pg_backend_pid());".to_string(),
+            Self::SerializationFailure => "DO $$ BEGIN RAISE EXCEPTION 'injected fault' USING ERRCODE = 'serialization_failure'; END $$;".to_string(),
+            Self::ConstraintViolation => "DO $$ BEGIN RAISE EXCEPTION 'injected fault' USING ERRCODE = 'unique_violation'; END $$;".to_string(),
+            Self::SlowStatement => format!("SELECT pg_sleep({});", config.slow_statement_ms as f64 / 1000.0),
+        }
+    }
+}
+
+impl FaultInjectionConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if cfg!(not(feature = "fault-injection")) {
+            return Err("\"fault_injection\" requires the plugin to be built with the \"fault-injection\" feature".to_string());
+        }
+        if self.slow_statement_one_in != 0 && self.slow_statement_ms == 0 {
+            return Err("fault_injection.slow_statement_ms must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// The fault to inject in the next batch of `operation`, if any
+    pub fn draw<R: Rng>(&self, operation: &str, rng: &mut R) -> Option<Fault> {
+        if !self.operations.is_empty() && !self.operations.iter().any(|o| o == operation) {
+            return None;
+        }
+        [
+            (Fault::ConnectionDrop, self.connection_drop_one_in),
+            (Fault::SerializationFailure, self.serialization_failure_one_in),
+            (Fault::ConstraintViolation, self.constraint_violation_one_in),
+            (Fault::SlowStatement, self.slow_statement_one_in),
+        ]
+        .into_iter()
+        .find(|(_, one_in)| *one_in != 0 && rng.gen_range(0..*one_in) == 0)
+        .map(|(fault, _)| fault)
+    }
+}
+
+/// Start injecting the configured faults, or stop when there are none
+pub fn init(config: &Option<FaultInjectionConfig>) {
+    if cfg!(not(feature = "fault-injection")) {
+        return;
+    }
+    if let Some(config) = config {
+        warn!("[fault_injection] injecting faults {:?}", config);
+    }
+    let generation = GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+    *FAULT_INJECTION.write().unwrap() = config.clone().map(|config| (generation, config));
+}
+
+/// `query` preceded by the statement of the fault drawn for this batch of `operation`, if any
+pub fn inject<'a>(operation: &'static str, query: &'a str) -> Cow<'a, str> {
+    if cfg!(not(feature = "fault-injection")) {
+        return Cow::Borrowed(query);
+    }
+    let fault_injection = FAULT_INJECTION.read().unwrap();
+    let (generation, config) = match &*fault_injection {
+        Some((generation, config)) => (*generation, config),
+        None => return Cow::Borrowed(query),
+    };
+    let fault = RNG.with(|rng| {
+        let mut rng = rng.borrow_mut();
+        if !matches!(&*rng, Some((rng_generation, _)) if *rng_generation == generation) {
+            *rng = Some((generation, StdRng::seed_from_u64(config.seed)));
+        }
+        config.draw(operation, &mut rng.as_mut().unwrap().1)
+    });
+    match fault {
+        Some(fault) => {
+            debug!("[fault_injection] {} in {}", fault.as_str(), operation);
+            registry().inc_counter(FAULTS_INJECTED_TOTAL, &[("operation", operation), ("fault", fault.as_str())], 1);
+            Cow::Owned(format!("{}{}", fault.statement(config), query))
+        }
+        None => Cow::Borrowed(query),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_faults() {
+        let config = FaultInjectionConfig {
+            seed: 7,
+            operations: vec!["flush_live_updates".to_string()],
+            serialization_failure_one_in: 3,
+            slow_statement_one_in: 5,
+            slow_statement_ms: 100,
+            ..FaultInjectionConfig::default()
+        };
+        let draws = |seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..100).map(|_| config.draw("flush_live_updates", &mut rng)).collect::<Vec<Option<Fault>>>()
+        };
+        // the same seed injects the same faults
+        let faults = draws(config.seed);
+        assert_eq!(faults, draws(config.seed));
+        assert!(faults.contains(&Some(Fault::SerializationFailure)));
+        assert!(faults.contains(&Some(Fault::SlowStatement)));
+        assert!(faults.contains(&None));
+        assert!(!faults.contains(&Some(Fault::ConnectionDrop)));
+        assert!(!faults.contains(&Some(Fault::ConstraintViolation)));
+
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        assert!((0..100).all(|_| config.draw("log_transaction", &mut rng).is_none()));
+        assert_eq!(Fault::SlowStatement.statement(&config), "SELECT pg_sleep(0.1);");
+    }
+}
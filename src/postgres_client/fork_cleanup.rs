@@ -0,0 +1,133 @@
+use crate::config::GeyserPluginPostgresConfig;
+use crate::postgres_client::is_identifier;
+use crate::postgres_client::table_prefix::TablePrefix;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+/// * The `fork_cleanup` section marks the slots of the forks abandoned once a competing slot is rooted.
+/// "fork_cleanup" : { "purge_tables" : \["account", "spl_token_account", "token_manager"\], "lookback_slots" : 256 }
+/// A slot below the rooted slot that is neither rooted nor one of its ancestors gets the `abandoned` status, and the rows
+/// written at the abandoned slots of the last `lookback_slots` are deleted from `purge_tables`, none by default as deleting
+/// the latest row of an account loses its state until its next update.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ForkCleanupConfig {
+    /// The tables with a `slot` column the rows of abandoned slots are deleted from, empty to only mark the slots
+    pub purge_tables: Vec<String>,
+    /// The slots before a rooted slot whose rows are deleted if they were abandoned, so the rows a worker flushed
+    /// once their slot was already abandoned are deleted by a later root
+    pub lookback_slots: u64,
+}
+
+impl Default for ForkCleanupConfig {
+    fn default() -> Self {
+        Self {
+            purge_tables: vec![],
+            lookback_slots: 256,
+        }
+    }
+}
+
+impl ForkCleanupConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(table) = self.purge_tables.iter().find(|table| !is_identifier(table) || *table == "slot") {
+            return Err(format!("fork_cleanup.purge_tables \"{}\" is not a table the rows of abandoned slots can be deleted from", table));
+        }
+        if self.lookback_slots == 0 {
+            return Err("fork_cleanup \"lookback_slots\" must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+pub struct ForkCleanupHandler {}
+
+impl ForkCleanupHandler {
+    /// Runs after the handlers created their tables, the purged tables that exist are indexed on their slot
+    pub fn init(config: &GeyserPluginPostgresConfig) -> String {
+        let fork_cleanup = match &config.fork_cleanup {
+            Some(fork_cleanup) => fork_cleanup,
+            None => return "".to_string(),
+        };
+        let prefix = TablePrefix::new(config);
+        let mut query = format!(
+            "
+            CREATE INDEX IF NOT EXISTS {0}slot_unrooted ON {0}slot (slot) WHERE status NOT IN ('rooted', 'abandoned');
+            CREATE OR REPLACE FUNCTION {0}abandon_forks(rooted BIGINT, purge_tables TEXT[], lookback_slots BIGINT) RETURNS VOID AS $$
+            DECLARE
+                abandoned BIGINT[];
+                purge_table TEXT;
+            BEGIN
+                WITH RECURSIVE ancestors AS (
+                    SELECT s.slot, s.parent FROM {0}slot s WHERE s.slot = rooted
+                    UNION ALL
+                    SELECT s.slot, s.parent FROM {0}slot s JOIN ancestors a ON s.slot = a.parent WHERE s.status NOT IN ('rooted', 'abandoned')
+                )
+                UPDATE {0}slot u SET status = 'abandoned', updated_on = NOW() AT TIME ZONE 'UTC'
+                WHERE u.slot < rooted AND u.status NOT IN ('rooted', 'abandoned') AND u.slot NOT IN (SELECT a.slot FROM ancestors a);
+                SELECT array_agg(s.slot) INTO abandoned FROM {0}slot s WHERE s.slot < rooted AND s.slot >= rooted - lookback_slots AND s.status = 'abandoned';
+                IF abandoned IS NULL THEN
+                    RETURN;
+                END IF;
+                FOREACH purge_table IN ARRAY purge_tables LOOP
+                    IF to_regclass(purge_table) IS NOT NULL THEN
+                        EXECUTE format('DELETE FROM %I WHERE slot = ANY($1)', purge_table) USING abandoned;
+                    END IF;
+                END LOOP;
+            END;
+            $$ LANGUAGE plpgsql;
+        ",
+            prefix
+        );
+        for table in &fork_cleanup.purge_tables {
+            query.push_str(&format!(
+                "DO $$ BEGIN IF to_regclass('{0}') IS NOT NULL THEN CREATE INDEX IF NOT EXISTS {0}_slot ON {0} (slot); END IF; END $$;\n",
+                prefix.table(table)
+            ));
+        }
+        query
+    }
+
+    /// Marks the slots abandoned by the rooted slot and deletes the rows written at them, in the batch updating the slot's status
+    pub fn rooted(prefix: &TablePrefix, slot: u64, fork_cleanup: &ForkCleanupConfig) -> String {
+        format!(
+            "SELECT {}abandon_forks({}, ARRAY[{}]::TEXT[], {});",
+            prefix,
+            slot,
+            fork_cleanup.purge_tables.iter().map(|table| format!("'{}'", prefix.table(table))).collect::<Vec<String>>().join(", "),
+            fork_cleanup.lookback_slots
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fork_cleanup() {
+        let fork_cleanup = ForkCleanupConfig {
+            purge_tables: vec!["account".to_string(), "spl_token_account".to_string(), "token_manager".to_string()],
+            ..ForkCleanupConfig::default()
+        };
+        assert!(fork_cleanup.validate().is_ok());
+        assert_eq!(
+            ForkCleanupHandler::rooted(&TablePrefix::default(), 42, &fork_cleanup),
+            "SELECT abandon_forks(42, ARRAY['account', 'spl_token_account', 'token_manager']::TEXT[], 256);"
+        );
+        // the default only marks the slots
+        assert_eq!(
+            ForkCleanupHandler::rooted(&TablePrefix::default(), 42, &ForkCleanupConfig::default()),
+            "SELECT abandon_forks(42, ARRAY[]::TEXT[], 256);"
+        );
+
+        for purge_tables in [vec!["slot".to_string()], vec!["account; DROP TABLE block".to_string()]] {
+            assert!(ForkCleanupConfig {
+                purge_tables,
+                ..ForkCleanupConfig::default()
+            }
+            .validate()
+            .is_err());
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use crate::config::GeyserPluginPostgresConfig;
+use crate::metrics::registry;
+use crate::metrics::HANDLER_ERRORS_TOTAL;
+use crate::postgres_client::db_errors::execute_batch;
+use crate::postgres_client::db_errors::execute_savepoint;
+use crate::postgres_client::db_errors::DbErrorClass;
+use crate::postgres_client::db_errors::SerializationRetryConfig;
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::DbAccountInfo;
+use crate::postgres_client::DbTransaction;
+use chrono::Utc;
+use log::*;
+use postgres::Client;
+use postgres::Transaction;
+use serde_json::json;
+
+/// The statements of a flush grouped by the handler that wrote them, in the order the handlers first
+/// appeared. Statements that aren't written by an account handler, such as the `write_batch` row, are
+/// grouped under their table.
+#[derive(Debug, Default)]
+pub struct HandlerBatch {
+    groups: Vec<(String, String)>,
+    /// The statements of each group
+    counts: Vec<usize>,
+    /// The updates pushed, as counted against the batch size
+    updates: usize,
+    bytes: usize,
+}
+
+impl HandlerBatch {
+    /// Add the statements of one update, by handler id
+    pub fn push(&mut self, queries: impl IntoIterator<Item = (String, String)>) {
+        for (handler_id, query) in queries {
+            self.bytes += query.len();
+            match self.groups.iter().position(|(id, _)| *id == handler_id) {
+                Some(index) => {
+                    self.groups[index].1.push_str(&query);
+                    self.counts[index] += 1;
+                }
+                None => {
+                    self.groups.push((handler_id, query));
+                    self.counts.push(1);
+                }
+            }
+        }
+        self.updates += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.updates
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn query(&self) -> String {
+        self.groups.iter().map(|(_, statements)| statements.as_str()).collect()
+    }
+
+    /// The statements written by each handler
+    pub fn handler_counts(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.groups.iter().zip(&self.counts).map(|((handler_id, _), count)| (handler_id.as_str(), *count))
+    }
+
+    /// Empty the batch, keeping the allocation of its groups
+    pub fn clear(&mut self) {
+        self.groups.clear();
+        self.counts.clear();
+        self.updates = 0;
+        self.bytes = 0;
+    }
+}
+
+/// The statements of a handler that failed on their own
+#[derive(Debug)]
+pub struct HandlerFailure {
+    pub handler_id: String,
+    pub statements: String,
+    pub error: postgres::Error,
+}
+
+/// Run the batch in a savepoint of the transaction of a flush, and if it fails on a problem with its statements
+/// rather than the connection, run the statements of each handler in a savepoint of their own. A failed savepoint is
+/// rolled back without aborting the transaction, so only the handlers at fault lose their writes. The failures are
+/// counted in the handler errors and returned to be recorded. Transient errors still fail the whole flush.
+pub fn execute_isolated_in(transaction: &mut Transaction, operation: &'static str, batch: &HandlerBatch) -> Result<Vec<HandlerFailure>, postgres::Error> {
+    isolate(operation, batch, |query| execute_savepoint(transaction, operation, query))
+}
+
+/// Run the batch with `execute`, and the statements of each handler on their own when it failed, see `execute_isolated_in`
+pub fn isolate(operation: &'static str, batch: &HandlerBatch, mut execute: impl FnMut(&str) -> Result<(), postgres::Error>) -> Result<Vec<HandlerFailure>, postgres::Error> {
+    if batch.groups.is_empty() {
+        return Ok(Vec::new());
+    }
+    let err = match execute(&batch.query()) {
+        Ok(()) => return Ok(Vec::new()),
+        Err(err) => err,
+    };
+    if batch.groups.len() <= 1 || DbErrorClass::from_error(&err).is_transient() {
+        return Err(err);
+    }
+    warn!("[{}] isolating the statements of {} handlers after error=[{}]", operation, batch.groups.len(), err);
+    let mut failures = Vec::new();
+    for (handler_id, statements) in &batch.groups {
+        if let Err(error) = execute(statements) {
+            if DbErrorClass::from_error(&error).is_transient() {
+                return Err(error);
+            }
+            error!("[{}] handler=[{}] error=[{}]", operation, handler_id, error);
+            registry().inc_counter(HANDLER_ERRORS_TOTAL, &[("handler", handler_id)], 1);
+            failures.push(HandlerFailure {
+                handler_id: handler_id.clone(),
+                statements: statements.clone(),
+                error,
+            });
+        }
+    }
+    Ok(failures)
+}
+
+/// The events written by each handler of a flush, as the JSON payloads recorded in the dead letters of the
+/// handlers that fail so their events can be replayed
+#[derive(Debug, Default)]
+pub struct DeadLetterPayloads {
+    payloads: Vec<String>,
+    /// The indexes of the payloads written by each handler
+    handlers: Vec<(String, Vec<usize>)>,
+}
+
+impl DeadLetterPayloads {
+    /// Add the payload of an event written by the handlers
+    pub fn push<'a>(&mut self, payload: String, handler_ids: impl IntoIterator<Item = &'a str>) {
+        let index = self.payloads.len();
+        self.payloads.push(payload);
+        for handler_id in handler_ids {
+            match self.handlers.iter_mut().find(|(id, _)| id == handler_id) {
+                Some((_, indexes)) => indexes.push(index),
+                None => self.handlers.push((handler_id.to_string(), vec![index])),
+            }
+        }
+    }
+
+    /// The payloads of the events written by a handler, as a JSON array
+    pub fn payload(&self, handler_id: &str) -> Option<String> {
+        let (_, indexes) = self.handlers.iter().find(|(id, _)| id == handler_id)?;
+        Some(format!("[{}]", indexes.iter().map(|index| self.payloads[*index].as_str()).collect::<Vec<_>>().join(",")))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.payloads.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.payloads.clear();
+        self.handlers.clear();
+    }
+}
+
+pub struct DeadLetterHandler {}
+
+impl DeadLetterHandler {
+    pub fn init(config: &GeyserPluginPostgresConfig) -> String {
+        if !config.dead_letters {
+            return "".to_string();
+        }
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}dead_letter (
+                id BIGSERIAL PRIMARY KEY,
+                handler VARCHAR(64) NOT NULL,
+                operation VARCHAR(64) NOT NULL,
+                sqlstate VARCHAR(5),
+                error TEXT NOT NULL,
+                statements TEXT NOT NULL,
+                batch_id BIGINT,
+                created_on TIMESTAMP NOT NULL,
+                payload JSONB
+            );
+            ALTER TABLE {prefix}dead_letter ADD COLUMN IF NOT EXISTS payload JSONB;
+            CREATE INDEX IF NOT EXISTS {prefix}dead_letter_handler ON {prefix}dead_letter (handler, created_on);
+        ",
+            prefix = TablePrefix::new(config)
+        );
+    }
+
+    /// The state of an account as notified, everything needed to notify it again
+    pub fn account_payload(account: &DbAccountInfo) -> String {
+        json!({
+            "kind": "account",
+            "pubkey": bs58::encode(&account.pubkey).into_string(),
+            "owner": bs58::encode(&account.owner).into_string(),
+            "lamports": account.lamports,
+            "executable": account.executable,
+            "rent_epoch": account.rent_epoch,
+            "data": base64::encode(&account.data),
+            "slot": account.slot,
+            "write_version": account.write_version,
+            "txn_signature": account.txn_signature.as_ref().map(|signature| bs58::encode(signature).into_string()),
+        })
+        .to_string()
+    }
+
+    /// The transaction as written, the signature encoded as it is looked up and the other bytes as arrays
+    pub fn transaction_payload(transaction: &DbTransaction) -> String {
+        let mut payload = serde_json::to_value(transaction).unwrap_or_default();
+        if let Some(fields) = payload.as_object_mut() {
+            fields.insert("kind".to_string(), json!("transaction"));
+            fields.insert("signature".to_string(), json!(bs58::encode(&transaction.signature).into_string()));
+        }
+        payload.to_string()
+    }
+
+    pub fn insert(prefix: &TablePrefix, operation: &str, failure: &HandlerFailure, batch_id: Option<u64>, payload: Option<String>) -> String {
+        format!(
+            "
+                INSERT INTO {8}dead_letter (handler, operation, sqlstate, error, statements, batch_id, created_on, payload) \
+                VALUES ({0}, '{1}', {2}, {3}, {4}, {5}, '{6}', {7});
+            ",
+            failure.handler_id.sql_literal(),
+            operation,
+            failure.error.code().map(|code| code.code().to_string()).sql_literal(),
+            failure.error.to_string().sql_literal(),
+            failure.statements.sql_literal(),
+            batch_id.map_or("NULL".to_string(), |id| id.to_string()),
+            &Utc::now().naive_utc(),
+            payload.sql_literal(),
+            prefix,
+        )
+    }
+
+    /// Record the failures in the `dead_letter` table, with the payloads of the events their handler wrote, on their own
+    /// so a dead letter can't fail with the statements it records
+    pub fn record(
+        client: &mut Client,
+        prefix: &TablePrefix,
+        operation: &'static str,
+        failures: &[HandlerFailure],
+        batch_id: Option<u64>,
+        payloads: &DeadLetterPayloads,
+        retry: &SerializationRetryConfig,
+    ) {
+        if failures.is_empty() {
+            return;
+        }
+        let query = failures
+            .iter()
+            .map(|failure| Self::insert(prefix, operation, failure, batch_id, payloads.payload(&failure.handler_id)))
+            .collect::<String>();
+        if let Err(err) = execute_batch(client, "dead_letter", &query, retry) {
+            error!("[{}] failed to record {} dead letters error=[{}]", operation, failures.len(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::AccountKey;
+
+    #[test]
+    fn test_handler_batch() {
+        let mut batch = HandlerBatch::default();
+        batch.push(vec![("token_account".to_string(), "A;".to_string()), ("unknown_account".to_string(), "B;".to_string())]);
+        batch.push(vec![("unknown_account".to_string(), "C;".to_string())]);
+        batch.push(vec![("token_account".to_string(), "D;".to_string())]);
+        assert_eq!(batch.len(), 3);
+        assert_eq!(batch.bytes(), 8);
+        assert_eq!(batch.query(), "A;D;B;C;");
+        assert_eq!(batch.groups[1], ("unknown_account".to_string(), "B;C;".to_string()));
+        assert_eq!(batch.handler_counts().collect::<Vec<_>>(), vec![("token_account", 2), ("unknown_account", 2)]);
+        batch.clear();
+        assert!(batch.is_empty());
+        assert_eq!(batch.len(), 0);
+    }
+
+    #[test]
+    fn test_dead_letter_payloads() {
+        let mut payloads = DeadLetterPayloads::default();
+        payloads.push("{\"slot\":1}".to_string(), ["token_account", "unknown_account"]);
+        payloads.push("{\"slot\":2}".to_string(), ["unknown_account"]);
+        assert_eq!(payloads.payload("token_account").unwrap(), "[{\"slot\":1}]");
+        assert_eq!(payloads.payload("unknown_account").unwrap(), "[{\"slot\":1},{\"slot\":2}]");
+        assert_eq!(payloads.payload("write_batch"), None);
+
+        let account = DbAccountInfo {
+            pubkey: AccountKey::from_slice(&[1; 32]),
+            lamports: 5,
+            owner: AccountKey::from_slice(&[0; 32]),
+            executable: false,
+            rent_epoch: 0,
+            data: vec![1, 2, 3],
+            slot: 7,
+            write_version: 3,
+            txn_signature: None,
+        };
+        let payload: serde_json::Value = serde_json::from_str(&DeadLetterHandler::account_payload(&account)).unwrap();
+        assert_eq!(payload["kind"], "account");
+        assert_eq!(payload["owner"], "11111111111111111111111111111111");
+        assert_eq!(payload["data"], "AQID");
+        assert_eq!(payload["slot"], 7);
+        assert!(payload["txn_signature"].is_null());
+        payloads.clear();
+        assert!(payloads.is_empty());
+    }
+}
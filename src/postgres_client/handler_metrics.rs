@@ -0,0 +1,61 @@
+use crate::metrics::registry;
+use crate::metrics::HANDLER_FLUSH_US;
+use crate::metrics::HANDLER_QUEUE_DEPTH;
+use crate::metrics::HANDLER_ROWS_TOTAL;
+use crate::postgres_client::handler_batch::HandlerFailure;
+use std::collections::BTreeMap;
+
+/// The statements and rows each handler queued in a flush, counted while the batch is being written
+/// and moved to the registry once it is, so the registry lock is taken once per handler and flush
+/// rather than once per account
+#[derive(Debug, Default)]
+pub struct HandlerFlush {
+    counts: BTreeMap<String, usize>,
+}
+
+impl HandlerFlush {
+    /// Add the statements or rows of a batch, by handler id
+    pub fn add<'a>(&mut self, counts: impl IntoIterator<Item = (&'a str, usize)>) {
+        for (handler_id, count) in counts {
+            match self.counts.get_mut(handler_id) {
+                Some(total) => *total += count,
+                None => {
+                    self.counts.insert(handler_id.to_string(), count);
+                }
+            }
+        }
+    }
+
+    /// Record the flush of the handlers: the depth their queue reached, the latency of the flush and the rows
+    /// written, but those of the handlers that failed, which are counted in the handler errors instead
+    pub fn record(self, failures: &[HandlerFailure], flush_us: u64) {
+        for (handler_id, count) in self.counts {
+            let labels = [("handler", handler_id.as_str())];
+            registry().set_gauge(HANDLER_QUEUE_DEPTH, &labels, count as i64);
+            registry().observe_us(HANDLER_FLUSH_US, &labels, flush_us);
+            if !failures.iter().any(|failure| failure.handler_id == handler_id) {
+                registry().inc_counter(HANDLER_ROWS_TOTAL, &labels, count as u64);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::handler_batch::HandlerBatch;
+
+    #[test]
+    fn test_handler_flush() {
+        let mut batch = HandlerBatch::default();
+        batch.push(vec![("test_flush_a".to_string(), "A;".to_string()), ("test_flush_b".to_string(), "B;".to_string())]);
+        batch.push(vec![("test_flush_a".to_string(), "C;".to_string())]);
+        let mut flush = HandlerFlush::default();
+        flush.add(batch.handler_counts());
+        flush.add([("test_flush_a", 3)]);
+        flush.record(&[], 40);
+        assert_eq!(registry().counter(HANDLER_ROWS_TOTAL, &[("handler", "test_flush_a")]), 5);
+        assert_eq!(registry().counter(HANDLER_ROWS_TOTAL, &[("handler", "test_flush_b")]), 1);
+        assert_eq!(registry().gauge(HANDLER_QUEUE_DEPTH, &[("handler", "test_flush_a")]), Some(5));
+    }
+}
@@ -0,0 +1,166 @@
+use crate::config::GeyserPluginPostgresConfig;
+use crate::metrics::registry;
+use crate::metrics::INDEXING_LAG_SLOTS;
+use crate::metrics::LAST_PROCESSED_SLOT_GAUGE;
+use crate::metrics::TIP_SLOT_GAUGE;
+use crate::postgres_client::periodic_writer::PeriodicWriter;
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use chrono::NaiveDateTime;
+use chrono::Utc;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+/// The highest slot whose data the workers wrote to the database
+static LAST_PROCESSED_SLOT: AtomicU64 = AtomicU64::new(0);
+/// The highest slot the validator reported a status for
+static TIP_SLOT: AtomicU64 = AtomicU64::new(0);
+
+/// Record that the data of `slot` was written
+pub fn record_processed_slot(slot: u64) {
+    LAST_PROCESSED_SLOT.fetch_max(slot, Ordering::Relaxed);
+    report_lag();
+}
+
+pub fn last_processed_slot() -> u64 {
+    LAST_PROCESSED_SLOT.load(Ordering::Relaxed)
+}
+
+/// Record a slot seen in a slot status update
+pub fn record_tip_slot(slot: u64) {
+    TIP_SLOT.fetch_max(slot, Ordering::Relaxed);
+    report_lag();
+}
+
+pub fn tip_slot() -> u64 {
+    TIP_SLOT.load(Ordering::Relaxed)
+}
+
+/// How many slots the data written to the database trails the validator tip
+pub fn indexing_lag(tip_slot: u64, last_processed_slot: u64) -> u64 {
+    tip_slot.saturating_sub(last_processed_slot)
+}
+
+fn report_lag() {
+    let (tip_slot, last_processed_slot) = (tip_slot(), last_processed_slot());
+    registry().set_gauge(TIP_SLOT_GAUGE, &[], tip_slot as i64);
+    registry().set_gauge(LAST_PROCESSED_SLOT_GAUGE, &[], last_processed_slot as i64);
+    registry().set_gauge(INDEXING_LAG_SLOTS, &[], indexing_lag(tip_slot, last_processed_slot) as i64);
+}
+
+/// * The `heartbeat` section writes a liveness row to `plugin_heartbeat` from a dedicated thread.
+/// "heartbeat" : { "instance" : "validator-1", "interval_secs" : 10, "record_lag" : true }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeartbeatConfig {
+    /// Identifies this plugin when several validators write to the same database
+    pub instance: String,
+    /// Seconds between heartbeats
+    pub interval_secs: u64,
+    /// Also write the validator tip and the indexing lag behind it
+    pub record_lag: bool,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            instance: "default".to_string(),
+            interval_secs: 10,
+            record_lag: false,
+        }
+    }
+}
+
+pub struct HeartbeatHandler {}
+
+impl HeartbeatHandler {
+    pub fn init(config: &GeyserPluginPostgresConfig) -> String {
+        if config.heartbeat.is_none() {
+            return "".to_string();
+        }
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {}plugin_heartbeat (
+                instance VARCHAR(64) PRIMARY KEY,
+                last_processed_slot BIGINT NOT NULL,
+                tip_slot BIGINT,
+                lag_slots BIGINT,
+                version VARCHAR(32) NOT NULL,
+                started_on TIMESTAMP NOT NULL,
+                updated_on TIMESTAMP NOT NULL
+            );
+        ",
+            TablePrefix::new(config)
+        );
+    }
+
+    pub fn update(prefix: &TablePrefix, config: &HeartbeatConfig, last_processed_slot: u64, tip_slot: u64, started_on: &NaiveDateTime) -> String {
+        let (tip_slot, lag_slots) = match config.record_lag {
+            true => (tip_slot.to_string(), indexing_lag(tip_slot, last_processed_slot).to_string()),
+            false => ("NULL".to_string(), "NULL".to_string()),
+        };
+        format!(
+            "
+                INSERT INTO {7}plugin_heartbeat (instance, last_processed_slot, tip_slot, lag_slots, version, started_on, updated_on) \
+                VALUES ({0}, {1}, {2}, {3}, '{4}', '{5}', '{6}') \
+                ON CONFLICT (instance) DO UPDATE SET last_processed_slot=excluded.last_processed_slot, tip_slot=excluded.tip_slot, \
+                lag_slots=excluded.lag_slots, version=excluded.version, started_on=excluded.started_on, updated_on=excluded.updated_on;
+            ",
+            config.instance.sql_literal(),
+            last_processed_slot,
+            tip_slot,
+            lag_slots,
+            env!("CARGO_PKG_VERSION"),
+            started_on,
+            &Utc::now().naive_utc(),
+            prefix
+        )
+    }
+}
+
+/// Writes the heartbeat row every `interval_secs` on its own connection, so the row keeps
+/// its `updated_on` current while the plugin is loaded even if the workers are wedged.
+/// A `last_processed_slot` that stops advancing while `updated_on` does points at the workers.
+pub struct Heartbeat {
+    writer: PeriodicWriter,
+}
+
+impl Heartbeat {
+    pub fn start(plugin_config: &GeyserPluginPostgresConfig) -> Result<Option<Self>, GeyserPluginError> {
+        let config = match &plugin_config.heartbeat {
+            Some(config) => config.clone(),
+            None => return Ok(None),
+        };
+        let interval = Duration::from_secs(config.interval_secs.max(1));
+        let started_on = Utc::now().naive_utc();
+        let prefix = TablePrefix::new(plugin_config);
+        let writer = PeriodicWriter::start("heartbeat", plugin_config, interval, move || {
+            HeartbeatHandler::update(&prefix, &config, last_processed_slot(), tip_slot(), &started_on)
+        })?;
+        Ok(Some(Self { writer }))
+    }
+
+    pub fn stop(&mut self) {
+        self.writer.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_update() {
+        let started_on = Utc::now().naive_utc();
+        let mut config = HeartbeatConfig::default();
+        assert_eq!(indexing_lag(110, 100), 10);
+        assert_eq!(indexing_lag(100, 110), 0);
+        assert!(HeartbeatHandler::update(&TablePrefix::default(), &config, 100, 110, &started_on).contains("VALUES ('default', 100, NULL, NULL"));
+        config.record_lag = true;
+        assert!(HeartbeatHandler::update(&TablePrefix::default(), &config, 100, 110, &started_on).contains("VALUES ('default', 100, 110, 10"));
+    }
+}
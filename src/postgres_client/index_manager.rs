@@ -0,0 +1,100 @@
+use crate::postgres_client::is_identifier;
+use crate::postgres_client::table_prefix::TablePrefix;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use std::collections::HashMap;
+
+/// * The `indexes` section allows the user to drop or add secondary indexes per table.
+/// "indexes" : {
+///     "account" : { "drop" : \["account_slot"\] },
+///     "spl_token_account" : { "create" : \[{ "columns" : \["mint", "owner"\] }\] }
+/// }
+/// Dropped indexes are no longer created by the handlers and are removed from existing tables.
+#[derive(Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TableIndexConfig {
+    /// Names of the indexes to drop
+    pub drop: Vec<String>,
+    /// Additional indexes to create
+    pub create: Vec<IndexConfig>,
+}
+
+#[derive(Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct IndexConfig {
+    /// The index name, defaults to the table name and columns joined by '_'
+    pub name: Option<String>,
+    pub columns: Vec<String>,
+    #[serde(default)]
+    pub unique: bool,
+    /// The index method e.g. 'btree', 'hash', 'gin'. The default is 'btree'
+    pub method: Option<String>,
+}
+
+impl IndexConfig {
+    pub fn name(&self, table: &str) -> String {
+        self.name.clone().unwrap_or_else(|| format!("{}_{}", table, self.columns.join("_")))
+    }
+}
+
+/// Check table, index and column names so they can be safely interpolated into DDL
+pub fn validate_index_config(indexes: &HashMap<String, TableIndexConfig>) -> Result<(), String> {
+    for (table, table_config) in indexes {
+        if !is_identifier(table) {
+            return Err(format!("indexes table \"{}\" is not a valid identifier", table));
+        }
+        for name in &table_config.drop {
+            if !is_identifier(name) {
+                return Err(format!("indexes.{}.drop \"{}\" is not a valid identifier", table, name));
+            }
+        }
+        for index in &table_config.create {
+            if index.columns.is_empty() {
+                return Err(format!("indexes.{}.create entries must list at least one column", table));
+            }
+            let mut names = index.columns.iter().chain(index.name.iter()).chain(index.method.iter());
+            if let Some(name) = names.find(|name| !is_identifier(name)) {
+                return Err(format!("indexes.{}.create \"{}\" is not a valid identifier", table, name));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Remove the creation of dropped indexes from the handler init query and
+/// append the configured drops and creates. The configured names are given the table prefix
+pub fn apply_index_config(init_query: &str, indexes: &HashMap<String, TableIndexConfig>, prefix: &TablePrefix) -> String {
+    if indexes.is_empty() {
+        return init_query.to_string();
+    }
+    let dropped = indexes.values().flat_map(|t| t.drop.iter()).map(|name| prefix.table(name)).collect::<Vec<String>>();
+    let mut query = init_query
+        .lines()
+        .filter(|line| {
+            let line = line.trim_start();
+            let index_name = line
+                .strip_prefix("CREATE INDEX IF NOT EXISTS ")
+                .or_else(|| line.strip_prefix("CREATE UNIQUE INDEX IF NOT EXISTS "))
+                .and_then(|rest| rest.split_whitespace().next());
+            !matches!(index_name, Some(name) if dropped.iter().any(|d| *d == name))
+        })
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    for (table, table_config) in indexes {
+        for name in &table_config.drop {
+            query.push_str(&format!("\nDROP INDEX IF EXISTS {}{};", prefix, name));
+        }
+        for index in &table_config.create {
+            query.push_str(&format!(
+                "\nCREATE {}INDEX IF NOT EXISTS {} ON {} USING {} ({});",
+                if index.unique { "UNIQUE " } else { "" },
+                prefix.table(&index.name(table)),
+                prefix.table(table),
+                index.method.as_deref().unwrap_or("btree"),
+                index.columns.join(", ")
+            ));
+        }
+    }
+    query.push('\n');
+    query
+}
@@ -0,0 +1,148 @@
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+/// * The `logical_replication` section keeps every table of the plugin decodable by logical replication.
+/// "logical_replication" : { "replica_identity" : "default" }
+/// Tables without a primary key are given one, their unique index if they have one and an `id` column otherwise,
+/// and the replica identity of every table is set.
+#[derive(Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogicalReplicationConfig {
+    pub replica_identity: ReplicaIdentity,
+}
+
+/// The old values of a row logical decoding emits for its updates and deletes
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplicaIdentity {
+    /// The primary key
+    #[default]
+    Default,
+    /// Every column
+    Full,
+}
+
+impl ReplicaIdentity {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            Self::Default => "DEFAULT",
+            Self::Full => "FULL",
+        }
+    }
+}
+
+/// The tables created by the init query, with whether they declare a primary key and their first unique index
+fn created_tables(init_query: &str) -> Vec<(String, bool, Option<String>)> {
+    let mut tables: Vec<(String, bool, Option<String>)> = Vec::new();
+    let mut current: Option<usize> = None;
+    for line in init_query.lines().map(str::trim_start) {
+        if let Some(rest) = line.strip_prefix("CREATE TABLE IF NOT EXISTS ") {
+            let table = rest.split(|c: char| c.is_whitespace() || c == '(').next().unwrap_or_default().to_string();
+            current = match tables.iter().position(|(name, _, _)| *name == table) {
+                Some(index) => Some(index),
+                None => {
+                    tables.push((table, false, None));
+                    Some(tables.len() - 1)
+                }
+            };
+            if rest.contains("PRIMARY KEY") {
+                tables[current.unwrap()].1 = true;
+            }
+        } else if let Some(rest) = line.strip_prefix("CREATE UNIQUE INDEX IF NOT EXISTS ") {
+            let mut words = rest.split_whitespace();
+            let (index, table) = (words.next(), words.nth(1));
+            if let (Some(index), Some(entry)) = (index, tables.iter_mut().find(|(name, _, _)| Some(name.as_str()) == table)) {
+                entry.2.get_or_insert_with(|| index.to_string());
+            }
+            current = None;
+        } else if line.starts_with("CREATE ") || line.starts_with(");") {
+            current = None;
+        } else if let (Some(index), true) = (current, line.contains("PRIMARY KEY")) {
+            tables[index].1 = true;
+        }
+    }
+    tables
+}
+
+/// Append to the init query the primary keys of the tables created without one, and the replica identity of every table
+pub fn apply_logical_replication(init_query: &str, config: &Option<LogicalReplicationConfig>) -> String {
+    let config = match config {
+        Some(config) => config,
+        None => return init_query.to_string(),
+    };
+    let mut query = init_query.to_string();
+    for (table, has_primary_key, unique_index) in created_tables(init_query) {
+        if !has_primary_key {
+            let primary_key = match unique_index {
+                Some(index) => format!("ADD CONSTRAINT {0} PRIMARY KEY USING INDEX {0}", index),
+                None => "ADD COLUMN id BIGSERIAL PRIMARY KEY".to_string(),
+            };
+            query.push_str(&format!(
+                "
+                DO $$ BEGIN
+                    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conrelid = '{0}'::regclass AND contype = 'p') THEN
+                        ALTER TABLE {0} {1};
+                    END IF;
+                END $$;
+                ",
+                table, primary_key
+            ));
+        }
+        query.push_str(&format!("ALTER TABLE {} REPLICA IDENTITY {};\n", table, config.replica_identity.as_sql()));
+    }
+    query
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INIT_QUERY: &str = "
+        CREATE TABLE IF NOT EXISTS spl_token_account (
+            pubkey VARCHAR(44) NOT NULL,
+            slot BIGINT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS spl_token_account_owner ON spl_token_account (owner);
+        CREATE UNIQUE INDEX IF NOT EXISTS spl_token_account_owner_pair ON spl_token_account (pubkey, owner, mint);
+        CREATE TABLE IF NOT EXISTS plugin_stats (
+            instance VARCHAR(64) NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS slot (
+            slot BIGINT PRIMARY KEY,
+            status VARCHAR(16) NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS listing (
+            id VARCHAR(44) NOT NULL,
+            PRIMARY KEY(id)
+        );
+    ";
+
+    #[test]
+    fn test_created_tables() {
+        assert_eq!(
+            created_tables(INIT_QUERY),
+            vec![
+                ("spl_token_account".to_string(), false, Some("spl_token_account_owner_pair".to_string())),
+                ("plugin_stats".to_string(), false, None),
+                ("slot".to_string(), true, None),
+                ("listing".to_string(), true, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_logical_replication() {
+        assert_eq!(apply_logical_replication(INIT_QUERY, &None), INIT_QUERY);
+        let query = apply_logical_replication(
+            INIT_QUERY,
+            &Some(LogicalReplicationConfig {
+                replica_identity: ReplicaIdentity::Full,
+            }),
+        );
+        assert!(query.contains("ALTER TABLE spl_token_account ADD CONSTRAINT spl_token_account_owner_pair PRIMARY KEY USING INDEX spl_token_account_owner_pair;"));
+        assert!(query.contains("ALTER TABLE plugin_stats ADD COLUMN id BIGSERIAL PRIMARY KEY;"));
+        assert!(!query.contains("ALTER TABLE slot ADD"));
+        assert!(query.contains("ALTER TABLE listing REPLICA IDENTITY FULL;"));
+        assert_eq!(query.matches("REPLICA IDENTITY FULL").count(), 4);
+    }
+}
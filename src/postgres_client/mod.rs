@@ -1,20 +1,112 @@
-mod accounts;
+pub(crate) mod accounts;
+pub mod async_pipeline;
 mod block_handler;
+pub mod bound_rows;
+pub mod bulk_load;
+pub mod close_detection;
+pub mod conflict_strategy;
+pub mod connection_pool;
+pub mod databases;
+pub mod db_errors;
+mod discriminator_registry;
+pub mod epoch_handler;
+pub mod external_handlers;
+pub mod fault_injection;
+pub mod fork_cleanup;
+pub mod handler_batch;
+pub mod handler_metrics;
+pub mod heartbeat_handler;
+pub mod index_manager;
+pub mod logical_replication;
+pub mod periodic_writer;
+pub mod plugin_stats_handler;
+pub mod prepared_statement;
+pub mod retention;
+mod schema_migrations;
+mod selector_stats_handler;
 mod slot_handler;
+pub mod sql_value;
+pub mod startup_progress;
+pub mod startup_summary;
+pub mod table_prefix;
+pub mod token_holder_views;
 mod transaction_handler;
+mod transactions;
+pub mod unload_summary;
+pub mod write_batch_handler;
 
 use crate::accounts_selector::AccountsSelectorConfig;
 use crate::config::GeyserPluginPostgresConfig;
 use crate::geyser_plugin_postgres::GeyserPluginPostgresError;
+use crate::memory_stats::add_queued_bytes;
+use crate::memory_stats::sub_queued_bytes;
+use crate::memory_stats::Queue;
+use crate::metrics::registry;
+use crate::metrics::solana_metrics_enabled;
+use crate::metrics::DB_CONNECTS_TOTAL;
+use crate::metrics::FLUSH_US;
+use crate::metrics::HANDLER_ERRORS_TOTAL;
+use crate::metrics::HANDLER_ROWS_TOTAL;
+use crate::metrics::STARTUP_ACCOUNTS_TOTAL;
+use crate::parallel_client::is_degraded;
 use crate::parallel_client::ParallelClient;
+use crate::postgres_client::accounts::account_handler::account_update_queries;
 use crate::postgres_client::accounts::account_handler::all_account_handlers;
 use crate::postgres_client::accounts::account_handler::select_account_handlers;
+use crate::postgres_client::accounts::decoded_account::promoted_columns_init;
+use crate::postgres_client::accounts::decoded_account::DecodedAccountTable;
+use crate::postgres_client::async_pipeline::AsyncPipeline;
 use crate::postgres_client::block_handler::BlockHandler;
+use crate::postgres_client::bound_rows::execute_rows;
+use crate::postgres_client::bound_rows::HandlerStatements;
+use crate::postgres_client::bound_rows::RowBatch;
+use crate::postgres_client::bulk_load::BulkLoad;
+use crate::postgres_client::close_detection::is_closed;
+use crate::postgres_client::close_detection::CloseDetectionHandler;
+use crate::postgres_client::connection_pool::ConnectionPool;
+use crate::postgres_client::connection_pool::ConnectionStatements;
+use crate::postgres_client::connection_pool::PooledConnection;
+use crate::postgres_client::db_errors::execute_batch;
+use crate::postgres_client::db_errors::execute_transaction;
+use crate::postgres_client::db_errors::DbErrorClass;
+use crate::postgres_client::discriminator_registry::DiscriminatorRegistry;
+use crate::postgres_client::epoch_handler::EpochHandler;
+use crate::postgres_client::external_handlers::ExternalHandlers;
+use crate::postgres_client::fork_cleanup::ForkCleanupHandler;
+use crate::postgres_client::handler_batch::execute_isolated_in;
+use crate::postgres_client::handler_batch::DeadLetterHandler;
+use crate::postgres_client::handler_batch::DeadLetterPayloads;
+use crate::postgres_client::handler_batch::HandlerBatch;
+use crate::postgres_client::handler_batch::HandlerFailure;
+use crate::postgres_client::handler_metrics::HandlerFlush;
+use crate::postgres_client::heartbeat_handler::record_processed_slot;
+use crate::postgres_client::heartbeat_handler::HeartbeatHandler;
+use crate::postgres_client::index_manager::apply_index_config;
+use crate::postgres_client::logical_replication::apply_logical_replication;
+use crate::postgres_client::plugin_stats_handler::PluginStatsHandler;
+use crate::postgres_client::prepared_statement::PreparedStatement;
+use crate::postgres_client::schema_migrations::SchemaMigrations;
+use crate::postgres_client::selector_stats_handler::SelectorStatsHandler;
 use crate::postgres_client::slot_handler::SlotHandler;
+use crate::postgres_client::startup_progress::startup_id;
+use crate::postgres_client::startup_progress::StartupProgressHandler;
+use crate::postgres_client::startup_summary::StartupSummaryHandler;
+use crate::postgres_client::startup_summary::StartupTracker;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::token_holder_views::TokenHolderViewsHandler;
+use crate::postgres_client::unload_summary::UnloadSummaryHandler;
+use crate::postgres_client::write_batch_handler::batch_context;
+use crate::postgres_client::write_batch_handler::WriteBatch;
+use crate::postgres_client::write_batch_handler::WriteBatchHandler;
+use crate::selector_reload;
+use crate::traces::child_span;
+use crate::transaction_selector::TransactionSelectorConfig;
+use libloading::Library;
 use log::*;
 use openssl::ssl::SslConnector;
 use openssl::ssl::SslFiletype;
 use openssl::ssl::SslMethod;
+use postgres::types::Type;
 use postgres::Client;
 use postgres::NoTls;
 use postgres_openssl::MakeTlsConnector;
@@ -24,27 +116,203 @@ use solana_measure::measure::Measure;
 use solana_metrics::*;
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::str::FromStr;
-use std::sync::Mutex;
+use std::mem::size_of;
+use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+use tracing::Span;
 
-use self::accounts::account_handler::AccountHandler;
+pub use self::accounts::account_audit_handler::apply_account_data_delta;
+pub use self::accounts::account_audit_handler::AccountAuditConfig;
+pub use self::accounts::account_audit_handler::AccountAuditMode;
+pub use self::accounts::account_data_compression::decompress_account_data;
+pub use self::accounts::account_data_compression::AccountDataCompression;
+pub use self::accounts::account_handler::AccountHandler;
 pub use self::accounts::account_handler::AccountHandlerId;
+pub use self::accounts::account_handler::AccountHandlersConfig;
+pub use self::accounts::account_handler::AccountKey;
 pub use self::accounts::account_handler::DbAccountInfo;
+pub use self::accounts::decoded_account::validate_promoted_columns;
+pub use self::accounts::decoded_account::PromotedColumnConfig;
+pub use self::accounts::idl_registry::IdlConfig;
+pub use self::accounts::idl_registry::IdlRegistry;
+pub use self::accounts::layout_account_handler::LayoutConfig;
+pub use self::accounts::script_account_handler::ScriptHandlerConfig;
+pub use self::accounts::wasm_account_handler::WasmHandlerConfig;
 pub use self::block_handler::DbBlockInfo;
+pub use self::bound_rows::BoundRow;
+pub use self::close_detection::CloseDetectionConfig;
+pub use self::conflict_strategy::ConflictStrategy;
+pub use self::connection_pool::PoolConfig;
+pub use self::databases::DatabaseConfig;
+pub use self::databases::DatabaseRouter;
+pub use self::db_errors::ReconnectConfig;
+pub use self::db_errors::SerializationRetryConfig;
+pub use self::epoch_handler::EpochsConfig;
+pub use self::external_handlers::CustomHandlers;
+pub use self::external_handlers::ExternalHandlerConfig;
+pub use self::external_handlers::HandlerRegistrar;
+pub use self::fault_injection::FaultInjectionConfig;
+pub use self::fork_cleanup::ForkCleanupConfig;
+pub use self::heartbeat_handler::HeartbeatConfig;
+pub use self::logical_replication::LogicalReplicationConfig;
+pub use self::plugin_stats_handler::PluginStatsConfig;
+pub use self::retention::RetentionConfig;
+pub use self::selector_stats_handler::DbSelectorStat;
+pub use self::slot_handler::SlotGap;
+pub use self::slot_handler::SlotWatermarks;
+pub use self::token_holder_views::TokenHolderViewsConfig;
 pub use self::transaction_handler::build_db_transaction;
 pub use self::transaction_handler::DbTransaction;
 use self::transaction_handler::TransactionHandler;
+use self::transactions::transaction_router::all_transaction_handlers;
+use self::transactions::transaction_router::select_transaction_handlers;
+pub use self::transactions::transaction_router::CustomTransactionHandler;
+pub use self::transactions::transaction_router::TransactionHandlerId;
+pub use self::transactions::transaction_router::TransactionUpdateHandler;
+pub use self::unload_summary::UnloadConfig;
+pub use solana_geyser_plugin_postgres_derive::GeyserAccountHandler;
+
+/// Whether a configured name can be written in a statement as it is, as a table, column or index name
+pub(crate) fn is_identifier(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// How long a connection suspected lost has to answer a no-op query
+const CONNECTION_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The payloads of the accounts of a failed flush, by the handlers selected for them. The close detection statements
+/// of the closed accounts aren't written by a selected handler
+fn dead_letter_payloads(account_selector: &Option<AccountsSelectorConfig>, close_detection: bool, accounts: &[DbAccountInfo], is_startup: bool) -> DeadLetterPayloads {
+    let mut payloads = DeadLetterPayloads::default();
+    for account in accounts {
+        let handlers = select_account_handlers(account_selector, account, is_startup);
+        let closed = (close_detection && is_closed(account.lamports as u64)).then(|| "close_detection");
+        payloads.push(DeadLetterHandler::account_payload(account), handlers.iter().map(|h| h.handler_id.as_str()).chain(closed));
+    }
+    payloads
+}
+
+/// A connection the server dropped may not be seen closed yet after the failure, it is checked with a no-op query
+fn is_connection_lost(client: &mut Client) -> bool {
+    client.is_closed() || client.is_valid(CONNECTION_CHECK_TIMEOUT).is_err()
+}
+
+/// Check a connection out of the pool, or connect without one, and prepare the statements of the handlers on it
+/// unless the pool kept them. A pooled connection the statements fail to prepare on is discarded
+fn checkout(
+    connection: &mut Option<PooledConnection>,
+    pool: Option<&ConnectionPool>,
+    config: &GeyserPluginPostgresConfig,
+    account_handlers: &HashMap<AccountHandlerId, Box<dyn AccountHandler>>,
+) -> Result<(), GeyserPluginError> {
+    let mut checked_out = match pool {
+        Some(pool) => pool.get()?,
+        None => PooledConnection::new(SimplePostgresClient::connect_to_db(config)?),
+    };
+    if checked_out.statements.is_none() {
+        match SimplePostgresClient::prepare_statements(&mut checked_out.client, config, account_handlers) {
+            Ok(statements) => checked_out.statements = Some(statements),
+            Err(err) => {
+                if let Some(pool) = pool {
+                    pool.discard(checked_out);
+                }
+                return Err(err);
+            }
+        }
+    }
+    *connection = Some(checked_out);
+    Ok(())
+}
+
+/// The connection of the client and its statements, checked out of the pool when the client holds none. Without
+/// a pool the connection is only missing once it was lost, the worker then reconnects.
+fn connected<'a>(
+    connection: &'a mut Option<PooledConnection>,
+    pool: &Option<Arc<ConnectionPool>>,
+    account_handlers: &HashMap<AccountHandlerId, Box<dyn AccountHandler>>,
+) -> Result<(&'a mut Client, &'a ConnectionStatements), GeyserPluginError> {
+    if connection.is_none() {
+        match pool {
+            Some(pool) => checkout(connection, Some(pool), pool.plugin_config(), account_handlers)?,
+            None => {
+                return Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::ConnectionError {
+                    msg: "[connected] the connection was lost".to_string(),
+                })))
+            }
+        }
+    }
+    let connection = connection.as_mut().unwrap();
+    Ok((&mut connection.client, connection.statements.as_ref().unwrap()))
+}
 
 pub struct SimplePostgresClient {
     batch_size: usize,
     slots_at_startup: HashSet<u64>,
     pending_account_updates: Vec<DbAccountInfo>,
-    block_handler: BlockHandler,
-    transaction_handler: TransactionHandler,
+    /// Startup accounts per handler not yet reported to the metrics registry
+    startup_accounts: HashMap<String, u64>,
+    flush_interval: Duration,
+    /// The pending live updates and rows that force a flush
+    live_batch_size: usize,
+    pending_live_updates: HandlerBatch,
+    /// The rows of the handlers with prepared statements, written after the statements of the flush
+    pending_live_rows: RowBatch,
+    /// The statements of the accounts closed in the flush, run after its rows so they delete or mark the rows written
+    /// at earlier slots of the same flush
+    pending_live_closes: HandlerBatch,
+    /// The pubkeys and slots of the pending live updates, kept when `write_batches` is set
+    pending_live_accounts: Vec<(AccountKey, i64)>,
+    write_batches: bool,
+    /// Record the statements of the handlers that failed in the `dead_letter` table
+    dead_letters: bool,
+    /// The pending live updates, kept with `dead_letters` to build the payloads of the handlers that fail
+    pending_live_dead_letters: Vec<DbAccountInfo>,
+    /// Load the startup accounts of the handlers with a staging table through it
+    startup_bulk_load: bool,
+    /// Copy the staged startup accounts in binary rather than inserting them
+    startup_copy: bool,
+    /// The column types of the staging tables the startup accounts are copied to
+    staging_types: HashMap<String, Vec<Type>>,
+    /// Connected through PgBouncer in transaction pooling mode, nothing is kept on the server connection across transactions
+    pgbouncer: bool,
+    /// Notify the `slot_finality` listeners of the confirmed and rooted slots
+    slot_finality_notify: bool,
+    /// Mark the slots of abandoned forks when a slot is rooted, and delete the rows written at them
+    fork_cleanup: Option<ForkCleanupConfig>,
+    /// Delete or mark the rows of the accounts closed by a live update
+    close_detection: Option<CloseDetectionConfig>,
+    /// The `table_prefix` of the relations this client writes to
+    prefix: TablePrefix,
+    serialization_retry: SerializationRetryConfig,
+    /// Keep the batches a lost connection failed, they are written again once the worker reconnected
+    retain_lost_batches: bool,
+    /// The handlers skipped for live updates while the plugin is in degraded mode
+    degraded_skip_handlers: Vec<String>,
+    /// The highest slot of the pending live updates
+    pending_live_slot: u64,
+    /// The live account updates written by the next flush
+    pending_live_requests: usize,
+    pending_live_since: Option<Instant>,
+    transaction_handlers: HashMap<TransactionHandlerId, Box<dyn TransactionUpdateHandler>>,
+    discriminator_registry: Option<DiscriminatorRegistry>,
+    idl_registry: Arc<IdlRegistry>,
     account_handlers: HashMap<AccountHandlerId, Box<dyn AccountHandler>>,
     account_selector: Option<AccountsSelectorConfig>,
-    client: Mutex<Client>,
+    transaction_selector: Option<TransactionSelectorConfig>,
+    /// Keeps the handlers of the selectors written to the database of the client
+    database_router: DatabaseRouter,
+    /// The generation of the reloaded selectors the worker routes with
+    selector_generation: u64,
+    external_transaction_handlers: HashMap<String, Box<dyn CustomTransactionHandler>>,
+    /// The connection written to, checked out of the `pool` for each request when there is one
+    connection: Option<PooledConnection>,
+    pool: Option<Arc<ConnectionPool>>,
+    /// The connection the bound rows are pipelined on in `async_mode`
+    pipeline: Option<AsyncPipeline>,
+    /// Dropped last so the external handlers never outlive their code
+    _external_libraries: Vec<Arc<Library>>,
 }
 
 pub trait PostgresClient {
@@ -54,156 +322,445 @@ pub trait PostgresClient {
 
     fn update_account(&mut self, account: DbAccountInfo, is_startup: bool) -> Result<(), GeyserPluginError>;
 
-    fn update_slot_status(&mut self, slot: u64, parent: Option<u64>, status: SlotStatus) -> Result<(), GeyserPluginError>;
+    /// Write the close of an account the selectors didn't pick, without running the handlers on it
+    fn close_account(&mut self, account: DbAccountInfo) -> Result<(), GeyserPluginError>;
+
+    /// Write the slot's status, `gap` being the slots its parent revealed were missed
+    fn update_slot_status(&mut self, slot: u64, parent: Option<u64>, status: SlotStatus, gap: Option<SlotGap>) -> Result<(), GeyserPluginError>;
 
     fn notify_end_of_startup(&mut self) -> Result<(), GeyserPluginError>;
 
-    fn log_transaction(&mut self, transaction_info: DbTransaction) -> Result<(), GeyserPluginError>;
+    /// Flush pending live updates once they are due, or unconditionally when `force` is set
+    fn flush_live_updates(&mut self, force: bool) -> Result<(), GeyserPluginError>;
+
+    fn log_transaction(&mut self, transaction_info: &DbTransaction) -> Result<(), GeyserPluginError>;
 
-    fn update_block_metadata(&mut self, block_info: DbBlockInfo) -> Result<(), GeyserPluginError>;
+    fn update_block_metadata(&mut self, block_info: &DbBlockInfo) -> Result<(), GeyserPluginError>;
+
+    fn update_selector_stats(&mut self, stats: &[DbSelectorStat]) -> Result<(), GeyserPluginError>;
 }
 
 impl SimplePostgresClient {
-    pub fn new(config: &GeyserPluginPostgresConfig) -> Result<Self, GeyserPluginError> {
+    pub fn new(config: &GeyserPluginPostgresConfig, idl_registry: Arc<IdlRegistry>, custom_handlers: &CustomHandlers) -> Result<Self, GeyserPluginError> {
+        Self::new_with_pool(config, idl_registry, custom_handlers, None)
+    }
+
+    /// A client checking its connections out of the pool shared by the workers, if any, rather than holding its own
+    pub fn new_with_pool(config: &GeyserPluginPostgresConfig, idl_registry: Arc<IdlRegistry>, custom_handlers: &CustomHandlers, pool: Option<Arc<ConnectionPool>>) -> Result<Self, GeyserPluginError> {
         info!("[SimplePostgresClient] creating");
-        let mut client = Self::connect_to_db(config)?;
-        let block_handler = BlockHandler::new(&mut client, config)?;
-        let transaction_handler = TransactionHandler::new(&mut client, config)?;
         let batch_size = config.batch_size;
+        let external_handlers = ExternalHandlers::load(config, custom_handlers)?;
+        let mut account_handlers = all_account_handlers(config, idl_registry.clone());
+        for (handler_id, handler) in external_handlers.account_handlers.into_iter().filter(|(id, _)| config.account_handler_enabled(id)) {
+            account_handlers.insert(AccountHandlerId::External(handler_id), handler);
+        }
+        let mut connection = None;
+        checkout(&mut connection, pool.as_deref(), config, &account_handlers)?;
+        // the statements are prepared up front so a wrong schema fails the load, a pooled connection is then handed back
+        if let Some(pool) = &pool {
+            pool.put(connection.take().unwrap());
+        }
+        let pipeline = config.async_mode.then(|| AsyncPipeline::connect(config, &account_handlers)).transpose()?;
+        let database_router = DatabaseRouter::new(config);
         Ok(Self {
             batch_size,
-            client: Mutex::new(client),
-            block_handler,
-            transaction_handler,
+            connection,
+            pool,
+            pipeline,
+            transaction_handlers: all_transaction_handlers(config, idl_registry.clone()),
+            discriminator_registry: config.discriminator_registry.then(DiscriminatorRegistry::default),
+            idl_registry,
             pending_account_updates: Vec::with_capacity(batch_size),
-            account_handlers: all_account_handlers(),
-            account_selector: config.accounts_selector.clone(),
+            startup_accounts: HashMap::default(),
+            flush_interval: Duration::from_millis(config.flush_interval_ms),
+            live_batch_size: config.live_batch_size.unwrap_or(batch_size),
+            pending_live_updates: HandlerBatch::default(),
+            pending_live_rows: RowBatch::default(),
+            pending_live_closes: HandlerBatch::default(),
+            pending_live_accounts: Vec::new(),
+            write_batches: config.write_batches,
+            dead_letters: config.dead_letters,
+            pending_live_dead_letters: Vec::new(),
+            startup_bulk_load: config.startup_bulk_load,
+            startup_copy: config.startup_copy,
+            staging_types: HashMap::default(),
+            pgbouncer: config.pgbouncer,
+            slot_finality_notify: config.slot_finality_notify,
+            fork_cleanup: config.fork_cleanup.clone(),
+            close_detection: config.close_detection.clone(),
+            prefix: TablePrefix::new(config),
+            serialization_retry: config.serialization_retry.clone(),
+            retain_lost_batches: config.reconnect.max_retries > 0,
+            degraded_skip_handlers: config
+                .queue_saturation
+                .as_ref()
+                .and_then(|queue_saturation| queue_saturation.degraded_mode.as_ref())
+                .map(|degraded_mode| degraded_mode.skip_handlers.clone())
+                .unwrap_or_default(),
+            pending_live_slot: 0,
+            pending_live_requests: 0,
+            pending_live_since: None,
+            account_handlers,
+            account_selector: database_router.route_accounts_selector(config.accounts_selector.clone()),
+            transaction_selector: database_router.route_transaction_selector(config.transaction_selector.clone()),
+            database_router,
+            selector_generation: selector_reload::generation(),
             slots_at_startup: HashSet::default(),
+            external_transaction_handlers: external_handlers.transaction_handlers,
+            _external_libraries: external_handlers.libraries,
         })
     }
 
-    pub fn connect_to_db(config: &GeyserPluginPostgresConfig) -> Result<Client, GeyserPluginError> {
-        let result = match config.use_ssl {
-            Some(true) => {
-                if config.server_ca.is_none() {
-                    let msg = "\"server_ca\" must be specified when \"use_ssl\" is set".to_string();
-                    return Err(GeyserPluginError::ConfigFileReadError { msg });
-                }
-                if config.client_cert.is_none() {
-                    let msg = "\"client_cert\" must be specified when \"use_ssl\" is set".to_string();
-                    return Err(GeyserPluginError::ConfigFileReadError { msg });
-                }
-                if config.client_key.is_none() {
-                    let msg = "\"client_key\" must be specified when \"use_ssl\" is set".to_string();
-                    return Err(GeyserPluginError::ConfigFileReadError { msg });
-                }
-                let mut builder = SslConnector::builder(SslMethod::tls()).unwrap();
-                if let Err(err) = builder.set_ca_file(config.server_ca.as_ref().unwrap()) {
-                    let msg = format!(
-                        "Failed to set the server certificate specified by \"server_ca\": {}. Error: ({})",
-                        config.server_ca.as_ref().unwrap(),
-                        err
-                    );
-                    return Err(GeyserPluginError::ConfigFileReadError { msg });
-                }
-                if let Err(err) = builder.set_certificate_file(config.client_cert.as_ref().unwrap(), SslFiletype::PEM) {
-                    let msg = format!(
-                        "Failed to set the client certificate specified by \"client_cert\": {}. Error: ({})",
-                        config.client_cert.as_ref().unwrap(),
-                        err
-                    );
-                    return Err(GeyserPluginError::ConfigFileReadError { msg });
+    /// Prepare the statements of the handlers on a new connection
+    fn prepare_statements(
+        client: &mut Client,
+        config: &GeyserPluginPostgresConfig,
+        account_handlers: &HashMap<AccountHandlerId, Box<dyn AccountHandler>>,
+    ) -> Result<ConnectionStatements, GeyserPluginError> {
+        let transaction_err = |err: postgres::Error| {
+            GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
+                msg: format!("[SimplePostgresClient::prepare_statements] error=[{}]", err),
+            }))
+        };
+        // the statements are prepared in a single transaction, see `PreparedStatement::prepare`
+        let mut transaction = client.transaction().map_err(transaction_err)?;
+        let block_handler = BlockHandler::new(&mut transaction, config)?;
+        let transaction_handler = TransactionHandler::new(&mut transaction, config)?;
+        let handler_statements = HandlerStatements::prepare(&mut transaction, account_handlers, config).map_err(transaction_err)?;
+        PreparedStatement::release(&mut transaction, config.pgbouncer).map_err(transaction_err)?;
+        transaction.commit().map_err(transaction_err)?;
+        Ok(ConnectionStatements {
+            block_handler,
+            transaction_handler,
+            handler_statements,
+        })
+    }
+
+    /// Whether the connection to the database was lost, the client can't be used until it reconnected. A client
+    /// that failed to check a connection out of the pool has none, and is reconnected the same way
+    pub fn is_connection_lost(&mut self) -> bool {
+        self.connection.as_mut().map_or(true, |connection| is_connection_lost(&mut connection.client)) || self.pipeline.as_ref().map_or(false, AsyncPipeline::is_closed)
+    }
+
+    /// Replace the lost connection with a new one and prepare the statements on it again. The
+    /// pending batches, and the batches the lost connection failed, are written by the next flush.
+    pub fn reconnect(&mut self, config: &GeyserPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+        if let (Some(pool), Some(connection)) = (&self.pool, self.connection.take()) {
+            pool.discard(connection);
+        }
+        checkout(&mut self.connection, self.pool.as_deref(), config, &self.account_handlers)?;
+        if self.pipeline.as_ref().map_or(false, AsyncPipeline::is_closed) {
+            self.pipeline = Some(AsyncPipeline::connect(config, &self.account_handlers)?);
+        }
+        self.staging_types.clear();
+        Ok(())
+    }
+
+    /// Hand the connection back to the pool once a request is written, the client keeps its own when there is no pool
+    pub fn release_connection(&mut self) {
+        if let Some(pool) = &self.pool {
+            if let Some(connection) = self.connection.take() {
+                pool.put(connection);
+            }
+        }
+    }
+
+    /// Write the batches kept from a lost connection, once reconnected
+    pub fn flush_retained(&mut self) -> Result<(), GeyserPluginError> {
+        self.flush_live_updates(true)?;
+        if self.pending_account_updates.len() >= self.batch_size {
+            self.flush_startup_accounts("update_account_batch")?;
+        }
+        Ok(())
+    }
+
+    /// Move the startup account counts to the registry, they are kept locally in between
+    /// batches to stay off the registry lock for every account
+    fn report_startup_accounts(&mut self) {
+        for (handler_id, count) in self.startup_accounts.drain() {
+            registry().inc_counter(STARTUP_ACCOUNTS_TOTAL, &[("handler", &handler_id)], count);
+        }
+    }
+
+    /// Write the pending startup accounts in a single batch, or per handler when one of them fails
+    pub fn flush_startup_accounts(&mut self, operation: &'static str) -> Result<(), GeyserPluginError> {
+        self.report_startup_accounts();
+        if self.pending_account_updates.is_empty() {
+            return Ok(());
+        }
+        // checked out before the batch is taken, a failed checkout keeps it pending
+        let (client, statements) = connected(&mut self.connection, &self.pool, &self.account_handlers)?;
+        // the batch is handed back emptied once written, so its allocation serves every batch
+        let mut accounts = std::mem::take(&mut self.pending_account_updates);
+        sub_queued_bytes(Queue::StartupAccounts, accounts.iter().map(|a| size_of::<DbAccountInfo>() + a.heap_bytes()).sum());
+        let mut updates = HandlerBatch::default();
+        let mut rows = RowBatch::default();
+        let mut bulk_load = (self.startup_bulk_load || self.startup_copy).then(|| BulkLoad::new(self.pgbouncer, self.startup_copy));
+        for account in &accounts {
+            updates.push(account_update_queries(
+                &self.account_selector,
+                &self.account_handlers,
+                account,
+                true,
+                &[],
+                bulk_load.as_mut(),
+                Some(&mut rows),
+            ));
+        }
+        // the staged rows are counted rather than the statements applying them
+        let mut handler_flush = HandlerFlush::default();
+        handler_flush.add(updates.handler_counts());
+        handler_flush.add(rows.handler_counts());
+        if let Some(bulk_load) = &bulk_load {
+            handler_flush.add(bulk_load.handler_counts());
+            updates.push(bulk_load.queries());
+        }
+        let batch = self.write_batches.then(|| WriteBatch::new("startup", accounts.iter().map(|a| (a.pubkey.as_slice(), a.slot))));
+        if let Some(batch) = &batch {
+            updates.push([("write_batch".to_string(), WriteBatchHandler::insert(&self.prefix, batch))]);
+        }
+
+        let _span = child_span!(&Span::current(), "flush", kind = "startup", accounts = accounts.len()).entered();
+        let mut measure = Measure::start("geyser-plugin-postgres-flush-startup");
+        let progress = startup_id().map(|id| StartupProgressHandler::batch(&self.prefix, id, accounts.len(), accounts.iter().map(|a| a.slot).max().unwrap_or_default()));
+        // the batch is committed whole with its progress, the bound rows included: the rows of the async pipeline
+        // couldn't join the transaction, so the startup batches don't use it
+        let staging_types = &mut self.staging_types;
+        let result = execute_transaction(client, operation, &self.serialization_retry, |transaction| {
+            let mut failures = execute_isolated_in(transaction, operation, &updates)?;
+            if let Some(bulk_load) = &bulk_load {
+                failures.extend(bulk_load.copy(transaction, operation, staging_types)?);
+            }
+            failures.extend(execute_rows(transaction, operation, &statements.handler_statements, &rows)?);
+            if let Some(progress) = &progress {
+                transaction.batch_execute(progress)?;
+            }
+            Ok(failures)
+        });
+        let failures = match result {
+            Ok(failures) => {
+                if self.dead_letters && !failures.is_empty() {
+                    let payloads = dead_letter_payloads(&self.account_selector, false, &accounts, true);
+                    DeadLetterHandler::record(client, &self.prefix, operation, &failures, batch.as_ref().map(|b| b.id), &payloads, &self.serialization_retry);
                 }
-                if let Err(err) = builder.set_private_key_file(config.client_key.as_ref().unwrap(), SslFiletype::PEM) {
-                    let msg = format!("Failed to set the client key specified by \"client_key\": {}. Error: ({})", config.client_key.as_ref().unwrap(), err);
-                    return Err(GeyserPluginError::ConfigFileReadError { msg });
+                failures
+            }
+            Err(err) => {
+                // the accounts are flushed again once reconnected
+                if self.retain_lost_batches && self.is_connection_lost() {
+                    add_queued_bytes(Queue::StartupAccounts, accounts.iter().map(|a| size_of::<DbAccountInfo>() + a.heap_bytes()).sum());
+                    self.pending_account_updates = accounts;
                 }
-
-                let mut connector = MakeTlsConnector::new(builder.build());
-                connector.set_callback(|connect_config, _domain| {
-                    connect_config.set_verify_hostname(false);
-                    Ok(())
-                });
-                Client::connect(&config.connection_str, connector)
+                return Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
+                    msg: format!("[{}]{} error=[{}]", operation, batch_context(&batch), err),
+                })));
             }
-            _ => Client::connect(&config.connection_str, NoTls),
+        };
+        record_processed_slot(accounts.iter().map(|a| a.slot as u64).max().unwrap_or_default());
+        accounts.drain(..).for_each(DbAccountInfo::recycle);
+        self.pending_account_updates = accounts;
+        measure.stop();
+        registry().observe_us(FLUSH_US, &[("kind", "startup")], measure.as_us());
+        handler_flush.record(&failures, measure.as_us());
+        Ok(())
+    }
+
+    /// Startup accounts waiting for their batch to fill up
+    pub fn pending_startup_accounts(&self) -> usize {
+        self.pending_account_updates.len()
+    }
+
+    /// Route with the selectors of the last reload, if any since the worker's
+    fn refresh_selectors(&mut self) {
+        if selector_reload::generation() == self.selector_generation {
+            return;
+        }
+        let (generation, (account_selector, transaction_selector)) = selector_reload::selectors();
+        info!("[refresh_selectors] generation=[{}]", generation);
+        self.selector_generation = generation;
+        self.account_selector = self.database_router.route_accounts_selector(account_selector);
+        self.transaction_selector = self.database_router.route_transaction_selector(transaction_selector);
+    }
+
+    /// Record the dead letter of a transaction a handler failed to write, unless the connection is at fault
+    fn dead_letter_transaction(&mut self, transaction: &DbTransaction, handler_id: &str, statements: &str, error: postgres::Error) {
+        if !self.dead_letters || DbErrorClass::from_error(&error).is_transient() {
+            return;
+        }
+        let mut payloads = DeadLetterPayloads::default();
+        payloads.push(DeadLetterHandler::transaction_payload(transaction), [handler_id]);
+        let failure = HandlerFailure {
+            handler_id: handler_id.to_string(),
+            statements: statements.to_string(),
+            error,
+        };
+        if let Some(connection) = self.connection.as_mut() {
+            DeadLetterHandler::record(&mut connection.client, &self.prefix, "log_transaction", &[failure], None, &payloads, &self.serialization_retry);
+        }
+    }
+
+    /// Live account updates waiting to be flushed
+    pub fn pending_live_requests(&self) -> usize {
+        self.pending_live_requests
+    }
+
+    /// The TLS connector of the connections when "use_ssl" is set
+    pub fn tls_connector(config: &GeyserPluginPostgresConfig) -> Result<Option<MakeTlsConnector>, GeyserPluginError> {
+        if config.use_ssl != Some(true) {
+            return Ok(None);
+        }
+        if config.server_ca.is_none() {
+            let msg = "\"server_ca\" must be specified when \"use_ssl\" is set".to_string();
+            return Err(GeyserPluginError::ConfigFileReadError { msg });
+        }
+        if config.client_cert.is_none() {
+            let msg = "\"client_cert\" must be specified when \"use_ssl\" is set".to_string();
+            return Err(GeyserPluginError::ConfigFileReadError { msg });
+        }
+        if config.client_key.is_none() {
+            let msg = "\"client_key\" must be specified when \"use_ssl\" is set".to_string();
+            return Err(GeyserPluginError::ConfigFileReadError { msg });
+        }
+        let mut builder = SslConnector::builder(SslMethod::tls()).unwrap();
+        if let Err(err) = builder.set_ca_file(config.server_ca.as_ref().unwrap()) {
+            let msg = format!(
+                "Failed to set the server certificate specified by \"server_ca\": {}. Error: ({})",
+                config.server_ca.as_ref().unwrap(),
+                err
+            );
+            return Err(GeyserPluginError::ConfigFileReadError { msg });
+        }
+        if let Err(err) = builder.set_certificate_file(config.client_cert.as_ref().unwrap(), SslFiletype::PEM) {
+            let msg = format!(
+                "Failed to set the client certificate specified by \"client_cert\": {}. Error: ({})",
+                config.client_cert.as_ref().unwrap(),
+                err
+            );
+            return Err(GeyserPluginError::ConfigFileReadError { msg });
+        }
+        if let Err(err) = builder.set_private_key_file(config.client_key.as_ref().unwrap(), SslFiletype::PEM) {
+            let msg = format!("Failed to set the client key specified by \"client_key\": {}. Error: ({})", config.client_key.as_ref().unwrap(), err);
+            return Err(GeyserPluginError::ConfigFileReadError { msg });
+        }
+
+        let mut connector = MakeTlsConnector::new(builder.build());
+        connector.set_callback(|connect_config, _domain| {
+            connect_config.set_verify_hostname(false);
+            Ok(())
+        });
+        Ok(Some(connector))
+    }
+
+    pub fn connect_to_db(config: &GeyserPluginPostgresConfig) -> Result<Client, GeyserPluginError> {
+        let result = match Self::tls_connector(config)? {
+            Some(connector) => Client::connect(&config.connection_str, connector),
+            None => Client::connect(&config.connection_str, NoTls),
         };
         match result {
             Err(err) => Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::ConnectionError {
                 msg: format!("[connect_to_db] connection_str={} error={}", config.connection_str, err),
             }))),
-            Ok(client) => Ok(client),
+            Ok(mut client) => {
+                registry().inc_counter(DB_CONNECTS_TOTAL, &[], 1);
+                // the schema is created by the init query, until then the tables are looked up in no schema
+                if let Some(schema) = &config.schema {
+                    client.batch_execute(&format!("SET search_path TO {};", schema)).map_err(|err| {
+                        GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::ConnectionError {
+                            msg: format!("[connect_to_db] schema={} error={}", schema, err),
+                        }))
+                    })?;
+                }
+                Ok(client)
+            }
+        }
+    }
+}
+
+impl SimplePostgresClient {
+    /// Queue the statements of a live update, and the close of the account when it was closed, then flush if due
+    fn queue_live_update(&mut self, account: DbAccountInfo, queries: Vec<(String, String)>, has_rows: bool) -> Result<(), GeyserPluginError> {
+        let close = (self.close_detection.is_some() && is_closed(account.lamports as u64)).then(|| CloseDetectionHandler::closed(&self.prefix, &account));
+        let pending = !queries.is_empty() || has_rows || close.is_some();
+        if pending {
+            self.pending_live_requests += 1;
+            self.pending_live_since.get_or_insert_with(Instant::now);
+            if self.write_batches {
+                self.pending_live_accounts.push((account.pubkey.clone(), account.slot));
+            }
         }
+        if !queries.is_empty() {
+            add_queued_bytes(Queue::LiveUpdates, queries.iter().map(|(_, query)| query.len()).sum());
+            self.pending_live_updates.push(queries);
+        }
+        if let Some(close) = close {
+            add_queued_bytes(Queue::LiveUpdates, close.len());
+            self.pending_live_closes.push([("close_detection".to_string(), close)]);
+        }
+        // the payload of a dead letter is only built when its handler fails
+        if self.dead_letters && pending {
+            self.pending_live_dead_letters.push(account);
+        } else {
+            account.recycle();
+        }
+        self.flush_live_updates(false)
     }
 }
 
 impl PostgresClient for SimplePostgresClient {
     fn update_account(&mut self, account: DbAccountInfo, is_startup: bool) -> Result<(), GeyserPluginError> {
-        let account_key = bs58::encode(&account.pubkey).into_string();
-        let owner_key = bs58::encode(&account.owner).into_string();
-        debug!("[update_account] account=[{}] owner=[{}] slot=[{}]", account_key, owner_key, account.slot,);
+        if log_enabled!(Level::Debug) {
+            let account_key = bs58::encode(&account.pubkey).into_string();
+            let owner_key = bs58::encode(&account.owner).into_string();
+            debug!("[update_account] account=[{}] owner=[{}] slot=[{}]", account_key, owner_key, account.slot,);
+        }
+        self.refresh_selectors();
+        if let Some(discriminator_registry) = &mut self.discriminator_registry {
+            discriminator_registry.record(&account);
+        }
 
-        let client = &mut self.client.get_mut().unwrap();
         if is_startup {
             self.slots_at_startup.insert(account.slot as u64);
+            for handler in select_account_handlers(&self.account_selector, &account, true) {
+                *self.startup_accounts.entry(handler.handler_id).or_default() += 1;
+            }
+            add_queued_bytes(Queue::StartupAccounts, size_of::<DbAccountInfo>() + account.heap_bytes());
             self.pending_account_updates.push(account);
             // flush if batch size
             if self.pending_account_updates.len() >= self.batch_size {
                 info!("[update_account_batch][flushing_accounts] length={}/{}", self.pending_account_updates.len(), self.batch_size);
-                let query = self
-                    .pending_account_updates
-                    .drain(..)
-                    .map(|a| {
-                        select_account_handlers(&self.account_selector, &a, true)
-                            .iter()
-                            // map feed through relevant handlers
-                            .map(|h| {
-                                self.account_handlers
-                                    .get(&AccountHandlerId::from_str(&h.handler_id).expect("Invalid account handler id"))
-                                    .expect("Invalid handler id")
-                                    .account_update(&a)
-                            })
-                            .collect::<Vec<String>>()
-                            .join("")
-                    })
-                    .collect::<Vec<String>>()
-                    .join("");
-
-                if let Err(err) = client.batch_execute(&query) {
-                    return Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
-                        msg: format!("[update_account_batch] error=[{}]", err),
-                    })));
-                };
+                self.flush_startup_accounts("update_account_batch")?;
             }
             return Ok(());
         }
-        let query = select_account_handlers(&self.account_selector, &account, false)
-            .iter()
-            .map(|h| {
-                self.account_handlers
-                    .get(&AccountHandlerId::from_str(&h.handler_id).expect("Invalid account handler id"))
-                    .expect("Invalid handler id")
-                    .account_update(&account)
-            })
-            .collect::<Vec<String>>()
-            .join("");
-        if !query.is_empty() {
-            return match client.batch_execute(&query) {
-                Ok(_) => Ok(()),
-                Err(err) => Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
-                    msg: format!("[update_account] error=[{}]", err),
-                }))),
-            };
-        }
-        Ok(())
+        self.pending_live_slot = self.pending_live_slot.max(account.slot as u64);
+        let skip_handlers = match is_degraded() {
+            true => self.degraded_skip_handlers.as_slice(),
+            false => &[],
+        };
+        let pending_rows = self.pending_live_rows.len();
+        let queries = account_update_queries(&self.account_selector, &self.account_handlers, &account, false, skip_handlers, None, Some(&mut self.pending_live_rows));
+        let has_rows = self.pending_live_rows.len() > pending_rows;
+        self.queue_live_update(account, queries, has_rows)
+    }
+
+    fn close_account(&mut self, account: DbAccountInfo) -> Result<(), GeyserPluginError> {
+        self.pending_live_slot = self.pending_live_slot.max(account.slot as u64);
+        self.queue_live_update(account, Vec::new(), false)
     }
 
-    fn update_slot_status(&mut self, slot: u64, parent: Option<u64>, status: SlotStatus) -> Result<(), GeyserPluginError> {
+    fn update_slot_status(&mut self, slot: u64, parent: Option<u64>, status: SlotStatus, gap: Option<SlotGap>) -> Result<(), GeyserPluginError> {
         info!("[update_slot_status] slot=[{:?}] status=[{:?}]", slot, status);
-        let client = &mut self.client.get_mut().unwrap();
-        let query = SlotHandler::update(slot, parent, status);
+        let (client, _) = connected(&mut self.connection, &self.pool, &self.account_handlers)?;
+        let mut query = SlotHandler::update(&self.prefix, slot, parent, status);
+        query.push_str(&SlotHandler::watermark(&self.prefix, slot, parent, status, gap.as_ref()));
+        if let Some(notify) = self.slot_finality_notify.then(|| SlotHandler::finality_notify(slot, status)).flatten() {
+            query.push_str(&notify);
+        }
+        if let (Some(fork_cleanup), SlotStatus::Rooted) = (&self.fork_cleanup, status) {
+            query.push_str(&ForkCleanupHandler::rooted(&self.prefix, slot, fork_cleanup));
+        }
         if !query.is_empty() {
-            return match client.batch_execute(&query) {
+            return match execute_batch(client, "update_slot_status", &query, &self.serialization_retry) {
                 Ok(_) => Ok(()),
                 Err(err) => Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
                     msg: format!("[update_slot_status] error=[{}]", err),
@@ -217,35 +774,19 @@ impl PostgresClient for SimplePostgresClient {
     fn notify_end_of_startup(&mut self) -> Result<(), GeyserPluginError> {
         // flush accounts
         info!("[notify_end_of_startup][flushing_accounts] length={}/{}", self.pending_account_updates.len(), self.batch_size);
-        let client = &mut self.client.get_mut().unwrap();
-        let query = self
-            .pending_account_updates
-            .drain(..)
-            .map(|a| {
-                select_account_handlers(&self.account_selector, &a, true)
-                    .iter()
-                    // map feed through relevant handlers
-                    .map(|h| {
-                        self.account_handlers
-                            .get(&AccountHandlerId::from_str(&h.handler_id).expect("Invalid account handler id"))
-                            .expect("Invalid handler id")
-                            .account_update(&a)
-                    })
-                    .collect::<Vec<String>>()
-                    .join("")
-            })
-            .collect::<Vec<String>>()
-            .join("");
-        if let Err(err) = client.batch_execute(&query) {
-            return Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
-                msg: format!("[notify_end_of_startup][flush_accounst_error] error=[{}]", err),
-            })));
-        };
+        self.flush_live_updates(true)?;
+        self.flush_startup_accounts("notify_end_of_startup")?;
+        let (client, _) = connected(&mut self.connection, &self.pool, &self.account_handlers)?;
 
         // flush slots sequentailly
         let mut measure = Measure::start("geyser-plugin-postgres-flush-slots-us");
         for s in &self.slots_at_startup {
-            if let Err(err) = client.batch_execute(&SlotHandler::update(*s, None, SlotStatus::Rooted)) {
+            if let Err(err) = execute_batch(
+                client,
+                "notify_end_of_startup",
+                &SlotHandler::update(&self.prefix, *s, None, SlotStatus::Rooted),
+                &self.serialization_retry,
+            ) {
                 return Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
                     msg: format!("[notify_end_of_startup][flush_slots] error=[{}]", err),
                 })));
@@ -255,7 +796,7 @@ impl PostgresClient for SimplePostgresClient {
         // let query = &self
         //     .slots_at_startup
         //     .drain()
-        //     .map(|s| SlotHandler::update(s, None, SlotStatus::Rooted))
+        //     .map(|s| SlotHandler::update(&self.prefix, s, None, SlotStatus::Rooted))
         //     .collect::<Vec<String>>()
         //     .join("");
         // if let Err(err) = client.batch_execute(&query) {
@@ -265,49 +806,288 @@ impl PostgresClient for SimplePostgresClient {
         // };
         measure.stop();
 
-        datapoint_info!(
-            "geyser_plugin_notify_account_restore_from_snapshot_summary",
-            ("flush_slots-us", measure.as_us(), i64),
-            ("flush-slots-counts", self.slots_at_startup.len(), i64),
-        );
+        if solana_metrics_enabled() {
+            datapoint_info!(
+                "geyser_plugin_notify_account_restore_from_snapshot_summary",
+                ("flush_slots-us", measure.as_us(), i64),
+                ("flush-slots-counts", self.slots_at_startup.len(), i64),
+            );
+        }
         Ok(())
     }
 
-    fn log_transaction(&mut self, transaction_info: DbTransaction) -> Result<(), GeyserPluginError> {
-        self.transaction_handler.update(&mut self.client.get_mut().unwrap(), transaction_info)
+    fn flush_live_updates(&mut self, force: bool) -> Result<(), GeyserPluginError> {
+        // observed discriminators ride along with the live updates
+        if let Some(discriminator_registry) = &mut self.discriminator_registry {
+            let query = discriminator_registry.flush_query(&self.prefix, force, &self.idl_registry);
+            if !query.is_empty() {
+                add_queued_bytes(Queue::LiveUpdates, query.len());
+                self.pending_live_updates.push([("discriminator_registry".to_string(), query)]);
+                self.pending_live_since.get_or_insert_with(Instant::now);
+            }
+        }
+        let is_due = match self.pending_live_since {
+            Some(pending_since) => force || self.pending_live_requests >= self.live_batch_size || pending_since.elapsed() >= self.flush_interval,
+            None => false,
+        };
+        if !is_due {
+            return Ok(());
+        }
+        let (client, statements) = connected(&mut self.connection, &self.pool, &self.account_handlers)?;
+
+        debug!("[flush_live_updates] length={}/{}", self.pending_live_requests, self.live_batch_size);
+        let mut updates = std::mem::take(&mut self.pending_live_updates);
+        sub_queued_bytes(Queue::LiveUpdates, updates.bytes());
+        let mut rows = std::mem::take(&mut self.pending_live_rows);
+        let mut closes = std::mem::take(&mut self.pending_live_closes);
+        sub_queued_bytes(Queue::LiveUpdates, closes.bytes());
+        let mut accounts = std::mem::take(&mut self.pending_live_accounts);
+        let mut dead_letters = std::mem::take(&mut self.pending_live_dead_letters);
+        let mut handler_flush = HandlerFlush::default();
+        handler_flush.add(updates.handler_counts());
+        handler_flush.add(rows.handler_counts());
+        handler_flush.add(closes.handler_counts());
+        let batch = (self.write_batches && !accounts.is_empty()).then(|| WriteBatch::new("live", accounts.iter().map(|(pubkey, slot)| (pubkey.as_slice(), *slot))));
+        if let Some(batch) = &batch {
+            updates.push([("write_batch".to_string(), WriteBatchHandler::insert(&self.prefix, batch))]);
+        }
+        let pending_since = self.pending_live_since.take();
+        let pending_requests = std::mem::take(&mut self.pending_live_requests);
+        let _span = child_span!(&Span::current(), "flush", kind = "live", accounts = accounts.len()).entered();
+        let mut measure = Measure::start("geyser-plugin-postgres-flush-live");
+        // the flush is committed whole with its write batch, on the async connection when the rows are pipelined
+        let result = match &mut self.pipeline {
+            Some(pipeline) => pipeline.execute_flush("flush_live_updates", &updates, &rows, &closes, &self.serialization_retry),
+            None => execute_transaction(client, "flush_live_updates", &self.serialization_retry, |transaction| {
+                let mut failures = execute_isolated_in(transaction, "flush_live_updates", &updates)?;
+                failures.extend(execute_rows(transaction, "flush_live_updates", &statements.handler_statements, &rows)?);
+                failures.extend(execute_isolated_in(transaction, "flush_live_updates", &closes)?);
+                Ok(failures)
+            }),
+        };
+        let failures = match result {
+            Ok(failures) => {
+                if self.dead_letters && !failures.is_empty() {
+                    let payloads = dead_letter_payloads(&self.account_selector, self.close_detection.is_some(), &dead_letters, false);
+                    DeadLetterHandler::record(
+                        client,
+                        &self.prefix,
+                        "flush_live_updates",
+                        &failures,
+                        batch.as_ref().map(|b| b.id),
+                        &payloads,
+                        &self.serialization_retry,
+                    );
+                }
+                failures
+            }
+            Err(err) => {
+                // the batch, with its write batch statement, is flushed again once reconnected
+                if self.retain_lost_batches && self.is_connection_lost() {
+                    add_queued_bytes(Queue::LiveUpdates, updates.bytes() + closes.bytes());
+                    self.pending_live_updates = updates;
+                    self.pending_live_rows = rows;
+                    self.pending_live_closes = closes;
+                    self.pending_live_dead_letters = dead_letters;
+                    self.pending_live_since = pending_since;
+                    self.pending_live_requests = pending_requests;
+                }
+                return Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
+                    msg: format!("[update_account]{} error=[{}]", batch_context(&batch), err),
+                })));
+            }
+        };
+        record_processed_slot(std::mem::take(&mut self.pending_live_slot));
+        rows.clear();
+        self.pending_live_rows = rows;
+        closes.clear();
+        self.pending_live_closes = closes;
+        accounts.clear();
+        self.pending_live_accounts = accounts;
+        dead_letters.drain(..).for_each(DbAccountInfo::recycle);
+        self.pending_live_dead_letters = dead_letters;
+        updates.clear();
+        self.pending_live_updates = updates;
+        measure.stop();
+        registry().observe_us(FLUSH_US, &[("kind", "live")], measure.as_us());
+        handler_flush.record(&failures, measure.as_us());
+        Ok(())
+    }
+
+    fn log_transaction(&mut self, transaction_info: &DbTransaction) -> Result<(), GeyserPluginError> {
+        self.refresh_selectors();
+        for handler_id in select_transaction_handlers(&self.transaction_selector, transaction_info) {
+            let (handler_name, query) = match &handler_id {
+                TransactionHandlerId::Transaction => {
+                    let (client, statements) = connected(&mut self.connection, &self.pool, &self.account_handlers)?;
+                    if let Err(err) = statements.transaction_handler.update(client, transaction_info) {
+                        registry().inc_counter(HANDLER_ERRORS_TOTAL, &[("handler", "transaction")], 1);
+                        let msg = format!("Failed to persist the update of transaction info to the PostgreSQL database. Error: {:?}", err);
+                        self.dead_letter_transaction(transaction_info, "transaction", "-- the transaction upsert statement\n", err);
+                        return Err(GeyserPluginError::AccountsUpdateError { msg });
+                    }
+                    registry().inc_counter(HANDLER_ROWS_TOTAL, &[("handler", "transaction")], 1);
+                    continue;
+                }
+                TransactionHandlerId::TransactionInstruction => ("transaction_instruction", TransactionHandler::instructions_update(&self.prefix, transaction_info)),
+                TransactionHandlerId::External(id) => match self.external_transaction_handlers.get(id) {
+                    Some(handler) => (id.as_str(), handler.transaction_update(transaction_info)),
+                    None => continue,
+                },
+                // the handlers left out of the build are rejected by the config validation
+                handler_id => match self.transaction_handlers.get(handler_id) {
+                    Some(handler) if handler.transaction_match(transaction_info) => (handler_id.as_str(), handler.transaction_update(transaction_info)),
+                    _ => continue,
+                },
+            };
+            if query.is_empty() {
+                continue;
+            }
+            let (client, _) = connected(&mut self.connection, &self.pool, &self.account_handlers)?;
+            if let Err(err) = execute_batch(client, "log_transaction", &query, &self.serialization_retry) {
+                registry().inc_counter(HANDLER_ERRORS_TOTAL, &[("handler", handler_name)], 1);
+                let msg = format!("[log_transaction][{}] error=[{}]", handler_name, err);
+                self.dead_letter_transaction(transaction_info, handler_name, &query, err);
+                return Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError { msg })));
+            }
+            registry().inc_counter(HANDLER_ROWS_TOTAL, &[("handler", handler_name)], 1);
+        }
+        record_processed_slot(transaction_info.slot as u64);
+        Ok(())
+    }
+
+    fn update_block_metadata(&mut self, block_info: &DbBlockInfo) -> Result<(), GeyserPluginError> {
+        let slot = block_info.slot as u64;
+        let (client, statements) = connected(&mut self.connection, &self.pool, &self.account_handlers)?;
+        statements.block_handler.update(client, block_info)?;
+        record_processed_slot(slot);
+        Ok(())
     }
 
-    fn update_block_metadata(&mut self, block_info: DbBlockInfo) -> Result<(), GeyserPluginError> {
-        self.block_handler.update(&mut self.client.get_mut().unwrap(), block_info)
+    fn update_selector_stats(&mut self, stats: &[DbSelectorStat]) -> Result<(), GeyserPluginError> {
+        let query = SelectorStatsHandler::update(&self.prefix, stats);
+        if query.is_empty() {
+            return Ok(());
+        }
+        let (client, _) = connected(&mut self.connection, &self.pool, &self.account_handlers)?;
+        match execute_batch(client, "update_selector_stats", &query, &self.serialization_retry) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
+                msg: format!("[update_selector_stats] error=[{}]", err),
+            }))),
+        }
     }
 }
 
 pub struct PostgresClientBuilder {}
 
 impl PostgresClientBuilder {
-    pub fn build_pararallel_postgres_client(config: &GeyserPluginPostgresConfig) -> Result<(ParallelClient, Option<u64>), GeyserPluginError> {
-        let mut client = SimplePostgresClient::connect_to_db(config)?;
-
-        let account_handlers = all_account_handlers();
-        let mut init_query = account_handlers.values().map(|a| a.init(config)).collect::<Vec<String>>().join("");
+    /// The schema DDL applied to the database when the plugin is loaded
+    pub fn init_query(config: &GeyserPluginPostgresConfig, custom_handlers: &CustomHandlers) -> Result<String, GeyserPluginError> {
+        let account_handlers = all_account_handlers(config, Arc::default());
+        let mut external_handlers = ExternalHandlers::load(config, custom_handlers)?;
+        external_handlers.account_handlers.retain(|handler_id, _| config.account_handler_enabled(handler_id));
+        let mut init_query = account_handlers
+            .values()
+            .chain(external_handlers.account_handlers.values())
+            .map(|a| a.init(config))
+            .collect::<Vec<String>>()
+            .join("");
+        init_query.push_str(&DecodedAccountTable::init(config));
         init_query.push_str(&SlotHandler::init(config));
+        init_query.push_str(&ForkCleanupHandler::init(config));
+        init_query.push_str(&CloseDetectionHandler::init(config));
         init_query.push_str(&BlockHandler::init(config));
         init_query.push_str(&TransactionHandler::init(config));
+        for handler in all_transaction_handlers(config, Arc::default()).values() {
+            init_query.push_str(&handler.init(config));
+        }
+        for handler in external_handlers.transaction_handlers.values() {
+            init_query.push_str(&handler.init(config));
+        }
+        init_query.push_str(&DiscriminatorRegistry::init(config));
+        init_query.push_str(&promoted_columns_init(config));
+        init_query.push_str(&SelectorStatsHandler::init(config));
+        init_query.push_str(&HeartbeatHandler::init(config));
+        init_query.push_str(&EpochHandler::init(config));
+        init_query.push_str(&PluginStatsHandler::init(config));
+        init_query.push_str(&StartupSummaryHandler::init(config));
+        init_query.push_str(&StartupProgressHandler::init(config));
+        init_query.push_str(&UnloadSummaryHandler::init(config));
+        init_query.push_str(&WriteBatchHandler::init(config));
+        init_query.push_str(&DeadLetterHandler::init(config));
+        init_query.push_str(&SchemaMigrations::init(config));
+        let prefix = TablePrefix::new(config);
+        let handlers = account_handlers
+            .iter()
+            .map(|(id, h)| (id.as_str(), h))
+            .chain(external_handlers.account_handlers.iter().map(|(id, h)| (id.as_str(), h)));
+        for (handler_id, handler) in handlers.filter(|(_, h)| h.enabled(config)) {
+            init_query.push_str(&SchemaMigrations::migrate(&prefix, handler_id, &handler.schema_migrations(config)));
+        }
+        init_query.push_str(&TokenHolderViewsHandler::init(config));
+        let init_query = apply_logical_replication(&apply_index_config(&init_query, &config.indexes, &prefix), &config.logical_replication);
+        Ok(match &config.schema {
+            Some(schema) => format!("CREATE SCHEMA IF NOT EXISTS {};{}", schema, init_query),
+            None => init_query,
+        })
+    }
+
+    pub fn build_pararallel_postgres_client(config: &GeyserPluginPostgresConfig, custom_handlers: &CustomHandlers) -> Result<(ParallelClient, Option<u64>), GeyserPluginError> {
+        if !config.writes_postgres() {
+            info!("[build_pararallel_postgres_client] publishing to the sinks only");
+            return Ok((ParallelClient::new(config, Arc::default(), custom_handlers, None)?, None));
+        }
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+
+        let init_query = Self::init_query(config, custom_handlers)?;
         if let Err(err) = client.batch_execute(&init_query) {
             return Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
                 msg: format!("[build_pararallel_postgres_client] error=[{}]", err),
             })));
         };
 
+        let prefix = TablePrefix::new(config);
         let batch_starting_slot = match config.skip_upsert_existing_accounts_at_startup {
             true => {
-                let batch_slot_bound = SlotHandler::get_highest_available_slot(&mut client)?.saturating_sub(config.safe_batch_starting_slot_cushion);
+                let batch_slot_bound = SlotHandler::get_highest_available_slot(&mut client, &prefix)?.saturating_sub(config.safe_batch_starting_slot_cushion);
                 info!("[batch_starting_slot] bound={}", batch_slot_bound);
                 Some(batch_slot_bound)
             }
             false => None,
         };
 
-        ParallelClient::new(config).map(|v| (v, batch_starting_slot))
+        let slot_watermarks = SlotHandler::get_watermarks(&mut client, &prefix)?;
+        let startup_tracker = StartupTracker::begin(&mut client);
+        if config.startup_progress {
+            StartupProgressHandler::begin(&mut client, &prefix)?;
+        }
+        let idl_registry = Arc::new(IdlRegistry::load(config)?);
+        // opened once the tables exist, the workers prepare their statements on the pooled connections
+        let pool = config.pool.as_ref().map(|pool| ConnectionPool::new(config, pool)).transpose()?;
+        let databases = config
+            .databases
+            .iter()
+            .map(|database| Self::build_database_client(&database.worker_config(config), idl_registry.clone(), custom_handlers))
+            .collect::<Result<Vec<ParallelClient>, GeyserPluginError>>()?;
+        let mut parallel_client = ParallelClient::new(config, idl_registry, custom_handlers, pool)?;
+        parallel_client.set_startup_tracker(startup_tracker);
+        parallel_client.set_slot_watermarks(slot_watermarks);
+        parallel_client.set_databases(databases);
+        Ok((parallel_client, batch_starting_slot))
+    }
+
+    /// The client of a database of `databases`, its schema is initialized like that of the main database
+    fn build_database_client(config: &GeyserPluginPostgresConfig, idl_registry: Arc<IdlRegistry>, custom_handlers: &CustomHandlers) -> Result<ParallelClient, GeyserPluginError> {
+        info!("[build_database_client] database=[{:?}]", config.database);
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        let init_query = Self::init_query(config, custom_handlers)?;
+        if let Err(err) = client.batch_execute(&init_query) {
+            return Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
+                msg: format!("[build_database_client] database=[{:?}] error=[{}]", config.database, err),
+            })));
+        };
+        let pool = config.pool.as_ref().map(|pool| ConnectionPool::new(config, pool)).transpose()?;
+        ParallelClient::new(config, idl_registry, custom_handlers, pool)
     }
 }
@@ -0,0 +1,97 @@
+use super::SimplePostgresClient;
+use crate::config::GeyserPluginPostgresConfig;
+use crate::geyser_plugin_postgres::GeyserPluginPostgresError;
+use crate::postgres_client::db_errors::record_db_error;
+use log::*;
+use postgres::Client;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::sleep;
+use std::thread::Builder;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How often the writer thread checks whether it is stopping
+const EXIT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Runs a statement, or a task, every `interval` from a dedicated thread on its own connection, so
+/// the writes keep going while the plugin is loaded even if the workers are wedged.
+pub struct PeriodicWriter {
+    exit: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// The connection of a writer, connecting again after it was dropped on an error
+pub(crate) fn connect<'a>(client: &'a mut Option<Client>, plugin_config: &GeyserPluginPostgresConfig) -> Result<&'a mut Client, GeyserPluginError> {
+    if client.is_none() {
+        *client = Some(SimplePostgresClient::connect_to_db(plugin_config)?);
+    }
+    Ok(client.as_mut().unwrap())
+}
+
+pub(crate) fn execute(client: &mut Option<Client>, plugin_config: &GeyserPluginPostgresConfig, name: &'static str, query: &str) -> Result<(), GeyserPluginError> {
+    if let Err(err) = connect(client, plugin_config)?.batch_execute(query) {
+        record_db_error(name, &err);
+        // reconnect on the next write
+        *client = None;
+        return Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError { msg: err.to_string() })));
+    }
+    Ok(())
+}
+
+impl PeriodicWriter {
+    /// Connects right away so a wrong configuration fails the plugin load
+    pub fn start<F>(name: &'static str, plugin_config: &GeyserPluginPostgresConfig, interval: Duration, mut query: F) -> Result<Self, GeyserPluginError>
+    where
+        F: FnMut() -> String + Send + 'static,
+    {
+        Self::spawn(name, plugin_config, interval, move |client, plugin_config, _| {
+            if let Err(err) = execute(client, plugin_config, name, &query()) {
+                error!("[{}] {}", name, err);
+            }
+        })
+    }
+
+    /// Like `start`, running `task` with the connection, dropped by the task after an error, and whether the writer is
+    /// stopping, for the tasks writing more than a statement
+    pub fn spawn<F>(name: &'static str, plugin_config: &GeyserPluginPostgresConfig, interval: Duration, mut task: F) -> Result<Self, GeyserPluginError>
+    where
+        F: FnMut(&mut Option<Client>, &GeyserPluginPostgresConfig, &dyn Fn() -> bool) + Send + 'static,
+    {
+        let mut client = Some(SimplePostgresClient::connect_to_db(plugin_config)?);
+        let plugin_config = plugin_config.clone();
+        let exit = Arc::new(AtomicBool::new(false));
+        let exit_writer = exit.clone();
+        let thread = Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                let stop = || exit_writer.load(Ordering::Relaxed);
+                let mut last_write: Option<Instant> = None;
+                while !stop() {
+                    if last_write.map_or(true, |last_write| last_write.elapsed() >= interval) {
+                        last_write = Some(Instant::now());
+                        task(&mut client, &plugin_config, &stop);
+                    }
+                    sleep(EXIT_CHECK_INTERVAL);
+                }
+            })
+            .unwrap();
+        Ok(Self { exit, thread: Some(thread) })
+    }
+
+    pub fn stop(&mut self) {
+        self.exit.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for PeriodicWriter {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
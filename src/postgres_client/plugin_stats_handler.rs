@@ -0,0 +1,196 @@
+use crate::config::GeyserPluginPostgresConfig;
+use crate::metrics::registry;
+use crate::metrics::BYTES_WRITTEN_TOTAL;
+use crate::metrics::DB_CONNECTS_TOTAL;
+use crate::metrics::DB_ERRORS_TOTAL;
+use crate::metrics::ERRORS_TOTAL;
+use crate::metrics::QUEUE_DEPTH;
+use crate::metrics::REQUESTS_TOTAL;
+use crate::postgres_client::periodic_writer::PeriodicWriter;
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use chrono::Utc;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use serde_json::json;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use std::time::Instant;
+
+/// * The `plugin_stats` section writes the plugin counters of every interval to `plugin_stats`.
+/// "plugin_stats" : { "instance" : "validator-1", "interval_secs" : 60 }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginStatsConfig {
+    /// Identifies this plugin when several validators write to the same database
+    pub instance: String,
+    /// Seconds between two rows
+    pub interval_secs: u64,
+}
+
+impl Default for PluginStatsConfig {
+    fn default() -> Self {
+        Self {
+            instance: "default".to_string(),
+            interval_secs: 60,
+        }
+    }
+}
+
+/// The cumulative plugin counters at one point in time
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    /// Requests run by the workers, by request
+    pub processed: BTreeMap<String, u64>,
+    /// Size of the statements sent to the database
+    pub bytes_written: u64,
+    /// Database connections opened by the workers and the background writers
+    pub connects: u64,
+    /// Failed requests, by request
+    pub errors: BTreeMap<String, u64>,
+    /// Failed statements, by SQLSTATE class
+    pub db_errors: BTreeMap<String, u64>,
+}
+
+fn delta(current: &BTreeMap<String, u64>, previous: &BTreeMap<String, u64>) -> BTreeMap<String, u64> {
+    current
+        .iter()
+        .map(|(key, value)| (key.clone(), value.saturating_sub(previous.get(key).copied().unwrap_or_default())))
+        .filter(|(_, value)| *value > 0)
+        .collect()
+}
+
+impl StatsSnapshot {
+    pub fn take() -> Self {
+        Self {
+            processed: registry().counters_by(REQUESTS_TOTAL, "request"),
+            bytes_written: registry().counter(BYTES_WRITTEN_TOTAL, &[]),
+            connects: registry().counter(DB_CONNECTS_TOTAL, &[]),
+            errors: registry().counters_by(ERRORS_TOTAL, "request"),
+            db_errors: registry().counters_by(DB_ERRORS_TOTAL, "class"),
+        }
+    }
+
+    /// The counts since `previous`
+    pub fn since(&self, previous: &Self) -> Self {
+        Self {
+            processed: delta(&self.processed, &previous.processed),
+            bytes_written: self.bytes_written.saturating_sub(previous.bytes_written),
+            connects: self.connects.saturating_sub(previous.connects),
+            errors: delta(&self.errors, &previous.errors),
+            db_errors: delta(&self.db_errors, &previous.db_errors),
+        }
+    }
+}
+
+pub struct PluginStatsHandler {}
+
+impl PluginStatsHandler {
+    pub fn init(config: &GeyserPluginPostgresConfig) -> String {
+        if config.plugin_stats.is_none() {
+            return "".to_string();
+        }
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}plugin_stats (
+                instance VARCHAR(64) NOT NULL,
+                interval_ms BIGINT NOT NULL,
+                queue_length BIGINT NOT NULL,
+                processed JSONB NOT NULL,
+                bytes_written BIGINT NOT NULL,
+                connects BIGINT NOT NULL,
+                errors JSONB NOT NULL,
+                db_errors JSONB NOT NULL,
+                created_on TIMESTAMP NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}plugin_stats_instance_created_on ON {prefix}plugin_stats (instance, created_on);
+        ",
+            prefix = TablePrefix::new(config)
+        );
+    }
+
+    pub fn insert(prefix: &TablePrefix, config: &PluginStatsConfig, interval_ms: u64, queue_length: i64, stats: &StatsSnapshot) -> String {
+        format!(
+            "
+                INSERT INTO {9}plugin_stats (instance, interval_ms, queue_length, processed, bytes_written, connects, errors, db_errors, created_on) \
+                VALUES ({0}, {1}, {2}, {3}, {4}, {5}, {6}, {7}, '{8}');
+            ",
+            config.instance.sql_literal(),
+            interval_ms,
+            queue_length,
+            json!(stats.processed).to_string().sql_literal(),
+            stats.bytes_written,
+            stats.connects,
+            json!(stats.errors).to_string().sql_literal(),
+            json!(stats.db_errors).to_string().sql_literal(),
+            &Utc::now().naive_utc(),
+            prefix
+        )
+    }
+}
+
+/// Writes a `plugin_stats` row with the counts of the last interval every `interval_secs`
+pub struct PluginStats {
+    writer: PeriodicWriter,
+}
+
+impl PluginStats {
+    pub fn start(plugin_config: &GeyserPluginPostgresConfig) -> Result<Option<Self>, GeyserPluginError> {
+        let config = match &plugin_config.plugin_stats {
+            Some(config) => config.clone(),
+            None => return Ok(None),
+        };
+        let interval = Duration::from_secs(config.interval_secs.max(1));
+        let mut previous = (Instant::now(), StatsSnapshot::take());
+        let prefix = TablePrefix::new(plugin_config);
+        let writer = PeriodicWriter::start("plugin_stats", plugin_config, interval, move || {
+            let current = (Instant::now(), StatsSnapshot::take());
+            let interval_ms = current.0.duration_since(previous.0).as_millis() as u64;
+            let queue_length = registry().gauge(QUEUE_DEPTH, &[]).unwrap_or_default();
+            let query = PluginStatsHandler::insert(&prefix, &config, interval_ms, queue_length, &current.1.since(&previous.1));
+            previous = current;
+            query
+        })?;
+        Ok(Some(Self { writer }))
+    }
+
+    pub fn stop(&mut self) {
+        self.writer.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plugin_stats() {
+        let previous = StatsSnapshot {
+            processed: BTreeMap::from([("update_account".to_string(), 10)]),
+            bytes_written: 1000,
+            connects: 10,
+            ..StatsSnapshot::default()
+        };
+        let current = StatsSnapshot {
+            processed: BTreeMap::from([("update_account".to_string(), 25), ("update_slot".to_string(), 2)]),
+            bytes_written: 1500,
+            connects: 11,
+            errors: BTreeMap::from([("update_account".to_string(), 1)]),
+            ..StatsSnapshot::default()
+        };
+        let stats = current.since(&previous);
+        assert_eq!(stats.processed, BTreeMap::from([("update_account".to_string(), 15), ("update_slot".to_string(), 2)]));
+        assert_eq!((stats.bytes_written, stats.connects), (500, 1));
+
+        let query = PluginStatsHandler::insert(&TablePrefix::default(), &PluginStatsConfig::default(), 60000, 42, &stats);
+        assert!(query.contains("VALUES ('default', 60000, 42, '{\"update_account\":15,\"update_slot\":2}', 500, 1, '{\"update_account\":1}', '{}',"));
+
+        let config = PluginStatsConfig {
+            instance: "validator's\0".to_string(),
+            ..PluginStatsConfig::default()
+        };
+        let query = PluginStatsHandler::insert(&TablePrefix::default(), &config, 60000, 42, &stats);
+        assert!(query.contains("VALUES ('validator''s', 60000,"), "{}", query);
+    }
+}
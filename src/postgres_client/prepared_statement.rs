@@ -0,0 +1,49 @@
+use postgres::types::ToSql;
+use postgres::GenericClient;
+use postgres::Statement;
+use postgres::Transaction;
+
+/// A statement with typed parameters. It is prepared once for the session, or with `pgbouncer` in the transaction
+/// of every execution and closed before it commits, as a server connection in transaction pooling mode only
+/// serves the client for the duration of a transaction.
+pub enum PreparedStatement {
+    Session(Statement),
+    PerTransaction(String),
+}
+
+impl PreparedStatement {
+    /// Prepare the statement in the transaction the client prepares all of its statements in when it connects,
+    /// so the types of their parameters are looked up and cached by the client in a single transaction
+    pub fn prepare(transaction: &mut Transaction, query: &str, pgbouncer: bool) -> Result<Self, postgres::Error> {
+        let statement = transaction.prepare(query)?;
+        match pgbouncer {
+            false => Ok(Self::Session(statement)),
+            true => Ok(Self::PerTransaction(query.to_string())),
+        }
+    }
+
+    /// Run before the transaction the statements were prepared in commits. With `pgbouncer` the statements the
+    /// client prepared to look up the types are deallocated so they aren't left behind on the server connection.
+    pub fn release(transaction: &mut Transaction, pgbouncer: bool) -> Result<(), postgres::Error> {
+        match pgbouncer {
+            true => transaction.batch_execute("DEALLOCATE ALL;"),
+            false => Ok(()),
+        }
+    }
+
+    pub fn execute(&self, client: &mut impl GenericClient, params: &[&(dyn ToSql + Sync)]) -> Result<u64, postgres::Error> {
+        match self {
+            Self::Session(statement) => client.execute(statement, params),
+            Self::PerTransaction(query) => {
+                let mut transaction = client.transaction()?;
+                let statement = transaction.prepare(query)?;
+                let rows = transaction.execute(&statement, params);
+                // closed before the commit releases the server connection
+                drop(statement);
+                let rows = rows?;
+                transaction.commit()?;
+                Ok(rows)
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::config::GeyserPluginPostgresConfig;
+use crate::metrics::registry;
+use crate::metrics::RETENTION_ROWS_DELETED_TOTAL;
+use crate::postgres_client::db_errors::record_db_error;
+use crate::postgres_client::is_identifier;
+use crate::postgres_client::periodic_writer::connect;
+use crate::postgres_client::periodic_writer::PeriodicWriter;
+use crate::postgres_client::table_prefix::TablePrefix;
+use log::*;
+use postgres::Client;
+use postgres::SimpleQueryMessage;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use std::time::Instant;
+
+/// * The `retention` section deletes the rows older than the retention of their table from a dedicated thread.
+/// "retention" : { "interval_secs" : 3600, "batch_size" : 10000, "tables" : { "transaction" : { "max_age_secs" : 604800 } } }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Seconds between two prunings
+    pub interval_secs: u64,
+    /// The rows deleted per statement, so a pruning doesn't hold its locks for long
+    pub batch_size: u64,
+    /// The retention of each pruned table
+    pub tables: BTreeMap<String, TableRetention>,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 3600,
+            batch_size: 10000,
+            tables: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TableRetention {
+    /// The rows older than this many seconds are deleted
+    pub max_age_secs: u64,
+    /// The UTC `TIMESTAMP` column the age of a row is read from
+    pub column: String,
+}
+
+impl Default for TableRetention {
+    fn default() -> Self {
+        Self {
+            max_age_secs: 0,
+            column: "updated_on".to_string(),
+        }
+    }
+}
+
+impl RetentionConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_secs == 0 || self.batch_size == 0 {
+            return Err("retention \"interval_secs\" and \"batch_size\" must be greater than 0".to_string());
+        }
+        if self.tables.is_empty() {
+            return Err("retention \"tables\" must name at least one table".to_string());
+        }
+        for (table, retention) in &self.tables {
+            if !is_identifier(table) || !is_identifier(&retention.column) {
+                return Err(format!("retention.tables \"{}\" is not a table with a \"{}\" column", table, retention.column));
+            }
+            if retention.max_age_secs == 0 {
+                return Err(format!("retention.tables.{}.max_age_secs must be greater than 0", table));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct RetentionHandler {}
+
+impl RetentionHandler {
+    /// Delete up to `batch_size` rows of the table older than its retention
+    pub fn prune(table: &str, retention: &TableRetention, batch_size: u64) -> String {
+        format!(
+            "DELETE FROM {0} WHERE ctid IN (SELECT ctid FROM {0} WHERE {1} < (now() AT TIME ZONE 'UTC') - interval '{2} seconds' LIMIT {3});",
+            table, retention.column, retention.max_age_secs, batch_size
+        )
+    }
+}
+
+/// Delete the expired rows of a table batch by batch, each in its own transaction, until a batch comes back short.
+/// The tables of the handlers that aren't enabled are skipped. The statements are sent with the simple query protocol,
+/// nothing is prepared on a connection PgBouncer may hand to another client in between.
+fn prune_table(client: &mut Client, prefix: &TablePrefix, table: &str, retention: &TableRetention, batch_size: u64, stop: &dyn Fn() -> bool) -> Result<u64, postgres::Error> {
+    // the table names are checked by the config validation
+    let relation = prefix.table(table);
+    let exists = client
+        .simple_query(&format!("SELECT to_regclass('{}') IS NOT NULL", relation))?
+        .iter()
+        .any(|message| matches!(message, SimpleQueryMessage::Row(row) if row.get(0) == Some("t")));
+    if !exists {
+        return Ok(0);
+    }
+    let query = RetentionHandler::prune(&relation, retention, batch_size);
+    let mut deleted = 0;
+    loop {
+        let rows = client
+            .simple_query(&query)?
+            .iter()
+            .map(|message| match message {
+                SimpleQueryMessage::CommandComplete(rows) => *rows,
+                _ => 0,
+            })
+            .sum::<u64>();
+        deleted += rows;
+        registry().inc_counter(RETENTION_ROWS_DELETED_TOTAL, &[("table", table)], rows);
+        if rows < batch_size || stop() {
+            return Ok(deleted);
+        }
+    }
+}
+
+/// Prunes the tables every `interval_secs` from a dedicated thread on its own connection
+pub struct Retention {
+    writer: PeriodicWriter,
+}
+
+impl Retention {
+    pub fn start(plugin_config: &GeyserPluginPostgresConfig) -> Result<Option<Self>, GeyserPluginError> {
+        let config = match &plugin_config.retention {
+            Some(config) => config.clone(),
+            None => return Ok(None),
+        };
+        let prefix = TablePrefix::new(plugin_config);
+        let interval = Duration::from_secs(config.interval_secs);
+        let writer = PeriodicWriter::spawn("retention", plugin_config, interval, move |client, plugin_config, stop| {
+            for (table, retention) in &config.tables {
+                let connected = match connect(client, plugin_config) {
+                    Ok(connected) => connected,
+                    Err(err) => {
+                        error!("[retention] failed to connect: {}", err);
+                        return;
+                    }
+                };
+                let started = Instant::now();
+                match prune_table(connected, &prefix, table, retention, config.batch_size, stop) {
+                    Ok(deleted) => info!("[retention] pruned table={} deleted={} elapsed_ms={}", table, deleted, started.elapsed().as_millis()),
+                    Err(err) => {
+                        record_db_error("retention", &err);
+                        error!("[retention] failed to prune {}: {}", table, err);
+                        // reconnect on the next table
+                        *client = None;
+                    }
+                }
+                if stop() {
+                    return;
+                }
+            }
+        })?;
+        Ok(Some(Self { writer }))
+    }
+
+    /// Stop the thread once the batch in progress, if any, is deleted
+    pub fn stop(&mut self) {
+        self.writer.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retention() {
+        let week = TableRetention {
+            max_age_secs: 604800,
+            ..TableRetention::default()
+        };
+        let mut config = RetentionConfig {
+            tables: BTreeMap::from([("transaction".to_string(), week.clone())]),
+            ..RetentionConfig::default()
+        };
+        assert!(config.validate().is_ok());
+        assert_eq!(
+            RetentionHandler::prune("transaction", &week, 100),
+            "DELETE FROM transaction WHERE ctid IN (SELECT ctid FROM transaction WHERE updated_on < (now() AT TIME ZONE 'UTC') - interval '604800 seconds' LIMIT 100);"
+        );
+
+        config.tables.insert("slot; DROP TABLE block".to_string(), week.clone());
+        assert!(config.validate().is_err());
+        config.tables = BTreeMap::from([("slot".to_string(), TableRetention::default())]);
+        assert!(config.validate().is_err());
+        assert!(RetentionConfig::default().validate().is_err());
+    }
+}
@@ -0,0 +1,70 @@
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+
+/// Records the schema version applied for each handler and runs the migrations of the handlers whose version was bumped
+pub struct SchemaMigrations {}
+
+impl SchemaMigrations {
+    pub fn init(config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {}handler_schema_version (
+                handler VARCHAR(64) NOT NULL,
+                version INT NOT NULL,
+                updated_on TIMESTAMP NOT NULL,
+                PRIMARY KEY (handler)
+            );
+        ",
+            TablePrefix::new(config)
+        );
+    }
+
+    /// Apply the migrations after the handler's recorded version, version `n` being `migrations[n - 1]`.
+    /// Each migration runs in the same statement as the version bump so a failed migration is retried on the next load.
+    pub fn migrate(prefix: &TablePrefix, handler: &str, migrations: &[String]) -> String {
+        let handler = handler.to_string().sql_literal();
+        migrations
+            .iter()
+            .enumerate()
+            .map(|(i, migration)| {
+                format!(
+                    "
+                    DO $migration$ BEGIN
+                        IF NOT EXISTS (SELECT 1 FROM {3}handler_schema_version WHERE handler = {0} AND version >= {1}) THEN
+                            {2}
+                            INSERT INTO {3}handler_schema_version AS v (handler, version, updated_on) VALUES ({0}, {1}, now()) \
+                            ON CONFLICT (handler) DO UPDATE SET version=excluded.version, updated_on=excluded.updated_on;
+                        END IF;
+                    END $migration$;
+                ",
+                    handler,
+                    i + 1,
+                    migration,
+                    prefix
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate() {
+        let query = SchemaMigrations::migrate(
+            &TablePrefix::default(),
+            "token_manager",
+            &[
+                "ALTER TABLE token_manager ADD COLUMN IF NOT EXISTS a BIGINT;".to_string(),
+                "ALTER TABLE token_manager ADD COLUMN IF NOT EXISTS b BIGINT;".to_string(),
+            ],
+        );
+        assert_eq!(query.matches("DO $migration$").count(), 2);
+        assert!(query.contains("WHERE handler = 'token_manager' AND version >= 2"));
+        assert!(query.find("ADD COLUMN IF NOT EXISTS a").unwrap() < query.find("ADD COLUMN IF NOT EXISTS b").unwrap());
+        assert!(SchemaMigrations::migrate(&TablePrefix::default(), "unknown_account", &[]).is_empty());
+    }
+}
@@ -0,0 +1,55 @@
+use crate::postgres_client::table_prefix::TablePrefix;
+use chrono::Utc;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DbSelectorStat {
+    /// `accounts`, `owners` or `mentions` for selected items, the selector name for skipped items
+    pub selector: String,
+    /// The matched selector entry, `*` for wildcards and skipped items
+    pub entry: String,
+    pub selected: i64,
+    pub skipped: i64,
+}
+
+pub struct SelectorStatsHandler {}
+
+impl SelectorStatsHandler {
+    pub fn init(config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {}selector_stats (
+                selector VARCHAR(32) NOT NULL,
+                entry VARCHAR(44) NOT NULL,
+                selected BIGINT NOT NULL,
+                skipped BIGINT NOT NULL,
+                updated_on TIMESTAMP NOT NULL,
+                PRIMARY KEY (selector, entry)
+            );
+        ",
+            TablePrefix::new(config)
+        );
+    }
+
+    pub fn update(prefix: &TablePrefix, stats: &[DbSelectorStat]) -> String {
+        stats
+            .iter()
+            .map(|stat| {
+                format!(
+                    "
+                    INSERT INTO {5}selector_stats AS stats (selector, entry, selected, skipped, updated_on) \
+                    VALUES ('{0}', '{1}', {2}, {3}, '{4}') \
+                    ON CONFLICT (selector, entry) DO UPDATE SET selected=stats.selected + excluded.selected, \
+                    skipped=stats.skipped + excluded.skipped, updated_on=excluded.updated_on;
+                ",
+                    &stat.selector,
+                    &stat.entry,
+                    &stat.selected,
+                    &stat.skipped,
+                    &Utc::now().naive_utc(),
+                    prefix,
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("")
+    }
+}
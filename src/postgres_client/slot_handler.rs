@@ -1,43 +1,171 @@
+use crate::metrics::registry;
+use crate::metrics::SLOT_GAPS_TOTAL;
+use crate::metrics::SLOT_WATERMARK;
+use crate::postgres_client::table_prefix::TablePrefix;
 use chrono::Utc;
+use log::*;
 use postgres::Client;
+use postgres::SimpleQueryMessage;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
 use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+use std::collections::HashMap;
+
+/// The channel `slot_finality_notify` sends the confirmed and rooted slots on
+pub const SLOT_FINALITY_CHANNEL: &str = "slot_finality";
+
+/// The slots between the watermark of a status and the parent of the next slot reaching it, which reached the
+/// status without being notified
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SlotGap {
+    pub first_slot: u64,
+    pub last_slot: u64,
+}
+
+/// The highest slot notified at each status, in the order the validator notified them
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SlotWatermarks {
+    slots: HashMap<&'static str, u64>,
+}
+
+impl SlotWatermarks {
+    /// Raise the watermark of the slot's status, returning the gap its parent reveals. A slot below the watermark
+    /// is on another fork, and a slot without a parent can't reveal a gap
+    pub fn update(&mut self, slot: u64, parent: Option<u64>, status: SlotStatus) -> Option<SlotGap> {
+        let watermark = self.slots.entry(status.as_str()).or_insert(slot);
+        let gap = match parent {
+            Some(parent) if parent > *watermark => Some(SlotGap {
+                first_slot: *watermark + 1,
+                last_slot: parent,
+            }),
+            _ => None,
+        };
+        *watermark = slot.max(*watermark);
+        registry().set_gauge(SLOT_WATERMARK, &[("status", status.as_str())], *watermark as i64);
+        if let Some(gap) = &gap {
+            warn!("[slot_watermark] status=[{}] missing slots=[{}..={}]", status.as_str(), gap.first_slot, gap.last_slot);
+            registry().inc_counter(SLOT_GAPS_TOTAL, &[("status", status.as_str())], 1);
+        }
+        gap
+    }
+}
 
 pub struct SlotHandler {}
 
 impl SlotHandler {
-    pub fn init(_config: &crate::config::GeyserPluginPostgresConfig) -> String {
-        return "
-            CREATE TABLE IF NOT EXISTS slot (
+    pub fn init(config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}slot (
                 slot BIGINT PRIMARY KEY,
                 parent BIGINT,
                 status VARCHAR(16) NOT NULL,
                 updated_on TIMESTAMP NOT NULL
             );
-        "
-        .to_string();
+            CREATE TABLE IF NOT EXISTS {prefix}slot_watermark (
+                status VARCHAR(16) PRIMARY KEY,
+                slot BIGINT NOT NULL,
+                parent BIGINT,
+                updated_on TIMESTAMP NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS {prefix}slot_gap (
+                status VARCHAR(16) NOT NULL,
+                first_slot BIGINT NOT NULL,
+                last_slot BIGINT NOT NULL,
+                detected_on TIMESTAMP NOT NULL,
+                PRIMARY KEY (status, first_slot)
+            );
+        ",
+            prefix = TablePrefix::new(config)
+        );
     }
 
-    pub fn update(slot: u64, parent: Option<u64>, status: SlotStatus) -> String {
+    pub fn update(prefix: &TablePrefix, slot: u64, parent: Option<u64>, status: SlotStatus) -> String {
         format!(
             "
-                INSERT INTO slot (slot, parent, status, updated_on) \
+                INSERT INTO {4}slot AS s (slot, parent, status, updated_on) \
                 VALUES ({0}, {1}, '{2}', '{3}') \
-                ON CONFLICT (slot) DO UPDATE SET parent=excluded.parent, status=excluded.status, updated_on=excluded.updated_on;
+                ON CONFLICT (slot) DO UPDATE SET parent=COALESCE(excluded.parent, s.parent), status=excluded.status, updated_on=excluded.updated_on;
             ",
             &slot,
             parent.map_or("NULL".to_string(), |p| p.to_string()),
             &status.as_str(),
-            &Utc::now().naive_utc()
+            &Utc::now().naive_utc(),
+            prefix
         )
     }
 
-    pub fn get_highest_available_slot(client: &mut Client) -> Result<u64, GeyserPluginError> {
-        match client.query_opt("SELECT slot FROM slot ORDER BY slot DESC LIMIT 1;", &[]) {
-            Ok(opt_slot) => Ok(opt_slot
-                .map(|row| {
-                    let raw_slot: i64 = row.get(0);
-                    raw_slot as u64
+    /// Raises the watermark of the slot's status, and records the gap its parent revealed. The watermark never goes
+    /// back, as the slots are written by different workers
+    pub fn watermark(prefix: &TablePrefix, slot: u64, parent: Option<u64>, status: SlotStatus, gap: Option<&SlotGap>) -> String {
+        let now = Utc::now().naive_utc();
+        let mut query = format!(
+            "
+                INSERT INTO {4}slot_watermark AS w (status, slot, parent, updated_on) \
+                VALUES ('{0}', {1}, {2}, '{3}') \
+                ON CONFLICT (status) DO UPDATE SET slot=excluded.slot, parent=excluded.parent, updated_on=excluded.updated_on \
+                WHERE w.slot < excluded.slot;
+            ",
+            status.as_str(),
+            slot,
+            parent.map_or("NULL".to_string(), |p| p.to_string()),
+            now,
+            prefix
+        );
+        if let Some(gap) = gap {
+            query.push_str(&format!(
+                "
+                    INSERT INTO {}slot_gap (status, first_slot, last_slot, detected_on) \
+                    VALUES ('{}', {}, {}, '{}') \
+                    ON CONFLICT (status, first_slot) DO NOTHING;
+                ",
+                prefix,
+                status.as_str(),
+                gap.first_slot,
+                gap.last_slot,
+                now
+            ));
+        }
+        query
+    }
+
+    /// The watermarks recorded by the previous runs, the slots missed while the plugin was down are reported as
+    /// a gap by the first slot notified at each status
+    pub fn get_watermarks(client: &mut Client, prefix: &TablePrefix) -> Result<SlotWatermarks, GeyserPluginError> {
+        let mut watermarks = SlotWatermarks::default();
+        let messages = client
+            .simple_query(&format!("SELECT status, slot FROM {}slot_watermark;", prefix))
+            .map_err(|err| GeyserPluginError::SlotStatusUpdateError {
+                msg: format!("Failed to receive the slot watermarks from PostgreSQL database. Error: {:?}", err),
+            })?;
+        for message in messages {
+            if let SimpleQueryMessage::Row(row) = message {
+                let status = [SlotStatus::Processed, SlotStatus::Confirmed, SlotStatus::Rooted]
+                    .into_iter()
+                    .find(|status| row.get(0) == Some(status.as_str()));
+                if let (Some(status), Some(slot)) = (status, row.get(1).and_then(|slot| slot.parse::<u64>().ok())) {
+                    watermarks.slots.insert(status.as_str(), slot);
+                }
+            }
+        }
+        Ok(watermarks)
+    }
+
+    /// Notifies the listeners of the slot reaching `status`, sent once the batch updating its row commits
+    pub fn finality_notify(slot: u64, status: SlotStatus) -> Option<String> {
+        match status {
+            SlotStatus::Confirmed | SlotStatus::Rooted => Some(format!("SELECT pg_notify('{}', '{{\"slot\":{},\"status\":\"{}\"}}');", SLOT_FINALITY_CHANNEL, slot, status.as_str())),
+            SlotStatus::Processed => None,
+        }
+    }
+
+    pub fn get_highest_available_slot(client: &mut Client, prefix: &TablePrefix) -> Result<u64, GeyserPluginError> {
+        // a simple query leaves no prepared statement on the server connection
+        match client.simple_query(&format!("SELECT slot FROM {}slot ORDER BY slot DESC LIMIT 1;", prefix)) {
+            Ok(messages) => Ok(messages
+                .iter()
+                .find_map(|message| match message {
+                    SimpleQueryMessage::Row(row) => row.get(0).and_then(|slot| slot.parse::<u64>().ok()),
+                    _ => None,
                 })
                 .unwrap_or(0)),
             Err(err) => Err(GeyserPluginError::SlotStatusUpdateError {
@@ -46,3 +174,39 @@ impl SlotHandler {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finality_notify() {
+        assert_eq!(SlotHandler::finality_notify(42, SlotStatus::Processed), None);
+        assert_eq!(
+            SlotHandler::finality_notify(42, SlotStatus::Confirmed).unwrap(),
+            "SELECT pg_notify('slot_finality', '{\"slot\":42,\"status\":\"confirmed\"}');"
+        );
+        assert!(SlotHandler::finality_notify(42, SlotStatus::Rooted).unwrap().contains("\"status\":\"rooted\""));
+    }
+
+    #[test]
+    fn test_slot_watermarks() {
+        let mut watermarks = SlotWatermarks::default();
+        assert_eq!(watermarks.update(10, Some(9), SlotStatus::Rooted), None);
+        assert_eq!(watermarks.update(11, Some(10), SlotStatus::Rooted), None);
+        // 12 was skipped by the leader
+        assert_eq!(watermarks.update(13, Some(11), SlotStatus::Rooted), None);
+        assert_eq!(watermarks.update(17, Some(16), SlotStatus::Rooted), Some(SlotGap { first_slot: 14, last_slot: 16 }));
+        assert_eq!(watermarks.update(18, None, SlotStatus::Rooted), None);
+        // the statuses have watermarks of their own, and a fork below the watermark isn't a gap
+        assert_eq!(watermarks.update(20, Some(18), SlotStatus::Processed), None);
+        assert_eq!(watermarks.update(19, Some(18), SlotStatus::Processed), None);
+        assert_eq!(watermarks.update(22, Some(21), SlotStatus::Processed), Some(SlotGap { first_slot: 21, last_slot: 21 }));
+
+        let query = SlotHandler::watermark(&TablePrefix::default(), 17, Some(16), SlotStatus::Rooted, Some(&SlotGap { first_slot: 14, last_slot: 16 }));
+        assert!(query.contains("VALUES ('rooted', 17, 16,"));
+        assert!(query.contains("WHERE w.slot < excluded.slot"));
+        assert!(query.contains("INSERT INTO slot_gap (status, first_slot, last_slot, detected_on) VALUES ('rooted', 14, 16,"));
+        assert!(!SlotHandler::watermark(&TablePrefix::default(), 18, None, SlotStatus::Rooted, None).contains("slot_gap"));
+    }
+}
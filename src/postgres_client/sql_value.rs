@@ -0,0 +1,91 @@
+//! Conversions from Rust values to SQL column types and literals, used by the
+//! handlers generated with `#[derive(GeyserAccountHandler)]`.
+use solana_sdk::pubkey::Pubkey;
+
+pub trait SqlValue {
+    /// The column type, without a NULL constraint
+    fn sql_type() -> String;
+
+    /// Whether the column accepts NULL
+    fn nullable() -> bool {
+        false
+    }
+
+    /// The value as a SQL literal
+    fn sql_literal(&self) -> String;
+}
+
+macro_rules! impl_sql_value {
+    ($sql_type:expr, $($t:ty),*) => {
+        $(impl SqlValue for $t {
+            fn sql_type() -> String {
+                $sql_type.to_string()
+            }
+
+            fn sql_literal(&self) -> String {
+                self.to_string()
+            }
+        })*
+    };
+}
+
+impl_sql_value!("BOOL", bool);
+impl_sql_value!("SMALLINT", u8, i8, i16);
+impl_sql_value!("INTEGER", u16, i32);
+impl_sql_value!("BIGINT", u32, i64);
+impl_sql_value!("NUMERIC(20,0)", u64);
+impl_sql_value!("NUMERIC(39,0)", u128, i128);
+
+impl SqlValue for String {
+    fn sql_type() -> String {
+        "TEXT".to_string()
+    }
+
+    fn sql_literal(&self) -> String {
+        format!("'{}'", self.replace('\0', "").replace('\'', "''"))
+    }
+}
+
+impl SqlValue for Pubkey {
+    fn sql_type() -> String {
+        "VARCHAR(44)".to_string()
+    }
+
+    fn sql_literal(&self) -> String {
+        format!("'{}'", self)
+    }
+}
+
+impl<T: SqlValue> SqlValue for Option<T> {
+    fn sql_type() -> String {
+        T::sql_type()
+    }
+
+    fn nullable() -> bool {
+        true
+    }
+
+    fn sql_literal(&self) -> String {
+        self.as_ref().map_or("NULL".to_string(), T::sql_literal)
+    }
+}
+
+impl<T: SqlValue> SqlValue for Vec<T> {
+    fn sql_type() -> String {
+        format!("{}[]", T::sql_type())
+    }
+
+    fn sql_literal(&self) -> String {
+        format!("ARRAY[{}]::{}[]", self.iter().map(T::sql_literal).collect::<Vec<String>>().join(","), T::sql_type())
+    }
+}
+
+/// The column definition of a field, e.g. `mint VARCHAR(44) NOT NULL`
+pub fn column_definition<T: SqlValue>(name: &str) -> String {
+    format!("{} {}{}", name, T::sql_type(), if T::nullable() { "" } else { " NOT NULL" })
+}
+
+/// A raw pubkey as a base58 SQL literal
+pub fn pubkey_literal(pubkey: &[u8]) -> String {
+    format!("'{}'", bs58::encode(pubkey).into_string())
+}
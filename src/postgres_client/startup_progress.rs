@@ -0,0 +1,144 @@
+use super::SimplePostgresClient;
+use crate::config::GeyserPluginPostgresConfig;
+use crate::geyser_plugin_postgres::GeyserPluginPostgresError;
+use crate::metrics::registry;
+use crate::metrics::STARTUP_RESUMES_TOTAL;
+use crate::postgres_client::db_errors::record_db_error;
+use crate::postgres_client::table_prefix::TablePrefix;
+use chrono::Utc;
+use log::*;
+use postgres::Client;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use std::sync::atomic::AtomicI64;
+use std::sync::atomic::Ordering;
+
+/// The `startup_progress` row of the running startup, 0 until it began
+static STARTUP_ID: AtomicI64 = AtomicI64::new(0);
+
+/// The `startup_progress` row the startup batches are counted in, when `startup_progress` is set
+pub fn startup_id() -> Option<i64> {
+    Some(STARTUP_ID.load(Ordering::Relaxed)).filter(|id| *id != 0)
+}
+
+/// A startup the plugin was unloaded or crashed during, as recorded in `startup_progress`
+#[derive(Debug, PartialEq, Eq)]
+pub struct InterruptedStartup {
+    pub id: i64,
+    pub batches: i64,
+    pub accounts: i64,
+    pub max_slot: i64,
+}
+
+pub struct StartupProgressHandler {}
+
+impl StartupProgressHandler {
+    pub fn init(config: &GeyserPluginPostgresConfig) -> String {
+        if !config.startup_progress {
+            return "".to_string();
+        }
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {}startup_progress (
+                id BIGSERIAL PRIMARY KEY,
+                resumed_from BIGINT,
+                batches BIGINT NOT NULL DEFAULT 0,
+                accounts BIGINT NOT NULL DEFAULT 0,
+                max_slot BIGINT NOT NULL DEFAULT 0,
+                started_on TIMESTAMP NOT NULL,
+                updated_on TIMESTAMP NOT NULL,
+                completed_on TIMESTAMP
+            );
+        ",
+            TablePrefix::new(config)
+        );
+    }
+
+    /// Start counting the batches of a new startup, recording the last one when it never completed. The batches of
+    /// the interrupted startup were committed whole, the snapshot is written again over them rather than resumed.
+    /// The statements run in a transaction, PgBouncer keeps it on one server connection.
+    pub fn begin(client: &mut Client, prefix: &TablePrefix) -> Result<Option<InterruptedStartup>, GeyserPluginError> {
+        let result = client.transaction().and_then(|mut transaction| {
+            let result = transaction
+                .query_opt(
+                    &format!(
+                        "SELECT id, batches, accounts, max_slot FROM {0}startup_progress WHERE completed_on IS NULL AND id = (SELECT MAX(id) FROM {0}startup_progress)",
+                        prefix
+                    ),
+                    &[],
+                )
+                .and_then(|row| {
+                    let interrupted = row.map(|row| InterruptedStartup {
+                        id: row.get(0),
+                        batches: row.get(1),
+                        accounts: row.get(2),
+                        max_slot: row.get(3),
+                    });
+                    let now = Utc::now().naive_utc();
+                    let row = transaction.query_one(
+                        &format!("INSERT INTO {}startup_progress (resumed_from, started_on, updated_on) VALUES ($1, $2, $2) RETURNING id", prefix),
+                        &[&interrupted.as_ref().map(|interrupted| interrupted.id), &now],
+                    )?;
+                    Ok((row.get::<_, i64>(0), interrupted))
+                })?;
+            transaction.commit()?;
+            Ok(result)
+        });
+        let (id, interrupted) = result.map_err(|err| {
+            record_db_error("startup_progress", &err);
+            GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
+                msg: format!("[startup_progress] error=[{}]", err),
+            }))
+        })?;
+        if let Some(interrupted) = &interrupted {
+            warn!(
+                "[startup_progress] the startup id=[{}] was interrupted after batches=[{}] accounts=[{}] max_slot=[{}], writing the snapshot again",
+                interrupted.id, interrupted.batches, interrupted.accounts, interrupted.max_slot
+            );
+            registry().inc_counter(STARTUP_RESUMES_TOTAL, &[], 1);
+        }
+        info!("[startup_progress] id=[{}]", id);
+        STARTUP_ID.store(id, Ordering::Relaxed);
+        Ok(interrupted)
+    }
+
+    /// Run in the transaction of a startup batch, so the progress only counts the batches committed
+    pub fn batch(prefix: &TablePrefix, id: i64, accounts: usize, max_slot: i64) -> String {
+        format!(
+            "UPDATE {}startup_progress SET batches = batches + 1, accounts = accounts + {}, max_slot = GREATEST(max_slot, {}), updated_on = '{}' WHERE id = {};",
+            prefix,
+            accounts,
+            max_slot,
+            Utc::now().naive_utc(),
+            id
+        )
+    }
+
+    /// Mark the startup completed once every worker flushed its last batch
+    pub fn complete(config: &GeyserPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+        let id = match startup_id() {
+            Some(id) => id,
+            None => return Ok(()),
+        };
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        let query = format!("UPDATE {}startup_progress SET completed_on = '{}' WHERE id = {};", TablePrefix::new(config), Utc::now().naive_utc(), id);
+        client.batch_execute(&query).map_err(|err| {
+            record_db_error("startup_progress", &err);
+            GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
+                msg: format!("[startup_progress] error=[{}]", err),
+            }))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_startup_progress() {
+        let query = StartupProgressHandler::batch(&TablePrefix::default(), 7, 500, 1200);
+        assert!(query.starts_with("UPDATE startup_progress SET batches = batches + 1, accounts = accounts + 500, max_slot = GREATEST(max_slot, 1200),"));
+        assert!(query.ends_with("WHERE id = 7;"));
+        assert!(StartupProgressHandler::init(&GeyserPluginPostgresConfig::default()).is_empty());
+    }
+}
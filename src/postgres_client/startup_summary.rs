@@ -0,0 +1,191 @@
+use super::SimplePostgresClient;
+use crate::config::GeyserPluginPostgresConfig;
+use crate::geyser_plugin_postgres::GeyserPluginPostgresError;
+use crate::metrics::registry;
+use crate::metrics::DB_ERRORS_TOTAL;
+use crate::metrics::ERRORS_TOTAL;
+use crate::metrics::HANDLER_ERRORS_TOTAL;
+use crate::metrics::STARTUP_ACCOUNTS_TOTAL;
+use crate::postgres_client::db_errors::record_db_error;
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use chrono::Utc;
+use log::*;
+use postgres::Client;
+use postgres::SimpleQueryMessage;
+use serde_json::json;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// Rows inserted and updated so far per table of the current schema
+pub type TableWrites = BTreeMap<String, i64>;
+
+/// Read the cumulative write counts of `pg_stat_user_tables`, the statistics are
+/// reported with a short delay so the counts are approximate
+pub fn table_writes(client: &mut Client) -> Result<TableWrites, GeyserPluginError> {
+    let messages = client
+        .simple_query("SELECT relname, n_tup_ins + n_tup_upd FROM pg_stat_user_tables WHERE schemaname = current_schema()")
+        .map_err(|err| {
+            record_db_error("startup_summary", &err);
+            GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
+                msg: format!("[table_writes] error=[{}]", err),
+            }))
+        })?;
+    Ok(messages
+        .iter()
+        .filter_map(|message| match message {
+            SimpleQueryMessage::Row(row) => Some((row.get(0)?.to_string(), row.get(1)?.parse().ok()?)),
+            _ => None,
+        })
+        .collect())
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StartupSummary {
+    /// From the plugin load to the end of the startup flushes of every worker
+    pub duration_ms: u64,
+    /// Startup accounts fed to each handler
+    pub accounts: BTreeMap<String, u64>,
+    /// Rows written to each table during startup
+    pub rows_written: BTreeMap<String, i64>,
+    /// Failed requests by request, failed statements by SQLSTATE class and handler errors by handler
+    pub errors: BTreeMap<&'static str, BTreeMap<String, u64>>,
+}
+
+impl StartupSummary {
+    pub fn new(duration_ms: u64, accounts: BTreeMap<String, u64>, before: &TableWrites, after: &TableWrites) -> Self {
+        let rows_written = after
+            .iter()
+            .map(|(table, writes)| (table.clone(), writes - before.get(table).copied().unwrap_or_default()))
+            .filter(|(_, writes)| *writes > 0)
+            .collect();
+        let mut errors = BTreeMap::new();
+        errors.insert("requests", registry().counters_by(ERRORS_TOTAL, "request"));
+        errors.insert("db", registry().counters_by(DB_ERRORS_TOTAL, "class"));
+        errors.insert("handlers", registry().counters_by(HANDLER_ERRORS_TOTAL, "handler"));
+        Self {
+            duration_ms,
+            accounts,
+            rows_written,
+            errors,
+        }
+    }
+
+    pub fn log(&self) {
+        info!(
+            "[startup_summary] duration_ms=[{}] accounts=[{:?}] rows_written=[{:?}] errors=[{:?}]",
+            self.duration_ms, self.accounts, self.rows_written, self.errors
+        );
+    }
+}
+
+pub struct StartupSummaryHandler {}
+
+impl StartupSummaryHandler {
+    pub fn init(config: &GeyserPluginPostgresConfig) -> String {
+        if !config.startup_summary {
+            return "".to_string();
+        }
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {}startup_summary (
+                id BIGSERIAL PRIMARY KEY,
+                version VARCHAR(32) NOT NULL,
+                profile VARCHAR(32),
+                threads BIGINT NOT NULL,
+                batch_size BIGINT NOT NULL,
+                duration_ms BIGINT NOT NULL,
+                accounts JSONB NOT NULL,
+                rows_written JSONB NOT NULL,
+                errors JSONB NOT NULL,
+                created_on TIMESTAMP NOT NULL
+            );
+        ",
+            TablePrefix::new(config)
+        );
+    }
+
+    pub fn insert(config: &GeyserPluginPostgresConfig, summary: &StartupSummary) -> String {
+        format!(
+            "
+                INSERT INTO {9}startup_summary (version, profile, threads, batch_size, duration_ms, accounts, rows_written, errors, created_on) \
+                VALUES ('{0}', {1}, {2}, {3}, {4}, {5}, {6}, {7}, '{8}');
+            ",
+            env!("CARGO_PKG_VERSION"),
+            config.profile.sql_literal(),
+            config.threads,
+            config.batch_size,
+            summary.duration_ms,
+            json!(summary.accounts).to_string().sql_literal(),
+            json!(summary.rows_written).to_string().sql_literal(),
+            json!(summary.errors).to_string().sql_literal(),
+            &Utc::now().naive_utc(),
+            TablePrefix::new(config)
+        )
+    }
+}
+
+/// Measures the snapshot load, from the plugin load to the end of startup
+pub struct StartupTracker {
+    started: Instant,
+    table_writes: TableWrites,
+}
+
+impl StartupTracker {
+    pub fn begin(client: &mut Client) -> Self {
+        let table_writes = table_writes(client).unwrap_or_else(|err| {
+            warn!("[startup_summary] rows written will not be reported {}", err);
+            TableWrites::default()
+        });
+        Self {
+            started: Instant::now(),
+            table_writes,
+        }
+    }
+
+    /// Log the summary, and write it to `startup_summary` if enabled
+    pub fn finish(&self, config: &GeyserPluginPostgresConfig) -> Result<StartupSummary, GeyserPluginError> {
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        let after = table_writes(&mut client)?;
+        let summary = StartupSummary::new(
+            self.started.elapsed().as_millis() as u64,
+            registry().counters_by(STARTUP_ACCOUNTS_TOTAL, "handler"),
+            &self.table_writes,
+            &after,
+        );
+        summary.log();
+        if config.startup_summary {
+            if let Err(err) = client.batch_execute(&StartupSummaryHandler::insert(config, &summary)) {
+                record_db_error("startup_summary", &err);
+                return Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
+                    msg: format!("[startup_summary] error=[{}]", err),
+                })));
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_startup_summary() {
+        let before = TableWrites::from([("token_account".to_string(), 10), ("slot".to_string(), 5)]);
+        let after = TableWrites::from([("token_account".to_string(), 25), ("slot".to_string(), 5), ("token_manager".to_string(), 3)]);
+        let accounts = BTreeMap::from([("token_account".to_string(), 15)]);
+        let summary = StartupSummary::new(1200, accounts, &before, &after);
+        assert_eq!(summary.rows_written, BTreeMap::from([("token_account".to_string(), 15), ("token_manager".to_string(), 3)]));
+
+        let config = GeyserPluginPostgresConfig {
+            startup_summary: true,
+            ..GeyserPluginPostgresConfig::default()
+        };
+        let query = StartupSummaryHandler::insert(&config, &summary);
+        assert!(query.contains(&format!("VALUES ('{}', NULL, 10, 10, 1200,", env!("CARGO_PKG_VERSION"))));
+        assert!(query.contains("'{\"token_account\":15}'"));
+        assert!(query.contains("'{\"token_account\":15,\"token_manager\":3}'"));
+    }
+}
@@ -0,0 +1,75 @@
+//! The prefix of the tables, views, indexes and functions of the plugin, so the plugins indexing
+//! several clusters can share a schema.
+//!
+//! The handlers build the names of their relations from the config of their client, in `init` and in
+//! the statements they write, so the clients of the routed databases each write to their own prefix.
+use crate::config::GeyserPluginPostgresConfig;
+use std::fmt;
+
+/// The `table_prefix` of a client, displayed in front of the names of its relations
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TablePrefix(String);
+
+impl TablePrefix {
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Self {
+        Self(config.table_prefix.clone())
+    }
+
+    /// `name` with the prefix
+    pub fn table(&self, name: &str) -> String {
+        format!("{}{}", self.0, name)
+    }
+}
+
+impl fmt::Display for TablePrefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// `name` with the `table_prefix` of `config`
+pub fn table(config: &GeyserPluginPostgresConfig, name: &str) -> String {
+    format!("{}{}", config.table_prefix, name)
+}
+
+/// Whether `word` is a name the plugin could have given to a relation
+fn is_name(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_ascii_lowercase() || c == '_') && word.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') && !word.starts_with("pg_")
+}
+
+/// The schema and the table prefix must be lowercase unquoted identifiers
+pub fn validate(schema: &Option<String>, table_prefix: &str) -> Result<(), String> {
+    if let Some(schema) = schema.as_ref().filter(|schema| !is_name(schema)) {
+        return Err(format!("\"schema\" \"{}\" is not a lowercase identifier", schema));
+    }
+    if !table_prefix.is_empty() && !is_name(table_prefix) {
+        return Err(format!("\"table_prefix\" \"{}\" is not a lowercase identifier", table_prefix));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table() {
+        let config = GeyserPluginPostgresConfig {
+            table_prefix: "devnet_".to_string(),
+            ..GeyserPluginPostgresConfig::default()
+        };
+        assert_eq!(table(&config, "slot"), "devnet_slot");
+        assert_eq!(TablePrefix::new(&config).table("account"), "devnet_account");
+        assert_eq!(format!("INSERT INTO {}account", TablePrefix::new(&config)), "INSERT INTO devnet_account");
+        assert_eq!(TablePrefix::default().table("slot"), "slot");
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&None, "").is_ok());
+        assert!(validate(&Some("devnet".to_string()), "devnet_").is_ok());
+        assert!(validate(&Some("Devnet".to_string()), "").is_err());
+        assert!(validate(&Some("pg_catalog".to_string()), "").is_err());
+        assert!(validate(&None, "devnet; DROP TABLE slot; --").is_err());
+    }
+}
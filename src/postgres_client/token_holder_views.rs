@@ -0,0 +1,190 @@
+use crate::config::GeyserPluginPostgresConfig;
+use crate::postgres_client::periodic_writer::execute;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::SimplePostgresClient;
+use crossbeam_channel::unbounded;
+use crossbeam_channel::RecvTimeoutError;
+use crossbeam_channel::Sender;
+use log::*;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use std::thread::Builder;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
+
+/// * The `token_holder_views` section maintains materialized views of the token holders derived from `spl_token_account`.
+/// "token_holder_views" : { "interval_secs" : 300, "rooted_slots" : 0 }
+/// The views are refreshed every `interval_secs`, every `rooted_slots` rooted slots, or both.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TokenHolderViewsConfig {
+    /// Seconds between refreshes, 0 to only refresh on rooted slots
+    pub interval_secs: u64,
+    /// Refresh when a rooted slot crosses a multiple of this many slots, 0 to only refresh on the interval
+    pub rooted_slots: u64,
+}
+
+impl Default for TokenHolderViewsConfig {
+    fn default() -> Self {
+        Self { interval_secs: 300, rooted_slots: 0 }
+    }
+}
+
+impl TokenHolderViewsConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_secs == 0 && self.rooted_slots == 0 {
+            return Err("token_holder_views must set \"interval_secs\" or \"rooted_slots\"".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The views refreshed, each has a unique index so it is refreshed without blocking its readers
+const VIEWS: [&str; 2] = ["token_holders_per_mint", "token_balance_per_owner"];
+
+pub struct TokenHolderViewsHandler {}
+
+impl TokenHolderViewsHandler {
+    /// Runs after the schema migrations, the views read the `amount` column of `spl_token_account`
+    pub fn init(config: &GeyserPluginPostgresConfig) -> String {
+        if config.token_holder_views.is_none() {
+            return "".to_string();
+        }
+        return format!(
+            "
+            CREATE MATERIALIZED VIEW IF NOT EXISTS {prefix}token_holders_per_mint AS
+                SELECT mint, COUNT(DISTINCT owner) AS holders, COUNT(*) AS accounts, SUM(amount) AS amount, MAX(slot) AS slot
+                FROM {prefix}spl_token_account WHERE amount > 0 GROUP BY mint;
+            CREATE UNIQUE INDEX IF NOT EXISTS {prefix}token_holders_per_mint_mint ON {prefix}token_holders_per_mint (mint);
+            CREATE INDEX IF NOT EXISTS {prefix}token_holders_per_mint_holders ON {prefix}token_holders_per_mint (holders DESC);
+            CREATE MATERIALIZED VIEW IF NOT EXISTS {prefix}token_balance_per_owner AS
+                SELECT owner, mint, SUM(amount) AS amount, COUNT(*) AS accounts, MAX(slot) AS slot
+                FROM {prefix}spl_token_account WHERE amount > 0 GROUP BY owner, mint;
+            CREATE UNIQUE INDEX IF NOT EXISTS {prefix}token_balance_per_owner_owner_mint ON {prefix}token_balance_per_owner (owner, mint);
+            CREATE INDEX IF NOT EXISTS {prefix}token_balance_per_owner_mint_amount ON {prefix}token_balance_per_owner (mint, amount DESC);
+        ",
+            prefix = TablePrefix::new(config)
+        );
+    }
+
+    pub fn refresh(prefix: &TablePrefix, view: &str) -> String {
+        format!("REFRESH MATERIALIZED VIEW CONCURRENTLY {}{};", prefix, view)
+    }
+}
+
+/// Refreshes the views from a dedicated thread on its own connection, on the interval and when
+/// asked to by a rooted slot. Refreshes asked for while one runs are coalesced.
+pub struct TokenHolderViews {
+    rooted_slots: u64,
+    last_rooted_slot: Option<u64>,
+    sender: Option<Sender<u64>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TokenHolderViews {
+    /// Opens the refresh connection before the thread starts, with `interval_secs` 0 the first refresh waits for a
+    /// rooted slot crossing `rooted_slots`, too late to fail the load on an unreachable database
+    pub fn start(plugin_config: &GeyserPluginPostgresConfig) -> Result<Option<Self>, GeyserPluginError> {
+        let config = match &plugin_config.token_holder_views {
+            Some(config) => config.clone(),
+            None => return Ok(None),
+        };
+        let mut client = Some(SimplePostgresClient::connect_to_db(plugin_config)?);
+        let prefix = TablePrefix::new(plugin_config);
+        let plugin_config = plugin_config.clone();
+        let interval = Duration::from_secs(config.interval_secs);
+        let (sender, receiver) = unbounded::<u64>();
+        let thread = Builder::new()
+            .name("token_holder_views".to_string())
+            .spawn(move || {
+                let mut last_refresh = Instant::now();
+                loop {
+                    // ends once the handle drops the sender
+                    let slot = match config.interval_secs {
+                        0 => match receiver.recv() {
+                            Ok(slot) => Some(slot),
+                            Err(_) => break,
+                        },
+                        _ => match receiver.recv_timeout(interval.saturating_sub(last_refresh.elapsed())) {
+                            Ok(slot) => Some(slot),
+                            Err(RecvTimeoutError::Timeout) => None,
+                            Err(RecvTimeoutError::Disconnected) => break,
+                        },
+                    };
+                    let slot = receiver.try_iter().last().or(slot);
+                    last_refresh = Instant::now();
+                    for view in VIEWS {
+                        if let Err(err) = execute(&mut client, &plugin_config, "refresh_token_holder_views", &TokenHolderViewsHandler::refresh(&prefix, view)) {
+                            error!("[token_holder_views] failed to refresh {}: {}", view, err);
+                        }
+                    }
+                    info!("[token_holder_views] refreshed rooted_slot={:?} elapsed_ms={}", slot, last_refresh.elapsed().as_millis());
+                }
+            })
+            .unwrap();
+        Ok(Some(Self {
+            rooted_slots: config.rooted_slots,
+            last_rooted_slot: None,
+            sender: Some(sender),
+            thread: Some(thread),
+        }))
+    }
+
+    /// Ask for a refresh when the rooted slot crosses a multiple of `rooted_slots`
+    pub fn record_rooted_slot(&mut self, slot: u64) {
+        if self.rooted_slots == 0 {
+            return;
+        }
+        if self.last_rooted_slot.map_or(false, |last_rooted_slot| slot <= last_rooted_slot) {
+            return;
+        }
+        let crossed = self.last_rooted_slot.map_or(true, |last_rooted_slot| slot / self.rooted_slots > last_rooted_slot / self.rooted_slots);
+        self.last_rooted_slot = Some(slot);
+        if let (true, Some(sender)) = (crossed, &self.sender) {
+            let _ = sender.send(slot);
+        }
+    }
+
+    /// Stop the thread once the refresh in progress, if any, is done
+    pub fn stop(&mut self) {
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for TokenHolderViews {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_holder_views() {
+        assert!(TokenHolderViewsConfig::default().validate().is_ok());
+        assert!(TokenHolderViewsConfig { interval_secs: 0, rooted_slots: 0 }.validate().is_err());
+        assert_eq!(
+            TokenHolderViewsHandler::refresh(&TablePrefix::default(), "token_holders_per_mint"),
+            "REFRESH MATERIALIZED VIEW CONCURRENTLY token_holders_per_mint;"
+        );
+
+        let (sender, receiver) = unbounded::<u64>();
+        let mut views = TokenHolderViews {
+            rooted_slots: 100,
+            last_rooted_slot: None,
+            sender: Some(sender),
+            thread: None,
+        };
+        for slot in [150, 160, 199, 200, 180, 250, 420] {
+            views.record_rooted_slot(slot);
+        }
+        assert_eq!(receiver.try_iter().collect::<Vec<u64>>(), vec![150, 200, 420]);
+    }
+}
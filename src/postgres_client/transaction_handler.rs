@@ -1,11 +1,15 @@
 use crate::config::GeyserPluginPostgresConfig;
 use crate::geyser_plugin_postgres::GeyserPluginPostgresError;
+use crate::postgres_client::db_errors::record_db_error;
+use crate::postgres_client::prepared_statement::PreparedStatement;
+use crate::postgres_client::table_prefix::TablePrefix;
 use chrono::Utc;
 use log::*;
 use postgres::Client;
-use postgres::Statement;
+use postgres::Transaction;
 use postgres_types::FromSql;
 use postgres_types::ToSql;
+use serde_derive::Serialize;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoV2;
 use solana_runtime::bank::RewardType;
@@ -24,7 +28,7 @@ use solana_transaction_status::TransactionTokenBalance;
 
 const MAX_TRANSACTION_STATUS_LEN: usize = 256;
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize)]
 #[postgres(name = "CompiledInstruction")]
 pub struct DbCompiledInstruction {
     pub program_id_index: i16,
@@ -32,14 +36,14 @@ pub struct DbCompiledInstruction {
     pub data: Vec<u8>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize)]
 #[postgres(name = "InnerInstructions")]
 pub struct DbInnerInstructions {
     pub index: i16,
     pub instructions: Vec<DbCompiledInstruction>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize)]
 #[postgres(name = "TransactionTokenBalance")]
 pub struct DbTransactionTokenBalance {
     pub account_index: i16,
@@ -48,7 +52,7 @@ pub struct DbTransactionTokenBalance {
     pub owner: String,
 }
 
-#[derive(Clone, Debug, Eq, FromSql, ToSql, PartialEq)]
+#[derive(Clone, Debug, Eq, FromSql, ToSql, PartialEq, Serialize)]
 #[postgres(name = "RewardType")]
 pub enum DbRewardType {
     Fee,
@@ -57,7 +61,7 @@ pub enum DbRewardType {
     Voting,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize)]
 #[postgres(name = "Reward")]
 pub struct DbReward {
     pub pubkey: String,
@@ -67,7 +71,7 @@ pub struct DbReward {
     pub commission: Option<i16>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize)]
 #[postgres(name = "TransactionStatusMeta")]
 pub struct DbTransactionStatusMeta {
     pub error: Option<DbTransactionError>,
@@ -81,7 +85,7 @@ pub struct DbTransactionStatusMeta {
     pub rewards: Option<Vec<DbReward>>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize)]
 #[postgres(name = "TransactionMessageHeader")]
 pub struct DbTransactionMessageHeader {
     pub num_required_signatures: i16,
@@ -89,7 +93,7 @@ pub struct DbTransactionMessageHeader {
     pub num_readonly_unsigned_accounts: i16,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize)]
 #[postgres(name = "TransactionMessage")]
 pub struct DbTransactionMessage {
     pub header: DbTransactionMessageHeader,
@@ -98,7 +102,7 @@ pub struct DbTransactionMessage {
     pub instructions: Vec<DbCompiledInstruction>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize)]
 #[postgres(name = "TransactionMessageAddressTableLookup")]
 pub struct DbTransactionMessageAddressTableLookup {
     pub account_key: Vec<u8>,
@@ -106,7 +110,7 @@ pub struct DbTransactionMessageAddressTableLookup {
     pub readonly_indexes: Vec<i16>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize)]
 #[postgres(name = "TransactionMessageV0")]
 pub struct DbTransactionMessageV0 {
     pub header: DbTransactionMessageHeader,
@@ -116,20 +120,21 @@ pub struct DbTransactionMessageV0 {
     pub address_table_lookups: Vec<DbTransactionMessageAddressTableLookup>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize)]
 #[postgres(name = "LoadedAddresses")]
 pub struct DbLoadedAddresses {
     pub writable: Vec<Vec<u8>>,
     pub readonly: Vec<Vec<u8>>,
 }
 
-#[derive(Clone, Debug, FromSql, ToSql)]
+#[derive(Clone, Debug, FromSql, ToSql, Serialize)]
 #[postgres(name = "LoadedMessageV0")]
 pub struct DbLoadedMessageV0 {
     pub message: DbTransactionMessageV0,
     pub loaded_addresses: DbLoadedAddresses,
 }
 
+#[derive(Serialize)]
 pub struct DbTransaction {
     pub signature: Vec<u8>,
     pub is_vote: bool,
@@ -139,6 +144,10 @@ pub struct DbTransaction {
     pub v0_loaded_message: Option<DbLoadedMessageV0>,
     pub message_hash: Vec<u8>,
     pub meta: DbTransactionStatusMeta,
+    /// Not part of the `TransactionStatusMeta` type of the `transaction` table
+    pub compute_units_consumed: Option<i64>,
+    /// The outer instruction that failed, when the transaction failed with an `InstructionError`
+    pub failed_instruction_index: Option<i16>,
     pub signatures: Vec<Vec<u8>>,
     /// This can be used to tell the order of transaction within a block
     /// Given a slot, the transaction with a smaller write_version appears
@@ -147,6 +156,129 @@ pub struct DbTransaction {
     pub index: i64,
 }
 
+impl DbTransaction {
+    /// All account keys of the message, including the addresses loaded from lookup tables
+    pub fn account_keys(&self) -> Vec<&[u8]> {
+        match (&self.legacy_message, &self.v0_loaded_message) {
+            (Some(message), _) => message.account_keys.iter().map(|k| k.as_slice()).collect(),
+            (None, Some(loaded_message)) => loaded_message
+                .message
+                .account_keys
+                .iter()
+                .chain(loaded_message.loaded_addresses.writable.iter())
+                .chain(loaded_message.loaded_addresses.readonly.iter())
+                .map(|k| k.as_slice())
+                .collect(),
+            (None, None) => vec![],
+        }
+    }
+
+    /// The approximate bytes held on the heap by the transaction, counting its keys, instructions and logs
+    pub fn heap_bytes(&self) -> usize {
+        let instruction_bytes = |instructions: &[DbCompiledInstruction]| instructions.iter().map(|i| i.data.len() + 2 * i.accounts.len()).sum::<usize>();
+        let inner_instructions = self.meta.inner_instructions.iter().flatten().map(|i| instruction_bytes(&i.instructions)).sum::<usize>();
+        let log_messages = self.meta.log_messages.iter().flatten().map(|l| l.len()).sum::<usize>();
+        self.signature.len()
+            + self.signatures.iter().map(|s| s.len()).sum::<usize>()
+            + self.account_keys().iter().map(|k| k.len()).sum::<usize>()
+            + instruction_bytes(self.instructions())
+            + inner_instructions
+            + log_messages
+    }
+
+    /// The outer instructions of the message
+    pub fn instructions(&self) -> &[DbCompiledInstruction] {
+        match (&self.legacy_message, &self.v0_loaded_message) {
+            (Some(message), _) => &message.instructions,
+            (None, Some(loaded_message)) => &loaded_message.message.instructions,
+            (None, None) => &[],
+        }
+    }
+
+    pub fn is_successful(&self) -> bool {
+        self.meta.error.is_none()
+    }
+
+    pub fn error_code(&self) -> Option<&DbTransactionErrorCode> {
+        self.meta.error.as_ref().map(|error| &error.error_code)
+    }
+
+    /// The program of the outer instruction that failed
+    pub fn failed_program_id(&self) -> Option<String> {
+        let instruction = self.instructions().get(self.failed_instruction_index? as usize)?;
+        let program_id = *self.account_keys().get(instruction.program_id_index as usize)?;
+        Some(bs58::encode(program_id).into_string())
+    }
+
+    /// The outer instructions followed each by its inner instructions, in the order they were executed
+    pub fn flattened_instructions(&self) -> Vec<DbInstruction> {
+        let account_keys = self.account_keys();
+        let executed = self.instructions().iter().enumerate().flat_map(|(index, instruction)| {
+            let inner_instructions = self
+                .meta
+                .inner_instructions
+                .iter()
+                .flatten()
+                .filter(move |inner_instructions| inner_instructions.index as usize == index)
+                .flat_map(move |inner_instructions| {
+                    inner_instructions
+                        .instructions
+                        .iter()
+                        .enumerate()
+                        .map(move |(inner_index, instruction)| (index as i16, inner_index as i16, instruction))
+                });
+            std::iter::once((index as i16, OUTER_INSTRUCTION_INNER_INDEX, instruction)).chain(inner_instructions)
+        });
+        let mut invokes = self.meta.log_messages.iter().flatten().filter_map(|log| parse_invoke_log(log)).peekable();
+        let mut instructions = Vec::new();
+        for (instruction_index, inner_index, instruction) in executed {
+            let program_id = match account_keys.get(instruction.program_id_index as usize) {
+                Some(program_id) => *program_id,
+                None => continue,
+            };
+            let is_outer = inner_index == OUTER_INSTRUCTION_INNER_INDEX;
+            // precompiled programs log no invocation, and the logs may be truncated
+            let stack_height = match invokes.peek() {
+                Some((invoked, depth)) if *invoked == bs58::encode(program_id).into_string() && (*depth == 1) == is_outer => invokes.next().map(|(_, depth)| depth),
+                _ if is_outer => Some(1),
+                _ => None,
+            };
+            instructions.push(DbInstruction {
+                instruction_index,
+                inner_index,
+                program_id,
+                accounts: instruction.accounts.iter().filter_map(|index| account_keys.get(*index as usize).copied()).collect(),
+                data: &instruction.data,
+                stack_height,
+            });
+        }
+        instructions
+    }
+}
+
+/// Inner index recorded for outer instructions
+pub(crate) const OUTER_INSTRUCTION_INNER_INDEX: i16 = -1;
+
+/// An outer or inner instruction of a transaction, with its program and accounts resolved
+#[derive(Debug, PartialEq)]
+pub struct DbInstruction<'a> {
+    /// The index of the outer instruction
+    pub instruction_index: i16,
+    /// The position among the inner instructions of the outer instruction, -1 for the outer instruction itself
+    pub inner_index: i16,
+    pub program_id: &'a [u8],
+    pub accounts: Vec<&'a [u8]>,
+    pub data: &'a [u8],
+    /// 1 for an outer instruction, the invocation depth of an inner instruction as logged by the runtime
+    pub stack_height: Option<i16>,
+}
+
+/// The program and depth of a `Program <program_id> invoke [<depth>]` log message
+fn parse_invoke_log(log: &str) -> Option<(&str, i16)> {
+    let (program_id, depth) = log.strip_prefix("Program ")?.split_once(" invoke [")?;
+    Some((program_id, depth.strip_suffix(']')?.parse().ok()?))
+}
+
 impl From<&MessageAddressTableLookup> for DbTransactionMessageAddressTableLookup {
     fn from(address_table_lookup: &MessageAddressTableLookup) -> Self {
         Self {
@@ -254,7 +386,7 @@ impl From<&Reward> for DbReward {
     }
 }
 
-#[derive(Clone, Debug, Eq, FromSql, ToSql, PartialEq)]
+#[derive(Clone, Debug, Eq, FromSql, ToSql, PartialEq, Serialize)]
 #[postgres(name = "TransactionErrorCode")]
 pub enum DbTransactionErrorCode {
     AccountInUse,
@@ -331,7 +463,7 @@ impl From<&TransactionError> for DbTransactionErrorCode {
     }
 }
 
-#[derive(Clone, Debug, Eq, FromSql, ToSql, PartialEq)]
+#[derive(Clone, Debug, Eq, FromSql, ToSql, PartialEq, Serialize)]
 #[postgres(name = "TransactionError")]
 pub struct DbTransactionError {
     error_code: DbTransactionErrorCode,
@@ -407,22 +539,28 @@ pub fn build_db_transaction(slot: u64, transaction_info: &ReplicaTransactionInfo
         signatures: transaction_info.transaction.signatures().iter().map(|signature| signature.as_ref().to_vec()).collect(),
         message_hash: transaction_info.transaction.message_hash().as_ref().to_vec(),
         meta: DbTransactionStatusMeta::from(transaction_info.transaction_status_meta),
+        compute_units_consumed: transaction_info.transaction_status_meta.compute_units_consumed.map(|units| units as i64),
+        failed_instruction_index: match &transaction_info.transaction_status_meta.status {
+            Err(TransactionError::InstructionError(index, _)) => Some(*index as i16),
+            _ => None,
+        },
         write_version: transaction_write_version as i64,
         index: 0,
     }
 }
 
 pub struct TransactionHandler {
-    pub upsert_statement: Statement,
+    pub upsert_statement: PreparedStatement,
 }
 
 impl TransactionHandler {
-    pub fn new(client: &mut Client, _config: &GeyserPluginPostgresConfig) -> Result<TransactionHandler, GeyserPluginError> {
-        let stmt = "
-            INSERT INTO transaction AS txn (signature, is_vote, slot, message_type, \
+    pub fn new(transaction: &mut Transaction, config: &GeyserPluginPostgresConfig) -> Result<TransactionHandler, GeyserPluginError> {
+        let stmt = format!(
+            "
+            INSERT INTO {}transaction AS txn (signature, is_vote, slot, message_type, \
                 legacy_message, v0_loaded_message, signatures, message_hash, meta, \
-                write_version, index, updated_on) \
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) \
+                write_version, index, updated_on, success, error_code, failed_instruction_index, failed_program_id) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16) \
             ON CONFLICT (slot, signature) DO UPDATE SET is_vote=excluded.is_vote, \
                 message_type=excluded.message_type, \
                 legacy_message=excluded.legacy_message, \
@@ -432,9 +570,15 @@ impl TransactionHandler {
                 meta=excluded.meta, \
                 write_version=excluded.write_version, \
                 index=excluded.index,
-                updated_on=excluded.updated_on;
-        ";
-        match client.prepare(stmt) {
+                updated_on=excluded.updated_on, \
+                success=excluded.success, \
+                error_code=excluded.error_code, \
+                failed_instruction_index=excluded.failed_instruction_index, \
+                failed_program_id=excluded.failed_program_id;
+        ",
+            TablePrefix::new(config)
+        );
+        match PreparedStatement::prepare(transaction, &stmt, config.pgbouncer) {
             Ok(statement) => Ok(TransactionHandler { upsert_statement: statement }),
             Err(err) => Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
                 msg: format!("[transction_handler::new] error=[{}]", err),
@@ -442,10 +586,11 @@ impl TransactionHandler {
         }
     }
 
-    pub fn init(_config: &crate::config::GeyserPluginPostgresConfig) -> String {
-        return "
+    pub fn init(config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        return format!(
+            "
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionErrorCode') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionErrorCode' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"TransactionErrorCode\" AS ENUM (
                         'AccountInUse',
                         'AccountLoadedTwice',
@@ -485,7 +630,7 @@ impl TransactionHandler {
             END $$;
 
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionError') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionError' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"TransactionError\" AS (
                         error_code \"TransactionErrorCode\",
                         error_detail VARCHAR(256)
@@ -495,7 +640,7 @@ impl TransactionHandler {
 
             
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'CompiledInstruction') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'CompiledInstruction' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"CompiledInstruction\" AS (
                         program_id_index SMALLINT,
                         accounts SMALLINT[],
@@ -505,7 +650,7 @@ impl TransactionHandler {
             END $$;
 
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'InnerInstructions') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'InnerInstructions' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"InnerInstructions\" AS (
                         index SMALLINT,
                         instructions \"CompiledInstruction\"[]
@@ -514,7 +659,7 @@ impl TransactionHandler {
             END $$;
 
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionTokenBalance') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionTokenBalance' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"TransactionTokenBalance\" AS (
                         account_index SMALLINT,
                         mint VARCHAR(44),
@@ -525,7 +670,7 @@ impl TransactionHandler {
             END $$;
             
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'RewardType') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'RewardType' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"RewardType\" AS ENUM (
                         'Fee',
                         'Rent',
@@ -536,7 +681,7 @@ impl TransactionHandler {
             END $$;
 
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'Reward') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'Reward' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"Reward\" AS (
                         pubkey VARCHAR(44),
                         lamports BIGINT,
@@ -548,7 +693,7 @@ impl TransactionHandler {
             END $$;
 
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionStatusMeta') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionStatusMeta' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"TransactionStatusMeta\" AS (
                         error \"TransactionError\",
                         fee BIGINT,
@@ -564,7 +709,7 @@ impl TransactionHandler {
             END $$;
 
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionMessageHeader') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionMessageHeader' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"TransactionMessageHeader\" AS (
                         num_required_signatures SMALLINT,
                         num_readonly_signed_accounts SMALLINT,
@@ -574,7 +719,7 @@ impl TransactionHandler {
             END $$;
 
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionMessage') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionMessage' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"TransactionMessage\" AS (
                         header \"TransactionMessageHeader\",
                         account_keys BYTEA[],
@@ -585,7 +730,7 @@ impl TransactionHandler {
             END $$;
 
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionMessageAddressTableLookup') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionMessageAddressTableLookup' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"TransactionMessageAddressTableLookup\" AS (
                         account_key BYTEA,
                         writable_indexes SMALLINT[],
@@ -595,7 +740,7 @@ impl TransactionHandler {
             END $$;
 
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionMessageV0') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'TransactionMessageV0' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"TransactionMessageV0\" AS (
                         header \"TransactionMessageHeader\",
                         account_keys BYTEA[],
@@ -607,7 +752,7 @@ impl TransactionHandler {
             END $$;
 
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'LoadedAddresses') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'LoadedAddresses' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"LoadedAddresses\" AS (
                         writable BYTEA[],
                         readonly BYTEA[]
@@ -616,7 +761,7 @@ impl TransactionHandler {
             END $$;
 
             DO $$ BEGIN
-                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'LoadedMessageV0') THEN
+                IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'LoadedMessageV0' AND typnamespace = current_schema()::regnamespace) THEN
                     CREATE TYPE \"LoadedMessageV0\" AS (
                         message \"TransactionMessageV0\",
                         loaded_addresses \"LoadedAddresses\"
@@ -624,7 +769,7 @@ impl TransactionHandler {
                 END IF;
             END $$;
             
-            CREATE TABLE IF NOT EXISTS transaction (
+            CREATE TABLE IF NOT EXISTS {prefix}transaction (
                 slot BIGINT NOT NULL,
                 signature BYTEA NOT NULL,
                 is_vote BOOL NOT NULL,
@@ -637,15 +782,73 @@ impl TransactionHandler {
                 write_version BIGINT,
                 updated_on TIMESTAMP NOT NULL,
                 index BIGINT NOT NULL,
+                success BOOL,
+                error_code \"TransactionErrorCode\",
+                failed_instruction_index SMALLINT,
+                failed_program_id VARCHAR(44),
                 CONSTRAINT transaction_pk PRIMARY KEY (slot, signature)
             );
-        "
-        .to_string();
+            ALTER TABLE {prefix}transaction ADD COLUMN IF NOT EXISTS success BOOL;
+            ALTER TABLE {prefix}transaction ADD COLUMN IF NOT EXISTS error_code \"TransactionErrorCode\";
+            ALTER TABLE {prefix}transaction ADD COLUMN IF NOT EXISTS failed_instruction_index SMALLINT;
+            ALTER TABLE {prefix}transaction ADD COLUMN IF NOT EXISTS failed_program_id VARCHAR(44);
+            CREATE INDEX IF NOT EXISTS {prefix}transaction_error_code ON {prefix}transaction (error_code, slot) WHERE error_code IS NOT NULL;
+            CREATE INDEX IF NOT EXISTS {prefix}transaction_failed_program_id ON {prefix}transaction (failed_program_id, slot) WHERE failed_program_id IS NOT NULL;
+
+            CREATE TABLE IF NOT EXISTS {prefix}transaction_instruction (
+                signature VARCHAR(88) NOT NULL,
+                slot BIGINT NOT NULL,
+                instruction_index SMALLINT NOT NULL,
+                inner_index SMALLINT NOT NULL,
+                program_id VARCHAR(44) NOT NULL,
+                accounts VARCHAR(44)[] NOT NULL,
+                data BYTEA NOT NULL,
+                stack_height SMALLINT,
+                PRIMARY KEY (signature, instruction_index, inner_index)
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}transaction_instruction_program_id ON {prefix}transaction_instruction (program_id, slot);
+        ",
+            prefix = TablePrefix::new(config)
+        );
+    }
+
+    /// One `transaction_instruction` row per outer and inner instruction of the transaction
+    pub fn instructions_update(prefix: &TablePrefix, transaction: &DbTransaction) -> String {
+        let signature = bs58::encode(&transaction.signature).into_string();
+        let rows = transaction
+            .flattened_instructions()
+            .iter()
+            .map(|instruction| {
+                format!(
+                    "('{0}', {1}, {2}, {3}, '{4}', '{{{5}}}', '\\x{6}', {7})",
+                    &signature,
+                    &transaction.slot,
+                    &instruction.instruction_index,
+                    &instruction.inner_index,
+                    &bs58::encode(instruction.program_id).into_string(),
+                    &instruction.accounts.iter().map(|account| bs58::encode(account).into_string()).collect::<Vec<String>>().join(","),
+                    &hex::encode(instruction.data),
+                    instruction.stack_height.map_or("NULL".to_string(), |stack_height| stack_height.to_string()),
+                )
+            })
+            .collect::<Vec<String>>();
+        if rows.is_empty() {
+            return "".to_string();
+        }
+        format!(
+            "
+            INSERT INTO {}transaction_instruction (signature, slot, instruction_index, inner_index, program_id, accounts, data, stack_height) \
+            VALUES {} \
+            ON CONFLICT (signature, instruction_index, inner_index) DO NOTHING;
+        ",
+            prefix,
+            rows.join(", ")
+        )
     }
 
-    pub fn update(&self, client: &mut Client, transaction_info: DbTransaction) -> Result<(), GeyserPluginError> {
-        let result = client.query(
-            &self.upsert_statement,
+    pub fn update(&self, client: &mut Client, transaction_info: &DbTransaction) -> Result<(), postgres::Error> {
+        let result = self.upsert_statement.execute(
+            client,
             &[
                 &transaction_info.signature,
                 &transaction_info.is_vote,
@@ -659,12 +862,16 @@ impl TransactionHandler {
                 &transaction_info.write_version,
                 &transaction_info.index,
                 &Utc::now().naive_utc(),
+                &transaction_info.is_successful(),
+                &transaction_info.error_code(),
+                &transaction_info.failed_instruction_index,
+                &transaction_info.failed_program_id(),
             ],
         );
         if let Err(err) = result {
-            let msg = format!("Failed to persist the update of transaction info to the PostgreSQL database. Error: {:?}", err);
-            error!("{}", msg);
-            return Err(GeyserPluginError::AccountsUpdateError { msg });
+            record_db_error("log_transaction", &err);
+            error!("Failed to persist the update of transaction info to the PostgreSQL database. Error: {:?}", err);
+            return Err(err);
         }
 
         Ok(())
@@ -676,6 +883,7 @@ pub(crate) mod tests {
     use super::*;
     use solana_account_decoder::parse_token::UiTokenAmount;
     use solana_sdk::hash::Hash;
+    use solana_sdk::instruction::InstructionError;
     use solana_sdk::message::VersionedMessage;
     use solana_sdk::pubkey::Pubkey;
     use solana_sdk::signature::Keypair;
@@ -1200,6 +1408,32 @@ pub(crate) mod tests {
         let slot = 54;
         let db_transaction = build_db_transaction(slot, &transaction_info, 1);
         check_transaction(slot, &transaction_info, &db_transaction);
+        assert!(db_transaction.error_code().is_none());
+        assert!(db_transaction.failed_program_id().is_none());
+    }
+
+    #[test]
+    fn test_build_db_transaction_failed_instruction() {
+        let signature = Signature::new(&[1u8; 64]);
+        let transaction = VersionedTransaction::from(build_test_transaction_legacy());
+        let transaction = SanitizedTransaction::try_create(transaction, Hash::new_unique(), Some(false), SimpleAddressLoader::Disabled, false).unwrap();
+        let transaction_status_meta = TransactionStatusMeta {
+            status: Err(TransactionError::InstructionError(0, InstructionError::InsufficientFunds)),
+            ..build_transaction_status_meta()
+        };
+        let transaction_info = ReplicaTransactionInfoV2 {
+            index: 0,
+            signature: &signature,
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+        };
+
+        let db_transaction = build_db_transaction(54, &transaction_info, 1);
+        assert!(!db_transaction.is_successful());
+        assert_eq!(db_transaction.error_code(), Some(&DbTransactionErrorCode::InstructionError));
+        assert_eq!(db_transaction.failed_instruction_index, Some(0));
+        assert_eq!(db_transaction.failed_program_id(), Some(solana_sdk::system_program::id().to_string()));
     }
 
     fn build_test_transaction_v0() -> VersionedTransaction {
@@ -1243,4 +1477,74 @@ pub(crate) mod tests {
         let db_transaction = build_db_transaction(slot, &transaction_info, 1);
         check_transaction(slot, &transaction_info, &db_transaction);
     }
+
+    #[test]
+    fn test_flattened_instructions() {
+        let signature = Signature::new(&[1u8; 64]);
+        let transaction = system_transaction::transfer(&Keypair::new(), &Pubkey::new_unique(), 42, Hash::default());
+        let (from, to) = (transaction.message.account_keys[0], transaction.message.account_keys[1]);
+        let transaction = SanitizedTransaction::try_create(VersionedTransaction::from(transaction), Hash::new_unique(), Some(false), SimpleAddressLoader::Disabled, false).unwrap();
+        let mut transaction_status_meta = build_transaction_status_meta();
+        transaction_status_meta.inner_instructions = Some(vec![InnerInstructions {
+            index: 0,
+            instructions: vec![CompiledInstruction {
+                program_id_index: 2,
+                accounts: vec![1, 0],
+                data: vec![7, 8],
+            }],
+        }]);
+        transaction_status_meta.log_messages = Some(vec![
+            "Program 11111111111111111111111111111111 invoke [1]".to_string(),
+            "Program 11111111111111111111111111111111 invoke [2]".to_string(),
+            "Program 11111111111111111111111111111111 success".to_string(),
+            "Program 11111111111111111111111111111111 success".to_string(),
+        ]);
+        let transaction_info = ReplicaTransactionInfoV2 {
+            index: 0,
+            signature: &signature,
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+        };
+        let db_transaction = build_db_transaction(54, &transaction_info, 1);
+        let system_program = solana_sdk::system_program::id();
+        let instructions = db_transaction.flattened_instructions();
+        assert_eq!(instructions.len(), 2);
+        assert_eq!((instructions[0].instruction_index, instructions[0].inner_index, instructions[0].stack_height), (0, -1, Some(1)));
+        assert_eq!(instructions[0].program_id, system_program.as_ref());
+        assert_eq!(instructions[0].accounts, vec![from.as_ref(), to.as_ref()]);
+        assert_eq!(
+            instructions[1],
+            DbInstruction {
+                instruction_index: 0,
+                inner_index: 0,
+                program_id: system_program.as_ref(),
+                accounts: vec![to.as_ref(), from.as_ref()],
+                data: &[7, 8],
+                stack_height: Some(2),
+            }
+        );
+
+        let query = TransactionHandler::instructions_update(&TablePrefix::default(), &db_transaction);
+        assert!(
+            query.contains(&format!("'{}', 54, 0, 0, '11111111111111111111111111111111', '{{{},{}}}', '\\x0708', 2)", signature, to, from)),
+            "{}",
+            query
+        );
+        assert!(query.contains("ON CONFLICT (signature, instruction_index, inner_index) DO NOTHING"));
+
+        // without the logs the depth of the inner instructions is unknown
+        let mut without_logs = transaction_status_meta.clone();
+        without_logs.log_messages = None;
+        let transaction_info = ReplicaTransactionInfoV2 {
+            transaction_status_meta: &without_logs,
+            ..transaction_info
+        };
+        let instructions = build_db_transaction(54, &transaction_info, 1)
+            .flattened_instructions()
+            .into_iter()
+            .map(|i| i.stack_height)
+            .collect::<Vec<_>>();
+        assert_eq!(instructions, vec![Some(1), None]);
+    }
 }
@@ -0,0 +1,185 @@
+use crate::postgres_client::accounts::idl_registry::IdlRegistry;
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::transactions::transaction_router::TransactionUpdateHandler;
+use crate::postgres_client::DbTransaction;
+use std::sync::Arc;
+
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+/// Finds the `Program data:` log lines emitted by `emit!` together with the
+/// program that was executing when they were logged
+fn program_data_logs(log_messages: &[String]) -> Vec<(&str, &str)> {
+    let mut invocations: Vec<&str> = Vec::new();
+    let mut program_data = Vec::new();
+    for log in log_messages {
+        if let Some(data) = log.strip_prefix(PROGRAM_DATA_PREFIX) {
+            if let Some(program_id) = invocations.last() {
+                program_data.push((*program_id, data));
+            }
+            continue;
+        }
+        let mut words = log.split_whitespace();
+        if words.next() != Some("Program") {
+            continue;
+        }
+        match (words.next(), words.next()) {
+            (Some(program_id), Some("invoke")) => invocations.push(program_id),
+            (Some(_), Some("success")) | (Some(_), Some("failed:")) => {
+                invocations.pop();
+            }
+            _ => {}
+        }
+    }
+    program_data
+}
+
+/// Decodes Anchor events of the programs configured in `idl` from transaction
+/// logs into the `anchor_event` table
+pub struct AnchorEventHandler {
+    pub registry: Arc<IdlRegistry>,
+    pub prefix: TablePrefix,
+}
+
+impl TransactionUpdateHandler for AnchorEventHandler {
+    fn init(&self, _config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}anchor_event (
+                signature VARCHAR(88) NOT NULL,
+                slot BIGINT NOT NULL,
+                event_index SMALLINT NOT NULL,
+                program_id VARCHAR(44) NOT NULL,
+                event_name VARCHAR(64) NOT NULL,
+                data JSONB NOT NULL,
+                PRIMARY KEY (signature, event_index)
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}anchor_event_program_id_event_name ON {prefix}anchor_event (program_id, event_name);
+            CREATE INDEX IF NOT EXISTS {prefix}anchor_event_slot ON {prefix}anchor_event (slot);
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn transaction_update(&self, transaction: &DbTransaction) -> String {
+        // events of failed transactions were rolled back
+        if !transaction.is_successful() {
+            return "".to_string();
+        }
+        let log_messages = match &transaction.meta.log_messages {
+            Some(log_messages) => log_messages,
+            None => return "".to_string(),
+        };
+        let signature = bs58::encode(&transaction.signature).into_string();
+        program_data_logs(log_messages)
+            .into_iter()
+            .filter_map(|(program_id, data)| {
+                let program = self.registry.get(&bs58::decode(program_id).into_vec().ok()?)?;
+                let data = base64::decode(data).ok()?;
+                let (event_name, value) = program.decoder.decode_event(&data)?;
+                Some((program_id, event_name.to_string(), value))
+            })
+            .enumerate()
+            .map(|(event_index, (program_id, event_name, value))| {
+                format!(
+                    "
+                    INSERT INTO {6}anchor_event (signature, slot, event_index, program_id, event_name, data) \
+                    VALUES ('{0}', {1}, {2}, '{3}', {4}, {5}) \
+                    ON CONFLICT (signature, event_index) DO NOTHING;
+                ",
+                    &signature,
+                    &transaction.slot,
+                    event_index,
+                    program_id,
+                    event_name.sql_literal(),
+                    value.to_string().sql_literal(),
+                    self.prefix,
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("")
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::postgres_client::accounts::idl_decoder::IdlDecoder;
+    use crate::postgres_client::accounts::idl_registry::IdlProgram;
+    use crate::postgres_client::build_db_transaction;
+    use crate::postgres_client::transactions::token_transfer_handler::tests::build_transfer_checked_transaction;
+    use borsh::BorshSerialize;
+    use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoV2;
+    use solana_program::hash::hash;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Signature;
+    use solana_transaction_status::TransactionStatusMeta;
+
+    #[test]
+    fn test_program_data_logs() {
+        let logs = [
+            "Program stkBL96RZkjY5ine4TvPihGqW8UHJfch2cokjAPzV8i invoke [1]",
+            "Program log: Instruction: Stake",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA invoke [2]",
+            "Program data: dG9rZW4=",
+            "Program TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA success",
+            "Program data: c3Rha2U=",
+            "Program stkBL96RZkjY5ine4TvPihGqW8UHJfch2cokjAPzV8i consumed 2000 of 200000 compute units",
+            "Program stkBL96RZkjY5ine4TvPihGqW8UHJfch2cokjAPzV8i success",
+        ]
+        .map(String::from);
+        assert_eq!(
+            program_data_logs(&logs),
+            vec![("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", "dG9rZW4="), ("stkBL96RZkjY5ine4TvPihGqW8UHJfch2cokjAPzV8i", "c3Rha2U="),]
+        );
+    }
+
+    #[test]
+    fn test_transaction_update() {
+        let program_id = Pubkey::new_unique();
+        let idl = serde_json::from_value(serde_json::json!({
+            "name": "stake_pool",
+            "events": [{ "name": "Staked", "fields": [{ "name": "name", "type": "string" }] }]
+        }))
+        .unwrap();
+        let registry = IdlRegistry::default();
+        registry.insert(
+            program_id.to_bytes().to_vec(),
+            IdlProgram {
+                table: "stake_pool".to_string(),
+                decoder: IdlDecoder::new(idl),
+                idl_address: Vec::new(),
+                pinned: true,
+                slot: 0,
+            },
+        );
+        let mut data = hash(b"event:Staked").to_bytes()[..8].to_vec();
+        "pool's\0\0\0\0".to_string().serialize(&mut data).unwrap();
+
+        let (source, mint, destination, authority) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let transaction = build_transfer_checked_transaction(&source, &mint, &destination, &authority, 42);
+        let transaction_status_meta = TransactionStatusMeta {
+            log_messages: Some(vec![
+                format!("Program {} invoke [1]", program_id),
+                format!("{}{}", PROGRAM_DATA_PREFIX, base64::encode(&data)),
+                format!("Program {} success", program_id),
+            ]),
+            ..TransactionStatusMeta::default()
+        };
+        let signature = Signature::new(&[1u8; 64]);
+        let transaction_info = ReplicaTransactionInfoV2 {
+            index: 0,
+            signature: &signature,
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+        };
+        let handler = AnchorEventHandler {
+            registry: Arc::new(registry),
+            prefix: TablePrefix::default(),
+        };
+        let query = handler.transaction_update(&build_db_transaction(7, &transaction_info, 1));
+        // the NUL padding is stripped from the decoded string and the quote is escaped
+        assert!(query.contains(&format!("'{}', 7, 0, '{}', 'Staked', '{{\"name\":\"pool''s\"}}')", signature, program_id)), "{}", query);
+    }
+}
@@ -0,0 +1,7 @@
+#[cfg(feature = "idl")]
+pub mod anchor_event_handler;
+#[cfg(feature = "cardinal")]
+pub mod token_manager_event_handler;
+pub mod token_transfer_handler;
+pub mod transaction_meta_handler;
+pub mod transaction_router;
@@ -0,0 +1,170 @@
+use solana_program::hash::hash;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::postgres_client::accounts::token_manager_handler::TOKEN_MANAGER_PROGRAM_ID;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::transactions::transaction_router::TransactionUpdateHandler;
+use crate::postgres_client::DbTransaction;
+
+/// The token manager instructions and whether their first account is the token manager
+const TOKEN_MANAGER_INSTRUCTIONS: [(&str, bool); 17] = [
+    ("init", true),
+    ("uninit", true),
+    ("set_claim_approver", true),
+    ("set_transfer_authority", true),
+    ("add_invalidator", true),
+    ("claim_receipt_mint", true),
+    ("issue", true),
+    ("unissue", true),
+    ("claim", true),
+    ("invalidate", true),
+    ("update_invalidation_type", true),
+    ("create_claim_receipt", true),
+    ("create_transfer_receipt", true),
+    ("update_transfer_receipt", true),
+    ("transfer", true),
+    ("create_mint_manager", false),
+    ("close_mint_manager", false),
+];
+
+/// The Anchor discriminator of an instruction
+fn instruction_discriminator(name: &str) -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash(format!("global:{}", name).as_bytes()).to_bytes()[..8]);
+    discriminator
+}
+
+/// Decodes the instructions of the Cardinal token manager program, including
+/// those invoked through CPI, into the `token_manager_event` table
+pub struct TokenManagerEventHandler {
+    instructions: Vec<([u8; 8], &'static str, bool)>,
+    prefix: TablePrefix,
+}
+
+impl Default for TokenManagerEventHandler {
+    fn default() -> Self {
+        Self::new(TablePrefix::default())
+    }
+}
+
+impl TokenManagerEventHandler {
+    pub fn new(prefix: TablePrefix) -> Self {
+        Self {
+            instructions: TOKEN_MANAGER_INSTRUCTIONS
+                .iter()
+                .map(|(name, token_manager_first)| (instruction_discriminator(name), *name, *token_manager_first))
+                .collect(),
+            prefix,
+        }
+    }
+}
+
+impl TransactionUpdateHandler for TokenManagerEventHandler {
+    fn init(&self, _config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}token_manager_event (
+                signature VARCHAR(88) NOT NULL,
+                slot BIGINT NOT NULL,
+                instruction_index SMALLINT NOT NULL,
+                inner_index SMALLINT NOT NULL,
+                instruction VARCHAR(64),
+                token_manager VARCHAR(44),
+                accounts VARCHAR(44)[] NOT NULL,
+                data BYTEA NOT NULL,
+                PRIMARY KEY (signature, instruction_index, inner_index)
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}token_manager_event_token_manager ON {prefix}token_manager_event (token_manager, slot);
+            CREATE INDEX IF NOT EXISTS {prefix}token_manager_event_slot ON {prefix}token_manager_event (slot);
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn program_ids(&self) -> Vec<Pubkey> {
+        vec![TOKEN_MANAGER_PROGRAM_ID]
+    }
+
+    fn transaction_update(&self, transaction: &DbTransaction) -> String {
+        // the instructions of failed transactions changed nothing
+        if !transaction.is_successful() {
+            return "".to_string();
+        }
+        let signature = bs58::encode(&transaction.signature).into_string();
+        transaction
+            .flattened_instructions()
+            .iter()
+            .filter(|instruction| instruction.program_id == TOKEN_MANAGER_PROGRAM_ID.as_ref())
+            .map(|instruction| {
+                // instructions added to the program since are stored without a name
+                let known = self.instructions.iter().find(|(discriminator, _, _)| instruction.data.get(0..8) == Some(&discriminator[..]));
+                let token_manager = match known {
+                    Some((_, _, true)) => instruction.accounts.first().map(|account| bs58::encode(account).into_string()),
+                    _ => None,
+                };
+                format!(
+                    "
+                    INSERT INTO {8}token_manager_event (signature, slot, instruction_index, inner_index, instruction, token_manager, accounts, data) \
+                    VALUES ('{0}', {1}, {2}, {3}, {4}, {5}, '{{{6}}}', '\\x{7}') \
+                    ON CONFLICT (signature, instruction_index, inner_index) DO NOTHING;
+                ",
+                    &signature,
+                    &transaction.slot,
+                    &instruction.instruction_index,
+                    &instruction.inner_index,
+                    known.map_or("NULL".to_string(), |(_, name, _)| format!("'{}'", name)),
+                    token_manager.map_or("NULL".to_string(), |token_manager| format!("'{}'", token_manager)),
+                    &instruction.accounts.iter().map(|account| bs58::encode(account).into_string()).collect::<Vec<String>>().join(","),
+                    &hex::encode(instruction.data),
+                    self.prefix,
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::build_db_transaction;
+    use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoV2;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::instruction::AccountMeta;
+    use solana_sdk::instruction::Instruction;
+    use solana_sdk::signature::Signature;
+    use solana_sdk::transaction::SanitizedTransaction;
+    use solana_sdk::transaction::SimpleAddressLoader;
+    use solana_sdk::transaction::Transaction;
+    use solana_sdk::transaction::VersionedTransaction;
+    use solana_transaction_status::TransactionStatusMeta;
+
+    #[test]
+    fn test_transaction_update() {
+        let (token_manager, recipient) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let claim = Instruction::new_with_bytes(
+            TOKEN_MANAGER_PROGRAM_ID,
+            &instruction_discriminator("claim"),
+            vec![AccountMeta::new(token_manager, false), AccountMeta::new(recipient, true)],
+        );
+        let unknown = Instruction::new_with_bytes(TOKEN_MANAGER_PROGRAM_ID, &[9u8; 8], vec![AccountMeta::new(token_manager, false)]);
+        let transaction = VersionedTransaction::from(Transaction::new_with_payer(&[claim, unknown], Some(&recipient)));
+        let transaction = SanitizedTransaction::try_create(transaction, Hash::new_unique(), Some(false), SimpleAddressLoader::Disabled, false).unwrap();
+        let transaction_status_meta = TransactionStatusMeta::default();
+        let signature = Signature::new(&[1u8; 64]);
+        let transaction_info = ReplicaTransactionInfoV2 {
+            index: 0,
+            signature: &signature,
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+        };
+
+        let handler = TokenManagerEventHandler::default();
+        let db_transaction = build_db_transaction(7, &transaction_info, 1);
+        assert!(handler.transaction_match(&db_transaction));
+        let query = handler.transaction_update(&db_transaction);
+        assert!(query.contains(&format!("'{}', 7, 0, -1, 'claim', '{}', '{{{},{}}}'", signature, token_manager, token_manager, recipient)));
+        assert!(query.contains(&format!("'{}', 7, 1, -1, NULL, NULL, '{{{}}}'", signature, token_manager)));
+    }
+}
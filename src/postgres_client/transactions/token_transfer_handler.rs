@@ -0,0 +1,202 @@
+use crate::postgres_client::accounts::token_account_handler::TOKENZ_PROGRAM_ID;
+use crate::postgres_client::accounts::token_account_handler::TOKEN_PROGRAM_ID;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::transaction_handler::DbCompiledInstruction;
+use crate::postgres_client::transaction_handler::OUTER_INSTRUCTION_INNER_INDEX;
+use crate::postgres_client::transactions::transaction_router::TransactionUpdateHandler;
+use crate::postgres_client::DbTransaction;
+
+const SPL_TOKEN_TRANSFER_TAG: u8 = 3;
+const SPL_TOKEN_TRANSFER_CHECKED_TAG: u8 = 12;
+
+struct TokenTransfer<'a> {
+    program_id: &'a [u8],
+    source: &'a [u8],
+    destination: &'a [u8],
+    authority: &'a [u8],
+    mint: Option<&'a [u8]>,
+    amount: u64,
+}
+
+fn decode_token_transfer<'a>(account_keys: &[&'a [u8]], instruction: &DbCompiledInstruction) -> Option<TokenTransfer<'a>> {
+    let program_id = *account_keys.get(instruction.program_id_index as usize)?;
+    if program_id != TOKEN_PROGRAM_ID.as_ref() && program_id != TOKENZ_PROGRAM_ID.as_ref() {
+        return None;
+    }
+    let account = |position: usize| -> Option<&'a [u8]> { account_keys.get(*instruction.accounts.get(position)? as usize).copied() };
+    let amount = u64::from_le_bytes(instruction.data.get(1..9)?.try_into().ok()?);
+    match *instruction.data.first()? {
+        SPL_TOKEN_TRANSFER_TAG => Some(TokenTransfer {
+            program_id,
+            source: account(0)?,
+            destination: account(1)?,
+            authority: account(2)?,
+            mint: None,
+            amount,
+        }),
+        SPL_TOKEN_TRANSFER_CHECKED_TAG => Some(TokenTransfer {
+            program_id,
+            source: account(0)?,
+            mint: Some(account(1)?),
+            destination: account(2)?,
+            authority: account(3)?,
+            amount,
+        }),
+        _ => None,
+    }
+}
+
+/// Decodes SPL token `Transfer` and `TransferChecked` instructions, including
+/// those invoked through CPI, into the `token_transfer` table
+#[derive(Default)]
+pub struct TokenTransferHandler {
+    pub prefix: TablePrefix,
+}
+
+impl TransactionUpdateHandler for TokenTransferHandler {
+    fn init(&self, _config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}token_transfer (
+                signature VARCHAR(88) NOT NULL,
+                slot BIGINT NOT NULL,
+                instruction_index SMALLINT NOT NULL,
+                inner_index SMALLINT NOT NULL,
+                program_id VARCHAR(44) NOT NULL,
+                source VARCHAR(44) NOT NULL,
+                destination VARCHAR(44) NOT NULL,
+                authority VARCHAR(44) NOT NULL,
+                mint VARCHAR(44),
+                amount NUMERIC(20, 0) NOT NULL,
+                PRIMARY KEY (signature, instruction_index, inner_index)
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}token_transfer_source ON {prefix}token_transfer (source);
+            CREATE INDEX IF NOT EXISTS {prefix}token_transfer_destination ON {prefix}token_transfer (destination);
+            CREATE INDEX IF NOT EXISTS {prefix}token_transfer_slot ON {prefix}token_transfer (slot);
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn transaction_update(&self, transaction: &DbTransaction) -> String {
+        // failed transactions did not move any tokens
+        if !transaction.is_successful() {
+            return "".to_string();
+        }
+        let account_keys = transaction.account_keys();
+        let outer_instructions = transaction
+            .instructions()
+            .iter()
+            .enumerate()
+            .map(|(index, instruction)| (index as i16, OUTER_INSTRUCTION_INNER_INDEX, instruction));
+        let inner_instructions = transaction.meta.inner_instructions.iter().flatten().flat_map(|inner_instructions| {
+            inner_instructions
+                .instructions
+                .iter()
+                .enumerate()
+                .map(move |(inner_index, instruction)| (inner_instructions.index, inner_index as i16, instruction))
+        });
+        let signature = bs58::encode(&transaction.signature).into_string();
+        outer_instructions
+            .chain(inner_instructions)
+            .filter_map(|(instruction_index, inner_index, instruction)| {
+                let transfer = decode_token_transfer(&account_keys, instruction)?;
+                Some(format!(
+                    "
+                    INSERT INTO {10}token_transfer (signature, slot, instruction_index, inner_index, program_id, source, destination, authority, mint, amount) \
+                    VALUES ('{0}', {1}, {2}, {3}, '{4}', '{5}', '{6}', '{7}', {8}, {9}) \
+                    ON CONFLICT (signature, instruction_index, inner_index) DO NOTHING;
+                ",
+                    &signature,
+                    &transaction.slot,
+                    &instruction_index,
+                    &inner_index,
+                    &bs58::encode(transfer.program_id).into_string(),
+                    &bs58::encode(transfer.source).into_string(),
+                    &bs58::encode(transfer.destination).into_string(),
+                    &bs58::encode(transfer.authority).into_string(),
+                    transfer.mint.map_or("NULL".to_string(), |mint| format!("'{}'", bs58::encode(mint).into_string())),
+                    &transfer.amount,
+                    self.prefix,
+                ))
+            })
+            .collect::<Vec<String>>()
+            .join("")
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::postgres_client::build_db_transaction;
+    use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoV2;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::instruction::AccountMeta;
+    use solana_sdk::instruction::Instruction;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Signature;
+    use solana_sdk::transaction::SanitizedTransaction;
+    use solana_sdk::transaction::SimpleAddressLoader;
+    use solana_sdk::transaction::Transaction;
+    use solana_sdk::transaction::TransactionError;
+    use solana_sdk::transaction::VersionedTransaction;
+    use solana_transaction_status::TransactionStatusMeta;
+
+    pub(crate) fn build_transfer_checked_transaction(source: &Pubkey, mint: &Pubkey, destination: &Pubkey, authority: &Pubkey, amount: u64) -> SanitizedTransaction {
+        let mut data = vec![SPL_TOKEN_TRANSFER_CHECKED_TAG];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.push(0);
+        let instruction = Instruction::new_with_bytes(
+            TOKEN_PROGRAM_ID,
+            &data,
+            vec![
+                AccountMeta::new(*source, false),
+                AccountMeta::new_readonly(*mint, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(*authority, true),
+            ],
+        );
+        let transaction = VersionedTransaction::from(Transaction::new_with_payer(&[instruction], Some(authority)));
+        SanitizedTransaction::try_create(transaction, Hash::new_unique(), Some(false), SimpleAddressLoader::Disabled, false).unwrap()
+    }
+
+    #[test]
+    fn test_transaction_update_transfer_checked() {
+        let (source, mint, destination, authority) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let transaction = build_transfer_checked_transaction(&source, &mint, &destination, &authority, 42);
+        let transaction_status_meta = TransactionStatusMeta::default();
+        let signature = Signature::new(&[1u8; 64]);
+        let transaction_info = ReplicaTransactionInfoV2 {
+            index: 0,
+            signature: &signature,
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+        };
+
+        let query = TokenTransferHandler::default().transaction_update(&build_db_transaction(7, &transaction_info, 1));
+        assert!(query.contains(&format!("'{}', '{}', '{}', '{}'", source, destination, authority, mint)));
+        assert!(query.contains(", 42)"));
+    }
+
+    #[test]
+    fn test_transaction_update_skips_failed_transactions() {
+        let (source, mint, destination, authority) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let transaction = build_transfer_checked_transaction(&source, &mint, &destination, &authority, 42);
+        let transaction_status_meta = TransactionStatusMeta {
+            status: Err(TransactionError::AccountInUse),
+            ..TransactionStatusMeta::default()
+        };
+        let signature = Signature::new(&[1u8; 64]);
+        let transaction_info = ReplicaTransactionInfoV2 {
+            index: 0,
+            signature: &signature,
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+        };
+
+        let query = TokenTransferHandler::default().transaction_update(&build_db_transaction(7, &transaction_info, 1));
+        assert!(query.is_empty());
+    }
+}
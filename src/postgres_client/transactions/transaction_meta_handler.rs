@@ -0,0 +1,170 @@
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use crate::postgres_client::transaction_handler::DbTransactionTokenBalance;
+use crate::postgres_client::transactions::transaction_router::TransactionUpdateHandler;
+use crate::postgres_client::DbTransaction;
+use serde_json::json;
+use serde_json::Value;
+
+/// Resolves the account of each token balance against the account keys of the message
+fn token_balances_json(account_keys: &[&[u8]], token_balances: &Option<Vec<DbTransactionTokenBalance>>) -> String {
+    let token_balances = match token_balances {
+        Some(token_balances) => token_balances,
+        None => return "NULL".to_string(),
+    };
+    let balances: Vec<Value> = token_balances
+        .iter()
+        .map(|balance| {
+            json!({
+                "account": account_keys.get(balance.account_index as usize).map(|key| bs58::encode(key).into_string()),
+                "mint": balance.mint,
+                "owner": balance.owner,
+                "ui_amount": balance.ui_token_amount,
+            })
+        })
+        .collect();
+    Value::Array(balances).to_string().sql_literal()
+}
+
+fn array_literal<T: ToString>(values: impl Iterator<Item = T>) -> String {
+    values.map(|value| value.to_string()).collect::<Vec<String>>().join(",")
+}
+
+/// Extracts the fee, compute units and balance changes of the status meta into
+/// the `transaction_meta` table, one row per transaction
+#[derive(Default)]
+pub struct TransactionMetaHandler {
+    pub prefix: TablePrefix,
+}
+
+impl TransactionUpdateHandler for TransactionMetaHandler {
+    fn init(&self, _config: &crate::config::GeyserPluginPostgresConfig) -> String {
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}transaction_meta (
+                signature VARCHAR(88) PRIMARY KEY,
+                slot BIGINT NOT NULL,
+                fee_payer VARCHAR(44) NOT NULL,
+                success BOOL NOT NULL,
+                fee BIGINT NOT NULL,
+                compute_units_consumed BIGINT,
+                account_keys VARCHAR(44)[] NOT NULL,
+                pre_balances BIGINT[] NOT NULL,
+                post_balances BIGINT[] NOT NULL,
+                balance_deltas BIGINT[] NOT NULL,
+                pre_token_balances JSONB,
+                post_token_balances JSONB
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}transaction_meta_slot ON {prefix}transaction_meta (slot);
+            CREATE INDEX IF NOT EXISTS {prefix}transaction_meta_fee_payer ON {prefix}transaction_meta (fee_payer);
+        ",
+            prefix = self.prefix
+        );
+    }
+
+    fn transaction_update(&self, transaction: &DbTransaction) -> String {
+        let account_keys = transaction.account_keys();
+        let fee_payer = match account_keys.first() {
+            Some(fee_payer) => bs58::encode(fee_payer).into_string(),
+            None => return "".to_string(),
+        };
+        let meta = &transaction.meta;
+        // the balances of the accounts missing from either side are not known, they have no delta
+        let balance_deltas = meta.pre_balances.iter().zip(meta.post_balances.iter()).map(|(pre, post)| post - pre);
+        format!(
+            "
+            INSERT INTO {12}transaction_meta (signature, slot, fee_payer, success, fee, compute_units_consumed, account_keys, \
+                pre_balances, post_balances, balance_deltas, pre_token_balances, post_token_balances) \
+            VALUES ('{0}', {1}, '{2}', {3}, {4}, {5}, '{{{6}}}', '{{{7}}}', '{{{8}}}', '{{{9}}}', {10}, {11}) \
+            ON CONFLICT (signature) DO NOTHING;
+        ",
+            bs58::encode(&transaction.signature).into_string(),
+            &transaction.slot,
+            &fee_payer,
+            transaction.is_successful(),
+            &meta.fee,
+            transaction.compute_units_consumed.map_or("NULL".to_string(), |units| units.to_string()),
+            array_literal(account_keys.iter().map(|key| bs58::encode(key).into_string())),
+            array_literal(meta.pre_balances.iter()),
+            array_literal(meta.post_balances.iter()),
+            array_literal(balance_deltas),
+            token_balances_json(&account_keys, &meta.pre_token_balances),
+            token_balances_json(&account_keys, &meta.post_token_balances),
+            self.prefix,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::build_db_transaction;
+    use crate::postgres_client::transactions::token_transfer_handler::tests::build_transfer_checked_transaction;
+    use solana_account_decoder::parse_token::UiTokenAmount;
+    use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoV2;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Signature;
+    use solana_transaction_status::TransactionStatusMeta;
+    use solana_transaction_status::TransactionTokenBalance;
+
+    fn token_balance(account_index: u8, mint: &Pubkey, owner: &Pubkey, ui_amount: f64) -> TransactionTokenBalance {
+        TransactionTokenBalance {
+            account_index,
+            mint: mint.to_string(),
+            ui_token_amount: UiTokenAmount {
+                ui_amount: Some(ui_amount),
+                decimals: 0,
+                amount: ui_amount.to_string(),
+                ui_amount_string: ui_amount.to_string(),
+            },
+            owner: owner.to_string(),
+            program_id: "".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_transaction_update() {
+        let (source, mint, destination, authority) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let transaction = build_transfer_checked_transaction(&source, &mint, &destination, &authority, 42);
+        let transaction_status_meta = TransactionStatusMeta {
+            fee: 5000,
+            pre_balances: vec![100000, 2039280, 2039280, 1461600, 1],
+            post_balances: vec![95000, 2039280, 2039280, 1461600, 1],
+            pre_token_balances: Some(vec![token_balance(1, &mint, &authority, 50.0)]),
+            post_token_balances: Some(vec![token_balance(1, &mint, &authority, 8.0)]),
+            compute_units_consumed: Some(6200),
+            ..TransactionStatusMeta::default()
+        };
+        let signature = Signature::new(&[1u8; 64]);
+        let transaction_info = ReplicaTransactionInfoV2 {
+            index: 0,
+            signature: &signature,
+            is_vote: false,
+            transaction: &transaction,
+            transaction_status_meta: &transaction_status_meta,
+        };
+
+        let db_transaction = build_db_transaction(7, &transaction_info, 1);
+        let query = TransactionMetaHandler::default().transaction_update(&db_transaction);
+        assert!(query.contains(&format!("'{}', 7, '{}', true, 5000, 6200,", signature, authority)));
+        assert!(query.contains("'{100000,2039280,2039280,1461600,1}', '{95000,2039280,2039280,1461600,1}', '{-5000,0,0,0,0}'"));
+        let account = bs58::encode(db_transaction.account_keys()[1]).into_string();
+        assert!(query.contains(&format!("\"account\":\"{}\"", account)));
+        assert!(query.contains("\"ui_amount\":8.0"));
+    }
+
+    #[test]
+    fn test_token_balances_json() {
+        let token_balances = Some(vec![DbTransactionTokenBalance {
+            account_index: 0,
+            mint: "it's".to_string(),
+            ui_token_amount: None,
+            owner: "".to_string(),
+        }]);
+        let account = Pubkey::new_unique();
+        let json = token_balances_json(&[account.as_ref()], &token_balances);
+        assert!(json.starts_with("'[{") && json.ends_with("}]'"), "{}", json);
+        assert!(json.contains("\"mint\":\"it''s\""), "{}", json);
+        assert_eq!(token_balances_json(&[], &None), "NULL");
+    }
+}
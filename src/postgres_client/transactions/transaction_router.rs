@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::transaction_selector::TransactionMentionsConfig;
+use crate::transaction_selector::TransactionSelectorConfig;
+
+use super::super::DbTransaction;
+#[cfg(feature = "idl")]
+use super::anchor_event_handler::AnchorEventHandler;
+#[cfg(feature = "cardinal")]
+use super::token_manager_event_handler::TokenManagerEventHandler;
+use super::token_transfer_handler::TokenTransferHandler;
+use super::transaction_meta_handler::TransactionMetaHandler;
+use crate::config::GeyserPluginPostgresConfig;
+use crate::postgres_client::accounts::idl_registry::IdlRegistry;
+use crate::postgres_client::table_prefix::TablePrefix;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum TransactionHandlerId {
+    Transaction,
+    TransactionInstruction,
+    TokenTransfer,
+    TransactionMeta,
+    AnchorEvent,
+    TokenManagerEvent,
+    /// A handler registered by an external library
+    External(String),
+}
+#[derive(Debug)]
+pub struct UnknownTransactionHandlerId;
+
+impl TransactionHandlerId {
+    /// Built in handler ids, any other id refers to an external handler
+    pub fn resolve(handler_id: &str) -> Self {
+        Self::from_str(handler_id).unwrap_or_else(|_| Self::External(handler_id.to_string()))
+    }
+
+    /// The id used in the selectors
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Transaction => "transaction",
+            Self::TransactionInstruction => "transaction_instruction",
+            Self::TokenTransfer => "token_transfer",
+            Self::TransactionMeta => "transaction_meta",
+            Self::AnchorEvent => "anchor_event",
+            Self::TokenManagerEvent => "token_manager_event",
+            Self::External(handler_id) => handler_id,
+        }
+    }
+
+    /// The Cargo feature the handler is compiled with, if it is not always built in
+    pub fn feature(&self) -> Option<&'static str> {
+        match self {
+            Self::AnchorEvent => Some("idl"),
+            Self::TokenManagerEvent => Some("cardinal"),
+            _ => None,
+        }
+    }
+
+    /// Whether the plugin was built with the handler
+    pub fn is_compiled(&self) -> bool {
+        match self {
+            Self::AnchorEvent => cfg!(feature = "idl"),
+            Self::TokenManagerEvent => cfg!(feature = "cardinal"),
+            _ => true,
+        }
+    }
+}
+
+/// A built-in handler writing the rows of its own tables for the transactions routed to it by the `transaction_selector`
+pub trait TransactionUpdateHandler {
+    fn init(&self, config: &GeyserPluginPostgresConfig) -> String;
+
+    /// The programs whose instructions the handler decodes, every transaction concerns the handler when empty
+    fn program_ids(&self) -> Vec<Pubkey> {
+        Vec::new()
+    }
+
+    /// Whether the transaction concerns the handler, by default whether it mentions one of the handler's `program_ids`
+    fn transaction_match(&self, transaction: &DbTransaction) -> bool {
+        let program_ids = self.program_ids();
+        program_ids.is_empty() || transaction.account_keys().iter().any(|key| program_ids.iter().any(|program_id| program_id.as_ref() == *key))
+    }
+
+    fn transaction_update(&self, transaction: &DbTransaction) -> String;
+}
+
+/// A transaction handler registered by an external library
+pub trait CustomTransactionHandler {
+    fn init(&self, config: &GeyserPluginPostgresConfig) -> String;
+
+    fn transaction_update(&self, transaction: &DbTransaction) -> String;
+}
+
+impl FromStr for TransactionHandlerId {
+    type Err = UnknownTransactionHandlerId;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "transaction" => Ok(Self::Transaction),
+            "transaction_instruction" => Ok(Self::TransactionInstruction),
+            "token_transfer" => Ok(Self::TokenTransfer),
+            "transaction_meta" => Ok(Self::TransactionMeta),
+            "anchor_event" => Ok(Self::AnchorEvent),
+            "token_manager_event" => Ok(Self::TokenManagerEvent),
+            _ => Err(UnknownTransactionHandlerId),
+        }
+    }
+}
+
+/// The built-in handlers other than `transaction` and `transaction_instruction`, which write through the
+/// `TransactionHandler`. Those of the handler families left out of the build are not registered
+#[cfg_attr(not(feature = "idl"), allow(unused_variables))]
+pub fn all_transaction_handlers(config: &GeyserPluginPostgresConfig, idl_registry: Arc<IdlRegistry>) -> HashMap<TransactionHandlerId, Box<dyn TransactionUpdateHandler>> {
+    let prefix = || TablePrefix::new(config);
+    let mut transaction_handlers: HashMap<TransactionHandlerId, Box<dyn TransactionUpdateHandler>> = HashMap::default();
+    transaction_handlers.insert(TransactionHandlerId::TokenTransfer, Box::new(TokenTransferHandler { prefix: prefix() }));
+    transaction_handlers.insert(TransactionHandlerId::TransactionMeta, Box::new(TransactionMetaHandler { prefix: prefix() }));
+    #[cfg(feature = "idl")]
+    transaction_handlers.insert(
+        TransactionHandlerId::AnchorEvent,
+        Box::new(AnchorEventHandler {
+            registry: idl_registry,
+            prefix: prefix(),
+        }),
+    );
+    #[cfg(feature = "cardinal")]
+    transaction_handlers.insert(TransactionHandlerId::TokenManagerEvent, Box::new(TokenManagerEventHandler::new(prefix())));
+    transaction_handlers
+}
+
+pub fn select_transaction_handlers(transaction_selector: &Option<TransactionSelectorConfig>, transaction: &DbTransaction) -> Vec<TransactionHandlerId> {
+    let handlers = match transaction_selector.as_ref().map(|selector| &selector.mentions) {
+        // a plain list of mentions stores the raw transaction
+        Some(TransactionMentionsConfig::Addresses(_)) => return vec![TransactionHandlerId::Transaction],
+        Some(TransactionMentionsConfig::Handlers(handlers)) => handlers,
+        None => return vec![],
+    };
+    let account_keys: HashSet<String> = transaction.account_keys().iter().map(|k| bs58::encode(k).into_string()).collect();
+    let mut selected_handlers = Vec::new();
+    for (key, key_handlers) in handlers {
+        let is_mentioned = match key.as_str() {
+            "*" | "all" => true,
+            "all_votes" => transaction.is_vote,
+            _ => account_keys.contains(key),
+        };
+        if !is_mentioned {
+            continue;
+        }
+        for handler in key_handlers {
+            let handler_id = TransactionHandlerId::resolve(&handler.handler_id);
+            if !selected_handlers.contains(&handler_id) {
+                selected_handlers.push(handler_id);
+            }
+        }
+    }
+    selected_handlers
+}
@@ -0,0 +1,165 @@
+use super::SimplePostgresClient;
+use crate::config::GeyserPluginPostgresConfig;
+use crate::geyser_plugin_postgres::GeyserPluginPostgresError;
+use crate::metrics::registry;
+use crate::metrics::UNLOAD_REQUESTS_TOTAL;
+use crate::postgres_client::db_errors::record_db_error;
+use crate::postgres_client::heartbeat_handler::last_processed_slot;
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use chrono::Utc;
+use log::*;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use serde_json::json;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use std::collections::BTreeMap;
+
+/// * The `unload` section bounds how long the queued requests are drained on unload.
+/// "unload" : { "drain_timeout_secs" : 30, "write_summary" : true }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UnloadConfig {
+    /// Seconds the workers keep running the queued requests before the rest are dropped
+    pub drain_timeout_secs: u64,
+    /// Write the summary to the `unload_summary` table
+    pub write_summary: bool,
+}
+
+impl Default for UnloadConfig {
+    fn default() -> Self {
+        Self {
+            drain_timeout_secs: 30,
+            write_summary: false,
+        }
+    }
+}
+
+/// Count a request queued when the plugin was unloaded, `outcome` being `processed`, `dropped` or `unflushed`
+pub fn record_unload_request(request: &'static str, outcome: &'static str, count: u64) {
+    registry().inc_counter(UNLOAD_REQUESTS_TOTAL, &[("request", request), ("outcome", outcome)], count);
+}
+
+/// What happened to the requests queued when the plugin was unloaded
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnloadSummary {
+    /// From the unload to the exit of the last worker
+    pub duration_ms: u64,
+    /// The highest slot written by the workers
+    pub last_processed_slot: u64,
+    /// Requests run by the workers while draining, by request
+    pub processed: BTreeMap<String, u64>,
+    /// Requests still queued once the drain timed out, by request
+    pub dropped: BTreeMap<String, u64>,
+    /// Account updates run by the workers but lost with a failed final flush
+    pub unflushed: BTreeMap<String, u64>,
+}
+
+impl UnloadSummary {
+    pub fn new(duration_ms: u64) -> Self {
+        Self {
+            duration_ms,
+            last_processed_slot: last_processed_slot(),
+            processed: registry().counters_by_where(UNLOAD_REQUESTS_TOTAL, "request", &[("outcome", "processed")]),
+            dropped: registry().counters_by_where(UNLOAD_REQUESTS_TOTAL, "request", &[("outcome", "dropped")]),
+            unflushed: registry().counters_by_where(UNLOAD_REQUESTS_TOTAL, "request", &[("outcome", "unflushed")]),
+        }
+    }
+
+    /// Whether some updates never made it to the database and need a backfill
+    pub fn has_gap(&self) -> bool {
+        self.dropped.values().chain(self.unflushed.values()).any(|count| *count > 0)
+    }
+
+    pub fn log(&self) {
+        let message = format!(
+            "[unload_summary] duration_ms=[{}] last_processed_slot=[{}] processed=[{:?}] dropped=[{:?}] unflushed=[{:?}]",
+            self.duration_ms, self.last_processed_slot, self.processed, self.dropped, self.unflushed
+        );
+        match self.has_gap() {
+            true => warn!("{}", message),
+            false => info!("{}", message),
+        }
+    }
+
+    /// Write the summary on a new connection, the workers' connections are closed by now
+    pub fn write(&self, config: &GeyserPluginPostgresConfig) -> Result<(), GeyserPluginError> {
+        let mut client = SimplePostgresClient::connect_to_db(config)?;
+        if let Err(err) = client.batch_execute(&UnloadSummaryHandler::insert(&TablePrefix::new(config), self)) {
+            record_db_error("unload_summary", &err);
+            return Err(GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::DataSchemaError {
+                msg: format!("[unload_summary] error=[{}]", err),
+            })));
+        }
+        Ok(())
+    }
+}
+
+pub struct UnloadSummaryHandler {}
+
+impl UnloadSummaryHandler {
+    pub fn init(config: &GeyserPluginPostgresConfig) -> String {
+        if !config.unload.write_summary {
+            return "".to_string();
+        }
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {}unload_summary (
+                id BIGSERIAL PRIMARY KEY,
+                version VARCHAR(32) NOT NULL,
+                duration_ms BIGINT NOT NULL,
+                last_processed_slot BIGINT NOT NULL,
+                processed JSONB NOT NULL,
+                dropped JSONB NOT NULL,
+                unflushed JSONB NOT NULL,
+                gap BOOL NOT NULL,
+                created_on TIMESTAMP NOT NULL
+            );
+        ",
+            TablePrefix::new(config)
+        );
+    }
+
+    pub fn insert(prefix: &TablePrefix, summary: &UnloadSummary) -> String {
+        format!(
+            "
+                INSERT INTO {8}unload_summary (version, duration_ms, last_processed_slot, processed, dropped, unflushed, gap, created_on) \
+                VALUES ('{0}', {1}, {2}, {3}, {4}, {5}, {6}, '{7}');
+            ",
+            env!("CARGO_PKG_VERSION"),
+            summary.duration_ms,
+            summary.last_processed_slot,
+            json!(summary.processed).to_string().sql_literal(),
+            json!(summary.dropped).to_string().sql_literal(),
+            json!(summary.unflushed).to_string().sql_literal(),
+            summary.has_gap(),
+            &Utc::now().naive_utc(),
+            prefix
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unload_summary() {
+        let mut summary = UnloadSummary {
+            duration_ms: 1500,
+            last_processed_slot: 42,
+            processed: BTreeMap::from([("update_account".to_string(), 120), ("update_slot".to_string(), 3)]),
+            ..UnloadSummary::default()
+        };
+        assert!(!summary.has_gap());
+        let query = UnloadSummaryHandler::insert(&TablePrefix::default(), &summary);
+        assert!(query.contains(&format!(
+            "VALUES ('{}', 1500, 42, '{{\"update_account\":120,\"update_slot\":3}}', '{{}}', '{{}}', false,",
+            env!("CARGO_PKG_VERSION")
+        )));
+
+        summary.unflushed = BTreeMap::from([("update_account".to_string(), 7)]);
+        assert!(summary.has_gap());
+        assert!(UnloadSummaryHandler::insert(&TablePrefix::default(), &summary).contains("'{\"update_account\":7}', true,"));
+    }
+}
@@ -0,0 +1,127 @@
+use crate::config::GeyserPluginPostgresConfig;
+use crate::postgres_client::sql_value::SqlValue;
+use crate::postgres_client::table_prefix::TablePrefix;
+use chrono::Utc;
+use lazy_static::lazy_static;
+use log::*;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+lazy_static! {
+    /// Batch ids start at the load time in microseconds, so they keep increasing across restarts. Another plugin
+    /// writing to the same database may allocate the same ids, the rows are keyed by a sequence of their own
+    static ref NEXT_BATCH_ID: AtomicU64 = AtomicU64::new(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64);
+}
+
+/// The accounts written together by one flush of a worker
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WriteBatch {
+    pub id: u64,
+    /// The name of the worker thread, as it appears in the logs
+    pub worker: String,
+    /// `startup` or `live`
+    pub kind: &'static str,
+    pub pubkeys: Vec<String>,
+    pub min_slot: i64,
+    pub max_slot: i64,
+}
+
+impl WriteBatch {
+    pub fn new<'a>(kind: &'static str, accounts: impl Iterator<Item = (&'a [u8], i64)>) -> Self {
+        let (pubkeys, slots): (Vec<String>, Vec<i64>) = accounts.map(|(pubkey, slot)| (bs58::encode(pubkey).into_string(), slot)).unzip();
+        let batch = Self {
+            id: NEXT_BATCH_ID.fetch_add(1, Ordering::Relaxed),
+            worker: thread::current().name().unwrap_or_default().to_string(),
+            kind,
+            pubkeys,
+            min_slot: slots.iter().copied().min().unwrap_or_default(),
+            max_slot: slots.iter().copied().max().unwrap_or_default(),
+        };
+        debug!(
+            "[write_batch] id=[{}] worker=[{}] kind=[{}] accounts=[{}] slots=[{}..{}]",
+            batch.id,
+            batch.worker,
+            batch.kind,
+            batch.pubkeys.len(),
+            batch.min_slot,
+            batch.max_slot
+        );
+        batch
+    }
+}
+
+/// ` batch_id=[..]` to append to the messages about a flush
+pub fn batch_context(batch: &Option<WriteBatch>) -> String {
+    batch.as_ref().map_or("".to_string(), |batch| format!(" batch_id=[{}]", batch.id))
+}
+
+pub struct WriteBatchHandler {}
+
+impl WriteBatchHandler {
+    pub fn init(config: &GeyserPluginPostgresConfig) -> String {
+        if !config.write_batches {
+            return "".to_string();
+        }
+        return format!(
+            "
+            CREATE TABLE IF NOT EXISTS {prefix}write_batch (
+                id BIGSERIAL PRIMARY KEY,
+                batch_id BIGINT NOT NULL,
+                worker VARCHAR(64) NOT NULL,
+                kind VARCHAR(16) NOT NULL,
+                pubkeys VARCHAR(44)[] NOT NULL,
+                min_slot BIGINT NOT NULL,
+                max_slot BIGINT NOT NULL,
+                created_on TIMESTAMP NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS {prefix}write_batch_pubkeys ON {prefix}write_batch USING GIN (pubkeys);
+            CREATE INDEX IF NOT EXISTS {prefix}write_batch_batch_id ON {prefix}write_batch (batch_id);
+        ",
+            prefix = TablePrefix::new(config)
+        );
+    }
+
+    /// Run in the same statement batch as the rows it describes, so both are committed together
+    pub fn insert(prefix: &TablePrefix, batch: &WriteBatch) -> String {
+        format!(
+            "
+                INSERT INTO {7}write_batch (batch_id, worker, kind, pubkeys, min_slot, max_slot, created_on) \
+                VALUES ({0}, {1}, '{2}', ARRAY[{3}]::VARCHAR(44)[], {4}, {5}, '{6}');
+            ",
+            batch.id,
+            batch.worker.sql_literal(),
+            batch.kind,
+            batch.pubkeys.iter().map(|pubkey| format!("'{}'", pubkey)).collect::<Vec<String>>().join(","),
+            batch.min_slot,
+            batch.max_slot,
+            &Utc::now().naive_utc(),
+            prefix
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_batch() {
+        let accounts = vec![(vec![1u8; 32], 12), (vec![2u8; 32], 10)];
+        let batch = WriteBatch::new("live", accounts.iter().map(|(pubkey, slot)| (pubkey.as_slice(), *slot)));
+        let next = WriteBatch::new("live", std::iter::empty());
+        assert_eq!(next.id, batch.id + 1);
+        assert_eq!((batch.min_slot, batch.max_slot), (10, 12));
+        assert_eq!(batch_context(&Some(batch.clone())), format!(" batch_id=[{}]", batch.id));
+        let query = WriteBatchHandler::insert(&TablePrefix::default(), &batch);
+        assert!(query.contains(&format!(
+            "VALUES ({}, '{}', 'live', ARRAY['{}','{}']::VARCHAR(44)[], 10, 12,",
+            batch.id,
+            batch.worker,
+            bs58::encode([1u8; 32]).into_string(),
+            bs58::encode([2u8; 32]).into_string()
+        )));
+    }
+}
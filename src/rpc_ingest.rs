@@ -0,0 +1,906 @@
+//! Drives the plugin from RPC websocket subscriptions instead of the geyser interface.
+//!
+//! The `geyser-rpc-ingest` runner loads the plugin the way a validator would and feeds it
+//! the selected accounts from `programSubscribe`/`accountSubscribe`, the slots from
+//! `slotSubscribe`/`rootSubscribe`, and the transactions from either `blockSubscribe` or
+//! `logsSubscribe` followed by `getTransaction`. The handlers, the workers and the database
+//! are the same as on a validator, which makes it usable on any machine with access to an
+//! RPC node, for development and for deployments that can tolerate the gaps of a websocket.
+//!
+//! The subscriptions only deliver what the RPC node has seen at the configured commitment:
+//! account updates carry no write version or transaction signature, and updates between two
+//! notifications of the same account are lost. A dropped subscription is resubscribed after
+//! `reconnect_secs`, the notifications sent meanwhile are lost as well.
+use crate::accounts_selector::AccountsSelectorConfig;
+use crate::fixtures;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use solana_sdk::commitment_config::CommitmentLevel;
+use solana_sdk::instruction::CompiledInstruction;
+use solana_sdk::message::v0::LoadedAddresses;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::MessageHash;
+use solana_sdk::transaction::SanitizedTransaction;
+use solana_sdk::transaction::VersionedTransaction;
+use solana_sdk::transaction_context::TransactionReturnData;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::EncodedTransactionWithStatusMeta;
+use solana_transaction_status::InnerInstructions;
+use solana_transaction_status::TransactionStatusMeta;
+use solana_transaction_status::TransactionTokenBalance;
+use solana_transaction_status::UiInnerInstructions;
+use solana_transaction_status::UiInstruction;
+use solana_transaction_status::UiLoadedAddresses;
+use solana_transaction_status::UiTransactionReturnData;
+use solana_transaction_status::UiTransactionStatusMeta;
+use solana_transaction_status::UiTransactionTokenBalance;
+use std::str::FromStr;
+
+#[cfg(feature = "rpc-ingest")]
+pub(crate) use runner::load_accounts;
+#[cfg(feature = "rpc-ingest")]
+pub(crate) use runner::notify_transaction;
+#[cfg(feature = "rpc-ingest")]
+pub(crate) use runner::selected_pubkeys;
+#[cfg(feature = "rpc-ingest")]
+pub use runner::IngestCounts;
+#[cfg(feature = "rpc-ingest")]
+pub use runner::RpcIngest;
+
+/// * The `rpc_ingest` section configures the `geyser-rpc-ingest` runner, requires the "rpc-ingest" feature.
+/// "rpc_ingest" : { "rpc_url" : "https://api.devnet.solana.com", "commitment" : "confirmed", "transactions" : "logs" }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RpcIngestConfig {
+    /// The JSON RPC endpoint, used for the startup accounts and to fetch the transactions of `logs`
+    pub rpc_url: String,
+    /// The websocket endpoint, derived from `rpc_url` when not set
+    pub websocket_url: Option<String>,
+    /// `processed`, `confirmed` or `finalized`
+    pub commitment: String,
+    /// Where the transactions matching the transaction selector come from
+    pub transactions: TransactionSource,
+    /// Load the selected accounts as startup accounts before streaming the updates
+    pub load_on_start: bool,
+    /// Seconds between two attempts to resubscribe a dropped subscription
+    pub reconnect_secs: u64,
+}
+
+impl Default for RpcIngestConfig {
+    fn default() -> Self {
+        Self {
+            rpc_url: "http://127.0.0.1:8899".to_string(),
+            websocket_url: None,
+            commitment: "confirmed".to_string(),
+            transactions: TransactionSource::Logs,
+            load_on_start: true,
+            reconnect_secs: 5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionSource {
+    /// `blockSubscribe`, requires the RPC node to run with `--rpc-pubsub-enable-block-subscription`
+    Blocks,
+    /// `logsSubscribe`, then `getTransaction` for every signature
+    Logs,
+    /// Don't stream transactions
+    None,
+}
+
+impl RpcIngestConfig {
+    pub fn validate(&self, accounts_selector: Option<&AccountsSelectorConfig>) -> Result<(), String> {
+        if cfg!(not(feature = "rpc-ingest")) {
+            return Err("\"rpc_ingest\" requires the plugin to be built with the \"rpc-ingest\" feature".to_string());
+        }
+        if !self.rpc_url.starts_with("http://") && !self.rpc_url.starts_with("https://") {
+            return Err(format!("rpc_ingest.rpc_url \"{}\" must be an http or https url", self.rpc_url));
+        }
+        if !self.websocket_url().starts_with("ws://") && !self.websocket_url().starts_with("wss://") {
+            return Err(format!("rpc_ingest.websocket_url \"{}\" must be a ws or wss url", self.websocket_url()));
+        }
+        let commitment = self.commitment_level()?;
+        if self.transactions != TransactionSource::None && commitment == CommitmentLevel::Processed {
+            return Err("rpc_ingest.transactions requires the \"confirmed\" or \"finalized\" commitment".to_string());
+        }
+        if self.reconnect_secs == 0 {
+            return Err("rpc_ingest.reconnect_secs must be greater than 0".to_string());
+        }
+        if accounts_selector.and_then(|selector| selector.accounts.as_ref()).map_or(false, |accounts| accounts.contains_key("*")) {
+            return Err("rpc_ingest can't subscribe to every account, select the accounts or their owners".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn commitment_level(&self) -> Result<CommitmentLevel, String> {
+        match CommitmentLevel::from_str(&self.commitment) {
+            Ok(level @ (CommitmentLevel::Processed | CommitmentLevel::Confirmed | CommitmentLevel::Finalized)) => Ok(level),
+            _ => Err(format!("rpc_ingest.commitment \"{}\" must be one of processed, confirmed or finalized", self.commitment)),
+        }
+    }
+
+    /// The websocket endpoint, by default the one RPC nodes serve next to `rpc_url`
+    pub fn websocket_url(&self) -> String {
+        if let Some(websocket_url) = &self.websocket_url {
+            return websocket_url.clone();
+        }
+        // the websocket is served on the port following the http one
+        self.rpc_url.replacen("http", "ws", 1).replacen(":8899", ":8900", 1)
+    }
+}
+
+/// A transaction as delivered by the RPC node, converted to what the validator delivers
+#[derive(Clone, Debug, PartialEq)]
+pub struct IngestedTransaction {
+    pub signature: Signature,
+    /// The position in the block, 0 when the transaction comes from `logs`
+    pub index: usize,
+    pub slot: u64,
+    pub transaction: VersionedTransaction,
+    pub meta: TransactionStatusMeta,
+}
+
+impl IngestedTransaction {
+    /// Decode a transaction fetched with the base64 encoding
+    pub fn from_encoded(encoded: EncodedTransactionWithStatusMeta, slot: u64, index: usize) -> Result<Self, String> {
+        let transaction = encoded.transaction.decode().ok_or_else(|| "the transaction is not binary encoded".to_string())?;
+        let signature = *transaction.signatures.first().ok_or_else(|| "the transaction is not signed".to_string())?;
+        let meta = encoded.meta.ok_or_else(|| format!("transaction {} has no status meta", signature))?;
+        Ok(Self {
+            signature,
+            index,
+            slot,
+            transaction,
+            meta: transaction_status_meta(meta)?,
+        })
+    }
+
+    /// The sanitized transaction and status meta the plugin is notified with
+    pub fn into_replica_parts(self) -> Result<(SanitizedTransaction, TransactionStatusMeta), String> {
+        fixtures::into_replica_parts(self.transaction, MessageHash::Compute, None, self.meta)
+    }
+
+    /// Whether the transaction loads one of `keys`
+    pub fn mentions_any(&self, keys: &[Pubkey]) -> bool {
+        fixtures::mentions_any(&self.transaction, &self.meta.loaded_addresses, keys)
+    }
+}
+
+fn parse_pubkeys<'a>(pubkeys: impl IntoIterator<Item = &'a String>) -> Result<Vec<Pubkey>, String> {
+    pubkeys
+        .into_iter()
+        .map(|pubkey| Pubkey::from_str(pubkey).map_err(|e| format!("invalid pubkey {}: {}", pubkey, e)))
+        .collect()
+}
+
+/// The status meta of a transaction fetched with the base64 encoding, whose inner instructions are compiled
+pub fn transaction_status_meta(meta: UiTransactionStatusMeta) -> Result<TransactionStatusMeta, String> {
+    let inner_instructions: Option<Vec<UiInnerInstructions>> = meta.inner_instructions.into();
+    let inner_instructions = match inner_instructions {
+        Some(inner_instructions) => Some(
+            inner_instructions
+                .into_iter()
+                .map(|inner| {
+                    let instructions = inner
+                        .instructions
+                        .into_iter()
+                        .map(|instruction| match instruction {
+                            UiInstruction::Compiled(instruction) => Ok(CompiledInstruction {
+                                program_id_index: instruction.program_id_index,
+                                accounts: instruction.accounts,
+                                data: bs58::decode(&instruction.data).into_vec().map_err(|e| format!("invalid inner instruction data: {}", e))?,
+                            }),
+                            UiInstruction::Parsed(_) => Err("parsed inner instructions are not supported".to_string()),
+                        })
+                        .collect::<Result<Vec<CompiledInstruction>, String>>()?;
+                    Ok(InnerInstructions { index: inner.index, instructions })
+                })
+                .collect::<Result<Vec<InnerInstructions>, String>>()?,
+        ),
+        None => None,
+    };
+    let token_balances = |balances: OptionSerializer<Vec<UiTransactionTokenBalance>>| {
+        let balances: Option<Vec<UiTransactionTokenBalance>> = balances.into();
+        balances.map(|balances| {
+            balances
+                .into_iter()
+                .map(|balance| {
+                    let owner: Option<String> = balance.owner.into();
+                    let program_id: Option<String> = balance.program_id.into();
+                    TransactionTokenBalance {
+                        account_index: balance.account_index,
+                        mint: balance.mint,
+                        ui_token_amount: balance.ui_token_amount,
+                        owner: owner.unwrap_or_default(),
+                        program_id: program_id.unwrap_or_default(),
+                    }
+                })
+                .collect()
+        })
+    };
+    let loaded_addresses: Option<UiLoadedAddresses> = meta.loaded_addresses.into();
+    let loaded_addresses = match loaded_addresses {
+        Some(loaded_addresses) => LoadedAddresses {
+            writable: parse_pubkeys(&loaded_addresses.writable)?,
+            readonly: parse_pubkeys(&loaded_addresses.readonly)?,
+        },
+        None => LoadedAddresses::default(),
+    };
+    let return_data: Option<UiTransactionReturnData> = meta.return_data.into();
+    let return_data = match return_data {
+        Some(return_data) => Some(TransactionReturnData {
+            program_id: Pubkey::from_str(&return_data.program_id).map_err(|e| format!("invalid return data program id: {}", e))?,
+            data: base64::decode(&return_data.data.0).map_err(|e| format!("invalid return data: {}", e))?,
+        }),
+        None => None,
+    };
+    Ok(TransactionStatusMeta {
+        status: meta.status,
+        fee: meta.fee,
+        pre_balances: meta.pre_balances,
+        post_balances: meta.post_balances,
+        inner_instructions,
+        log_messages: meta.log_messages.into(),
+        pre_token_balances: token_balances(meta.pre_token_balances),
+        post_token_balances: token_balances(meta.post_token_balances),
+        rewards: meta.rewards.into(),
+        loaded_addresses,
+        return_data,
+        compute_units_consumed: meta.compute_units_consumed.into(),
+    })
+}
+
+#[cfg(feature = "rpc-ingest")]
+mod runner {
+    use super::parse_pubkeys;
+    use super::IngestedTransaction;
+    use super::RpcIngestConfig;
+    use super::TransactionSource;
+    use crate::config::GeyserPluginPostgresConfig;
+    use crate::fixtures::RecordedAccount;
+    use crate::geyser_plugin_postgres::GeyserPluginPostgres;
+    use crossbeam_channel::unbounded;
+    use crossbeam_channel::Receiver;
+    use crossbeam_channel::RecvTimeoutError;
+    use crossbeam_channel::Sender;
+    use log::*;
+    use serde::de::DeserializeOwned;
+    use solana_account_decoder::UiAccount;
+    use solana_account_decoder::UiAccountEncoding;
+    use solana_client::pubsub_client::PubsubClient;
+    use solana_client::pubsub_client::PubsubClientError;
+    use solana_client::pubsub_client::PubsubClientSubscription;
+    use solana_client::rpc_client::RpcClient;
+    use solana_client::rpc_config::RpcAccountInfoConfig;
+    use solana_client::rpc_config::RpcBlockSubscribeConfig;
+    use solana_client::rpc_config::RpcBlockSubscribeFilter;
+    use solana_client::rpc_config::RpcProgramAccountsConfig;
+    use solana_client::rpc_config::RpcTransactionConfig;
+    use solana_client::rpc_config::RpcTransactionLogsConfig;
+    use solana_client::rpc_config::RpcTransactionLogsFilter;
+    use solana_client::rpc_response::Response;
+    use solana_client::rpc_response::RpcBlockUpdate;
+    use solana_client::rpc_response::RpcKeyedAccount;
+    use solana_client::rpc_response::RpcLogsResponse;
+    use solana_client::rpc_response::SlotInfo;
+    use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
+    use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
+    use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaBlockInfo;
+    use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaBlockInfoVersions;
+    use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoV2;
+    use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoVersions;
+    use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+    use solana_sdk::account::Account;
+    use solana_sdk::commitment_config::CommitmentConfig;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Signature;
+    use solana_transaction_status::Reward;
+    use solana_transaction_status::TransactionDetails;
+    use solana_transaction_status::UiTransactionEncoding;
+    use std::collections::BTreeSet;
+    use std::str::FromStr;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::AtomicU64;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::thread::sleep;
+    use std::thread::Builder;
+    use std::thread::JoinHandle;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    /// How often the subscriptions and the runner check whether they are stopping
+    const EXIT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+    /// getMultipleAccounts accepts up to 100 accounts
+    const MULTIPLE_ACCOUNTS_CHUNK: usize = 100;
+    /// The slots of the last blocks notified, a block sent again by a resubscription is skipped
+    const NOTIFIED_BLOCKS: usize = 512;
+
+    enum IngestEvent {
+        Account(RecordedAccount),
+        Slot {
+            slot: u64,
+            parent: Option<u64>,
+            status: SlotStatus,
+        },
+        Transaction(IngestedTransaction),
+        Block {
+            slot: u64,
+            blockhash: String,
+            rewards: Vec<Reward>,
+            block_time: Option<i64>,
+            block_height: Option<u64>,
+        },
+    }
+
+    /// What the runner notified the plugin of
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct IngestCounts {
+        pub startup_accounts: u64,
+        pub accounts: u64,
+        pub slots: u64,
+        pub transactions: u64,
+        pub blocks: u64,
+    }
+
+    /// Subscribes to the selected accounts and transactions and notifies the plugin
+    pub struct RpcIngest {
+        config: RpcIngestConfig,
+        commitment: CommitmentConfig,
+        owners: Vec<Pubkey>,
+        accounts: Vec<Pubkey>,
+        /// None to stream every transaction
+        mentions: Option<Vec<String>>,
+        exit: Arc<AtomicBool>,
+        write_version: Arc<AtomicU64>,
+    }
+
+    fn decode_account(pubkey: &Pubkey, account: &UiAccount, slot: u64, write_version: u64) -> Option<RecordedAccount> {
+        let account = match account.decode::<Account>() {
+            Some(account) => account,
+            None => {
+                warn!("[rpc_ingest] failed to decode account {}", pubkey);
+                return None;
+            }
+        };
+        Some(recorded_account(pubkey, account, slot, write_version, false))
+    }
+
+    fn recorded_account(pubkey: &Pubkey, account: Account, slot: u64, write_version: u64, is_startup: bool) -> RecordedAccount {
+        RecordedAccount {
+            pubkey: pubkey.to_bytes().to_vec(),
+            lamports: account.lamports,
+            owner: account.owner.to_bytes().to_vec(),
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data: account.data,
+            write_version,
+            txn_signature: None,
+            slot,
+            is_startup,
+        }
+    }
+
+    /// Forward the notifications of a subscription until the runner stops, resubscribing when it drops
+    fn spawn_subscription<T, S, C>(name: String, exit: Arc<AtomicBool>, reconnect: Duration, events: Sender<IngestEvent>, subscribe: S, convert: C) -> JoinHandle<()>
+    where
+        T: DeserializeOwned + Send + 'static,
+        S: Fn() -> Result<(PubsubClientSubscription<T>, Receiver<T>), PubsubClientError> + Send + 'static,
+        C: Fn(T) -> Vec<IngestEvent> + Send + 'static,
+    {
+        Builder::new()
+            .name(format!("rpc_ingest_{}", name.split(' ').next().unwrap_or_default()))
+            .spawn(move || {
+                while !exit.load(Ordering::Relaxed) {
+                    match subscribe() {
+                        Ok((mut subscription, receiver)) => {
+                            info!("[rpc_ingest] subscribed to {}", name);
+                            while !exit.load(Ordering::Relaxed) {
+                                match receiver.recv_timeout(EXIT_CHECK_INTERVAL) {
+                                    Ok(notification) => {
+                                        for event in convert(notification) {
+                                            if events.send(event).is_err() {
+                                                return;
+                                            }
+                                        }
+                                    }
+                                    Err(RecvTimeoutError::Timeout) => {}
+                                    Err(RecvTimeoutError::Disconnected) => {
+                                        warn!("[rpc_ingest] {} disconnected", name);
+                                        break;
+                                    }
+                                }
+                            }
+                            let _ = subscription.send_unsubscribe();
+                            let _ = subscription.shutdown();
+                        }
+                        Err(err) => warn!("[rpc_ingest] failed to subscribe to {}: {}", name, err),
+                    }
+                    let retry_at = Instant::now() + reconnect;
+                    while !exit.load(Ordering::Relaxed) && Instant::now() < retry_at {
+                        sleep(EXIT_CHECK_INTERVAL);
+                    }
+                }
+            })
+            .unwrap()
+    }
+
+    /// The owners and the accounts of the accounts selector
+    pub fn selected_pubkeys(config: &GeyserPluginPostgresConfig) -> Result<(Vec<Pubkey>, Vec<Pubkey>), String> {
+        match &config.accounts_selector {
+            Some(selector) => Ok((
+                parse_pubkeys(selector.owners.iter().flat_map(|o| o.keys()))?,
+                parse_pubkeys(selector.accounts.iter().flat_map(|a| a.keys()))?,
+            )),
+            None => Ok((Vec::new(), Vec::new())),
+        }
+    }
+
+    /// Notify the plugin of the current state of the given accounts and of the accounts of the given owners as
+    /// startup accounts
+    pub fn load_accounts(
+        plugin: &mut GeyserPluginPostgres,
+        rpc_client: &RpcClient,
+        account_config: RpcAccountInfoConfig,
+        owners: &[Pubkey],
+        pubkeys: &[Pubkey],
+        write_version: &AtomicU64,
+    ) -> Result<u64, String> {
+        let slot = rpc_client.get_slot().map_err(|e| format!("getSlot failed: {}", e))?;
+        let mut accounts = Vec::new();
+        for owner in owners {
+            let config = RpcProgramAccountsConfig {
+                account_config: account_config.clone(),
+                ..RpcProgramAccountsConfig::default()
+            };
+            let program_accounts = rpc_client
+                .get_program_accounts_with_config(owner, config)
+                .map_err(|e| format!("getProgramAccounts {} failed: {}", owner, e))?;
+            info!("[rpc_ingest] loaded {} accounts of {}", program_accounts.len(), owner);
+            accounts.extend(program_accounts);
+        }
+        for chunk in pubkeys.chunks(MULTIPLE_ACCOUNTS_CHUNK) {
+            let chunk_accounts = rpc_client.get_multiple_accounts(chunk).map_err(|e| format!("getMultipleAccounts failed: {}", e))?;
+            accounts.extend(chunk.iter().zip(chunk_accounts).filter_map(|(pubkey, account)| Some((*pubkey, account?))));
+        }
+        let count = accounts.len() as u64;
+        for (pubkey, account) in accounts {
+            let write_version = write_version.fetch_add(1, Ordering::Relaxed) + 1;
+            let account = recorded_account(&pubkey, account, slot, write_version, true);
+            plugin
+                .update_account(ReplicaAccountInfoVersions::V0_0_2(&account.as_replica()), slot, true)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(count)
+    }
+
+    /// Notify the plugin of a transaction, false when it was skipped as it can't be sanitized
+    pub fn notify_transaction(plugin: &mut GeyserPluginPostgres, transaction: IngestedTransaction) -> Result<bool, String> {
+        let (signature, index, slot) = (transaction.signature, transaction.index, transaction.slot);
+        let (sanitized, meta) = match transaction.into_replica_parts() {
+            Ok(parts) => parts,
+            Err(err) => {
+                warn!("[rpc_ingest] skipping transaction {}: {}", signature, err);
+                return Ok(false);
+            }
+        };
+        plugin
+            .notify_transaction(
+                ReplicaTransactionInfoVersions::V0_0_2(&ReplicaTransactionInfoV2 {
+                    signature: &signature,
+                    is_vote: sanitized.is_simple_vote_transaction(),
+                    transaction: &sanitized,
+                    transaction_status_meta: &meta,
+                    index,
+                }),
+                slot,
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    impl RpcIngest {
+        pub fn new(config: &GeyserPluginPostgresConfig) -> Result<Self, String> {
+            let rpc_ingest = config.rpc_ingest.clone().ok_or_else(|| "the config has no \"rpc_ingest\" section".to_string())?;
+            rpc_ingest.validate(config.accounts_selector.as_ref())?;
+            let commitment = CommitmentConfig {
+                commitment: rpc_ingest.commitment_level()?,
+            };
+            let (owners, accounts) = selected_pubkeys(config)?;
+            let mentions = match &config.transaction_selector {
+                Some(selector) => {
+                    let keys = selector.mentions.keys();
+                    if keys.iter().any(|key| *key == "*" || *key == "all_votes") {
+                        None
+                    } else {
+                        Some(keys.into_iter().cloned().collect())
+                    }
+                }
+                None => Some(Vec::new()),
+            };
+            Ok(Self {
+                config: rpc_ingest,
+                commitment,
+                owners,
+                accounts,
+                mentions,
+                exit: Arc::new(AtomicBool::new(false)),
+                write_version: Arc::new(AtomicU64::new(0)),
+            })
+        }
+
+        /// Set it to stop the runner, e.g. from a signal handler
+        pub fn exit(&self) -> Arc<AtomicBool> {
+            self.exit.clone()
+        }
+
+        fn account_config(&self) -> RpcAccountInfoConfig {
+            RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(self.commitment),
+                ..RpcAccountInfoConfig::default()
+            }
+        }
+
+        fn subscribe(&self, events: &Sender<IngestEvent>) -> Vec<JoinHandle<()>> {
+            let websocket_url = self.config.websocket_url();
+            let reconnect = Duration::from_secs(self.config.reconnect_secs);
+            let mut threads = Vec::new();
+            for owner in self.owners.clone() {
+                let (url, config, write_version) = (websocket_url.clone(), self.account_config(), self.write_version.clone());
+                threads.push(spawn_subscription(
+                    format!("program {}", owner),
+                    self.exit.clone(),
+                    reconnect,
+                    events.clone(),
+                    move || {
+                        let config = RpcProgramAccountsConfig {
+                            account_config: config.clone(),
+                            with_context: Some(true),
+                            ..RpcProgramAccountsConfig::default()
+                        };
+                        PubsubClient::program_subscribe(&url, &owner, Some(config))
+                    },
+                    move |notification: Response<RpcKeyedAccount>| {
+                        let pubkey = match Pubkey::from_str(&notification.value.pubkey) {
+                            Ok(pubkey) => pubkey,
+                            Err(_) => return Vec::new(),
+                        };
+                        let write_version = write_version.fetch_add(1, Ordering::Relaxed) + 1;
+                        decode_account(&pubkey, &notification.value.account, notification.context.slot, write_version)
+                            .map(IngestEvent::Account)
+                            .into_iter()
+                            .collect()
+                    },
+                ));
+            }
+            for pubkey in self.accounts.clone() {
+                let (url, config, write_version) = (websocket_url.clone(), self.account_config(), self.write_version.clone());
+                threads.push(spawn_subscription(
+                    format!("account {}", pubkey),
+                    self.exit.clone(),
+                    reconnect,
+                    events.clone(),
+                    move || PubsubClient::account_subscribe(&url, &pubkey, Some(config.clone())),
+                    move |notification: Response<UiAccount>| {
+                        let write_version = write_version.fetch_add(1, Ordering::Relaxed) + 1;
+                        decode_account(&pubkey, &notification.value, notification.context.slot, write_version)
+                            .map(IngestEvent::Account)
+                            .into_iter()
+                            .collect()
+                    },
+                ));
+            }
+
+            let url = websocket_url.clone();
+            threads.push(spawn_subscription(
+                "slots".to_string(),
+                self.exit.clone(),
+                reconnect,
+                events.clone(),
+                move || PubsubClient::slot_subscribe(&url),
+                |slot_info: SlotInfo| {
+                    vec![IngestEvent::Slot {
+                        slot: slot_info.slot,
+                        parent: Some(slot_info.parent),
+                        status: SlotStatus::Processed,
+                    }]
+                },
+            ));
+            let url = websocket_url.clone();
+            threads.push(spawn_subscription(
+                "roots".to_string(),
+                self.exit.clone(),
+                reconnect,
+                events.clone(),
+                move || PubsubClient::root_subscribe(&url),
+                |slot: u64| {
+                    vec![IngestEvent::Slot {
+                        slot,
+                        parent: None,
+                        status: SlotStatus::Rooted,
+                    }]
+                },
+            ));
+
+            match self.config.transactions {
+                TransactionSource::Blocks => threads.extend(self.subscribe_blocks(&websocket_url, reconnect, events)),
+                TransactionSource::Logs => threads.extend(self.subscribe_logs(&websocket_url, reconnect, events)),
+                TransactionSource::None => {}
+            }
+            threads
+        }
+
+        /// The filters of the logs subscriptions, one per mentioned address as the RPC nodes only accept one
+        fn logs_filters(&self) -> Vec<(String, RpcTransactionLogsFilter)> {
+            match &self.mentions {
+                None => vec![("transactions".to_string(), RpcTransactionLogsFilter::AllWithVotes)],
+                Some(keys) => keys
+                    .iter()
+                    .map(|key| (format!("transactions mentioning {}", key), RpcTransactionLogsFilter::Mentions(vec![key.clone()])))
+                    .collect(),
+            }
+        }
+
+        /// A single subscription to every block, so each block is notified once and its transactions keep their
+        /// position in it. The transactions not mentioning a selected address are skipped here rather than by the
+        /// RPC node, whose filter would renumber them
+        fn subscribe_blocks(&self, websocket_url: &str, reconnect: Duration, events: &Sender<IngestEvent>) -> Vec<JoinHandle<()>> {
+            let mentions = match &self.mentions {
+                Some(keys) if keys.is_empty() => return Vec::new(),
+                Some(keys) => Some(keys.iter().filter_map(|key| Pubkey::from_str(key).ok()).collect::<Vec<Pubkey>>()),
+                None => None,
+            };
+            let config = RpcBlockSubscribeConfig {
+                commitment: Some(self.commitment),
+                encoding: Some(UiTransactionEncoding::Base64),
+                transaction_details: Some(TransactionDetails::Full),
+                show_rewards: Some(true),
+                max_supported_transaction_version: Some(0),
+            };
+            let url = websocket_url.to_string();
+            let notified = Mutex::new(BTreeSet::new());
+            vec![spawn_subscription(
+                "blocks".to_string(),
+                self.exit.clone(),
+                reconnect,
+                events.clone(),
+                move || PubsubClient::block_subscribe(&url, RpcBlockSubscribeFilter::All, Some(config.clone())),
+                move |notification: Response<RpcBlockUpdate>| {
+                    let slot = notification.value.slot;
+                    let block = match notification.value.block {
+                        Some(block) => block,
+                        None => return Vec::new(),
+                    };
+                    let mut notified = notified.lock().unwrap();
+                    if !notified.insert(slot) {
+                        return Vec::new();
+                    }
+                    while notified.len() > NOTIFIED_BLOCKS {
+                        let oldest = *notified.iter().next().unwrap();
+                        notified.remove(&oldest);
+                    }
+                    let mut events = Vec::new();
+                    for (index, transaction) in block.transactions.into_iter().flatten().enumerate() {
+                        match IngestedTransaction::from_encoded(transaction, slot, index) {
+                            Ok(transaction) if mentions.as_ref().map_or(true, |keys| transaction.mentions_any(keys)) => events.push(IngestEvent::Transaction(transaction)),
+                            Ok(_) => {}
+                            Err(err) => warn!("[rpc_ingest] skipping transaction {} of slot {}: {}", index, slot, err),
+                        }
+                    }
+                    events.push(IngestEvent::Block {
+                        slot,
+                        blockhash: block.blockhash,
+                        rewards: block.rewards.unwrap_or_default(),
+                        block_time: block.block_time,
+                        block_height: block.block_height,
+                    });
+                    events
+                },
+            )]
+        }
+
+        fn subscribe_logs(&self, websocket_url: &str, reconnect: Duration, events: &Sender<IngestEvent>) -> Vec<JoinHandle<()>> {
+            self.logs_filters()
+                .into_iter()
+                .map(|(name, filter)| {
+                    let (url, commitment) = (websocket_url.to_string(), self.commitment);
+                    let rpc_client = RpcClient::new_with_commitment(self.config.rpc_url.clone(), commitment);
+                    spawn_subscription(
+                        name,
+                        self.exit.clone(),
+                        reconnect,
+                        events.clone(),
+                        move || PubsubClient::logs_subscribe(&url, filter.clone(), RpcTransactionLogsConfig { commitment: Some(commitment) }),
+                        move |notification: Response<RpcLogsResponse>| {
+                            let signature = match Signature::from_str(&notification.value.signature) {
+                                Ok(signature) => signature,
+                                Err(_) => return Vec::new(),
+                            };
+                            let config = RpcTransactionConfig {
+                                encoding: Some(UiTransactionEncoding::Base64),
+                                commitment: Some(commitment),
+                                max_supported_transaction_version: Some(0),
+                            };
+                            // the position of the transaction in its block is not known, (slot, signature) still identifies it
+                            match rpc_client
+                                .get_transaction_with_config(&signature, config)
+                                .map_err(|e| e.to_string())
+                                .and_then(|confirmed| IngestedTransaction::from_encoded(confirmed.transaction, confirmed.slot, 0))
+                            {
+                                Ok(transaction) => vec![IngestEvent::Transaction(transaction)],
+                                Err(err) => {
+                                    warn!("[rpc_ingest] failed to fetch transaction {}: {}", signature, err);
+                                    Vec::new()
+                                }
+                            }
+                        },
+                    )
+                })
+                .collect()
+        }
+
+        /// Notify the plugin of the selected accounts as startup accounts
+        fn load_startup_accounts(&self, plugin: &mut GeyserPluginPostgres) -> Result<u64, String> {
+            let rpc_client = RpcClient::new_with_commitment(self.config.rpc_url.clone(), self.commitment);
+            load_accounts(plugin, &rpc_client, self.account_config(), &self.owners, &self.accounts, &self.write_version)
+        }
+
+        fn notify(&self, plugin: &mut GeyserPluginPostgres, event: IngestEvent, counts: &mut IngestCounts) -> Result<(), String> {
+            let result = match event {
+                IngestEvent::Account(account) => {
+                    counts.accounts += 1;
+                    plugin.update_account(ReplicaAccountInfoVersions::V0_0_2(&account.as_replica()), account.slot, false)
+                }
+                IngestEvent::Slot { slot, parent, status } => {
+                    counts.slots += 1;
+                    plugin.update_slot_status(slot, parent, status)
+                }
+                IngestEvent::Transaction(transaction) => {
+                    if notify_transaction(plugin, transaction)? {
+                        counts.transactions += 1;
+                    }
+                    return Ok(());
+                }
+                IngestEvent::Block {
+                    slot,
+                    blockhash,
+                    rewards,
+                    block_time,
+                    block_height,
+                } => {
+                    counts.blocks += 1;
+                    plugin.notify_block_metadata(ReplicaBlockInfoVersions::V0_0_1(&ReplicaBlockInfo {
+                        slot,
+                        blockhash: &blockhash,
+                        rewards: &rewards,
+                        block_time,
+                        block_height,
+                    }))
+                }
+            };
+            result.map_err(|e| e.to_string())
+        }
+
+        fn ingest(&self, plugin: &mut GeyserPluginPostgres, receiver: &Receiver<IngestEvent>, counts: &mut IngestCounts) -> Result<(), String> {
+            if self.config.load_on_start {
+                counts.startup_accounts = self.load_startup_accounts(plugin)?;
+            }
+            plugin.notify_end_of_startup().map_err(|e| e.to_string())?;
+            while !self.exit.load(Ordering::Relaxed) {
+                match receiver.recv_timeout(EXIT_CHECK_INTERVAL) {
+                    Ok(event) => self.notify(plugin, event, counts)?,
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return Err("every subscription stopped".to_string()),
+                }
+            }
+            Ok(())
+        }
+
+        /// Notify the loaded plugin until `exit` is set, then wait for the workers to receive the queued requests
+        pub fn run(&self, plugin: &mut GeyserPluginPostgres) -> Result<IngestCounts, String> {
+            let (sender, receiver) = unbounded();
+            // subscribe first so the updates made while the startup accounts load are kept
+            let threads = self.subscribe(&sender);
+            drop(sender);
+            let mut counts = IngestCounts::default();
+            let result = self.ingest(plugin, &receiver, &mut counts);
+            self.exit.store(true, Ordering::Relaxed);
+            for thread in threads {
+                let _ = thread.join();
+            }
+            plugin.wait_for_empty_queue();
+            result.map(|_| counts)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_account_decoder::parse_token::UiTokenAmount;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::message::Message;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+    use solana_sdk::system_instruction;
+    use solana_sdk::transaction::Transaction;
+    use solana_transaction_status::EncodedTransaction;
+    use solana_transaction_status::TransactionBinaryEncoding;
+
+    #[test]
+    fn test_rpc_ingest_config() {
+        let config = RpcIngestConfig {
+            rpc_url: "https://api.devnet.solana.com:8899".to_string(),
+            ..RpcIngestConfig::default()
+        };
+        assert_eq!(config.websocket_url(), "wss://api.devnet.solana.com:8900");
+        assert_eq!(config.commitment_level(), Ok(CommitmentLevel::Confirmed));
+        assert_eq!(config.validate(None).is_ok(), cfg!(feature = "rpc-ingest"));
+        let processed = RpcIngestConfig {
+            commitment: "processed".to_string(),
+            ..config.clone()
+        };
+        assert!(processed.validate(None).is_err());
+        assert!(RpcIngestConfig {
+            commitment: "max".to_string(),
+            ..config
+        }
+        .commitment_level()
+        .is_err());
+    }
+
+    #[test]
+    fn test_ingested_transaction() {
+        let payer = Keypair::new();
+        let instruction = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 42);
+        let transaction = Transaction::new(&[&payer], Message::new(&[instruction], Some(&payer.pubkey())), Hash::new_unique());
+        let meta = TransactionStatusMeta {
+            fee: 5000,
+            pre_balances: vec![10_000, 0, 1],
+            post_balances: vec![4_958, 42, 1],
+            inner_instructions: Some(vec![InnerInstructions {
+                index: 0,
+                instructions: vec![CompiledInstruction::new_from_raw_parts(2, vec![1, 2, 3], vec![0, 1])],
+            }]),
+            log_messages: Some(vec!["Program 11111111111111111111111111111111 success".to_string()]),
+            pre_token_balances: Some(vec![TransactionTokenBalance {
+                account_index: 1,
+                mint: Pubkey::new_unique().to_string(),
+                ui_token_amount: UiTokenAmount {
+                    ui_amount: Some(1.0),
+                    decimals: 0,
+                    amount: "1".to_string(),
+                    ui_amount_string: "1".to_string(),
+                },
+                owner: Pubkey::new_unique().to_string(),
+                program_id: Pubkey::new_unique().to_string(),
+            }]),
+            return_data: Some(TransactionReturnData {
+                program_id: Pubkey::new_unique(),
+                data: vec![7, 8],
+            }),
+            compute_units_consumed: Some(150),
+            ..TransactionStatusMeta::default()
+        };
+        let encoded = EncodedTransactionWithStatusMeta {
+            transaction: EncodedTransaction::Binary(base64::encode(bincode::serialize(&transaction).unwrap()), TransactionBinaryEncoding::Base64),
+            meta: Some(UiTransactionStatusMeta::from(meta.clone())),
+            version: None,
+        };
+
+        let ingested = IngestedTransaction::from_encoded(encoded, 7, 3).unwrap();
+        assert_eq!(ingested.signature, transaction.signatures[0]);
+        assert_eq!((ingested.slot, ingested.index), (7, 3));
+        assert_eq!(ingested.meta, meta);
+        assert!(ingested.mentions_any(&[payer.pubkey()]));
+        assert!(!ingested.mentions_any(&[Pubkey::new_unique()]));
+        let (sanitized, _) = ingested.into_replica_parts().unwrap();
+        assert_eq!(sanitized.signature(), &transaction.signatures[0]);
+        assert!(!sanitized.is_simple_vote_transaction());
+    }
+}
@@ -0,0 +1,190 @@
+//! Reloads the `accounts_selector` and `transaction_selector` of a running plugin.
+//!
+//! With `selector_reload_interval_secs`, the plugin checks the modification time of its config
+//! file that often from the slot notifications. When the file changed, its selectors are validated
+//! against the loaded config and swapped in the plugin, then published to the workers, which pick
+//! them up before their next account or transaction. The other sections of the file are only read
+//! when the plugin is loaded, so a reloaded `accounts_selector` may only route to the built-in
+//! handlers that were enabled then, the tables of the others were never initialized.
+use crate::accounts_selector::AccountsSelectorConfig;
+use crate::config::GeyserPluginPostgresConfig;
+use crate::metrics::registry;
+use crate::metrics::SELECTOR_RELOADS_TOTAL;
+use crate::postgres_client::accounts::account_handler::all_account_handlers;
+use crate::postgres_client::accounts::account_handler::AccountHandlerId;
+use crate::transaction_selector::TransactionSelectorConfig;
+use lazy_static::lazy_static;
+use log::*;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use std::collections::HashSet;
+use std::fs;
+use std::str::FromStr;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+/// The selectors published by the last reload
+type Selectors = (Option<AccountsSelectorConfig>, Option<TransactionSelectorConfig>);
+
+lazy_static! {
+    static ref SELECTORS: RwLock<(u64, Selectors)> = RwLock::new((0, (None, None)));
+}
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Publish reloaded selectors to the workers
+pub fn publish(accounts_selector: Option<AccountsSelectorConfig>, transaction_selector: Option<TransactionSelectorConfig>) {
+    let mut selectors = SELECTORS.write().unwrap();
+    let generation = GENERATION.load(Ordering::Acquire) + 1;
+    *selectors = (generation, (accounts_selector, transaction_selector));
+    GENERATION.store(generation, Ordering::Release);
+}
+
+/// Bumped by every reload, a worker compares it to the generation of its selectors before reading them
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::Acquire)
+}
+
+/// The selectors of the last reload and their generation
+pub fn selectors() -> (u64, Selectors) {
+    SELECTORS.read().unwrap().clone()
+}
+
+/// Watches the config file of the plugin for new selectors
+pub struct SelectorReload {
+    config_file: String,
+    interval: Duration,
+    last_check: Instant,
+    modified: Option<SystemTime>,
+    /// The built-in account handlers enabled when the plugin was loaded
+    account_handler_ids: HashSet<AccountHandlerId>,
+}
+
+impl SelectorReload {
+    pub fn new(config_file: &str, config: &GeyserPluginPostgresConfig) -> Option<Self> {
+        let interval_secs = config.selector_reload_interval_secs?;
+        Some(Self {
+            config_file: config_file.to_string(),
+            interval: Duration::from_secs(interval_secs),
+            last_check: Instant::now(),
+            modified: fs::metadata(config_file).and_then(|metadata| metadata.modified()).ok(),
+            account_handler_ids: all_account_handlers(config, Arc::default())
+                .into_iter()
+                .filter(|(_, handler)| handler.enabled(config))
+                .map(|(handler_id, _)| handler_id)
+                .collect(),
+        })
+    }
+
+    /// The loaded config with the selectors of the config file, when the file changed since the last check and
+    /// its selectors differ and are valid. A config that fails to read or validate is logged and skipped until
+    /// the file changes again.
+    pub fn poll(&mut self, config: &GeyserPluginPostgresConfig, account_handler_ids: &[String], transaction_handler_ids: &[String]) -> Option<GeyserPluginPostgresConfig> {
+        if self.last_check.elapsed() < self.interval {
+            return None;
+        }
+        self.last_check = Instant::now();
+        let modified = fs::metadata(&self.config_file).and_then(|metadata| metadata.modified()).ok();
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        let reloaded = GeyserPluginPostgresConfig::read_from(&self.config_file).and_then(|file_config| {
+            let reloaded = GeyserPluginPostgresConfig {
+                accounts_selector: file_config.accounts_selector,
+                transaction_selector: file_config.transaction_selector,
+                ..config.clone()
+            };
+            reloaded.validate_with_custom_handlers(account_handler_ids, transaction_handler_ids)?;
+            let handler_ids = reloaded.accounts_selector.iter().flat_map(|accounts_selector| accounts_selector.handler_ids());
+            for handler_id in handler_ids {
+                if matches!(AccountHandlerId::from_str(handler_id), Ok(id) if !self.account_handler_ids.contains(&id)) {
+                    return Err(GeyserPluginError::ConfigFileReadError {
+                        msg: format!("accounts_selector handler_id \"{}\" was not enabled when the plugin was loaded", handler_id),
+                    });
+                }
+            }
+            Ok(reloaded)
+        });
+        match reloaded {
+            Ok(reloaded) if reloaded.accounts_selector == config.accounts_selector && reloaded.transaction_selector == config.transaction_selector => None,
+            Ok(reloaded) => {
+                info!("[selector_reload] reloaded the selectors of config_file=[{}]", self.config_file);
+                registry().inc_counter(SELECTOR_RELOADS_TOTAL, &[("result", "reloaded")], 1);
+                Some(reloaded)
+            }
+            Err(err) => {
+                error!("[selector_reload] keeping the selectors, failed to reload config_file=[{}] error=[{}]", self.config_file, err);
+                registry().inc_counter(SELECTOR_RELOADS_TOTAL, &[("result", "invalid")], 1);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selector_reload() {
+        let path = std::env::temp_dir().join(format!("selector_reload_{}.json", std::process::id()));
+        let config_file = path.to_str().unwrap();
+        let write = |owner: &str| {
+            fs::write(
+                &path,
+                format!(
+                    r#"{{ "connection_str": "host=localhost", "accounts_selector": {{ "owners": {{ "{}": [{{ "handler_id": "unknown_account" }}] }} }} }}"#,
+                    owner
+                ),
+            )
+            .unwrap()
+        };
+        write("11111111111111111111111111111111");
+        let config = GeyserPluginPostgresConfig {
+            selector_reload_interval_secs: Some(1),
+            ..GeyserPluginPostgresConfig::read_from(config_file).unwrap()
+        };
+        let mut selector_reload = SelectorReload::new(config_file, &config).unwrap();
+        selector_reload.interval = Duration::ZERO;
+        assert!(selector_reload.poll(&config, &[], &[]).is_none());
+
+        selector_reload.modified = None;
+        write("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+        let reloaded = selector_reload.poll(&config, &[], &[]).unwrap();
+        assert_ne!(reloaded.accounts_selector, config.accounts_selector);
+        assert_eq!(reloaded.selector_reload_interval_secs, Some(1));
+
+        // an invalid selector keeps the loaded ones
+        selector_reload.modified = None;
+        write("not a pubkey");
+        assert!(selector_reload.poll(&reloaded, &[], &[]).is_none());
+
+        // the tables of a handler disabled at load were never initialized
+        selector_reload.modified = None;
+        fs::write(
+            &path,
+            r#"{ "connection_str": "host=localhost", "accounts_selector": { "owners": { "11111111111111111111111111111111": [{ "handler_id": "layout" }] } } }"#,
+        )
+        .unwrap();
+        assert!(selector_reload.poll(&reloaded, &[], &[]).is_none());
+
+        // nor is a handler the plugin doesn't know
+        selector_reload.modified = None;
+        fs::write(
+            &path,
+            r#"{ "connection_str": "host=localhost", "accounts_selector": { "owners": { "11111111111111111111111111111111": [{ "handler_id": "my_pool" }] } } }"#,
+        )
+        .unwrap();
+        assert!(selector_reload.poll(&reloaded, &[], &[]).is_none());
+        fs::remove_file(&path).unwrap();
+
+        let published = generation() + 1;
+        publish(reloaded.accounts_selector.clone(), None);
+        assert_eq!(generation(), published);
+        assert_eq!(selectors(), (published, (reloaded.accounts_selector, None)));
+    }
+}
@@ -0,0 +1,50 @@
+use crate::postgres_client::DbSelectorStat;
+use std::collections::HashMap;
+
+/// Selector name recorded for skipped accounts
+pub const ACCOUNTS_SELECTOR: &str = "accounts_selector";
+/// Selector name recorded for skipped transactions
+pub const TRANSACTION_SELECTOR: &str = "transaction_selector";
+
+/// Counts of selected items per selector entry and skipped items per selector
+/// since the last report
+#[derive(Debug, Default)]
+pub struct SelectorStats {
+    selected: HashMap<&'static str, HashMap<Vec<u8>, u64>>,
+    skipped: HashMap<&'static str, u64>,
+}
+
+impl SelectorStats {
+    pub fn record_selected(&mut self, selector: &'static str, entry: &[u8]) {
+        let entries = self.selected.entry(selector).or_default();
+        match entries.get_mut(entry) {
+            Some(count) => *count += 1,
+            None => {
+                entries.insert(entry.to_vec(), 1);
+            }
+        }
+    }
+
+    pub fn record_skipped(&mut self, selector: &'static str) {
+        *self.skipped.entry(selector).or_default() += 1;
+    }
+
+    /// Take the counts recorded since the last call
+    pub fn drain(&mut self) -> Vec<DbSelectorStat> {
+        let selected = self.selected.drain().flat_map(|(selector, entries)| {
+            entries.into_iter().map(move |(entry, count)| DbSelectorStat {
+                selector: selector.to_string(),
+                entry: if entry.is_empty() { "*".to_string() } else { bs58::encode(entry).into_string() },
+                selected: count as i64,
+                skipped: 0,
+            })
+        });
+        let skipped = self.skipped.drain().map(|(selector, count)| DbSelectorStat {
+            selector: selector.to_string(),
+            entry: "*".to_string(),
+            selected: 0,
+            skipped: count as i64,
+        });
+        selected.chain(skipped).collect()
+    }
+}
@@ -0,0 +1,193 @@
+use super::AccountEvent;
+use super::BlockEvent;
+use super::ClickHouseSinkConfig;
+use super::EventSink;
+use super::SinkFormat;
+use super::SlotEvent;
+use super::TransactionEvent;
+use crate::geyser_plugin_postgres::GeyserPluginPostgresError;
+use crate::postgres_client::DbAccountInfo;
+use crate::postgres_client::DbBlockInfo;
+use crate::postgres_client::DbTransaction;
+use log::*;
+use reqwest::blocking::Client;
+use reqwest::blocking::RequestBuilder;
+use serde::Serialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+use std::time::Duration;
+use std::time::Instant;
+
+/// The MergeTree tables of the events, with their columns and engine. The columns are the fields of the JSON events,
+/// and the latest version of a row is kept when the parts are merged so replayed events don't add rows.
+const TABLES: [(&str, &str, &str); 4] = [
+    (
+        "account",
+        "pubkey String, owner String, lamports Int64, executable Bool, rent_epoch Int64, data String CODEC(ZSTD), \
+        slot Int64, write_version UInt64, txn_signature Nullable(String), is_startup Bool",
+        "ReplacingMergeTree(write_version) ORDER BY pubkey",
+    ),
+    (
+        "slot",
+        "slot UInt64, parent Nullable(UInt64), status LowCardinality(String), updated_on DateTime64(3) DEFAULT now64(3)",
+        "ReplacingMergeTree(updated_on) ORDER BY slot",
+    ),
+    (
+        "transaction",
+        "signature String, slot Int64, index Int64, write_version Int64, is_vote Bool, success Bool, fee Int64, \
+        account_keys Array(String), log_messages Array(String) CODEC(ZSTD)",
+        "ReplacingMergeTree PARTITION BY intDiv(slot, 432000) ORDER BY (slot, signature)",
+    ),
+    (
+        "block",
+        "slot Int64, blockhash String, block_time Nullable(Int64), block_height Nullable(Int64)",
+        "ReplacingMergeTree ORDER BY slot",
+    ),
+];
+const ACCOUNT_TABLE: usize = 0;
+const SLOT_TABLE: usize = 1;
+const TRANSACTION_TABLE: usize = 2;
+const BLOCK_TABLE: usize = 3;
+
+fn sink_err(msg: String) -> GeyserPluginError {
+    GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::SinkError { msg }))
+}
+
+/// The JSONEachRow rows of a table waiting to be inserted
+#[derive(Default)]
+struct PendingRows {
+    rows: Vec<u8>,
+    count: usize,
+    since: Option<Instant>,
+}
+
+/// Inserts the events in batches to MergeTree tables over the HTTP interface of ClickHouse
+pub struct ClickHouseSink {
+    client: Client,
+    config: ClickHouseSinkConfig,
+    /// The rows pending per table, in the order of `TABLES`
+    pending: [PendingRows; 4],
+}
+
+impl ClickHouseSink {
+    pub fn new(config: &ClickHouseSinkConfig) -> Result<Self, GeyserPluginError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .map_err(|err| sink_err(format!("[ClickHouseSink::new] error=[{}]", err)))?;
+        let sink = Self {
+            client,
+            config: config.clone(),
+            pending: Default::default(),
+        };
+        sink.execute(&format!("CREATE DATABASE IF NOT EXISTS {}", config.database))?;
+        for (table, columns, engine) in TABLES {
+            sink.execute(&format!("CREATE TABLE IF NOT EXISTS {}.{} ({}) ENGINE = {}", config.database, table, columns, engine))?;
+        }
+        info!("[ClickHouseSink] url=[{}] database=[{}]", config.url, config.database);
+        Ok(sink)
+    }
+
+    fn request(&self) -> RequestBuilder {
+        let mut request = self.client.post(&self.config.url);
+        if let Some(user) = &self.config.user {
+            request = request.header("X-ClickHouse-User", user);
+        }
+        if let Some(password) = &self.config.password {
+            request = request.header("X-ClickHouse-Key", password);
+        }
+        request
+    }
+
+    fn send(request: RequestBuilder, context: &str) -> Result<(), GeyserPluginError> {
+        let response = request.send().map_err(|err| sink_err(format!("[ClickHouseSink] {} error=[{}]", context, err)))?;
+        if response.status().is_success() {
+            return Ok(());
+        }
+        let status = response.status();
+        let body = response.text().unwrap_or_default();
+        Err(sink_err(format!("[ClickHouseSink] {} status=[{}] error=[{}]", context, status, body.trim())))
+    }
+
+    fn execute(&self, query: &str) -> Result<(), GeyserPluginError> {
+        Self::send(self.request().body(query.to_string()), "execute")
+    }
+
+    /// Queue a row for `TABLES[table]`, inserting its rows once `batch_size` of them are pending or the oldest is due
+    fn push(&mut self, table: usize, event: &impl Serialize) -> Result<(), GeyserPluginError> {
+        let pending = &mut self.pending[table];
+        pending.rows.extend(SinkFormat::Json.encode(event).map_err(sink_err)?);
+        pending.rows.push(b'\n');
+        pending.count += 1;
+        let is_due = pending.since.get_or_insert_with(Instant::now).elapsed() >= Duration::from_millis(self.config.flush_interval_ms);
+        // rows kept by a failed insert are retried with every further batch
+        match pending.count % self.config.batch_size == 0 || is_due {
+            true => self.insert(table),
+            false => Ok(()),
+        }
+    }
+
+    /// Insert the pending rows of `TABLES[table]`. The rows of a failed insert are kept for the next one, due
+    /// `flush_interval_ms` later, until `max_pending_rows` of them are pending.
+    fn insert(&mut self, table: usize) -> Result<(), GeyserPluginError> {
+        if self.pending[table].count == 0 {
+            return Ok(());
+        }
+        let (name, _, _) = TABLES[table];
+        let mut request = self.request().query(&[
+            ("query", format!("INSERT INTO {}.{} FORMAT JSONEachRow", self.config.database, name)),
+            ("input_format_skip_unknown_fields", "1".to_string()),
+        ]);
+        if self.config.async_insert {
+            request = request.query(&[("async_insert", "1"), ("wait_for_async_insert", "1")]);
+        }
+        let pending = &mut self.pending[table];
+        match Self::send(request.body(pending.rows.clone()), &format!("insert table=[{}]", name)) {
+            Ok(()) => {
+                *pending = PendingRows::default();
+                Ok(())
+            }
+            Err(err) if pending.count >= self.config.max_pending_rows => {
+                error!("[ClickHouseSink] dropping {} rows of table=[{}]", pending.count, name);
+                *pending = PendingRows::default();
+                Err(err)
+            }
+            Err(err) => {
+                pending.since = Some(Instant::now());
+                Err(err)
+            }
+        }
+    }
+}
+
+impl EventSink for ClickHouseSink {
+    fn name(&self) -> &'static str {
+        "clickhouse"
+    }
+
+    fn update_account(&mut self, account: &DbAccountInfo, is_startup: bool) -> Result<(), GeyserPluginError> {
+        self.push(ACCOUNT_TABLE, &AccountEvent::new(account, is_startup))
+    }
+
+    fn update_slot_status(&mut self, slot: u64, parent: Option<u64>, status: SlotStatus) -> Result<(), GeyserPluginError> {
+        let event = SlotEvent {
+            slot,
+            parent,
+            status: status.as_str(),
+        };
+        self.push(SLOT_TABLE, &event)
+    }
+
+    fn log_transaction(&mut self, transaction: &DbTransaction) -> Result<(), GeyserPluginError> {
+        self.push(TRANSACTION_TABLE, &TransactionEvent::new(transaction))
+    }
+
+    fn update_block_metadata(&mut self, block: &DbBlockInfo) -> Result<(), GeyserPluginError> {
+        self.push(BLOCK_TABLE, &BlockEvent::new(block))
+    }
+
+    fn flush(&mut self) -> Result<(), GeyserPluginError> {
+        // a failing table doesn't keep the rows of the others from being inserted
+        (0..TABLES.len()).map(|table| self.insert(table)).fold(Ok(()), |result, insert| result.and(insert))
+    }
+}
@@ -0,0 +1,137 @@
+use super::AccountEvent;
+use super::BlockEvent;
+use super::EventSink;
+use super::KafkaSinkConfig;
+use super::KafkaTopicsConfig;
+use super::SinkFormat;
+use super::SlotEvent;
+use super::TransactionEvent;
+use crate::geyser_plugin_postgres::GeyserPluginPostgresError;
+use crate::metrics::registry;
+use crate::metrics::SINK_ERRORS_TOTAL;
+use crate::postgres_client::DbAccountInfo;
+use crate::postgres_client::DbBlockInfo;
+use crate::postgres_client::DbTransaction;
+use log::*;
+use rdkafka::config::ClientConfig;
+use rdkafka::error::KafkaError;
+use rdkafka::error::RDKafkaErrorCode;
+use rdkafka::message::Message;
+use rdkafka::producer::BaseProducer;
+use rdkafka::producer::BaseRecord;
+use rdkafka::producer::DeliveryResult;
+use rdkafka::producer::Producer;
+use rdkafka::producer::ProducerContext;
+use rdkafka::ClientContext;
+use serde::Serialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+use std::time::Duration;
+
+/// How long a send waits for room in the producer queue before trying again
+const QUEUE_FULL_POLL: Duration = Duration::from_millis(100);
+
+fn sink_err(msg: String) -> GeyserPluginError {
+    GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::SinkError { msg }))
+}
+
+/// Counts the events the brokers failed to acknowledge, reported once the producer is polled
+struct DeliveryContext;
+
+impl ClientContext for DeliveryContext {}
+
+impl ProducerContext for DeliveryContext {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult<'_>, _: Self::DeliveryOpaque) {
+        if let Err((err, message)) = result {
+            error!("[KafkaSink] delivery failed topic=[{}] error=[{}]", message.topic(), err);
+            registry().inc_counter(SINK_ERRORS_TOTAL, &[("sink", "kafka"), ("request", "delivery")], 1);
+        }
+    }
+}
+
+/// Publishes the events to their topic, keyed by account, slot or signature so the events of an
+/// account or transaction land on the same partition in order
+pub struct KafkaSink {
+    producer: BaseProducer<DeliveryContext>,
+    topics: KafkaTopicsConfig,
+    format: SinkFormat,
+    flush_timeout: Duration,
+}
+
+impl KafkaSink {
+    pub fn new(config: &KafkaSinkConfig) -> Result<Self, GeyserPluginError> {
+        let mut client_config = ClientConfig::new();
+        client_config.set("bootstrap.servers", &config.brokers);
+        for (key, value) in &config.producer {
+            client_config.set(key, value);
+        }
+        let producer = client_config
+            .create_with_context(DeliveryContext)
+            .map_err(|err| sink_err(format!("[KafkaSink::new] error=[{}]", err)))?;
+        info!("[KafkaSink] brokers=[{}] topics=[{:?}]", config.brokers, config.topics);
+        Ok(Self {
+            producer,
+            topics: config.topics.clone(),
+            format: config.format,
+            flush_timeout: Duration::from_millis(config.flush_timeout_ms),
+        })
+    }
+
+    fn publish(&self, topic: &Option<String>, key: &[u8], event: &impl Serialize) -> Result<(), GeyserPluginError> {
+        let topic = match topic {
+            Some(topic) => topic,
+            None => return Ok(()),
+        };
+        let payload = self.format.encode(event).map_err(sink_err)?;
+        let mut record = BaseRecord::to(topic).key(key).payload(&payload);
+        loop {
+            match self.producer.send(record) {
+                Ok(()) => break,
+                // the validator is held back rather than events dropped while the brokers catch up
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), returned)) => {
+                    self.producer.poll(QUEUE_FULL_POLL);
+                    record = returned;
+                }
+                Err((err, _)) => return Err(sink_err(format!("[KafkaSink] topic=[{}] error=[{}]", topic, err))),
+            }
+        }
+        self.producer.poll(Duration::ZERO);
+        Ok(())
+    }
+}
+
+impl EventSink for KafkaSink {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    fn update_account(&mut self, account: &DbAccountInfo, is_startup: bool) -> Result<(), GeyserPluginError> {
+        self.publish(&self.topics.account, &account.pubkey, &AccountEvent::new(account, is_startup))
+    }
+
+    fn update_slot_status(&mut self, slot: u64, parent: Option<u64>, status: SlotStatus) -> Result<(), GeyserPluginError> {
+        let event = SlotEvent {
+            slot,
+            parent,
+            status: status.as_str(),
+        };
+        self.publish(&self.topics.slot, &slot.to_be_bytes(), &event)
+    }
+
+    fn log_transaction(&mut self, transaction: &DbTransaction) -> Result<(), GeyserPluginError> {
+        self.publish(&self.topics.transaction, &transaction.signature, &TransactionEvent::new(transaction))
+    }
+
+    fn update_block_metadata(&mut self, block: &DbBlockInfo) -> Result<(), GeyserPluginError> {
+        self.publish(&self.topics.block, &block.slot.to_be_bytes(), &BlockEvent::new(block))
+    }
+
+    fn flush(&mut self) -> Result<(), GeyserPluginError> {
+        if self.producer.in_flight_count() == 0 {
+            return Ok(());
+        }
+        self.producer.flush(self.flush_timeout).map_err(|err| sink_err(format!("[KafkaSink::flush] error=[{}]", err)))
+    }
+}
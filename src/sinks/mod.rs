@@ -0,0 +1,544 @@
+#[cfg(feature = "clickhouse")]
+pub mod clickhouse_sink;
+#[cfg(feature = "kafka")]
+pub mod kafka_sink;
+#[cfg(feature = "webhook")]
+pub mod webhook_sink;
+
+use crate::config::GeyserPluginPostgresConfig;
+use crate::postgres_client::DbAccountInfo;
+use crate::postgres_client::DbBlockInfo;
+use crate::postgres_client::DbTransaction;
+use serde::Deserialize;
+use serde::Serialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// An output the workers publish the account, slot, transaction and block events to, next to or
+/// instead of the PostgreSQL database. Each worker owns sinks of its own.
+pub trait EventSink: Send {
+    /// The sink label of the metrics
+    fn name(&self) -> &'static str;
+
+    fn update_account(&mut self, account: &DbAccountInfo, is_startup: bool) -> Result<(), GeyserPluginError>;
+
+    fn update_slot_status(&mut self, slot: u64, parent: Option<u64>, status: SlotStatus) -> Result<(), GeyserPluginError>;
+
+    fn log_transaction(&mut self, transaction: &DbTransaction) -> Result<(), GeyserPluginError>;
+
+    fn update_block_metadata(&mut self, block: &DbBlockInfo) -> Result<(), GeyserPluginError>;
+
+    /// Called when the worker is idle and before it exits, waits for the published events to be delivered
+    fn flush(&mut self) -> Result<(), GeyserPluginError>;
+}
+
+/// The sinks of a worker, from the config
+pub fn build_sinks(config: &GeyserPluginPostgresConfig) -> Result<Vec<Box<dyn EventSink>>, GeyserPluginError> {
+    #[cfg_attr(not(any(feature = "kafka", feature = "clickhouse")), allow(unused_mut))]
+    let mut sinks: Vec<Box<dyn EventSink>> = Vec::new();
+    #[cfg(feature = "kafka")]
+    if let Some(kafka) = &config.kafka {
+        sinks.push(Box::new(kafka_sink::KafkaSink::new(kafka)?));
+    }
+    #[cfg(feature = "clickhouse")]
+    if config.db_backend == DbBackend::Clickhouse {
+        sinks.push(Box::new(clickhouse_sink::ClickHouseSink::new(&config.clickhouse)?));
+    }
+    #[cfg(not(any(feature = "kafka", feature = "clickhouse")))]
+    let _ = config;
+    Ok(sinks)
+}
+
+/// How the events are encoded
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SinkFormat {
+    /// A JSON object, with the keys and signatures in base58 and the account data in base64
+    #[default]
+    Json,
+    /// The bincode encoding of the same event, with the keys and signatures as bytes
+    Bincode,
+}
+
+impl SinkFormat {
+    pub fn encode<T: Serialize>(&self, event: &T) -> Result<Vec<u8>, String> {
+        match self {
+            SinkFormat::Json => serde_json::to_vec(event).map_err(|err| err.to_string()),
+            SinkFormat::Bincode => bincode::serialize(event).map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/// The database the events are written to
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DbBackend {
+    /// The tables of the handlers in PostgreSQL
+    #[default]
+    Postgres,
+    /// The MergeTree tables of the events in ClickHouse, see `ClickHouseSinkConfig`
+    Clickhouse,
+}
+
+/// * The `clickhouse` section, the ClickHouse server written to with the "clickhouse" `db_backend`, requires the
+/// `clickhouse` feature. The events are inserted over the HTTP interface in batches of `batch_size` rows per table:
+/// "clickhouse" : {
+///     "url" : "http://localhost:8123",
+///     "database" : "solana",
+///     "batch_size" : 10000,
+///     "flush_interval_ms" : 1000,
+///     "async_insert" : true
+/// }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClickHouseSinkConfig {
+    /// The HTTP interface of the server
+    pub url: String,
+    /// The database the tables are created in
+    pub database: String,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    /// Rows queued per table before they are inserted
+    pub batch_size: usize,
+    /// Milliseconds the rows of a table are queued before they are inserted, whatever their count
+    pub flush_interval_ms: u64,
+    /// Let the server merge the inserts of the workers into fewer parts, acknowledged once written
+    pub async_insert: bool,
+    /// Rows a table keeps queued while its inserts fail, before they are dropped
+    pub max_pending_rows: usize,
+    /// Seconds a request waits for the server
+    pub timeout_secs: u64,
+}
+
+impl Default for ClickHouseSinkConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://localhost:8123".to_string(),
+            database: "solana".to_string(),
+            user: None,
+            password: None,
+            batch_size: 10000,
+            flush_interval_ms: 1000,
+            async_insert: true,
+            max_pending_rows: 1_000_000,
+            timeout_secs: 30,
+        }
+    }
+}
+
+impl ClickHouseSinkConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if cfg!(not(feature = "clickhouse")) {
+            return Err("the \"clickhouse\" db_backend requires the plugin to be built with the \"clickhouse\" feature".to_string());
+        }
+        if !self.url.starts_with("http://") && !self.url.starts_with("https://") {
+            return Err(format!("clickhouse \"url\" {} must be an http:// or https:// url", self.url));
+        }
+        if self.database.is_empty() || !self.database.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("clickhouse \"database\" \"{}\" must be made of letters, digits and underscores", self.database));
+        }
+        if self.batch_size == 0 {
+            return Err("clickhouse \"batch_size\" must be greater than 0".to_string());
+        }
+        if self.flush_interval_ms == 0 {
+            return Err("clickhouse \"flush_interval_ms\" must be greater than 0".to_string());
+        }
+        if self.max_pending_rows < self.batch_size {
+            return Err("clickhouse \"max_pending_rows\" must be at least \"batch_size\"".to_string());
+        }
+        if self.timeout_secs == 0 {
+            return Err("clickhouse \"timeout_secs\" must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// * The `kafka` section publishes the events to Kafka topics, requires the `kafka` feature.
+/// "kafka" : {
+///     "brokers" : "localhost:9092",
+///     "topics" : { "account" : "solana.account", "slot" : "solana.slot", "transaction" : "solana.transaction", "block" : "solana.block" },
+///     "format" : "json",
+///     "producer" : { "compression.type" : "lz4" }
+/// }
+/// A topic set to null is not published to. With "replace_postgres" the events are only published to Kafka.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KafkaSinkConfig {
+    /// The bootstrap servers, comma separated
+    pub brokers: String,
+    pub topics: KafkaTopicsConfig,
+    pub format: SinkFormat,
+    /// Additional librdkafka producer properties
+    pub producer: HashMap<String, String>,
+    /// Milliseconds a flush waits for the queued events to be delivered
+    pub flush_timeout_ms: u64,
+    /// Publish to Kafka instead of writing to PostgreSQL, no database connection is made
+    pub replace_postgres: bool,
+}
+
+impl Default for KafkaSinkConfig {
+    fn default() -> Self {
+        Self {
+            brokers: "localhost:9092".to_string(),
+            topics: KafkaTopicsConfig::default(),
+            format: SinkFormat::default(),
+            producer: HashMap::default(),
+            flush_timeout_ms: 5000,
+            replace_postgres: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KafkaTopicsConfig {
+    pub account: Option<String>,
+    pub slot: Option<String>,
+    pub transaction: Option<String>,
+    pub block: Option<String>,
+}
+
+impl Default for KafkaTopicsConfig {
+    fn default() -> Self {
+        Self {
+            account: Some("solana.account".to_string()),
+            slot: Some("solana.slot".to_string()),
+            transaction: Some("solana.transaction".to_string()),
+            block: Some("solana.block".to_string()),
+        }
+    }
+}
+
+impl KafkaSinkConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if cfg!(not(feature = "kafka")) {
+            return Err("\"kafka\" requires the plugin to be built with the \"kafka\" feature".to_string());
+        }
+        if self.brokers.is_empty() {
+            return Err("kafka \"brokers\" must be specified".to_string());
+        }
+        let topics = [&self.topics.account, &self.topics.slot, &self.topics.transaction, &self.topics.block];
+        if topics.iter().any(|topic| topic.as_deref() == Some("")) {
+            return Err("kafka topics can't be empty, set them to null to not publish the events".to_string());
+        }
+        if topics.iter().all(|topic| topic.is_none()) {
+            return Err("kafka publishes to no topic".to_string());
+        }
+        if self.flush_timeout_ms == 0 {
+            return Err("kafka \"flush_timeout_ms\" must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The slot statuses a webhook can be notified of
+const WEBHOOK_SLOT_STATUSES: [&str; 3] = ["processed", "confirmed", "rooted"];
+
+/// * The `webhooks` section POSTs the account updates and slot statuses as JSON to HTTP endpoints, requires the
+/// `webhook` feature:
+/// "webhooks" : \[{
+///     "url" : "https://indexer.example.com/geyser",
+///     "secret" : "...",
+///     "owners" : \["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"\],
+///     "slot_statuses" : \["rooted"\]
+/// }\]
+/// The body of a request is a JSON array of events, signed with HMAC-SHA256 in the `X-Signature-256` header when a
+/// `secret` is set. Each webhook sends its requests from a thread of its own, fed by a queue of `queue_size` events.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookSinkConfig {
+    pub url: String,
+    /// The key the bodies are signed with
+    pub secret: Option<String>,
+    /// The accounts notified by pubkey, together with those of the `owners`. Every account the `accounts_selector`
+    /// picks is notified when both are empty
+    pub accounts: Vec<String>,
+    /// The accounts notified by owner
+    pub owners: Vec<String>,
+    /// Notify the accounts of the startup snapshot
+    pub notify_startup: bool,
+    /// The slot statuses notified, 'processed', 'confirmed' or 'rooted'
+    pub slot_statuses: Vec<String>,
+    /// Events sent per request
+    pub batch_size: usize,
+    /// Milliseconds an event is queued before it is sent, whatever the count of the queued events
+    pub flush_interval_ms: u64,
+    /// Attempts after a failed request, waiting `retry_backoff_ms` before the first and twice as long before each next
+    pub max_retries: u32,
+    pub retry_backoff_ms: u64,
+    /// Events kept queued while the requests fail, before they are dropped
+    pub max_pending_events: usize,
+    /// Seconds a request waits for the endpoint
+    pub timeout_secs: u64,
+    /// Events waiting for the webhook's thread, the events notified while it is full are dropped
+    pub queue_size: usize,
+}
+
+impl Default for WebhookSinkConfig {
+    fn default() -> Self {
+        Self {
+            url: "".to_string(),
+            secret: None,
+            accounts: Vec::new(),
+            owners: Vec::new(),
+            notify_startup: false,
+            slot_statuses: vec!["rooted".to_string()],
+            batch_size: 100,
+            flush_interval_ms: 1000,
+            max_retries: 3,
+            retry_backoff_ms: 500,
+            max_pending_events: 100_000,
+            timeout_secs: 10,
+            queue_size: 10_000,
+        }
+    }
+}
+
+impl WebhookSinkConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if cfg!(not(feature = "webhook")) {
+            return Err("\"webhooks\" requires the plugin to be built with the \"webhook\" feature".to_string());
+        }
+        if !self.url.starts_with("http://") && !self.url.starts_with("https://") {
+            return Err(format!("webhook \"url\" {} must be an http:// or https:// url", self.url));
+        }
+        if self.secret.as_deref() == Some("") {
+            return Err(format!("webhook {} \"secret\" can't be empty", self.url));
+        }
+        if let Some(key) = self.accounts.iter().chain(self.owners.iter()).find(|key| Pubkey::from_str(key).is_err()) {
+            return Err(format!("webhook {} account or owner \"{}\" is not a valid pubkey", self.url, key));
+        }
+        if let Some(status) = self.slot_statuses.iter().find(|status| !WEBHOOK_SLOT_STATUSES.contains(&status.as_str())) {
+            return Err(format!("webhook {} slot status \"{}\" must be one of {:?}", self.url, status, WEBHOOK_SLOT_STATUSES));
+        }
+        if self.batch_size == 0 {
+            return Err(format!("webhook {} \"batch_size\" must be greater than 0", self.url));
+        }
+        if self.flush_interval_ms == 0 {
+            return Err(format!("webhook {} \"flush_interval_ms\" must be greater than 0", self.url));
+        }
+        if self.max_pending_events < self.batch_size {
+            return Err(format!("webhook {} \"max_pending_events\" must be at least \"batch_size\"", self.url));
+        }
+        if self.timeout_secs == 0 {
+            return Err(format!("webhook {} \"timeout_secs\" must be greater than 0", self.url));
+        }
+        if self.queue_size == 0 {
+            return Err(format!("webhook {} \"queue_size\" must be greater than 0", self.url));
+        }
+        Ok(())
+    }
+}
+
+/// Encodes keys and signatures in base58 for JSON, as bytes otherwise
+mod base58_bytes {
+    use serde::Serializer;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        match serializer.is_human_readable() {
+            true => serializer.serialize_str(&bs58::encode(bytes).into_string()),
+            false => serializer.serialize_bytes(bytes),
+        }
+    }
+}
+
+/// Encodes the account data in base64 for JSON, as bytes otherwise
+mod base64_bytes {
+    use serde::Serializer;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        match serializer.is_human_readable() {
+            true => serializer.serialize_str(&base64::encode(bytes)),
+            false => serializer.serialize_bytes(bytes),
+        }
+    }
+}
+
+/// A key or signature nested in an event, encoded like the event's own keys
+#[derive(Debug, Serialize)]
+pub struct Base58<'a>(#[serde(with = "base58_bytes")] &'a [u8]);
+
+#[derive(Debug, Serialize)]
+pub struct AccountEvent<'a> {
+    #[serde(with = "base58_bytes")]
+    pub pubkey: &'a [u8],
+    #[serde(with = "base58_bytes")]
+    pub owner: &'a [u8],
+    pub lamports: i64,
+    pub executable: bool,
+    pub rent_epoch: i64,
+    #[serde(with = "base64_bytes")]
+    pub data: &'a [u8],
+    pub slot: i64,
+    pub write_version: i64,
+    pub txn_signature: Option<Base58<'a>>,
+    pub is_startup: bool,
+}
+
+impl<'a> AccountEvent<'a> {
+    pub fn new(account: &'a DbAccountInfo, is_startup: bool) -> Self {
+        Self {
+            pubkey: &account.pubkey,
+            owner: &account.owner,
+            lamports: account.lamports,
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data: &account.data,
+            slot: account.slot,
+            write_version: account.write_version,
+            txn_signature: account.txn_signature.as_deref().map(Base58),
+            is_startup,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SlotEvent {
+    pub slot: u64,
+    pub parent: Option<u64>,
+    pub status: &'static str,
+}
+
+/// The transaction, its accounts and the outcome of its execution
+#[derive(Debug, Serialize)]
+pub struct TransactionEvent<'a> {
+    #[serde(with = "base58_bytes")]
+    pub signature: &'a [u8],
+    pub slot: i64,
+    pub index: i64,
+    pub write_version: i64,
+    pub is_vote: bool,
+    pub success: bool,
+    pub fee: i64,
+    pub account_keys: Vec<Base58<'a>>,
+    pub log_messages: Option<&'a [String]>,
+}
+
+impl<'a> TransactionEvent<'a> {
+    pub fn new(transaction: &'a DbTransaction) -> Self {
+        Self {
+            signature: &transaction.signature,
+            slot: transaction.slot,
+            index: transaction.index,
+            write_version: transaction.write_version,
+            is_vote: transaction.is_vote,
+            success: transaction.meta.error.is_none(),
+            fee: transaction.meta.fee,
+            account_keys: transaction.account_keys().into_iter().map(Base58).collect(),
+            log_messages: transaction.meta.log_messages.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlockEvent<'a> {
+    pub slot: i64,
+    pub blockhash: &'a str,
+    pub block_time: Option<i64>,
+    pub block_height: Option<i64>,
+}
+
+impl<'a> BlockEvent<'a> {
+    pub fn new(block: &'a DbBlockInfo) -> Self {
+        Self {
+            slot: block.slot,
+            blockhash: &block.blockhash,
+            block_time: block.block_time,
+            block_height: block.block_height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::AccountKey;
+
+    #[test]
+    fn test_account_event() {
+        let account = DbAccountInfo {
+            pubkey: AccountKey::from_slice(&[1; 32]),
+            lamports: 10,
+            owner: AccountKey::from_slice(&[2; 32]),
+            executable: false,
+            rent_epoch: 0,
+            data: vec![1, 2, 3],
+            slot: 5,
+            write_version: 7,
+            txn_signature: None,
+        };
+        let event = AccountEvent::new(&account, true);
+        let json: serde_json::Value = serde_json::from_slice(&SinkFormat::Json.encode(&event).unwrap()).unwrap();
+        assert_eq!(json["pubkey"], "4vJ9JU1bJJE96FWSJKvHsmmFADCg4gpZQff4P3bkLKi");
+        assert_eq!(json["data"], "AQID");
+        assert_eq!(json["slot"], 5);
+        assert_eq!(json["is_startup"], true);
+
+        let bincode = SinkFormat::Bincode.encode(&event).unwrap();
+        // the pubkey comes first, as its length followed by its bytes
+        assert_eq!(&bincode[..8], &32u64.to_le_bytes());
+        assert_eq!(&bincode[8..40], &[1; 32]);
+    }
+
+    #[test]
+    fn test_clickhouse_sink_config() {
+        let config = ClickHouseSinkConfig::default();
+        assert_eq!(config.validate().is_ok(), cfg!(feature = "clickhouse"));
+        if cfg!(feature = "clickhouse") {
+            let database = ClickHouseSinkConfig {
+                database: "solana; DROP TABLE account".to_string(),
+                ..ClickHouseSinkConfig::default()
+            };
+            assert!(database.validate().is_err());
+            let url = ClickHouseSinkConfig {
+                url: "localhost:8123".to_string(),
+                ..ClickHouseSinkConfig::default()
+            };
+            assert!(url.validate().is_err());
+        }
+        let backend: DbBackend = serde_json::from_str("\"clickhouse\"").unwrap();
+        assert_eq!(backend, DbBackend::Clickhouse);
+    }
+
+    #[test]
+    fn test_webhook_sink_config() {
+        let config = WebhookSinkConfig {
+            url: "https://indexer.example.com/geyser".to_string(),
+            ..WebhookSinkConfig::default()
+        };
+        assert_eq!(config.validate().is_ok(), cfg!(feature = "webhook"));
+        if cfg!(feature = "webhook") {
+            let owner = WebhookSinkConfig {
+                owners: vec!["not a pubkey".to_string()],
+                ..config.clone()
+            };
+            assert!(owner.validate().is_err());
+            let slot_status = WebhookSinkConfig {
+                slot_statuses: vec!["finalized".to_string()],
+                ..config
+            };
+            assert!(slot_status.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_kafka_sink_config() {
+        let config = KafkaSinkConfig::default();
+        assert_eq!(config.validate().is_ok(), cfg!(feature = "kafka"));
+        if cfg!(feature = "kafka") {
+            let no_topics = KafkaSinkConfig {
+                topics: KafkaTopicsConfig {
+                    account: None,
+                    slot: None,
+                    transaction: None,
+                    block: None,
+                },
+                ..KafkaSinkConfig::default()
+            };
+            assert!(no_topics.validate().is_err());
+        }
+    }
+}
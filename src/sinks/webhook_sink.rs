@@ -0,0 +1,346 @@
+use super::AccountEvent;
+use super::SinkFormat;
+use super::SlotEvent;
+use super::WebhookSinkConfig;
+use crate::geyser_plugin_postgres::GeyserPluginPostgresError;
+use crate::metrics::registry;
+use crate::metrics::SINK_DROPPED_TOTAL;
+use crate::metrics::SINK_ERRORS_TOTAL;
+use crate::postgres_client::DbAccountInfo;
+use crossbeam_channel::bounded;
+use crossbeam_channel::Receiver;
+use crossbeam_channel::RecvTimeoutError;
+use crossbeam_channel::Sender;
+use crossbeam_channel::TrySendError;
+use log::*;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use reqwest::blocking::Client;
+use serde::Serialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::thread::Builder;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
+
+/// The header of the HMAC-SHA256 signature of the body
+const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+fn sink_err(msg: String) -> GeyserPluginError {
+    GeyserPluginError::Custom(Box::new(GeyserPluginPostgresError::SinkError { msg }))
+}
+
+/// The events of a request body, told apart by their `kind`
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum WebhookEvent<'a> {
+    Account(AccountEvent<'a>),
+    Slot(SlotEvent),
+}
+
+/// The `sha256=<hex>` HMAC-SHA256 signature of a body
+fn signature(secret: &str, body: &[u8]) -> Result<String, openssl::error::ErrorStack> {
+    let key = PKey::hmac(secret.as_bytes())?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(body)?;
+    Ok(format!("sha256={}", hex::encode(signer.sign_to_vec()?)))
+}
+
+/// What the plugin hands over to a webhook's thread
+enum WebhookMessage {
+    Account(DbAccountInfo, bool),
+    Slot(SlotEvent),
+}
+
+/// A webhook of the plugin, built once. The account updates and slot statuses are queued in the order the
+/// plugin is notified of them and POSTed by a thread of the webhook's own, so a slow or unreachable endpoint
+/// never holds up the workers. Events are dropped, and counted, once `queue_size` of them wait to be encoded.
+pub struct Webhook {
+    sender: Option<Sender<WebhookMessage>>,
+    handle: Option<JoinHandle<()>>,
+    url: String,
+    notify_startup: bool,
+    slot_statuses: Vec<String>,
+    accounts: HashSet<Vec<u8>>,
+    owners: HashSet<Vec<u8>>,
+}
+
+impl Webhook {
+    pub fn start(config: &WebhookSinkConfig) -> Result<Self, GeyserPluginError> {
+        let mut sender_thread = WebhookSender::new(config)?;
+        let (sender, receiver) = bounded(config.queue_size);
+        let handle = Builder::new()
+            .name("geyser-webhook".to_string())
+            .spawn(move || sender_thread.run(receiver))
+            .map_err(|err| sink_err(format!("[Webhook::start] error=[{}]", err)))?;
+        // checked by the config validation
+        let keys = |keys: &[String]| keys.iter().filter_map(|key| Pubkey::from_str(key).ok()).map(|key| key.to_bytes().to_vec()).collect();
+        info!("[Webhook] url=[{}]", config.url);
+        Ok(Self {
+            sender: Some(sender),
+            handle: Some(handle),
+            url: config.url.clone(),
+            notify_startup: config.notify_startup,
+            slot_statuses: config.slot_statuses.clone(),
+            accounts: keys(&config.accounts),
+            owners: keys(&config.owners),
+        })
+    }
+
+    fn is_notified(&self, account: &DbAccountInfo, is_startup: bool) -> bool {
+        if is_startup && !self.notify_startup {
+            return false;
+        }
+        (self.accounts.is_empty() && self.owners.is_empty()) || self.accounts.contains(account.pubkey.as_slice()) || self.owners.contains(account.owner.as_slice())
+    }
+
+    fn queue(&self, request: &'static str, message: WebhookMessage) {
+        if let Some(Err(TrySendError::Full(_))) = self.sender.as_ref().map(|sender| sender.try_send(message)) {
+            registry().inc_counter(SINK_DROPPED_TOTAL, &[("sink", "webhook"), ("request", request)], 1);
+        }
+    }
+
+    pub fn update_account(&self, account: &DbAccountInfo, is_startup: bool) {
+        if self.is_notified(account, is_startup) {
+            self.queue("update_account", WebhookMessage::Account(account.clone(), is_startup));
+        }
+    }
+
+    pub fn update_slot_status(&self, slot: u64, parent: Option<u64>, status: SlotStatus) {
+        if self.slot_statuses.iter().any(|notified| notified == status.as_str()) {
+            self.queue(
+                "update_slot",
+                WebhookMessage::Slot(SlotEvent {
+                    slot,
+                    parent,
+                    status: status.as_str(),
+                }),
+            );
+        }
+    }
+
+    /// Send the queued events and stop the thread
+    pub fn stop(&mut self) {
+        self.sender.take();
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                error!("[Webhook] url=[{}] the webhook thread panicked", self.url);
+            }
+        }
+    }
+}
+
+impl Drop for Webhook {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// POSTs the events of a webhook in batches, as a JSON array, on the webhook's thread
+struct WebhookSender {
+    client: Client,
+    config: WebhookSinkConfig,
+    /// The encoded events waiting to be sent, comma separated
+    pending: Vec<u8>,
+    count: usize,
+    since: Option<Instant>,
+}
+
+impl WebhookSender {
+    fn new(config: &WebhookSinkConfig) -> Result<Self, GeyserPluginError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .map_err(|err| sink_err(format!("[Webhook::start] error=[{}]", err)))?;
+        Ok(Self {
+            client,
+            config: config.clone(),
+            pending: Vec::new(),
+            count: 0,
+            since: None,
+        })
+    }
+
+    /// Send the events as they are queued until the webhook is stopped, then send what is left
+    fn run(&mut self, receiver: Receiver<WebhookMessage>) {
+        let flush_interval = Duration::from_millis(self.config.flush_interval_ms);
+        loop {
+            match receiver.recv_timeout(flush_interval) {
+                Ok(WebhookMessage::Account(account, is_startup)) => self.push(&WebhookEvent::Account(AccountEvent::new(&account, is_startup))),
+                Ok(WebhookMessage::Slot(slot)) => self.push(&WebhookEvent::Slot(slot)),
+                Err(RecvTimeoutError::Timeout) => self.send(),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        self.send();
+    }
+
+    /// Queue an event, sending the queued events once `batch_size` of them are queued or the oldest is due
+    fn push(&mut self, event: &WebhookEvent) {
+        let encoded = match SinkFormat::Json.encode(event) {
+            Ok(encoded) => encoded,
+            Err(err) => {
+                error!("[Webhook] url=[{}] failed to encode an event error=[{}]", self.config.url, err);
+                registry().inc_counter(SINK_ERRORS_TOTAL, &[("sink", "webhook"), ("request", "encode")], 1);
+                return;
+            }
+        };
+        if self.count > 0 {
+            self.pending.push(b',');
+        }
+        self.pending.extend(encoded);
+        self.count += 1;
+        let is_due = self.since.get_or_insert_with(Instant::now).elapsed() >= Duration::from_millis(self.config.flush_interval_ms);
+        // events kept by a failed request are retried with every further batch
+        if self.count % self.config.batch_size == 0 || is_due {
+            self.send();
+        }
+    }
+
+    fn post(&self, body: &[u8]) -> Result<(), String> {
+        let mut request = self.client.post(&self.config.url).header("Content-Type", "application/json");
+        if let Some(secret) = &self.config.secret {
+            request = request.header(SIGNATURE_HEADER, signature(secret, body).map_err(|err| err.to_string())?);
+        }
+        let response = request.body(body.to_vec()).send().map_err(|err| err.to_string())?;
+        match response.status().is_success() {
+            true => Ok(()),
+            false => Err(format!("status=[{}]", response.status())),
+        }
+    }
+
+    /// Send the queued events, attempting `max_retries` more times when the request fails. The events of a failed
+    /// request are kept for the next one, due `flush_interval_ms` later, until `max_pending_events` of them are queued.
+    fn send(&mut self) {
+        if self.count == 0 {
+            return;
+        }
+        let mut body = Vec::with_capacity(self.pending.len() + 2);
+        body.push(b'[');
+        body.extend_from_slice(&self.pending);
+        body.push(b']');
+        let mut backoff = Duration::from_millis(self.config.retry_backoff_ms);
+        let mut attempt = 0;
+        let error = loop {
+            match self.post(&body) {
+                Ok(()) => {
+                    self.pending.clear();
+                    self.count = 0;
+                    self.since = None;
+                    return;
+                }
+                Err(err) if attempt < self.config.max_retries => {
+                    warn!("[Webhook] url=[{}] retrying {} events in {:?} after error=[{}]", self.config.url, self.count, backoff, err);
+                    std::thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                Err(err) => break err,
+            }
+        };
+        error!("[Webhook] url=[{}] error=[{}]", self.config.url, error);
+        registry().inc_counter(SINK_ERRORS_TOTAL, &[("sink", "webhook"), ("request", "post")], 1);
+        if self.count >= self.config.max_pending_events {
+            error!("[Webhook] url=[{}] dropping {} events", self.config.url, self.count);
+            registry().inc_counter(SINK_DROPPED_TOTAL, &[("sink", "webhook"), ("request", "post")], self.count as u64);
+            self.pending.clear();
+            self.count = 0;
+            self.since = None;
+        } else {
+            self.since = Some(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgres_client::AccountKey;
+
+    #[test]
+    fn test_signature() {
+        assert_eq!(
+            signature("key", b"The quick brown fox jumps over the lazy dog").unwrap(),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn test_push() {
+        let config = WebhookSinkConfig {
+            url: "http://127.0.0.1:1/geyser".to_string(),
+            batch_size: 10,
+            flush_interval_ms: 60_000,
+            ..WebhookSinkConfig::default()
+        };
+        let mut sender = WebhookSender::new(&config).unwrap();
+        let account = DbAccountInfo {
+            pubkey: AccountKey::from_slice(&[1; 32]),
+            lamports: 10,
+            owner: AccountKey::from_slice(&[2; 32]),
+            executable: false,
+            rent_epoch: 0,
+            data: vec![1, 2, 3],
+            slot: 5,
+            write_version: 7,
+            txn_signature: None,
+        };
+        sender.push(&WebhookEvent::Account(AccountEvent::new(&account, false)));
+        sender.push(&WebhookEvent::Slot(SlotEvent {
+            slot: 5,
+            parent: Some(4),
+            status: "rooted",
+        }));
+        assert_eq!(sender.count, 2);
+
+        let events: serde_json::Value = serde_json::from_slice(&[b"[", sender.pending.as_slice(), b"]"].concat()).unwrap();
+        assert_eq!(events[0]["kind"], "account");
+        assert_eq!(events[0]["owner"], bs58::encode([2; 32]).into_string());
+        assert_eq!(events[1]["kind"], "slot");
+        assert_eq!(events[1]["status"], "rooted");
+    }
+
+    #[test]
+    fn test_webhook() {
+        let owner = Pubkey::new_unique();
+        let config = WebhookSinkConfig {
+            url: "http://127.0.0.1:1/geyser".to_string(),
+            owners: vec![owner.to_string()],
+            queue_size: 1,
+            max_retries: 0,
+            ..WebhookSinkConfig::default()
+        };
+        let mut webhook = Webhook::start(&config).unwrap();
+        let account = DbAccountInfo {
+            pubkey: AccountKey::from_slice(&[1; 32]),
+            lamports: 10,
+            owner: AccountKey::from_slice(owner.as_ref()),
+            executable: false,
+            rent_epoch: 0,
+            data: vec![1, 2, 3],
+            slot: 5,
+            write_version: 7,
+            txn_signature: None,
+        };
+        assert!(webhook.is_notified(&account, false));
+        assert!(!webhook.is_notified(&account, true));
+        assert!(!webhook.is_notified(
+            &DbAccountInfo {
+                owner: AccountKey::from_slice(&[2; 32]),
+                ..account.clone()
+            },
+            false
+        ));
+        // queueing never blocks on the unreachable endpoint, the events beyond `queue_size` are dropped
+        for _ in 0..100 {
+            webhook.update_account(&account, false);
+            webhook.update_slot_status(5, Some(4), SlotStatus::Rooted);
+        }
+        webhook.stop();
+    }
+}
@@ -0,0 +1,293 @@
+use crate::fixtures::RecordedAccount;
+use crate::fixtures::RecordedTransaction;
+use crate::metrics::registry;
+use crate::metrics::SPOOL_BYTES;
+use crate::metrics::SPOOL_RECORDS_TOTAL;
+use log::*;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPluginError;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaBlockInfo;
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+use solana_transaction_status::Reward;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+
+pub const SPOOL_FILE: &str = "spool.bin";
+
+/// * The `spool` section appends the live events to a file while a worker is reconnecting to the database,
+/// and replays them once every worker is connected again.
+/// "spool" : { "path" : "/solana/spool", "max_bytes" : 10737418240, "replay_batch" : 1000 }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpoolConfig {
+    /// The directory of the spool file
+    pub path: String,
+    /// The size the spool file is capped at, the events are dropped past it
+    pub max_bytes: u64,
+    /// The events replayed per callback of the validator
+    pub replay_batch: usize,
+}
+
+impl Default for SpoolConfig {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            max_bytes: 10 * 1024 * 1024 * 1024,
+            replay_batch: 1000,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecordedSlotStatus {
+    Processed,
+    Confirmed,
+    Rooted,
+}
+
+impl From<&SlotStatus> for RecordedSlotStatus {
+    fn from(status: &SlotStatus) -> Self {
+        match status {
+            SlotStatus::Processed => Self::Processed,
+            SlotStatus::Confirmed => Self::Confirmed,
+            SlotStatus::Rooted => Self::Rooted,
+        }
+    }
+}
+
+impl From<RecordedSlotStatus> for SlotStatus {
+    fn from(status: RecordedSlotStatus) -> Self {
+        match status {
+            RecordedSlotStatus::Processed => Self::Processed,
+            RecordedSlotStatus::Confirmed => Self::Confirmed,
+            RecordedSlotStatus::Rooted => Self::Rooted,
+        }
+    }
+}
+
+/// The block metadata as delivered by the validator
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedBlock {
+    pub slot: u64,
+    pub blockhash: String,
+    pub rewards: Vec<Reward>,
+    pub block_time: Option<i64>,
+    pub block_height: Option<u64>,
+}
+
+impl RecordedBlock {
+    pub fn new(block_info: &ReplicaBlockInfo) -> Self {
+        Self {
+            slot: block_info.slot,
+            blockhash: block_info.blockhash.to_string(),
+            rewards: block_info.rewards.to_vec(),
+            block_time: block_info.block_time,
+            block_height: block_info.block_height,
+        }
+    }
+
+    pub fn as_replica(&self) -> ReplicaBlockInfo {
+        ReplicaBlockInfo {
+            slot: self.slot,
+            blockhash: &self.blockhash,
+            rewards: &self.rewards,
+            block_time: self.block_time,
+            block_height: self.block_height,
+        }
+    }
+}
+
+/// A live event of the validator, spooled in the order it was delivered. `ClosedAccount` is an account the selectors
+/// didn't pick, only its close is written
+#[derive(Serialize, Deserialize)]
+pub enum SpoolRecord {
+    Account(RecordedAccount),
+    Slot { slot: u64, parent: Option<u64>, status: RecordedSlotStatus },
+    Transaction(Box<RecordedTransaction>),
+    Block(RecordedBlock),
+    ClosedAccount(RecordedAccount),
+}
+
+impl SpoolRecord {
+    /// The request label of the metrics
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpoolRecord::Account(_) | SpoolRecord::ClosedAccount(_) => "update_account",
+            SpoolRecord::Slot { .. } => "update_slot",
+            SpoolRecord::Transaction(_) => "log_transaction",
+            SpoolRecord::Block(_) => "update_block_metadata",
+        }
+    }
+}
+
+fn spool_err(msg: String) -> GeyserPluginError {
+    GeyserPluginError::Custom(msg.into())
+}
+
+/// The events appended to the spool file and not replayed yet. The file is read back from where the last
+/// replay stopped and truncated once every event was replayed. The events left over by a previous run are
+/// replayed as well.
+pub struct Spool {
+    writer: BufWriter<File>,
+    reader: BufReader<File>,
+    max_bytes: u64,
+    replay_batch: usize,
+    /// The bytes appended to the file
+    written: u64,
+    /// The bytes replayed from the file
+    replayed: u64,
+}
+
+impl Spool {
+    pub fn open(config: &SpoolConfig) -> Result<Self, GeyserPluginError> {
+        let open_err = |e: std::io::Error| GeyserPluginError::ConfigFileReadError {
+            msg: format!("Failed to open spool path {}: {}", config.path, e),
+        };
+        let dir = Path::new(&config.path);
+        std::fs::create_dir_all(dir).map_err(open_err)?;
+        let path = dir.join(SPOOL_FILE);
+        let file = OpenOptions::new().create(true).append(true).open(&path).map_err(open_err)?;
+        let written = file.metadata().map_err(open_err)?.len();
+        if written > 0 {
+            info!("[spool] {} bytes left over to replay path=[{}]", written, path.display());
+        }
+        registry().set_gauge(SPOOL_BYTES, &[], written as i64);
+        Ok(Self {
+            writer: BufWriter::new(file),
+            reader: BufReader::new(File::open(&path).map_err(open_err)?),
+            max_bytes: config.max_bytes,
+            replay_batch: config.replay_batch.max(1),
+            written,
+            replayed: 0,
+        })
+    }
+
+    /// Whether every spooled event was replayed
+    pub fn is_empty(&self) -> bool {
+        self.replayed >= self.written
+    }
+
+    /// The bytes of the events not replayed yet
+    pub fn bytes(&self) -> u64 {
+        self.written - self.replayed
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Append an event, it is dropped once the file reached `max_bytes`
+    pub fn append(&mut self, record: &SpoolRecord) -> Result<(), GeyserPluginError> {
+        let size = bincode::serialized_size(record).map_err(|e| spool_err(e.to_string()))?;
+        if self.written + size > self.max_bytes {
+            registry().inc_counter(SPOOL_RECORDS_TOTAL, &[("request", record.name()), ("outcome", "dropped")], 1);
+            return Ok(());
+        }
+        bincode::serialize_into(&mut self.writer, record).map_err(|e| spool_err(format!("Failed to spool {}: {}", record.name(), e)))?;
+        self.written += size;
+        registry().set_gauge(SPOOL_BYTES, &[], self.bytes() as i64);
+        registry().inc_counter(SPOOL_RECORDS_TOTAL, &[("request", record.name()), ("outcome", "spooled")], 1);
+        Ok(())
+    }
+
+    /// The next `replay_batch` events to replay, the file is truncated once they were all read
+    pub fn next_batch(&mut self) -> Result<Vec<SpoolRecord>, GeyserPluginError> {
+        let io_err = |e: std::io::Error| spool_err(format!("Failed to read the spool: {}", e));
+        self.writer.flush().map_err(io_err)?;
+        let mut records = Vec::with_capacity(self.replay_batch);
+        while records.len() < self.replay_batch && !self.is_empty() {
+            match bincode::deserialize_from::<_, SpoolRecord>(&mut self.reader) {
+                Ok(record) => {
+                    registry().inc_counter(SPOOL_RECORDS_TOTAL, &[("request", record.name()), ("outcome", "replayed")], 1);
+                    records.push(record);
+                    self.replayed = self.reader.stream_position().map_err(io_err)?;
+                }
+                // the last event of a run that stopped while writing it
+                Err(e) => {
+                    warn!("[spool] dropping {} bytes after an invalid record error=[{}]", self.written - self.replayed, e);
+                    self.replayed = self.written;
+                }
+            }
+        }
+        if self.is_empty() {
+            self.writer.get_ref().set_len(0).map_err(io_err)?;
+            self.reader.seek(SeekFrom::Start(0)).map_err(io_err)?;
+            self.written = 0;
+            self.replayed = 0;
+        }
+        registry().set_gauge(SPOOL_BYTES, &[], self.bytes() as i64);
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(slot: u64) -> SpoolRecord {
+        SpoolRecord::Slot {
+            slot,
+            parent: slot.checked_sub(1),
+            status: RecordedSlotStatus::Rooted,
+        }
+    }
+
+    fn slots(records: Vec<SpoolRecord>) -> Vec<u64> {
+        records
+            .into_iter()
+            .map(|record| match record {
+                SpoolRecord::Slot { slot, .. } => slot,
+                _ => panic!("unexpected record"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_spool() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SpoolConfig {
+            path: dir.path().to_str().unwrap().to_string(),
+            max_bytes: 1024,
+            replay_batch: 2,
+        };
+        let mut spool = Spool::open(&config).unwrap();
+        assert!(spool.is_empty());
+        for s in 1..=3 {
+            spool.append(&slot(s)).unwrap();
+        }
+        assert_eq!(slots(spool.next_batch().unwrap()), vec![1, 2]);
+        // the events appended while replaying follow the others
+        spool.append(&slot(4)).unwrap();
+        assert_eq!(slots(spool.next_batch().unwrap()), vec![3, 4]);
+        assert!(spool.is_empty());
+        assert_eq!(std::fs::metadata(dir.path().join(SPOOL_FILE)).unwrap().len(), 0);
+
+        // the events of a previous run are replayed, but the truncated last one
+        spool.append(&slot(5)).unwrap();
+        spool.append(&slot(6)).unwrap();
+        drop(spool);
+        let file = OpenOptions::new().write(true).open(dir.path().join(SPOOL_FILE)).unwrap();
+        file.set_len(file.metadata().unwrap().len() - 1).unwrap();
+        let mut spool = Spool::open(&config).unwrap();
+        assert_eq!(slots(spool.next_batch().unwrap()), vec![5]);
+        assert!(spool.is_empty());
+
+        // past max_bytes the events are dropped
+        for s in 0..100 {
+            spool.append(&slot(s)).unwrap();
+        }
+        let mut replayed = Vec::new();
+        while !spool.is_empty() {
+            replayed.extend(slots(spool.next_batch().unwrap()));
+        }
+        assert!(!replayed.is_empty() && replayed.len() < 100);
+        assert_eq!(replayed, (0..replayed.len() as u64).collect::<Vec<_>>());
+    }
+}
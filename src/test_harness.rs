@@ -0,0 +1,300 @@
+//! Utilities for testing account handlers without a running database.
+//!
+//! Fixture accounts are fed through a handler and the generated statements are parsed back
+//! into rows, so assertions can be made on column values the same way the integration tests
+//! query the database:
+//!
+//! ```ignore
+//! let harness = HandlerHarness::new(TokenAccountHandler::default(), GeyserPluginPostgresConfig::default());
+//! let output = harness.account(&AccountFixture::new(address, TOKEN_PROGRAM_ID, data));
+//! output.assert_row("spl_token_account", &[("pubkey", Some(address.to_string().as_str())), ("mint", Some(mint.to_string().as_str()))]);
+//! ```
+use crate::accounts_selector::AccountsSelectorConfig;
+use crate::config::GeyserPluginPostgresConfig;
+use crate::postgres_client::accounts::account_handler::account_update_query;
+use crate::postgres_client::accounts::account_handler::all_account_handlers;
+use crate::postgres_client::bound_rows::BoundRow;
+use crate::postgres_client::AccountHandler;
+use crate::postgres_client::AccountHandlerId;
+use crate::postgres_client::AccountKey;
+use crate::postgres_client::DbAccountInfo;
+use crate::postgres_client::IdlRegistry;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// An account as delivered by the validator
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccountFixture {
+    pub pubkey: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub slot: u64,
+    pub write_version: u64,
+}
+
+impl AccountFixture {
+    pub fn new(pubkey: Pubkey, owner: Pubkey, data: Vec<u8>) -> Self {
+        Self {
+            pubkey,
+            owner,
+            lamports: 1,
+            data,
+            slot: 1,
+            write_version: 1,
+        }
+    }
+
+    pub fn slot(mut self, slot: u64) -> Self {
+        self.slot = slot;
+        self
+    }
+
+    pub fn lamports(mut self, lamports: u64) -> Self {
+        self.lamports = lamports;
+        self
+    }
+
+    pub fn to_db_account(&self) -> DbAccountInfo {
+        DbAccountInfo {
+            pubkey: AccountKey::from_slice(self.pubkey.as_ref()),
+            lamports: self.lamports as i64,
+            owner: AccountKey::from_slice(self.owner.as_ref()),
+            executable: false,
+            rent_epoch: 0,
+            data: self.data.clone(),
+            slot: self.slot as i64,
+            write_version: self.write_version as i64,
+            txn_signature: None,
+        }
+    }
+}
+
+/// A row inserted by a statement, `None` values are SQL NULLs
+pub type Row = HashMap<String, Option<String>>;
+
+/// The statements a handler generated for an account
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HarnessOutput {
+    pub matched: bool,
+    pub statements: Vec<String>,
+}
+
+impl HarnessOutput {
+    /// Rows inserted into `table`, in statement order
+    pub fn rows(&self, table: &str) -> Vec<Row> {
+        self.statements
+            .iter()
+            .filter_map(|statement| parse_insert(statement))
+            .filter(|(insert_table, _)| insert_table == table)
+            .flat_map(|(_, rows)| rows)
+            .collect()
+    }
+
+    /// Assert exactly one row was inserted into `table` and it has the expected values
+    pub fn assert_row(&self, table: &str, expected: &[(&str, Option<&str>)]) -> Row {
+        let rows = self.rows(table);
+        assert_eq!(rows.len(), 1, "Expected one row in {} but found {}, statements: {:#?}", table, rows.len(), self.statements);
+        let row = rows.into_iter().next().unwrap();
+        for (column, value) in expected {
+            match row.get(*column) {
+                Some(actual) => assert_eq!(actual.as_deref(), *value, "Incorrect {}.{}", table, column),
+                None => panic!("Column {} not inserted into {}, row: {:?}", column, table, row),
+            }
+        }
+        row
+    }
+}
+
+/// Feeds fixtures through a handler
+pub struct HandlerHarness<H: AccountHandler> {
+    pub handler: H,
+    pub config: GeyserPluginPostgresConfig,
+}
+
+impl<H: AccountHandler> HandlerHarness<H> {
+    pub fn new(handler: H, config: GeyserPluginPostgresConfig) -> Self {
+        Self { handler, config }
+    }
+
+    /// The handler's schema statements
+    pub fn init(&self) -> Vec<String> {
+        split_statements(&self.handler.init(&self.config))
+    }
+
+    pub fn account(&self, fixture: &AccountFixture) -> HarnessOutput {
+        let account = fixture.to_db_account();
+        HarnessOutput {
+            matched: self.handler.account_match(&account),
+            statements: split_statements(&self.handler.account_update(&account)),
+        }
+    }
+
+    /// The rows the handler binds to its `prepared_statements` for the fixture
+    pub fn account_rows(&self, fixture: &AccountFixture) -> Vec<BoundRow> {
+        self.handler.account_rows(&fixture.to_db_account())
+    }
+}
+
+/// Feeds accounts through the built-in handlers the config's `accounts_selector` picks for them,
+/// the same way the workers do, without connecting to a database
+pub struct SelectedHandlers {
+    selector: Option<AccountsSelectorConfig>,
+    handlers: HashMap<AccountHandlerId, Box<dyn AccountHandler>>,
+}
+
+impl SelectedHandlers {
+    /// IDLs are not loaded, the `idl` and `diff` handlers decode nothing
+    pub fn new(config: &GeyserPluginPostgresConfig) -> Self {
+        Self {
+            selector: config.accounts_selector.clone(),
+            handlers: all_account_handlers(config, Arc::new(IdlRegistry::default())),
+        }
+    }
+
+    /// The statements written for the account, joined as they are sent to the database
+    pub fn account_update(&self, account: &DbAccountInfo, is_startup: bool) -> String {
+        account_update_query(&self.selector, &self.handlers, account, is_startup, &[])
+    }
+}
+
+/// Split a query on the semicolons outside of string literals
+pub fn split_statements(query: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut in_literal = false;
+    let mut start = 0;
+    for (i, c) in query.char_indices() {
+        match c {
+            '\'' => in_literal = !in_literal,
+            ';' if !in_literal => {
+                statements.push(query[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(query[start..].trim().to_string());
+    statements.retain(|statement| !statement.is_empty());
+    statements
+}
+
+/// Split on the commas outside of literals, parentheses and brackets
+fn split_top_level(list: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let (mut in_literal, mut depth, mut start) = (false, 0, 0);
+    for (i, c) in list.char_indices() {
+        match c {
+            '\'' => in_literal = !in_literal,
+            '(' | '[' if !in_literal => depth += 1,
+            ')' | ']' if !in_literal => depth -= 1,
+            ',' if !in_literal && depth == 0 => {
+                items.push(list[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(list[start..].trim());
+    items
+}
+
+/// The index of the parenthesis closing the one at `open`
+fn closing_paren(text: &str, open: usize) -> Option<usize> {
+    let (mut in_literal, mut depth) = (false, 0);
+    for (i, c) in text[open..].char_indices() {
+        match c {
+            '\'' => in_literal = !in_literal,
+            '(' if !in_literal => depth += 1,
+            ')' if !in_literal => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Unquote string literals, dropping any cast, and map NULL to `None`
+fn sql_value(expression: &str) -> Option<String> {
+    if expression.eq_ignore_ascii_case("NULL") {
+        return None;
+    }
+    if let Some(rest) = expression.strip_prefix('\'') {
+        let mut value = String::new();
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '\'' {
+                if chars.peek() == Some(&'\'') {
+                    chars.next();
+                } else {
+                    return Some(value);
+                }
+            }
+            value.push(c);
+        }
+    }
+    Some(expression.to_string())
+}
+
+/// Parse an `INSERT INTO table [AS alias] (columns) VALUES (values), ...` statement into its table and rows
+pub fn parse_insert(statement: &str) -> Option<(String, Vec<Row>)> {
+    let rest = statement.trim_start();
+    if !rest.get(..12)?.eq_ignore_ascii_case("INSERT INTO ") {
+        return None;
+    }
+    let rest = rest[12..].trim_start();
+    let table = rest.split(|c: char| c.is_whitespace() || c == '(').next()?.to_string();
+    let columns_start = rest.find('(')?;
+    let columns_end = closing_paren(rest, columns_start)?;
+    let columns = split_top_level(&rest[columns_start + 1..columns_end]);
+
+    let mut rest = rest[columns_end + 1..].trim_start();
+    if !rest.get(..6)?.eq_ignore_ascii_case("VALUES") {
+        return None;
+    }
+    rest = rest[6..].trim_start();
+    let mut rows = Vec::new();
+    while rest.starts_with('(') {
+        let values_end = closing_paren(rest, 0)?;
+        let values = split_top_level(&rest[1..values_end]);
+        if values.len() != columns.len() {
+            return None;
+        }
+        rows.push(columns.iter().zip(values).map(|(column, value)| (column.to_string(), sql_value(value))).collect());
+        rest = rest[values_end + 1..].trim_start();
+        match rest.strip_prefix(',') {
+            Some(next) => rest = next.trim_start(),
+            None => break,
+        }
+    }
+    Some((table, rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_insert() {
+        let statements = split_statements(
+            "INSERT INTO t AS acc (id, name, tags, parent) VALUES ('a;b', 'it''s', '{x,y}', NULL), ('c', concat('d', 'e'), '{}'::VARCHAR[], 1) ON CONFLICT (id) DO NOTHING;
+            DELETE FROM t WHERE id = 'z';",
+        );
+        assert_eq!(statements.len(), 2);
+        let (table, rows) = parse_insert(&statements[0]).unwrap();
+        assert_eq!(table, "t");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["id"].as_deref(), Some("a;b"));
+        assert_eq!(rows[0]["name"].as_deref(), Some("it's"));
+        assert_eq!(rows[0]["tags"].as_deref(), Some("{x,y}"));
+        assert_eq!(rows[0]["parent"], None);
+        assert_eq!(rows[1]["name"].as_deref(), Some("concat('d', 'e')"));
+        assert_eq!(rows[1]["tags"].as_deref(), Some("{}"));
+        assert_eq!(rows[1]["parent"].as_deref(), Some("1"));
+        assert!(parse_insert(&statements[1]).is_none());
+    }
+}
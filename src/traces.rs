@@ -0,0 +1,99 @@
+//! Tracing spans of the geyser callbacks and of the worker requests they queue.
+//!
+//! The plugin is loaded as a separate library with its own copy of `tracing`, so the
+//! spans are only collected by the subscriber installed here when the `tracing`
+//! section is configured. One in `sample_rate` callbacks gets a span, the worker and
+//! flush spans are children of it and are skipped for unsampled callbacks.
+use log::*;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+/// Trace one in this many callbacks, 0 when tracing is not configured
+static SAMPLE_RATE: AtomicU64 = AtomicU64::new(0);
+static CALLBACKS: AtomicU64 = AtomicU64::new(0);
+
+/// * The `tracing` section logs the duration of sampled callbacks and of the worker requests they queue.
+/// "tracing" : { "sample_rate" : 1000 }
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TracingConfig {
+    /// Trace one in this many callbacks
+    pub sample_rate: u64,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self { sample_rate: 100 }
+    }
+}
+
+/// Install the subscriber logging every closed span with its busy and idle time
+pub fn init(config: &Option<TracingConfig>) {
+    let config = match config {
+        Some(config) => config,
+        None => return,
+    };
+    let result = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_thread_names(true)
+        .with_ansi(false)
+        .try_init();
+    if let Err(err) = result {
+        warn!("[tracing] failed to install the subscriber {}", err);
+        return;
+    }
+    SAMPLE_RATE.store(config.sample_rate.max(1), Ordering::Relaxed);
+}
+
+pub fn tracing_enabled() -> bool {
+    SAMPLE_RATE.load(Ordering::Relaxed) != 0
+}
+
+/// Whether the current callback should be traced
+pub fn sampled() -> bool {
+    let sample_rate = SAMPLE_RATE.load(Ordering::Relaxed);
+    sample_rate != 0 && CALLBACKS.fetch_add(1, Ordering::Relaxed) % sample_rate == 0
+}
+
+/// A callback span, disabled unless the callback is sampled
+macro_rules! callback_span {
+    ($($span:tt)*) => {
+        if $crate::traces::sampled() {
+            tracing::debug_span!($($span)*)
+        } else {
+            tracing::Span::none()
+        }
+    };
+}
+
+/// A span under `$parent`, disabled along with it so unsampled callbacks stay untraced
+macro_rules! child_span {
+    ($parent:expr, $($span:tt)*) => {
+        if $parent.is_disabled() {
+            tracing::Span::none()
+        } else {
+            tracing::debug_span!(parent: $parent, $($span)*)
+        }
+    };
+}
+
+pub(crate) use callback_span;
+pub(crate) use child_span;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampled() {
+        assert!(!sampled());
+        SAMPLE_RATE.store(3, Ordering::Relaxed);
+        CALLBACKS.store(0, Ordering::Relaxed);
+        assert_eq!((0..6).filter(|_| sampled()).count(), 2);
+        SAMPLE_RATE.store(0, Ordering::Relaxed);
+    }
+}
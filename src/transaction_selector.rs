@@ -1,8 +1,11 @@
+use crate::postgres_client::TransactionHandlerId;
 use log::*;
 use serde::Deserialize;
 use serde::Serialize;
 use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::str::FromStr;
 
 /// "transaction_selector" : {
 ///     "mentions" : \["pubkey-1", "pubkey-2", ..., "pubkey-n"\],
@@ -16,9 +19,71 @@ use std::collections::HashSet;
 /// "transaction_selector" : {
 ///     "mentions" : \["all_votes"\],
 /// }
+/// Like the `accounts_selector`, mentions can also map to the handlers run for the transactions mentioning them.
+/// Transactions selected by a plain list of mentions are stored by the 'transaction' handler:
+/// "transaction_selector" : {
+///     "mentions" : {
+///         "*" : \[{ handler_id: 'transaction' }\],
+///         "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA" : \[{ handler_id: 'token_transfer' }\]
+///     }
+/// }
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct TransactionSelectorConfig {
-    mentions: Vec<String>,
+    pub mentions: TransactionMentionsConfig,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TransactionMentionsConfig {
+    Addresses(Vec<String>),
+    Handlers(HashMap<String, Vec<TransactionHandlerConfig>>),
+}
+
+#[derive(Clone, Default, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TransactionHandlerConfig {
+    pub handler_id: String,
+}
+
+impl TransactionMentionsConfig {
+    pub fn keys(&self) -> Vec<&String> {
+        match self {
+            Self::Addresses(addresses) => addresses.iter().collect(),
+            Self::Handlers(handlers) => handlers.keys().collect(),
+        }
+    }
+}
+
+impl TransactionSelectorConfig {
+    /// Check that every mention is either a wildcard or a valid pubkey and every handler id is either built in or external
+    pub fn validate(&self, external_handler_ids: &[String]) -> Result<(), String> {
+        for key in self.mentions.keys() {
+            if key != "*" && key != "all" && key != "all_votes" && Pubkey::from_str(key).is_err() {
+                return Err(format!("transaction_selector.mentions entry \"{}\" is not a valid pubkey", key));
+            }
+        }
+        if let TransactionMentionsConfig::Handlers(handlers) = &self.mentions {
+            for (key, handlers) in handlers {
+                for handler in handlers {
+                    match TransactionHandlerId::from_str(&handler.handler_id) {
+                        Ok(id) if !id.is_compiled() => {
+                            return Err(format!(
+                                "transaction_selector.mentions.{} handler_id \"{}\" requires the plugin to be built with the \"{}\" feature",
+                                key,
+                                handler.handler_id,
+                                id.feature().unwrap_or_default()
+                            ));
+                        }
+                        Ok(_) => {}
+                        Err(_) if !external_handler_ids.contains(&handler.handler_id) => {
+                            return Err(format!("transaction_selector.mentions.{} has unknown handler_id \"{}\"", key, handler.handler_id));
+                        }
+                        Err(_) => {}
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Default, Debug)]
@@ -33,7 +98,8 @@ impl TransactionSelector {
     pub fn new(config: &TransactionSelectorConfig) -> Self {
         info!("[transaction_selector] config=[{:?}]", config);
 
-        let select_all_transactions = config.mentions.iter().any(|key| key == "*" || key == "all");
+        let mentions = config.mentions.keys();
+        let select_all_transactions = mentions.iter().any(|key| *key == "*" || *key == "all");
         if select_all_transactions {
             return Self {
                 mentioned_addresses: HashSet::default(),
@@ -41,7 +107,7 @@ impl TransactionSelector {
                 select_all_vote_transactions: true,
             };
         }
-        let select_all_vote_transactions = config.mentions.iter().any(|key| key == "all_votes");
+        let select_all_vote_transactions = mentions.iter().any(|key| *key == "all_votes");
         if select_all_vote_transactions {
             return Self {
                 mentioned_addresses: HashSet::default(),
@@ -50,27 +116,27 @@ impl TransactionSelector {
             };
         }
         Self {
-            mentioned_addresses: config.mentions.iter().map(|key| bs58::decode(key).into_vec().unwrap()).collect(),
+            mentioned_addresses: mentions.iter().map(|key| bs58::decode(key).into_vec().unwrap()).collect(),
             select_all_transactions: false,
             select_all_vote_transactions: false,
         }
     }
 
     /// Check if a transaction is of interest.
-    pub fn is_transaction_selected(&self, is_vote: bool, mentioned_addresses: Box<dyn Iterator<Item = &Pubkey> + '_>) -> bool {
+    pub fn is_transaction_selected<'a>(&self, is_vote: bool, mentioned_addresses: Box<dyn Iterator<Item = &'a Pubkey> + 'a>) -> bool {
+        self.select_transaction(is_vote, mentioned_addresses).is_some()
+    }
+
+    /// The mention that selected the transaction if any, empty for the wildcards
+    pub fn select_transaction<'a>(&self, is_vote: bool, mut mentioned_addresses: Box<dyn Iterator<Item = &'a Pubkey> + 'a>) -> Option<&'a [u8]> {
         if !self.is_enabled() {
-            return false;
+            return None;
         }
 
         if self.select_all_transactions || (self.select_all_vote_transactions && is_vote) {
-            return true;
-        }
-        for address in mentioned_addresses {
-            if self.mentioned_addresses.contains(address.as_ref()) {
-                return true;
-            }
+            return Some(&[]);
         }
-        false
+        mentioned_addresses.find(|address| self.mentioned_addresses.contains(address.as_ref())).map(|address| address.as_ref())
     }
 
     /// Check if any transaction is of interest at all
@@ -0,0 +1,31 @@
+/// The config file a test loads. With the `embedded-postgres` feature it is a copy connecting
+/// to a server started for the test, otherwise the file itself and the database it names.
+pub struct TestDatabase {
+    config_path: String,
+    #[cfg(feature = "embedded-postgres")]
+    _config_file: tempfile::NamedTempFile,
+    #[cfg(feature = "embedded-postgres")]
+    _database: solana_geyser_plugin_postgres::embedded_postgres::EmbeddedPostgres,
+}
+
+impl TestDatabase {
+    #[cfg(feature = "embedded-postgres")]
+    pub fn start(config_path: &str) -> Self {
+        let database = solana_geyser_plugin_postgres::embedded_postgres::EmbeddedPostgres::start().expect("Failed to start PostgreSQL");
+        let config_file = database.config_file(config_path).expect("Failed to write config");
+        Self {
+            config_path: config_file.path().to_str().unwrap().to_string(),
+            _config_file: config_file,
+            _database: database,
+        }
+    }
+
+    #[cfg(not(feature = "embedded-postgres"))]
+    pub fn start(config_path: &str) -> Self {
+        Self { config_path: config_path.to_string() }
+    }
+
+    pub fn config_path(&self) -> &str {
+        &self.config_path
+    }
+}
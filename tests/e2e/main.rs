@@ -0,0 +1,161 @@
+//! End to end tests running the built plugin in `solana-test-validator`.
+//!
+//! They are ignored by default, run them once the plugin is built with:
+//!
+//! ```text
+//! cargo build && cargo test --test e2e -- --ignored
+//! ```
+//!
+//! The token manager scenario also needs the cardinal-token-manager program, deployed
+//! from the path in `TOKEN_MANAGER_PROGRAM`, and is skipped without it.
+#[path = "../common/mod.rs"]
+mod common;
+mod token_manager;
+mod validator;
+
+use common::TestDatabase;
+use postgres::types::ToSql;
+use postgres::Client;
+use postgres::Row;
+use serial_test::serial;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::postgres_client::SimplePostgresClient;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::Transaction;
+use spl_associated_token_account::get_associated_token_address;
+use spl_associated_token_account::instruction::create_associated_token_account;
+use std::env;
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::Instant;
+use token_manager::TOKEN_MANAGER_PROGRAM_ID;
+use validator::validator_version;
+use validator::TestValidator;
+
+const CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_e2e.json");
+const INDEXING_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn connect(database: &TestDatabase) -> Client {
+    let config = GeyserPluginPostgresConfig::read_from(database.config_path()).unwrap();
+    SimplePostgresClient::connect_to_db(&config).unwrap()
+}
+
+/// Poll `query` until it returns a row, the plugin writes asynchronously
+fn wait_for_row(client: &mut Client, query: &str, params: &[&(dyn ToSql + Sync)]) -> Row {
+    let started = Instant::now();
+    loop {
+        if let Some(row) = client.query_opt(query, params).unwrap() {
+            return row;
+        }
+        assert!(started.elapsed() < INDEXING_TIMEOUT, "no row indexed for {}", query);
+        sleep(Duration::from_millis(250));
+    }
+}
+
+/// Create a mint with `payer` as its authority, and the token accounts of the `holders`
+fn create_mint(validator: &TestValidator, payer: &Keypair, holders: &[Pubkey]) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = validator.rpc_client().get_minimum_balance_for_rent_exemption(spl_token::state::Mint::LEN).unwrap();
+    let mut instructions = vec![
+        system_instruction::create_account(&payer.pubkey(), &mint.pubkey(), rent, spl_token::state::Mint::LEN as u64, &spl_token::id()),
+        spl_token::instruction::initialize_mint(&spl_token::id(), &mint.pubkey(), &payer.pubkey(), None, 0).unwrap(),
+    ];
+    for holder in holders {
+        instructions.push(create_associated_token_account(&payer.pubkey(), holder, &mint.pubkey(), &spl_token::id()));
+    }
+    validator.send(&mut Transaction::new_with_payer(&instructions, Some(&payer.pubkey())), &[payer, &mint]);
+    mint.pubkey()
+}
+
+#[test]
+#[ignore]
+fn test_validator_version() {
+    // the plugin and the validator must be built from the same solana version
+    assert_eq!(validator_version(), env!("CARGO_PKG_VERSION"), "solana-test-validator does not match the plugin's solana version");
+}
+
+#[test]
+#[ignore]
+#[serial]
+fn test_e2e_token_transfer() {
+    let database = TestDatabase::start(CONFIG);
+    let validator = TestValidator::start(database.config_path(), &[]);
+    let payer = validator.funded_keypair(10);
+    let recipient = Keypair::new();
+    let mint = create_mint(&validator, &payer, &[payer.pubkey(), recipient.pubkey()]);
+    let source = get_associated_token_address(&payer.pubkey(), &mint);
+    let destination = get_associated_token_address(&recipient.pubkey(), &mint);
+
+    let mint_to = spl_token::instruction::mint_to(&spl_token::id(), &mint, &source, &payer.pubkey(), &[], 10).unwrap();
+    let transfer = spl_token::instruction::transfer(&spl_token::id(), &source, &destination, &payer.pubkey(), &[], 3).unwrap();
+    let signature = validator.send(&mut Transaction::new_with_payer(&[mint_to, transfer], Some(&payer.pubkey())), &[&payer]);
+
+    let mut client = connect(&database);
+    for (token_account, owner) in [(source, payer.pubkey()), (destination, recipient.pubkey())] {
+        let row = wait_for_row(&mut client, "SELECT owner, mint FROM spl_token_account WHERE pubkey = $1", &[&token_account.to_string()]);
+        assert_eq!(row.get::<_, String>("owner"), owner.to_string());
+        assert_eq!(row.get::<_, String>("mint"), mint.to_string());
+    }
+
+    let row = wait_for_row(
+        &mut client,
+        "SELECT source, destination, authority, amount::TEXT FROM token_transfer WHERE signature = $1",
+        &[&signature.to_string()],
+    );
+    assert_eq!(row.get::<_, String>("source"), source.to_string());
+    assert_eq!(row.get::<_, String>("destination"), destination.to_string());
+    assert_eq!(row.get::<_, String>("authority"), payer.pubkey().to_string());
+    assert_eq!(row.get::<_, String>("amount"), "3");
+
+    wait_for_row(&mut client, "SELECT slot FROM transaction WHERE signature = $1", &[&signature.as_ref()]);
+    client.close().unwrap();
+}
+
+#[test]
+#[ignore]
+#[serial]
+fn test_e2e_token_manager() {
+    let program = match env::var("TOKEN_MANAGER_PROGRAM") {
+        Ok(program) => PathBuf::from(program),
+        Err(_) => {
+            eprintln!("TOKEN_MANAGER_PROGRAM is not set, skipping the token manager scenario");
+            return;
+        }
+    };
+    let database = TestDatabase::start(CONFIG);
+    let validator = TestValidator::start(database.config_path(), &[(TOKEN_MANAGER_PROGRAM_ID, program)]);
+    let issuer = validator.funded_keypair(10);
+    let recipient = validator.funded_keypair(1);
+    let mint = create_mint(&validator, &issuer, &[issuer.pubkey(), recipient.pubkey()]);
+    let token_manager = token_manager::token_manager_address(&mint);
+
+    let mint_to = spl_token::instruction::mint_to(&spl_token::id(), &mint, &get_associated_token_address(&issuer.pubkey(), &mint), &issuer.pubkey(), &[], 1).unwrap();
+    let instructions = [
+        mint_to,
+        token_manager::init(&mint, &issuer.pubkey(), 1),
+        create_associated_token_account(&issuer.pubkey(), &token_manager, &mint, &spl_token::id()),
+        token_manager::issue(&mint, &issuer.pubkey()),
+    ];
+    validator.send(&mut Transaction::new_with_payer(&instructions, Some(&issuer.pubkey())), &[&issuer]);
+    validator.send(
+        &mut Transaction::new_with_payer(&[token_manager::claim(&mint, &recipient.pubkey())], Some(&recipient.pubkey())),
+        &[&recipient],
+    );
+
+    let mut client = connect(&database);
+    let row = wait_for_row(
+        &mut client,
+        "SELECT issuer, mint, kind, recipient_token_account FROM token_manager WHERE id = $1 AND state = $2",
+        &[&token_manager.to_string(), &token_manager::STATE_CLAIMED],
+    );
+    assert_eq!(row.get::<_, String>("issuer"), issuer.pubkey().to_string());
+    assert_eq!(row.get::<_, String>("mint"), mint.to_string());
+    assert_eq!(row.get::<_, i16>("kind"), token_manager::KIND_UNMANAGED as i16);
+    assert_eq!(row.get::<_, String>("recipient_token_account"), get_associated_token_address(&recipient.pubkey(), &mint).to_string());
+    client.close().unwrap();
+}
@@ -0,0 +1,98 @@
+//! Instructions of the cardinal-token-manager program, built by hand so the harness does
+//! not depend on the program crate and its Anchor version.
+use borsh::BorshSerialize;
+use solana_sdk::hash::hash;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_program;
+use spl_associated_token_account::get_associated_token_address;
+
+pub static TOKEN_MANAGER_PROGRAM_ID: Pubkey = pubkey!("mgr99QFMYByTqGPWmNqunV7vBLmWWXdSrHUfV8Jf3JM");
+
+pub const KIND_UNMANAGED: u8 = 2;
+pub const INVALIDATION_TYPE_RETURN: u8 = 1;
+pub const STATE_CLAIMED: i16 = 2;
+
+#[derive(BorshSerialize)]
+struct InitIx {
+    amount: u64,
+    kind: u8,
+    invalidation_type: u8,
+    num_invalidators: u8,
+}
+
+/// The Anchor discriminator of the instruction
+fn sighash(name: &str) -> Vec<u8> {
+    hash(format!("global:{}", name).as_bytes()).to_bytes()[..8].to_vec()
+}
+
+pub fn token_manager_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"token-manager", mint.as_ref()], &TOKEN_MANAGER_PROGRAM_ID).0
+}
+
+fn mint_counter_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"mint-counter", mint.as_ref()], &TOKEN_MANAGER_PROGRAM_ID).0
+}
+
+pub fn init(mint: &Pubkey, issuer: &Pubkey, amount: u64) -> Instruction {
+    let mut data = sighash("init");
+    InitIx {
+        amount,
+        kind: KIND_UNMANAGED,
+        invalidation_type: INVALIDATION_TYPE_RETURN,
+        num_invalidators: 1,
+    }
+    .serialize(&mut data)
+    .unwrap();
+    Instruction::new_with_bytes(
+        TOKEN_MANAGER_PROGRAM_ID,
+        &data,
+        vec![
+            AccountMeta::new(token_manager_address(mint), false),
+            AccountMeta::new(mint_counter_address(mint), false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new(*issuer, true),
+            AccountMeta::new(*issuer, true),
+            AccountMeta::new_readonly(get_associated_token_address(issuer, mint), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Moves the token from the issuer to the token account of the token manager, which must exist
+pub fn issue(mint: &Pubkey, issuer: &Pubkey) -> Instruction {
+    let token_manager = token_manager_address(mint);
+    Instruction::new_with_bytes(
+        TOKEN_MANAGER_PROGRAM_ID,
+        &sighash("issue"),
+        vec![
+            AccountMeta::new(token_manager, false),
+            AccountMeta::new(get_associated_token_address(&token_manager, mint), false),
+            AccountMeta::new(*issuer, true),
+            AccountMeta::new(get_associated_token_address(issuer, mint), false),
+            AccountMeta::new(*issuer, true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Moves the token to the token account of the recipient, which must exist
+pub fn claim(mint: &Pubkey, recipient: &Pubkey) -> Instruction {
+    let token_manager = token_manager_address(mint);
+    Instruction::new_with_bytes(
+        TOKEN_MANAGER_PROGRAM_ID,
+        &sighash("claim"),
+        vec![
+            AccountMeta::new(token_manager, false),
+            AccountMeta::new(get_associated_token_address(&token_manager, mint), false),
+            AccountMeta::new(*mint, false),
+            AccountMeta::new(get_associated_token_address(recipient, mint), false),
+            AccountMeta::new(*recipient, true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
@@ -0,0 +1,143 @@
+use serde_json::Value;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signature::Signature;
+use solana_sdk::signer::Signer;
+use solana_sdk::transaction::Transaction;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::net::TcpListener;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Child;
+use std::process::Command;
+use std::process::Stdio;
+use std::thread::sleep;
+use std::time::Duration;
+use std::time::Instant;
+use tempfile::NamedTempFile;
+use tempfile::TempDir;
+
+const VALIDATOR: &str = "solana-test-validator";
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+/// The plugin library built by `cargo build`, or `GEYSER_PLUGIN_LIBPATH`
+pub fn plugin_libpath() -> PathBuf {
+    if let Ok(libpath) = env::var("GEYSER_PLUGIN_LIBPATH") {
+        return PathBuf::from(libpath);
+    }
+    let target_dir = env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| concat!(env!("CARGO_MANIFEST_DIR"), "/target").to_string());
+    Path::new(&target_dir)
+        .join("debug")
+        .join(format!("{}solana_geyser_plugin_postgres{}", env::consts::DLL_PREFIX, env::consts::DLL_SUFFIX))
+}
+
+/// The version of `solana-test-validator` on the path, e.g. `1.14.17`
+pub fn validator_version() -> String {
+    let output = Command::new(VALIDATOR)
+        .arg("--version")
+        .output()
+        .unwrap_or_else(|err| panic!("{} is not on the path: {}", VALIDATOR, err));
+    // solana-test-validator 1.14.17 (src:...; feat:...)
+    String::from_utf8_lossy(&output.stdout).split_whitespace().nth(1).unwrap_or_default().to_string()
+}
+
+/// A `solana-test-validator` loading the plugin, killed when dropped
+pub struct TestValidator {
+    process: Child,
+    ledger: TempDir,
+    rpc_url: String,
+    _plugin_config: NamedTempFile,
+}
+
+impl TestValidator {
+    /// Start the validator with the plugin loaded from `plugin_config` and the `programs` deployed at genesis,
+    /// returning once its RPC answers
+    pub fn start(plugin_config: &str, programs: &[(Pubkey, PathBuf)]) -> Self {
+        let libpath = plugin_libpath();
+        assert!(libpath.exists(), "{} not found, run `cargo build` first or set GEYSER_PLUGIN_LIBPATH", libpath.display());
+        let mut config: Value = serde_json::from_reader(File::open(plugin_config).unwrap()).unwrap();
+        config["libpath"] = Value::String(libpath.to_str().unwrap().to_string());
+        let plugin_config = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+        serde_json::to_writer(plugin_config.as_file(), &config).unwrap();
+
+        let ledger = tempfile::tempdir().unwrap();
+        let rpc_port = free_port();
+        let mut command = Command::new(VALIDATOR);
+        command
+            .arg("--ledger")
+            .arg(ledger.path())
+            .arg("--reset")
+            .arg("--quiet")
+            .args(["--rpc-port", &rpc_port.to_string()])
+            .args(["--faucet-port", &free_port().to_string()])
+            .arg("--geyser-plugin-config")
+            .arg(plugin_config.path());
+        for (program_id, path) in programs {
+            command.arg("--bpf-program").arg(program_id.to_string()).arg(path);
+        }
+        let process = command.stdout(Stdio::null()).stderr(Stdio::null()).spawn().unwrap();
+        let validator = Self {
+            process,
+            ledger,
+            rpc_url: format!("http://127.0.0.1:{}", rpc_port),
+            _plugin_config: plugin_config,
+        };
+        validator.wait_for_health();
+        validator
+    }
+
+    fn wait_for_health(&self) {
+        let started = Instant::now();
+        let rpc_client = self.rpc_client();
+        while rpc_client.get_health().is_err() {
+            assert!(started.elapsed() < STARTUP_TIMEOUT, "the validator did not start, see the log:\n{}", self.log_tail());
+            sleep(Duration::from_millis(500));
+        }
+    }
+
+    /// The end of the validator log, where a plugin that failed to load shows up
+    pub fn log_tail(&self) -> String {
+        let log = fs::read_to_string(self.ledger.path().join("validator.log")).unwrap_or_default();
+        let lines = log.lines().collect::<Vec<&str>>();
+        lines[lines.len().saturating_sub(50)..].join("\n")
+    }
+
+    pub fn rpc_client(&self) -> RpcClient {
+        RpcClient::new_with_commitment(self.rpc_url.clone(), CommitmentConfig::confirmed())
+    }
+
+    /// A new keypair holding `sol` airdropped SOL
+    pub fn funded_keypair(&self, sol: u64) -> Keypair {
+        let keypair = Keypair::new();
+        let rpc_client = self.rpc_client();
+        let signature = rpc_client.request_airdrop(&keypair.pubkey(), sol * LAMPORTS_PER_SOL).unwrap();
+        while !rpc_client.confirm_transaction(&signature).unwrap() {
+            sleep(Duration::from_millis(200));
+        }
+        keypair
+    }
+
+    pub fn send(&self, transaction: &mut Transaction, signers: &[&Keypair]) -> Signature {
+        let rpc_client = self.rpc_client();
+        transaction.sign(signers, rpc_client.get_latest_blockhash().unwrap());
+        rpc_client
+            .send_and_confirm_transaction(transaction)
+            .unwrap_or_else(|err| panic!("transaction failed: {}\n{}", err, self.log_tail()))
+    }
+}
+
+impl Drop for TestValidator {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
@@ -1,6 +1,9 @@
+mod common;
+
 use std::thread::sleep;
 use std::time::Duration;
 
+use common::TestDatabase;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
@@ -16,8 +19,9 @@ static OWNER: Pubkey = pubkey!("EmdsWm9dJ1d6BgQzHDcMJkDvB5SVvpfrAtpiGMVW1gxx");
 #[test]
 fn test_account() {
     let address: Pubkey = Keypair::new().pubkey();
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_single_threaded_unknown.json"));
     let mut geyser_plugin = GeyserPluginPostgres::default();
-    geyser_plugin.on_load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_single_threaded_unknown.json")).unwrap();
+    geyser_plugin.on_load(database.config_path()).unwrap();
 
     geyser_plugin
         .update_account(
@@ -0,0 +1,113 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::TestDatabase;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::geyser_plugin_postgres::GeyserPluginPostgres;
+use solana_geyser_plugin_postgres::postgres_client::apply_account_data_delta;
+use solana_geyser_plugin_postgres::postgres_client::AccountAuditConfig;
+use solana_geyser_plugin_postgres::postgres_client::ForkCleanupConfig;
+use solana_geyser_plugin_postgres::postgres_client::RetentionConfig;
+use solana_geyser_plugin_postgres::postgres_client::SimplePostgresClient;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+
+static OWNER: Pubkey = pubkey!("EmdsWm9dJ1d6BgQzHDcMJkDvB5SVvpfrAtpiGMVW1gxx");
+
+#[test]
+fn test_account_audit_diff() {
+    let slot = rand::random::<u32>() as u64;
+    let address = Keypair::new().pubkey();
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_account_audit.json"));
+    let mut geyser_plugin = GeyserPluginPostgres::default();
+    geyser_plugin.on_load(database.config_path()).unwrap();
+
+    // a byte changes per version, then the account grows
+    let mut versions = vec![vec![7u8; 256]];
+    for i in 1..4 {
+        let mut data = versions[i - 1].clone();
+        data[i * 64] = i as u8;
+        versions.push(data);
+    }
+    versions[3].extend_from_slice(&[1, 2, 3]);
+    for (i, data) in versions.iter().enumerate() {
+        geyser_plugin
+            .update_account(
+                ReplicaAccountInfoVersions::V0_0_2(&ReplicaAccountInfoV2 {
+                    pubkey: address.as_ref(),
+                    lamports: 1,
+                    owner: OWNER.as_ref(),
+                    executable: false,
+                    rent_epoch: 0,
+                    data,
+                    write_version: i as u64,
+                    txn_signature: None,
+                }),
+                slot + i as u64,
+                false,
+            )
+            .unwrap();
+        geyser_plugin.wait_for_empty_queue();
+    }
+    sleep(Duration::from_secs(1));
+
+    let mut client = SimplePostgresClient::connect_to_db(&geyser_plugin.config.clone().expect("No plugin config found")).expect("Failed to connect");
+    let rows = client
+        .query(
+            "SELECT data, delta, account_audit_data(id) AS reconstructed FROM account_audit WHERE pubkey = $1 ORDER BY slot",
+            &[&address.as_ref()],
+        )
+        .unwrap();
+    assert_eq!(rows.len(), versions.len());
+    // a checkpoint every two deltas
+    let is_delta: Vec<bool> = rows.iter().map(|row| row.get::<_, Option<Vec<u8>>>("data").is_none()).collect();
+    assert_eq!(is_delta, vec![false, true, true, false]);
+    let mut data = Vec::new();
+    for (row, version) in rows.iter().zip(&versions) {
+        assert_eq!(&row.get::<_, Vec<u8>>("reconstructed"), version);
+        data = match row.get::<_, Option<Vec<u8>>>("delta") {
+            Some(delta) => apply_account_data_delta(&data, &delta).unwrap(),
+            None => row.get("data"),
+        };
+        assert_eq!(&data, version);
+    }
+
+    client.close().expect("Error disconnecting");
+    geyser_plugin.on_unload();
+}
+
+#[test]
+fn test_account_audit_diff_purge() {
+    let config = GeyserPluginPostgresConfig::read_from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_account_audit.json")).unwrap();
+    assert!(config.validate().is_ok());
+    let retention = serde_json::from_value::<RetentionConfig>(serde_json::json!({ "tables": { "account_audit": { "max_age_secs": 86400 } } })).unwrap();
+    let fork_cleanup = ForkCleanupConfig {
+        purge_tables: vec!["account_audit".to_string()],
+        ..ForkCleanupConfig::default()
+    };
+    for config in [
+        GeyserPluginPostgresConfig {
+            retention: Some(retention),
+            ..config.clone()
+        },
+        GeyserPluginPostgresConfig {
+            fork_cleanup: Some(fork_cleanup),
+            ..config.clone()
+        },
+    ] {
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("the deltas of the remaining rows"), "{}", err);
+        let full = GeyserPluginPostgresConfig {
+            account_audit: AccountAuditConfig::default(),
+            ..config
+        };
+        assert!(full.validate().is_ok());
+    }
+}
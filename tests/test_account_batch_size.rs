@@ -1,6 +1,9 @@
+mod common;
+
 use std::thread::sleep;
 use std::time::Duration;
 
+use common::TestDatabase;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
@@ -28,8 +31,9 @@ fn test_account_batch_size() {
         0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
     ];
 
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_single_threaded_unknown.json"));
     let mut geyser_plugin = GeyserPluginPostgres::default();
-    geyser_plugin.on_load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_single_threaded_unknown.json")).unwrap();
+    geyser_plugin.on_load(database.config_path()).unwrap();
     geyser_plugin
         .update_account(
             ReplicaAccountInfoVersions::V0_0_2(&ReplicaAccountInfoV2 {
@@ -0,0 +1,53 @@
+use solana_geyser_plugin_postgres::accounts_selector::AccountsSelectorConfig;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::postgres_client::AccountHandlersConfig;
+use solana_geyser_plugin_postgres::postgres_client::CustomHandlers;
+use solana_geyser_plugin_postgres::postgres_client::PostgresClientBuilder;
+
+fn config_with(account_handlers: serde_json::Value, handler_id: &str) -> GeyserPluginPostgresConfig {
+    GeyserPluginPostgresConfig {
+        connection_str: "host=localhost".to_string(),
+        account_handlers: Some(serde_json::from_value::<AccountHandlersConfig>(account_handlers).unwrap()),
+        accounts_selector: Some(
+            serde_json::from_value::<AccountsSelectorConfig>(serde_json::json!({
+                "owners": { "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA": [{ "handler_id": handler_id }] }
+            }))
+            .unwrap(),
+        ),
+        ..GeyserPluginPostgresConfig::default()
+    }
+}
+
+#[test]
+fn test_account_handlers() {
+    let disabled = config_with(serde_json::json!({ "disabled": ["balance_history"] }), "token_account");
+    assert!(disabled.validate().is_ok());
+    let init_query = PostgresClientBuilder::init_query(&disabled, &CustomHandlers::default()).unwrap();
+    assert!(!init_query.contains("CREATE TABLE IF NOT EXISTS balance_history"));
+    assert!(init_query.contains("CREATE TABLE IF NOT EXISTS spl_token_account"));
+    assert!(init_query.contains("CREATE TABLE IF NOT EXISTS account "));
+
+    let enabled = config_with(serde_json::json!({ "enabled": ["token_account"] }), "token_account");
+    assert!(enabled.validate().is_ok());
+    let init_query = PostgresClientBuilder::init_query(&enabled, &CustomHandlers::default()).unwrap();
+    assert!(init_query.contains("CREATE TABLE IF NOT EXISTS spl_token_account"));
+    assert!(!init_query.contains("CREATE TABLE IF NOT EXISTS account "));
+    assert!(!init_query.contains("CREATE TABLE IF NOT EXISTS wallet_ata"));
+
+    for (account_handlers, handler_id, error) in [
+        (serde_json::json!({ "disabled": ["token_account"] }), "token_account", "which account_handlers doesn't create"),
+        (serde_json::json!({ "enabled": ["unknown_account"] }), "token_account", "which account_handlers doesn't create"),
+        (serde_json::json!({ "disabled": ["my_pool"] }), "token_account", "unknown handler_id \"my_pool\""),
+        (serde_json::json!({ "enabled": ["token_account"], "disabled": ["listing"] }), "token_account", "not both"),
+    ] {
+        let err = config_with(account_handlers, handler_id).validate().unwrap_err();
+        assert!(err.to_string().contains(error), "{}", err);
+    }
+}
+
+#[test]
+fn test_unknown_handler_id() {
+    let config = config_with(serde_json::json!({}), "my_pool");
+    let err = config.validate().unwrap_err();
+    assert!(err.to_string().contains("unknown handler_id \"my_pool\""), "{}", err);
+}
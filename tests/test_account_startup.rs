@@ -1,6 +1,9 @@
+mod common;
+
 use std::thread::sleep;
 use std::time::Duration;
 
+use common::TestDatabase;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
@@ -16,8 +19,9 @@ static OWNER: Pubkey = pubkey!("EmdsWm9dJ1d6BgQzHDcMJkDvB5SVvpfrAtpiGMVW1gxx");
 #[test]
 fn test_account_startup() {
     let address: Pubkey = Keypair::new().pubkey();
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_single_threaded_unknown.json"));
     let mut geyser_plugin = GeyserPluginPostgres::default();
-    geyser_plugin.on_load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_single_threaded_unknown.json")).unwrap();
+    geyser_plugin.on_load(database.config_path()).unwrap();
 
     geyser_plugin
         .update_account(
@@ -63,3 +67,54 @@ fn test_account_startup() {
     client.close().expect("Error disconnecting");
     geyser_plugin.on_unload();
 }
+
+#[test]
+fn test_account_startup_bulk_load() {
+    check_startup_bulk_load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_startup_bulk_load.json"));
+}
+
+#[test]
+fn test_account_startup_copy() {
+    check_startup_bulk_load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_startup_copy.json"));
+}
+
+fn check_startup_bulk_load(config_path: &str) {
+    let address: Pubkey = Keypair::new().pubkey();
+    let database = TestDatabase::start(config_path);
+    let mut geyser_plugin = GeyserPluginPostgres::default();
+    geyser_plugin.on_load(database.config_path()).unwrap();
+
+    // the snapshot holds the account at several slots, the latest entry by slot then write version wins
+    for (slot, write_version, lamports) in [(5, 1, 500), (7, 2, 700), (7, 3, 701), (6, 4, 600)] {
+        geyser_plugin
+            .update_account(
+                ReplicaAccountInfoVersions::V0_0_2(&ReplicaAccountInfoV2 {
+                    pubkey: address.as_ref(),
+                    lamports,
+                    owner: OWNER.as_ref(),
+                    executable: false,
+                    rent_epoch: 0,
+                    data: &[1, 2, 3],
+                    write_version,
+                    txn_signature: None,
+                }),
+                slot,
+                true,
+            )
+            .unwrap();
+    }
+
+    geyser_plugin.notify_end_of_startup().unwrap();
+    sleep(Duration::from_secs(1));
+
+    let mut client = SimplePostgresClient::connect_to_db(&geyser_plugin.config.clone().expect("No plugin config found")).expect("Failed to connect");
+    let rows = client
+        .query("SELECT lamports, slot, write_version from account where pubkey=$1", &[&address.as_ref()])
+        .expect("Error selecting accounts");
+    assert_eq!(rows.len(), 1, "Incorrect number of rows found");
+    assert_eq!(rows[0].get::<_, i64>("lamports"), 701);
+    assert_eq!(rows[0].get::<_, i64>("slot"), 7);
+    assert_eq!(rows[0].get::<_, i64>("write_version"), 3);
+    client.close().expect("Error disconnecting");
+    geyser_plugin.on_unload();
+}
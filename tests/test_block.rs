@@ -1,6 +1,9 @@
+mod common;
+
 use std::thread::sleep;
 use std::time::Duration;
 
+use common::TestDatabase;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaBlockInfo;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaBlockInfoVersions;
@@ -20,8 +23,9 @@ fn test_block() {
     let block_time: i64 = rand::random::<i64>();
     let block_height: u64 = rand::random::<u64>();
 
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json"));
     let mut geyser_plugin = GeyserPluginPostgres::default();
-    geyser_plugin.on_load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json")).unwrap();
+    geyser_plugin.on_load(database.config_path()).unwrap();
     geyser_plugin
         .notify_block_metadata(ReplicaBlockInfoVersions::V0_0_1(&ReplicaBlockInfo {
             slot: slot as u64,
@@ -49,6 +53,13 @@ fn test_block() {
     let check_height: Option<i64> = first_row.get("block_height");
     assert_eq!(check_height.unwrap(), block_height as i64, "Incorrect block height");
 
+    let rewards = client
+        .query("SELECT reward_index, pubkey, lamports, commission FROM block_reward WHERE slot=$1", &[&slot])
+        .expect("Error selecting block rewards");
+    assert_eq!(rewards.len(), 1, "Incorrect number of block rewards");
+    let (reward_index, pubkey, lamports, commission): (i32, String, i64, Option<i16>) = (rewards[0].get(0), rewards[0].get(1), rewards[0].get(2), rewards[0].get(3));
+    assert_eq!((reward_index, pubkey, lamports, commission), (0, address.to_string(), 10, Some(10)));
+
     client.close().expect("Error disconnecting");
     geyser_plugin.on_unload();
 }
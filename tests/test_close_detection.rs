@@ -0,0 +1,63 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::TestDatabase;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
+use solana_geyser_plugin_postgres::geyser_plugin_postgres::GeyserPluginPostgres;
+use solana_geyser_plugin_postgres::postgres_client::SimplePostgresClient;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use solana_sdk::system_program;
+
+static OWNER: Pubkey = pubkey!("EmdsWm9dJ1d6BgQzHDcMJkDvB5SVvpfrAtpiGMVW1gxx");
+
+#[test]
+fn test_close_detection() {
+    let slot = rand::random::<u32>() as u64;
+    let (closed, kept) = (Keypair::new().pubkey(), Keypair::new().pubkey());
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_close_detection.json"));
+    let mut geyser_plugin = GeyserPluginPostgres::default();
+    geyser_plugin.on_load(database.config_path()).unwrap();
+
+    // both accounts are written, then the first one is closed a slot later
+    let system_program_id = system_program::id();
+    let updates = [
+        (closed, 1, &OWNER, &[1, 2, 3][..], slot),
+        (kept, 1, &OWNER, &[1, 2, 3][..], slot),
+        (closed, 0, &system_program_id, &[][..], slot + 1),
+    ];
+    for (address, lamports, owner, data, slot) in updates {
+        geyser_plugin
+            .update_account(
+                ReplicaAccountInfoVersions::V0_0_2(&ReplicaAccountInfoV2 {
+                    pubkey: address.as_ref(),
+                    lamports,
+                    owner: owner.as_ref(),
+                    executable: false,
+                    rent_epoch: 0,
+                    data,
+                    write_version: 0,
+                    txn_signature: None,
+                }),
+                slot,
+                false,
+            )
+            .unwrap();
+        geyser_plugin.wait_for_empty_queue();
+        sleep(Duration::from_secs(1));
+    }
+
+    let mut client = SimplePostgresClient::connect_to_db(&geyser_plugin.config.clone().expect("No plugin config found")).expect("Failed to connect");
+    let accounts = |client: &mut postgres::Client, address: &Pubkey| client.query("SELECT slot FROM account WHERE pubkey = $1", &[&address.as_ref()]).unwrap().len();
+    assert_eq!(accounts(&mut client, &closed), 0, "The closed account should be deleted");
+    assert_eq!(accounts(&mut client, &kept), 1);
+
+    client.close().expect("Error disconnecting");
+    geyser_plugin.on_unload();
+}
@@ -0,0 +1,24 @@
+use solana_geyser_plugin_postgres::config::deprecated_keys;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+
+#[test]
+fn test_config_deprecated_keys() {
+    // the token index keys of earlier releases still load, and are reported rather than applied
+    let value = serde_json::json!({
+        "connection_str": "host=localhost",
+        "index_token_owner": true,
+        "index_token_mint": true,
+    });
+    let keys: Vec<&str> = deprecated_keys(&value).into_iter().map(|(key, _)| key).collect();
+    assert_eq!(keys, vec!["index_token_owner", "index_token_mint"]);
+    let config = GeyserPluginPostgresConfig::from_value(value).unwrap();
+    assert_eq!(config, GeyserPluginPostgresConfig::from_value(serde_json::json!({ "connection_str": "host=localhost" })).unwrap());
+    assert!(config.validate().is_ok());
+
+    // the integration configs still set them
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_single_threaded.json");
+    let value: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    assert_eq!(deprecated_keys(&value).len(), 2);
+    assert!(GeyserPluginPostgresConfig::read_from(path).is_ok());
+    assert!(deprecated_keys(&serde_json::json!({ "connection_str": "host=localhost" })).is_empty());
+}
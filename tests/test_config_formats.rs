@@ -0,0 +1,10 @@
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+
+#[test]
+fn test_config_formats() {
+    let json_config = GeyserPluginPostgresConfig::read_from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_single_threaded.json")).unwrap();
+    let yaml_config = GeyserPluginPostgresConfig::read_from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_single_threaded.yaml")).unwrap();
+    let toml_config = GeyserPluginPostgresConfig::read_from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_single_threaded.toml")).unwrap();
+    assert_eq!(json_config, yaml_config, "YAML config differs from JSON config");
+    assert_eq!(json_config, toml_config, "TOML config differs from JSON config");
+}
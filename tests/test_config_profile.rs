@@ -0,0 +1,24 @@
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+
+#[test]
+fn test_config_profile() {
+    let config = GeyserPluginPostgresConfig::read_from(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_profile.json")).unwrap();
+    assert_eq!(config.profile, Some("nft-only".to_string()));
+
+    // explicit values take precedence over the profile
+    assert_eq!(config.threads, 2, "Incorrect threads");
+    assert_eq!(config.batch_size, 100, "Incorrect batch_size");
+
+    // owners are merged with the profile owners
+    let owners = config.accounts_selector.expect("No accounts selector").owners.expect("No owners");
+    assert_eq!(owners.len(), 5, "Incorrect number of owners");
+    assert_eq!(owners["EmdsWm9dJ1d6BgQzHDcMJkDvB5SVvpfrAtpiGMVW1gxx"][0].handler_id, "unknown_account");
+    assert_eq!(owners["mgr99QFMYByTqGPWmNqunV7vBLmWWXdSrHUfV8Jf3JM"][0].handler_id, "token_manager");
+    assert_eq!(owners["mgr99QFMYByTqGPWmNqunV7vBLmWWXdSrHUfV8Jf3JM"][1].handler_id, "token_manager_receipt");
+}
+
+#[test]
+fn test_config_unknown_profile() {
+    let result = GeyserPluginPostgresConfig::from_value(serde_json::json!({ "profile": "nope" }));
+    assert!(result.is_err(), "Unknown profile should be rejected");
+}
@@ -0,0 +1,52 @@
+use solana_geyser_plugin_postgres::accounts_selector::AccountsSelectorConfig;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::postgres_client::ConflictStrategy;
+use solana_geyser_plugin_postgres::test_harness::AccountFixture;
+use solana_geyser_plugin_postgres::test_harness::SelectedHandlers;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+static TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+fn config_with(conflict_strategies: serde_json::Value) -> GeyserPluginPostgresConfig {
+    GeyserPluginPostgresConfig {
+        connection_str: "host=localhost".to_string(),
+        accounts_selector: Some(
+            serde_json::from_value::<AccountsSelectorConfig>(serde_json::json!({
+                "owners": { "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA": [{ "handler_id": "token_account" }] }
+            }))
+            .unwrap(),
+        ),
+        conflict_strategies: serde_json::from_value(conflict_strategies).unwrap(),
+        ..GeyserPluginPostgresConfig::default()
+    }
+}
+
+#[test]
+fn test_conflict_strategies() {
+    let fixture = AccountFixture::new(Pubkey::new_unique(), TOKEN_PROGRAM_ID, vec![0; 165]).slot(9);
+
+    let config = config_with(serde_json::json!({}));
+    assert_eq!(config.conflict_strategy("token_account"), ConflictStrategy::SlotWriteVersion);
+    assert_eq!(config.conflict_strategy("transfer_authority"), ConflictStrategy::LatestSlot);
+    let query = SelectedHandlers::new(&config).account_update(&fixture.to_db_account(), false);
+    assert!(query.contains("(acc.slot = excluded.slot AND acc.write_version < excluded.write_version)"), "{}", query);
+
+    let config = config_with(serde_json::json!({ "token_account": "always_overwrite" }));
+    assert!(config.validate().is_ok());
+    let query = SelectedHandlers::new(&config).account_update(&fixture.to_db_account(), false);
+    assert!(query.contains("slot=excluded.slot;"), "{}", query);
+    assert!(!query.contains("WHERE acc.slot"), "{}", query);
+
+    for (conflict_strategies, error) in [
+        (serde_json::json!({ "balance_history": "latest_slot" }), "doesn't upsert with a conflict strategy"),
+        (
+            serde_json::json!({ "transfer_authority": "slot_write_version" }),
+            "requires a handler whose tables record the write version",
+        ),
+    ] {
+        let err = config_with(conflict_strategies).validate().unwrap_err();
+        assert!(err.to_string().contains(error), "{}", err);
+    }
+    assert!(serde_json::from_value::<ConflictStrategy>(serde_json::json!("newest")).is_err());
+}
@@ -0,0 +1,44 @@
+use solana_geyser_plugin_postgres::accounts_selector::AccountsSelectorConfig;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::postgres_client::AccountHandler;
+use solana_geyser_plugin_postgres::postgres_client::CustomHandlers;
+use solana_geyser_plugin_postgres::postgres_client::DbAccountInfo;
+use solana_geyser_plugin_postgres::postgres_client::PostgresClientBuilder;
+use std::sync::Arc;
+
+struct PoolHandler {}
+
+impl AccountHandler for PoolHandler {
+    fn init(&self, _config: &GeyserPluginPostgresConfig) -> String {
+        "CREATE TABLE IF NOT EXISTS custom_pool (id VARCHAR(44) PRIMARY KEY);".to_string()
+    }
+
+    fn account_match(&self, _account: &DbAccountInfo) -> bool {
+        true
+    }
+
+    fn account_update(&self, _account: &DbAccountInfo) -> String {
+        "".to_string()
+    }
+}
+
+#[test]
+fn test_custom_handlers() {
+    let config = GeyserPluginPostgresConfig {
+        connection_str: "host=localhost".to_string(),
+        accounts_selector: Some(
+            serde_json::from_value::<AccountsSelectorConfig>(serde_json::json!({
+                "owners": { "stkBL96RZkjY5ine4TvPihGqW8UHJfch2cokjAPzV8i": [{ "handler_id": "custom_pool" }] }
+            }))
+            .unwrap(),
+        ),
+        ..GeyserPluginPostgresConfig::default()
+    };
+    assert!(config.validate().is_err(), "Unregistered handler ids should be rejected");
+    config.validate_with_custom_handlers(&["custom_pool".to_string()], &[]).unwrap();
+
+    let mut custom_handlers = CustomHandlers::default();
+    custom_handlers.account_handlers.insert("custom_pool".to_string(), Arc::new(PoolHandler {}));
+    let init_query = PostgresClientBuilder::init_query(&config, &custom_handlers).unwrap();
+    assert!(init_query.contains("CREATE TABLE IF NOT EXISTS custom_pool"));
+}
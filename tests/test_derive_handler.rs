@@ -0,0 +1,97 @@
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::postgres_client::table_prefix::TablePrefix;
+use solana_geyser_plugin_postgres::postgres_client::AccountHandler;
+use solana_geyser_plugin_postgres::postgres_client::AccountKey;
+use solana_geyser_plugin_postgres::postgres_client::DbAccountInfo;
+use solana_geyser_plugin_postgres::postgres_client::GeyserAccountHandler;
+use solana_program::hash::hash;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+const PROGRAM_ID: &str = "stkBL96RZkjY5ine4TvPihGqW8UHJfch2cokjAPzV8i";
+
+#[derive(BorshSerialize, BorshDeserialize, GeyserAccountHandler)]
+#[geyser(program_id = "stkBL96RZkjY5ine4TvPihGqW8UHJfch2cokjAPzV8i", table = "derived_stake_entry")]
+pub struct StakeEntry {
+    pub bump: u8,
+    pub pool: Pubkey,
+    pub amount: u64,
+    pub last_staker: Option<Pubkey>,
+    pub kind: Vec<u8>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, GeyserAccountHandler)]
+#[geyser(program_id = "stkBL96RZkjY5ine4TvPihGqW8UHJfch2cokjAPzV8i", table = "derived_stake_pool", conflict = "always_overwrite")]
+pub struct StakePool {
+    pub bump: u8,
+    pub total: u64,
+}
+
+#[test]
+fn test_derive_handler() {
+    let handler = StakeEntryHandler::default();
+    let init = handler.init(&GeyserPluginPostgresConfig::default());
+    assert!(init.contains("CREATE TABLE IF NOT EXISTS derived_stake_entry"));
+    assert!(init.contains("amount NUMERIC(20,0) NOT NULL,"));
+    assert!(init.contains("last_staker VARCHAR(44),"));
+    assert!(init.contains("kind SMALLINT[] NOT NULL,"));
+    assert!(init.contains("write_version BIGINT NOT NULL DEFAULT 0"));
+    assert_eq!(
+        handler.schema_migrations(&GeyserPluginPostgresConfig::default()),
+        vec!["ALTER TABLE derived_stake_entry ADD COLUMN IF NOT EXISTS write_version BIGINT NOT NULL DEFAULT 0;".to_string()]
+    );
+    let config = GeyserPluginPostgresConfig {
+        table_prefix: "devnet_".to_string(),
+        ..GeyserPluginPostgresConfig::default()
+    };
+    let prefixed = StakeEntryHandler { prefix: TablePrefix::new(&config) };
+    assert!(prefixed.init(&config).contains("CREATE TABLE IF NOT EXISTS devnet_derived_stake_entry"));
+
+    let pool = Pubkey::new_unique();
+    let mut data = hash(b"account:StakeEntry").to_bytes()[..8].to_vec();
+    StakeEntry {
+        bump: 1,
+        pool,
+        amount: 10,
+        last_staker: None,
+        kind: vec![2],
+    }
+    .serialize(&mut data)
+    .unwrap();
+    let pubkey = Pubkey::new_unique();
+    let account = DbAccountInfo {
+        pubkey: AccountKey::from_slice(pubkey.as_ref()),
+        lamports: 0,
+        owner: AccountKey::from_slice(Pubkey::from_str(PROGRAM_ID).unwrap().as_ref()),
+        executable: false,
+        rent_epoch: 0,
+        data,
+        slot: 3,
+        write_version: 5,
+        txn_signature: None,
+    };
+    assert!(handler.account_match(&account));
+    let query = handler.account_update(&account);
+    assert!(
+        query.contains(&format!("VALUES ('{}', 1, '{}', 10, NULL, ARRAY[2]::SMALLINT[], 3, 5)", pubkey, pool)),
+        "Unexpected query {}",
+        query
+    );
+    assert!(query.contains("write_version=excluded.write_version, slot=excluded.slot WHERE acc.slot < excluded.slot OR (acc.slot = excluded.slot AND acc.write_version < excluded.write_version);"));
+    assert!(prefixed.account_update(&account).starts_with("INSERT INTO devnet_derived_stake_entry AS acc"));
+
+    let mut other = account.clone();
+    other.owner = AccountKey::from_slice(Pubkey::new_unique().as_ref());
+    assert!(!handler.account_match(&other));
+
+    let mut data = hash(b"account:StakePool").to_bytes()[..8].to_vec();
+    StakePool { bump: 1, total: 20 }.serialize(&mut data).unwrap();
+    let query = StakePoolHandler::default().account_update(&DbAccountInfo { data, ..account });
+    assert!(
+        query.ends_with("total=excluded.total, write_version=excluded.write_version, slot=excluded.slot;"),
+        "Unexpected query {}",
+        query
+    );
+}
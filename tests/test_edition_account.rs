@@ -1,6 +1,9 @@
+mod common;
+
 use std::thread::sleep;
 use std::time::Duration;
 
+use common::TestDatabase;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
@@ -17,8 +20,9 @@ static OWNER: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
 #[test]
 fn test_edition_account() {
     let address: Pubkey = Keypair::new().pubkey();
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json"));
     let mut geyser_plugin = GeyserPluginPostgres::default();
-    geyser_plugin.on_load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json")).unwrap();
+    geyser_plugin.on_load(database.config_path()).unwrap();
 
     geyser_plugin
         .update_account(
@@ -0,0 +1,90 @@
+mod common;
+
+use common::TestDatabase;
+use serial_test::serial;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
+use solana_geyser_plugin_postgres::geyser_plugin_postgres::GeyserPluginPostgres;
+use solana_geyser_plugin_postgres::metrics::registry;
+use solana_geyser_plugin_postgres::metrics::DB_ERRORS_TOTAL;
+use solana_geyser_plugin_postgres::metrics::DB_RECONNECTS_TOTAL;
+use solana_geyser_plugin_postgres::metrics::DB_RETRIES_TOTAL;
+use solana_geyser_plugin_postgres::metrics::FAULTS_INJECTED_TOTAL;
+use solana_geyser_plugin_postgres::postgres_client::SimplePostgresClient;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use std::thread::sleep;
+use std::time::Duration;
+
+static TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+const ACCOUNTS: usize = 10;
+
+/// Send token accounts in live slots and return the number of them indexed
+fn index_token_accounts(config_path: &str) -> i64 {
+    let mut geyser_plugin = GeyserPluginPostgres::default();
+    geyser_plugin.on_load(config_path).unwrap();
+    let mint = Pubkey::new_unique();
+    for slot in 0..ACCOUNTS {
+        let mut data = vec![0; 165];
+        data[..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(Pubkey::new_unique().as_ref());
+        data[108] = 1;
+        geyser_plugin
+            .update_account(
+                ReplicaAccountInfoVersions::V0_0_2(&ReplicaAccountInfoV2 {
+                    pubkey: Pubkey::new_unique().as_ref(),
+                    lamports: 2039280,
+                    owner: TOKEN_PROGRAM_ID.as_ref(),
+                    executable: false,
+                    rent_epoch: 0,
+                    data: &data,
+                    write_version: slot as u64,
+                    txn_signature: None,
+                }),
+                slot as u64,
+                false,
+            )
+            .unwrap();
+    }
+    sleep(Duration::from_secs(2));
+
+    let mut client = SimplePostgresClient::connect_to_db(&geyser_plugin.config.clone().expect("No plugin config found")).expect("Failed to connect");
+    let indexed: i64 = client
+        .query_one("SELECT COUNT(*) FROM spl_token_account WHERE mint = $1", &[&mint.to_string()])
+        .expect("Error counting accounts")
+        .get(0);
+    client.close().expect("Error disconnecting");
+    geyser_plugin.on_unload();
+    indexed
+}
+
+#[test]
+#[serial]
+fn test_serialization_failures_are_retried() {
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_fault_injection_serialization_failure.json"));
+    let retries = registry().counter(DB_RETRIES_TOTAL, &[("operation", "flush_live_updates")]);
+    assert_eq!(index_token_accounts(database.config_path()), ACCOUNTS as i64);
+    assert!(registry().counter(FAULTS_INJECTED_TOTAL, &[("operation", "flush_live_updates"), ("fault", "serialization_failure")]) > 0);
+    assert!(registry().counter(DB_RETRIES_TOTAL, &[("operation", "flush_live_updates")]) > retries);
+}
+
+#[test]
+#[serial]
+fn test_connection_drops_are_reconnected() {
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_fault_injection_connection_drop.json"));
+    let reconnects = registry().counter(DB_RECONNECTS_TOTAL, &[("outcome", "success")]);
+    assert_eq!(index_token_accounts(database.config_path()), ACCOUNTS as i64);
+    assert!(registry().counter(FAULTS_INJECTED_TOTAL, &[("operation", "flush_live_updates"), ("fault", "connection_drop")]) > 0);
+    assert!(registry().counter(DB_RECONNECTS_TOTAL, &[("outcome", "success")]) > reconnects);
+}
+
+#[test]
+#[serial]
+fn test_constraint_violations_fail_the_batch() {
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_fault_injection_constraint_violation.json"));
+    let labels = [("operation", "flush_live_updates"), ("class", "constraint_violation"), ("kind", "structural")];
+    let errors = registry().counter(DB_ERRORS_TOTAL, &labels);
+    assert_eq!(index_token_accounts(database.config_path()), 0);
+    assert!(registry().counter(DB_ERRORS_TOTAL, &labels) >= errors + ACCOUNTS as u64);
+}
@@ -0,0 +1,72 @@
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaTransactionInfoV2;
+use solana_geyser_plugin_postgres::fixtures::read_fixtures;
+use solana_geyser_plugin_postgres::fixtures::FixtureFormat;
+use solana_geyser_plugin_postgres::fixtures::FixtureRecorder;
+use solana_geyser_plugin_postgres::fixtures::FixtureRecorderConfig;
+use solana_geyser_plugin_postgres::fixtures::RecordedAccount;
+use solana_geyser_plugin_postgres::fixtures::RecordedTransaction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::SanitizedTransaction;
+use solana_sdk::transaction::Transaction;
+use solana_transaction_status::TransactionStatusMeta;
+
+#[test]
+fn test_fixture_recorder() {
+    for format in [FixtureFormat::Json, FixtureFormat::Bincode] {
+        let dir = tempfile::tempdir().unwrap();
+        let config = FixtureRecorderConfig {
+            path: dir.path().to_str().unwrap().to_string(),
+            format,
+            max_records: Some(1),
+        };
+        let mut recorder = FixtureRecorder::new(&config).unwrap();
+
+        let (pubkey, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let account = ReplicaAccountInfoV2 {
+            pubkey: pubkey.as_ref(),
+            lamports: 10,
+            owner: owner.as_ref(),
+            executable: false,
+            rent_epoch: 0,
+            data: &[1, 2, 3],
+            write_version: 4,
+            txn_signature: None,
+        };
+        recorder.record_account(&account, 5, true).unwrap();
+        // max_records stops recording
+        recorder.record_account(&account, 6, false).unwrap();
+
+        let transaction = SanitizedTransaction::from_transaction_for_tests(Transaction::new_with_payer(&[], Some(&Pubkey::new_unique())));
+        let meta = TransactionStatusMeta {
+            fee: 5000,
+            log_messages: Some(vec!["Program log: hello".to_string()]),
+            ..TransactionStatusMeta::default()
+        };
+        recorder
+            .record_transaction(
+                &ReplicaTransactionInfoV2 {
+                    signature: transaction.signature(),
+                    is_vote: false,
+                    transaction: &transaction,
+                    transaction_status_meta: &meta,
+                    index: 2,
+                },
+                5,
+            )
+            .unwrap();
+        recorder.flush().unwrap();
+
+        let accounts: Vec<RecordedAccount> = read_fixtures(&dir.path().join(format!("accounts.{}", format.extension()))).unwrap();
+        assert_eq!(accounts, vec![RecordedAccount::new(&account, 5, true)]);
+
+        let mut transactions: Vec<RecordedTransaction> = read_fixtures(&dir.path().join(format!("transactions.{}", format.extension()))).unwrap();
+        assert_eq!(transactions.len(), 1);
+        let recorded = transactions.remove(0);
+        assert_eq!((recorded.slot, recorded.index), (5, 2));
+        let (replayed, replayed_meta) = recorded.into_replica_parts().unwrap();
+        assert_eq!(replayed.signature(), transaction.signature());
+        assert_eq!(replayed_meta.fee, 5000);
+        assert_eq!(replayed_meta.log_messages, meta.log_messages);
+    }
+}
@@ -0,0 +1,63 @@
+use solana_geyser_plugin_postgres::accounts_selector::AccountsSelectorConfig;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::postgres_client::CustomHandlers;
+use solana_geyser_plugin_postgres::postgres_client::IdlConfig;
+use solana_geyser_plugin_postgres::postgres_client::PostgresClientBuilder;
+use solana_geyser_plugin_postgres::transaction_selector::TransactionSelectorConfig;
+
+fn config_selecting(handler_id: &str) -> GeyserPluginPostgresConfig {
+    GeyserPluginPostgresConfig {
+        connection_str: "host=localhost".to_string(),
+        accounts_selector: Some(
+            serde_json::from_value::<AccountsSelectorConfig>(serde_json::json!({
+                "owners": { "mgr99QFMYByTqGPWmNqunV7vBLmWWXdSrHUfV8Jf3JM": [{ "handler_id": handler_id }] }
+            }))
+            .unwrap(),
+        ),
+        ..GeyserPluginPostgresConfig::default()
+    }
+}
+
+#[test]
+fn test_handler_features() {
+    for (handler_id, is_compiled) in [
+        ("token_manager", cfg!(feature = "cardinal")),
+        ("token_manager_receipt", cfg!(feature = "cardinal")),
+        ("transfer_authority", cfg!(feature = "cardinal")),
+        ("listing", cfg!(feature = "cardinal")),
+        ("token_metadata_creators", cfg!(feature = "metaplex")),
+        ("token_metadata", cfg!(feature = "metaplex")),
+        ("idl", cfg!(feature = "idl")),
+        ("diff", cfg!(feature = "idl")),
+        ("token_account", true),
+    ] {
+        let result = config_selecting(handler_id).validate();
+        assert_eq!(result.is_ok(), is_compiled, "handler {} validated as {:?}", handler_id, result.err());
+        if let Err(err) = result {
+            assert!(err.to_string().contains("requires the plugin to be built with"), "{}", err);
+        }
+    }
+
+    let anchor_events = GeyserPluginPostgresConfig {
+        connection_str: "host=localhost".to_string(),
+        transaction_selector: Some(
+            serde_json::from_value::<TransactionSelectorConfig>(serde_json::json!({
+                "mentions": { "*": [{ "handler_id": "anchor_event" }] }
+            }))
+            .unwrap(),
+        ),
+        ..GeyserPluginPostgresConfig::default()
+    };
+    assert_eq!(anchor_events.validate().is_ok(), cfg!(feature = "idl"));
+    let idl = GeyserPluginPostgresConfig {
+        connection_str: "host=localhost".to_string(),
+        idl: Some(IdlConfig::default()),
+        ..GeyserPluginPostgresConfig::default()
+    };
+    assert_eq!(idl.validate().is_ok(), cfg!(feature = "idl"));
+
+    // the tables of the left out handlers are not created
+    let init_query = PostgresClientBuilder::init_query(&config_selecting("token_account"), &CustomHandlers::default()).unwrap();
+    assert_eq!(init_query.contains("CREATE TABLE IF NOT EXISTS token_manager "), cfg!(feature = "cardinal"));
+    assert!(init_query.contains("CREATE TABLE IF NOT EXISTS spl_token_account"));
+}
@@ -0,0 +1,95 @@
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::postgres_client::GeyserAccountHandler;
+use solana_geyser_plugin_postgres::test_harness::split_statements;
+use solana_geyser_plugin_postgres::test_harness::AccountFixture;
+use solana_geyser_plugin_postgres::test_harness::HandlerHarness;
+use solana_geyser_plugin_postgres::test_harness::HarnessOutput;
+use solana_geyser_plugin_postgres::test_harness::SelectedHandlers;
+use solana_program::hash::hash;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+static PROGRAM_ID: Pubkey = pubkey!("stkBL96RZkjY5ine4TvPihGqW8UHJfch2cokjAPzV8i");
+
+#[derive(BorshSerialize, BorshDeserialize, GeyserAccountHandler)]
+#[geyser(program_id = "stkBL96RZkjY5ine4TvPihGqW8UHJfch2cokjAPzV8i", table = "harness_stake_entry")]
+pub struct StakeEntry {
+    pub pool: Pubkey,
+    pub amount: u64,
+    pub last_staker: Option<Pubkey>,
+}
+
+#[test]
+fn test_handler_harness() {
+    let harness = HandlerHarness::new(StakeEntryHandler::default(), GeyserPluginPostgresConfig::default());
+    assert_eq!(harness.init().len(), 1);
+
+    let pool = Pubkey::new_unique();
+    let mut data = hash(b"account:StakeEntry").to_bytes()[..8].to_vec();
+    StakeEntry { pool, amount: 10, last_staker: None }.serialize(&mut data).unwrap();
+    let address = Pubkey::new_unique();
+    let output = harness.account(&AccountFixture::new(address, PROGRAM_ID, data).slot(7));
+    assert!(output.matched);
+    output.assert_row(
+        "harness_stake_entry",
+        &[
+            ("id", Some(address.to_string().as_str())),
+            ("pool", Some(pool.to_string().as_str())),
+            ("amount", Some("10")),
+            ("last_staker", None),
+            ("slot", Some("7")),
+        ],
+    );
+
+    let output = harness.account(&AccountFixture::new(address, Pubkey::new_unique(), vec![0; 8]));
+    assert!(!output.matched);
+    assert!(output.rows("harness_stake_entry").is_empty());
+}
+
+#[test]
+fn test_selected_handlers() {
+    let config: GeyserPluginPostgresConfig = serde_json::from_value(serde_json::json!({
+        "connection_str": "",
+        "accounts_selector": { "owners": { "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA": [{ "handler_id": "token_account" }] } }
+    }))
+    .unwrap();
+    let handlers = SelectedHandlers::new(&config);
+
+    let (mint, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut data = vec![0; 165];
+    data[..32].copy_from_slice(mint.as_ref());
+    data[32..64].copy_from_slice(owner.as_ref());
+    let address = Pubkey::new_unique();
+    let account = AccountFixture::new(address, spl_token::id(), data).slot(3).to_db_account();
+    let output = HarnessOutput {
+        matched: true,
+        statements: split_statements(&handlers.account_update(&account, false)),
+    };
+    output.assert_row(
+        "spl_token_account",
+        &[
+            ("pubkey", Some(address.to_string().as_str())),
+            ("owner", Some(owner.to_string().as_str())),
+            ("mint", Some(mint.to_string().as_str())),
+            ("slot", Some("3")),
+        ],
+    );
+
+    let unselected = AccountFixture::new(address, Pubkey::new_unique(), vec![0; 165]).to_db_account();
+    assert_eq!(handlers.account_update(&unselected, false), "");
+}
+
+#[test]
+fn test_selected_unknown_handler() {
+    // validation rejects the id, a selected id without a handler is skipped rather than failing the write
+    let config: GeyserPluginPostgresConfig = serde_json::from_value(serde_json::json!({
+        "connection_str": "",
+        "accounts_selector": { "owners": { "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA": [{ "handler_id": "my_pool" }] } }
+    }))
+    .unwrap();
+    assert!(config.validate().is_err());
+    let account = AccountFixture::new(Pubkey::new_unique(), spl_token::id(), vec![0; 165]).slot(3).to_db_account();
+    assert_eq!(SelectedHandlers::new(&config).account_update(&account, false), "");
+}
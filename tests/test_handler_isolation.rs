@@ -0,0 +1,63 @@
+mod common;
+
+use common::TestDatabase;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::metrics::registry;
+use solana_geyser_plugin_postgres::metrics::HANDLER_ERRORS_TOTAL;
+use solana_geyser_plugin_postgres::postgres_client::AccountHandler;
+use solana_geyser_plugin_postgres::postgres_client::CustomHandlers;
+use solana_geyser_plugin_postgres::postgres_client::DbAccountInfo;
+use solana_geyser_plugin_postgres::postgres_client::PostgresClient;
+use solana_geyser_plugin_postgres::postgres_client::PostgresClientBuilder;
+use solana_geyser_plugin_postgres::postgres_client::SimplePostgresClient;
+use solana_geyser_plugin_postgres::test_harness::AccountFixture;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use std::sync::Arc;
+
+static TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+/// Writes to a table that doesn't exist
+struct BrokenHandler {}
+
+impl AccountHandler for BrokenHandler {
+    fn init(&self, _config: &GeyserPluginPostgresConfig) -> String {
+        "".to_string()
+    }
+
+    fn account_match(&self, _account: &DbAccountInfo) -> bool {
+        true
+    }
+
+    fn account_update(&self, _account: &DbAccountInfo) -> String {
+        "INSERT INTO broken_handler_missing_table (id) VALUES (1);".to_string()
+    }
+}
+
+#[test]
+fn test_failing_handler_is_isolated() {
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_handler_isolation.json"));
+    let config = GeyserPluginPostgresConfig::read_from(database.config_path()).unwrap();
+    let mut custom_handlers = CustomHandlers::default();
+    custom_handlers.account_handlers.insert("broken".to_string(), Arc::new(BrokenHandler {}));
+    let mut db = SimplePostgresClient::connect_to_db(&config).expect("Failed to connect");
+    db.batch_execute(&PostgresClientBuilder::init_query(&config, &custom_handlers).unwrap()).unwrap();
+
+    let errors = registry().counter(HANDLER_ERRORS_TOTAL, &[("handler", "broken")]);
+    let mut client = SimplePostgresClient::new(&config, Arc::default(), &custom_handlers).unwrap();
+    let mint = Pubkey::new_unique();
+    let mut data = vec![0; 165];
+    data[..32].copy_from_slice(mint.as_ref());
+    data[108] = 1;
+    let account = AccountFixture::new(Pubkey::new_unique(), TOKEN_PROGRAM_ID, data).slot(10);
+    client.update_account(account.to_db_account(), false).unwrap();
+
+    let indexed: i64 = db.query_one("SELECT COUNT(*) FROM spl_token_account WHERE mint = $1", &[&mint.to_string()]).unwrap().get(0);
+    assert_eq!(indexed, 1, "the token account should be written despite the other handler failing");
+    assert_eq!(registry().counter(HANDLER_ERRORS_TOTAL, &[("handler", "broken")]), errors + 1);
+    let row = db
+        .query_one("SELECT sqlstate, statements FROM dead_letter WHERE handler = 'broken' ORDER BY id DESC LIMIT 1", &[])
+        .unwrap();
+    assert_eq!(row.get::<_, Option<String>>(0).as_deref(), Some("42P01"));
+    assert!(row.get::<_, String>(1).contains("broken_handler_missing_table"));
+}
@@ -0,0 +1,71 @@
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::postgres_client::IdlRegistry;
+use solana_sdk::pubkey::Pubkey;
+use std::io::Write;
+use std::str::FromStr;
+
+const PROGRAM_ID: &str = "stkBL96RZkjY5ine4TvPihGqW8UHJfch2cokjAPzV8i";
+
+#[test]
+fn test_idl_local_file() {
+    let config = GeyserPluginPostgresConfig::from_value(serde_json::json!({
+        "connection_str": "host=localhost user=postgres password=postgres port=5432",
+        "idl": {
+            "programs": {
+                PROGRAM_ID: { "path": concat!(env!("CARGO_MANIFEST_DIR"), "/tests/idl/stake_pool.json") }
+            }
+        }
+    }))
+    .unwrap();
+    config.validate().unwrap();
+
+    let registry = IdlRegistry::load(&config).unwrap();
+    let program = registry.get(Pubkey::from_str(PROGRAM_ID).unwrap().as_ref()).expect("IDL not loaded");
+    assert_eq!(program.table, format!("idl_{}", PROGRAM_ID.to_lowercase()));
+    assert_eq!(program.decoder.idl.name, "stake_pool");
+}
+
+#[test]
+fn test_idl_missing_local_file() {
+    let config = GeyserPluginPostgresConfig::from_value(serde_json::json!({
+        "connection_str": "host=localhost user=postgres password=postgres port=5432",
+        "idl": { "programs": { PROGRAM_ID: { "path": "/nonexistent/idl.json" } } }
+    }))
+    .unwrap();
+    assert!(config.validate().is_err(), "Missing IDL file should be rejected");
+    assert!(IdlRegistry::load(&config).is_err(), "Missing IDL file should fail to load");
+}
+
+fn idl_account_data(idl: &serde_json::Value) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(idl.to_string().as_bytes()).unwrap();
+    let compressed = encoder.finish().unwrap();
+    let mut data = vec![0u8; 40];
+    data.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+    data.extend_from_slice(&compressed);
+    data
+}
+
+#[test]
+fn test_idl_reload() {
+    let config = GeyserPluginPostgresConfig::from_value(serde_json::json!({
+        "connection_str": "host=localhost user=postgres password=postgres port=5432",
+        "idl": { "rpc_url": "http://127.0.0.1:1", "programs": { PROGRAM_ID: {} } }
+    }))
+    .unwrap();
+    // the IDL can't be fetched but the program stays registered
+    let registry = IdlRegistry::load(&config).unwrap();
+    let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+    assert!(registry.get(program_id.as_ref()).expect("Program not registered").decoder.idl.accounts.is_empty());
+
+    let data = idl_account_data(&serde_json::json!({ "name": "stake_pool_v2", "accounts": [] }));
+    assert!(registry.reload(program_id.as_ref(), &data, 10).unwrap());
+    assert_eq!(registry.get(program_id.as_ref()).unwrap().decoder.idl.name, "stake_pool_v2");
+
+    // older IDL accounts don't replace a newer decoder
+    let data = idl_account_data(&serde_json::json!({ "name": "stake_pool_v1", "accounts": [] }));
+    assert!(!registry.reload(program_id.as_ref(), &data, 5).unwrap());
+    assert_eq!(registry.get(program_id.as_ref()).unwrap().decoder.idl.name, "stake_pool_v2");
+}
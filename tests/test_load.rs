@@ -1,8 +1,12 @@
+mod common;
+
+use common::TestDatabase;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
 use solana_geyser_plugin_postgres::geyser_plugin_postgres::GeyserPluginPostgres;
 
 #[test]
 fn test_load() {
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json"));
     let mut geyser_plugin = GeyserPluginPostgres::default();
-    geyser_plugin.on_load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json")).unwrap();
+    geyser_plugin.on_load(database.config_path()).unwrap();
 }
@@ -1,6 +1,9 @@
+mod common;
+
 use std::thread::sleep;
 use std::time::Duration;
 
+use common::TestDatabase;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
@@ -21,8 +24,9 @@ static THIRD_CREATOR_VERIFIED: bool = false;
 
 #[test]
 fn test_metadata_account() {
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json"));
     let mut geyser_plugin = GeyserPluginPostgres::default();
-    geyser_plugin.on_load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json")).unwrap();
+    geyser_plugin.on_load(database.config_path()).unwrap();
 
     geyser_plugin
         .update_account(
@@ -98,6 +102,27 @@ fn test_metadata_account() {
     let creator: String = rows[2].get("creator");
     assert_eq!(creator, THIRD_CREATOR_ADRESS.to_string(), "Incorrect creator pubkey");
 
+    // the whole metadata
+    let rows = client
+        .query(
+            "SELECT pubkey, name, symbol, seller_fee_basis_points, primary_sale_happened, is_mutable, edition_nonce, token_standard, collection, use_method \
+            FROM token_metadata WHERE mint=$1",
+            &[&MINT.to_string()],
+        )
+        .expect("Error selecting metadata");
+    assert_eq!(rows.len(), 1, "Incorrect number of rows found");
+    let row = rows.first().expect("No results found");
+    assert_eq!(row.get::<_, String>("pubkey"), METADATA_ADDRESS.to_string());
+    assert_eq!(row.get::<_, String>("name"), "Miniverse Hero #3597");
+    assert_eq!(row.get::<_, String>("symbol"), "MINIROYALE");
+    assert_eq!(row.get::<_, i32>("seller_fee_basis_points"), 500);
+    assert!(row.get::<_, bool>("primary_sale_happened"));
+    assert!(row.get::<_, bool>("is_mutable"));
+    assert_eq!(row.get::<_, Option<i16>>("edition_nonce"), Some(255));
+    assert_eq!(row.get::<_, Option<String>>("token_standard"), Some("non_fungible".to_string()));
+    assert_eq!(row.get::<_, Option<String>>("collection"), None);
+    assert_eq!(row.get::<_, Option<String>>("use_method"), None);
+
     client.close().expect("Error disconnecting");
     geyser_plugin.on_unload();
 }
@@ -0,0 +1,77 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::TestDatabase;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
+use solana_geyser_plugin_postgres::geyser_plugin_postgres::GeyserPluginPostgres;
+use solana_geyser_plugin_postgres::postgres_client::SimplePostgresClient;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+
+static OWNER: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+/// An initialized mint with a mint authority and no freeze authority
+fn mint_data(authority: &Pubkey, supply: u64, decimals: u8) -> Vec<u8> {
+    let mut data = vec![0; 82];
+    data[..4].copy_from_slice(&1u32.to_le_bytes());
+    data[4..36].copy_from_slice(authority.as_ref());
+    data[36..44].copy_from_slice(&supply.to_le_bytes());
+    data[44] = decimals;
+    data[45] = 1;
+    data
+}
+
+#[test]
+fn test_mint_account() {
+    let (address, authority) = (Keypair::new().pubkey(), Keypair::new().pubkey());
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_mint_account.json"));
+    let mut geyser_plugin = GeyserPluginPostgres::default();
+    geyser_plugin.on_load(database.config_path()).unwrap();
+
+    // the update of an earlier slot arrives last and is ignored
+    for (slot, supply) in [(2, u64::MAX), (1, 1_000)] {
+        geyser_plugin
+            .update_account(
+                ReplicaAccountInfoVersions::V0_0_2(&ReplicaAccountInfoV2 {
+                    pubkey: address.as_ref(),
+                    lamports: 1461600,
+                    owner: OWNER.as_ref(),
+                    executable: false,
+                    rent_epoch: 0,
+                    data: &mint_data(&authority, supply, 9),
+                    write_version: slot,
+                    txn_signature: None,
+                }),
+                slot,
+                false,
+            )
+            .unwrap();
+    }
+
+    sleep(Duration::from_secs(1));
+
+    let mut client = SimplePostgresClient::connect_to_db(&geyser_plugin.config.clone().expect("No plugin config found")).expect("Failed to connect");
+    let rows = client
+        .query(
+            "SELECT token_program, mint_authority, supply::TEXT AS supply, decimals, freeze_authority, slot FROM spl_token_mint WHERE pubkey=$1",
+            &[&address.to_string()],
+        )
+        .expect("Error selecting mints");
+    assert_eq!(rows.len(), 1, "Incorrect number of rows found");
+    let row = rows.first().expect("No results found");
+    assert_eq!(row.get::<_, String>("token_program"), OWNER.to_string());
+    assert_eq!(row.get::<_, Option<String>>("mint_authority"), Some(authority.to_string()));
+    assert_eq!(row.get::<_, String>("supply"), u64::MAX.to_string());
+    assert_eq!(row.get::<_, i16>("decimals"), 9);
+    assert_eq!(row.get::<_, Option<String>>("freeze_authority"), None);
+    assert_eq!(row.get::<_, i64>("slot"), 2);
+
+    client.close().expect("Error disconnecting");
+    geyser_plugin.on_unload();
+}
@@ -0,0 +1,41 @@
+use solana_geyser_plugin_postgres::postgres_client::DbSelectorStat;
+use solana_geyser_plugin_postgres::selector_stats::SelectorStats;
+use solana_geyser_plugin_postgres::selector_stats::ACCOUNTS_SELECTOR;
+use solana_sdk::pubkey::Pubkey;
+
+#[test]
+fn test_selector_stats() {
+    let owner = Pubkey::new_unique();
+    let mut stats = SelectorStats::default();
+    stats.record_selected("owners", owner.as_ref());
+    stats.record_selected("owners", owner.as_ref());
+    stats.record_selected("mentions", &[]);
+    stats.record_skipped(ACCOUNTS_SELECTOR);
+
+    let mut drained = stats.drain();
+    drained.sort_by(|a, b| a.selector.cmp(&b.selector));
+    assert_eq!(
+        drained,
+        vec![
+            DbSelectorStat {
+                selector: ACCOUNTS_SELECTOR.to_string(),
+                entry: "*".to_string(),
+                selected: 0,
+                skipped: 1,
+            },
+            DbSelectorStat {
+                selector: "mentions".to_string(),
+                entry: "*".to_string(),
+                selected: 1,
+                skipped: 0,
+            },
+            DbSelectorStat {
+                selector: "owners".to_string(),
+                entry: owner.to_string(),
+                selected: 2,
+                skipped: 0,
+            },
+        ]
+    );
+    assert!(stats.drain().is_empty(), "Counts should reset after draining");
+}
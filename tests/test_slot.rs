@@ -1,6 +1,11 @@
+mod common;
+
 use std::thread::sleep;
 use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
+use common::TestDatabase;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
 use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
 use solana_geyser_plugin_postgres::geyser_plugin_postgres::GeyserPluginPostgres;
@@ -10,8 +15,9 @@ use solana_geyser_plugin_postgres::postgres_client::SimplePostgresClient;
 fn test_slot() {
     let slot_num: u32 = rand::random::<u32>();
     let slot = slot_num as i64;
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json"));
     let mut geyser_plugin = GeyserPluginPostgres::default();
-    geyser_plugin.on_load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json")).unwrap();
+    geyser_plugin.on_load(database.config_path()).unwrap();
     geyser_plugin.update_slot_status(slot as u64, None, SlotStatus::Confirmed).unwrap();
 
     sleep(Duration::from_secs(1));
@@ -28,3 +34,28 @@ fn test_slot() {
     client.close().expect("Error disconnecting");
     geyser_plugin.on_unload();
 }
+
+#[test]
+fn test_slot_watermark() {
+    // above the slots of the other tests, and of the previous runs sharing the database
+    let base = (1u64 << 40) + SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64 * 10;
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json"));
+    let mut geyser_plugin = GeyserPluginPostgres::default();
+    geyser_plugin.on_load(database.config_path()).unwrap();
+    geyser_plugin.update_slot_status(base, Some(base - 1), SlotStatus::Rooted).unwrap();
+    geyser_plugin.update_slot_status(base + 4, Some(base + 3), SlotStatus::Rooted).unwrap();
+
+    sleep(Duration::from_secs(1));
+    let mut client = SimplePostgresClient::connect_to_db(&geyser_plugin.config.clone().expect("No plugin config found")).expect("Failed to connect");
+    let watermark: i64 = client.query_one("SELECT slot FROM slot_watermark WHERE status = 'rooted'", &[]).expect("No rooted watermark").get(0);
+    assert_eq!(watermark, (base + 4) as i64);
+    let rows = client
+        .query("SELECT last_slot FROM slot_gap WHERE status = 'rooted' AND first_slot = $1", &[&((base + 1) as i64)])
+        .expect("Error selecting gaps");
+    assert_eq!(rows.len(), 1, "Incorrect number of gaps found");
+    let last_slot: i64 = rows[0].get(0);
+    assert_eq!(last_slot, (base + 3) as i64);
+
+    client.close().expect("Error disconnecting");
+    geyser_plugin.on_unload();
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use common::TestDatabase;
+use postgres::fallible_iterator::FallibleIterator;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+use solana_geyser_plugin_postgres::config::GeyserPluginPostgresConfig;
+use solana_geyser_plugin_postgres::geyser_plugin_postgres::GeyserPluginPostgres;
+use solana_geyser_plugin_postgres::postgres_client::SimplePostgresClient;
+use std::time::Duration;
+
+#[test]
+fn test_slot_finality() {
+    let slot = rand::random::<u32>() as u64;
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_slot_finality.json"));
+    let config = GeyserPluginPostgresConfig::read_from(database.config_path()).unwrap();
+    let mut client = SimplePostgresClient::connect_to_db(&config).expect("Failed to connect");
+    client.batch_execute("LISTEN slot_finality;").expect("Failed to listen");
+
+    let mut geyser_plugin = GeyserPluginPostgres::default();
+    geyser_plugin.on_load(database.config_path()).unwrap();
+    geyser_plugin.update_slot_status(slot, None, SlotStatus::Processed).unwrap();
+    geyser_plugin.update_slot_status(slot, None, SlotStatus::Confirmed).unwrap();
+    geyser_plugin.update_slot_status(slot, None, SlotStatus::Rooted).unwrap();
+    geyser_plugin.wait_for_empty_queue();
+
+    // the config has a single worker, so the notifications arrive in order. Other tests may
+    // finalize slots concurrently, only the ones of this slot are checked
+    let payloads = client
+        .notifications()
+        .timeout_iter(Duration::from_secs(5))
+        .iterator()
+        .filter_map(|notification| notification.ok())
+        .map(|notification| serde_json::from_str::<serde_json::Value>(notification.payload()).unwrap())
+        .filter(|payload| payload["slot"] == slot)
+        .take(2)
+        .map(|payload| payload["status"].as_str().unwrap().to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(payloads, vec!["confirmed", "rooted"]);
+
+    client.close().expect("Error disconnecting");
+    geyser_plugin.on_unload();
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::TestDatabase;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+use solana_geyser_plugin_postgres::geyser_plugin_postgres::GeyserPluginPostgres;
+use solana_geyser_plugin_postgres::postgres_client::SimplePostgresClient;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+
+static OWNER: Pubkey = pubkey!("EmdsWm9dJ1d6BgQzHDcMJkDvB5SVvpfrAtpiGMVW1gxx");
+
+#[test]
+fn test_table_prefix() {
+    let root = rand::random::<u32>() as u64;
+    let (abandoned, rooted) = (root + 1, root + 2);
+    let (forked, kept) = (Keypair::new().pubkey(), Keypair::new().pubkey());
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_table_prefix.json"));
+    let mut geyser_plugin = GeyserPluginPostgres::default();
+    geyser_plugin.on_load(database.config_path()).unwrap();
+
+    geyser_plugin.update_slot_status(root, None, SlotStatus::Rooted).unwrap();
+    geyser_plugin.update_slot_status(abandoned, Some(root), SlotStatus::Processed).unwrap();
+    geyser_plugin.update_slot_status(rooted, Some(root), SlotStatus::Processed).unwrap();
+    for (address, slot) in [(forked, abandoned), (kept, rooted)] {
+        geyser_plugin
+            .update_account(
+                ReplicaAccountInfoVersions::V0_0_2(&ReplicaAccountInfoV2 {
+                    pubkey: address.as_ref(),
+                    lamports: 1,
+                    owner: OWNER.as_ref(),
+                    executable: false,
+                    rent_epoch: 0,
+                    data: &[1, 2, 3],
+                    write_version: 0,
+                    txn_signature: None,
+                }),
+                slot,
+                false,
+            )
+            .unwrap();
+    }
+    geyser_plugin.wait_for_empty_queue();
+    geyser_plugin.update_slot_status(rooted, Some(root), SlotStatus::Rooted).unwrap();
+    geyser_plugin.wait_for_empty_queue();
+
+    // the tables are written in the `devnet` schema with their prefix, whatever the search path
+    let mut client = SimplePostgresClient::connect_to_db(&geyser_plugin.config.clone().expect("No plugin config found")).expect("Failed to connect");
+    client.batch_execute("SET search_path TO public;").unwrap();
+    let status = |client: &mut postgres::Client, slot: u64| -> String { client.query_one("SELECT status FROM devnet.devnet_slot WHERE slot = $1", &[&(slot as i64)]).unwrap().get(0) };
+    assert_eq!(status(&mut client, root), "rooted");
+    assert_eq!(status(&mut client, abandoned), "abandoned");
+    assert_eq!(status(&mut client, rooted), "rooted");
+    let accounts = |client: &mut postgres::Client, address: &Pubkey| client.query("SELECT slot FROM devnet.devnet_account WHERE pubkey = $1", &[&address.as_ref()]).unwrap().len();
+    assert_eq!(accounts(&mut client, &forked), 0, "The account written on the abandoned fork should be deleted");
+    assert_eq!(accounts(&mut client, &kept), 1);
+    let unprefixed: Option<String> = client.query_one("SELECT to_regclass('devnet.slot')::TEXT", &[]).unwrap().get(0);
+    assert_eq!(unprefixed, None);
+
+    client.close().expect("Error disconnecting");
+    geyser_plugin.on_unload();
+}
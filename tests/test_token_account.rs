@@ -1,6 +1,9 @@
+mod common;
+
 use std::thread::sleep;
 use std::time::Duration;
 
+use common::TestDatabase;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
@@ -18,8 +21,9 @@ static MINT: Pubkey = pubkey!("DUSTawucrTsGU8hcqRdHDCbuYhCPADMLM2VcCb8VnFnQ");
 #[test]
 fn test_token_account() {
     let address: Pubkey = Keypair::new().pubkey();
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json"));
     let mut geyser_plugin = GeyserPluginPostgres::default();
-    geyser_plugin.on_load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json")).unwrap();
+    geyser_plugin.on_load(database.config_path()).unwrap();
 
     geyser_plugin
         .update_account(
@@ -61,6 +65,11 @@ fn test_token_account() {
     assert_eq!(owner, TOKEN_ACCOUNT_OWNER.to_string(), "Incorrect pubkey");
     let mint: String = first_row.get("mint");
     assert_eq!(mint, MINT.to_string(), "Incorrect pubkey");
+    let rows = client
+        .query("SELECT amount::TEXT AS amount from spl_token_account where pubkey=$1", &[&address.to_string()])
+        .expect("Error selecting accounts");
+    let amount: String = rows.first().expect("No results found").get("amount");
+    assert_eq!(amount, "26186805005000", "Incorrect amount");
 
     client.close().expect("Error disconnecting");
     geyser_plugin.on_unload();
@@ -0,0 +1,90 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::TestDatabase;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
+use solana_geyser_plugin_postgres::geyser_plugin_postgres::GeyserPluginPostgres;
+use solana_geyser_plugin_postgres::postgres_client::SimplePostgresClient;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+
+static OWNER: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+fn tlv_entry(extension_type: u16, value: &[u8]) -> Vec<u8> {
+    let mut entry = extension_type.to_le_bytes().to_vec();
+    entry.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    entry.extend_from_slice(value);
+    entry
+}
+
+#[test]
+fn test_token_extensions() {
+    let (address, delegate, authority) = (Keypair::new().pubkey(), Keypair::new().pubkey(), Keypair::new().pubkey());
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_token_extensions.json"));
+    let mut geyser_plugin = GeyserPluginPostgres::default();
+    geyser_plugin.on_load(database.config_path()).unwrap();
+
+    // a mint padded to the length of a token account, followed by its extensions
+    let mut data = vec![0; 165];
+    data.push(1);
+    let mut transfer_fee_config = authority.to_bytes().to_vec();
+    transfer_fee_config.extend_from_slice(&[0; 32]);
+    transfer_fee_config.extend_from_slice(&u64::MAX.to_le_bytes());
+    for (epoch, maximum_fee, basis_points) in [(10u64, 5_000u64, 25u16), (20, 10_000, 50)] {
+        transfer_fee_config.extend_from_slice(&epoch.to_le_bytes());
+        transfer_fee_config.extend_from_slice(&maximum_fee.to_le_bytes());
+        transfer_fee_config.extend_from_slice(&basis_points.to_le_bytes());
+    }
+    data.extend(tlv_entry(1, &transfer_fee_config));
+    data.extend(tlv_entry(12, delegate.as_ref()));
+    geyser_plugin
+        .update_account(
+            ReplicaAccountInfoVersions::V0_0_2(&ReplicaAccountInfoV2 {
+                pubkey: address.as_ref(),
+                lamports: 2039280,
+                owner: OWNER.as_ref(),
+                executable: false,
+                rent_epoch: 0,
+                data: &data,
+                write_version: 0,
+                txn_signature: None,
+            }),
+            0,
+            false,
+        )
+        .unwrap();
+
+    sleep(Duration::from_secs(1));
+
+    let mut client = SimplePostgresClient::connect_to_db(&geyser_plugin.config.clone().expect("No plugin config found")).expect("Failed to connect");
+    let rows = client
+        .query(
+            "SELECT account_type, extensions, transfer_fee_config_authority, withdraw_withheld_authority, withheld_amount::TEXT AS withheld_amount, \
+            newer_transfer_fee_basis_points, permanent_delegate FROM spl_token_extension WHERE pubkey=$1",
+            &[&address.to_string()],
+        )
+        .expect("Error selecting extensions");
+    assert_eq!(rows.len(), 1, "Incorrect number of rows found");
+    let row = rows.first().expect("No results found");
+    assert_eq!(row.get::<_, String>("account_type"), "mint");
+    assert_eq!(row.get::<_, Vec<String>>("extensions"), ["transfer_fee_config", "permanent_delegate"]);
+    assert_eq!(row.get::<_, Option<String>>("transfer_fee_config_authority"), Some(authority.to_string()));
+    assert_eq!(row.get::<_, Option<String>>("withdraw_withheld_authority"), None);
+    assert_eq!(row.get::<_, String>("withheld_amount"), u64::MAX.to_string());
+    assert_eq!(row.get::<_, i32>("newer_transfer_fee_basis_points"), 50);
+    assert_eq!(row.get::<_, String>("permanent_delegate"), delegate.to_string());
+    // a mint has no token account row
+    let rows = client
+        .query("SELECT * FROM spl_token_account WHERE pubkey=$1", &[&address.to_string()])
+        .expect("Error selecting accounts");
+    assert!(rows.is_empty());
+
+    client.close().expect("Error disconnecting");
+    geyser_plugin.on_unload();
+}
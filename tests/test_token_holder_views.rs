@@ -0,0 +1,77 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::TestDatabase;
+use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
+use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
+use solana_geyser_plugin_interface::geyser_plugin_interface::SlotStatus;
+use solana_geyser_plugin_postgres::geyser_plugin_postgres::GeyserPluginPostgres;
+use solana_geyser_plugin_postgres::postgres_client::SimplePostgresClient;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+static OWNER: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+fn token_account_data(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Vec<u8> {
+    let mut data = vec![0; 165];
+    data[0..32].copy_from_slice(mint.as_ref());
+    data[32..64].copy_from_slice(owner.as_ref());
+    data[64..72].copy_from_slice(&amount.to_le_bytes());
+    data
+}
+
+#[test]
+fn test_token_holder_views() {
+    let mint = Pubkey::new_unique();
+    let holders = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_token_holder_views.json"));
+    let mut geyser_plugin = GeyserPluginPostgres::default();
+    geyser_plugin.on_load(database.config_path()).unwrap();
+
+    // the last holder's account is empty and isn't counted
+    for (i, (holder, amount)) in holders.iter().zip([100, 50, 0]).enumerate() {
+        let address = Pubkey::new_unique();
+        let data = token_account_data(&mint, holder, amount);
+        geyser_plugin
+            .update_account(
+                ReplicaAccountInfoVersions::V0_0_2(&ReplicaAccountInfoV2 {
+                    pubkey: address.as_ref(),
+                    lamports: 2039280,
+                    owner: OWNER.as_ref(),
+                    executable: false,
+                    rent_epoch: 0,
+                    data: &data,
+                    write_version: i as u64,
+                    txn_signature: None,
+                }),
+                1,
+                false,
+            )
+            .unwrap();
+    }
+    geyser_plugin.wait_for_empty_queue();
+    sleep(Duration::from_secs(1));
+    let slot = rand::random::<u32>() as u64;
+    geyser_plugin.update_slot_status(slot, None, SlotStatus::Rooted).unwrap();
+    // unloading waits for the refresh the rooted slot asked for
+    let config = geyser_plugin.config.clone().expect("No plugin config found");
+    geyser_plugin.on_unload();
+
+    let mut client = SimplePostgresClient::connect_to_db(&config).expect("Failed to connect");
+    let rows = client
+        .query("SELECT holders, amount::TEXT AS amount FROM token_holders_per_mint WHERE mint=$1", &[&mint.to_string()])
+        .expect("Error selecting holders");
+    let row = rows.first().expect("No holders found");
+    assert_eq!(row.get::<_, i64>("holders"), 2);
+    assert_eq!(row.get::<_, String>("amount"), "150");
+    let rows = client
+        .query("SELECT owner FROM token_balance_per_owner WHERE mint=$1 ORDER BY amount DESC", &[&mint.to_string()])
+        .expect("Error selecting balances");
+    let owners = rows.iter().map(|row| row.get::<_, String>("owner")).collect::<Vec<String>>();
+    assert_eq!(owners, vec![holders[0].to_string(), holders[1].to_string()]);
+
+    client.close().expect("Error disconnecting");
+}
@@ -1,3 +1,6 @@
+mod common;
+
+use common::TestDatabase;
 use solana_geyser_plugin_interface::geyser_plugin_interface::GeyserPlugin;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoV2;
 use solana_geyser_plugin_interface::geyser_plugin_interface::ReplicaAccountInfoVersions;
@@ -5,17 +8,32 @@ use solana_geyser_plugin_postgres::geyser_plugin_postgres::GeyserPluginPostgres;
 use solana_geyser_plugin_postgres::postgres_client::SimplePostgresClient;
 use solana_sdk::pubkey;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use solana_sdk::system_program;
 use std::thread::sleep;
 use std::time::Duration;
 
 static OWNER: Pubkey = pubkey!("mgr99QFMYByTqGPWmNqunV7vBLmWWXdSrHUfV8Jf3JM");
 static MINT: Pubkey = pubkey!("4utZa12Q3j9J76JaNPncXfVyUxRA3uAjBkFS6Rp9z4Ek");
 static TOKEN_MANAGER_ADDRESS: Pubkey = pubkey!("DxH9YVD9yafZ5vo8goKgxuMPR6zQtCC7uw3nnozArMcP");
+static TOKEN_MANAGER_DATA: &[u8] = &[
+    0xb9, 0x61, 0x7c, 0xe7, 0x46, 0x4b, 0xe4, 0x2f, 0x00, 0xfb, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x6b, 0xd3, 0x95, 0x2b, 0x7f, 0x86, 0x39, 0x67, 0x93, 0x89, 0x1a, 0x91, 0x4c,
+    0x34, 0x30, 0x05, 0xa4, 0xef, 0x9d, 0x3b, 0xad, 0xe4, 0x11, 0x8d, 0xdc, 0x97, 0xb4, 0xa4, 0x58, 0x60, 0x05, 0xcf, 0x3a, 0x1f, 0x90, 0x4c, 0x3d, 0x7a, 0x4d, 0xf3, 0x39, 0xf7, 0x65, 0x73, 0xcc,
+    0x2a, 0x95, 0xaf, 0x95, 0xc4, 0xbb, 0x53, 0x6a, 0x82, 0x05, 0xad, 0xfb, 0x6c, 0x9c, 0x55, 0xcc, 0x4b, 0xc9, 0xd1, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x01, 0x95, 0xe8, 0x1a,
+    0x64, 0x00, 0x00, 0x00, 0x00, 0x04, 0xaa, 0xa5, 0xbb, 0x22, 0xe0, 0x66, 0xc7, 0x5c, 0x3e, 0x16, 0xe6, 0x48, 0xae, 0x7b, 0xae, 0x75, 0xcd, 0xe5, 0x02, 0xfe, 0xb3, 0x8c, 0xcd, 0xb7, 0x4d, 0x77,
+    0xd4, 0x82, 0x38, 0x27, 0x4f, 0x1a, 0x00, 0x00, 0x01, 0xc0, 0x75, 0x72, 0x06, 0xbc, 0xe0, 0x82, 0x9a, 0xb6, 0x13, 0xff, 0xa5, 0x1d, 0x3c, 0x6b, 0x0c, 0x99, 0x9c, 0x6a, 0x35, 0xfd, 0xab, 0x35,
+    0x47, 0xb0, 0x0c, 0x39, 0xa8, 0x6f, 0x9e, 0x47, 0xac, 0x01, 0x00, 0x00, 0x00, 0x34, 0x42, 0x31, 0x34, 0xc8, 0xbb, 0x12, 0x54, 0xf1, 0xbf, 0x2c, 0xb9, 0xb5, 0x8e, 0x9a, 0x8e, 0x53, 0x55, 0xb7,
+    0xca, 0x66, 0x47, 0x08, 0x12, 0x91, 0xf5, 0xad, 0xa0, 0x88, 0x10, 0x13, 0xe5, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
 
 #[test]
 fn test_token_manager_account() {
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json"));
     let mut geyser_plugin = GeyserPluginPostgres::default();
-    geyser_plugin.on_load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config.json")).unwrap();
+    geyser_plugin.on_load(database.config_path()).unwrap();
 
     geyser_plugin
         .update_account(
@@ -25,18 +43,7 @@ fn test_token_manager_account() {
                 owner: OWNER.as_ref(),
                 executable: false,
                 rent_epoch: 0,
-                data: &[
-                    0xb9, 0x61, 0x7c, 0xe7, 0x46, 0x4b, 0xe4, 0x2f, 0x00, 0xfb, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x6b, 0xd3, 0x95, 0x2b, 0x7f, 0x86, 0x39, 0x67, 0x93, 0x89, 0x1a,
-                    0x91, 0x4c, 0x34, 0x30, 0x05, 0xa4, 0xef, 0x9d, 0x3b, 0xad, 0xe4, 0x11, 0x8d, 0xdc, 0x97, 0xb4, 0xa4, 0x58, 0x60, 0x05, 0xcf, 0x3a, 0x1f, 0x90, 0x4c, 0x3d, 0x7a, 0x4d, 0xf3, 0x39,
-                    0xf7, 0x65, 0x73, 0xcc, 0x2a, 0x95, 0xaf, 0x95, 0xc4, 0xbb, 0x53, 0x6a, 0x82, 0x05, 0xad, 0xfb, 0x6c, 0x9c, 0x55, 0xcc, 0x4b, 0xc9, 0xd1, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                    0x00, 0x03, 0x01, 0x95, 0xe8, 0x1a, 0x64, 0x00, 0x00, 0x00, 0x00, 0x04, 0xaa, 0xa5, 0xbb, 0x22, 0xe0, 0x66, 0xc7, 0x5c, 0x3e, 0x16, 0xe6, 0x48, 0xae, 0x7b, 0xae, 0x75, 0xcd, 0xe5,
-                    0x02, 0xfe, 0xb3, 0x8c, 0xcd, 0xb7, 0x4d, 0x77, 0xd4, 0x82, 0x38, 0x27, 0x4f, 0x1a, 0x00, 0x00, 0x01, 0xc0, 0x75, 0x72, 0x06, 0xbc, 0xe0, 0x82, 0x9a, 0xb6, 0x13, 0xff, 0xa5, 0x1d,
-                    0x3c, 0x6b, 0x0c, 0x99, 0x9c, 0x6a, 0x35, 0xfd, 0xab, 0x35, 0x47, 0xb0, 0x0c, 0x39, 0xa8, 0x6f, 0x9e, 0x47, 0xac, 0x01, 0x00, 0x00, 0x00, 0x34, 0x42, 0x31, 0x34, 0xc8, 0xbb, 0x12,
-                    0x54, 0xf1, 0xbf, 0x2c, 0xb9, 0xb5, 0x8e, 0x9a, 0x8e, 0x53, 0x55, 0xb7, 0xca, 0x66, 0x47, 0x08, 0x12, 0x91, 0xf5, 0xad, 0xa0, 0x88, 0x10, 0x13, 0xe5, 0x00, 0x00, 0x00, 0x00, 0x00,
-                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                    0x00, 0x00, 0x00,
-                ],
+                data: TOKEN_MANAGER_DATA,
                 write_version: 0,
                 txn_signature: None,
             }),
@@ -99,3 +106,49 @@ fn test_token_manager_account() {
     client.close().expect("Error disconnecting");
     geyser_plugin.on_unload();
 }
+
+#[test]
+fn test_token_manager_closed_in_batch() {
+    let slot = rand::random::<u32>() as u64;
+    let (closed, kept) = (Keypair::new().pubkey(), Keypair::new().pubkey());
+    let database = TestDatabase::start(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/test_config_close_detection_batched.json"));
+    let mut geyser_plugin = GeyserPluginPostgres::default();
+    geyser_plugin.on_load(database.config_path()).unwrap();
+
+    // both token managers are written, then the first one is closed and reassigned to the system program a slot later,
+    // all in the same flush
+    let system_program_id = system_program::id();
+    let updates = [
+        (closed, 2790960, &OWNER, TOKEN_MANAGER_DATA, slot),
+        (kept, 2790960, &OWNER, TOKEN_MANAGER_DATA, slot),
+        (closed, 0, &system_program_id, &[][..], slot + 1),
+    ];
+    for (address, lamports, owner, data, slot) in updates {
+        geyser_plugin
+            .update_account(
+                ReplicaAccountInfoVersions::V0_0_2(&ReplicaAccountInfoV2 {
+                    pubkey: address.as_ref(),
+                    lamports,
+                    owner: owner.as_ref(),
+                    executable: false,
+                    rent_epoch: 0,
+                    data,
+                    write_version: 0,
+                    txn_signature: None,
+                }),
+                slot,
+                false,
+            )
+            .unwrap();
+    }
+    geyser_plugin.wait_for_empty_queue();
+    sleep(Duration::from_secs(1));
+
+    let mut client = SimplePostgresClient::connect_to_db(&geyser_plugin.config.clone().expect("No plugin config found")).expect("Failed to connect");
+    let token_managers = |client: &mut postgres::Client, address: &Pubkey| client.query("SELECT slot FROM token_manager WHERE id = $1", &[&address.to_string()]).unwrap().len();
+    assert_eq!(token_managers(&mut client, &closed), 0, "The closed token manager should be deleted");
+    assert_eq!(token_managers(&mut client, &kept), 1);
+
+    client.close().expect("Error disconnecting");
+    geyser_plugin.on_unload();
+}